@@ -0,0 +1,105 @@
+use aitrading::data::{calculate_intraday_series, calculate_longer_term_data};
+use aitrading::indicators;
+use aitrading::types::{IntradayData, Kline};
+use criterion::{BenchmarkId, Criterion, black_box, criterion_group, criterion_main};
+
+fn synthetic_klines(n: usize) -> Vec<Kline> {
+    (0..n)
+        .map(|i| {
+            let base = 100.0 + (i as f64 * 0.37).sin() * 5.0 + i as f64 * 0.01;
+            Kline {
+                open_time: i as i64 * 180_000,
+                open: base,
+                high: base + 0.8,
+                low: base - 0.7,
+                close: base + (i as f64 * 1.3).cos() * 0.5,
+                volume: 1_000.0 + (i % 17) as f64 * 10.0,
+                close_time: i as i64 * 180_000 + 179_999,
+                quote_volume: 0.0,
+                trades: 0,
+                taker_buy_base_volume: 0.0,
+                taker_buy_quote_volume: 0.0,
+            }
+        })
+        .collect()
+}
+
+/// The previous implementation: every series point recomputes each indicator
+/// from scratch over a growing prefix of the candles.
+fn naive_intraday_series(klines: &[Kline], points: usize) -> IntradayData {
+    let mut data = IntradayData::default();
+    let start = klines.len().saturating_sub(points);
+    for i in start..klines.len() {
+        let slice = &klines[..=i];
+        data.mid_prices.push(slice[i].close);
+        if slice.len() >= 20 {
+            data.ema20_values.push(indicators::ema(slice, 20));
+        }
+        if slice.len() >= 26 {
+            data.macd_values.push(indicators::macd(slice));
+        }
+        if slice.len() > 7 {
+            data.rsi7_values.push(indicators::rsi(slice, 7));
+        }
+        if slice.len() > 14 {
+            data.rsi14_values.push(indicators::rsi(slice, 14));
+        }
+    }
+    data
+}
+
+fn bench_intraday(c: &mut Criterion) {
+    let mut group = c.benchmark_group("intraday_series");
+    for n in [50usize, 500, 2_000] {
+        let klines = synthetic_klines(n);
+        group.bench_with_input(BenchmarkId::new("incremental", n), &klines, |b, k| {
+            b.iter(|| calculate_intraday_series(black_box(k)))
+        });
+        group.bench_with_input(BenchmarkId::new("naive", n), &klines, |b, k| {
+            b.iter(|| naive_intraday_series(black_box(k), 10))
+        });
+    }
+    group.finish();
+}
+
+fn bench_full_history(c: &mut Criterion) {
+    // Series over every candle: the case a websocket-fed buffer has to keep current.
+    let mut group = c.benchmark_group("full_series");
+    for n in [200usize, 1_000] {
+        let klines = synthetic_klines(n);
+        group.bench_with_input(BenchmarkId::new("incremental", n), &klines, |b, k| {
+            b.iter(|| {
+                let mut set = indicators::IndicatorSet::new();
+                let mut data = IntradayData::default();
+                for kline in black_box(k) {
+                    set.update(kline);
+                    data.mid_prices.push(kline.close);
+                    data.ema20_values.extend(set.ema20.value());
+                    data.macd_values.extend(set.macd.value());
+                    data.rsi7_values.extend(set.rsi7.value());
+                    data.rsi14_values.extend(set.rsi14.value());
+                }
+                data
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("naive", n), &klines, |b, k| {
+            b.iter(|| naive_intraday_series(black_box(k), k.len()))
+        });
+    }
+    group.finish();
+}
+
+fn bench_longer_term(c: &mut Criterion) {
+    let klines = synthetic_klines(60);
+    c.bench_function("longer_term_data/60", |b| {
+        b.iter(|| calculate_longer_term_data(black_box(&klines)))
+    });
+}
+
+criterion_group!(
+    benches,
+    bench_intraday,
+    bench_full_history,
+    bench_longer_term
+);
+criterion_main!(benches);
@@ -0,0 +1,291 @@
+//! Hit rate of the AI's directional calls.
+//!
+//! Every directional decision a model makes is recorded with the price it saw,
+//! whether or not it was executed: risk filters, approvals and failed orders do
+//! not change what the model called. Once the horizon has passed the call is
+//! scored against the price at that point. An open long or a close short is a
+//! bullish call, an open short or a close long a bearish one; the call is
+//! correct when the price moved its way. Hold and wait decisions are not calls.
+//!
+//! The hit rates per model and per symbol are fed back to the model in its
+//! prompt and shown next to the tournament standings.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use humantime_serde::re::humantime::format_duration;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::data;
+use crate::database::{Database, DecisionCall};
+use crate::decision::{Action, Decision};
+use crate::types::Data;
+
+#[derive(Error, Debug)]
+pub enum AccuracyError {
+    #[error("Database error: {0}")]
+    Database(#[from] anyhow::Error),
+}
+
+pub type Result<T> = std::result::Result<T, AccuracyError>;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[serde(default)]
+pub struct AccuracyParams {
+    /// How long after a call it is scored.
+    #[serde(with = "humantime_serde")]
+    pub horizon: Duration,
+    /// Calls made within this window count towards the hit rates.
+    #[serde(with = "humantime_serde")]
+    pub window: Duration,
+    /// Calls older than this are deleted.
+    #[serde(with = "humantime_serde")]
+    pub retention: Duration,
+}
+
+impl Default for AccuracyParams {
+    fn default() -> Self {
+        Self {
+            horizon: Duration::from_secs(4 * 3600),
+            window: Duration::from_secs(7 * 86400),
+            retention: Duration::from_secs(30 * 86400),
+        }
+    }
+}
+
+impl AccuracyParams {
+    /// Start of the window ending now.
+    pub fn since(&self) -> DateTime<Utc> {
+        Utc::now() - chrono::Duration::from_std(self.window).unwrap_or_default()
+    }
+}
+
+/// +1 for a bullish call, -1 for a bearish one, `None` for no call.
+pub fn direction(action: Action) -> Option<f64> {
+    match action {
+        Action::OpenLong | Action::CloseShort => Some(1.0),
+        Action::OpenShort | Action::CloseLong => Some(-1.0),
+        Action::Hold | Action::Wait => None,
+    }
+}
+
+/// Records the directional calls among `decisions` at the prices in
+/// `market_data`. Returns how many were recorded.
+pub async fn record(
+    db: &Database,
+    user_id: &str,
+    trader_id: &str,
+    model: &str,
+    decisions: &[Decision],
+    market_data: &HashMap<String, Data>,
+) -> Result<usize> {
+    let decided_at = Utc::now();
+    let mut recorded = 0;
+    for d in decisions.iter().flat_map(Decision::legs) {
+        if direction(d.action).is_none() {
+            continue;
+        }
+        let symbol = data::normalize(&d.symbol);
+        let Some(entry_price) = market_data
+            .get(&symbol)
+            .map(|m| m.current_price)
+            .filter(|p| *p > 0.0)
+        else {
+            continue;
+        };
+        db.save_decision_call(&DecisionCall {
+            id: Uuid::new_v4().to_string(),
+            user_id: user_id.to_string(),
+            trader_id: trader_id.to_string(),
+            model: model.to_string(),
+            symbol,
+            action: d.action.as_str().to_string(),
+            entry_price,
+            decided_at,
+            ..Default::default()
+        })
+        .await?;
+        recorded += 1;
+    }
+    Ok(recorded)
+}
+
+/// Scores a call against `exit_price`.
+pub async fn settle(db: &Database, call: &DecisionCall, exit_price: f64) -> Result<()> {
+    let sign = match call.action.as_str() {
+        "open_long" | "close_short" => 1.0,
+        _ => -1.0,
+    };
+    let return_pct = if call.entry_price > 0.0 {
+        sign * (exit_price - call.entry_price) / call.entry_price * 100.0
+    } else {
+        0.0
+    };
+    db.settle_decision_call(
+        &call.id,
+        exit_price,
+        return_pct,
+        return_pct > 0.0,
+        Utc::now(),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Scores every call older than the horizon at current prices and prunes
+/// expired ones. Returns how many were scored.
+pub async fn settle_due(db: &Database, params: &AccuracyParams) -> Result<usize> {
+    let horizon = chrono::Duration::from_std(params.horizon).unwrap_or_default();
+    let due = db
+        .get_unsettled_decision_calls(Utc::now() - horizon)
+        .await?;
+
+    let mut prices: HashMap<String, Option<f64>> = HashMap::new();
+    let mut settled = 0;
+    for call in &due {
+        if !prices.contains_key(&call.symbol) {
+            let price = match data::get(&call.symbol).await {
+                Ok(d) if d.current_price > 0.0 => Some(d.current_price),
+                Ok(_) => None,
+                Err(e) => {
+                    tracing::warn!("⚠️ 方向判断评分时获取 {} 价格失败: {}", call.symbol, e);
+                    None
+                }
+            };
+            prices.insert(call.symbol.clone(), price);
+        }
+        if let Some(price) = prices[&call.symbol] {
+            settle(db, call, price).await?;
+            settled += 1;
+        }
+    }
+
+    let retention = chrono::Duration::from_std(params.retention).unwrap_or_default();
+    let pruned = db.prune_decision_calls(Utc::now() - retention).await?;
+    if settled > 0 || pruned > 0 {
+        tracing::info!("🎯 方向判断已评分 {} 个，清理过期 {} 个", settled, pruned);
+    }
+    Ok(settled)
+}
+
+/// Scored calls of one model or one symbol.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct HitRate {
+    pub key: String,
+    pub calls: u32,
+    pub hits: u32,
+    /// Sum of the calls' moves in their direction, in percent.
+    pub total_return_pct: f64,
+}
+
+impl HitRate {
+    pub fn hit_rate(&self) -> f64 {
+        if self.calls == 0 {
+            0.0
+        } else {
+            self.hits as f64 / self.calls as f64 * 100.0
+        }
+    }
+
+    pub fn avg_return_pct(&self) -> f64 {
+        if self.calls == 0 {
+            0.0
+        } else {
+            self.total_return_pct / self.calls as f64
+        }
+    }
+
+    fn add(&mut self, call: &DecisionCall) {
+        self.calls += 1;
+        if call.correct == Some(true) {
+            self.hits += 1;
+        }
+        self.total_return_pct += call.return_pct.unwrap_or_default();
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AccuracyStats {
+    pub overall: HitRate,
+    /// Sorted by key.
+    pub by_model: Vec<HitRate>,
+    /// Sorted by key.
+    pub by_symbol: Vec<HitRate>,
+}
+
+impl AccuracyStats {
+    /// Aggregates scored calls; unscored ones are skipped.
+    pub fn from_calls<'a>(calls: impl IntoIterator<Item = &'a DecisionCall>) -> Self {
+        let mut overall = HitRate::default();
+        let mut by_model: BTreeMap<&str, HitRate> = BTreeMap::new();
+        let mut by_symbol: BTreeMap<&str, HitRate> = BTreeMap::new();
+        for call in calls.into_iter().filter(|c| c.correct.is_some()) {
+            overall.add(call);
+            by_model.entry(&call.model).or_default().add(call);
+            by_symbol.entry(&call.symbol).or_default().add(call);
+        }
+        let keyed = |map: BTreeMap<&str, HitRate>| {
+            map.into_iter()
+                .map(|(key, rate)| HitRate {
+                    key: key.to_string(),
+                    ..rate
+                })
+                .collect()
+        };
+        Self {
+            overall,
+            by_model: keyed(by_model),
+            by_symbol: keyed(by_symbol),
+        }
+    }
+
+    pub fn model(&self, model: &str) -> Option<&HitRate> {
+        self.by_model.iter().find(|r| r.key == model)
+    }
+}
+
+/// Hit rates of the user's calls made since `since`, optionally limited to
+/// one model.
+pub async fn stats(
+    db: &Database,
+    user_id: &str,
+    model: Option<&str>,
+    since: DateTime<Utc>,
+) -> Result<AccuracyStats> {
+    let calls = db.get_settled_decision_calls(user_id, since).await?;
+    Ok(AccuracyStats::from_calls(
+        calls.iter().filter(|c| model.is_none_or(|m| c.model == m)),
+    ))
+}
+
+/// A prompt note with a model's own track record, `None` before any of its
+/// calls were scored.
+pub fn prompt_annotation(stats: &AccuracyStats, params: &AccuracyParams) -> Option<String> {
+    if stats.overall.calls == 0 {
+        return None;
+    }
+    let mut note = format!(
+        "Your directional calls over the last {}, scored {} later: {:.0}% correct ({}/{}), avg move {:+.2}%",
+        format_duration(params.window),
+        format_duration(params.horizon),
+        stats.overall.hit_rate(),
+        stats.overall.hits,
+        stats.overall.calls,
+        stats.overall.avg_return_pct()
+    );
+    for rate in &stats.by_symbol {
+        let _ = write!(
+            note,
+            "\n  {}: {:.0}% ({}/{})",
+            rate.key,
+            rate.hit_rate(),
+            rate.hits,
+            rate.calls
+        );
+    }
+    Some(note)
+}
@@ -0,0 +1,743 @@
+//! AI model clients.
+//!
+//! Every model is reached through [`AiProvider`]: a system prompt and a user
+//! prompt in, the model's raw text out. DeepSeek, Qwen and custom models all
+//! speak the OpenAI chat completions protocol, so one client covers them with
+//! different endpoints and default models. Anthropic's Claude and Google's
+//! Gemini have clients of their own for their native APIs, which take the
+//! system prompt as a separate field and need a larger output budget.
+
+use std::future::Future;
+use std::pin::Pin;
+
+use serde::Deserialize;
+use serde_json::json;
+use thiserror::Error;
+
+use crate::api_client::{EndpointClass, client_for, timeout_for};
+use crate::database::AIModelConfig;
+
+pub const DEEPSEEK_URL: &str = "https://api.deepseek.com/v1";
+pub const DEEPSEEK_MODEL: &str = "deepseek-chat";
+pub const QWEN_URL: &str = "https://dashscope.aliyuncs.com/compatible-mode/v1";
+pub const QWEN_MODEL: &str = "qwen3-max";
+pub const ANTHROPIC_URL: &str = "https://api.anthropic.com/v1";
+pub const ANTHROPIC_MODEL: &str = "claude-sonnet-4-5";
+pub const GEMINI_URL: &str = "https://generativelanguage.googleapis.com/v1beta";
+pub const GEMINI_MODEL: &str = "gemini-2.5-pro";
+
+const DEFAULT_TEMPERATURE: f64 = 0.5;
+const DEFAULT_MAX_TOKENS: u32 = 4000;
+// The Messages API requires a limit; Claude's chain of thought often runs past
+// the default.
+const ANTHROPIC_MAX_TOKENS: u32 = 8192;
+// Gemini 2.5 counts its hidden reasoning against the output limit.
+const GEMINI_MAX_TOKENS: u32 = 16384;
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+
+#[derive(Error, Debug)]
+pub enum AiError {
+    #[error("AI model is not configured: {0}")]
+    Config(String),
+    #[error("HTTP error: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("AI API error {status}: {body}")]
+    Api { status: u16, body: String },
+    #[error("Unexpected response: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("AI returned an empty response")]
+    EmptyResponse,
+}
+
+pub type Result<T> = std::result::Result<T, AiError>;
+
+pub type AiFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T>> + Send + 'a>>;
+
+/// A chat model that turns prompts into a raw response.
+pub trait AiProvider: Send {
+    /// Provider and model, e.g. `deepseek/deepseek-chat`.
+    fn name(&self) -> String;
+
+    fn chat_completion<'a>(
+        &'a mut self,
+        system_prompt: &'a str,
+        user_prompt: &'a str,
+    ) -> AiFuture<'a, String>;
+
+    /// Like [`chat_completion`](Self::chat_completion), but hands each piece of
+    /// the response to `on_delta` as it arrives. Providers without streaming
+    /// deliver the whole response as one piece.
+    fn chat_completion_stream<'a>(
+        &'a mut self,
+        system_prompt: &'a str,
+        user_prompt: &'a str,
+        on_delta: &'a mut (dyn FnMut(&str) + Send),
+    ) -> AiFuture<'a, String> {
+        Box::pin(async move {
+            let text = self.chat_completion(system_prompt, user_prompt).await?;
+            on_delta(&text);
+            Ok(text)
+        })
+    }
+}
+
+/// Client for any OpenAI-compatible chat completions endpoint.
+#[derive(Clone)]
+pub struct OpenAiCompatClient {
+    provider: String,
+    base_url: String,
+    api_key: String,
+    model: String,
+    temperature: f64,
+    max_tokens: u32,
+}
+
+impl std::fmt::Debug for OpenAiCompatClient {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OpenAiCompatClient")
+            .field("provider", &self.provider)
+            .field("base_url", &self.base_url)
+            .field("model", &self.model)
+            .finish_non_exhaustive()
+    }
+}
+
+impl OpenAiCompatClient {
+    /// `base_url` is the API root (`.../v1`) or the full `/chat/completions` URL.
+    pub fn new(provider: &str, base_url: &str, api_key: &str, model: &str) -> Result<Self> {
+        check_config(provider, base_url, api_key, model)?;
+        Ok(Self {
+            provider: provider.to_string(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            api_key: api_key.to_string(),
+            model: model.to_string(),
+            temperature: DEFAULT_TEMPERATURE,
+            max_tokens: DEFAULT_MAX_TOKENS,
+        })
+    }
+
+    pub fn deepseek(api_key: &str) -> Result<Self> {
+        Self::new("deepseek", DEEPSEEK_URL, api_key, DEEPSEEK_MODEL)
+    }
+
+    pub fn qwen(api_key: &str) -> Result<Self> {
+        Self::new("qwen", QWEN_URL, api_key, QWEN_MODEL)
+    }
+
+    pub fn custom(api_url: &str, api_key: &str, model: &str) -> Result<Self> {
+        Self::new("custom", api_url, api_key, model)
+    }
+
+    pub fn with_model(mut self, model: &str) -> Self {
+        self.model = model.to_string();
+        self
+    }
+
+    pub fn with_temperature(mut self, temperature: f64) -> Self {
+        self.temperature = temperature;
+        self
+    }
+
+    pub fn with_max_tokens(mut self, max_tokens: u32) -> Self {
+        self.max_tokens = max_tokens;
+        self
+    }
+
+    pub fn model(&self) -> &str {
+        &self.model
+    }
+
+    fn url(&self) -> String {
+        if self.base_url.ends_with("/chat/completions") {
+            self.base_url.clone()
+        } else {
+            format!("{}/chat/completions", self.base_url)
+        }
+    }
+
+    async fn send(
+        &self,
+        system_prompt: &str,
+        user_prompt: &str,
+        stream: bool,
+    ) -> Result<reqwest::Response> {
+        let body = json!({
+            "model": self.model,
+            "messages": [
+                { "role": "system", "content": system_prompt },
+                { "role": "user", "content": user_prompt },
+            ],
+            "temperature": self.temperature,
+            "max_tokens": self.max_tokens,
+            "stream": stream,
+        });
+        let resp = client_for(EndpointClass::Ai)
+            .post(self.url())
+            .bearer_auth(&self.api_key)
+            .timeout(timeout_for(EndpointClass::Ai))
+            .json(&body)
+            .send()
+            .await?;
+        check_status(resp).await
+    }
+
+    #[tracing::instrument(
+        name = "ai_call",
+        skip_all,
+        fields(provider = %self.provider, model = %self.model),
+        err
+    )]
+    async fn complete(&self, system_prompt: &str, user_prompt: &str) -> Result<String> {
+        let resp = self.send(system_prompt, user_prompt, false).await?;
+        let completion: Completion = serde_json::from_str(&resp.text().await?)?;
+        completion
+            .choices
+            .into_iter()
+            .next()
+            .and_then(|c| c.message.content)
+            .filter(|text| !text.trim().is_empty())
+            .ok_or(AiError::EmptyResponse)
+    }
+
+    #[tracing::instrument(
+        name = "ai_call",
+        skip_all,
+        fields(provider = %self.provider, model = %self.model),
+        err
+    )]
+    async fn complete_streaming(
+        &self,
+        system_prompt: &str,
+        user_prompt: &str,
+        on_delta: &mut (dyn FnMut(&str) + Send),
+    ) -> Result<String> {
+        let resp = self.send(system_prompt, user_prompt, true).await?;
+        read_events(resp, on_delta, |data| {
+            if data == "[DONE]" {
+                return Ok(None);
+            }
+            let chunk: StreamChunk = serde_json::from_str(data)?;
+            Ok(chunk
+                .choices
+                .into_iter()
+                .next()
+                .and_then(|c| c.delta.content))
+        })
+        .await
+    }
+}
+
+impl AiProvider for OpenAiCompatClient {
+    fn name(&self) -> String {
+        format!("{}/{}", self.provider, self.model)
+    }
+
+    fn chat_completion<'a>(
+        &'a mut self,
+        system_prompt: &'a str,
+        user_prompt: &'a str,
+    ) -> AiFuture<'a, String> {
+        Box::pin(self.complete(system_prompt, user_prompt))
+    }
+
+    fn chat_completion_stream<'a>(
+        &'a mut self,
+        system_prompt: &'a str,
+        user_prompt: &'a str,
+        on_delta: &'a mut (dyn FnMut(&str) + Send),
+    ) -> AiFuture<'a, String> {
+        Box::pin(self.complete_streaming(system_prompt, user_prompt, on_delta))
+    }
+}
+
+#[derive(Deserialize)]
+struct Completion {
+    choices: Vec<CompletionChoice>,
+}
+
+#[derive(Deserialize)]
+struct CompletionChoice {
+    message: CompletionMessage,
+}
+
+#[derive(Deserialize)]
+struct CompletionMessage {
+    content: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct StreamChunk {
+    choices: Vec<StreamChoice>,
+}
+
+#[derive(Deserialize)]
+struct StreamChoice {
+    delta: CompletionMessage,
+}
+
+/// Client for Anthropic's Messages API.
+#[derive(Clone)]
+pub struct AnthropicClient {
+    base_url: String,
+    api_key: String,
+    model: String,
+    temperature: f64,
+    max_tokens: u32,
+}
+
+impl std::fmt::Debug for AnthropicClient {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AnthropicClient")
+            .field("base_url", &self.base_url)
+            .field("model", &self.model)
+            .finish_non_exhaustive()
+    }
+}
+
+impl AnthropicClient {
+    /// `base_url` is the API root (`.../v1`) or the full `/messages` URL.
+    pub fn new(base_url: &str, api_key: &str, model: &str) -> Result<Self> {
+        check_config("anthropic", base_url, api_key, model)?;
+        Ok(Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            api_key: api_key.to_string(),
+            model: model.to_string(),
+            temperature: DEFAULT_TEMPERATURE,
+            max_tokens: ANTHROPIC_MAX_TOKENS,
+        })
+    }
+
+    pub fn claude(api_key: &str) -> Result<Self> {
+        Self::new(ANTHROPIC_URL, api_key, ANTHROPIC_MODEL)
+    }
+
+    pub fn with_model(mut self, model: &str) -> Self {
+        self.model = model.to_string();
+        self
+    }
+
+    pub fn with_temperature(mut self, temperature: f64) -> Self {
+        self.temperature = temperature;
+        self
+    }
+
+    pub fn with_max_tokens(mut self, max_tokens: u32) -> Self {
+        self.max_tokens = max_tokens;
+        self
+    }
+
+    pub fn model(&self) -> &str {
+        &self.model
+    }
+
+    fn url(&self) -> String {
+        if self.base_url.ends_with("/messages") {
+            self.base_url.clone()
+        } else {
+            format!("{}/messages", self.base_url)
+        }
+    }
+
+    async fn send(
+        &self,
+        system_prompt: &str,
+        user_prompt: &str,
+        stream: bool,
+    ) -> Result<reqwest::Response> {
+        // The system prompt is a top-level field, not a message, and may not
+        // be empty.
+        let mut body = json!({
+            "model": self.model,
+            "messages": [{ "role": "user", "content": user_prompt }],
+            "temperature": self.temperature,
+            "max_tokens": self.max_tokens,
+            "stream": stream,
+        });
+        if !system_prompt.is_empty() {
+            body["system"] = json!(system_prompt);
+        }
+        let resp = client_for(EndpointClass::Ai)
+            .post(self.url())
+            .header("x-api-key", &self.api_key)
+            .header("anthropic-version", ANTHROPIC_VERSION)
+            .timeout(timeout_for(EndpointClass::Ai))
+            .json(&body)
+            .send()
+            .await?;
+        check_status(resp).await
+    }
+
+    #[tracing::instrument(
+        name = "ai_call",
+        skip_all,
+        fields(provider = "anthropic", model = %self.model),
+        err
+    )]
+    async fn complete(&self, system_prompt: &str, user_prompt: &str) -> Result<String> {
+        let resp = self.send(system_prompt, user_prompt, false).await?;
+        let message: AnthropicMessage = serde_json::from_str(&resp.text().await?)?;
+        let text: String = message
+            .content
+            .into_iter()
+            .filter(|block| block.kind == "text")
+            .filter_map(|block| block.text)
+            .collect();
+        if text.trim().is_empty() {
+            return Err(AiError::EmptyResponse);
+        }
+        Ok(text)
+    }
+
+    #[tracing::instrument(
+        name = "ai_call",
+        skip_all,
+        fields(provider = "anthropic", model = %self.model),
+        err
+    )]
+    async fn complete_streaming(
+        &self,
+        system_prompt: &str,
+        user_prompt: &str,
+        on_delta: &mut (dyn FnMut(&str) + Send),
+    ) -> Result<String> {
+        let resp = self.send(system_prompt, user_prompt, true).await?;
+        read_events(resp, on_delta, |data| {
+            let event: AnthropicEvent = serde_json::from_str(data)?;
+            match event.kind.as_str() {
+                "content_block_delta" => Ok(event
+                    .delta
+                    .filter(|d| d.kind == "text_delta")
+                    .and_then(|d| d.text)),
+                // Errors after the response started arrive as an event.
+                "error" => Err(AiError::Api {
+                    status: 200,
+                    body: data.to_string(),
+                }),
+                _ => Ok(None),
+            }
+        })
+        .await
+    }
+}
+
+impl AiProvider for AnthropicClient {
+    fn name(&self) -> String {
+        format!("anthropic/{}", self.model)
+    }
+
+    fn chat_completion<'a>(
+        &'a mut self,
+        system_prompt: &'a str,
+        user_prompt: &'a str,
+    ) -> AiFuture<'a, String> {
+        Box::pin(self.complete(system_prompt, user_prompt))
+    }
+
+    fn chat_completion_stream<'a>(
+        &'a mut self,
+        system_prompt: &'a str,
+        user_prompt: &'a str,
+        on_delta: &'a mut (dyn FnMut(&str) + Send),
+    ) -> AiFuture<'a, String> {
+        Box::pin(self.complete_streaming(system_prompt, user_prompt, on_delta))
+    }
+}
+
+#[derive(Deserialize)]
+struct AnthropicMessage {
+    content: Vec<AnthropicBlock>,
+}
+
+#[derive(Deserialize)]
+struct AnthropicBlock {
+    #[serde(rename = "type")]
+    kind: String,
+    text: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct AnthropicEvent {
+    #[serde(rename = "type")]
+    kind: String,
+    delta: Option<AnthropicBlock>,
+}
+
+/// Client for Google's Gemini `generateContent` API.
+#[derive(Clone)]
+pub struct GeminiClient {
+    base_url: String,
+    api_key: String,
+    model: String,
+    temperature: f64,
+    max_tokens: u32,
+}
+
+impl std::fmt::Debug for GeminiClient {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("GeminiClient")
+            .field("base_url", &self.base_url)
+            .field("model", &self.model)
+            .finish_non_exhaustive()
+    }
+}
+
+impl GeminiClient {
+    /// `base_url` is the API root (`.../v1beta`); the model is part of the
+    /// request path.
+    pub fn new(base_url: &str, api_key: &str, model: &str) -> Result<Self> {
+        check_config("gemini", base_url, api_key, model)?;
+        Ok(Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            api_key: api_key.to_string(),
+            model: model.to_string(),
+            temperature: DEFAULT_TEMPERATURE,
+            max_tokens: GEMINI_MAX_TOKENS,
+        })
+    }
+
+    pub fn gemini(api_key: &str) -> Result<Self> {
+        Self::new(GEMINI_URL, api_key, GEMINI_MODEL)
+    }
+
+    pub fn with_model(mut self, model: &str) -> Self {
+        self.model = model.to_string();
+        self
+    }
+
+    pub fn with_temperature(mut self, temperature: f64) -> Self {
+        self.temperature = temperature;
+        self
+    }
+
+    pub fn with_max_tokens(mut self, max_tokens: u32) -> Self {
+        self.max_tokens = max_tokens;
+        self
+    }
+
+    pub fn model(&self) -> &str {
+        &self.model
+    }
+
+    async fn send(
+        &self,
+        system_prompt: &str,
+        user_prompt: &str,
+        stream: bool,
+    ) -> Result<reqwest::Response> {
+        let mut body = json!({
+            "contents": [{ "role": "user", "parts": [{ "text": user_prompt }] }],
+            "generationConfig": {
+                "temperature": self.temperature,
+                "maxOutputTokens": self.max_tokens,
+            },
+        });
+        if !system_prompt.is_empty() {
+            body["systemInstruction"] = json!({ "parts": [{ "text": system_prompt }] });
+        }
+        let url = if stream {
+            format!(
+                "{}/models/{}:streamGenerateContent?alt=sse",
+                self.base_url, self.model
+            )
+        } else {
+            format!("{}/models/{}:generateContent", self.base_url, self.model)
+        };
+        let resp = client_for(EndpointClass::Ai)
+            .post(url)
+            .header("x-goog-api-key", &self.api_key)
+            .timeout(timeout_for(EndpointClass::Ai))
+            .json(&body)
+            .send()
+            .await?;
+        check_status(resp).await
+    }
+
+    #[tracing::instrument(
+        name = "ai_call",
+        skip_all,
+        fields(provider = "gemini", model = %self.model),
+        err
+    )]
+    async fn complete(&self, system_prompt: &str, user_prompt: &str) -> Result<String> {
+        let resp = self.send(system_prompt, user_prompt, false).await?;
+        let response: GeminiResponse = serde_json::from_str(&resp.text().await?)?;
+        let text = response.text();
+        if text.trim().is_empty() {
+            return Err(AiError::EmptyResponse);
+        }
+        Ok(text)
+    }
+
+    #[tracing::instrument(
+        name = "ai_call",
+        skip_all,
+        fields(provider = "gemini", model = %self.model),
+        err
+    )]
+    async fn complete_streaming(
+        &self,
+        system_prompt: &str,
+        user_prompt: &str,
+        on_delta: &mut (dyn FnMut(&str) + Send),
+    ) -> Result<String> {
+        let resp = self.send(system_prompt, user_prompt, true).await?;
+        read_events(resp, on_delta, |data| {
+            let response: GeminiResponse = serde_json::from_str(data)?;
+            Ok(Some(response.text()).filter(|t| !t.is_empty()))
+        })
+        .await
+    }
+}
+
+impl AiProvider for GeminiClient {
+    fn name(&self) -> String {
+        format!("gemini/{}", self.model)
+    }
+
+    fn chat_completion<'a>(
+        &'a mut self,
+        system_prompt: &'a str,
+        user_prompt: &'a str,
+    ) -> AiFuture<'a, String> {
+        Box::pin(self.complete(system_prompt, user_prompt))
+    }
+
+    fn chat_completion_stream<'a>(
+        &'a mut self,
+        system_prompt: &'a str,
+        user_prompt: &'a str,
+        on_delta: &'a mut (dyn FnMut(&str) + Send),
+    ) -> AiFuture<'a, String> {
+        Box::pin(self.complete_streaming(system_prompt, user_prompt, on_delta))
+    }
+}
+
+#[derive(Deserialize)]
+struct GeminiResponse {
+    // Missing when the prompt was blocked.
+    #[serde(default)]
+    candidates: Vec<GeminiCandidate>,
+}
+
+impl GeminiResponse {
+    // The first candidate's answer, without thought summaries.
+    fn text(self) -> String {
+        self.candidates
+            .into_iter()
+            .next()
+            .and_then(|c| c.content)
+            .map(|c| {
+                c.parts
+                    .into_iter()
+                    .filter(|p| !p.thought)
+                    .filter_map(|p| p.text)
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Deserialize)]
+struct GeminiCandidate {
+    content: Option<GeminiContent>,
+}
+
+#[derive(Deserialize)]
+struct GeminiContent {
+    #[serde(default)]
+    parts: Vec<GeminiPart>,
+}
+
+#[derive(Deserialize)]
+struct GeminiPart {
+    text: Option<String>,
+    #[serde(default)]
+    thought: bool,
+}
+
+fn check_config(provider: &str, base_url: &str, api_key: &str, model: &str) -> Result<()> {
+    if api_key.is_empty() {
+        return Err(AiError::Config(format!("{} has no API key", provider)));
+    }
+    if base_url.is_empty() || model.is_empty() {
+        return Err(AiError::Config(format!(
+            "{} needs an API URL and a model name",
+            provider
+        )));
+    }
+    Ok(())
+}
+
+async fn check_status(resp: reqwest::Response) -> Result<reqwest::Response> {
+    let status = resp.status();
+    if !status.is_success() {
+        return Err(AiError::Api {
+            status: status.as_u16(),
+            body: resp.text().await.unwrap_or_default(),
+        });
+    }
+    Ok(resp)
+}
+
+// Reads a server-sent event stream, passing each `data:` payload to `delta`
+// for the text it adds, and returns the whole text.
+async fn read_events(
+    mut resp: reqwest::Response,
+    on_delta: &mut (dyn FnMut(&str) + Send),
+    delta: impl Fn(&str) -> Result<Option<String>>,
+) -> Result<String> {
+    let mut text = String::new();
+    // Bytes, not text: a chunk may end in the middle of a UTF-8 character.
+    let mut pending: Vec<u8> = Vec::new();
+    while let Some(chunk) = resp.chunk().await? {
+        pending.extend_from_slice(&chunk);
+        while let Some(end) = pending.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = pending.drain(..=end).collect();
+            let line = String::from_utf8_lossy(&line);
+            let Some(data) = line.trim().strip_prefix("data:") else {
+                continue;
+            };
+            if let Some(piece) = delta(data.trim())? {
+                on_delta(&piece);
+                text.push_str(&piece);
+            }
+        }
+    }
+    if text.trim().is_empty() {
+        return Err(AiError::EmptyResponse);
+    }
+    Ok(text)
+}
+
+/// The client for an AI model from the ai_models table. A custom API URL or
+/// model name overrides the provider's default.
+pub fn from_model_config(model: &AIModelConfig) -> Result<Box<dyn AiProvider>> {
+    let (default_url, default_model) = match model.provider.as_str() {
+        "deepseek" => (DEEPSEEK_URL, DEEPSEEK_MODEL),
+        "qwen" => (QWEN_URL, QWEN_MODEL),
+        "anthropic" => (ANTHROPIC_URL, ANTHROPIC_MODEL),
+        "gemini" => (GEMINI_URL, GEMINI_MODEL),
+        "custom" => ("", ""),
+        other => return Err(AiError::Config(format!("unknown AI provider '{}'", other))),
+    };
+    let url = if model.custom_api_url.is_empty() {
+        default_url
+    } else {
+        &model.custom_api_url
+    };
+    let name = if model.custom_model_name.is_empty() {
+        default_model
+    } else {
+        &model.custom_model_name
+    };
+    Ok(match model.provider.as_str() {
+        "anthropic" => Box::new(AnthropicClient::new(url, &model.api_key, name)?),
+        "gemini" => Box::new(GeminiClient::new(url, &model.api_key, name)?),
+        provider => Box::new(OpenAiCompatClient::new(
+            provider,
+            url,
+            &model.api_key,
+            name,
+        )?),
+    })
+}
@@ -0,0 +1,141 @@
+//! Optional cache of AI responses keyed on the model and a hash of the
+//! prompts.
+//!
+//! A trader that re-sends the exact same prompts, e.g. when a cycle is retried
+//! after a transient network error, gets the earlier response back instead of
+//! paying for a second completion. Entries live for a short TTL so
+//! a changed market always produces a fresh call. Disabled unless
+//! `ai_response_cache.enabled` is set.
+
+use std::sync::RwLock;
+use std::time::Duration;
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::ai::{AiFuture, AiProvider};
+use crate::cache::{BoundedCache, CacheStats};
+
+const CAPACITY: usize = 256;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct AiCacheParams {
+    pub enabled: bool,
+    /// How long a response can be reused.
+    #[serde(with = "humantime_serde")]
+    pub ttl: Duration,
+}
+
+impl Default for AiCacheParams {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            ttl: Duration::from_secs(5 * 60),
+        }
+    }
+}
+
+static PARAMS: Lazy<RwLock<AiCacheParams>> = Lazy::new(|| RwLock::new(AiCacheParams::default()));
+// (model, SHA-256 of the prompts) -> response.
+static RESPONSES: Lazy<BoundedCache<(String, [u8; 32]), String>> =
+    Lazy::new(|| BoundedCache::new(CAPACITY, Duration::from_secs(5 * 60)));
+
+/// Replaces the cache settings, e.g. from the config file.
+pub fn set_params(params: AiCacheParams) {
+    *PARAMS.write().unwrap_or_else(|e| e.into_inner()) = params;
+}
+
+fn params() -> AiCacheParams {
+    *PARAMS.read().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Hit/miss counters of the response cache.
+pub fn stats() -> CacheStats {
+    RESPONSES.stats()
+}
+
+/// Drops every cached response.
+pub fn clear() {
+    RESPONSES.clear();
+}
+
+/// Wraps `provider` so its responses are cached while the cache is enabled.
+pub fn wrap(provider: Box<dyn AiProvider>) -> Box<dyn AiProvider> {
+    Box::new(CachedProvider { inner: provider })
+}
+
+/// An [`AiProvider`] that answers repeated prompts from the shared cache.
+pub struct CachedProvider {
+    inner: Box<dyn AiProvider>,
+}
+
+impl CachedProvider {
+    fn key(&self, system_prompt: &str, user_prompt: &str) -> (String, [u8; 32]) {
+        let mut hasher = Sha256::new();
+        hasher.update(system_prompt.len().to_le_bytes());
+        hasher.update(system_prompt);
+        hasher.update(user_prompt);
+        (self.inner.name(), hasher.finalize().into())
+    }
+}
+
+impl AiProvider for CachedProvider {
+    fn name(&self) -> String {
+        self.inner.name()
+    }
+
+    fn chat_completion<'a>(
+        &'a mut self,
+        system_prompt: &'a str,
+        user_prompt: &'a str,
+    ) -> AiFuture<'a, String> {
+        Box::pin(async move {
+            let params = params();
+            if !params.enabled {
+                return self.inner.chat_completion(system_prompt, user_prompt).await;
+            }
+            let key = self.key(system_prompt, user_prompt);
+            if let Some(response) = RESPONSES.get(&key) {
+                tracing::info!("♻️ 复用缓存的 AI 响应 ({})", key.0);
+                return Ok(response);
+            }
+            let response = self
+                .inner
+                .chat_completion(system_prompt, user_prompt)
+                .await?;
+            RESPONSES.insert_with_ttl(key, response.clone(), params.ttl);
+            Ok(response)
+        })
+    }
+
+    fn chat_completion_stream<'a>(
+        &'a mut self,
+        system_prompt: &'a str,
+        user_prompt: &'a str,
+        on_delta: &'a mut (dyn FnMut(&str) + Send),
+    ) -> AiFuture<'a, String> {
+        Box::pin(async move {
+            let params = params();
+            if !params.enabled {
+                return self
+                    .inner
+                    .chat_completion_stream(system_prompt, user_prompt, on_delta)
+                    .await;
+            }
+            let key = self.key(system_prompt, user_prompt);
+            if let Some(response) = RESPONSES.get(&key) {
+                tracing::info!("♻️ 复用缓存的 AI 响应 ({})", key.0);
+                on_delta(&response);
+                return Ok(response);
+            }
+            let response = self
+                .inner
+                .chat_completion_stream(system_prompt, user_prompt, on_delta)
+                .await?;
+            RESPONSES.insert_with_ttl(key, response.clone(), params.ttl);
+            Ok(response)
+        })
+    }
+}
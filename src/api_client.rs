@@ -1,62 +1,648 @@
-use anyhow::{Context, Ok, Result};
-use serde::Deserialize;
-use serde::de::{self, Deserializer, SeqAccess, Visitor};
+use anyhow::{Context, Result};
+use hmac::{Hmac, Mac};
+use once_cell::sync::{Lazy, OnceCell};
+use reqwest::Method;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
 use std::fmt;
-use std::time::Duration;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::time::{Duration, Instant};
+use thiserror::Error;
 
-use crate::types::{ExchangeInfo, Kline, PriceTicker};
+use crate::audit::{self, AuditOwner, OrderCall};
+use crate::database::ExchangeConfig;
+use crate::decision::PositionInfo;
+use crate::executor::{self, ExecutorError, OrderFill, TradeExecutor};
+use crate::money::{self, Decimal};
+use crate::rate_limit::{RateLimitError, SendLimited};
+use crate::symbol_meta::{self, SymbolMeta};
+use crate::types::{
+    AccountBalance, ApiRestrictions, ExchangeInfo, IncomeRecord, Kline, OrderRequest,
+    OrderResponse, OrderSide, OrderType, PositionRisk, PriceTicker, Ticker24h,
+};
 
 const BASE_URL: &str = "https://fapi.binance.com";
+const TESTNET_URL: &str = "https://testnet.binancefuture.com";
+// Account-wide endpoints (API key permissions) live on the spot API.
+const SPOT_URL: &str = "https://api.binance.com";
+
+/// How long after its timestamp Binance still accepts a signed request.
+const DEFAULT_RECV_WINDOW: Duration = Duration::from_millis(5000);
+/// Binance's upper limit for recvWindow.
+const MAX_RECV_WINDOW: Duration = Duration::from_millis(60000);
+/// "Timestamp for this request is outside of the recvWindow."
+const CODE_TIMESTAMP_OUTSIDE_RECV_WINDOW: i64 = -1021;
+
+// One client for the whole process so every module shares the connection pool.
+static HTTP_CLIENT: Lazy<reqwest::Client> = Lazy::new(|| {
+    reqwest::Client::builder()
+        .pool_idle_timeout(Duration::from_secs(90))
+        .tcp_keepalive(Duration::from_secs(60))
+        .build()
+        .expect("Failed to build HTTP client")
+});
+
+// Timeouts, can only be set once at startup.
+static TIMEOUTS: OnceCell<Timeouts> = OnceCell::new();
+
+// Clients for proxied traffic, can only be set once at startup.
+static PROXIED_CLIENTS: OnceCell<ProxiedClients> = OnceCell::new();
+
+/// Returns a handle to the shared HTTP client (cheap to clone). It never goes
+/// through a proxy; exchange, market data and AI calls use [`client_for`].
+pub fn shared_client() -> reqwest::Client {
+    HTTP_CLIENT.clone()
+}
+
+/// Returns the shared client for a class of endpoint, routed through the proxy
+/// configured for its kind of traffic, if any.
+pub fn client_for(class: EndpointClass) -> reqwest::Client {
+    let clients = PROXIED_CLIENTS.get();
+    let proxied = match class {
+        EndpointClass::MarketData => clients.and_then(|c| c.market_data.as_ref()),
+        EndpointClass::ExchangeInfo | EndpointClass::Trading => {
+            clients.and_then(|c| c.exchange.as_ref())
+        }
+        EndpointClass::Ai => clients.and_then(|c| c.ai.as_ref()),
+    };
+    proxied.cloned().unwrap_or_else(shared_client)
+}
+
+/// Builds the clients for the configured proxies.
+/// This function can only be called successfully once.
+pub fn set_proxies(proxies: &Proxies) -> Result<()> {
+    let clients = ProxiedClients {
+        exchange: proxied_client("exchange", proxies.exchange.as_ref())?,
+        market_data: proxied_client("market_data", proxies.market_data.as_ref())?,
+        ai: proxied_client("ai", proxies.ai.as_ref())?,
+    };
+    let _ = PROXIED_CLIENTS.set(clients);
+    Ok(())
+}
+
+struct ProxiedClients {
+    exchange: Option<reqwest::Client>,
+    market_data: Option<reqwest::Client>,
+    ai: Option<reqwest::Client>,
+}
+
+/// Outbound proxies per kind of traffic; unset goes direct. The market data
+/// WebSocket stream always connects directly.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct Proxies {
+    /// Order placement, account queries and exchange metadata.
+    pub exchange: Option<ProxySettings>,
+    /// Klines, tickers, funding and the fallback data sources.
+    pub market_data: Option<ProxySettings>,
+    /// LLM API calls.
+    pub ai: Option<ProxySettings>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct ProxySettings {
+    /// `http://`, `https://`, `socks5://` or `socks5h://host:port`. SOCKS
+    /// proxies need the `socks` feature.
+    pub url: String,
+    pub username: String,
+    pub password: String,
+    /// Comma-separated hosts that bypass the proxy, e.g. "localhost,.internal".
+    pub no_proxy: String,
+}
+
+// Never print the password.
+impl fmt::Debug for ProxySettings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProxySettings")
+            .field("url", &self.url)
+            .field("username", &self.username)
+            .field("password", &"***")
+            .field("no_proxy", &self.no_proxy)
+            .finish()
+    }
+}
+
+impl ProxySettings {
+    fn proxy(&self) -> Result<reqwest::Proxy> {
+        let scheme = self.url.split("://").next().unwrap_or_default();
+        if scheme.starts_with("socks") && !cfg!(feature = "socks") {
+            anyhow::bail!("SOCKS proxy {} needs the `socks` feature", self.url);
+        }
+        let mut proxy = reqwest::Proxy::all(&self.url)
+            .with_context(|| format!("Invalid proxy URL: {}", self.url))?;
+        if !self.username.is_empty() {
+            proxy = proxy.basic_auth(&self.username, &self.password);
+        }
+        if !self.no_proxy.is_empty() {
+            proxy = proxy.no_proxy(reqwest::NoProxy::from_string(&self.no_proxy));
+        }
+        Ok(proxy)
+    }
+}
+
+fn proxied_client(
+    traffic: &str,
+    settings: Option<&ProxySettings>,
+) -> Result<Option<reqwest::Client>> {
+    let Some(settings) = settings else {
+        return Ok(None);
+    };
+    let client = reqwest::Client::builder()
+        .pool_idle_timeout(Duration::from_secs(90))
+        .tcp_keepalive(Duration::from_secs(60))
+        .proxy(settings.proxy()?)
+        .build()
+        .context("Failed to build proxied HTTP client")?;
+    tracing::info!("🌐 {} 流量经代理 {} 转发", traffic, settings.url);
+    Ok(Some(client))
+}
+
+/// Sets the global per-endpoint timeouts.
+/// This function can only be called successfully once.
+pub fn set_timeouts(timeouts: Timeouts) {
+    let _ = TIMEOUTS.set(timeouts);
+}
+
+/// Returns the timeout configured for a class of endpoint.
+pub fn timeout_for(class: EndpointClass) -> Duration {
+    let timeouts = TIMEOUTS.get().copied().unwrap_or_default();
+    match class {
+        EndpointClass::MarketData => timeouts.market_data,
+        EndpointClass::ExchangeInfo => timeouts.exchange_info,
+        EndpointClass::Trading => timeouts.trading,
+        EndpointClass::Ai => timeouts.ai,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EndpointClass {
+    /// Klines, tickers, funding, open interest.
+    MarketData,
+    /// Large, rarely-changing metadata such as exchangeInfo.
+    ExchangeInfo,
+    /// Order placement and account queries.
+    Trading,
+    /// LLM chat completions, which can take minutes with long reasoning.
+    Ai,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[serde(default)]
+pub struct Timeouts {
+    #[serde(with = "humantime_serde")]
+    pub market_data: Duration,
+    #[serde(with = "humantime_serde")]
+    pub exchange_info: Duration,
+    #[serde(with = "humantime_serde")]
+    pub trading: Duration,
+    #[serde(with = "humantime_serde")]
+    pub ai: Duration,
+}
+
+impl Default for Timeouts {
+    fn default() -> Self {
+        Self {
+            market_data: Duration::from_secs(10),
+            exchange_info: Duration::from_secs(30),
+            trading: Duration::from_secs(15),
+            ai: Duration::from_secs(120),
+        }
+    }
+}
+
+/// An error response from Binance (`{"code": -2019, "msg": "..."}`).
+#[derive(Error, Debug, Clone, PartialEq, Eq, Deserialize)]
+#[error("Binance API error {code}: {msg}")]
+pub struct BinanceApiError {
+    pub code: i64,
+    pub msg: String,
+}
+
+/// API key and secret for signed endpoints.
+#[derive(Clone)]
+pub struct Credentials {
+    pub api_key: String,
+    secret_key: String,
+}
+
+impl Credentials {
+    pub fn new(api_key: &str, secret_key: &str) -> Self {
+        Self {
+            api_key: api_key.to_string(),
+            secret_key: secret_key.to_string(),
+        }
+    }
+
+    /// Hex HMAC-SHA256 of a query string, as Binance expects in `signature`.
+    pub fn sign(&self, query: &str) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(self.secret_key.as_bytes())
+            .expect("HMAC accepts keys of any length");
+        mac.update(query.as_bytes());
+        hex::encode(mac.finalize().into_bytes())
+    }
+}
+
+// Never print the secret.
+impl fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Credentials")
+            .field("api_key", &self.api_key)
+            .field("secret_key", &"***")
+            .finish()
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ServerTime {
+    server_time: i64,
+}
 
 pub struct ApiClient {
-    client: reqwest::blocking::Client,
+    base_url: String,
+    // None on the testnet, which has no spot account API.
+    spot_url: Option<String>,
+    credentials: Option<Credentials>,
+    recv_window: Duration,
+    // Binance server time minus local time, in milliseconds.
+    time_offset_ms: AtomicI64,
+    // Attributed to order calls in the execution audit.
+    audit_owner: AuditOwner,
 }
 
 impl ApiClient {
-    pub fn new() -> Result<Self> {
-        let client = reqwest::blocking::Client::builder()
-            .timeout(Duration::from_secs(30))
-            .build()
-            .context("Failed to build HTTP client")?;
+    /// A client for the public endpoints only.
+    pub fn new() -> Self {
+        Self {
+            base_url: BASE_URL.to_string(),
+            spot_url: Some(SPOT_URL.to_string()),
+            credentials: None,
+            recv_window: DEFAULT_RECV_WINDOW,
+            time_offset_ms: AtomicI64::new(0),
+            audit_owner: AuditOwner::default(),
+        }
+    }
 
-        Ok(Self { client })
+    /// A client for a Binance account from the exchanges table.
+    pub fn for_exchange(exchange: &ExchangeConfig) -> Self {
+        let client = Self::new()
+            .with_credentials(&exchange.api_key, &exchange.secret_key)
+            .with_audit_owner(&exchange.user_id, "");
+        if exchange.testnet {
+            let mut client = client.with_base_url(TESTNET_URL);
+            client.spot_url = None;
+            client
+        } else {
+            client
+        }
     }
 
-    pub fn get_exchange_info(&self) -> Result<ExchangeInfo> {
-        let url = format!("{}/fapi/v1/exchangeInfo", BASE_URL);
-        let resp = self.client.get(url).send()?;
+    pub fn with_credentials(mut self, api_key: &str, secret_key: &str) -> Self {
+        self.credentials = Some(Credentials::new(api_key, secret_key));
+        self
+    }
+
+    pub fn with_base_url(mut self, base_url: &str) -> Self {
+        self.base_url = base_url.trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Attributes this client's order calls to a user and trader in the
+    /// execution audit.
+    pub fn with_audit_owner(mut self, user_id: &str, trader_id: &str) -> Self {
+        self.audit_owner = AuditOwner {
+            user_id: user_id.to_string(),
+            trader_id: trader_id.to_string(),
+        };
+        self
+    }
+
+    pub fn with_spot_url(mut self, spot_url: &str) -> Self {
+        self.spot_url = Some(spot_url.trim_end_matches('/').to_string());
+        self
+    }
+
+    /// Capped at Binance's maximum of 60s.
+    pub fn with_recv_window(mut self, recv_window: Duration) -> Self {
+        self.recv_window = recv_window.min(MAX_RECV_WINDOW);
+        self
+    }
+
+    /// Measures the local clock's offset from Binance server time, so signed
+    /// requests are not rejected when the local clock drifts.
+    pub async fn sync_time(&self) -> Result<i64> {
+        let started = chrono::Utc::now().timestamp_millis();
+        let server = client_for(EndpointClass::MarketData)
+            .get(format!("{}/fapi/v1/time", self.base_url))
+            .timeout(timeout_for(EndpointClass::MarketData))
+            .send_limited()
+            .await?
+            .json::<ServerTime>()
+            .await
+            .context("Failed to deserialize server time")?;
+        let finished = chrono::Utc::now().timestamp_millis();
+
+        // Assume the server read its clock halfway through the round trip.
+        let offset = server.server_time - (started + finished) / 2;
+        self.time_offset_ms.store(offset, Ordering::Relaxed);
+        if offset.abs() > 1000 {
+            tracing::warn!("⏱️ 本地时钟与币安服务器相差 {} ms", offset);
+        }
+        Ok(offset)
+    }
+
+    fn timestamp(&self) -> i64 {
+        chrono::Utc::now().timestamp_millis() + self.time_offset_ms.load(Ordering::Relaxed)
+    }
+
+    /// Sends a signed request and decodes the response. A request rejected for
+    /// falling outside recvWindow is retried once after re-syncing the clock.
+    async fn signed<T: DeserializeOwned>(
+        &self,
+        method: Method,
+        path: &str,
+        params: &[(&str, String)],
+    ) -> Result<T> {
+        self.signed_at(&self.base_url, method, path, params).await
+    }
+
+    async fn signed_at<T: DeserializeOwned>(
+        &self,
+        base_url: &str,
+        method: Method,
+        path: &str,
+        params: &[(&str, String)],
+    ) -> Result<T> {
+        match self
+            .send_signed(base_url, method.clone(), path, params)
+            .await
+        {
+            Err(e)
+                if e.downcast_ref::<BinanceApiError>()
+                    .is_some_and(|e| e.code == CODE_TIMESTAMP_OUTSIDE_RECV_WINDOW) =>
+            {
+                tracing::warn!("⏱️ 请求 {} 超出 recvWindow，同步服务器时间后重试", path);
+                self.sync_time().await?;
+                self.send_signed(base_url, method, path, params).await
+            }
+            result => result,
+        }
+    }
+
+    async fn send_signed<T: DeserializeOwned>(
+        &self,
+        base_url: &str,
+        method: Method,
+        path: &str,
+        params: &[(&str, String)],
+    ) -> Result<T> {
+        let credentials = self
+            .credentials
+            .as_ref()
+            .context("Signed endpoint requires API credentials")?;
+
+        let mut pairs: Vec<(String, String)> = params
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.clone()))
+            .collect();
+        pairs.push((
+            "recvWindow".to_string(),
+            self.recv_window.as_millis().to_string(),
+        ));
+        pairs.push(("timestamp".to_string(), self.timestamp().to_string()));
+        let query = pairs
+            .iter()
+            .map(|(k, v)| format!("{}={}", k, urlencoding::encode(v)))
+            .collect::<Vec<_>>()
+            .join("&");
+        let signature = credentials.sign(&query);
+
+        let url = format!("{}{}?{}&signature={}", base_url, path, query, signature);
+        let started = Instant::now();
+        let sent = async {
+            let resp = client_for(EndpointClass::Trading)
+                .request(method.clone(), url)
+                .header("X-MBX-APIKEY", &credentials.api_key)
+                .timeout(timeout_for(EndpointClass::Trading))
+                .send_limited()
+                .await?;
+            let status = resp.status();
+            Ok::<_, RateLimitError>((status, resp.text().await?))
+        }
+        .await;
+        if audit::is_order_endpoint(path) {
+            let (status, body, error) = match &sent {
+                Ok((status, body)) => (status.as_u16(), body.as_str(), String::new()),
+                Err(e) => (0, "", e.to_string()),
+            };
+            let call = OrderCall {
+                exchange: "binance",
+                method: method.as_str(),
+                endpoint: path,
+                params: &pairs,
+                status,
+                response: body,
+                error: &error,
+                started,
+            };
+            audit::record(&self.audit_owner, call).await;
+        }
+
+        let (status, body) = sent?;
+        if !status.is_success() {
+            return Err(match serde_json::from_str::<BinanceApiError>(&body) {
+                Ok(e) => e.into(),
+                Err(_) => anyhow::anyhow!("HTTP {}: {}", status.as_u16(), body),
+            });
+        }
+        serde_json::from_str(&body).with_context(|| format!("Failed to deserialize {}", path))
+    }
+
+    #[tracing::instrument(
+        name = "exchange_request",
+        skip(self, order),
+        fields(endpoint = "order", symbol = %order.symbol),
+        err
+    )]
+    pub async fn place_order(&self, order: &OrderRequest) -> Result<OrderResponse> {
+        self.signed(Method::POST, "/fapi/v1/order", &order.params())
+            .await
+    }
+
+    #[tracing::instrument(name = "exchange_request", skip(self), fields(endpoint = "order"), err)]
+    pub async fn cancel_order(&self, symbol: &str, order_id: i64) -> Result<OrderResponse> {
+        let params = [
+            ("symbol", symbol.to_string()),
+            ("orderId", order_id.to_string()),
+        ];
+        self.signed(Method::DELETE, "/fapi/v1/order", &params).await
+    }
+
+    /// Open orders for one symbol, or for all symbols.
+    #[tracing::instrument(
+        name = "exchange_request",
+        skip(self),
+        fields(endpoint = "openOrders"),
+        err
+    )]
+    pub async fn get_open_orders(&self, symbol: Option<&str>) -> Result<Vec<OrderResponse>> {
+        let params: Vec<(&str, String)> = symbol
+            .map(|s| ("symbol", s.to_string()))
+            .into_iter()
+            .collect();
+        self.signed(Method::GET, "/fapi/v1/openOrders", &params)
+            .await
+    }
+
+    #[tracing::instrument(
+        name = "exchange_request",
+        skip(self),
+        fields(endpoint = "balance"),
+        err
+    )]
+    pub async fn get_balances(&self) -> Result<Vec<AccountBalance>> {
+        self.signed(Method::GET, "/fapi/v2/balance", &[]).await
+    }
+
+    /// Permissions of the API key. Not available on the testnet.
+    #[tracing::instrument(
+        name = "exchange_request",
+        skip(self),
+        fields(endpoint = "apiRestrictions"),
+        err
+    )]
+    pub async fn get_api_restrictions(&self) -> Result<ApiRestrictions> {
+        let spot_url = self
+            .spot_url
+            .as_deref()
+            .context("API key permissions are not available on the testnet")?;
+        self.signed_at(
+            spot_url,
+            Method::GET,
+            "/sapi/v1/account/apiRestrictions",
+            &[],
+        )
+        .await
+    }
+
+    /// Position risk for one symbol, or for every symbol (including flat ones).
+    #[tracing::instrument(
+        name = "exchange_request",
+        skip(self),
+        fields(endpoint = "positionRisk"),
+        err
+    )]
+    pub async fn get_position_risk(&self, symbol: Option<&str>) -> Result<Vec<PositionRisk>> {
+        let params: Vec<(&str, String)> = symbol
+            .map(|s| ("symbol", s.to_string()))
+            .into_iter()
+            .collect();
+        self.signed(Method::GET, "/fapi/v2/positionRisk", &params)
+            .await
+    }
+
+    #[tracing::instrument(
+        name = "exchange_request",
+        skip(self),
+        fields(endpoint = "leverage"),
+        err
+    )]
+    pub async fn set_leverage(&self, symbol: &str, leverage: i32) -> Result<()> {
+        let params = [
+            ("symbol", symbol.to_string()),
+            ("leverage", leverage.to_string()),
+        ];
+        self.signed::<serde_json::Value>(Method::POST, "/fapi/v1/leverage", &params)
+            .await?;
+        Ok(())
+    }
+
+    /// Income history since `start_time` (ms), optionally of one type (e.g. "TRANSFER").
+    #[tracing::instrument(
+        name = "exchange_request",
+        skip(self),
+        fields(endpoint = "income"),
+        err
+    )]
+    pub async fn get_income(
+        &self,
+        income_type: Option<&str>,
+        start_time: Option<i64>,
+    ) -> Result<Vec<IncomeRecord>> {
+        let mut params = vec![("limit", "1000".to_string())];
+        if let Some(income_type) = income_type {
+            params.push(("incomeType", income_type.to_string()));
+        }
+        if let Some(start_time) = start_time {
+            params.push(("startTime", start_time.to_string()));
+        }
+        self.signed(Method::GET, "/fapi/v1/income", &params).await
+    }
+
+    #[tracing::instrument(
+        name = "exchange_request",
+        skip(self),
+        fields(endpoint = "exchangeInfo"),
+        err
+    )]
+    pub async fn get_exchange_info(&self) -> Result<ExchangeInfo> {
+        let url = format!("{}/fapi/v1/exchangeInfo", self.base_url);
+        let resp = client_for(EndpointClass::ExchangeInfo)
+            .get(url)
+            .timeout(timeout_for(EndpointClass::ExchangeInfo))
+            .send_limited()
+            .await?;
         let exchange_info = resp
             .json::<ExchangeInfo>()
+            .await
             .context("Failed to deserialize ExchangeInfo")?;
 
         Ok(exchange_info)
     }
 
-    pub fn get_klines(&self, symbol: &str, interval: &str, limit: i32) -> Result<Vec<Kline>> {
-        let url = format!("{}/fapi/v1/klines", BASE_URL);
-        let klines = self
-            .client
+    #[tracing::instrument(
+        name = "exchange_request",
+        skip(self),
+        fields(endpoint = "klines"),
+        err
+    )]
+    pub async fn get_klines(&self, symbol: &str, interval: &str, limit: i32) -> Result<Vec<Kline>> {
+        let url = format!("{}/fapi/v1/klines", self.base_url);
+        let klines = client_for(EndpointClass::MarketData)
             .get(&url)
+            .timeout(timeout_for(EndpointClass::MarketData))
             .query(&[
                 ("symbol", symbol),
                 ("interval", interval),
                 ("limit", &limit.to_string()),
             ])
-            .send()?
+            .send_limited()
+            .await?
             .json::<Vec<Kline>>()
+            .await
             .context("Failed to deserialize Klines")?;
 
         Ok(klines)
     }
 
-    pub fn get_current_price(&self, symbol: &str) -> Result<f64> {
-        let url = format!("{}/fapi/v1/ticker/price", BASE_URL);
-        let ticker = self
-            .client
+    #[tracing::instrument(
+        name = "exchange_request",
+        skip(self),
+        fields(endpoint = "ticker/price"),
+        err
+    )]
+    pub async fn get_current_price(&self, symbol: &str) -> Result<f64> {
+        let url = format!("{}/fapi/v1/ticker/price", self.base_url);
+        let ticker = client_for(EndpointClass::MarketData)
             .get(&url)
+            .timeout(timeout_for(EndpointClass::MarketData))
             .query(&[("symbol", symbol)])
-            .send()?
+            .send_limited()
+            .await?
             .json::<PriceTicker>()
+            .await
             .context("Failed to deserialize PriceTicker")?;
 
         // Parse the price string into a float
@@ -67,4 +653,230 @@ impl ApiClient {
 
         Ok(price)
     }
+
+    #[tracing::instrument(
+        name = "exchange_request",
+        skip(self),
+        fields(endpoint = "ticker/24hr"),
+        err
+    )]
+    pub async fn get_24h_tickers(&self) -> Result<Vec<Ticker24h>> {
+        let url = format!("{}/fapi/v1/ticker/24hr", self.base_url);
+        let tickers = client_for(EndpointClass::MarketData)
+            .get(&url)
+            .timeout(timeout_for(EndpointClass::MarketData))
+            .send_limited()
+            .await?
+            .json::<Vec<Ticker24h>>()
+            .await
+            .context("Failed to deserialize 24h tickers")?;
+
+        Ok(tickers)
+    }
+}
+
+/// Converts a Binance-format position into the engine's view; `None` when flat.
+pub fn position_info(risk: &PositionRisk) -> Option<PositionInfo> {
+    let parse = |v: &str| v.parse::<f64>().unwrap_or(0.0);
+    let amount = parse(&risk.position_amt);
+    if amount == 0.0 {
+        return None;
+    }
+
+    let side = match risk.position_side.as_str() {
+        "LONG" => "long",
+        "SHORT" => "short",
+        _ if amount < 0.0 => "short",
+        _ => "long",
+    };
+    let quantity = amount.abs();
+    let entry_price = parse(&risk.entry_price);
+    let leverage = risk.leverage.parse::<i32>().unwrap_or(1).max(1);
+    let unrealized_pnl = parse(&risk.unrealized_profit);
+    let margin_used = quantity * entry_price / leverage as f64;
+
+    Some(PositionInfo {
+        symbol: risk.symbol.clone(),
+        side: side.to_string(),
+        entry_price,
+        mark_price: parse(&risk.mark_price),
+        quantity,
+        leverage,
+        unrealized_pnl,
+        unrealized_pnl_pct: if margin_used > 0.0 {
+            unrealized_pnl / margin_used * 100.0
+        } else {
+            0.0
+        },
+        liquidation_price: parse(&risk.liquidation_price),
+        margin_used,
+        update_time: risk.update_time,
+    })
+}
+
+impl ApiClient {
+    // The symbol's order rules, from the cached exchangeInfo.
+    async fn meta(&self, symbol: &str) -> Result<SymbolMeta> {
+        symbol_meta::lookup(&self.base_url, symbol, || self.get_exchange_info()).await
+    }
+
+    async fn market(
+        &mut self,
+        symbol: &str,
+        side: OrderSide,
+        quantity: Decimal,
+        reduce_only: bool,
+    ) -> Result<OrderFill> {
+        let quantity = self.meta(symbol).await?.round_quantity(quantity);
+        if quantity <= Decimal::ZERO {
+            anyhow::bail!("{} quantity rounds to zero", symbol);
+        }
+        let mut order = OrderRequest::market(symbol, side, quantity);
+        order.reduce_only = reduce_only;
+        let resp = self.place_order(&order).await?;
+
+        Ok(OrderFill {
+            order_id: resp.order_id,
+            symbol: resp.symbol,
+            requested_quantity: quantity,
+            filled_quantity: money::parse(&resp.executed_qty),
+            price: money::parse(&resp.avg_price),
+            fill_latency_ms: 0,
+        })
+    }
+
+    async fn positions(&self) -> Result<Vec<PositionInfo>> {
+        Ok(self
+            .get_position_risk(None)
+            .await?
+            .iter()
+            .filter_map(position_info)
+            .collect())
+    }
+}
+
+fn exchange_error(e: anyhow::Error) -> ExecutorError {
+    ExecutorError::Exchange(e.to_string())
+}
+
+impl TradeExecutor for ApiClient {
+    async fn open_long(
+        &mut self,
+        symbol: &str,
+        quantity: Decimal,
+        _leverage: i32,
+    ) -> executor::Result<OrderFill> {
+        self.market(symbol, OrderSide::Buy, quantity, false)
+            .await
+            .map_err(exchange_error)
+    }
+
+    async fn open_short(
+        &mut self,
+        symbol: &str,
+        quantity: Decimal,
+        _leverage: i32,
+    ) -> executor::Result<OrderFill> {
+        self.market(symbol, OrderSide::Sell, quantity, false)
+            .await
+            .map_err(exchange_error)
+    }
+
+    async fn close(
+        &mut self,
+        symbol: &str,
+        side: &str,
+        quantity: Decimal,
+    ) -> executor::Result<OrderFill> {
+        let held = self
+            .positions()
+            .await
+            .map_err(exchange_error)?
+            .into_iter()
+            .find(|p| p.symbol == symbol && p.side == side)
+            .map_or(Decimal::ZERO, |p| money::from_f64(p.quantity));
+        if held <= Decimal::ZERO {
+            return Err(ExecutorError::NoPosition(
+                symbol.to_string(),
+                side.to_string(),
+            ));
+        }
+        let quantity = if quantity > Decimal::ZERO {
+            quantity.min(held)
+        } else {
+            held
+        };
+        let order_side = if side == "long" {
+            OrderSide::Sell
+        } else {
+            OrderSide::Buy
+        };
+        self.market(symbol, order_side, quantity, true)
+            .await
+            .map_err(exchange_error)
+    }
+
+    async fn set_leverage(&mut self, symbol: &str, leverage: i32) -> executor::Result<()> {
+        ApiClient::set_leverage(self, symbol, leverage)
+            .await
+            .map_err(exchange_error)
+    }
+
+    async fn get_positions(&mut self) -> executor::Result<Vec<PositionInfo>> {
+        self.positions().await.map_err(exchange_error)
+    }
+
+    async fn get_price(&mut self, symbol: &str) -> executor::Result<f64> {
+        self.get_current_price(symbol).await.map_err(exchange_error)
+    }
+
+    async fn symbol_meta(&mut self, symbol: &str) -> executor::Result<Option<SymbolMeta>> {
+        self.meta(symbol).await.map(Some).map_err(exchange_error)
+    }
+
+    async fn set_protective_orders(
+        &mut self,
+        symbol: &str,
+        side: &str,
+        _quantity: Decimal,
+        stop_loss: f64,
+        take_profit: f64,
+    ) -> executor::Result<()> {
+        let exit_side = if side == "long" {
+            OrderSide::Sell
+        } else {
+            OrderSide::Buy
+        };
+        let meta = self.meta(symbol).await.map_err(exchange_error)?;
+        for (order_type, price) in [
+            (OrderType::StopMarket, stop_loss),
+            (OrderType::TakeProfitMarket, take_profit),
+        ] {
+            if price > 0.0 {
+                let price = meta.round_price(money::from_f64(price));
+                let order = OrderRequest::close_at(symbol, exit_side, order_type, price);
+                self.place_order(&order).await.map_err(exchange_error)?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn cancel_protective_orders(&mut self, symbol: &str) -> executor::Result<()> {
+        let orders = self
+            .get_open_orders(Some(symbol))
+            .await
+            .map_err(exchange_error)?;
+        for order in orders.iter().filter(|o| o.is_protective()) {
+            self.cancel_order(symbol, order.order_id)
+                .await
+                .map_err(exchange_error)?;
+        }
+        Ok(())
+    }
+}
+
+impl Default for ApiClient {
+    fn default() -> Self {
+        Self::new()
+    }
 }
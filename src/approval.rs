@@ -0,0 +1,247 @@
+//! Human-in-the-loop approval of large trades.
+//!
+//! A trader with `approval_threshold_usd` set does not execute entries whose
+//! notional exceeds it. [`hold`] queues them as trade proposals that expire
+//! after `approval_ttl_minutes` and announces each one as a `trade_proposal`
+//! notification. A user approves or rejects them through the API; the trader's
+//! next cycle executes what was approved and records the approver with each
+//! execution.
+
+use chrono::{Duration, Utc};
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::database::{Database, TradeProposal, TraderRecord};
+use crate::decision::Decision;
+use crate::executor::Execution;
+use crate::logger::DecisionRecord;
+use crate::notify::{self, Event};
+
+/// Proposal lifetime when the trader does not set one.
+pub const DEFAULT_TTL_MINUTES: i32 = 30;
+
+#[derive(Error, Debug)]
+pub enum ApprovalError {
+    #[error("Trade proposal {0} not found")]
+    NotFound(String),
+    /// Already decided or expired; carries the current status.
+    #[error("Trade proposal is {0}")]
+    NotPending(String),
+    #[error("Database error: {0}")]
+    Database(#[from] anyhow::Error),
+}
+
+/// Whether `decision` has to wait for a human under the trader's settings.
+pub fn needs_approval(trader: &TraderRecord, decision: &Decision) -> bool {
+    trader.approval_threshold_usd > 0.0
+        && decision.action.is_open()
+        && decision.notional_usd() > trader.approval_threshold_usd
+}
+
+/// Queues the decisions that need approval and returns the rest for immediate
+/// execution. A proposal that cannot be stored is dropped rather than executed.
+pub async fn hold(
+    db: &Database,
+    trader: &TraderRecord,
+    decisions: Vec<Decision>,
+    record: &mut DecisionRecord,
+) -> Vec<Decision> {
+    let (held, now): (Vec<Decision>, Vec<Decision>) = decisions
+        .into_iter()
+        .partition(|d| needs_approval(trader, d));
+    let ttl = if trader.approval_ttl_minutes > 0 {
+        trader.approval_ttl_minutes
+    } else {
+        DEFAULT_TTL_MINUTES
+    };
+
+    for decision in held {
+        let created_at = Utc::now();
+        let proposal = TradeProposal {
+            id: Uuid::new_v4().to_string(),
+            user_id: trader.user_id.clone(),
+            trader_id: trader.id.clone(),
+            symbol: decision.symbol.clone(),
+            action: decision.action.as_str().to_string(),
+            decision: serde_json::to_string(&decision).unwrap_or_default(),
+            notional_usd: decision.notional_usd(),
+            status: "pending".to_string(),
+            created_at,
+            expires_at: created_at + Duration::minutes(ttl as i64),
+            decided_by: String::new(),
+            decided_at: None,
+            result: String::new(),
+        };
+        if let Err(e) = db.create_trade_proposal(&proposal).await {
+            record.log(format!(
+                "❌ {} {} 需要审批但保存提案失败，已放弃: {:#}",
+                proposal.symbol, proposal.action, e
+            ));
+            continue;
+        }
+        record.log(format!(
+            "🕒 {} {} 名义价值 {:.2} USD 超过审批阈值 {:.2} USD，等待审批 (提案 {}，{} 分钟内有效)",
+            proposal.symbol,
+            proposal.action,
+            proposal.notional_usd,
+            trader.approval_threshold_usd,
+            proposal.id,
+            ttl
+        ));
+        notify::notify(
+            Event::alert(
+                "trade_proposal",
+                format!(
+                    "{}: {} {} for {:.2} USD awaits approval until {}",
+                    trader.name,
+                    proposal.action,
+                    proposal.symbol,
+                    proposal.notional_usd,
+                    proposal.expires_at.format("%Y-%m-%d %H:%M UTC")
+                ),
+            )
+            .user(&trader.user_id)
+            .trader(&trader.id)
+            .symbol(&proposal.symbol)
+            .detail("proposal_id", &proposal.id)
+            .detail("action", &proposal.action)
+            .detail("notional_usd", proposal.notional_usd)
+            .detail("expires_at", proposal.expires_at),
+        );
+    }
+    now
+}
+
+/// Approves a pending proposal on behalf of `approver`.
+pub async fn approve(
+    db: &Database,
+    user_id: &str,
+    trader_id: &str,
+    id: &str,
+    approver: &str,
+) -> Result<TradeProposal, ApprovalError> {
+    decide(db, user_id, trader_id, id, "approved", approver).await
+}
+
+/// Rejects a pending proposal on behalf of `approver`.
+pub async fn reject(
+    db: &Database,
+    user_id: &str,
+    trader_id: &str,
+    id: &str,
+    approver: &str,
+) -> Result<TradeProposal, ApprovalError> {
+    decide(db, user_id, trader_id, id, "rejected", approver).await
+}
+
+async fn decide(
+    db: &Database,
+    user_id: &str,
+    trader_id: &str,
+    id: &str,
+    status: &str,
+    approver: &str,
+) -> Result<TradeProposal, ApprovalError> {
+    let decided = db
+        .decide_trade_proposal(user_id, trader_id, id, status, approver, Utc::now())
+        .await?;
+    let proposal = db
+        .get_trade_proposal(user_id, trader_id, id)
+        .await?
+        .ok_or_else(|| ApprovalError::NotFound(id.to_string()))?;
+    if !decided {
+        // Past its expiry but not yet swept by a cycle.
+        let status = if proposal.status == "pending" {
+            "expired".to_string()
+        } else {
+            proposal.status
+        };
+        return Err(ApprovalError::NotPending(status));
+    }
+    tracing::info!(
+        "🧑‍⚖️ 交易提案 {} ({} {}) 已{}，审批人: {}",
+        id,
+        proposal.symbol,
+        proposal.action,
+        if status == "approved" {
+            "批准"
+        } else {
+            "拒绝"
+        },
+        approver
+    );
+    Ok(proposal)
+}
+
+/// Expires overdue proposals and claims the approved ones for execution.
+pub async fn release(
+    db: &Database,
+    trader: &TraderRecord,
+    record: &mut DecisionRecord,
+) -> Vec<(TradeProposal, Decision)> {
+    match db
+        .expire_trade_proposals(&trader.user_id, &trader.id, Utc::now())
+        .await
+    {
+        Ok(0) => {}
+        Ok(n) => record.log(format!("⌛ {} 个交易提案未在有效期内审批，已过期", n)),
+        Err(e) => tracing::warn!("⚠️ 交易提案过期处理失败: {:#}", e),
+    }
+    let approved = match db
+        .get_trade_proposals(&trader.user_id, &trader.id, "approved")
+        .await
+    {
+        Ok(approved) => approved,
+        Err(e) => {
+            tracing::warn!("⚠️ 读取已审批交易提案失败: {:#}", e);
+            return Vec::new();
+        }
+    };
+
+    let mut released = Vec::new();
+    for proposal in approved.into_iter().rev() {
+        let decision = match serde_json::from_str::<Decision>(&proposal.decision) {
+            Ok(decision) => decision,
+            Err(e) => {
+                let _ = db
+                    .transition_trade_proposal(&proposal.id, "approved", "failed", &e.to_string())
+                    .await;
+                continue;
+            }
+        };
+        match db
+            .transition_trade_proposal(&proposal.id, "approved", "executing", "")
+            .await
+        {
+            Ok(true) => {
+                record.log(format!(
+                    "✅ 执行已审批提案 {}: {} {} (审批人: {})",
+                    proposal.id, proposal.symbol, proposal.action, proposal.decided_by
+                ));
+                released.push((proposal, decision));
+            }
+            Ok(false) => {}
+            Err(e) => tracing::warn!("⚠️ 认领交易提案 {} 失败: {:#}", proposal.id, e),
+        }
+    }
+    released
+}
+
+/// Stores how a released proposal's execution went.
+pub async fn finish(db: &Database, proposal: &TradeProposal, executions: &[Execution]) {
+    let errors: Vec<String> = executions
+        .iter()
+        .filter_map(|e| e.result.as_ref().err().map(|e| e.to_string()))
+        .collect();
+    let (status, result) = if errors.is_empty() {
+        ("executed", String::new())
+    } else {
+        ("failed", errors.join("; "))
+    };
+    if let Err(e) = db
+        .transition_trade_proposal(&proposal.id, "executing", status, &result)
+        .await
+    {
+        tracing::warn!("⚠️ 保存交易提案 {} 执行结果失败: {:#}", proposal.id, e);
+    }
+}
@@ -0,0 +1,639 @@
+//! Aster DEX perpetual futures client.
+//!
+//! Aster's futures API mirrors Binance's endpoints and payloads, but signed
+//! requests are authorized with an Ethereum-style signature instead of an
+//! HMAC: the sorted request parameters, the main wallet (`user`), the API
+//! wallet (`signer`) and a microsecond nonce are ABI-encoded, hashed with
+//! keccak256 and signed (EIP-191) with the API wallet's private key.
+
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+use k256::ecdsa::SigningKey;
+use reqwest::Method;
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use sha3::{Digest, Keccak256};
+use thiserror::Error;
+
+use crate::api_client::{self, EndpointClass, client_for, timeout_for};
+use crate::audit::{self, AuditOwner, OrderCall};
+use crate::data::{MarketError, RawMarketData};
+use crate::database::ExchangeConfig;
+use crate::decision::PositionInfo;
+use crate::executor::{self, ExecutorError, OrderFill, TradeExecutor};
+use crate::money::{self, Decimal};
+use crate::rate_limit::{RateLimitError, SendLimited};
+use crate::symbol_meta::{self, SymbolMeta};
+use crate::types::{
+    AccountBalance, ExchangeInfo, Kline, OIData, OrderRequest, OrderResponse, OrderSide, OrderType,
+    PositionRisk, PriceTicker,
+};
+
+/// Production futures API.
+pub const BASE_URL: &str = "https://fapi.asterdex.com";
+const DEFAULT_RECV_WINDOW: Duration = Duration::from_millis(5000);
+
+#[derive(Error, Debug)]
+pub enum AsterError {
+    #[error("Invalid Aster credentials: {0}")]
+    Credentials(String),
+    #[error("Aster API error {code}: {msg}")]
+    Api { code: i64, msg: String },
+    #[error("HTTP error: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error(transparent)]
+    Send(#[from] RateLimitError),
+    #[error("Unexpected response: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Signing failed: {0}")]
+    Signing(String),
+}
+
+#[derive(Deserialize)]
+struct ApiErrorBody {
+    code: i64,
+    msg: String,
+}
+
+pub struct AsterClient {
+    base_url: String,
+    user: [u8; 20],
+    signer: [u8; 20],
+    key: SigningKey,
+    recv_window: Duration,
+    // Attributed to order calls in the execution audit.
+    audit_owner: AuditOwner,
+}
+
+impl AsterClient {
+    /// `user` is the main wallet address, `signer` the API wallet whose
+    /// `private_key` signs requests.
+    pub fn new(user: &str, signer: &str, private_key: &str) -> Result<Self, AsterError> {
+        let user = parse_address(user)?;
+        let signer = parse_address(signer)?;
+        let key = parse_key(private_key)?;
+        if address_of(&key) != signer {
+            return Err(AsterError::Credentials(
+                "private key does not belong to the signer address".to_string(),
+            ));
+        }
+
+        Ok(Self {
+            base_url: BASE_URL.to_string(),
+            user,
+            signer,
+            key,
+            recv_window: DEFAULT_RECV_WINDOW,
+            audit_owner: AuditOwner::default(),
+        })
+    }
+
+    /// A client for an Aster account from the exchanges table.
+    pub fn for_exchange(exchange: &ExchangeConfig) -> Result<Self, AsterError> {
+        Ok(Self::new(
+            &exchange.aster_user,
+            &exchange.aster_signer,
+            &exchange.aster_private_key,
+        )?
+        .with_audit_owner(&exchange.user_id, ""))
+    }
+
+    pub fn with_base_url(mut self, base_url: &str) -> Self {
+        self.base_url = base_url.trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Attributes this client's order calls to a user and trader in the
+    /// execution audit.
+    pub fn with_audit_owner(mut self, user_id: &str, trader_id: &str) -> Self {
+        self.audit_owner = AuditOwner {
+            user_id: user_id.to_string(),
+            trader_id: trader_id.to_string(),
+        };
+        self
+    }
+
+    pub fn with_recv_window(mut self, recv_window: Duration) -> Self {
+        self.recv_window = recv_window;
+        self
+    }
+
+    /// Signature over the JSON of the sorted parameters, as Aster expects it.
+    fn sign(&self, params_json: &str, nonce: u64) -> Result<String, AsterError> {
+        let encoded = abi_encode(params_json, &self.user, &self.signer, nonce);
+        let hash = Keccak256::digest(&encoded);
+
+        // EIP-191 personal message over the 32-byte hash.
+        let mut prefixed = Keccak256::new();
+        prefixed.update(b"\x19Ethereum Signed Message:\n32");
+        prefixed.update(hash);
+        let digest = prefixed.finalize();
+
+        let (signature, recovery_id) = self
+            .key
+            .sign_prehash_recoverable(&digest)
+            .map_err(|e| AsterError::Signing(e.to_string()))?;
+        let mut bytes = signature.to_bytes().to_vec();
+        bytes.push(27 + recovery_id.to_byte());
+        Ok(format!("0x{}", hex::encode(bytes)))
+    }
+
+    async fn signed<T: DeserializeOwned>(
+        &self,
+        method: Method,
+        path: &str,
+        params: &[(&str, String)],
+    ) -> Result<T, AsterError> {
+        let now = chrono::Utc::now();
+        let mut signed: BTreeMap<String, String> = params
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.clone()))
+            .collect();
+        signed.insert(
+            "recvWindow".to_string(),
+            self.recv_window.as_millis().to_string(),
+        );
+        signed.insert("timestamp".to_string(), now.timestamp_millis().to_string());
+
+        let nonce = now.timestamp_micros() as u64;
+        let signature = self.sign(&serde_json::to_string(&signed)?, nonce)?;
+
+        let mut form: Vec<(String, String)> = signed.into_iter().collect();
+        form.push(("nonce".to_string(), nonce.to_string()));
+        form.push(("user".to_string(), address_hex(&self.user)));
+        form.push(("signer".to_string(), address_hex(&self.signer)));
+        form.push(("signature".to_string(), signature));
+
+        let url = format!("{}{}", self.base_url, path);
+        let request = client_for(EndpointClass::Trading)
+            .request(method.clone(), url)
+            .timeout(timeout_for(EndpointClass::Trading));
+        let request = if method == Method::GET || method == Method::DELETE {
+            request.query(&form)
+        } else {
+            request.form(&form)
+        };
+        let started = Instant::now();
+        let sent = async {
+            let resp = request.send_limited().await?;
+            let status = resp.status();
+            Ok::<_, RateLimitError>((status, resp.text().await?))
+        }
+        .await;
+        if audit::is_order_endpoint(path) {
+            let (status, body, error) = match &sent {
+                Ok((status, body)) => (status.as_u16(), body.as_str(), String::new()),
+                Err(e) => (0, "", e.to_string()),
+            };
+            let call = OrderCall {
+                exchange: "aster",
+                method: method.as_str(),
+                endpoint: path,
+                params: &form,
+                status,
+                response: body,
+                error: &error,
+                started,
+            };
+            audit::record(&self.audit_owner, call).await;
+        }
+
+        let (status, body) = sent?;
+        parse_body(status, &body)
+    }
+
+    async fn public<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        params: &[(&str, &str)],
+        class: EndpointClass,
+    ) -> Result<T, AsterError> {
+        let resp = client_for(class)
+            .get(format!("{}{}", self.base_url, path))
+            .timeout(timeout_for(class))
+            .query(params)
+            .send_limited()
+            .await?;
+        decode(resp).await
+    }
+
+    pub async fn get_exchange_info(&self) -> Result<ExchangeInfo, AsterError> {
+        self.public("/fapi/v1/exchangeInfo", &[], EndpointClass::ExchangeInfo)
+            .await
+    }
+
+    pub async fn get_current_price(&self, symbol: &str) -> Result<f64, AsterError> {
+        let ticker: PriceTicker = self
+            .public(
+                "/fapi/v1/ticker/price",
+                &[("symbol", symbol)],
+                EndpointClass::MarketData,
+            )
+            .await?;
+        ticker.price.parse().map_err(|_| AsterError::Api {
+            code: 0,
+            msg: format!("invalid price '{}'", ticker.price),
+        })
+    }
+
+    pub async fn place_order(&self, order: &OrderRequest) -> Result<OrderResponse, AsterError> {
+        self.signed(Method::POST, "/fapi/v3/order", &order.params())
+            .await
+    }
+
+    pub async fn cancel_order(
+        &self,
+        symbol: &str,
+        order_id: i64,
+    ) -> Result<OrderResponse, AsterError> {
+        let params = [
+            ("symbol", symbol.to_string()),
+            ("orderId", order_id.to_string()),
+        ];
+        self.signed(Method::DELETE, "/fapi/v3/order", &params).await
+    }
+
+    pub async fn get_open_orders(
+        &self,
+        symbol: Option<&str>,
+    ) -> Result<Vec<OrderResponse>, AsterError> {
+        let params: Vec<(&str, String)> = symbol
+            .map(|s| ("symbol", s.to_string()))
+            .into_iter()
+            .collect();
+        self.signed(Method::GET, "/fapi/v3/openOrders", &params)
+            .await
+    }
+
+    pub async fn get_balances(&self) -> Result<Vec<AccountBalance>, AsterError> {
+        self.signed(Method::GET, "/fapi/v3/balance", &[]).await
+    }
+
+    pub async fn get_position_risk(
+        &self,
+        symbol: Option<&str>,
+    ) -> Result<Vec<PositionRisk>, AsterError> {
+        let params: Vec<(&str, String)> = symbol
+            .map(|s| ("symbol", s.to_string()))
+            .into_iter()
+            .collect();
+        self.signed(Method::GET, "/fapi/v3/positionRisk", &params)
+            .await
+    }
+
+    pub async fn set_leverage(&self, symbol: &str, leverage: i32) -> Result<(), AsterError> {
+        let params = [
+            ("symbol", symbol.to_string()),
+            ("leverage", leverage.to_string()),
+        ];
+        self.signed::<serde_json::Value>(Method::POST, "/fapi/v3/leverage", &params)
+            .await?;
+        Ok(())
+    }
+
+    // The symbol's order rules, from the cached exchangeInfo.
+    async fn meta(&self, symbol: &str) -> Result<SymbolMeta, AsterError> {
+        symbol_meta::lookup(&self.base_url, symbol, || self.get_exchange_info()).await
+    }
+
+    async fn market(
+        &mut self,
+        symbol: &str,
+        side: OrderSide,
+        quantity: Decimal,
+        reduce_only: bool,
+    ) -> Result<OrderFill, AsterError> {
+        let quantity = self.meta(symbol).await?.round_quantity(quantity);
+        if quantity <= Decimal::ZERO {
+            return Err(AsterError::Api {
+                code: 0,
+                msg: format!("{} quantity rounds to zero", symbol),
+            });
+        }
+        let mut order = OrderRequest::market(symbol, side, quantity);
+        order.reduce_only = reduce_only;
+        let resp = self.place_order(&order).await?;
+
+        Ok(OrderFill {
+            order_id: resp.order_id,
+            symbol: resp.symbol,
+            requested_quantity: quantity,
+            filled_quantity: money::parse(&resp.executed_qty),
+            price: money::parse(&resp.avg_price),
+            fill_latency_ms: 0,
+        })
+    }
+
+    async fn positions(&self) -> Result<Vec<PositionInfo>, AsterError> {
+        Ok(self
+            .get_position_risk(None)
+            .await?
+            .iter()
+            .filter_map(api_client::position_info)
+            .collect())
+    }
+}
+
+fn exchange_error(e: AsterError) -> ExecutorError {
+    ExecutorError::Exchange(e.to_string())
+}
+
+impl TradeExecutor for AsterClient {
+    async fn open_long(
+        &mut self,
+        symbol: &str,
+        quantity: Decimal,
+        _leverage: i32,
+    ) -> executor::Result<OrderFill> {
+        self.market(symbol, OrderSide::Buy, quantity, false)
+            .await
+            .map_err(exchange_error)
+    }
+
+    async fn open_short(
+        &mut self,
+        symbol: &str,
+        quantity: Decimal,
+        _leverage: i32,
+    ) -> executor::Result<OrderFill> {
+        self.market(symbol, OrderSide::Sell, quantity, false)
+            .await
+            .map_err(exchange_error)
+    }
+
+    async fn close(
+        &mut self,
+        symbol: &str,
+        side: &str,
+        quantity: Decimal,
+    ) -> executor::Result<OrderFill> {
+        let held = self
+            .positions()
+            .await
+            .map_err(exchange_error)?
+            .into_iter()
+            .find(|p| p.symbol == symbol && p.side == side)
+            .map_or(Decimal::ZERO, |p| money::from_f64(p.quantity));
+        if held <= Decimal::ZERO {
+            return Err(ExecutorError::NoPosition(
+                symbol.to_string(),
+                side.to_string(),
+            ));
+        }
+        let quantity = if quantity > Decimal::ZERO {
+            quantity.min(held)
+        } else {
+            held
+        };
+        let order_side = if side == "long" {
+            OrderSide::Sell
+        } else {
+            OrderSide::Buy
+        };
+        self.market(symbol, order_side, quantity, true)
+            .await
+            .map_err(exchange_error)
+    }
+
+    async fn set_leverage(&mut self, symbol: &str, leverage: i32) -> executor::Result<()> {
+        AsterClient::set_leverage(self, symbol, leverage)
+            .await
+            .map_err(exchange_error)
+    }
+
+    async fn get_positions(&mut self) -> executor::Result<Vec<PositionInfo>> {
+        self.positions().await.map_err(exchange_error)
+    }
+
+    async fn get_price(&mut self, symbol: &str) -> executor::Result<f64> {
+        self.get_current_price(symbol).await.map_err(exchange_error)
+    }
+
+    async fn symbol_meta(&mut self, symbol: &str) -> executor::Result<Option<SymbolMeta>> {
+        self.meta(symbol).await.map(Some).map_err(exchange_error)
+    }
+
+    async fn set_protective_orders(
+        &mut self,
+        symbol: &str,
+        side: &str,
+        _quantity: Decimal,
+        stop_loss: f64,
+        take_profit: f64,
+    ) -> executor::Result<()> {
+        let exit_side = if side == "long" {
+            OrderSide::Sell
+        } else {
+            OrderSide::Buy
+        };
+        let meta = self.meta(symbol).await.map_err(exchange_error)?;
+        for (order_type, price) in [
+            (OrderType::StopMarket, stop_loss),
+            (OrderType::TakeProfitMarket, take_profit),
+        ] {
+            if price > 0.0 {
+                let price = meta.round_price(money::from_f64(price));
+                let order = OrderRequest::close_at(symbol, exit_side, order_type, price);
+                self.place_order(&order).await.map_err(exchange_error)?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn cancel_protective_orders(&mut self, symbol: &str) -> executor::Result<()> {
+        let orders = self
+            .get_open_orders(Some(symbol))
+            .await
+            .map_err(exchange_error)?;
+        for order in orders.iter().filter(|o| o.is_protective()) {
+            self.cancel_order(symbol, order.order_id)
+                .await
+                .map_err(exchange_error)?;
+        }
+        Ok(())
+    }
+}
+
+// --- Market data ---
+//
+// Public endpoints, so traders on Aster price their decisions off Aster's own
+// candles and mark rather than Binance's, which can diverge on a DEX.
+
+async fn market_get<T: DeserializeOwned>(
+    base_url: &str,
+    path: &str,
+    query: &[(&str, &str)],
+) -> Result<Option<T>, MarketError> {
+    let resp = client_for(EndpointClass::MarketData)
+        .get(format!("{}{}", base_url.trim_end_matches('/'), path))
+        .timeout(timeout_for(EndpointClass::MarketData))
+        .query(query)
+        .send_limited()
+        .await?;
+    if !resp.status().is_success() {
+        return Ok(None);
+    }
+    Ok(Some(resp.json::<T>().await?))
+}
+
+/// The last `limit` candles of `symbol`, oldest first.
+pub async fn klines(
+    base_url: &str,
+    symbol: &str,
+    interval: &str,
+    limit: u16,
+) -> Result<Vec<Kline>, MarketError> {
+    let limit = limit.to_string();
+    let query = [
+        ("symbol", symbol),
+        ("interval", interval),
+        ("limit", &limit),
+    ];
+    market_get(base_url, "/fapi/v1/klines", &query)
+        .await?
+        .ok_or_else(|| MarketError::NotListed(symbol.to_string()))
+}
+
+/// Mark price and last funding rate of `symbol`, if Aster lists it.
+pub async fn mark_price(base_url: &str, symbol: &str) -> Result<Option<(f64, f64)>, MarketError> {
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct PremiumIndex {
+        mark_price: String,
+        last_funding_rate: String,
+    }
+    let index: Option<PremiumIndex> =
+        market_get(base_url, "/fapi/v1/premiumIndex", &[("symbol", symbol)]).await?;
+    index
+        .map(|i| Ok((i.mark_price.parse()?, i.last_funding_rate.parse()?)))
+        .transpose()
+}
+
+async fn open_interest(base_url: &str, symbol: &str) -> Result<Option<OIData>, MarketError> {
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct OpenInterest {
+        open_interest: String,
+    }
+    let oi: Option<OpenInterest> =
+        market_get(base_url, "/fapi/v1/openInterest", &[("symbol", symbol)]).await?;
+    oi.map(|oi| {
+        let latest: f64 = oi.open_interest.parse()?;
+        Ok(OIData {
+            latest,
+            average: latest * 0.999,
+        })
+    })
+    .transpose()
+}
+
+/// Fetches candles, mark price, funding and open interest for `symbol` from
+/// the Aster futures API at `base_url`.
+pub async fn fetch_market_data_from(
+    base_url: &str,
+    symbol: &str,
+) -> Result<RawMarketData, MarketError> {
+    let (klines_3m, klines_4h, mark, open_interest) = tokio::try_join!(
+        klines(base_url, symbol, "3m", 50),
+        klines(base_url, symbol, "4h", 60),
+        mark_price(base_url, symbol),
+        open_interest(base_url, symbol)
+    )?;
+
+    Ok(RawMarketData {
+        klines_3m,
+        klines_4h,
+        open_interest,
+        funding_rate: mark.map(|(_, funding)| funding),
+        mark_price: mark.map(|(price, _)| price),
+    })
+}
+
+/// [`fetch_market_data_from`] against Aster's production API.
+pub async fn fetch_market_data(symbol: &str) -> Result<RawMarketData, MarketError> {
+    fetch_market_data_from(BASE_URL, symbol).await
+}
+
+async fn decode<T: DeserializeOwned>(resp: reqwest::Response) -> Result<T, AsterError> {
+    let status = resp.status();
+    let body = resp.text().await?;
+    parse_body(status, &body)
+}
+
+fn parse_body<T: DeserializeOwned>(
+    status: reqwest::StatusCode,
+    body: &str,
+) -> Result<T, AsterError> {
+    if !status.is_success() {
+        return Err(match serde_json::from_str::<ApiErrorBody>(body) {
+            Ok(e) => AsterError::Api {
+                code: e.code,
+                msg: e.msg,
+            },
+            Err(_) => AsterError::Api {
+                code: status.as_u16() as i64,
+                msg: body.to_string(),
+            },
+        });
+    }
+    Ok(serde_json::from_str(body)?)
+}
+
+fn parse_key(private_key: &str) -> Result<SigningKey, AsterError> {
+    let key_bytes = hex::decode(private_key.trim().trim_start_matches("0x"))
+        .map_err(|_| AsterError::Credentials("private key is not hex".to_string()))?;
+    SigningKey::from_slice(&key_bytes)
+        .map_err(|_| AsterError::Credentials("private key is not a secp256k1 key".to_string()))
+}
+
+/// The `0x` wallet address a hex private key signs for.
+pub fn address_for_key(private_key: &str) -> Result<String, AsterError> {
+    Ok(address_hex(&address_of(&parse_key(private_key)?)))
+}
+
+fn parse_address(address: &str) -> Result<[u8; 20], AsterError> {
+    let bytes = hex::decode(address.trim().trim_start_matches("0x"))
+        .map_err(|_| AsterError::Credentials(format!("'{}' is not a hex address", address)))?;
+    bytes
+        .try_into()
+        .map_err(|_| AsterError::Credentials(format!("'{}' is not a 20-byte address", address)))
+}
+
+fn address_hex(address: &[u8; 20]) -> String {
+    format!("0x{}", hex::encode(address))
+}
+
+/// The Ethereum address of a private key.
+fn address_of(key: &SigningKey) -> [u8; 20] {
+    let point = key.verifying_key().to_encoded_point(false);
+    let hash = Keccak256::digest(&point.as_bytes()[1..]);
+    let mut address = [0u8; 20];
+    address.copy_from_slice(&hash[12..]);
+    address
+}
+
+/// Solidity ABI encoding of `(string, address, address, uint256)`.
+fn abi_encode(message: &str, user: &[u8; 20], signer: &[u8; 20], nonce: u64) -> Vec<u8> {
+    fn word(value: u64) -> [u8; 32] {
+        let mut w = [0u8; 32];
+        w[24..].copy_from_slice(&value.to_be_bytes());
+        w
+    }
+    fn address_word(address: &[u8; 20]) -> [u8; 32] {
+        let mut w = [0u8; 32];
+        w[12..].copy_from_slice(address);
+        w
+    }
+
+    let mut out = Vec::with_capacity(32 * 6 + message.len());
+    // The string is dynamic: its head slot holds the offset of its data.
+    out.extend_from_slice(&word(4 * 32));
+    out.extend_from_slice(&address_word(user));
+    out.extend_from_slice(&address_word(signer));
+    out.extend_from_slice(&word(nonce));
+    out.extend_from_slice(&word(message.len() as u64));
+    out.extend_from_slice(message.as_bytes());
+    out.resize(out.len().div_ceil(32) * 32, 0);
+    out
+}
@@ -0,0 +1,152 @@
+//! Execution audit trail.
+//!
+//! Every order call (placing or cancelling orders) is stored with the raw
+//! request parameters and the exchange's raw response in the `execution_audit`
+//! table, so a dispute like "the bot says filled, the exchange says rejected"
+//! can be settled from what was actually sent and received. Signatures and keys
+//! are stripped before anything is stored. A scheduled job prunes entries past
+//! the retention period or beyond the row limit.
+
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
+
+use chrono::Utc;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::database::{Database, ExecutionAudit};
+
+// Parameter names (lowercase, without separators) that are never stored.
+const SECRET_PARAMS: &[&str] = &[
+    "signature",
+    "apikey",
+    "secretkey",
+    "apisecret",
+    "privatekey",
+];
+// Longer request or response bodies are cut to this many bytes.
+const MAX_PAYLOAD_BYTES: usize = 16 * 1024;
+
+// Where entries go; nothing is recorded until it is set.
+static DATABASE: Lazy<RwLock<Option<Database>>> = Lazy::new(|| RwLock::new(None));
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[serde(default)]
+pub struct AuditParams {
+    /// Entries older than this are deleted.
+    #[serde(with = "humantime_serde")]
+    pub retention: Duration,
+    /// Newest entries kept at most.
+    pub max_rows: i64,
+}
+
+impl Default for AuditParams {
+    fn default() -> Self {
+        Self {
+            retention: Duration::from_secs(90 * 86400),
+            max_rows: 200_000,
+        }
+    }
+}
+
+/// The user and trader an exchange client places orders for.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AuditOwner {
+    pub user_id: String,
+    pub trader_id: String,
+}
+
+/// One order call as it was sent and answered.
+#[derive(Debug)]
+pub struct OrderCall<'a> {
+    pub exchange: &'a str,
+    pub method: &'a str,
+    pub endpoint: &'a str,
+    pub params: &'a [(String, String)],
+    /// HTTP status; 0 when no response arrived.
+    pub status: u16,
+    pub response: &'a str,
+    /// Transport error, if the call failed before a response.
+    pub error: &'a str,
+    pub started: Instant,
+}
+
+/// Sends audit entries to `db` from now on.
+pub fn set_database(db: Database) {
+    *DATABASE.write().unwrap_or_else(|e| e.into_inner()) = Some(db);
+}
+
+/// Whether a request path places or cancels orders and so must be audited.
+pub fn is_order_endpoint(path: &str) -> bool {
+    ["/order", "/batchOrders", "/allOpenOrders"]
+        .iter()
+        .any(|suffix| path.ends_with(suffix))
+}
+
+fn is_secret(name: &str) -> bool {
+    let name: String = name
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+    SECRET_PARAMS.contains(&name.as_str())
+}
+
+fn truncate(s: &str) -> String {
+    if s.len() <= MAX_PAYLOAD_BYTES {
+        return s.to_string();
+    }
+    let mut end = MAX_PAYLOAD_BYTES;
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}…", &s[..end])
+}
+
+/// Request parameters as a JSON object, without signatures or keys.
+pub fn strip_secrets(params: &[(String, String)]) -> String {
+    let map: Map<String, Value> = params
+        .iter()
+        .filter(|(k, _)| !is_secret(k))
+        .map(|(k, v)| (k.clone(), Value::String(v.clone())))
+        .collect();
+    Value::Object(map).to_string()
+}
+
+/// Stores an order call. Failing to store it is logged, never returned: the
+/// order itself has already been sent.
+pub async fn record(owner: &AuditOwner, call: OrderCall<'_>) {
+    let Some(db) = DATABASE.read().unwrap_or_else(|e| e.into_inner()).clone() else {
+        return;
+    };
+    let entry = ExecutionAudit {
+        user_id: owner.user_id.clone(),
+        trader_id: owner.trader_id.clone(),
+        exchange: call.exchange.to_string(),
+        method: call.method.to_string(),
+        endpoint: call.endpoint.to_string(),
+        request: truncate(&strip_secrets(call.params)),
+        status: call.status as i64,
+        response: truncate(call.response),
+        error: call.error.to_string(),
+        latency_ms: call.started.elapsed().as_millis() as i64,
+        created_at: Utc::now(),
+        ..Default::default()
+    };
+    if let Err(e) = db.save_execution_audit(&entry).await {
+        tracing::warn!("⚠️ 保存下单审计记录失败: {:#}", e);
+    }
+}
+
+/// Deletes entries past the retention period or beyond the row limit.
+pub async fn prune(db: &Database, params: &AuditParams) -> anyhow::Result<u64> {
+    let retention = chrono::Duration::from_std(params.retention).unwrap_or_default();
+    let removed = db
+        .prune_execution_audit(Utc::now() - retention, params.max_rows)
+        .await?;
+    if removed > 0 {
+        tracing::info!("🧹 清理了 {} 条下单审计记录", removed);
+    }
+    Ok(removed)
+}
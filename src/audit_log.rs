@@ -0,0 +1,161 @@
+//! Audit log of configuration and trading-state changes.
+//!
+//! Every successful mutating API request is recorded in the `audit_log`
+//! table by [`crate::server`]'s audit middleware: who made it, the route, the
+//! request body as the new value and, where the handler captured it with
+//! [`before`], the value it replaced. Trader starts and stops outside the API
+//! (CLI, runner, pause windows) are recorded with [`record`] directly.
+//! Credentials are redacted from both values before anything is stored; the
+//! raw order trail lives separately in [`crate::audit`].
+
+use std::cell::RefCell;
+
+use chrono::Utc;
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::database::{AuditLogEntry, Database};
+use crate::secrets;
+
+/// A change a user requested, through the HTTP API or a runner handle.
+pub const SOURCE_API: &str = "api";
+/// A change made with the command-line tool.
+pub const SOURCE_CLI: &str = "cli";
+/// A change the engine made on its own, e.g. a pause window starting.
+pub const SOURCE_SYSTEM: &str = "system";
+
+// Field names (lowercase, without separators) whose values are never stored
+// when they contain one of these.
+const SECRET_FIELDS: &[&str] = &["key", "secret", "password", "passphrase", "token"];
+// Field names that are secret as a whole, e.g. one-time codes.
+const SECRET_NAMES: &[&str] = &["code", "otpcode"];
+const REDACTED: &str = "[redacted]";
+// Longer values are cut to this many bytes.
+const MAX_VALUE_BYTES: usize = 16 * 1024;
+
+tokio::task_local! {
+    static BEFORE: RefCell<Option<Value>>;
+}
+
+fn is_secret(name: &str) -> bool {
+    let name: String = name
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+    SECRET_NAMES.contains(&name.as_str()) || SECRET_FIELDS.iter().any(|s| name.contains(s))
+}
+
+/// `value` with every credential replaced by a marker. Empty values and
+/// references to an external secret store are kept, so the log still shows
+/// when a key was cleared or moved to a store.
+pub fn redact(value: Value) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.into_iter()
+                .map(|(k, v)| {
+                    let v = if !is_secret(&k) {
+                        redact(v)
+                    } else {
+                        match v {
+                            Value::Null => Value::Null,
+                            Value::String(s) if s.is_empty() || secrets::is_reference(&s) => {
+                                Value::String(s)
+                            }
+                            _ => Value::String(REDACTED.to_string()),
+                        }
+                    };
+                    (k, v)
+                })
+                .collect::<Map<String, Value>>(),
+        ),
+        Value::Array(items) => Value::Array(items.into_iter().map(redact).collect()),
+        value => value,
+    }
+}
+
+fn stored(value: Option<Value>) -> String {
+    let Some(value) = value else {
+        return String::new();
+    };
+    let text = redact(value).to_string();
+    if text.len() <= MAX_VALUE_BYTES {
+        return text;
+    }
+    let mut end = MAX_VALUE_BYTES;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}…", &text[..end])
+}
+
+/// Runs `f` with room for the handler to capture the value it replaces.
+/// Returns what was captured, if anything.
+pub async fn capture<F: Future>(f: F) -> (F::Output, Option<Value>) {
+    BEFORE
+        .scope(RefCell::new(None), async move {
+            let output = f.await;
+            (output, BEFORE.with(|b| b.borrow_mut().take()))
+        })
+        .await
+}
+
+/// Captures the value a request is about to replace, for its audit entry.
+/// Does nothing outside [`capture`].
+pub fn before(value: &impl Serialize) {
+    let Ok(value) = serde_json::to_value(value) else {
+        return;
+    };
+    let _ = BEFORE.try_with(|b| *b.borrow_mut() = Some(value));
+}
+
+/// Stores one change. Failing to store it is logged, never returned: the
+/// change itself has already been made.
+pub async fn record(
+    db: &Database,
+    user_id: &str,
+    source: &str,
+    action: &str,
+    target: &str,
+    old: Option<Value>,
+    new: Option<Value>,
+) {
+    let entry = AuditLogEntry {
+        id: 0,
+        user_id: user_id.to_string(),
+        source: source.to_string(),
+        action: action.to_string(),
+        target: target.to_string(),
+        old_value: stored(old),
+        new_value: stored(new),
+        created_at: Utc::now(),
+    };
+    if let Err(e) = db.save_audit_log(&entry).await {
+        tracing::warn!("⚠️ 保存变更审计记录失败: {:#}", e);
+    }
+}
+
+/// Records a trader being started or stopped.
+pub async fn trader_status(
+    db: &Database,
+    user_id: &str,
+    source: &str,
+    trader_id: &str,
+    running: bool,
+) {
+    let action = if running {
+        "trader.start"
+    } else {
+        "trader.stop"
+    };
+    record(
+        db,
+        user_id,
+        source,
+        action,
+        &format!("traders/{}", trader_id),
+        Some(serde_json::json!({ "is_running": !running })),
+        Some(serde_json::json!({ "is_running": running })),
+    )
+    .await;
+}
@@ -1,8 +1,9 @@
 use chrono::{Duration, Utc};
 use jsonwebtoken::{DecodingKey, EncodingKey, Header, TokenData, Validation, decode, encode};
 use once_cell::sync::OnceCell;
+use rand::Rng;
 use serde::{Deserialize, Serialize};
-use std::sync::atomic::{AtomicBool, Ordering};
+use sha2::{Digest, Sha256};
 use thiserror::Error;
 use totp_rs::{Algorithm, Secret, TOTP};
 use urlencoding;
@@ -10,12 +11,16 @@ use uuid::Uuid;
 
 // JWT secret, can only be set once.
 static JWT_SECRET: OnceCell<Vec<u8>> = OnceCell::new();
-// Admin mode flag, atomically updatable.
-static ADMIN_MODE: AtomicBool = AtomicBool::new(false);
 
 // OTP Issuer name, a constant.
 pub const OTP_ISSUER: &str = "AITrading";
 
+/// Number of recovery codes issued per OTP enrollment.
+pub const RECOVERY_CODE_COUNT: usize = 10;
+// Unambiguous lowercase characters; a 10-character code carries 50 bits.
+const RECOVERY_CODE_ALPHABET: &[u8] = b"abcdefghjkmnpqrstuvwxyz23456789";
+const RECOVERY_CODE_LEN: usize = 10;
+
 #[derive(Error, Debug)]
 pub enum AuthError {
     #[error("Bcrypt hashing error: {0}")]
@@ -36,14 +41,31 @@ pub fn set_jwt_secret(secret: &str) {
     let _ = JWT_SECRET.set(secret.as_bytes().to_vec());
 }
 
-/// Sets the global admin mode.
-pub fn set_admin_mode(enabled: bool) {
-    ADMIN_MODE.store(enabled, Ordering::Relaxed);
+/// What a user is allowed to do. Admins can manage users, beta codes and
+/// system configuration.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    #[default]
+    User,
+    Admin,
 }
 
-/// Checks if admin mode is currently enabled.
-pub fn is_admin_mode() -> bool {
-    ADMIN_MODE.load(Ordering::Relaxed)
+impl Role {
+    /// Parses a stored role; anything unknown is a plain user.
+    pub fn parse(s: &str) -> Self {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "admin" => Role::Admin,
+            _ => Role::User,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Role::User => "user",
+            Role::Admin => "admin",
+        }
+    }
 }
 
 // --- JWT Claims ---
@@ -52,6 +74,9 @@ pub fn is_admin_mode() -> bool {
 pub struct Claims {
     pub user_id: String,
     pub email: String,
+    /// Tokens issued before roles existed carry none and act as a plain user.
+    #[serde(default)]
+    pub role: Role,
     // Registered claims
     exp: i64,    // Expiration time (as UTC timestamp)
     iat: i64,    // Issued at (as UTC timestamp)
@@ -93,7 +118,9 @@ pub fn generate_otp_secret() -> Result<String, AuthError> {
 
 /// Verifies a TOTP code against a secret.
 pub fn verify_otp(secret: &str, code: &str) -> bool {
-    let secret_bytes = Secret::Encoded(secret.to_string()).to_bytes().unwrap();
+    let Ok(secret_bytes) = Secret::Encoded(secret.to_string()).to_bytes() else {
+        return false;
+    };
 
     let totp_result = TOTP::new(
         Algorithm::SHA1,
@@ -111,14 +138,49 @@ pub fn verify_otp(secret: &str, code: &str) -> bool {
     }
 }
 
-/// Generates a new JWT for a given user.
-pub fn generate_jwt(user_id: &str, email: &str) -> Result<String, AuthError> {
+/// Generates a fresh set of one-time recovery codes, formatted as `xxxxx-xxxxx`.
+pub fn generate_recovery_codes() -> Vec<String> {
+    let mut rng = rand::thread_rng();
+    (0..RECOVERY_CODE_COUNT)
+        .map(|_| {
+            let code: String = (0..RECOVERY_CODE_LEN)
+                .map(|_| {
+                    RECOVERY_CODE_ALPHABET[rng.gen_range(0..RECOVERY_CODE_ALPHABET.len())] as char
+                })
+                .collect();
+            format!(
+                "{}-{}",
+                &code[..RECOVERY_CODE_LEN / 2],
+                &code[RECOVERY_CODE_LEN / 2..]
+            )
+        })
+        .collect()
+}
+
+/// Hashes a recovery code for storage. Case, spaces and dashes are ignored so
+/// a code typed back by hand matches the one that was issued.
+///
+/// The codes are random, so a plain SHA-256 is enough and lets a code be looked
+/// up by its hash.
+pub fn hash_recovery_code(code: &str) -> String {
+    let normalized: String = code
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '-')
+        .flat_map(char::to_lowercase)
+        .collect();
+    hex::encode(Sha256::digest(normalized.as_bytes()))
+}
+
+/// Generates a new JWT for a given user. The role is fixed for the token's
+/// lifetime; a changed role applies from the next login.
+pub fn generate_jwt(user_id: &str, email: &str, role: Role) -> Result<String, AuthError> {
     let now = Utc::now();
     let expiration = now + Duration::hours(24);
 
     let claims = Claims {
         user_id: user_id.to_string(),
         email: email.to_string(),
+        role,
         iat: now.timestamp(),
         nbf: now.timestamp(),
         exp: expiration.timestamp(),
@@ -0,0 +1,471 @@
+//! Shareable strategy files.
+//!
+//! A strategy is everything that makes a trader behave the way it does (prompts,
+//! symbols, leverage, sizing, indicators) without anything tied to an account:
+//! no API keys, no model or exchange ids. Exports are signed with the
+//! installation's Ed25519 key so an importer can tell a file was not altered
+//! after it was shared, and by whom it was signed.
+//!
+//! A valid signature alone proves nothing, since anyone can re-sign an edited
+//! file with their own key. Imports are only accepted from keys the importing
+//! user trusts: this installation's own key, and keys whose fingerprint the
+//! user confirmed on an earlier import.
+//!
+//! A user prompt template the trader uses travels with the strategy and is
+//! recreated for the importing user, renamed when they already have a
+//! different template of that name.
+
+use std::collections::BTreeSet;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use chrono::{DateTime, Utc};
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::approval;
+use crate::database::{Database, PromptTemplate, TraderRecord};
+use crate::indicators::IndicatorSet;
+use crate::prompt;
+use crate::prompt_template::{self, TemplateError};
+use crate::quota;
+
+/// Value of the `format` field of every strategy file.
+pub const STRATEGY_FORMAT: &str = "aitrading.strategy";
+/// Current strategy file version.
+pub const STRATEGY_VERSION: u32 = 1;
+
+// system_config key holding this installation's signing key.
+const SIGNING_KEY_CONFIG: &str = "strategy_signing_key";
+// system_config key prefix for the signer keys each user trusts.
+const TRUSTED_KEYS_CONFIG: &str = "strategy_trusted_keys";
+
+#[derive(Error, Debug)]
+pub enum BundleError {
+    #[error("Unsupported strategy file: format '{0}' version {1}")]
+    UnsupportedFormat(String, u32),
+    #[error("Invalid public key or signature encoding")]
+    Malformed,
+    #[error("Signature verification failed; the file was modified after signing")]
+    InvalidSignature,
+    #[error("Strategy is signed by an untrusted key {0}; confirm its fingerprint to import it")]
+    UntrustedSigner(String),
+    #[error("Invalid prompt template in strategy: {0}")]
+    Template(#[from] TemplateError),
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+/// Approval and margin alert settings of a strategy.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RiskSettings {
+    pub approval_threshold_usd: f64,
+    pub approval_ttl_minutes: i32,
+    pub liquidation_alert_pct: f64,
+    pub margin_ratio_alert_pct: f64,
+    pub auto_deleverage_pct: f64,
+}
+
+impl Default for RiskSettings {
+    fn default() -> Self {
+        Self {
+            approval_threshold_usd: 0.0,
+            approval_ttl_minutes: approval::DEFAULT_TTL_MINUTES,
+            liquidation_alert_pct: 0.0,
+            margin_ratio_alert_pct: 0.0,
+            auto_deleverage_pct: 0.0,
+        }
+    }
+}
+
+impl RiskSettings {
+    fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Trading behaviour of a trader, free of credentials and account references.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Strategy {
+    pub name: String,
+    pub custom_prompt: String,
+    pub override_base_prompt: bool,
+    pub system_prompt_template: String,
+    /// Content of `system_prompt_template` when it is a user template.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompt_template: Option<String>,
+    pub trading_symbols: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub custom_coins: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub quote_assets: Vec<String>,
+    pub btc_eth_leverage: i32,
+    pub altcoin_leverage: i32,
+    pub is_cross_margin: bool,
+    #[serde(default)]
+    pub stop_loss_cooldown_minutes: i32,
+    pub scan_interval_minutes: i32,
+    pub initial_balance: f64,
+    pub use_coin_pool: bool,
+    pub use_oi_top: bool,
+    pub indicators: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub timeframes: Vec<String>,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub position_sizing: String,
+    /// Registered [`strategy`](crate::strategy) that replaces or reviews the AI.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub decision_strategy: String,
+    // New fields are skipped at their defaults so older files still verify.
+    #[serde(default, skip_serializing_if = "RiskSettings::is_default")]
+    pub risk: RiskSettings,
+}
+
+fn split_list(list: &str) -> Vec<String> {
+    list.split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+impl Strategy {
+    pub fn from_trader(trader: &TraderRecord) -> Self {
+        Self {
+            name: trader.name.clone(),
+            custom_prompt: trader.custom_prompt.clone(),
+            override_base_prompt: trader.override_base_prompt,
+            system_prompt_template: trader.system_prompt_template.clone(),
+            prompt_template: None,
+            trading_symbols: split_list(&trader.trading_symbols),
+            custom_coins: split_list(&trader.custom_coins),
+            quote_assets: split_list(&trader.quote_assets),
+            btc_eth_leverage: trader.btc_eth_leverage,
+            altcoin_leverage: trader.altcoin_leverage,
+            is_cross_margin: trader.is_cross_margin,
+            stop_loss_cooldown_minutes: trader.stop_loss_cooldown_minutes,
+            scan_interval_minutes: trader.scan_interval_minutes,
+            initial_balance: trader.initial_balance,
+            use_coin_pool: trader.use_coin_pool,
+            use_oi_top: trader.use_oi_top,
+            indicators: IndicatorSet::NAMES.iter().map(|s| s.to_string()).collect(),
+            timeframes: split_list(&trader.timeframes),
+            position_sizing: trader.position_sizing.clone(),
+            decision_strategy: trader.strategy.clone(),
+            risk: RiskSettings {
+                approval_threshold_usd: trader.approval_threshold_usd,
+                approval_ttl_minutes: trader.approval_ttl_minutes,
+                liquidation_alert_pct: trader.liquidation_alert_pct,
+                margin_ratio_alert_pct: trader.margin_ratio_alert_pct,
+                auto_deleverage_pct: trader.auto_deleverage_pct,
+            },
+        }
+    }
+
+    /// Builds a stopped trader for `user_id` under a fresh id.
+    pub fn to_trader(&self, user_id: &str, ai_model_id: &str, exchange_id: &str) -> TraderRecord {
+        TraderRecord {
+            id: Uuid::new_v4().to_string(),
+            user_id: user_id.to_string(),
+            name: self.name.clone(),
+            ai_model_id: ai_model_id.to_string(),
+            exchange_id: exchange_id.to_string(),
+            initial_balance: self.initial_balance,
+            scan_interval_minutes: self.scan_interval_minutes,
+            is_running: false,
+            btc_eth_leverage: self.btc_eth_leverage,
+            altcoin_leverage: self.altcoin_leverage,
+            trading_symbols: self.trading_symbols.join(","),
+            custom_coins: self.custom_coins.join(","),
+            quote_assets: self.quote_assets.join(","),
+            use_coin_pool: self.use_coin_pool,
+            use_oi_top: self.use_oi_top,
+            custom_prompt: self.custom_prompt.clone(),
+            override_base_prompt: self.override_base_prompt,
+            system_prompt_template: self.system_prompt_template.clone(),
+            is_cross_margin: self.is_cross_margin,
+            stop_loss_cooldown_minutes: self.stop_loss_cooldown_minutes,
+            timeframes: self.timeframes.join(","),
+            position_sizing: self.position_sizing.clone(),
+            strategy: self.decision_strategy.clone(),
+            approval_threshold_usd: self.risk.approval_threshold_usd,
+            approval_ttl_minutes: self.risk.approval_ttl_minutes,
+            liquidation_alert_pct: self.risk.liquidation_alert_pct,
+            margin_ratio_alert_pct: self.risk.margin_ratio_alert_pct,
+            auto_deleverage_pct: self.risk.auto_deleverage_pct,
+            ..Default::default()
+        }
+    }
+}
+
+/// The on-disk strategy file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignedStrategy {
+    pub format: String,
+    pub version: u32,
+    pub exported_at: DateTime<Utc>,
+    pub strategy: Strategy,
+    /// Base64 Ed25519 public key of the signer.
+    pub public_key: String,
+    /// Base64 Ed25519 signature over the fields above.
+    pub signature: String,
+}
+
+// The exact bytes that get signed; field order is fixed by the struct.
+#[derive(Serialize)]
+struct SignedPayload<'a> {
+    format: &'a str,
+    version: u32,
+    exported_at: &'a DateTime<Utc>,
+    strategy: &'a Strategy,
+}
+
+impl SignedStrategy {
+    pub fn sign(strategy: Strategy, key: &SigningKey) -> Result<Self, BundleError> {
+        let exported_at = Utc::now();
+        let payload = serde_json::to_vec(&SignedPayload {
+            format: STRATEGY_FORMAT,
+            version: STRATEGY_VERSION,
+            exported_at: &exported_at,
+            strategy: &strategy,
+        })?;
+
+        Ok(Self {
+            format: STRATEGY_FORMAT.to_string(),
+            version: STRATEGY_VERSION,
+            exported_at,
+            public_key: BASE64.encode(key.verifying_key().as_bytes()),
+            signature: BASE64.encode(key.sign(&payload).to_bytes()),
+            strategy,
+        })
+    }
+
+    /// Checks the format and signature, and that the signer is one of the
+    /// `trusted` base64 public keys, and returns the strategy.
+    pub fn verify(&self, trusted: &BTreeSet<String>) -> Result<&Strategy, BundleError> {
+        if self.format != STRATEGY_FORMAT || self.version > STRATEGY_VERSION {
+            return Err(BundleError::UnsupportedFormat(
+                self.format.clone(),
+                self.version,
+            ));
+        }
+
+        let key_bytes: [u8; 32] = decode_fixed(&self.public_key)?;
+        let sig_bytes: [u8; 64] = decode_fixed(&self.signature)?;
+        let key = VerifyingKey::from_bytes(&key_bytes).map_err(|_| BundleError::Malformed)?;
+
+        let payload = serde_json::to_vec(&SignedPayload {
+            format: &self.format,
+            version: self.version,
+            exported_at: &self.exported_at,
+            strategy: &self.strategy,
+        })?;
+        key.verify(&payload, &Signature::from_bytes(&sig_bytes))
+            .map_err(|_| BundleError::InvalidSignature)?;
+        if !trusted.contains(&self.public_key) {
+            return Err(BundleError::UntrustedSigner(self.signer_fingerprint()));
+        }
+
+        Ok(&self.strategy)
+    }
+
+    /// Short, human-comparable fingerprint of the signer's key.
+    pub fn signer_fingerprint(&self) -> String {
+        let digest = Sha256::digest(self.public_key.as_bytes());
+        digest[..8].iter().map(|b| format!("{:02x}", b)).collect()
+    }
+}
+
+fn decode_fixed<const N: usize>(encoded: &str) -> Result<[u8; N], BundleError> {
+    BASE64
+        .decode(encoded)
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or(BundleError::Malformed)
+}
+
+/// Loads this installation's signing key, generating and storing one on first use.
+pub async fn signing_key(db: &Database) -> anyhow::Result<SigningKey> {
+    if let Ok(stored) = db.get_system_config(SIGNING_KEY_CONFIG).await {
+        let bytes: [u8; 32] = decode_fixed(&stored)?;
+        return Ok(SigningKey::from_bytes(&bytes));
+    }
+
+    let key = SigningKey::generate(&mut rand::rngs::OsRng);
+    db.set_system_config(SIGNING_KEY_CONFIG, &BASE64.encode(key.to_bytes()))
+        .await?;
+    tracing::info!("🔑 Generated strategy signing key");
+    Ok(key)
+}
+
+fn trusted_keys_config(user_id: &str) -> String {
+    format!("{}:{}", TRUSTED_KEYS_CONFIG, user_id)
+}
+
+/// Signer keys `user_id` accepts strategies from: this installation's own key
+/// and the keys the user confirmed.
+pub async fn trusted_keys(db: &Database, user_id: &str) -> anyhow::Result<BTreeSet<String>> {
+    let mut keys: BTreeSet<String> = match db.get_system_config(&trusted_keys_config(user_id)).await
+    {
+        Ok(stored) => serde_json::from_str(&stored)?,
+        Err(_) => BTreeSet::new(),
+    };
+    let own = signing_key(db).await?;
+    keys.insert(BASE64.encode(own.verifying_key().as_bytes()));
+    Ok(keys)
+}
+
+/// Adds a base64 public key to the keys `user_id` trusts.
+pub async fn trust_key(db: &Database, user_id: &str, public_key: &str) -> anyhow::Result<()> {
+    let config = trusted_keys_config(user_id);
+    let mut keys: BTreeSet<String> = match db.get_system_config(&config).await {
+        Ok(stored) => serde_json::from_str(&stored)?,
+        Err(_) => BTreeSet::new(),
+    };
+    if keys.insert(public_key.to_string()) {
+        db.set_system_config(&config, &serde_json::to_string(&keys)?)
+            .await?;
+    }
+    Ok(())
+}
+
+/// Exports one of a user's traders as a signed strategy.
+pub async fn export_trader(
+    db: &Database,
+    user_id: &str,
+    trader_id: &str,
+) -> anyhow::Result<SignedStrategy> {
+    let trader = db
+        .get_trader(user_id, trader_id)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("trader '{}' not found", trader_id))?;
+    let mut strategy = Strategy::from_trader(&trader);
+    if prompt::builtin_template(&trader.system_prompt_template).is_none() {
+        strategy.prompt_template = db
+            .get_prompt_template(user_id, &trader.system_prompt_template)
+            .await?
+            .map(|t| t.content);
+    }
+    let key = signing_key(db).await?;
+
+    Ok(SignedStrategy::sign(strategy, &key)?)
+}
+
+/// A verified strategy turned into a trader for the importing user, not yet
+/// stored.
+#[derive(Debug, Clone)]
+pub struct Import {
+    pub trader: TraderRecord,
+    /// User template the trader refers to that has to be created first.
+    pub template: Option<PromptTemplate>,
+}
+
+impl Import {
+    /// Stores the template, if any, and the trader.
+    pub async fn create(&self, db: &Database) -> anyhow::Result<()> {
+        if let Some(template) = &self.template {
+            db.create_prompt_template(&template.user_id, &template.name, &template.content)
+                .await?;
+        }
+        db.create_trader(&self.trader).await
+    }
+}
+
+/// Verifies a strategy file and builds the stopped trader it describes for
+/// `user_id`. A bundled prompt template reuses the user's template of the same
+/// name and content, or gets a free name with a numeric suffix.
+///
+/// A file from a key the user does not trust yet fails with
+/// [`BundleError::UntrustedSigner`] unless `trust_signer` is that key's
+/// fingerprint, in which case the key is trusted from now on.
+pub async fn prepare_import(
+    db: &Database,
+    bundle: &SignedStrategy,
+    user_id: &str,
+    ai_model_id: &str,
+    exchange_id: &str,
+    trust_signer: Option<&str>,
+) -> anyhow::Result<Import> {
+    let mut trusted = trusted_keys(db, user_id).await?;
+    let confirmed = trust_signer.is_some_and(|f| f.trim() == bundle.signer_fingerprint());
+    if confirmed && !trusted.contains(&bundle.public_key) {
+        // 签名有效后才记住用户确认的密钥
+        trusted.insert(bundle.public_key.clone());
+        bundle.verify(&trusted)?;
+        trust_key(db, user_id, &bundle.public_key).await?;
+        tracing::info!(
+            "🔑 User {} now trusts strategy signer {}",
+            user_id,
+            bundle.signer_fingerprint()
+        );
+    }
+    let strategy = bundle.verify(&trusted)?;
+    let mut trader = strategy.to_trader(user_id, ai_model_id, exchange_id);
+    let Some(content) = &strategy.prompt_template else {
+        return Ok(Import {
+            trader,
+            template: None,
+        });
+    };
+
+    let base = strategy.system_prompt_template.as_str();
+    let mut suffix = 1;
+    loop {
+        let name = match suffix {
+            1 => base.to_string(),
+            n => format!("{}-{}", base, n),
+        };
+        match db.get_prompt_template(user_id, &name).await? {
+            Some(existing) if existing.content == *content => {
+                trader.system_prompt_template = name;
+                return Ok(Import {
+                    trader,
+                    template: None,
+                });
+            }
+            Some(_) => suffix += 1,
+            None => {
+                prompt_template::validate(&name, content).map_err(BundleError::from)?;
+                trader.system_prompt_template = name.clone();
+                return Ok(Import {
+                    trader,
+                    template: Some(PromptTemplate {
+                        user_id: user_id.to_string(),
+                        name,
+                        content: content.clone(),
+                        created_at: None,
+                        updated_at: None,
+                    }),
+                });
+            }
+        }
+    }
+}
+
+/// Verifies a strategy file and recreates it as a new, stopped trader.
+/// Returns the new trader's id.
+pub async fn import_trader(
+    db: &Database,
+    bundle: &SignedStrategy,
+    user_id: &str,
+    ai_model_id: &str,
+    exchange_id: &str,
+    trust_signer: Option<&str>,
+) -> anyhow::Result<String> {
+    let import =
+        prepare_import(db, bundle, user_id, ai_model_id, exchange_id, trust_signer).await?;
+    quota::check_create(db, &import.trader).await?;
+    import.create(db).await?;
+
+    tracing::info!(
+        "📥 Imported strategy '{}' as trader {} (signed by {})",
+        bundle.strategy.name,
+        import.trader.id,
+        bundle.signer_fingerprint()
+    );
+    Ok(import.trader.id)
+}
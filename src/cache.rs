@@ -0,0 +1,205 @@
+use std::collections::{BTreeMap, HashMap};
+use std::hash::Hash;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+/// Thread-safe LRU cache bounded by entry count and per-entry TTL.
+///
+/// Expired entries are dropped lazily on access and eagerly when the cache is full,
+/// so memory stays bounded even when many keys are only touched once.
+pub struct BoundedCache<K, V> {
+    inner: Mutex<Inner<K, V>>,
+}
+
+struct Inner<K, V> {
+    capacity: usize,
+    ttl: Duration,
+    entries: HashMap<K, Entry<V>>,
+    // Access tick -> key; the smallest tick is the least recently used entry.
+    order: BTreeMap<u64, K>,
+    tick: u64,
+    stats: CacheStats,
+}
+
+struct Entry<V> {
+    value: V,
+    expires_at: Instant,
+    tick: u64,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+    pub expirations: u64,
+}
+
+impl CacheStats {
+    pub fn hit_rate(&self) -> f64 {
+        let total = self.hits + self.misses;
+        if total == 0 {
+            0.0
+        } else {
+            self.hits as f64 / total as f64
+        }
+    }
+}
+
+impl<K, V> BoundedCache<K, V>
+where
+    K: Eq + Hash + Clone,
+    V: Clone,
+{
+    pub fn new(capacity: usize, ttl: Duration) -> Self {
+        Self {
+            inner: Mutex::new(Inner {
+                capacity: capacity.max(1),
+                ttl,
+                entries: HashMap::new(),
+                order: BTreeMap::new(),
+                tick: 0,
+                stats: CacheStats::default(),
+            }),
+        }
+    }
+
+    /// Returns a clone of the cached value if present and not expired.
+    pub fn get(&self, key: &K) -> Option<V> {
+        let mut inner = self.lock();
+        let now = Instant::now();
+        inner.tick += 1;
+        let tick = inner.tick;
+
+        let Some(entry) = inner.entries.get_mut(key) else {
+            inner.stats.misses += 1;
+            return None;
+        };
+
+        if entry.expires_at <= now {
+            let old_tick = entry.tick;
+            inner.entries.remove(key);
+            inner.order.remove(&old_tick);
+            inner.stats.expirations += 1;
+            inner.stats.misses += 1;
+            return None;
+        }
+
+        let old_tick = std::mem::replace(&mut entry.tick, tick);
+        let value = entry.value.clone();
+        inner.order.remove(&old_tick);
+        inner.order.insert(tick, key.clone());
+        inner.stats.hits += 1;
+        Some(value)
+    }
+
+    /// Whether an unexpired value is cached; unlike [`get`](Self::get) this
+    /// leaves the recency order and the stats alone.
+    pub fn contains(&self, key: &K) -> bool {
+        let inner = self.lock();
+        inner
+            .entries
+            .get(key)
+            .is_some_and(|e| e.expires_at > Instant::now())
+    }
+
+    /// Inserts a value with the cache's default TTL.
+    pub fn insert(&self, key: K, value: V) {
+        let ttl = self.lock().ttl;
+        self.insert_with_ttl(key, value, ttl);
+    }
+
+    pub fn insert_with_ttl(&self, key: K, value: V, ttl: Duration) {
+        let mut inner = self.lock();
+        let now = Instant::now();
+        inner.tick += 1;
+        let tick = inner.tick;
+
+        if let Some(old) = inner.entries.remove(&key) {
+            inner.order.remove(&old.tick);
+        } else if inner.entries.len() >= inner.capacity {
+            inner.purge_expired(now);
+            while inner.entries.len() >= inner.capacity {
+                let Some((_, lru_key)) = inner.order.pop_first() else {
+                    break;
+                };
+                inner.entries.remove(&lru_key);
+                inner.stats.evictions += 1;
+            }
+        }
+
+        inner.order.insert(tick, key.clone());
+        inner.entries.insert(
+            key,
+            Entry {
+                value,
+                expires_at: now + ttl,
+                tick,
+            },
+        );
+    }
+
+    /// Returns the cached value, or computes, stores and returns it.
+    pub async fn get_or_try_insert<F, Fut, E>(&self, key: K, f: F) -> Result<V, E>
+    where
+        F: FnOnce() -> Fut,
+        Fut: std::future::Future<Output = Result<V, E>>,
+    {
+        if let Some(value) = self.get(&key) {
+            return Ok(value);
+        }
+        let value = f().await?;
+        self.insert(key, value.clone());
+        Ok(value)
+    }
+
+    pub fn remove(&self, key: &K) -> Option<V> {
+        let mut inner = self.lock();
+        let entry = inner.entries.remove(key)?;
+        inner.order.remove(&entry.tick);
+        Some(entry.value)
+    }
+
+    pub fn clear(&self) {
+        let mut inner = self.lock();
+        inner.entries.clear();
+        inner.order.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.lock().entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        self.lock().stats
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Inner<K, V>> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl<K, V> Inner<K, V>
+where
+    K: Eq + Hash + Clone,
+{
+    fn purge_expired(&mut self, now: Instant) {
+        let expired: Vec<(K, u64)> = self
+            .entries
+            .iter()
+            .filter(|(_, e)| e.expires_at <= now)
+            .map(|(k, e)| (k.clone(), e.tick))
+            .collect();
+        for (key, tick) in expired {
+            self.entries.remove(&key);
+            self.order.remove(&tick);
+            self.stats.expirations += 1;
+        }
+    }
+}
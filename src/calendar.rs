@@ -0,0 +1,501 @@
+//! Economic calendar.
+//!
+//! High-impact macro events (CPI, FOMC, payrolls) routinely wreck leveraged
+//! positions, so a configurable ICS or JSON feed is fetched periodically and
+//! its relevant events are kept in memory. Upcoming events are summarized in
+//! the decision prompt ("US CPI (USD) in 42 minutes"), and when blackouts are
+//! enabled each event becomes a pause window for the running traders, applied
+//! by the same `trader_pauses` job as windows users schedule themselves.
+
+use std::sync::RwLock;
+use std::time::Duration;
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use thiserror::Error;
+
+use crate::api_client::{EndpointClass, client_for, timeout_for};
+use crate::database::Database;
+use crate::pause;
+
+/// Prefix of the reason of every pause window created from the calendar.
+pub const BLACKOUT_REASON: &str = "calendar:";
+
+#[derive(Error, Debug)]
+pub enum CalendarError {
+    #[error("HTTP error: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("Calendar feed returned HTTP {0}")]
+    Status(u16),
+    #[error("Invalid calendar feed: {0}")]
+    Parse(String),
+    #[error("Database error: {0}")]
+    Database(#[from] anyhow::Error),
+}
+
+pub type Result<T> = std::result::Result<T, CalendarError>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Impact {
+    Low,
+    Medium,
+    High,
+}
+
+impl Impact {
+    fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "high" | "3" => Some(Impact::High),
+            "medium" | "moderate" | "2" => Some(Impact::Medium),
+            "low" | "1" => Some(Impact::Low),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FeedFormat {
+    /// Decided by the body: JSON if it starts with `[` or `{`, otherwise ICS.
+    #[default]
+    Auto,
+    Ics,
+    Json,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct CalendarParams {
+    /// Feed URL; empty disables the calendar.
+    pub url: String,
+    pub format: FeedFormat,
+    /// Events below this impact are ignored.
+    pub min_impact: Impact,
+    /// Currencies whose events matter, e.g. `["USD"]`; empty keeps all.
+    pub currencies: Vec<String>,
+    /// How far ahead events are shown in the prompt.
+    #[serde(with = "humantime_serde")]
+    pub lookahead: Duration,
+    /// Pause running traders around each event.
+    pub blackout: bool,
+    #[serde(with = "humantime_serde")]
+    pub blackout_before: Duration,
+    #[serde(with = "humantime_serde")]
+    pub blackout_after: Duration,
+}
+
+impl Default for CalendarParams {
+    fn default() -> Self {
+        Self {
+            url: String::new(),
+            format: FeedFormat::Auto,
+            min_impact: Impact::High,
+            currencies: vec!["USD".to_string()],
+            lookahead: Duration::from_secs(12 * 3600),
+            blackout: false,
+            blackout_before: Duration::from_secs(15 * 60),
+            blackout_after: Duration::from_secs(30 * 60),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Event {
+    pub title: String,
+    /// Currency the event moves, e.g. "USD"; empty if the feed does not say.
+    pub currency: String,
+    pub impact: Impact,
+    pub time: DateTime<Utc>,
+}
+
+#[derive(Default)]
+struct State {
+    params: CalendarParams,
+    events: Vec<Event>,
+}
+
+static STATE: Lazy<RwLock<State>> = Lazy::new(|| RwLock::new(State::default()));
+
+pub fn set_params(params: CalendarParams) {
+    STATE.write().unwrap_or_else(|e| e.into_inner()).params = params;
+}
+
+pub fn params() -> CalendarParams {
+    STATE
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .params
+        .clone()
+}
+
+/// Replaces the known events, keeping them sorted by time.
+pub fn set_events(mut events: Vec<Event>) {
+    events.sort_by_key(|e| e.time);
+    STATE.write().unwrap_or_else(|e| e.into_inner()).events = events;
+}
+
+/// Known events between `from` and `to`.
+pub fn events_between(from: DateTime<Utc>, to: DateTime<Utc>) -> Vec<Event> {
+    STATE
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .events
+        .iter()
+        .filter(|e| e.time >= from && e.time <= to)
+        .cloned()
+        .collect()
+}
+
+/// Parses a feed body in the given format.
+pub fn parse(body: &str, format: FeedFormat) -> Result<Vec<Event>> {
+    let json = match format {
+        FeedFormat::Json => true,
+        FeedFormat::Ics => false,
+        FeedFormat::Auto => body.trim_start().starts_with(['[', '{']),
+    };
+    if json {
+        parse_json(body)
+    } else {
+        parse_ics(body)
+    }
+}
+
+/// Parses a JSON feed: an array of events (or an object with an `events`
+/// array) with a title, an RFC 3339 `date`, and optional `impact` and
+/// `country`/`currency`, as published by the common forex calendars.
+pub fn parse_json(body: &str) -> Result<Vec<Event>> {
+    let value: Value =
+        serde_json::from_str(body).map_err(|e| CalendarError::Parse(e.to_string()))?;
+    let items = match &value {
+        Value::Array(items) => items,
+        Value::Object(o) => o
+            .get("events")
+            .and_then(Value::as_array)
+            .ok_or_else(|| CalendarError::Parse("no events array".to_string()))?,
+        _ => {
+            return Err(CalendarError::Parse(
+                "expected an array of events".to_string(),
+            ));
+        }
+    };
+    let text = |item: &Value, keys: &[&str]| {
+        keys.iter()
+            .find_map(|k| item.get(*k).and_then(Value::as_str))
+            .unwrap_or_default()
+            .trim()
+            .to_string()
+    };
+
+    let mut events = Vec::new();
+    for item in items {
+        let title = text(item, &["title", "event", "name"]);
+        let date = text(item, &["date", "time", "datetime"]);
+        let Ok(time) = DateTime::parse_from_rfc3339(&date) else {
+            continue;
+        };
+        if title.is_empty() {
+            continue;
+        }
+        events.push(Event {
+            title,
+            currency: text(item, &["currency", "country"]).to_uppercase(),
+            impact: Impact::parse(&text(item, &["impact"])).unwrap_or(Impact::High),
+            time: time.with_timezone(&Utc),
+        });
+    }
+    Ok(events)
+}
+
+/// Parses an iCalendar feed. The impact comes from `X-IMPACT` or, failing
+/// that, `PRIORITY` (1-4 high, 5 medium, 6-9 low); events without either are
+/// treated as high impact. The currency comes from `X-CURRENCY` or the first
+/// `CATEGORIES` entry. All-day events start at midnight UTC.
+pub fn parse_ics(body: &str) -> Result<Vec<Event>> {
+    if !body.contains("BEGIN:VCALENDAR") {
+        return Err(CalendarError::Parse("not an iCalendar feed".to_string()));
+    }
+    // Long lines are folded onto continuation lines starting with a space or tab.
+    let mut lines: Vec<String> = Vec::new();
+    for line in body.lines() {
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(rest), Some(last)) => last.push_str(rest),
+            _ => lines.push(line.trim_end().to_string()),
+        }
+    }
+
+    let mut events = Vec::new();
+    let mut current: Option<IcsEvent> = None;
+    for line in &lines {
+        match line.as_str() {
+            "BEGIN:VEVENT" => current = Some(IcsEvent::default()),
+            "END:VEVENT" => {
+                if let Some(event) = current.take().and_then(IcsEvent::finish) {
+                    events.push(event);
+                }
+            }
+            _ => {
+                let (Some(event), Some((name, value))) = (current.as_mut(), line.split_once(':'))
+                else {
+                    continue;
+                };
+                let (name, params) = name.split_once(';').unwrap_or((name, ""));
+                let value = unescape(value);
+                match name.to_uppercase().as_str() {
+                    "SUMMARY" => event.title = value,
+                    "DTSTART" => event.time = ics_time(&value, params),
+                    "X-IMPACT" => event.impact = Impact::parse(&value),
+                    "PRIORITY" if event.impact.is_none() => {
+                        event.priority = value.trim().parse().ok();
+                    }
+                    "X-CURRENCY" => event.currency = value.trim().to_uppercase(),
+                    "CATEGORIES" if event.currency.is_empty() => {
+                        event.currency = value
+                            .split(',')
+                            .next()
+                            .unwrap_or_default()
+                            .trim()
+                            .to_uppercase();
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+    Ok(events)
+}
+
+#[derive(Default)]
+struct IcsEvent {
+    title: String,
+    currency: String,
+    impact: Option<Impact>,
+    priority: Option<u8>,
+    time: Option<DateTime<Utc>>,
+}
+
+impl IcsEvent {
+    fn finish(self) -> Option<Event> {
+        let impact = self.impact.unwrap_or(match self.priority {
+            Some(5) => Impact::Medium,
+            Some(6..=9) => Impact::Low,
+            _ => Impact::High,
+        });
+        Some(Event {
+            title: self.title,
+            currency: self.currency,
+            impact,
+            time: self.time?,
+        })
+    }
+}
+
+fn unescape(value: &str) -> String {
+    value
+        .replace("\\n", " ")
+        .replace("\\,", ",")
+        .replace("\\;", ";")
+        .replace("\\\\", "\\")
+        .trim()
+        .to_string()
+}
+
+// `20240111T133000Z`, `20240111T083000` with a `TZID=` parameter (UTC without
+// one), or an all-day `20240111`.
+fn ics_time(value: &str, params: &str) -> Option<DateTime<Utc>> {
+    let value = value.trim();
+    if let Some(utc) = value.strip_suffix('Z') {
+        let naive = NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S").ok()?;
+        return Some(naive.and_utc());
+    }
+    let naive = match NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S") {
+        Ok(naive) => naive,
+        Err(_) => NaiveDate::parse_from_str(value, "%Y%m%d")
+            .ok()?
+            .and_hms_opt(0, 0, 0)?,
+    };
+    let tz = params
+        .split(';')
+        .find_map(|p| p.strip_prefix("TZID="))
+        .and_then(|name| name.trim_matches('"').parse::<Tz>().ok());
+    match tz {
+        Some(tz) => tz
+            .from_local_datetime(&naive)
+            .earliest()
+            .map(|t| t.with_timezone(&Utc)),
+        None => Some(naive.and_utc()),
+    }
+}
+
+/// Whether an event passes the impact and currency filters.
+pub fn is_relevant(event: &Event, params: &CalendarParams) -> bool {
+    event.impact >= params.min_impact
+        && (params.currencies.is_empty()
+            || event.currency.is_empty()
+            || params
+                .currencies
+                .iter()
+                .any(|c| c.eq_ignore_ascii_case(&event.currency)))
+}
+
+/// Fetches the feed and replaces the known events with its relevant ones.
+/// Returns how many were kept.
+pub async fn refresh(params: &CalendarParams) -> Result<usize> {
+    let resp = client_for(EndpointClass::MarketData)
+        .get(&params.url)
+        .timeout(timeout_for(EndpointClass::MarketData))
+        .send()
+        .await?;
+    let status = resp.status();
+    if !status.is_success() {
+        return Err(CalendarError::Status(status.as_u16()));
+    }
+    let events: Vec<Event> = parse(&resp.text().await?, params.format)?
+        .into_iter()
+        .filter(|e| is_relevant(e, params))
+        .collect();
+    let kept = events.len();
+    set_events(events);
+    tracing::info!("📅 经济日历已更新，{} 个重要事件", kept);
+    Ok(kept)
+}
+
+fn describe(event: &Event) -> String {
+    if event.currency.is_empty() {
+        event.title.clone()
+    } else {
+        format!("{} ({})", event.title, event.currency)
+    }
+}
+
+fn humanize(minutes: i64) -> String {
+    if minutes < 60 {
+        format!("{} minutes", minutes)
+    } else {
+        format!("{}h{:02}m", minutes / 60, minutes % 60)
+    }
+}
+
+/// Prompt lines for events from the last blackout-after period up to the
+/// lookahead, e.g. "US CPI (USD) in 42 minutes". `None` when there are none.
+pub fn prompt_annotation(now: DateTime<Utc>) -> Option<String> {
+    let params = params();
+    let after = chrono::Duration::from_std(params.blackout_after).unwrap_or_default();
+    let ahead = chrono::Duration::from_std(params.lookahead).unwrap_or_default();
+    let events = events_between(now - after, now + ahead);
+    if events.is_empty() {
+        return None;
+    }
+    let mut lines = vec![
+        "High-impact economic events (expect volatility; prefer smaller size or waiting):"
+            .to_string(),
+    ];
+    for event in &events {
+        let minutes = (event.time - now).num_minutes();
+        let when = if minutes >= 0 {
+            format!("in {}", humanize(minutes))
+        } else {
+            format!("released {} ago", humanize(-minutes))
+        };
+        lines.push(format!("- {} {}", describe(event), when));
+    }
+    Some(lines.join("\n"))
+}
+
+/// A span to pause trading in, covering one or more overlapping events.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Blackout {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub reason: String,
+}
+
+/// Blackouts around `events`, with overlapping ones merged.
+pub fn blackouts(events: &[Event], before: Duration, after: Duration) -> Vec<Blackout> {
+    let before = chrono::Duration::from_std(before).unwrap_or_default();
+    let after = chrono::Duration::from_std(after).unwrap_or_default();
+    let mut sorted: Vec<&Event> = events.iter().collect();
+    sorted.sort_by_key(|e| e.time);
+
+    let mut spans: Vec<Blackout> = Vec::new();
+    for event in sorted {
+        let (start, end) = (event.time - before, event.time + after);
+        match spans.last_mut() {
+            Some(last) if start <= last.end => {
+                last.end = last.end.max(end);
+                last.reason.push_str(", ");
+                last.reason.push_str(&describe(event));
+            }
+            _ => spans.push(Blackout {
+                start,
+                end,
+                reason: format!("{} {}", BLACKOUT_REASON, describe(event)),
+            }),
+        }
+    }
+    spans
+}
+
+/// Schedules a pause window for every running trader over each upcoming
+/// blackout within the lookahead, skipping ones already scheduled. Returns
+/// how many windows were created.
+pub async fn schedule_blackouts(
+    db: &Database,
+    params: &CalendarParams,
+    now: DateTime<Utc>,
+) -> Result<usize> {
+    if !params.blackout {
+        return Ok(0);
+    }
+    let ahead = chrono::Duration::from_std(params.lookahead).unwrap_or_default();
+    let upcoming: Vec<Blackout> = blackouts(
+        &events_between(now, now + ahead),
+        params.blackout_before,
+        params.blackout_after,
+    )
+    .into_iter()
+    .filter(|b| b.end > now)
+    .collect();
+    if upcoming.is_empty() {
+        return Ok(0);
+    }
+
+    let mut created = 0;
+    for user_id in db.get_all_users_id().await? {
+        for trader in db.get_traders(&user_id).await? {
+            if !trader.is_running {
+                continue;
+            }
+            let existing = db.get_pause_windows(&user_id, &trader.id).await?;
+            for blackout in &upcoming {
+                let scheduled = existing.iter().any(|w| {
+                    w.reason.starts_with(BLACKOUT_REASON)
+                        && w.pause_at <= blackout.start
+                        && w.resume_at.is_some_and(|r| r >= blackout.start)
+                });
+                if scheduled {
+                    continue;
+                }
+                match pause::schedule(
+                    db,
+                    &user_id,
+                    &trader.id,
+                    blackout.start.max(now),
+                    Some(blackout.end),
+                    &blackout.reason,
+                )
+                .await
+                {
+                    Ok(_) => created += 1,
+                    Err(e) => {
+                        tracing::warn!("⚠️ 交易员 {} 的经济事件暂停窗口创建失败: {}", trader.id, e)
+                    }
+                }
+            }
+        }
+    }
+    Ok(created)
+}
@@ -0,0 +1,763 @@
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use anyhow::{Context, Result, bail};
+use chrono::{DateTime, Utc};
+use clap::{Args, Parser, Subcommand};
+use rand::Rng;
+use uuid::Uuid;
+
+use aitrading::ai;
+use aitrading::audit_log;
+use aitrading::auth;
+use aitrading::bundle::{self, SignedStrategy};
+use aitrading::config::{self, ConfigKey};
+use aitrading::database::{Database, User};
+use aitrading::decision::Decision;
+use aitrading::export;
+use aitrading::logger::{DecisionLogger, RecordCipher};
+use aitrading::maintenance;
+use aitrading::quota;
+use aitrading::replay;
+use aitrading::stress;
+use aitrading::timezone;
+use aitrading::tournament;
+
+// Beta code alphabet without look-alike characters (0/O, 1/I/L).
+const BETA_CODE_CHARSET: &[u8] = b"ABCDEFGHJKMNPQRSTUVWXYZ23456789";
+const BETA_CODE_LEN: usize = 8;
+// Same minimum as registration through the API.
+const MIN_PASSWORD_LEN: usize = 8;
+
+/// AI trading system server and administration tool.
+#[derive(Parser, Debug)]
+#[command(version, about)]
+pub struct Cli {
+    /// Path to the JSON, TOML or YAML configuration file.
+    #[arg(long, global = true, default_value = "config.json")]
+    pub config: String,
+
+    /// Key file for an encrypted configuration file. Defaults to
+    /// `AITRADING_CONFIG_KEY_FILE`, then `AITRADING_CONFIG_PASSPHRASE`, then a prompt.
+    #[arg(long, global = true)]
+    pub config_key_file: Option<PathBuf>,
+
+    /// Path to the SQLite database, or a `postgres://` connection URL.
+    #[arg(long, global = true, default_value = "config.db")]
+    pub db: String,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Run the trading server (default when no subcommand is given).
+    Run,
+    /// Manage user accounts.
+    #[command(subcommand)]
+    Users(UsersCommand),
+    /// Inspect and toggle traders.
+    #[command(subcommand)]
+    Trader(TraderCommand),
+    /// Manage beta invitation codes.
+    #[command(subcommand)]
+    Betacode(BetacodeCommand),
+    /// Write a consistent copy of the database to a new file.
+    Backup {
+        /// Destination file; must not exist yet.
+        #[arg(long, short)]
+        output: String,
+    },
+    /// Read decision logs.
+    #[command(subcommand)]
+    Decisions(DecisionsCommand),
+    /// Replay recorded market snapshots through AI models.
+    #[command(subcommand)]
+    Backtest(BacktestCommand),
+    /// Inspect background jobs.
+    #[command(subcommand)]
+    Jobs(JobsCommand),
+    /// Config file utilities.
+    #[command(subcommand)]
+    Config(ConfigCommand),
+    /// Pause trading and make the API read-only, e.g. around backups and upgrades.
+    /// A running server picks up the change within 30 seconds.
+    #[command(subcommand)]
+    Maintenance(MaintenanceCommand),
+}
+
+#[derive(Subcommand, Debug)]
+pub enum UsersCommand {
+    /// Create a new user; the password is prompted for.
+    Create {
+        #[arg(long)]
+        email: String,
+        /// Language for messages and notifications ("en" or "zh").
+        #[arg(long, default_value = "en")]
+        locale: String,
+        /// IANA timezone used for daily boundaries and reports.
+        #[arg(long, default_value = "UTC")]
+        timezone: String,
+    },
+    /// Replace a user's password; the new one is prompted for.
+    ResetPassword {
+        #[arg(long)]
+        email: String,
+    },
+    /// Cap the combined margin usage of all of a user's traders.
+    SetMarginCeiling {
+        #[arg(long)]
+        email: String,
+        /// Percent of account equity; 0 disables the cap.
+        #[arg(long)]
+        pct: f64,
+    },
+    /// Write all of a user's data to a new directory of JSON and CSV files.
+    Export {
+        #[arg(long)]
+        email: String,
+        /// Destination directory; must not exist yet.
+        #[arg(long, short)]
+        output: PathBuf,
+        /// Master key file, to decrypt decision records written with
+        /// `decision_log_key_file` set.
+        #[arg(long)]
+        key_file: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum TraderCommand {
+    /// List a user's traders.
+    List {
+        #[arg(long, default_value = "default")]
+        user: String,
+    },
+    /// Mark a trader as running.
+    Start {
+        id: String,
+        #[arg(long, default_value = "default")]
+        user: String,
+    },
+    /// Mark a trader as stopped.
+    Stop {
+        id: String,
+        #[arg(long, default_value = "default")]
+        user: String,
+    },
+    /// Export a trader's strategy (no credentials) to a signed JSON file.
+    Export {
+        id: String,
+        #[arg(long, default_value = "default")]
+        user: String,
+        #[arg(long, short)]
+        output: String,
+    },
+    /// Write a trader's decisions, trades or equity snapshots to a CSV or
+    /// Parquet file.
+    History {
+        id: String,
+        #[arg(long, default_value = "default")]
+        user: String,
+        /// decisions, trades or equity.
+        #[arg(long)]
+        dataset: export::Dataset,
+        /// csv, or parquet when built with the `parquet` feature.
+        #[arg(long, default_value = "csv")]
+        format: export::FileFormat,
+        /// Only rows at or after this RFC 3339 time.
+        #[arg(long)]
+        since: Option<DateTime<Utc>>,
+        /// Only rows at or before this RFC 3339 time.
+        #[arg(long)]
+        until: Option<DateTime<Utc>>,
+        #[arg(long, short)]
+        output: PathBuf,
+        /// Master key file, for decision records written with
+        /// `decision_log_key_file` set.
+        #[arg(long)]
+        key_file: Option<PathBuf>,
+    },
+    /// Attach a WASM decision filter to a trader.
+    SetFilter {
+        id: String,
+        #[arg(long, default_value = "default")]
+        user: String,
+        /// Compiled filter module (.wasm).
+        #[arg(long)]
+        file: PathBuf,
+    },
+    /// Replay the trader's open positions through crash scenarios.
+    Stress {
+        id: String,
+        #[arg(long, default_value = "default")]
+        user: String,
+        /// Re-lever BTC/ETH positions to this leverage first.
+        #[arg(long)]
+        btc_eth_leverage: Option<i32>,
+        /// Re-lever altcoin positions to this leverage first.
+        #[arg(long)]
+        altcoin_leverage: Option<i32>,
+        /// Master key file, for decision records written with
+        /// `decision_log_key_file` set.
+        #[arg(long)]
+        key_file: Option<PathBuf>,
+    },
+    /// Re-run a stored decision with the trader's current prompt template
+    /// and print how the decisions differ.
+    Replay {
+        id: String,
+        #[arg(long, default_value = "default")]
+        user: String,
+        /// Decision record to replay.
+        #[arg(long)]
+        record: String,
+        /// Replay with the model response in this file instead of calling
+        /// the trader's model.
+        #[arg(long)]
+        response_file: Option<PathBuf>,
+        /// Master key file, for decision records written with
+        /// `decision_log_key_file` set.
+        #[arg(long)]
+        key_file: Option<PathBuf>,
+    },
+    /// Remove a trader's decision filter.
+    ClearFilter {
+        id: String,
+        #[arg(long, default_value = "default")]
+        user: String,
+    },
+    /// Recreate a trader from a signed strategy file.
+    Import {
+        file: String,
+        #[arg(long, default_value = "default")]
+        user: String,
+        /// AI model the new trader should use.
+        #[arg(long)]
+        ai_model: String,
+        /// Exchange the new trader should trade on.
+        #[arg(long)]
+        exchange: String,
+        /// Fingerprint of a signer not trusted yet, confirmed with its author.
+        #[arg(long)]
+        trust_signer: Option<String>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum BetacodeCommand {
+    /// Generate new codes, store them and print them.
+    Generate {
+        #[arg(long, short, default_value_t = 10)]
+        count: usize,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum DecisionsCommand {
+    /// Print the most recent decision records as JSON.
+    Tail {
+        #[arg(long, default_value = "decision_logs")]
+        dir: String,
+        #[arg(long, short, default_value_t = 10)]
+        n: usize,
+        /// Master key file, for logs written with `decision_log_key_file` set.
+        #[arg(long, requires = "user")]
+        key_file: Option<PathBuf>,
+        /// Owner of the logs; selects the per-user decryption key.
+        #[arg(long)]
+        user: Option<String>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum BacktestCommand {
+    /// Replay a user's settled evaluation scenarios through their models in
+    /// dry run and print how each would have scored. Nothing is stored and
+    /// no order is placed.
+    Run {
+        #[arg(long, default_value = "default")]
+        user: String,
+        /// Model id to test; repeat for several. Defaults to every enabled model.
+        #[arg(long = "model")]
+        models: Vec<String>,
+        /// Most recent settled scenarios to replay.
+        #[arg(long, default_value_t = 50)]
+        scenarios: usize,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum JobsCommand {
+    /// Show job definitions and their last run.
+    List,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum MaintenanceCommand {
+    /// Turn maintenance mode on.
+    On {
+        #[arg(long, default_value = "maintenance")]
+        reason: String,
+        /// Resume automatically after this long, e.g. "30m".
+        #[arg(long = "for", value_parser = humantime_serde::re::humantime::parse_duration)]
+        duration: Option<Duration>,
+    },
+    /// Turn maintenance mode off.
+    Off,
+    /// Show whether maintenance mode is on.
+    Status,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ConfigCommand {
+    /// Encrypt a plaintext config file.
+    Encrypt {
+        #[arg(long)]
+        input: String,
+        #[arg(long)]
+        output: String,
+        #[command(flatten)]
+        key: KeyArgs,
+    },
+}
+
+#[derive(Args, Debug)]
+pub struct KeyArgs {
+    /// Read the key material from this file instead of prompting.
+    #[arg(long, conflicts_with = "passphrase")]
+    key_file: Option<PathBuf>,
+    /// Use this passphrase instead of prompting.
+    #[arg(long)]
+    passphrase: Option<String>,
+}
+
+impl KeyArgs {
+    fn into_key(self) -> ConfigKey {
+        match (self.key_file, self.passphrase) {
+            (Some(path), _) => ConfigKey::KeyFile(path),
+            (None, Some(passphrase)) => ConfigKey::Passphrase(passphrase),
+            (None, None) => ConfigKey::Prompt,
+        }
+    }
+}
+
+/// Executes an administrative subcommand against the database at `db_path`.
+pub async fn run(command: Command, db_path: &str) -> Result<()> {
+    match command {
+        Command::Run => unreachable!("handled by main"),
+        Command::Config(ConfigCommand::Encrypt { input, output, key }) => {
+            config::encrypt_config_file(&input, &output, &key.into_key())?;
+            println!("Encrypted {} -> {}", input, output);
+        }
+        Command::Decisions(DecisionsCommand::Tail {
+            dir,
+            n,
+            key_file,
+            user,
+        }) => {
+            let mut logger = DecisionLogger::new(&dir);
+            if let (Some(key_file), Some(user)) = (key_file, user) {
+                let secret = ConfigKey::KeyFile(key_file).secret()?;
+                logger = logger.with_key(RecordCipher::from_secret(&secret).user_key(&user));
+            }
+            let records = logger
+                .get_latest_records(n)
+                .map_err(|e| anyhow::anyhow!("{}", e))?;
+            for record in records {
+                println!("{}", serde_json::to_string(&record)?);
+            }
+        }
+        command => {
+            let db = Database::new(db_path).await?;
+            let result = run_db_command(command, &db).await;
+            db.close().await?;
+            result?;
+        }
+    }
+    Ok(())
+}
+
+async fn run_db_command(command: Command, db: &Database) -> Result<()> {
+    match command {
+        Command::Users(UsersCommand::Create {
+            email,
+            locale,
+            timezone,
+        }) => {
+            timezone::parse_tz(&timezone)?;
+            if db.get_user_by_email(&email).await?.is_some() {
+                bail!("user {} already exists", email);
+            }
+            let password = prompt_new_password()?;
+            let user = User {
+                id: Uuid::new_v4().to_string(),
+                email,
+                password_hash: auth::hash_password(&password)?,
+                locale,
+                timezone,
+                ..Default::default()
+            };
+            db.create_user(&user).await?;
+            println!("Created user {} ({})", user.email, user.id);
+        }
+        Command::Users(UsersCommand::ResetPassword { email }) => {
+            let user = db
+                .get_user_by_email(&email)
+                .await?
+                .with_context(|| format!("user {} not found", email))?;
+            let password = prompt_new_password()?;
+            db.update_user_password(&user.id, &auth::hash_password(&password)?)
+                .await?;
+            println!("Password reset for {}", email);
+        }
+        Command::Users(UsersCommand::SetMarginCeiling { email, pct }) => {
+            let user = db
+                .get_user_by_email(&email)
+                .await?
+                .with_context(|| format!("user {} not found", email))?;
+            db.update_user_margin_ceiling(&user.id, pct).await?;
+            println!("Margin ceiling for {} set to {}%", email, pct);
+        }
+        Command::Users(UsersCommand::Export {
+            email,
+            output,
+            key_file,
+        }) => {
+            let user = db
+                .get_user_by_email(&email)
+                .await?
+                .with_context(|| format!("user {} not found", email))?;
+            let key = match key_file {
+                Some(path) => {
+                    let secret = ConfigKey::KeyFile(path).secret()?;
+                    Some(RecordCipher::from_secret(&secret).user_key(&user.id))
+                }
+                None => None,
+            };
+            let takeout = export::collect(db, &user.id, key.as_ref()).await?;
+            let files = export::write_dir(&takeout, &output)?;
+            println!(
+                "Exported {} traders for {} to {} ({} files)",
+                takeout.traders.len(),
+                email,
+                output.display(),
+                files.len()
+            );
+        }
+        Command::Trader(TraderCommand::List { user }) => {
+            let traders = db.get_traders(&user).await?;
+            if traders.is_empty() {
+                println!("No traders for user {}", user);
+            }
+            for t in traders {
+                println!(
+                    "{:<40} {:<20} {:<8} model={} exchange={} interval={}m",
+                    t.id,
+                    t.name,
+                    if t.is_running { "running" } else { "stopped" },
+                    t.ai_model_id,
+                    t.exchange_id,
+                    t.scan_interval_minutes
+                );
+            }
+        }
+        Command::Trader(TraderCommand::Start { id, user }) => {
+            let trader = db
+                .get_trader(&user, &id)
+                .await?
+                .with_context(|| format!("trader {} not found", id))?;
+            quota::check_start(db, &trader).await?;
+            db.update_trader_status(&user, &id, true).await?;
+            audit_log::trader_status(db, "", audit_log::SOURCE_CLI, &id, true).await;
+            println!("Trader {} marked as running", id);
+        }
+        Command::Trader(TraderCommand::Stop { id, user }) => {
+            db.update_trader_status(&user, &id, false).await?;
+            audit_log::trader_status(db, "", audit_log::SOURCE_CLI, &id, false).await;
+            println!("Trader {} marked as stopped", id);
+        }
+        Command::Trader(TraderCommand::Export { id, user, output }) => {
+            let bundle = bundle::export_trader(db, &user, &id).await?;
+            fs::write(&output, serde_json::to_string_pretty(&bundle)?)?;
+            println!(
+                "Exported trader {} to {} (key {})",
+                id,
+                output,
+                bundle.signer_fingerprint()
+            );
+        }
+        Command::Trader(TraderCommand::History {
+            id,
+            user,
+            dataset,
+            format,
+            since,
+            until,
+            output,
+            key_file,
+        }) => {
+            let key = match key_file {
+                Some(path) => {
+                    let secret = ConfigKey::KeyFile(path).secret()?;
+                    Some(RecordCipher::from_secret(&secret).user_key(&user))
+                }
+                None => None,
+            };
+            let range = export::DateRange { since, until };
+            let table = export::history(db, &user, &id, dataset, range, key.as_ref()).await?;
+            fs::write(&output, table.encode(format)?)?;
+            println!(
+                "Exported {} {} rows of trader {} to {}",
+                table.len(),
+                dataset.as_str(),
+                id,
+                output.display()
+            );
+        }
+        Command::Trader(TraderCommand::SetFilter { id, user, file }) => {
+            db.get_trader(&user, &id)
+                .await?
+                .with_context(|| format!("trader {} not found", id))?;
+            let wasm = fs::read(&file)?;
+            #[cfg(feature = "wasm-filters")]
+            aitrading::wasm_filter::WasmFilter::from_bytes(&wasm)?;
+            db.set_decision_filter(&user, &id, &wasm).await?;
+            println!("Decision filter attached to trader {}", id);
+        }
+        Command::Trader(TraderCommand::Stress {
+            id,
+            user,
+            btc_eth_leverage,
+            altcoin_leverage,
+            key_file,
+        }) => {
+            let key = match key_file {
+                Some(path) => {
+                    let secret = ConfigKey::KeyFile(path).secret()?;
+                    Some(RecordCipher::from_secret(&secret).user_key(&user))
+                }
+                None => None,
+            };
+            let leverage = stress::Leverage {
+                btc_eth: btc_eth_leverage,
+                altcoin: altcoin_leverage,
+            };
+            let report = stress::for_trader(db, &user, &id, key.as_ref(), leverage).await?;
+            println!(
+                "Trader {}: {} positions, equity {:.2} as of {}",
+                id,
+                report.positions,
+                report.equity,
+                report.as_of.to_rfc3339()
+            );
+            for s in &report.scenarios {
+                println!(
+                    "{:<12} worst={:>12.2} drawdown={:>6.1}% final={:>12.2} liquidations={}{}",
+                    s.name,
+                    s.worst_equity,
+                    s.max_drawdown_pct,
+                    s.final_equity,
+                    s.liquidations.len(),
+                    if s.wiped_out { " WIPED OUT" } else { "" }
+                );
+                for l in &s.liquidations {
+                    println!(
+                        "    {} {} liquidated at {} (step {}, loss {:.2})",
+                        l.symbol, l.side, l.price, l.step, l.loss
+                    );
+                }
+            }
+        }
+        Command::Trader(TraderCommand::Replay {
+            id,
+            user,
+            record,
+            response_file,
+            key_file,
+        }) => {
+            let key = match key_file {
+                Some(path) => {
+                    let secret = ConfigKey::KeyFile(path).secret()?;
+                    Some(RecordCipher::from_secret(&secret).user_key(&user))
+                }
+                None => None,
+            };
+            let canned = response_file.map(fs::read_to_string).transpose()?;
+            let replay =
+                replay::for_record(db, &user, &id, &record, key.as_ref(), canned.as_deref())
+                    .await?;
+            println!(
+                "Replayed record {} with {} (system prompt {})",
+                replay.record_id,
+                replay.model,
+                if replay.system_prompt_changed {
+                    "changed"
+                } else {
+                    "unchanged"
+                }
+            );
+            if let Some(error) = &replay.error {
+                println!("Response has no usable decisions: {}", error);
+            }
+            for rejected in &replay.rejected {
+                println!("Rejected: {}", rejected);
+            }
+            if replay.diff.is_empty() {
+                println!("Decisions are unchanged");
+            }
+            let describe = |d: &Option<Decision>| {
+                d.as_ref().map_or("-".to_string(), |d| {
+                    format!(
+                        "{} {:.2} USD {}x",
+                        d.action.as_str(),
+                        d.position_size_usd,
+                        d.leverage
+                    )
+                })
+            };
+            for d in &replay.diff {
+                println!(
+                    "{:<12} {:<30} -> {:<30} {}",
+                    d.symbol,
+                    describe(&d.original),
+                    describe(&d.replayed),
+                    d.changed.join(",")
+                );
+            }
+        }
+        Command::Trader(TraderCommand::ClearFilter { id, user }) => {
+            db.delete_decision_filter(&user, &id).await?;
+            println!("Decision filter removed from trader {}", id);
+        }
+        Command::Trader(TraderCommand::Import {
+            file,
+            user,
+            ai_model,
+            exchange,
+            trust_signer,
+        }) => {
+            let data = fs::read_to_string(&file)?;
+            let signed: SignedStrategy = serde_json::from_str(&data)?;
+            let id = bundle::import_trader(
+                db,
+                &signed,
+                &user,
+                &ai_model,
+                &exchange,
+                trust_signer.as_deref(),
+            )
+            .await?;
+            println!(
+                "Imported '{}' as trader {} (signed by {})",
+                signed.strategy.name,
+                id,
+                signed.signer_fingerprint()
+            );
+        }
+        Command::Betacode(BetacodeCommand::Generate { count }) => {
+            let codes: Vec<String> = (0..count).map(|_| generate_beta_code()).collect();
+            let inserted = db.insert_beta_codes(&codes).await?;
+            for code in &codes {
+                println!("{}", code);
+            }
+            eprintln!("Stored {} new beta codes", inserted);
+        }
+        Command::Jobs(JobsCommand::List) => {
+            for job in db.get_scheduled_jobs().await? {
+                println!(
+                    "{:<24} {:<20} {:<8} last={} outcome={} runs={} failures={} skipped={}",
+                    job.name,
+                    job.schedule,
+                    if job.enabled { "enabled" } else { "disabled" },
+                    job.last_started_at
+                        .map(|t| t.to_rfc3339())
+                        .unwrap_or_else(|| "-".to_string()),
+                    job.last_outcome.as_deref().unwrap_or("-"),
+                    job.run_count,
+                    job.failure_count,
+                    job.skipped_count
+                );
+            }
+        }
+        Command::Maintenance(MaintenanceCommand::On { reason, duration }) => {
+            let m = maintenance::enable(db, &reason, duration).await?;
+            match m.until {
+                Some(until) => println!("Maintenance mode on until {}", until.to_rfc3339()),
+                None => println!("Maintenance mode on"),
+            }
+        }
+        Command::Maintenance(MaintenanceCommand::Off) => {
+            maintenance::resume(db).await?;
+            println!("Maintenance mode off");
+        }
+        Command::Maintenance(MaintenanceCommand::Status) => {
+            maintenance::sync(db).await?;
+            match maintenance::current() {
+                Some(m) => println!(
+                    "on since {} ({}), resumes {}",
+                    m.since.to_rfc3339(),
+                    m.reason,
+                    m.until
+                        .map(|t| t.to_rfc3339())
+                        .unwrap_or_else(|| "manually".to_string())
+                ),
+                None => println!("off"),
+            }
+        }
+        Command::Backtest(BacktestCommand::Run {
+            user,
+            models,
+            scenarios,
+        }) => {
+            let scenarios = tournament::settled_scenarios(db, &user, scenarios).await?;
+            if scenarios.is_empty() {
+                bail!("user {} has no settled evaluation scenarios yet", user);
+            }
+            let mut contestants = Vec::new();
+            for model in db.get_aimodels(&user).await? {
+                let wanted = if models.is_empty() {
+                    model.enabled
+                } else {
+                    models.contains(&model.id)
+                };
+                if wanted {
+                    contestants.push(ai::from_model_config(&model)?);
+                }
+            }
+            if contestants.is_empty() {
+                bail!("no matching AI model for user {}", user);
+            }
+            let report = tournament::run(&mut contestants, &scenarios).await;
+            print!("{}", report.table());
+        }
+        Command::Backup { output } => {
+            db.backup_to(&output).await?;
+            println!("Database backed up to {}", output);
+        }
+        Command::Run | Command::Decisions(_) | Command::Config(_) => unreachable!(),
+    }
+    Ok(())
+}
+
+// Reads a new password twice from the terminal, so it never appears in argv
+// or shell history.
+fn prompt_new_password() -> Result<String> {
+    let password = rpassword::prompt_password("New password: ")?;
+    if password.chars().count() < MIN_PASSWORD_LEN {
+        bail!("password must be at least {} characters", MIN_PASSWORD_LEN);
+    }
+    if rpassword::prompt_password("Repeat password: ")? != password {
+        bail!("passwords do not match");
+    }
+    Ok(password)
+}
+
+fn generate_beta_code() -> String {
+    let mut rng = rand::thread_rng();
+    (0..BETA_CODE_LEN)
+        .map(|_| BETA_CODE_CHARSET[rng.gen_range(0..BETA_CODE_CHARSET.len())] as char)
+        .collect()
+}
@@ -1,9 +1,60 @@
+//! Application configuration.
+//!
+//! The config file may be JSON, TOML or YAML, chosen by its extension
+//! (`.toml`, `.yaml`/`.yml`, anything else is JSON). Settings are resolved in
+//! this order, later sources winning:
+//!
+//! 1. the built-in defaults,
+//! 2. the config file,
+//! 3. environment variables starting with `AIT_`.
+//!
+//! An environment variable names a setting by its path, upper-cased, with `__`
+//! between levels and array indexes as numbers: `AIT_API_SERVER_PORT=9090`
+//! sets `api_server_port` and `AIT_TRADERS__0__BINANCE_API_KEY=...` sets the
+//! first trader's `binance_api_key`, so secrets can stay out of the file. The
+//! value is taken as JSON where the file or the defaults hold a number, bool,
+//! list or table there, and as a plain string otherwise.
+//!
+//! Trader keys may also be references to an external secret store such as
+//! `vault://secret/data/aitrading#binance_secret`; see [`crate::secrets`].
+//!
+//! A file sealed with [`encrypt_config_file`] is recognized by its header and
+//! decrypted on load, with the key file in `AITRADING_CONFIG_KEY_FILE`, the
+//! passphrase in `AITRADING_CONFIG_PASSPHRASE`, or a passphrase prompt.
+
 use chrono::Duration;
 use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
 use std::fs;
+use std::path::{Path, PathBuf};
 use thiserror::Error;
 
+use crate::accuracy::AccuracyParams;
+use crate::ai_cache::AiCacheParams;
+use crate::api_client::{Proxies, Timeouts};
+use crate::audit::AuditParams;
+use crate::calendar::CalendarParams;
+use crate::cost_model::CostParams;
+use crate::crypto::{self, CryptoError};
+use crate::currency;
+use crate::daily_report::ReportParams;
+use crate::data::FallbackSource;
+use crate::database::DatabaseParams;
+use crate::logger::{RecordCipher, RotationParams};
+use crate::login_guard::GuardParams;
+use crate::margin_monitor::MonitorParams;
+use crate::notify::NotifierSettings;
+use crate::rate_limit::RateLimits;
+use crate::recovery::ReconcileParams;
+use crate::retry_queue::RetryPolicy;
+use crate::runner::CycleAlignment;
+use crate::stream::StreamParams;
+use crate::telemetry::LogFormat;
+use crate::timezone::{self, SessionWindow};
+use crate::tournament::TournamentParams;
+use crate::universe::UniverseParams;
+
 // --- Custom Error Type ---
 
 #[derive(Error, Debug)]
@@ -12,8 +63,18 @@ pub enum ConfigError {
     Io(#[from] std::io::Error),
     #[error("Failed to parse JSON config: {0}")]
     Json(#[from] serde_json::Error),
+    #[error("Failed to parse TOML config: {0}")]
+    Toml(#[from] toml::de::Error),
+    #[error("Failed to parse YAML config: {0}")]
+    Yaml(#[from] serde_yaml::Error),
+    #[error("Invalid environment override {0}: {1}")]
+    EnvOverride(String, String),
     #[error("Configuration validation failed: {0}")]
     Validation(String),
+    #[error("Failed to decrypt config file: {0}")]
+    Crypto(#[from] CryptoError),
+    #[error("Encrypted config is not valid UTF-8")]
+    Utf8(#[from] std::string::FromUtf8Error),
 }
 
 // --- Enums for Type Safety ---
@@ -90,12 +151,38 @@ pub struct TraderConfig {
     pub initial_balance: f64,
     #[serde(default = "default_scan_interval")]
     pub scan_interval_minutes: i32,
+
+    /// Name of a registered strategy to use instead of, or alongside, the AI model.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub strategy: Option<String>,
+
+    /// Local-time windows in which new positions may be opened; empty means always.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub trading_windows: Vec<SessionWindow>,
+
+    /// Minutes after a stop-loss during which the symbol may not be re-entered; 0 disables.
+    #[serde(default = "default_stop_loss_cooldown")]
+    pub stop_loss_cooldown_minutes: u32,
+
+    /// Quote assets to trade against, in order of preference, e.g. `["USDC"]`.
+    /// Empty uses the exchange's setting from `quote_assets`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub quote_assets: Vec<String>,
+
+    /// Never place orders; track the externally managed account with read-only
+    /// keys and log the AI's suggestions next to its actual trades.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub watch_only: bool,
 }
 
 fn default_scan_interval() -> i32 {
     3
 }
 
+fn default_stop_loss_cooldown() -> u32 {
+    30
+}
+
 impl TraderConfig {
     /// Returns the scan interval as a `chrono::Duration`.
     pub fn get_scan_interval(&self) -> Duration {
@@ -211,10 +298,90 @@ pub struct Config {
     pub use_default_coins: bool,
     pub default_coins: Vec<String>,
     pub api_server_port: u16,
+    /// Serve the API on this unix socket instead of `api_server_port`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub api_server_socket: Option<PathBuf>,
     pub max_daily_loss: f64,
     pub max_drawdown: f64,
+    /// IANA timezone that defines "daily" boundaries and report times, e.g. "Asia/Shanghai".
+    pub timezone: String,
     pub stop_trading_minutes: i32,
     pub leverage: LeverageConfig,
+    /// Log output format: "pretty" for humans, "json" for log shippers (Loki/ELK).
+    pub log_format: LogFormat,
+    /// Per-endpoint-class HTTP timeouts, e.g. `{"market_data": "10s", "trading": "15s"}`.
+    pub http_timeouts: Timeouts,
+    /// Outbound proxies for exchange, market data and AI traffic, e.g.
+    /// `{"exchange": {"url": "socks5h://127.0.0.1:1080"}}`.
+    pub proxies: Proxies,
+    /// Per-host request weight budgets and 429/5xx backoff for exchange and
+    /// market data calls, e.g. `{"weight_per_minute": {"fapi.binance.com": 1200}}`.
+    pub rate_limits: RateLimits,
+    /// Lockout of accounts and client IPs after repeated failed logins or
+    /// two-factor codes, e.g. `{"max_account_failures": 5, "base_lockout": "1m"}`.
+    pub login_guard: GuardParams,
+    /// Reuse of AI responses to identical prompts for a short while, e.g.
+    /// `{"enabled": true, "ttl": "5m"}`.
+    pub ai_response_cache: AiCacheParams,
+    /// Optional Sentry DSN; panics, AI-call failures and order rejections are reported there.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sentry_dsn: Option<String>,
+    /// Shared secret an external risk system signs its directives with.
+    /// Unset disables the risk directive endpoint.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub risk_webhook_secret: Option<String>,
+    /// Discord and HTTP webhooks that trade and alert events are posted to.
+    pub notifications: Vec<NotifierSettings>,
+    /// Market data source used after Binance fails repeatedly.
+    pub market_data_fallback: FallbackSource,
+    /// WebSocket kline/ticker streaming, with REST only as cold-start backfill.
+    pub market_stream: StreamParams,
+    /// Quote-asset preference per exchange, e.g. `{"hyperliquid": ["USDC"]}`.
+    /// Exchanges not listed trade against USDT.
+    pub quote_assets: HashMap<String, Vec<String>>,
+    /// Retrying of orders that failed with a timeout or rate limit.
+    pub order_retry: RetryPolicy,
+    /// Fees and hold duration used to project trading costs.
+    pub cost_model: CostParams,
+    /// Scenario horizon and size of the scheduled model evaluation tournaments.
+    pub tournament: TournamentParams,
+    /// Run trader cycles just after candle closes instead of drifting with
+    /// their start time, e.g. `{"enabled": true, "delay": "5s"}`.
+    pub cycle_alignment: CycleAlignment,
+    /// Scoring horizon and window of the AI's directional-call hit rates.
+    pub decision_accuracy: AccuracyParams,
+    /// Economic calendar feed shown in prompts and optional trading blackouts around events.
+    pub calendar: CalendarParams,
+    /// Scheduled refresh of `default_coins` from exchange volume rankings.
+    pub universe: UniverseParams,
+    /// Retention of the raw order request/response audit trail.
+    pub execution_audit: AuditParams,
+    /// Master key file for encrypting decision logs at rest, with a separate
+    /// key per user. Unset keeps decision logs in plaintext.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub decision_log_key_file: Option<PathBuf>,
+    /// Compression and deletion of old decision log files, e.g.
+    /// `{"compress_after": "7d", "max_active_bytes": 67108864, "retention": "180d"}`.
+    pub decision_log_rotation: RotationParams,
+    /// Seed for randomized elements (scheduler jitter, simulated slippage), so
+    /// dry runs are reproducible. Unset draws from entropy.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub simulation_seed: Option<u64>,
+    /// Currency aggregate views value accounts in, e.g. "USDC". Collateral in
+    /// other assets is converted at live prices.
+    pub reporting_currency: String,
+    /// Connection pool sizes and an optional Postgres read replica for
+    /// analytics queries.
+    pub database: DatabaseParams,
+    /// How often running traders' recorded positions are checked against the
+    /// exchange between cycles, e.g. `{"interval": "5m"}`.
+    pub position_reconciliation: ReconcileParams,
+    /// How often running traders' positions are checked against their
+    /// liquidation alert thresholds, e.g. `{"interval": "30s"}`.
+    pub margin_monitor: MonitorParams,
+    /// Daily per-trader performance report, stored and sent to the
+    /// notification sinks, e.g. `{"schedule": "0 8 * * *"}` (UTC).
+    pub daily_report: ReportParams,
 }
 
 fn default_coin_list() -> Vec<String> {
@@ -237,10 +404,40 @@ impl Default for Config {
             use_default_coins: true,
             default_coins: default_coin_list(),
             api_server_port: 8080,
+            api_server_socket: None,
             max_daily_loss: 0.0,
             max_drawdown: 0.0,
+            timezone: "UTC".to_string(),
             stop_trading_minutes: 0,
             leverage: LeverageConfig::default(),
+            log_format: LogFormat::default(),
+            http_timeouts: Timeouts::default(),
+            proxies: Proxies::default(),
+            rate_limits: RateLimits::default(),
+            login_guard: GuardParams::default(),
+            ai_response_cache: AiCacheParams::default(),
+            sentry_dsn: None,
+            risk_webhook_secret: None,
+            notifications: Vec::new(),
+            market_data_fallback: FallbackSource::default(),
+            market_stream: StreamParams::default(),
+            quote_assets: HashMap::new(),
+            order_retry: RetryPolicy::default(),
+            cost_model: CostParams::default(),
+            tournament: TournamentParams::default(),
+            cycle_alignment: CycleAlignment::default(),
+            decision_accuracy: AccuracyParams::default(),
+            calendar: CalendarParams::default(),
+            universe: UniverseParams::default(),
+            execution_audit: AuditParams::default(),
+            decision_log_key_file: None,
+            decision_log_rotation: RotationParams::default(),
+            simulation_seed: None,
+            reporting_currency: currency::DEFAULT_REPORTING_CURRENCY.to_string(),
+            database: DatabaseParams::default(),
+            position_reconciliation: ReconcileParams::default(),
+            margin_monitor: MonitorParams::default(),
+            daily_report: ReportParams::default(),
         }
     }
 }
@@ -254,6 +451,8 @@ impl Config {
             ));
         }
 
+        timezone::parse_tz(&self.timezone).map_err(|e| ConfigError::Validation(e.to_string()))?;
+
         let mut trader_ids = HashSet::new();
         for (i, trader) in self.traders.iter().enumerate() {
             if !trader_ids.insert(&trader.id) {
@@ -274,11 +473,249 @@ impl Config {
     }
 }
 
-/// Loads, parses, and validates the configuration from a JSON file.
+/// Header written at the start of every encrypted config file.
+pub const ENCRYPTED_CONFIG_MAGIC: &[u8] = b"AITCFG1\0";
+
+/// Where the secret used to decrypt an encrypted config file comes from.
+#[derive(Debug, Clone)]
+pub enum ConfigKey {
+    /// A passphrase supplied directly (e.g. from an environment variable).
+    Passphrase(String),
+    /// A file whose entire contents are used as key material.
+    KeyFile(PathBuf),
+    /// Interactively prompt for a passphrase on the terminal.
+    Prompt,
+}
+
+/// Environment variable naming the key file of an encrypted config.
+pub const CONFIG_KEY_FILE_ENV: &str = "AITRADING_CONFIG_KEY_FILE";
+/// Environment variable holding the passphrase of an encrypted config.
+pub const CONFIG_PASSPHRASE_ENV: &str = "AITRADING_CONFIG_PASSPHRASE";
+
+impl ConfigKey {
+    /// The key file or passphrase named by the environment, else a prompt.
+    pub fn from_env() -> Self {
+        if let Some(path) = std::env::var_os(CONFIG_KEY_FILE_ENV) {
+            return ConfigKey::KeyFile(path.into());
+        }
+        match std::env::var(CONFIG_PASSPHRASE_ENV) {
+            Ok(passphrase) => ConfigKey::Passphrase(passphrase),
+            Err(_) => ConfigKey::Prompt,
+        }
+    }
+
+    /// Asks for the passphrase now if this is [`ConfigKey::Prompt`], so later
+    /// reads of the file (e.g. hot reloads) do not prompt again.
+    pub fn resolve(self) -> Result<Self, ConfigError> {
+        match self {
+            ConfigKey::Prompt => Ok(ConfigKey::Passphrase(String::from_utf8(self.secret()?)?)),
+            key => Ok(key),
+        }
+    }
+
+    pub fn secret(&self) -> Result<Vec<u8>, ConfigError> {
+        match self {
+            ConfigKey::Passphrase(p) => Ok(p.as_bytes().to_vec()),
+            ConfigKey::KeyFile(path) => Ok(fs::read(path)?),
+            ConfigKey::Prompt => {
+                let pass = rpassword::prompt_password("Config passphrase: ")?;
+                Ok(pass.into_bytes())
+            }
+        }
+    }
+}
+
+impl Config {
+    /// The decision log cipher, if `decision_log_key_file` is set.
+    pub fn decision_log_cipher(&self) -> Result<Option<RecordCipher>, ConfigError> {
+        match &self.decision_log_key_file {
+            Some(path) => {
+                let secret = ConfigKey::KeyFile(path.clone()).secret()?;
+                Ok(Some(RecordCipher::from_secret(&secret)))
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+/// Returns true if the file at `filename` starts with the encrypted config header.
+pub fn is_encrypted_config(filename: &str) -> Result<bool, ConfigError> {
+    let data = fs::read(filename)?;
+    Ok(data.starts_with(ENCRYPTED_CONFIG_MAGIC))
+}
+
+/// Prefix of the environment variables that override config settings.
+pub const ENV_PREFIX: &str = "AIT_";
+
+/// Syntax of a config file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigFormat {
+    Json,
+    Toml,
+    Yaml,
+}
+
+impl ConfigFormat {
+    /// The format implied by the file extension; JSON unless it is `.toml`,
+    /// `.yaml` or `.yml`.
+    pub fn from_path(path: impl AsRef<Path>) -> Self {
+        let extension = path
+            .as_ref()
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(str::to_ascii_lowercase);
+        match extension.as_deref() {
+            Some("toml") => ConfigFormat::Toml,
+            Some("yaml" | "yml") => ConfigFormat::Yaml,
+            _ => ConfigFormat::Json,
+        }
+    }
+
+    fn parse(self, data: &str) -> Result<Value, ConfigError> {
+        Ok(match self {
+            ConfigFormat::Json => serde_json::from_str(data)?,
+            ConfigFormat::Toml => toml::from_str(data)?,
+            ConfigFormat::Yaml => serde_yaml::from_str(data)?,
+        })
+    }
+}
+
+/// Loads, parses, and validates the configuration from a JSON, TOML or YAML
+/// file, with `AIT_*` environment overrides applied. A file encrypted with
+/// [`encrypt_config_file`] is decrypted with [`ConfigKey::from_env`], which
+/// prompts for the passphrase when the environment names no key.
 pub fn load_config(filename: &str) -> Result<Config, ConfigError> {
-    let data = fs::read_to_string(filename)?;
-    let mut config: Config = serde_json::from_str(&data)?;
+    load_config_with_key(filename, &ConfigKey::from_env())
+}
+
+/// [`load_config`], decrypting an encrypted file with `key`. Plaintext files
+/// are read as they are and `key` is not used.
+pub fn load_config_with_key(filename: &str, key: &ConfigKey) -> Result<Config, ConfigError> {
+    let data = fs::read(filename)?;
+    if data.starts_with(ENCRYPTED_CONFIG_MAGIC) {
+        return decrypt_config(&data, key);
+    }
+    let data = String::from_utf8(data)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+    parse_config(&data, ConfigFormat::from_path(filename), std::env::vars())
+}
+
+/// Loads a configuration file encrypted with [`encrypt_config_file`].
+pub fn load_encrypted_config(filename: &str, key: &ConfigKey) -> Result<Config, ConfigError> {
+    decrypt_config(&fs::read(filename)?, key)
+}
+
+fn decrypt_config(data: &[u8], key: &ConfigKey) -> Result<Config, ConfigError> {
+    let secret = key.secret()?;
+    let plaintext = crypto::decrypt_with_secret(ENCRYPTED_CONFIG_MAGIC, &secret, data)?;
+    parse_config(
+        &String::from_utf8(plaintext)?,
+        ConfigFormat::Json,
+        std::env::vars(),
+    )
+}
+
+/// Encrypts a plaintext config so secrets never sit on disk in the clear.
+/// The input is parsed first so a broken config is rejected before it gets
+/// sealed; TOML and YAML input is sealed as JSON.
+pub fn encrypt_config_file(input: &str, output: &str, key: &ConfigKey) -> Result<(), ConfigError> {
+    let data = fs::read_to_string(input)?;
+    let value = ConfigFormat::from_path(input).parse(&data)?;
+    serde_json::from_value::<Config>(value.clone())?;
+
+    let secret = key.secret()?;
+    let json = serde_json::to_vec_pretty(&value)?;
+    let sealed = crypto::encrypt_with_secret(ENCRYPTED_CONFIG_MAGIC, &secret, &json)?;
+    fs::write(output, sealed)?;
+    Ok(())
+}
+
+/// Parses and validates a config in `format`, applying the `AIT_*` overrides
+/// among `env`.
+pub fn parse_config(
+    data: &str,
+    format: ConfigFormat,
+    env: impl IntoIterator<Item = (String, String)>,
+) -> Result<Config, ConfigError> {
+    let mut value = format.parse(data)?;
+    let defaults = serde_json::to_value(Config::default())?;
+    let mut overrides: Vec<(String, String)> = env
+        .into_iter()
+        .filter(|(name, _)| name.starts_with(ENV_PREFIX))
+        .collect();
+    // Lower array indexes first, so `__0__` exists before `__1__` is appended.
+    overrides.sort_by_cached_key(|(name, _)| {
+        name.split("__")
+            .map(|s| (s.parse::<usize>().ok(), s.to_string()))
+            .collect::<Vec<_>>()
+    });
+    for (name, raw) in overrides {
+        apply_env_override(&mut value, &defaults, &name, &raw)
+            .map_err(|e| ConfigError::EnvOverride(name.clone(), e))?;
+    }
+    finish_config(serde_json::from_value(value)?)
+}
+
+// Sets the setting `name` (e.g. "AIT_TRADERS__0__BINANCE_API_KEY") points at.
+fn apply_env_override(
+    config: &mut Value,
+    defaults: &Value,
+    name: &str,
+    raw: &str,
+) -> Result<(), String> {
+    let path: Vec<String> = name[ENV_PREFIX.len()..]
+        .split("__")
+        .map(str::to_ascii_lowercase)
+        .collect();
+    if path.iter().any(String::is_empty) {
+        return Err("empty path segment".to_string());
+    }
+
+    let mut default = Some(defaults);
+    let mut slot = config;
+    for key in &path {
+        default = default.and_then(|d| match d {
+            Value::Array(items) => key.parse().ok().and_then(|i: usize| items.get(i)),
+            _ => d.get(key),
+        });
+        if slot.is_null() {
+            *slot = if key.parse::<usize>().is_ok() {
+                Value::Array(Vec::new())
+            } else {
+                Value::Object(Default::default())
+            };
+        }
+        slot = match slot {
+            Value::Object(map) => map.entry(key.clone()).or_insert(Value::Null),
+            Value::Array(items) => {
+                let index: usize = key
+                    .parse()
+                    .map_err(|_| format!("'{}' is not a list index", key))?;
+                if index == items.len() {
+                    items.push(Value::Object(Default::default()));
+                }
+                items
+                    .get_mut(index)
+                    .ok_or_else(|| format!("index {} is past the end of the list", index))?
+            }
+            _ => return Err(format!("'{}' is not inside a table or list", key)),
+        };
+    }
+
+    let structured = match (&*slot, default) {
+        (Value::Null, Some(d)) => !d.is_string() && !d.is_null(),
+        (Value::Null, None) => false,
+        (current, _) => !current.is_string(),
+    };
+    *slot = if structured {
+        serde_json::from_str(raw).map_err(|e| e.to_string())?
+    } else {
+        Value::String(raw.to_string())
+    };
+    Ok(())
+}
 
+fn finish_config(mut config: Config) -> Result<Config, ConfigError> {
     // Handle special default case: if default_coins is provided but empty, populate it.
     if config.use_default_coins && config.default_coins.is_empty() {
         config.default_coins = default_coin_list();
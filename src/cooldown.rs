@@ -0,0 +1,125 @@
+//! Per-symbol re-entry cooldown after a stop-loss.
+//!
+//! LLMs tend to re-open the same losing trade on the very next cycle. Once a
+//! stop-loss fires on a symbol, the trader may not open a new position on it
+//! until its cooldown has passed; closing and managing other positions is
+//! unaffected. The runner spots stop-loss exits when it reconciles a trader's
+//! ledger: a position the exchange closed at or through the stop of the
+//! decision that opened it counts as one.
+
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+use chrono::{DateTime, Duration, Utc};
+use once_cell::sync::Lazy;
+
+use crate::data::normalize;
+use crate::decision::Decision;
+
+// (trader_id, symbol)
+type Key = (String, String);
+
+// Time of the last stop-loss per trader and symbol.
+static STOP_LOSSES: Lazy<RwLock<HashMap<Key, DateTime<Utc>>>> =
+    Lazy::new(|| RwLock::new(HashMap::new()));
+
+/// Records that a stop-loss was hit on `symbol`.
+pub fn record_stop_loss(trader_id: &str, symbol: &str, at: DateTime<Utc>) {
+    let symbol = normalize(symbol);
+    tracing::info!("🧊 交易员 {} 的 {} 触发止损，进入冷却期", trader_id, symbol);
+    STOP_LOSSES
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert((trader_id.to_string(), symbol), at);
+}
+
+/// Whether a `side` position last priced at `price` went out through its
+/// `stop_loss`. A position without a stop, or without a known price, never did.
+pub fn is_stop_loss_exit(side: &str, stop_loss: f64, price: f64) -> bool {
+    if stop_loss <= 0.0 || price <= 0.0 {
+        return false;
+    }
+    if side == "short" {
+        price >= stop_loss
+    } else {
+        price <= stop_loss
+    }
+}
+
+/// When the cooldown on `symbol` ends, if it is still cooling down.
+pub fn cooldown_until(trader_id: &str, symbol: &str, minutes: u32) -> Option<DateTime<Utc>> {
+    if minutes == 0 {
+        return None;
+    }
+    let hit = *STOP_LOSSES
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(&(trader_id.to_string(), normalize(symbol)))?;
+    let until = hit + Duration::minutes(minutes as i64);
+    (until > Utc::now()).then_some(until)
+}
+
+/// Symbols of this trader still cooling down, with when each cooldown ends.
+pub fn cooling_symbols(trader_id: &str, minutes: u32) -> Vec<(String, DateTime<Utc>)> {
+    if minutes == 0 {
+        return Vec::new();
+    }
+    let now = Utc::now();
+    let mut cooling: Vec<_> = STOP_LOSSES
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter()
+        .filter(|((trader, _), _)| trader == trader_id)
+        .map(|((_, symbol), hit)| (symbol.clone(), *hit + Duration::minutes(minutes as i64)))
+        .filter(|(_, until)| *until > now)
+        .collect();
+    cooling.sort();
+    cooling
+}
+
+/// Drops opening decisions with a leg on a symbol that is cooling down.
+pub fn drop_cooling_entries(
+    trader_id: &str,
+    minutes: u32,
+    decisions: Vec<Decision>,
+) -> Vec<Decision> {
+    decisions
+        .into_iter()
+        .filter(|d| {
+            if !d.action.is_open() {
+                return true;
+            }
+            let cooling = d
+                .symbols()
+                .find_map(|s| Some((s, cooldown_until(trader_id, s, minutes)?)));
+            match cooling {
+                Some((symbol, until)) => {
+                    tracing::warn!(
+                        "🧊 {} 止损冷却中（至 {}），拒绝开仓 {:?}",
+                        symbol,
+                        until.format("%H:%M:%S"),
+                        d.action
+                    );
+                    false
+                }
+                None => true,
+            }
+        })
+        .collect()
+}
+
+/// A line for the prompt listing symbols the AI must not re-enter yet.
+pub fn prompt_annotation(trader_id: &str, minutes: u32) -> Option<String> {
+    let cooling = cooling_symbols(trader_id, minutes);
+    if cooling.is_empty() {
+        return None;
+    }
+    let list: Vec<String> = cooling
+        .iter()
+        .map(|(symbol, until)| format!("{} (until {} UTC)", symbol, until.format("%H:%M")))
+        .collect();
+    Some(format!(
+        "🧊 Stop-loss cooldown, do not open new positions on: {}",
+        list.join(", ")
+    ))
+}
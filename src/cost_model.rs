@@ -0,0 +1,231 @@
+//! Projected cost of holding a trade.
+//!
+//! A trade has to earn back its entry and exit fees plus the funding paid
+//! while it is open. Costs are projected over the trader's typical hold
+//! duration, with funding at both the current and the average rate (whichever
+//! costs more), and shown in the prompt. Opens whose take-profit would not
+//! clear those costs by a margin are dropped before execution.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::data::normalize;
+use crate::decision::{Action, Decision};
+use crate::types::Data;
+
+/// Binance futures settles funding every 8 hours.
+const FUNDING_INTERVAL_HOURS: f64 = 8.0;
+/// Notional used to quote costs in the prompt.
+const PROMPT_NOTIONAL_USD: f64 = 1000.0;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[serde(default)]
+pub struct CostParams {
+    /// Taker fee per side, in percent of notional.
+    pub taker_fee_pct: f64,
+    /// Hold duration assumed until the trader has closed trades of its own.
+    #[serde(with = "humantime_serde")]
+    pub default_hold: Duration,
+    /// An open is rejected unless its expected gain is at least this many
+    /// times its projected cost; 0 disables the check.
+    pub min_edge_to_cost: f64,
+}
+
+impl Default for CostParams {
+    fn default() -> Self {
+        Self {
+            taker_fee_pct: 0.05,
+            default_hold: Duration::from_secs(4 * 3600),
+            min_edge_to_cost: 1.5,
+        }
+    }
+}
+
+/// Projected costs of one position, in USD. Funding is positive when paid
+/// and negative when received.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct CostProjection {
+    pub notional: f64,
+    pub fees: f64,
+    pub funding_at_current_rate: f64,
+    pub funding_at_average_rate: f64,
+    /// Fees plus the costlier of the two funding projections.
+    pub total: f64,
+    pub hold_hours: f64,
+}
+
+impl CostProjection {
+    /// Total cost in percent of notional.
+    pub fn total_pct(&self) -> f64 {
+        if self.notional > 0.0 {
+            self.total / self.notional * 100.0
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Projects the costs of holding `notional` USD long (or short) for `hold`.
+/// Funding rates are per settlement, as fractions (0.0001 = 0.01%).
+pub fn project(
+    notional: f64,
+    long: bool,
+    current_funding_rate: f64,
+    average_funding_rate: Option<f64>,
+    hold: Duration,
+    params: &CostParams,
+) -> CostProjection {
+    let hold_hours = hold.as_secs_f64() / 3600.0;
+    let settlements = hold_hours / FUNDING_INTERVAL_HOURS;
+    // Longs pay positive funding, shorts pay negative funding.
+    let sign = if long { 1.0 } else { -1.0 };
+    let funding = |rate: f64| sign * rate * notional * settlements;
+
+    let fees = notional * params.taker_fee_pct / 100.0 * 2.0;
+    let funding_at_current_rate = funding(current_funding_rate);
+    let funding_at_average_rate = funding(average_funding_rate.unwrap_or(current_funding_rate));
+
+    CostProjection {
+        notional,
+        fees,
+        funding_at_current_rate,
+        funding_at_average_rate,
+        total: fees + funding_at_current_rate.max(funding_at_average_rate),
+        hold_hours,
+    }
+}
+
+/// Why an open was rejected.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Uneconomic {
+    /// Gain if the take-profit is hit, in USD.
+    pub edge: f64,
+    pub cost: f64,
+}
+
+/// Checks that an open's take-profit pays for its costs. Decisions without a
+/// take-profit or market data cannot be judged and pass.
+pub fn check(
+    d: &Decision,
+    data: &Data,
+    average_funding_rate: Option<f64>,
+    hold: Duration,
+    params: &CostParams,
+) -> Result<CostProjection, Uneconomic> {
+    let long = d.action == Action::OpenLong;
+    let cost = project(
+        d.position_size_usd,
+        long,
+        data.funding_rate,
+        average_funding_rate,
+        hold,
+        params,
+    );
+    if !d.action.is_open()
+        || params.min_edge_to_cost <= 0.0
+        || d.take_profit <= 0.0
+        || data.current_price <= 0.0
+    {
+        return Ok(cost);
+    }
+
+    let moved = if long {
+        d.take_profit - data.current_price
+    } else {
+        data.current_price - d.take_profit
+    };
+    let edge = moved / data.current_price * d.position_size_usd;
+    if edge < cost.total * params.min_edge_to_cost {
+        return Err(Uneconomic {
+            edge,
+            cost: cost.total,
+        });
+    }
+    Ok(cost)
+}
+
+/// Drops opens whose expected gain does not cover their projected costs.
+/// `average_funding` maps symbols to their average funding rate, if known.
+pub fn drop_uneconomic_entries(
+    decisions: Vec<Decision>,
+    market_data: &HashMap<String, Data>,
+    average_funding: &HashMap<String, f64>,
+    hold: Duration,
+    params: &CostParams,
+) -> Vec<Decision> {
+    decisions
+        .into_iter()
+        .filter(|d| {
+            let symbol = normalize(&d.symbol);
+            let Some(data) = market_data.get(&symbol) else {
+                return true;
+            };
+            let average = average_funding.get(&symbol).copied();
+            match check(d, data, average, hold, params) {
+                Ok(_) => true,
+                Err(Uneconomic { edge, cost }) => {
+                    tracing::warn!(
+                        "💸 {} {:?} 预期收益 {:.2} USD 不足以覆盖持仓成本 {:.2} USD，拒绝开仓",
+                        d.symbol,
+                        d.action,
+                        edge,
+                        cost
+                    );
+                    false
+                }
+            }
+        })
+        .collect()
+}
+
+/// Prompt lines with the projected cost of a long and a short per symbol,
+/// quoted per 1000 USD over `hold`.
+pub fn prompt_annotation(
+    market_data: &HashMap<String, Data>,
+    average_funding: &HashMap<String, f64>,
+    hold: Duration,
+    params: &CostParams,
+) -> Option<String> {
+    if market_data.is_empty() {
+        return None;
+    }
+    let mut symbols: Vec<&String> = market_data.keys().collect();
+    symbols.sort();
+
+    let mut s = format!(
+        "💸 Projected costs per {:.0} USD held ~{:.1}h (fees both ways + funding); a trade's target must clear them:\n",
+        PROMPT_NOTIONAL_USD,
+        hold.as_secs_f64() / 3600.0
+    );
+    for symbol in symbols {
+        let data = &market_data[symbol];
+        let average = average_funding.get(symbol).copied();
+        let long = project(
+            PROMPT_NOTIONAL_USD,
+            true,
+            data.funding_rate,
+            average,
+            hold,
+            params,
+        );
+        let short = project(
+            PROMPT_NOTIONAL_USD,
+            false,
+            data.funding_rate,
+            average,
+            hold,
+            params,
+        );
+        s.push_str(&format!(
+            "{}: long {:.2} USD ({:.3}%), short {:.2} USD ({:.3}%)\n",
+            symbol,
+            long.total,
+            long.total_pct(),
+            short.total,
+            short.total_pct()
+        ));
+    }
+    Some(s)
+}
@@ -0,0 +1,100 @@
+use aes_gcm::aead::{Aead, KeyInit, OsRng};
+use aes_gcm::{AeadCore, Aes256Gcm, Key, Nonce};
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use sha2::Sha256;
+use thiserror::Error;
+
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+// OWASP recommendation for PBKDF2-HMAC-SHA256.
+const PBKDF2_ROUNDS: u32 = 600_000;
+
+#[derive(Error, Debug)]
+pub enum CryptoError {
+    #[error("Ciphertext is truncated or has an unknown header")]
+    Malformed,
+    #[error("Decryption failed (wrong key or corrupted data)")]
+    Decrypt,
+    #[error("Encryption failed")]
+    Encrypt,
+}
+
+/// Derives a 256-bit key from arbitrary secret material (a passphrase or key file contents).
+pub fn derive_key(secret: &[u8], salt: &[u8]) -> [u8; 32] {
+    let mut key = [0u8; 32];
+    pbkdf2::pbkdf2_hmac::<Sha256>(secret, salt, PBKDF2_ROUNDS, &mut key);
+    key
+}
+
+/// Derives an independent key for `context` (e.g. one per user) from a key
+/// that is already uniformly random, such as the output of [`derive_key`].
+pub fn derive_subkey(master: &[u8; 32], context: &[u8]) -> [u8; 32] {
+    let mut mac =
+        <Hmac<Sha256> as Mac>::new_from_slice(master).expect("HMAC accepts keys of any length");
+    mac.update(context);
+    mac.finalize().into_bytes().into()
+}
+
+/// Encrypts `plaintext` with a key derived from `secret`.
+///
+/// Output layout: `magic | salt(16) | nonce(12) | ciphertext+tag`.
+pub fn encrypt_with_secret(
+    magic: &[u8],
+    secret: &[u8],
+    plaintext: &[u8],
+) -> Result<Vec<u8>, CryptoError> {
+    let mut salt = [0u8; SALT_LEN];
+    rand::thread_rng().fill_bytes(&mut salt);
+    let key = derive_key(secret, &salt);
+
+    let sealed = seal(&key, plaintext)?;
+
+    let mut out = Vec::with_capacity(magic.len() + SALT_LEN + sealed.len());
+    out.extend_from_slice(magic);
+    out.extend_from_slice(&salt);
+    out.extend_from_slice(&sealed);
+    Ok(out)
+}
+
+/// Reverses [`encrypt_with_secret`].
+pub fn decrypt_with_secret(
+    magic: &[u8],
+    secret: &[u8],
+    data: &[u8],
+) -> Result<Vec<u8>, CryptoError> {
+    let body = data.strip_prefix(magic).ok_or(CryptoError::Malformed)?;
+    if body.len() < SALT_LEN {
+        return Err(CryptoError::Malformed);
+    }
+    let (salt, sealed) = body.split_at(SALT_LEN);
+    let key = derive_key(secret, salt);
+    open(&key, sealed)
+}
+
+/// Encrypts with an already-derived key. Output layout: `nonce(12) | ciphertext+tag`.
+pub fn seal(key: &[u8; 32], plaintext: &[u8]) -> Result<Vec<u8>, CryptoError> {
+    let cipher = Aes256Gcm::new(&Key::<Aes256Gcm>::from(*key));
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(&nonce, plaintext)
+        .map_err(|_| CryptoError::Encrypt)?;
+
+    let mut out = Vec::with_capacity(NONCE_LEN + ciphertext.len());
+    out.extend_from_slice(&nonce);
+    out.extend_from_slice(&ciphertext);
+    Ok(out)
+}
+
+/// Decrypts data produced by [`seal`].
+pub fn open(key: &[u8; 32], sealed: &[u8]) -> Result<Vec<u8>, CryptoError> {
+    if sealed.len() < NONCE_LEN {
+        return Err(CryptoError::Malformed);
+    }
+    let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+    let nonce: [u8; NONCE_LEN] = nonce.try_into().map_err(|_| CryptoError::Malformed)?;
+    let cipher = Aes256Gcm::new(&Key::<Aes256Gcm>::from(*key));
+    cipher
+        .decrypt(&Nonce::from(nonce), ciphertext)
+        .map_err(|_| CryptoError::Decrypt)
+}
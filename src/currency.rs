@@ -0,0 +1,169 @@
+//! Conversion of balances between assets.
+//!
+//! Accounts hold collateral in more than one asset (USDT, USDC, BTC). Risk
+//! limits need the account's value in the margin currency, and aggregate views
+//! need every account in one reporting currency. [`rate`] resolves a rate from
+//! live futures prices: the direct pair, the inverse pair, or a hop through
+//! USDT. Stablecoins fall back to parity when no pair is listed. Prices are
+//! cached for a minute.
+
+use std::sync::RwLock;
+use std::time::Duration;
+
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::api_client::ApiClient;
+use crate::cache::BoundedCache;
+use crate::types::AccountBalance;
+
+/// Reporting currency used when none is configured.
+pub const DEFAULT_REPORTING_CURRENCY: &str = "USDT";
+// Every rate can be routed through this asset.
+const HUB: &str = "USDT";
+// Assets pegged to the US dollar, worth 1:1 when no pair is listed.
+const STABLECOINS: &[&str] = &["USDT", "USDC", "FDUSD", "BUSD", "USD"];
+
+static REPORTING_CURRENCY: Lazy<RwLock<String>> =
+    Lazy::new(|| RwLock::new(DEFAULT_REPORTING_CURRENCY.to_string()));
+
+// Latest price per pair; a failed lookup is cached as `None`.
+static PRICES: Lazy<BoundedCache<String, Option<f64>>> =
+    Lazy::new(|| BoundedCache::new(256, Duration::from_secs(60)));
+
+#[derive(Error, Debug, PartialEq)]
+pub enum CurrencyError {
+    #[error("No price to convert {from} to {to}")]
+    NoRate { from: String, to: String },
+}
+
+/// Sets the currency aggregate views report in, e.g. "USDC".
+pub fn set_reporting_currency(currency: &str) {
+    let currency = currency.trim().to_uppercase();
+    if currency.is_empty() {
+        return;
+    }
+    *REPORTING_CURRENCY
+        .write()
+        .unwrap_or_else(|e| e.into_inner()) = currency;
+}
+
+pub fn reporting_currency() -> String {
+    REPORTING_CURRENCY
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+}
+
+/// Records a price seen elsewhere (a ticker stream, a fill), so conversions
+/// do not have to fetch it.
+pub fn set_price(symbol: &str, price: f64) {
+    if price > 0.0 {
+        PRICES.insert(symbol.to_uppercase(), Some(price));
+    }
+}
+
+async fn price(symbol: String) -> Option<f64> {
+    let fetched: Result<_, std::convert::Infallible> = PRICES
+        .get_or_try_insert(symbol.clone(), || async move {
+            Ok(ApiClient::new()
+                .get_current_price(&symbol)
+                .await
+                .ok()
+                .filter(|p| *p > 0.0))
+        })
+        .await;
+    fetched.ok().flatten()
+}
+
+// Rate from a listed pair in either direction.
+async fn pair_rate(from: &str, to: &str) -> Option<f64> {
+    if from == to {
+        return Some(1.0);
+    }
+    let (direct, inverse) = (format!("{}{}", from, to), format!("{}{}", to, from));
+    // A known price in either direction saves a request.
+    if let Some(Some(p)) = PRICES.get(&direct) {
+        return Some(p);
+    }
+    if let Some(Some(p)) = PRICES.get(&inverse) {
+        return Some(1.0 / p);
+    }
+    if let Some(p) = price(direct).await {
+        return Some(p);
+    }
+    price(inverse).await.map(|p| 1.0 / p)
+}
+
+fn is_stablecoin(asset: &str) -> bool {
+    STABLECOINS.contains(&asset)
+}
+
+/// How many units of `to` one unit of `from` is worth.
+pub async fn rate(from: &str, to: &str) -> Result<f64, CurrencyError> {
+    let (from, to) = (from.trim().to_uppercase(), to.trim().to_uppercase());
+    if let Some(r) = pair_rate(&from, &to).await {
+        return Ok(r);
+    }
+    if from != HUB
+        && to != HUB
+        && let (Some(a), Some(b)) = (pair_rate(&from, HUB).await, pair_rate(HUB, &to).await)
+    {
+        return Ok(a * b);
+    }
+    if is_stablecoin(&from) && is_stablecoin(&to) {
+        return Ok(1.0);
+    }
+    Err(CurrencyError::NoRate { from, to })
+}
+
+pub async fn convert(amount: f64, from: &str, to: &str) -> Result<f64, CurrencyError> {
+    if amount == 0.0 {
+        return Ok(0.0);
+    }
+    Ok(amount * rate(from, to).await?)
+}
+
+/// Converts `amount` of `from` into the reporting currency.
+pub async fn to_reporting(amount: f64, from: &str) -> Result<f64, CurrencyError> {
+    convert(amount, from, &reporting_currency()).await
+}
+
+/// An account's balances valued in one currency.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Valuation {
+    pub currency: String,
+    pub wallet: f64,
+    pub available: f64,
+    /// Assets left out because no price was available.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub unpriced: Vec<String>,
+}
+
+/// Values every non-zero balance in `currency`. Assets without a price are
+/// skipped and listed in [`Valuation::unpriced`].
+pub async fn value_balances(balances: &[AccountBalance], currency: &str) -> Valuation {
+    let parse = |v: &str| v.parse::<f64>().unwrap_or(0.0);
+    let mut valuation = Valuation {
+        currency: currency.to_uppercase(),
+        ..Default::default()
+    };
+    for b in balances {
+        let (wallet, available) = (parse(&b.balance), parse(&b.available_balance));
+        if wallet == 0.0 && available == 0.0 {
+            continue;
+        }
+        match rate(&b.asset, currency).await {
+            Ok(r) => {
+                valuation.wallet += wallet * r;
+                valuation.available += available * r;
+            }
+            Err(e) => {
+                tracing::warn!("⚠️ {} 余额未计入: {}", b.asset, e);
+                valuation.unpriced.push(b.asset.clone());
+            }
+        }
+    }
+    valuation
+}
@@ -0,0 +1,182 @@
+//! Scheduled daily performance reports.
+//!
+//! Once a day a job sums up each trader's previous UTC day: fills, realized
+//! PnL, win rate, the biggest winning and losing close and the AI spend. The
+//! report is stored in `daily_reports` and sent to the notification sinks.
+//! Realized PnL is computed from the trade ledger with average-cost entries
+//! per symbol and side, net of both the entry and exit fees.
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::database::{AiUsage, DailyReport, Database, Trade, TraderRecord};
+use crate::i18n::{self, Locale, Msg};
+use crate::money::Decimal;
+use crate::notify::{self, Event};
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct ReportParams {
+    pub enabled: bool,
+    /// Cron expression in UTC; the report covers the day before the run.
+    pub schedule: String,
+}
+
+impl Default for ReportParams {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            schedule: "5 0 * * *".to_string(),
+        }
+    }
+}
+
+// Open quantity of one symbol and side, with what it cost to enter.
+#[derive(Default)]
+struct Book {
+    quantity: Decimal,
+    cost: Decimal,
+    fees: Decimal,
+}
+
+/// The last complete UTC day before `now`.
+pub fn previous_day(now: DateTime<Utc>) -> NaiveDate {
+    now.date_naive() - Duration::days(1)
+}
+
+/// Builds `trader`'s report for `day` from its trades up to the end of that
+/// day; earlier trades only provide the entry prices of positions closed on
+/// `day`.
+pub fn compile(
+    trader: &TraderRecord,
+    day: NaiveDate,
+    trades: &[Trade],
+    usage: Option<&AiUsage>,
+) -> DailyReport {
+    let start = day.and_time(chrono::NaiveTime::MIN).and_utc();
+    let end = start + Duration::days(1);
+    let mut report = DailyReport {
+        user_id: trader.user_id.clone(),
+        trader_id: trader.id.clone(),
+        day: day.to_string(),
+        ai_calls: usage.map(|u| u.calls).unwrap_or_default(),
+        ai_cost_usd: usage.map(|u| u.cost_usd).unwrap_or_default(),
+        generated_at: Utc::now(),
+        ..Default::default()
+    };
+
+    let mut books: HashMap<(&str, &str), Book> = HashMap::new();
+    for trade in trades.iter().filter(|t| t.executed_at < end) {
+        let today = trade.executed_at >= start;
+        if today {
+            report.trades += 1;
+            report.fees += trade.fee;
+        }
+        let (opening, side) = match trade.action.split_once('_') {
+            Some(("open", side)) => (true, side),
+            Some(("close", side)) => (false, side),
+            _ => continue,
+        };
+        let book = books.entry((&trade.symbol, side)).or_default();
+        if opening {
+            book.quantity += trade.quantity;
+            book.cost += trade.quantity * trade.price;
+            book.fees += trade.fee;
+            continue;
+        }
+
+        let closed = trade.quantity.min(book.quantity);
+        let (entry, entry_fees) = if book.quantity.is_zero() {
+            (Decimal::ZERO, Decimal::ZERO)
+        } else {
+            let share = closed / book.quantity;
+            (book.cost * share, book.fees * share)
+        };
+        book.quantity -= closed;
+        book.cost -= entry;
+        book.fees -= entry_fees;
+        if !today {
+            continue;
+        }
+
+        let gross = trade.price * closed - entry;
+        let gross = if side == "short" { -gross } else { gross };
+        let pnl = gross - entry_fees - trade.fee;
+        report.closed_trades += 1;
+        report.realized_pnl += pnl;
+        if pnl > Decimal::ZERO {
+            report.wins += 1;
+            if pnl > report.best_pnl {
+                report.best_pnl = pnl;
+                report.best_symbol = trade.symbol.clone();
+            }
+        } else if pnl < report.worst_pnl {
+            report.worst_pnl = pnl;
+            report.worst_symbol = trade.symbol.clone();
+        }
+    }
+    report
+}
+
+/// Compiles, stores and sends the reports for `day` of every trader that
+/// traded or called its AI model that day.
+pub async fn generate(db: &Database, day: NaiveDate) -> anyhow::Result<Vec<DailyReport>> {
+    let mut reports = Vec::new();
+    for user_id in db.get_all_users_id().await? {
+        let usage = db.get_ai_usage(&user_id, day).await?;
+        let locale = match db.get_user_by_id(&user_id).await {
+            Ok(Some(user)) => user.locale(),
+            _ => Locale::default(),
+        };
+        for trader in db.get_traders(&user_id).await? {
+            let trades = db.get_trades(&user_id, &trader.id, None).await?;
+            let usage = usage.iter().find(|u| u.trader_id == trader.id);
+            let report = compile(&trader, day, &trades, usage);
+            if report.trades == 0 && report.ai_calls == 0 {
+                continue;
+            }
+            db.save_daily_report(&report).await?;
+            send(&trader, &report, locale);
+            reports.push(report);
+        }
+    }
+    tracing::info!("📊 已生成 {} 份 {} 的每日报告", reports.len(), day);
+    Ok(reports)
+}
+
+/// Reports on the day before now; run by the scheduler.
+pub async fn run(db: &Database) -> anyhow::Result<Vec<DailyReport>> {
+    generate(db, previous_day(Utc::now())).await
+}
+
+fn send(trader: &TraderRecord, report: &DailyReport, locale: Locale) {
+    let close = |symbol: &str, pnl: Decimal| {
+        if symbol.is_empty() {
+            "-".to_string()
+        } else {
+            format!("{} {:+}", symbol, pnl.round_dp(2))
+        }
+    };
+    let message = i18n::render(
+        locale,
+        Msg::DailyReport,
+        &[
+            ("name", &trader.name),
+            ("day", &report.day),
+            ("trades", &report.trades),
+            ("pnl", &report.realized_pnl.round_dp(2)),
+            ("win_rate", &format!("{:.0}", report.win_rate() * 100.0)),
+            ("best", &close(&report.best_symbol, report.best_pnl)),
+            ("worst", &close(&report.worst_symbol, report.worst_pnl)),
+            ("ai_cost", &format!("{:.2}", report.ai_cost_usd)),
+        ],
+    );
+    notify::notify(
+        Event::alert("daily_report", message)
+            .user(&report.user_id)
+            .trader(&report.trader_id)
+            .detail("report", report),
+    );
+}
@@ -1,43 +1,555 @@
-use serde::Deserialize;
+use once_cell::sync::{Lazy, OnceCell};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use thiserror::Error;
+use tokio::sync::Semaphore;
 
-use crate::types::{Data, IntradayData, Kline, LongerTermData, OIData};
+use crate::api_client::{EndpointClass, client_for, timeout_for};
+use crate::aster;
+use crate::cache::{BoundedCache, CacheStats};
+use crate::fallback;
+use crate::hyperliquid;
+use crate::indicators::{self, IndicatorSet};
+use crate::profiler;
+use crate::rate_limit::{RateLimitError, SendLimited};
+use crate::stream;
+use crate::types::{
+    Data, IntradayData, Kline, LongerTermData, MarketDataSource, OIData, Ticker24h, TimeframeData,
+};
 
 #[derive(Error, Debug)]
 pub enum MarketError {
     #[error("Failed to fetch data from API: {0}")]
     RequestError(#[from] reqwest::Error),
+    #[error(transparent)]
+    Send(#[from] RateLimitError),
     #[error("Failed to parse string to float: {0}")]
     ParseFloatError(#[from] std::num::ParseFloatError),
     #[error("Failed to parse JSON response: {0}")]
     ParseJsonError(#[from] serde_json::Error),
     #[error("Insufficient data for calculation: {0}")]
     InsufficientData(String),
+    #[error("Unsupported timeframe: {0}")]
+    UnsupportedTimeframe(String),
+    #[error("{0} is not a listed perpetual")]
+    NotListed(String),
 }
 
+impl MarketError {
+    /// The host that failed, when the error means the host itself is down: a
+    /// connection failure, a timeout or a 5xx response. Errors specific to a
+    /// request, like an unknown symbol or a bad payload, return `None`.
+    pub fn outage_host(&self) -> Option<&str> {
+        let e = match self {
+            MarketError::RequestError(e) | MarketError::Send(RateLimitError::Request(e)) => e,
+            _ => return None,
+        };
+        let down =
+            e.is_connect() || e.is_timeout() || e.status().is_some_and(|s| s.is_server_error());
+        if !down {
+            return None;
+        }
+        e.url().and_then(|u| u.host_str())
+    }
+}
+
+// Candles per interval loaded over REST when a symbol is first used; matches
+// the stream's buffer so it is filled in one go.
+const WARMUP_CANDLES: u16 = 120;
+
+// Recently assembled market data, so several traders watching the same symbol
+// within one scan window share a single set of requests.
+static MARKET_DATA_CACHE: Lazy<BoundedCache<String, Data>> =
+    Lazy::new(|| BoundedCache::new(512, Duration::from_secs(30)));
+
 /// Get market data for a specific symbol.
+#[tracing::instrument(name = "market_data", err)]
 pub async fn get(symbol: &str) -> Result<Data, MarketError> {
     let symbol = normalize(symbol);
+    MARKET_DATA_CACHE
+        .get_or_try_insert(symbol.clone(), || fetch(symbol))
+        .await
+}
+
+// Symbols `get_many` fetches at once; each fetch is itself four requests.
+const FETCH_CONCURRENCY: usize = 4;
+
+// Uncached symbols from which `get_many` first loads the all-symbol endpoints;
+// below this the per-symbol requests are cheaper than the batched ones.
+const BATCH_MIN_SYMBOLS: usize = 4;
+
+// Funding rates of every perpetual from one premiumIndex request, consulted
+// before the per-symbol request.
+static FUNDING_RATES: Lazy<BoundedCache<String, f64>> =
+    Lazy::new(|| BoundedCache::new(2048, Duration::from_secs(30)));
+
+// Perpetuals with a 24h ticker. Listings change rarely and the all-symbol
+// ticker is heavy, so it is kept for a while.
+static LISTED_SYMBOLS: Lazy<BoundedCache<(), Arc<HashSet<String>>>> =
+    Lazy::new(|| BoundedCache::new(1, Duration::from_secs(10 * 60)));
+
+/// Market data for several symbols, keyed by normalized symbol. Symbols are
+/// fetched concurrently, a few at a time. When enough of them are not cached,
+/// the funding rates of every perpetual and the listed symbols are loaded
+/// first with one request each: the per-symbol funding requests are skipped
+/// and unlisted symbols fail without a request.
+pub async fn get_many(symbols: &[&str]) -> HashMap<String, Result<Data, MarketError>> {
+    let mut symbols: Vec<String> = symbols.iter().map(|s| normalize(s)).collect();
+    symbols.sort();
+    symbols.dedup();
+
+    let uncached = symbols
+        .iter()
+        .filter(|s| !MARKET_DATA_CACHE.contains(s))
+        .count();
+    let listed = if uncached >= BATCH_MIN_SYMBOLS && !is_failed_over() {
+        prefetch_batched().await
+    } else {
+        None
+    };
+
+    let permits = Semaphore::new(FETCH_CONCURRENCY);
+    let fetches = symbols.into_iter().map(|symbol| {
+        let permits = &permits;
+        let listed = listed.as_deref();
+        async move {
+            if listed.is_some_and(|l| !l.contains(&symbol)) {
+                return (symbol.clone(), Err(MarketError::NotListed(symbol)));
+            }
+            let _permit = permits.acquire().await.expect("semaphore is never closed");
+            let data = get(&symbol).await;
+            (symbol, data)
+        }
+    });
+    futures_util::future::join_all(fetches)
+        .await
+        .into_iter()
+        .collect()
+}
+
+// Sources that are trading venues with their own market data API, as opposed
+// to Binance and its fallbacks.
+fn is_venue(source: MarketDataSource) -> bool {
+    matches!(
+        source,
+        MarketDataSource::Hyperliquid | MarketDataSource::Aster
+    )
+}
+
+// Cache key of a symbol's data from `source`; Binance data keeps the bare
+// symbol, so the stream and failover paths share it.
+fn cache_key(source: MarketDataSource, symbol: &str) -> String {
+    if is_venue(source) {
+        format!("{:?}:{}", source, symbol)
+    } else {
+        symbol.to_string()
+    }
+}
+
+async fn fetch_venue(source: MarketDataSource, symbol: &str) -> Result<RawMarketData, MarketError> {
+    match source {
+        MarketDataSource::Hyperliquid => hyperliquid::fetch(symbol).await,
+        MarketDataSource::Aster => aster::fetch_market_data(symbol).await,
+        _ => fetch_binance(symbol).await,
+    }
+}
+
+/// Market data for a symbol from `source`'s venue. Binance goes through
+/// [`get`] with its stream and failover; other venues are fetched from their
+/// own APIs and cached separately, with no failover: data from another venue
+/// would be priced differently from the positions it is used for.
+pub async fn get_from(source: MarketDataSource, symbol: &str) -> Result<Data, MarketError> {
+    if !is_venue(source) {
+        return get(symbol).await;
+    }
+    let symbol = normalize(symbol);
+    MARKET_DATA_CACHE
+        .get_or_try_insert(cache_key(source, &symbol), || async {
+            let _timer = profiler::time_stage("market_data");
+            let raw = fetch_venue(source, &symbol).await?;
+            assemble(symbol.clone(), raw, source, false)
+        })
+        .await
+}
+
+/// [`get_many`] from `source`'s venue.
+pub async fn get_many_from(
+    source: MarketDataSource,
+    symbols: &[&str],
+) -> HashMap<String, Result<Data, MarketError>> {
+    if !is_venue(source) {
+        return get_many(symbols).await;
+    }
+    let mut symbols: Vec<String> = symbols.iter().map(|s| normalize(s)).collect();
+    symbols.sort();
+    symbols.dedup();
+
+    let permits = Semaphore::new(FETCH_CONCURRENCY);
+    let fetches = symbols.into_iter().map(|symbol| {
+        let permits = &permits;
+        async move {
+            let _permit = permits.acquire().await.expect("semaphore is never closed");
+            let data = get_from(source, &symbol).await;
+            (symbol, data)
+        }
+    });
+    futures_util::future::join_all(fetches)
+        .await
+        .into_iter()
+        .collect()
+}
+
+// Loads the funding rates of every perpetual into `FUNDING_RATES` and returns
+// the listed symbols. Either failing only costs the batching.
+async fn prefetch_batched() -> Option<Arc<HashSet<String>>> {
+    let (rates, listed) = tokio::join!(
+        get_funding_rates(),
+        LISTED_SYMBOLS.get_or_try_insert((), || async { get_listed_symbols().await.map(Arc::new) })
+    );
+    match rates {
+        Ok(rates) => {
+            for (symbol, rate) in rates {
+                FUNDING_RATES.insert(symbol, rate);
+            }
+        }
+        Err(e) => tracing::warn!("⚠️ 批量资金费率获取失败: {}", e),
+    }
+    listed
+        .inspect_err(|e| tracing::warn!("⚠️ 合约列表获取失败: {}", e))
+        .ok()
+        .filter(|l| !l.is_empty())
+}
+
+/// Kline intervals a trader can ask for on top of the 3m series and 4h context.
+pub const TIMEFRAMES: &[&str] = &[
+    "1m", "3m", "5m", "15m", "30m", "1h", "2h", "4h", "6h", "12h", "1d", "1w",
+];
+
+// Candles fetched per extra timeframe; enough to seed every indicator.
+const TIMEFRAME_CANDLES: u16 = 100;
+
+// Extra timeframes per (symbol, interval), shared like the market data cache.
+static TIMEFRAME_CACHE: Lazy<BoundedCache<(String, String), TimeframeData>> =
+    Lazy::new(|| BoundedCache::new(1024, Duration::from_secs(30)));
+
+/// Parses a trader's comma-separated timeframe list ("15m,1h,1d"), dropping
+/// duplicates. An empty list means only the default 3m/4h data.
+pub fn parse_timeframes(list: &str) -> Result<Vec<String>, MarketError> {
+    let mut timeframes: Vec<String> = Vec::new();
+    for interval in list.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+        if !TIMEFRAMES.contains(&interval) {
+            return Err(MarketError::UnsupportedTimeframe(interval.to_string()));
+        }
+        if !timeframes.iter().any(|t| t == interval) {
+            timeframes.push(interval.to_string());
+        }
+    }
+    Ok(timeframes)
+}
+
+/// Fetches the given timeframes for a symbol concurrently. Timeframes that
+/// fail to load are logged and left out rather than failing the whole set.
+pub async fn get_timeframes(symbol: &str, intervals: &[String]) -> BTreeMap<String, TimeframeData> {
+    get_timeframes_from(MarketDataSource::Binance, symbol, intervals).await
+}
+
+/// [`get_timeframes`] with candles from `source`'s venue.
+pub async fn get_timeframes_from(
+    source: MarketDataSource,
+    symbol: &str,
+    intervals: &[String],
+) -> BTreeMap<String, TimeframeData> {
+    let symbol = normalize(symbol);
+    let key = cache_key(source, &symbol);
+    let fetches = intervals.iter().map(|interval| {
+        let symbol = symbol.clone();
+        let key = key.clone();
+        async move {
+            let data = TIMEFRAME_CACHE
+                .get_or_try_insert((key, interval.clone()), || {
+                    fetch_timeframe(source, &symbol, interval)
+                })
+                .await;
+            (interval, data)
+        }
+    });
+
+    let mut timeframes = BTreeMap::new();
+    for (interval, data) in futures_util::future::join_all(fetches).await {
+        match data {
+            Ok(data) => {
+                timeframes.insert(interval.clone(), data);
+            }
+            Err(e) => tracing::warn!("⚠️ {} {} 周期K线获取失败: {}", symbol, interval, e),
+        }
+    }
+    timeframes
+}
+
+async fn fetch_timeframe(
+    source: MarketDataSource,
+    symbol: &str,
+    interval: &str,
+) -> Result<TimeframeData, MarketError> {
+    let klines = match source {
+        MarketDataSource::Hyperliquid => {
+            let coin = hyperliquid::coin(symbol);
+            hyperliquid::candles(
+                hyperliquid::HYPERLIQUID_URL,
+                &coin,
+                interval,
+                TIMEFRAME_CANDLES,
+            )
+            .await?
+        }
+        MarketDataSource::Aster => {
+            aster::klines(aster::BASE_URL, symbol, interval, TIMEFRAME_CANDLES).await?
+        }
+        // Only the stream's own intervals have buffers; the rest come over REST.
+        _ if stream::INTERVALS.contains(&interval) => {
+            candles(symbol, interval, TIMEFRAME_CANDLES).await?
+        }
+        _ => get_klines(symbol, interval, TIMEFRAME_CANDLES).await?,
+    };
+    Ok(calculate_timeframe_data(interval, &klines))
+}
+
+/// Hit/miss counters of the market data cache.
+pub fn cache_stats() -> CacheStats {
+    MARKET_DATA_CACHE.stats()
+}
+
+/// Candles and derivatives data from one venue, before indicators are computed.
+pub struct RawMarketData {
+    pub klines_3m: Vec<Kline>,
+    pub klines_4h: Vec<Kline>,
+    pub open_interest: Option<OIData>,
+    pub funding_rate: Option<f64>,
+    /// The venue's mark price, when it reports one; otherwise the last 3m close
+    /// is the current price.
+    pub mark_price: Option<f64>,
+}
+
+/// Where market data comes from once Binance has failed repeatedly.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FallbackSource {
+    /// No failover: Binance errors are returned to the caller.
+    None,
+    Bybit,
+    Okx,
+    /// Serve the last successfully assembled data for the symbol.
+    #[default]
+    Snapshot,
+}
 
+/// Consecutive outages of one Binance host before switching to the fallback
+/// source.
+pub const FAILOVER_THRESHOLD: u32 = 3;
+/// How long to stay on the fallback before probing Binance again.
+pub const PRIMARY_RETRY_AFTER: Duration = Duration::from_secs(120);
+
+// Fallback source, can only be set once at startup.
+static FALLBACK_SOURCE: OnceCell<FallbackSource> = OnceCell::new();
+
+static FAILOVER: Lazy<Mutex<Failover>> = Lazy::new(|| Mutex::new(Failover::default()));
+
+// Last good data per symbol, served as a degraded snapshot during outages.
+static SNAPSHOTS: Lazy<BoundedCache<String, Data>> =
+    Lazy::new(|| BoundedCache::new(512, Duration::from_secs(60 * 60)));
+
+/// When to switch market data to the fallback source and back: after
+/// [`FAILOVER_THRESHOLD`] consecutive outages of one Binance host, for
+/// [`PRIMARY_RETRY_AFTER`] before Binance is tried again.
+#[derive(Debug, Default)]
+pub struct Failover {
+    // Consecutive outages per Binance host; any success resets them all.
+    outages: HashMap<String, u32>,
+    // The host that tripped the failover, and when.
+    failed_over: Option<(String, Instant)>,
+}
+
+impl Failover {
+    /// Whether data is being served from the fallback source.
+    pub fn is_failed_over(&self) -> bool {
+        self.failed_over.is_some()
+    }
+
+    /// The host that tripped the failover, while Binance should not be
+    /// tried again yet.
+    pub fn cooling_host(&self, now: Instant) -> Option<&str> {
+        self.failed_over
+            .as_ref()
+            .filter(|(_, at)| now.duration_since(*at) < PRIMARY_RETRY_AFTER)
+            .map(|(host, _)| host.as_str())
+    }
+
+    /// Records a successful Binance fetch; returns true when this ends a failover.
+    pub fn record_success(&mut self) -> bool {
+        self.outages.clear();
+        self.failed_over.take().is_some()
+    }
+
+    /// Records an outage of `host`; returns its consecutive outages when
+    /// they reached the threshold and data should come from the fallback.
+    pub fn record_outage(&mut self, host: &str, now: Instant) -> Option<u32> {
+        let outages = self.outages.entry(host.to_string()).or_insert(0);
+        *outages += 1;
+        let outages = *outages;
+        if outages < FAILOVER_THRESHOLD {
+            return None;
+        }
+        self.failed_over = Some((host.to_string(), now));
+        Some(outages)
+    }
+}
+
+/// Sets the global fallback source.
+/// This function can only be called successfully once.
+pub fn set_fallback_source(source: FallbackSource) {
+    let _ = FALLBACK_SOURCE.set(source);
+}
+
+/// Returns true while market data is being served from the fallback source.
+pub fn is_failed_over() -> bool {
+    lock_failover().is_failed_over()
+}
+
+fn lock_failover() -> std::sync::MutexGuard<'static, Failover> {
+    FAILOVER.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+async fn fetch(symbol: String) -> Result<Data, MarketError> {
+    let _timer = profiler::time_stage("market_data");
+
+    let cooling_host = lock_failover()
+        .cooling_host(Instant::now())
+        .map(str::to_string);
+
+    let primary_err = match cooling_host {
+        None => match fetch_binance(&symbol).await {
+            Ok(raw) => {
+                if lock_failover().record_success() {
+                    tracing::info!("✅ Binance market data recovered, leaving failover");
+                }
+
+                let data = assemble(symbol.clone(), raw, MarketDataSource::Binance, false)?;
+                SNAPSHOTS.insert(symbol, data.clone());
+                return Ok(data);
+            }
+            Err(e) => {
+                // 只有主机宕机（连接失败、超时、5xx）才计入故障转移；
+                // 单个交易对的错误（未上架、解析失败）直接返回
+                let Some(host) = e.outage_host() else {
+                    return Err(e);
+                };
+                let mut state = lock_failover();
+                let was_failed_over = state.is_failed_over();
+                let Some(outages) = state.record_outage(host, Instant::now()) else {
+                    return Err(e);
+                };
+                if !was_failed_over {
+                    tracing::warn!(
+                        "⚠️ Binance host {} failed {} times in a row ({}), switching to fallback",
+                        host,
+                        outages,
+                        e
+                    );
+                }
+                drop(state);
+                e
+            }
+        },
+        Some(host) => {
+            MarketError::InsufficientData(format!("Binance host {} is in failover cooldown", host))
+        }
+    };
+
+    fetch_fallback(symbol, primary_err).await
+}
+
+async fn fetch_fallback(symbol: String, primary_err: MarketError) -> Result<Data, MarketError> {
+    let source = FALLBACK_SOURCE.get().copied().unwrap_or_default();
+    let raw = match source {
+        FallbackSource::None => return Err(primary_err),
+        FallbackSource::Bybit => fallback::fetch_bybit(&symbol).await,
+        FallbackSource::Okx => fallback::fetch_okx(&symbol).await,
+        FallbackSource::Snapshot => {
+            return match SNAPSHOTS.get(&symbol) {
+                Some(mut data) => {
+                    data.source = MarketDataSource::Snapshot;
+                    data.degraded = true;
+                    Ok(data)
+                }
+                None => Err(primary_err),
+            };
+        }
+    };
+
+    let source = match source {
+        FallbackSource::Bybit => MarketDataSource::Bybit,
+        _ => MarketDataSource::Okx,
+    };
+    match raw {
+        Ok(raw) => assemble(symbol, raw, source, true),
+        Err(e) => {
+            tracing::warn!("⚠️ Fallback market data source {:?} failed: {}", source, e);
+            Err(primary_err)
+        }
+    }
+}
+
+async fn fetch_binance(symbol: &str) -> Result<RawMarketData, MarketError> {
+    if stream::is_running() {
+        stream::track(symbol);
+    }
     // Concurrently fetch all required data
-    let (klines3m, klines4h, oi_data, funding_rate) = tokio::try_join!(
-        get_klines(&symbol, "3m", 50), // Fetch more for calculations
-        get_klines(&symbol, "4h", 60), // Fetch more for calculations
-        get_open_interest_data(&symbol),
-        get_funding_rate(&symbol)
+    let (klines_3m, klines_4h, open_interest, funding_rate) = tokio::try_join!(
+        candles(symbol, "3m", 50), // Fetch more for calculations
+        candles(symbol, "4h", 60), // Fetch more for calculations
+        get_open_interest_data(symbol),
+        funding_rate(symbol)
     )?;
 
-    let current_price = klines3m.last().map_or(0.0, |k| k.close);
+    Ok(RawMarketData {
+        klines_3m,
+        klines_4h,
+        open_interest,
+        funding_rate,
+        mark_price: None,
+    })
+}
+
+/// Computes indicators over raw venue data and builds the prompt-facing `Data`.
+fn assemble(
+    symbol: String,
+    raw: RawMarketData,
+    source: MarketDataSource,
+    degraded: bool,
+) -> Result<Data, MarketError> {
+    let RawMarketData {
+        klines_3m: klines3m,
+        klines_4h: klines4h,
+        open_interest,
+        funding_rate,
+        mark_price,
+    } = raw;
+
+    let current_price = mark_price
+        .filter(|p| *p > 0.0)
+        .unwrap_or_else(|| klines3m.last().map_or(0.0, |k| k.close));
     if current_price == 0.0 {
         return Err(MarketError::InsufficientData(
             "Could not get current price from 3m klines.".into(),
         ));
     }
 
-    let current_ema20 = calculate_ema(&klines3m, 20);
-    let current_macd = calculate_macd(&klines3m);
-    let current_rsi7 = calculate_rsi(&klines3m, 7);
+    let current_ema20 = indicators::ema(&klines3m, 20);
+    let current_macd = indicators::macd(&klines3m);
+    let current_rsi7 = indicators::rsi(&klines3m, 7);
 
     // Calculate price change percentages
     let price_change_1h = if klines3m.len() >= 21 {
@@ -64,6 +576,7 @@ pub async fn get(symbol: &str) -> Result<Data, MarketError> {
 
     let intraday_data = calculate_intraday_series(&klines3m);
     let longer_term_data = calculate_longer_term_data(&klines4h);
+    let warming_up = !is_seeded(&klines3m) || !is_seeded(&klines4h);
 
     Ok(Data {
         symbol,
@@ -73,181 +586,198 @@ pub async fn get(symbol: &str) -> Result<Data, MarketError> {
         current_ema20,
         current_macd,
         current_rsi7,
-        open_interest: oi_data,
-        funding_rate: funding_rate.unwrap(),
+        open_interest,
+        funding_rate: funding_rate.unwrap_or(0.0),
         intraday_series: Some(intraday_data),
         longer_term_context: Some(longer_term_data),
+        source,
+        degraded,
+        warming_up,
+        timeframes: BTreeMap::new(),
     })
 }
 
-// --- Indicator Calculations ---
-
-fn calculate_ema(klines: &[Kline], period: usize) -> f64 {
-    if klines.len() < period {
-        return 0.0;
-    }
-    let closes: Vec<f64> = klines.iter().map(|k| k.close).collect();
-
-    // Calculate SMA for the first value
-    let mut ema = closes[..period].iter().sum::<f64>() / period as f64;
-    let multiplier = 2.0 / (period as f64 + 1.0);
-
-    // Calculate EMA for the rest of the values
-    for price in closes[period..].iter() {
-        ema = (price - ema) * multiplier + ema;
-    }
-    ema
-}
-
-fn calculate_macd(klines: &[Kline]) -> f64 {
-    if klines.len() < 26 {
-        return 0.0;
-    }
-    let ema12 = calculate_ema(klines, 12);
-    let ema26 = calculate_ema(klines, 26);
-    ema12 - ema26
+/// True when `klines` are enough to give every indicator of an
+/// [`IndicatorSet`] a value.
+pub fn is_seeded(klines: &[Kline]) -> bool {
+    let mut indicators = IndicatorSet::new();
+    klines.iter().for_each(|k| indicators.update(k));
+    indicators.is_ready()
 }
 
-fn calculate_rsi(klines: &[Kline], period: usize) -> f64 {
-    if klines.len() <= period {
-        return 0.0;
+/// Loads `symbol`'s history before a trader first decides on it. With the
+/// stream connected, a deep REST backfill seeds the candle buffers so every
+/// cycle is served from the stream with fully seeded indicators; otherwise each
+/// fetch already covers the warm-up. The returned data is fresh, and still
+/// flagged as warming up if the venue has too little history.
+pub async fn warm_up(symbol: &str) -> Result<Data, MarketError> {
+    let symbol = normalize(symbol);
+    if stream::is_running() {
+        stream::track(&symbol);
     }
-    let mut gains = 0.0;
-    let mut losses = 0.0;
-
-    for i in 1..=period {
-        let change = klines[i].close - klines[i - 1].close;
-        if change > 0.0 {
-            gains += change;
-        } else {
-            losses += -change;
+    if stream::is_connected() {
+        for interval in stream::INTERVALS {
+            let klines = get_klines(&symbol, interval, WARMUP_CANDLES).await?;
+            stream::backfill(&symbol, interval, &klines);
         }
     }
+    MARKET_DATA_CACHE.remove(&symbol);
+    let data = get(&symbol).await?;
+    log_warm_up(&data);
+    Ok(data)
+}
 
-    let mut avg_gain = gains / period as f64;
-    let mut avg_loss = losses / period as f64;
-
-    for i in (period + 1)..klines.len() {
-        let change = klines[i].close - klines[i - 1].close;
-        let (gain, loss) = if change > 0.0 {
-            (change, 0.0)
-        } else {
-            (0.0, -change)
-        };
-        avg_gain = (avg_gain * (period - 1) as f64 + gain) / period as f64;
-        avg_loss = (avg_loss * (period - 1) as f64 + loss) / period as f64;
-    }
-
-    if avg_loss == 0.0 {
-        return 100.0;
+/// [`warm_up`] from `source`'s venue. Venues other than Binance have no
+/// stream, so every fetch already covers the warm-up.
+pub async fn warm_up_from(source: MarketDataSource, symbol: &str) -> Result<Data, MarketError> {
+    if !is_venue(source) {
+        return warm_up(symbol).await;
     }
-    let rs = avg_gain / avg_loss;
-    100.0 - (100.0 / (1.0 + rs))
+    let symbol = normalize(symbol);
+    MARKET_DATA_CACHE.remove(&cache_key(source, &symbol));
+    let data = get_from(source, &symbol).await?;
+    log_warm_up(&data);
+    Ok(data)
 }
 
-fn calculate_atr(klines: &[Kline], period: usize) -> f64 {
-    if klines.len() <= period {
-        return 0.0;
+fn log_warm_up(data: &Data) {
+    if data.warming_up {
+        tracing::warn!("⏳ {} 历史K线不足，指标预热中，暂不开仓", data.symbol);
+    } else {
+        tracing::info!("🔥 {} 指标预热完成", data.symbol);
     }
-    let mut trs = Vec::with_capacity(klines.len());
-    trs.push(0.0); // No TR for the first candle
-
-    for i in 1..klines.len() {
-        let high = klines[i].high;
-        let low = klines[i].low;
-        let prev_close = klines[i - 1].close;
+}
 
-        let tr1 = high - low;
-        let tr2 = (high - prev_close).abs();
-        let tr3 = (low - prev_close).abs();
+// --- Indicator Calculations ---
 
-        trs.push(tr1.max(tr2).max(tr3));
-    }
+/// Builds the last 10 points of the 3m series in a single pass over the candles.
+pub fn calculate_intraday_series(klines: &[Kline]) -> IntradayData {
+    let mut data = IntradayData::default();
+    let start = klines.len().saturating_sub(10);
 
-    // Initial ATR is a simple moving average
-    let mut atr = trs[1..=period].iter().sum::<f64>() / period as f64;
+    let mut indicators = IndicatorSet::new();
+    for (i, kline) in klines.iter().enumerate() {
+        indicators.update(kline);
+        if i < start {
+            continue;
+        }
 
-    // Wilder's smoothing
-    for i in (period + 1)..trs.len() {
-        atr = (atr * (period - 1) as f64 + trs[i]) / period as f64;
+        data.mid_prices.push(kline.close);
+        data.ema20_values.extend(indicators.ema20.value());
+        data.macd_values.extend(indicators.macd.value());
+        data.rsi7_values.extend(indicators.rsi7.value());
+        data.rsi14_values.extend(indicators.rsi14.value());
+        data.bollinger_values.extend(indicators.bb20.value());
+        if let Some((k, d)) = indicators.stoch14.value() {
+            data.stoch_k_values.push(k);
+            data.stoch_d_values.push(d);
+        }
+        data.vwap_values.extend(indicators.vwap.value());
     }
-
-    atr
+    data
 }
 
-fn calculate_intraday_series(klines: &[Kline]) -> IntradayData {
-    let mut data = IntradayData::default();
+/// Builds the 4h context in a single pass over the candles.
+pub fn calculate_longer_term_data(klines: &[Kline]) -> LongerTermData {
+    let mut data = LongerTermData::default();
     let total_len = klines.len();
     if total_len == 0 {
         return data;
     }
 
     let start = total_len.saturating_sub(10);
-
-    for i in start..total_len {
-        let kline_slice = &klines[..=i];
-        data.mid_prices.push(kline_slice.last().unwrap().close);
-
-        if kline_slice.len() >= 20 {
-            data.ema20_values.push(calculate_ema(kline_slice, 20));
+    let mut indicators = IndicatorSet::new();
+    for (i, kline) in klines.iter().enumerate() {
+        indicators.update(kline);
+        if i < start {
+            continue;
         }
-        if kline_slice.len() >= 26 {
-            data.macd_values.push(calculate_macd(kline_slice));
-        }
-        if kline_slice.len() > 7 {
-            data.rsi7_values.push(calculate_rsi(kline_slice, 7));
-        }
-        if kline_slice.len() > 14 {
-            data.rsi14_values.push(calculate_rsi(kline_slice, 14));
-        }
-    }
-    data
-}
 
-fn calculate_longer_term_data(klines: &[Kline]) -> LongerTermData {
-    let mut data = LongerTermData::default();
-    let total_len = klines.len();
-    if total_len == 0 {
-        return data;
+        data.macd_values.extend(indicators.macd.value());
+        data.rsi14_values.extend(indicators.rsi14.value());
     }
 
-    data.ema20 = calculate_ema(klines, 20);
-    data.ema50 = calculate_ema(klines, 50);
-    data.atr3 = calculate_atr(klines, 3);
-    data.atr14 = calculate_atr(klines, 14);
+    data.ema20 = indicators.ema20.value().unwrap_or(0.0);
+    data.ema50 = indicators.ema50.value().unwrap_or(0.0);
+    data.atr3 = indicators.atr3.value().unwrap_or(0.0);
+    data.atr14 = indicators.atr14.value().unwrap_or(0.0);
+    data.bollinger = indicators.bb20.value().unwrap_or_default();
+    (data.stoch_k, data.stoch_d) = indicators.stoch14.value().unwrap_or_default();
+    data.vwap = indicators.vwap.value().unwrap_or(0.0);
 
     data.current_volume = klines.last().map_or(0.0, |k| k.volume);
     let volume_sum: f64 = klines.iter().map(|k| k.volume).sum();
-    data.average_volume = if !klines.is_empty() {
-        volume_sum / klines.len() as f64
-    } else {
-        0.0
+    data.average_volume = volume_sum / total_len as f64;
+    data
+}
+
+/// Builds one extra timeframe's indicators in a single pass over the candles.
+pub fn calculate_timeframe_data(interval: &str, klines: &[Kline]) -> TimeframeData {
+    let mut data = TimeframeData {
+        interval: interval.to_string(),
+        ..Default::default()
     };
+    let mut indicators = IndicatorSet::new();
+    klines.iter().for_each(|k| indicators.update(k));
 
-    let start = total_len.saturating_sub(10);
-    for i in start..total_len {
-        let kline_slice = &klines[..=i];
-        if kline_slice.len() >= 26 {
-            data.macd_values.push(calculate_macd(kline_slice));
-        }
-        if kline_slice.len() > 14 {
-            data.rsi14_values.push(calculate_rsi(kline_slice, 14));
-        }
+    data.closes = klines[klines.len().saturating_sub(10)..]
+        .iter()
+        .map(|k| k.close)
+        .collect();
+    if let [.., previous, last] = klines
+        && previous.close > 0.0
+    {
+        data.price_change = (last.close - previous.close) / previous.close * 100.0;
     }
+    data.ema20 = indicators.ema20.value().unwrap_or(0.0);
+    data.ema50 = indicators.ema50.value().unwrap_or(0.0);
+    data.macd = indicators.macd.value().unwrap_or(0.0);
+    data.rsi14 = indicators.rsi14.value().unwrap_or(0.0);
+    data.atr14 = indicators.atr14.value().unwrap_or(0.0);
+    data.bollinger = indicators.bb20.value().unwrap_or_default();
+    (data.stoch_k, data.stoch_d) = indicators.stoch14.value().unwrap_or_default();
+    data.warming_up = !indicators.is_ready();
     data
 }
 
 // --- API Fetchers ---
 
+// Candles from the WebSocket buffers, falling back to REST; a REST fetch seeds
+// the buffer so later cycles are served from the stream.
+async fn candles(symbol: &str, interval: &str, limit: u16) -> Result<Vec<Kline>, MarketError> {
+    if let Some(klines) = stream::klines(symbol, interval, limit as usize) {
+        return Ok(klines);
+    }
+    let klines = get_klines(symbol, interval, limit).await?;
+    stream::backfill(symbol, interval, &klines);
+    Ok(klines)
+}
+
+async fn funding_rate(symbol: &str) -> Result<Option<f64>, MarketError> {
+    match stream::funding_rate(symbol).or_else(|| FUNDING_RATES.get(&symbol.to_string())) {
+        Some(rate) => Ok(Some(rate)),
+        None => get_funding_rate(symbol).await,
+    }
+}
+
+#[tracing::instrument(name = "exchange_request", fields(endpoint = "klines"), err)]
 async fn get_klines(symbol: &str, interval: &str, limit: u16) -> Result<Vec<Kline>, MarketError> {
     let url = format!(
         "https://api.binance.com/api/v3/klines?symbol={}&interval={}&limit={}",
         symbol, interval, limit
     );
-    let klines = reqwest::get(&url).await?.json::<Vec<Kline>>().await?;
+    let klines = client_for(EndpointClass::MarketData)
+        .get(&url)
+        .timeout(timeout_for(EndpointClass::MarketData))
+        .send_limited()
+        .await?
+        .error_for_status()?
+        .json::<Vec<Kline>>()
+        .await?;
     Ok(klines)
 }
 
+#[tracing::instrument(name = "exchange_request", fields(endpoint = "openInterest"), err)]
 async fn get_open_interest_data(symbol: &str) -> Result<Option<OIData>, MarketError> {
     #[derive(Deserialize)]
     #[serde(rename_all = "camelCase")]
@@ -259,7 +789,11 @@ async fn get_open_interest_data(symbol: &str) -> Result<Option<OIData>, MarketEr
         symbol
     );
 
-    let resp = reqwest::get(&url).await?;
+    let resp = client_for(EndpointClass::MarketData)
+        .get(&url)
+        .timeout(timeout_for(EndpointClass::MarketData))
+        .send_limited()
+        .await?;
     if !resp.status().is_success() {
         return Ok(None); // API might fail (e.g., for spot symbols), return None
     }
@@ -273,6 +807,7 @@ async fn get_open_interest_data(symbol: &str) -> Result<Option<OIData>, MarketEr
     }))
 }
 
+#[tracing::instrument(name = "exchange_request", fields(endpoint = "premiumIndex"), err)]
 async fn get_funding_rate(symbol: &str) -> Result<Option<f64>, MarketError> {
     #[derive(Deserialize)]
     #[serde(rename_all = "camelCase")]
@@ -284,7 +819,11 @@ async fn get_funding_rate(symbol: &str) -> Result<Option<f64>, MarketError> {
         symbol
     );
 
-    let resp = reqwest::get(&url).await?;
+    let resp = client_for(EndpointClass::MarketData)
+        .get(&url)
+        .timeout(timeout_for(EndpointClass::MarketData))
+        .send_limited()
+        .await?;
     if !resp.status().is_success() {
         return Ok(None);
     }
@@ -294,12 +833,75 @@ async fn get_funding_rate(symbol: &str) -> Result<Option<f64>, MarketError> {
     Ok(Some(rate))
 }
 
+#[tracing::instrument(name = "exchange_request", fields(endpoint = "premiumIndex"), err)]
+async fn get_funding_rates() -> Result<Vec<(String, f64)>, MarketError> {
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct FundingResponse {
+        symbol: String,
+        last_funding_rate: String,
+    }
+    let url = "https://fapi.binance.com/fapi/v1/premiumIndex";
+
+    let result = client_for(EndpointClass::MarketData)
+        .get(url)
+        .timeout(timeout_for(EndpointClass::MarketData))
+        .send_limited()
+        .await?
+        .error_for_status()?
+        .json::<Vec<FundingResponse>>()
+        .await?;
+    // Delivery contracts have no funding and report an empty rate.
+    Ok(result
+        .into_iter()
+        .filter_map(|r| Some((r.symbol, r.last_funding_rate.parse::<f64>().ok()?)))
+        .collect())
+}
+
+#[tracing::instrument(name = "exchange_request", fields(endpoint = "ticker/24hr"), err)]
+async fn get_listed_symbols() -> Result<HashSet<String>, MarketError> {
+    let url = "https://fapi.binance.com/fapi/v1/ticker/24hr";
+    let tickers = client_for(EndpointClass::MarketData)
+        .get(url)
+        .timeout(timeout_for(EndpointClass::MarketData))
+        .send_limited()
+        .await?
+        .error_for_status()?
+        .json::<Vec<Ticker24h>>()
+        .await?;
+    Ok(tickers.into_iter().map(|t| t.symbol).collect())
+}
+
 // --- Formatting & Helpers ---
 
 /// Formats the market data into a human-readable string.
 pub fn format(data: &Data) -> String {
     let mut s = String::new();
 
+    if data.degraded {
+        let _ = writeln!(
+            s,
+            "⚠️ Market data for {} is degraded (source: {:?}); only manage existing positions.\n",
+            data.symbol, data.source
+        );
+    }
+
+    if data.warming_up {
+        let _ = writeln!(
+            s,
+            "⏳ Indicators for {} are still warming up (not enough candle history); do not open new positions on it yet.\n",
+            data.symbol
+        );
+    }
+
+    if let Some(u) = crate::symbol_watch::unavailable(&data.symbol) {
+        let _ = writeln!(
+            s,
+            "🚫 {} is no longer tradable on the exchange (status: {}); do not open new positions, only close existing ones.\n",
+            data.symbol, u.status
+        );
+    }
+
     let _ = writeln!(
         s,
         "current_price = {:.2}, current_ema20 = {:.3}, current_macd = {:.3}, current_rsi (7 period) = {:.3}\n",
@@ -326,70 +928,120 @@ pub fn format(data: &Data) -> String {
         s,
         "Intraday series (3‑minute intervals, oldest → latest):\n"
     );
-    match &data.intraday_series {
-        Some(intraday_series) => {
-            let _ = writeln!(
-                s,
-                "Mid prices: {}\n",
-                format_float_slice(&intraday_series.mid_prices)
-            );
-            let _ = writeln!(
-                s,
-                "EMA indicators (20‑period): {}\n",
-                format_float_slice(&intraday_series.ema20_values)
-            );
-            let _ = writeln!(
-                s,
-                "MACD indicators: {}\n",
-                format_float_slice(&intraday_series.macd_values)
-            );
-            let _ = writeln!(
-                s,
-                "RSI indicators (7‑Period): {}\n",
-                format_float_slice(&intraday_series.rsi7_values)
-            );
-            let _ = writeln!(
-                s,
-                "RSI indicators (14‑Period): {}\n",
-                format_float_slice(&intraday_series.rsi14_values)
-            );
-        }
-        None => (),
+    if let Some(intraday_series) = &data.intraday_series {
+        let _ = writeln!(
+            s,
+            "Mid prices: {}\n",
+            format_float_slice(&intraday_series.mid_prices)
+        );
+        let _ = writeln!(
+            s,
+            "EMA indicators (20‑period): {}\n",
+            format_float_slice(&intraday_series.ema20_values)
+        );
+        let _ = writeln!(
+            s,
+            "MACD indicators: {}\n",
+            format_float_slice(&intraday_series.macd_values)
+        );
+        let _ = writeln!(
+            s,
+            "RSI indicators (7‑Period): {}\n",
+            format_float_slice(&intraday_series.rsi7_values)
+        );
+        let _ = writeln!(
+            s,
+            "RSI indicators (14‑Period): {}\n",
+            format_float_slice(&intraday_series.rsi14_values)
+        );
+        let bands = &intraday_series.bollinger_values;
+        let _ = writeln!(
+            s,
+            "Bollinger Bands (20, 2): upper {} / middle {} / lower {}\n",
+            format_float_slice(&bands.iter().map(|b| b.upper).collect::<Vec<_>>()),
+            format_float_slice(&bands.iter().map(|b| b.middle).collect::<Vec<_>>()),
+            format_float_slice(&bands.iter().map(|b| b.lower).collect::<Vec<_>>())
+        );
+        let _ = writeln!(
+            s,
+            "Stochastic %K (14): {} vs. %D (3): {}\n",
+            format_float_slice(&intraday_series.stoch_k_values),
+            format_float_slice(&intraday_series.stoch_d_values)
+        );
+        let _ = writeln!(
+            s,
+            "Session VWAP (since 00:00 UTC): {}\n",
+            format_float_slice(&intraday_series.vwap_values)
+        );
     }
 
     let _ = writeln!(s, "Longer‑term context (4‑hour timeframe):\n");
     let ltc = &data.longer_term_context;
-    match ltc {
-        Some(ltc) => {
-            let _ = writeln!(
-                s,
-                "20‑Period EMA: {:.3} vs. 50‑Period EMA: {:.3}\n",
-                &ltc.ema20, &ltc.ema50
-            );
-            let _ = writeln!(
-                s,
-                "3‑Period ATR: {:.3} vs. 14‑Period ATR: {:.3}\n",
-                &ltc.atr3, &ltc.atr14
-            );
-            let _ = writeln!(
-                s,
-                "Current Volume: {:.3} vs. Average Volume: {:.3}\n",
-                &ltc.current_volume, &ltc.average_volume
-            );
+    if let Some(ltc) = ltc {
+        let _ = writeln!(
+            s,
+            "20‑Period EMA: {:.3} vs. 50‑Period EMA: {:.3}\n",
+            &ltc.ema20, &ltc.ema50
+        );
+        let _ = writeln!(
+            s,
+            "3‑Period ATR: {:.3} vs. 14‑Period ATR: {:.3}\n",
+            &ltc.atr3, &ltc.atr14
+        );
+        let _ = writeln!(
+            s,
+            "Current Volume: {:.3} vs. Average Volume: {:.3}\n",
+            &ltc.current_volume, &ltc.average_volume
+        );
 
-            let _ = writeln!(
-                s,
-                "MACD indicators: {}\n",
-                format_float_slice(&ltc.macd_values)
-            );
+        let _ = writeln!(
+            s,
+            "MACD indicators: {}\n",
+            format_float_slice(&ltc.macd_values)
+        );
 
+        let _ = writeln!(
+            s,
+            "RSI indicators (14‑Period): {}\n",
+            format_float_slice(&ltc.rsi14_values)
+        );
+        let _ = writeln!(
+            s,
+            "Bollinger Bands (20, 2): upper {:.3} / middle {:.3} / lower {:.3}\n",
+            ltc.bollinger.upper, ltc.bollinger.middle, ltc.bollinger.lower
+        );
+        let _ = writeln!(
+            s,
+            "Stochastic %K (14): {:.3} vs. %D (3): {:.3}\n",
+            ltc.stoch_k, ltc.stoch_d
+        );
+        let _ = writeln!(s, "Session VWAP (since 00:00 UTC): {:.3}\n", ltc.vwap);
+    }
+
+    for tf in data.timeframes.values() {
+        let _ = writeln!(s, "Additional timeframe ({} candles):\n", tf.interval);
+        if tf.warming_up {
             let _ = writeln!(
                 s,
-                "RSI indicators (14‑Period): {}\n",
-                format_float_slice(&ltc.rsi14_values)
+                "(not enough history yet; indicators may be incomplete)\n"
             );
         }
-        None => (),
+        let _ = writeln!(
+            s,
+            "Closes: {} (last candle {:+.2}%)\n",
+            format_float_slice(&tf.closes),
+            tf.price_change
+        );
+        let _ = writeln!(
+            s,
+            "20‑Period EMA: {:.3} vs. 50‑Period EMA: {:.3}, MACD: {:.3}, RSI (14‑Period): {:.3}, 14‑Period ATR: {:.3}\n",
+            tf.ema20, tf.ema50, tf.macd, tf.rsi14, tf.atr14
+        );
+        let _ = writeln!(
+            s,
+            "Bollinger Bands (20, 2): upper {:.3} / middle {:.3} / lower {:.3}, Stochastic %K (14): {:.3} vs. %D (3): {:.3}\n",
+            tf.bollinger.upper, tf.bollinger.middle, tf.bollinger.lower, tf.stoch_k, tf.stoch_d
+        );
     }
 
     s
@@ -403,10 +1055,5 @@ fn format_float_slice(values: &[f64]) -> String {
 
 /// Normalizes a symbol to its uppercase USDT pair format.
 pub fn normalize(symbol: &str) -> String {
-    let upper = symbol.to_uppercase();
-    if upper.ends_with("USDT") {
-        upper
-    } else {
-        format!("{}USDT", upper)
-    }
+    crate::symbols::normalize(symbol)
 }
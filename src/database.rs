@@ -1,25 +1,282 @@
 use anyhow::{Context, Result};
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, Utc};
+use chrono_tz::Tz;
+use once_cell::sync::Lazy;
 use rand::RngCore;
 use serde::{Deserialize, Serialize};
-use sqlx::{FromRow, Row, SqlitePool, error::DatabaseError, sqlite::SqliteError};
-use std::collections::HashSet;
+use sha2::{Digest, Sha256};
+use sqlx::postgres::{PgConnectOptions, PgConnection, PgPool, PgPoolOptions};
+use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions};
+use sqlx::{FromRow, SqliteConnection, SqlitePool};
+use std::collections::{HashMap, HashSet};
 use std::fs;
+use std::future::Future;
+use std::pin::Pin;
+use std::str::FromStr;
+use std::sync::RwLock;
+
+use crate::auth::Role;
+use crate::i18n::Locale;
+use crate::money::{self, Decimal};
+use crate::performance::{self, EquityCurve, EquityRange};
+use crate::symbols;
+use crate::timezone;
+/// Future returned by a [`Database::read_snapshot`] closure.
+pub type ReadFuture<'c, T> = Pin<Box<dyn Future<Output = Result<T>> + Send + 'c>>;
+
+// 在当前后端的连接池上执行同一段查询代码；每个分支各编译一次，
+// 所以所有查询都同时按 SQLite 和 Postgres 做类型检查
+macro_rules! on_pool {
+    ($pool:expr, |$p:ident| $body:expr) => {
+        match $pool {
+            DbPool::Sqlite($p) => $body,
+            DbPool::Postgres($p) => $body,
+        }
+    };
+}
+
+// 同上，作用于读事务中的连接
+macro_rules! on_conn {
+    ($conn:expr, |$c:ident| $body:expr) => {
+        match $conn {
+            DbConn::Sqlite($c) => $body,
+            DbConn::Postgres($c) => $body,
+        }
+    };
+}
+
+/// The database server behind a [`Database`], detected from the connection string.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    Sqlite,
+    Postgres,
+}
+
+// Postgres 版本的查询语句，按原语句缓存（查询语句都是静态字符串，数量有限）
+static POSTGRES_SQL: Lazy<RwLock<HashMap<&'static str, &'static str>>> =
+    Lazy::new(|| RwLock::new(HashMap::new()));
+
+impl Backend {
+    /// `postgres://` and `postgresql://` URLs select Postgres; anything else
+    /// is a SQLite URL (`sqlite:...`) or file path.
+    pub fn detect(url: &str) -> Self {
+        if url.starts_with("postgres://") || url.starts_with("postgresql://") {
+            Backend::Postgres
+        } else {
+            Backend::Sqlite
+        }
+    }
+
+    /// A query written with `?` placeholders, as this backend expects it.
+    pub fn sql(self, query: &'static str) -> &'static str {
+        if self == Backend::Sqlite {
+            return query;
+        }
+        if let Some(&numbered) = POSTGRES_SQL
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(query)
+        {
+            return numbered;
+        }
+        let numbered: &'static str = Box::leak(number_placeholders(query).into_boxed_str());
+        POSTGRES_SQL
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(query, numbered);
+        numbered
+    }
+}
+
+/// Rewrites `?` placeholders to Postgres' `$1, $2, ...`, leaving quoted
+/// literals alone.
+pub fn number_placeholders(query: &str) -> String {
+    let mut out = String::with_capacity(query.len() + 8);
+    let mut n = 0;
+    let mut quoted = false;
+    for c in query.chars() {
+        match c {
+            '\'' => {
+                quoted = !quoted;
+                out.push(c);
+            }
+            '?' if !quoted => {
+                n += 1;
+                out.push('$');
+                out.push_str(&n.to_string());
+            }
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+// 连接池和连接知道自己对应的后端
+trait Dialect {
+    const BACKEND: Backend;
+}
+
+impl Dialect for SqlitePool {
+    const BACKEND: Backend = Backend::Sqlite;
+}
+
+impl Dialect for SqliteConnection {
+    const BACKEND: Backend = Backend::Sqlite;
+}
+
+impl Dialect for PgPool {
+    const BACKEND: Backend = Backend::Postgres;
+}
 
-use crate::data::normalize;
+impl Dialect for PgConnection {
+    const BACKEND: Backend = Backend::Postgres;
+}
+
+fn backend<D: Dialect + ?Sized>(_: &D) -> Backend {
+    D::BACKEND
+}
+
+// 按执行查询的连接池/连接所属后端改写查询语句
+fn sql<D: Dialect + ?Sized>(_: &D, query: &'static str) -> &'static str {
+    D::BACKEND.sql(query)
+}
+
+#[derive(Clone)]
+enum DbPool {
+    Sqlite(SqlitePool),
+    Postgres(PgPool),
+}
+
+/// A connection inside a [`Database::read_snapshot`] transaction.
+pub enum DbConn<'c> {
+    Sqlite(&'c mut SqliteConnection),
+    Postgres(&'c mut PgConnection),
+}
+
+impl<'c> From<&'c mut SqliteConnection> for DbConn<'c> {
+    fn from(conn: &'c mut SqliteConnection) -> Self {
+        DbConn::Sqlite(conn)
+    }
+}
+
+impl<'c> From<&'c mut PgConnection> for DbConn<'c> {
+    fn from(conn: &'c mut PgConnection) -> Self {
+        DbConn::Postgres(conn)
+    }
+}
+
+/// Connection pools: one for the trading loop and other writes, and a
+/// separate one for analytics reads so heavy reports cannot take all the
+/// connections the order path needs.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DatabaseParams {
+    /// Connections of the main pool.
+    pub max_connections: u32,
+    /// Connections of the analytics read pool.
+    pub read_max_connections: u32,
+    /// Postgres read replica the analytics pool connects to instead of the
+    /// primary.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub read_replica_url: Option<String>,
+}
+
+impl Default for DatabaseParams {
+    fn default() -> Self {
+        Self {
+            max_connections: 10,
+            read_max_connections: 4,
+            read_replica_url: None,
+        }
+    }
+}
+
+// 副本地址中可能含密码，不输出
+impl std::fmt::Debug for DatabaseParams {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DatabaseParams")
+            .field("max_connections", &self.max_connections)
+            .field("read_max_connections", &self.read_max_connections)
+            .field("read_replica", &self.read_replica_url.is_some())
+            .finish()
+    }
+}
+
+#[derive(Clone)]
 pub struct Database {
-    pool: SqlitePool,
+    pool: DbPool,
+    // 分析类查询使用的只读连接池（Postgres 可指向只读副本）
+    read_pool: DbPool,
 }
 
 impl Database {
+    /// Opens the database at `db_path`: a SQLite file path or `sqlite:` URL,
+    /// or a `postgres://` URL. The schema is created if missing.
     pub async fn new(db_path: &str) -> Result<Self> {
-        let pool = SqlitePool::connect(db_path)
-            .await
-            .with_context(|| format!("Failed to open or create database at '{}'", db_path))?;
+        Self::connect(db_path, &DatabaseParams::default()).await
+    }
+
+    /// Like [`new`](Self::new), with the pool sizes and read replica in `params`.
+    pub async fn connect(db_path: &str, params: &DatabaseParams) -> Result<Self> {
+        let (pool, read_pool) = match Backend::detect(db_path) {
+            Backend::Postgres => {
+                let pool = PgPoolOptions::new()
+                    .max_connections(params.max_connections.max(1))
+                    .connect(db_path)
+                    .await
+                    .context("Failed to connect to Postgres")?;
+                let read_url = params.read_replica_url.as_deref().unwrap_or(db_path);
+                if Backend::detect(read_url) != Backend::Postgres {
+                    anyhow::bail!("Read replica must be a postgres:// URL");
+                }
+                let read_options = PgConnectOptions::from_str(read_url)?
+                    .options([("default_transaction_read_only", "on")]);
+                let read_pool = PgPoolOptions::new()
+                    .max_connections(params.read_max_connections.max(1))
+                    .connect_with(read_options)
+                    .await
+                    .context("Failed to connect to the Postgres read pool")?;
+                (DbPool::Postgres(pool), DbPool::Postgres(read_pool))
+            }
+            Backend::Sqlite => {
+                if params.read_replica_url.is_some() {
+                    anyhow::bail!("Read replicas are only supported with Postgres");
+                }
+                // Accept both plain file paths ("config.db") and sqlx URLs ("sqlite::memory:").
+                let options = if db_path.starts_with("sqlite:") {
+                    SqliteConnectOptions::from_str(db_path)?
+                } else {
+                    SqliteConnectOptions::new().filename(db_path)
+                };
+                // Default rows reference the 'default' user, which never exists as a users row,
+                // so foreign keys stay unenforced like the original Go implementation.
+                let mut options = options.create_if_missing(true).foreign_keys(false);
+                // WAL 模式下读不阻塞写；内存数据库不支持 WAL
+                if !db_path.contains(":memory:") && !db_path.contains("mode=memory") {
+                    options = options.journal_mode(SqliteJournalMode::Wal);
+                }
+
+                let pool = SqlitePoolOptions::new()
+                    .max_connections(params.max_connections.max(1))
+                    .connect_with(options.clone())
+                    .await
+                    .with_context(|| {
+                        format!("Failed to open or create database at '{}'", db_path)
+                    })?;
+                let read_pool = SqlitePoolOptions::new()
+                    .max_connections(params.read_max_connections.max(1))
+                    .connect_with(options.pragma("query_only", "ON"))
+                    .await
+                    .with_context(|| {
+                        format!("Failed to open database at '{}' for reading", db_path)
+                    })?;
+                (DbPool::Sqlite(pool), DbPool::Sqlite(read_pool))
+            }
+        };
 
-        let database = Self { pool };
+        let database = Self { pool, read_pool };
 
-        database.create_tables().await.context("创建表失败")?;
+        database.migrate().await.context("数据库迁移失败")?;
         database
             .init_default_data()
             .await
@@ -28,512 +285,528 @@ impl Database {
         Ok(database)
     }
 
-    pub async fn create_tables(&self) -> Result<()> {
-        log::info!("Setting up database schema...");
-
-        // A transaction ensures that all schema setup operations succeed or none do.
-        let mut tx = self.pool.begin().await?;
-
-        const queries: &[&str] = &[
-            // AI模型配置表
-            r#"
-            CREATE TABLE IF NOT EXISTS ai_models (
-                id TEXT PRIMARY KEY,
-                user_id TEXT NOT NULL DEFAULT 'default',
-                name TEXT NOT NULL,
-                provider TEXT NOT NULL,
-                enabled BOOLEAN DEFAULT 0,
-                api_key TEXT DEFAULT '',
-                created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
-                updated_at DATETIME DEFAULT CURRENT_TIMESTAMP,
-                FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
-            )
-            "#,
-            // 交易所配置表
-            r#"
-            CREATE TABLE IF NOT EXISTS exchanges (
-                id TEXT PRIMARY KEY,
-                user_id TEXT NOT NULL DEFAULT 'default',
-                name TEXT NOT NULL,
-                type TEXT NOT NULL, -- 'cex' or 'dex'
-                enabled BOOLEAN DEFAULT 0,
-                api_key TEXT DEFAULT '',
-                secret_key TEXT DEFAULT '',
-                testnet BOOLEAN DEFAULT 0,
-                -- Hyperliquid 特定字段
-                hyperliquid_wallet_addr TEXT DEFAULT '',
-                -- Aster 特定字段
-                aster_user TEXT DEFAULT '',
-                aster_signer TEXT DEFAULT '',
-                aster_private_key TEXT DEFAULT '',
-                created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
-                updated_at DATETIME DEFAULT CURRENT_TIMESTAMP,
-                FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
-            )
-            "#,
-            // 用户信号源配置表
-            r#"
-            CREATE TABLE IF NOT EXISTS user_signal_sources (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                user_id TEXT NOT NULL,
-                coin_pool_url TEXT DEFAULT '',
-                oi_top_url TEXT DEFAULT '',
-                created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
-                updated_at DATETIME DEFAULT CURRENT_TIMESTAMP,
-                FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
-                UNIQUE(user_id)
-            )
-            "#,
-            // 交易员配置表
-            r#"
-            CREATE TABLE IF NOT EXISTS traders (
-                id TEXT PRIMARY KEY,
-                user_id TEXT NOT NULL DEFAULT 'default',
-                name TEXT NOT NULL,
-                ai_model_id TEXT NOT NULL,
-                exchange_id TEXT NOT NULL,
-                initial_balance REAL NOT NULL,
-                scan_interval_minutes INTEGER DEFAULT 3,
-                is_running BOOLEAN DEFAULT 0,
-                btc_eth_leverage INTEGER DEFAULT 5,
-                altcoin_leverage INTEGER DEFAULT 5,
-                trading_symbols TEXT DEFAULT '',
-                use_coin_pool BOOLEAN DEFAULT 0,
-                use_oi_top BOOLEAN DEFAULT 0,
-                created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
-                updated_at DATETIME DEFAULT CURRENT_TIMESTAMP,
-                FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
-                FOREIGN KEY (ai_model_id) REFERENCES ai_models(id),
-                FOREIGN KEY (exchange_id) REFERENCES exchanges(id)
-            )
-            "#,
-            // 用户表
-            r#"
-            CREATE TABLE IF NOT EXISTS users (
-                id TEXT PRIMARY KEY,
-                email TEXT UNIQUE NOT NULL,
-                password_hash TEXT NOT NULL,
-                otp_secret TEXT,
-                otp_verified BOOLEAN DEFAULT 0,
-                created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
-                updated_at DATETIME DEFAULT CURRENT_TIMESTAMP
-            )
-            "#,
-            // 系统配置表
-            r#"
-            CREATE TABLE IF NOT EXISTS system_config (
-                key TEXT PRIMARY KEY,
-                value TEXT NOT NULL,
-                updated_at DATETIME DEFAULT CURRENT_TIMESTAMP
-            )
-            "#,
-            // 内测码表
-            r#"
-            CREATE TABLE IF NOT EXISTS beta_codes (
-                code TEXT PRIMARY KEY,
-                used BOOLEAN DEFAULT 0,
-                used_by TEXT DEFAULT '',
-                used_at DATETIME DEFAULT NULL,
-                created_at DATETIME DEFAULT CURRENT_TIMESTAMP
-            )
-            "#,
-        ];
-
-        // 触发器：自动更新 updated_at
-        let triggers: &[&str] = &[
-            r#"
-            CREATE TRIGGER IF NOT EXISTS update_users_updated_at
-			AFTER UPDATE ON users
-			BEGIN
-				UPDATE users SET updated_at = CURRENT_TIMESTAMP WHERE id = NEW.id;
-			END
-            "#,
-            r#"
-            CREATE TRIGGER IF NOT EXISTS update_ai_models_updated_at
-			AFTER UPDATE ON ai_models
-			BEGIN
-				UPDATE ai_models SET updated_at = CURRENT_TIMESTAMP WHERE id = NEW.id;
-			END
-            "#,
-            r#"
-            CREATE TRIGGER IF NOT EXISTS update_exchanges_updated_at
-			AFTER UPDATE ON exchanges
-			BEGIN
-				UPDATE exchanges SET updated_at = CURRENT_TIMESTAMP WHERE id = NEW.id;
-			END
-            "#,
-            r#"
-            CREATE TRIGGER IF NOT EXISTS update_traders_updated_at
-			AFTER UPDATE ON traders
-			BEGIN
-				UPDATE traders SET updated_at = CURRENT_TIMESTAMP WHERE id = NEW.id;
-			END
-            "#,
-            r#"
-            CREATE TRIGGER IF NOT EXISTS update_user_signal_sources_updated_at
-			AFTER UPDATE ON user_signal_sources
-			BEGIN
-				UPDATE user_signal_sources SET updated_at = CURRENT_TIMESTAMP WHERE id = NEW.id;
-			END
-            "#,
-            r#"
-            CREATE TRIGGER IF NOT EXISTS update_system_config_updated_at
-			AFTER UPDATE ON system_config
-			BEGIN
-				UPDATE system_config SET updated_at = CURRENT_TIMESTAMP WHERE key = NEW.key;
-			END
-            "#,
-        ];
+    /// A handle whose queries all go to the analytics read pool. Dashboards,
+    /// reports and other heavy reads use it so they never wait for, or hold,
+    /// connections of the trading loop. Writes through it fail.
+    pub fn analytics(&self) -> Database {
+        Self {
+            pool: self.read_pool.clone(),
+            read_pool: self.read_pool.clone(),
+        }
+    }
 
-        for query in queries.iter().chain(triggers) {
-            sqlx::query(query).execute(&mut *tx).await?;
+    pub fn backend(&self) -> Backend {
+        match self.pool {
+            DbPool::Sqlite(_) => Backend::Sqlite,
+            DbPool::Postgres(_) => Backend::Postgres,
         }
+    }
 
-        tx.commit()
-            .await
-            .context("Failed to commit schema creation transaction")?;
-
-        let alter_quries: &[&str] = &[
-            r#"ALTER TABLE exchanges ADD COLUMN hyperliquid_wallet_addr TEXT DEFAULT ''"#,
-            r#"ALTER TABLE exchanges ADD COLUMN aster_user TEXT DEFAULT ''"#,
-            r#"ALTER TABLE exchanges ADD COLUMN aster_signer TEXT DEFAULT ''"#,
-            r#"ALTER TABLE exchanges ADD COLUMN aster_private_key TEXT DEFAULT ''"#,
-            r#"ALTER TABLE traders ADD COLUMN custom_prompt TEXT DEFAULT ''"#,
-            r#"ALTER TABLE traders ADD COLUMN override_base_prompt BOOLEAN DEFAULT 0"#,
-            r#"ALTER TABLE traders ADD COLUMN is_cross_margin BOOLEAN DEFAULT 1"#,
-            r#"ALTER TABLE traders ADD COLUMN use_default_coins BOOLEAN DEFAULT 1"#,
-            r#"ALTER TABLE traders ADD COLUMN custom_coins TEXT DEFAULT ''"#,
-            r#"ALTER TABLE traders ADD COLUMN btc_eth_leverage INTEGER DEFAULT 5"#,
-            r#"ALTER TABLE traders ADD COLUMN altcoin_leverage INTEGER DEFAULT 5"#,
-            r#"ALTER TABLE traders ADD COLUMN trading_symbols TEXT DEFAULT ''"#,
-            r#"ALTER TABLE traders ADD COLUMN use_coin_pool BOOLEAN DEFAULT 0"#,
-            r#"ALTER TABLE traders ADD COLUMN use_oi_top BOOLEAN DEFAULT 0"#,
-            r#"ALTER TABLE traders ADD COLUMN system_prompt_template TEXT DEFAULT 'default'"#,
-            r#"ALTER TABLE ai_models ADD COLUMN custom_api_url TEXT DEFAULT ''"#,
-            r#"ALTER TABLE ai_models ADD COLUMN custom_model_name TEXT DEFAULT ''"#,
-        ];
-
-        for query in alter_quries {
-            match sqlx::query(&query).execute(&self.pool).await {
-                Ok(_) => log::debug!("Successfully applied alteration: {}", query),
-                Err(sqlx::Error::Database(db_err)) => {
-                    let sqlite_err = db_err.downcast_ref::<SqliteError>();
-                    // Now we know it's a SqliteError. Check the message.
-                    if sqlite_err.message().contains("duplicate column name") {
-                        log::trace!("Column already exists, skipping alteration: {}", query);
-                    } else {
-                        // It's a different SQLite error. We need to own the message
-                        // before passing it to anyhow.
-                        let error_message: String = sqlite_err.message().to_string(); // <-- THE FIX
+    // 当前数据库已应用的迁移版本，0 表示尚未迁移
+    pub async fn schema_version(&self) -> Result<i64> {
+        on_pool!(&self.pool, |pool| {
+            let version: i64 =
+                sqlx::query_scalar("SELECT COALESCE(MAX(version), 0) FROM schema_version")
+                    .fetch_one(pool)
+                    .await?;
+            Ok(version)
+        })
+    }
 
-                        return Err(anyhow::anyhow!(error_message)
-                            .context(format!("Failed to execute alteration query: {}", query)));
-                    }
+    // 按版本号顺序执行尚未应用的迁移，每个迁移在独立事务中执行并记录到 schema_version，
+    // 返回迁移后的版本
+    pub async fn migrate(&self) -> Result<i64> {
+        on_pool!(&self.pool, |pool| {
+            let mut tx = pool.begin().await?;
+            lock_schema(&mut DbConn::from(&mut *tx)).await?;
+            let ddl = match backend(pool) {
+                Backend::Sqlite => SQLITE_SCHEMA_VERSION,
+                Backend::Postgres => POSTGRES_SCHEMA_VERSION,
+            };
+            sqlx::query(ddl).execute(&mut *tx).await?;
+            tx.commit().await?;
+
+            for migration in MIGRATIONS {
+                let mut tx = pool.begin().await?;
+                // 多个实例同时启动时串行执行，并在锁内重新检查是否已被其他实例应用
+                lock_schema(&mut DbConn::from(&mut *tx)).await?;
+                let applied: i64 = sqlx::query_scalar(sql(
+                    pool,
+                    "SELECT COUNT(*) FROM schema_version WHERE version = ?",
+                ))
+                .bind(migration.version)
+                .fetch_one(&mut *tx)
+                .await?;
+                if applied > 0 {
+                    continue;
                 }
-                Err(e) => return Err(e.into()),
-            }
-        }
 
-        if let Err(e) = self.migrate_exchange_table().await {
-            log::warn!("⚠️ 迁移exchanges表失败: {e:?}");
-        }
+                (migration.run)(DbConn::from(&mut *tx))
+                    .await
+                    .with_context(|| {
+                        format!(
+                            "Migration {} ({}) failed",
+                            migration.version, migration.name
+                        )
+                    })?;
+                sqlx::query(sql(
+                    pool,
+                    "INSERT INTO schema_version (version, name) VALUES (?, ?)",
+                ))
+                .bind(migration.version)
+                .bind(migration.name)
+                .execute(&mut *tx)
+                .await?;
+                tx.commit()
+                    .await
+                    .with_context(|| format!("Failed to commit migration {}", migration.version))?;
+                tracing::info!(
+                    "🗄️ 已应用数据库迁移 {} ({})",
+                    migration.version,
+                    migration.name
+                );
+            }
 
-        Ok(())
+            self.schema_version().await
+        })
     }
 
     pub async fn init_default_data(&self) -> Result<()> {
-        let mut tx = self
-            .pool
-            .begin()
-            .await
-            .context("Failed to begin transaction for default data initialization")?;
-
-        const AI_MODELS: &[(&str, &str, &str)] = &[
-            ("deepseek", "DeepSeek", "deepseek"),
-            ("qwen", "Qwen", "qwen"),
-        ];
-
-        for &(id, name, provider) in AI_MODELS {
-            sqlx::query(
-                r#"
-                INSERT OR IGNORE INTO ai_models (id, user_id, name, provider, enabled) 
-                VALUES (?, 'default', ?, ?, 0)
+        on_pool!(&self.pool, |pool| {
+            let mut tx = pool
+                .begin()
+                .await
+                .context("Failed to begin transaction for default data initialization")?;
+
+            const AI_MODELS: &[(&str, &str, &str)] = &[
+                ("deepseek", "DeepSeek", "deepseek"),
+                ("qwen", "Qwen", "qwen"),
+                ("claude", "Claude", "anthropic"),
+                ("gemini", "Gemini", "gemini"),
+            ];
+
+            for &(id, name, provider) in AI_MODELS {
+                sqlx::query(sql(
+                    pool,
+                    r#"
+                INSERT INTO ai_models (id, user_id, name, provider, enabled) 
+                VALUES (?, 'default', ?, ?, FALSE) ON CONFLICT DO NOTHING
             "#,
-            )
-            .bind(id)
-            .bind(name)
-            .bind(provider)
-            .execute(&mut *tx)
-            .await
-            .context("Failed to initialize default AI models")?;
-        }
-
-        const EXCHANGES: &[(&str, &str, &str)] = &[
-            ("binance", "Binance Futures", "binance"),
-            ("hyperliquid", "Hyperliquid", "hyperliquid"),
-            ("aster", "Aster DEX", "aster"),
-        ];
+                ))
+                .bind(id)
+                .bind(name)
+                .bind(provider)
+                .execute(&mut *tx)
+                .await
+                .context("Failed to initialize default AI models")?;
+            }
 
-        for &(id, name, typ) in EXCHANGES {
-            sqlx::query(
-                r#"
-                INSERT OR IGNORE INTO exchanges (id, user_id, name, type, enabled) 
-                VALUES (?, 'default', ?, ?, 0)
+            const EXCHANGES: &[(&str, &str, &str)] = &[
+                ("binance", "Binance Futures", "binance"),
+                ("hyperliquid", "Hyperliquid", "hyperliquid"),
+                ("aster", "Aster DEX", "aster"),
+            ];
+
+            for &(id, name, typ) in EXCHANGES {
+                sqlx::query(sql(
+                    pool,
+                    r#"
+                INSERT INTO exchanges (id, user_id, name, type, enabled) 
+                VALUES (?, 'default', ?, ?, FALSE) ON CONFLICT DO NOTHING
             "#,
-            )
-            .bind(id)
-            .bind(name)
-            .bind(typ)
-            .execute(&mut *tx)
-            .await
-            .context("Failed to initialize default exchanges")?;
-        }
+                ))
+                .bind(id)
+                .bind(name)
+                .bind(typ)
+                .execute(&mut *tx)
+                .await
+                .context("Failed to initialize default exchanges")?;
+            }
 
-        const SYSTEM_CONFIGS: &[(&str, &str)] = &[
-            ("admin_mode", "true"),
-            ("beta_mode", "false"),
-            ("api_server_port", "8080"),
-            ("use_default_coins", "true"),
-            (
-                "default_coins",
-                r#"["BTCUSDT","ETHUSDT","SOLUSDT","BNBUSDT","XRPUSDT","DOGEUSDT","ADAUSDT","HYPEUSDT"]"#,
-            ),
-            ("max_daily_loss", "10.0"),
-            ("max_drawdown", "20.0"),
-            ("stop_trading_minutes", "60"),
-            ("btc_eth_leverage", "5"),
-            ("altcoin_leverage", "5"),
-            ("jwt_secret", ""),
-        ];
-
-        for &(key, value) in SYSTEM_CONFIGS {
-            sqlx::query("INSERT OR IGNORE INTO system_config (key, value) VALUES (?, ?)")
+            const SYSTEM_CONFIGS: &[(&str, &str)] = &[
+                ("admin_mode", "false"),
+                ("beta_mode", "false"),
+                ("api_server_port", "8080"),
+                ("use_default_coins", "true"),
+                (
+                    "default_coins",
+                    r#"["BTCUSDT","ETHUSDT","SOLUSDT","BNBUSDT","XRPUSDT","DOGEUSDT","ADAUSDT","HYPEUSDT"]"#,
+                ),
+                ("max_daily_loss", "10.0"),
+                ("max_drawdown", "20.0"),
+                ("stop_trading_minutes", "60"),
+                ("btc_eth_leverage", "5"),
+                ("altcoin_leverage", "5"),
+                ("jwt_secret", ""),
+            ];
+
+            for &(key, value) in SYSTEM_CONFIGS {
+                sqlx::query(sql(
+                    pool,
+                    "INSERT INTO system_config (key, value) VALUES (?, ?) ON CONFLICT DO NOTHING",
+                ))
                 .bind(key)
                 .bind(value)
                 .execute(&mut *tx)
                 .await
                 .context("Failed to initialize system configurations")?;
-        }
+            }
 
-        tx.commit()
-            .await
-            .context("Failed to commit transaction for default data")?;
+            tx.commit()
+                .await
+                .context("Failed to commit transaction for default data")?;
 
-        Ok(())
+            Ok(())
+        })
     }
 
-    pub async fn migrate_exchange_table(&self) -> Result<()> {
-        // 检查是否已经迁移过
-        let pk_count: i64 = match sqlx::query_scalar(
-            "SELECT COUNT(*) FROM pragma_table_info('exchanges') WHERE pk > 0",
+    pub async fn create_user(&self, user: &User) -> Result<()> {
+        on_pool!(&self.pool, |pool| {
+            sqlx::query(
+            sql(pool, r#"INSERT INTO users (id, email, password_hash, otp_secret, otp_verified, locale, timezone, role)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?)"#),
         )
-        .fetch_one(&self.pool)
+        .bind(&user.id)
+        .bind(&user.email)
+        .bind(&user.password_hash)
+        .bind(&user.otp_secret)
+        .bind(user.otp_verified)
+        .bind(user.locale().as_str())
+        .bind(user.tz().name())
+        .bind(user.role().as_str())
+        .execute(pool)
         .await
-        {
-            Ok(count) => count,
-            Err(sqlx::Error::Database(db_err)) if db_err.message().contains("no such table") => {
-                return Ok(());
-            }
-            Err(e) => return Err(e.into()),
-        };
+        .context("failed to create user")?;
 
-        // 如果已经迁移过，直接返回
-        if pk_count >= 2 {
-            return Ok(());
-        }
+            Ok(())
+        })
+    }
 
-        let mut tx = self
-            .pool
-            .begin()
+    pub async fn ensure_admin_user(&self) -> Result<()> {
+        on_pool!(&self.pool, |pool| {
+            let result = sqlx::query(sql(
+                pool,
+                r#"
+                INSERT INTO users (id, email, password_hash, otp_secret, otp_verified, role)
+                VALUES ('admin', 'admin@localhost', '', '', TRUE, 'admin') ON CONFLICT DO NOTHING
+            "#,
+            ))
+            .execute(pool)
             .await
-            .context("Failed to begin migration transaction")?;
+            .context("Failed to ensure admin user exists")?;
 
-        log::info!("🔄 开始迁移exchanges表...");
-
-        // 创建新的exchanges表，使用复合主键
-        sqlx::query(
-            r#"
-            CREATE TABLE exchanges_new (
-                id TEXT NOT NULL,
-                user_id TEXT NOT NULL DEFAULT 'default',
-                name TEXT NOT NULL,
-                type TEXT NOT NULL,
-                enabled BOOLEAN DEFAULT 0,
-                api_key TEXT DEFAULT '',
-                secret_key TEXT DEFAULT '',
-                testnet BOOLEAN DEFAULT 0,
-                hyperliquid_wallet_addr TEXT DEFAULT '',
-                aster_user TEXT DEFAULT '',
-                aster_signer TEXT DEFAULT '',
-                aster_private_key TEXT DEFAULT '',
-                created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
-                updated_at DATETIME DEFAULT CURRENT_TIMESTAMP,
-                PRIMARY KEY (id, user_id),
-                FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
-            )
-        "#,
-        )
-        .execute(&mut *tx)
-        .await
-        .context("Failed to create new 'exchanges_new' table")?;
+            if result.rows_affected() > 0 {
+                tracing::info!("Admin user did not exist and was created.");
+            } else {
+                tracing::info!("Admin user already exists.");
+            }
 
-        // 复制数据到新表
-        sqlx::query("INSERT INTO exchanges_new SELECT * FROM exchanges")
-            .execute(&mut *tx)
-            .await
-            .context("Failed to copy data from 'exchanges' to 'exchanges_new'")?;
+            Ok(())
+        })
+    }
 
-        // 删除旧表
-        sqlx::query("DROP TABLE exchanges")
-            .execute(&mut *tx)
-            .await
-            .context("Failed to drop old 'exchanges' table")?;
+    pub async fn get_user_by_email(&self, email: &str) -> Result<Option<User>> {
+        on_pool!(&self.pool, |pool| {
+            let user_result =
+                sqlx::query_as::<_, User>(sql(pool, "SELECT * FROM users WHERE email = ?"))
+                    .bind(email)
+                    .fetch_optional(pool)
+                    .await;
+
+            match user_result {
+                Ok(user) => Ok(user),
+                Err(e) => {
+                    // If an error occurs, wrap it with context for better debugging.
+                    Err(e).context(format!("Failed to fetch user with email: {}", email))
+                }
+            }
+        })
+    }
 
-        // 重命名新表
-        sqlx::query("ALTER TABLE exchanges_new RENAME TO exchanges")
-            .execute(&mut *tx)
-            .await
-            .context("Failed to rename 'exchanges_new' to 'exchanges'")?;
+    pub async fn get_user_by_id(&self, user_id: &str) -> Result<Option<User>> {
+        on_pool!(&self.pool, |pool| {
+            let user_result =
+                sqlx::query_as::<_, User>(sql(pool, "SELECT * FROM users WHERE id = ?"))
+                    .bind(user_id)
+                    .fetch_optional(pool)
+                    .await;
+
+            match user_result {
+                Ok(user) => Ok(user),
+                Err(e) => {
+                    // If an error occurs, wrap it with context for better debugging.
+                    Err(e).context(format!("Failed to fetch user with id: {}", user_id))
+                }
+            }
+        })
+    }
 
-        // 重新创建触发器
-        sqlx::query(
-            r#"
-            CREATE TRIGGER IF NOT EXISTS update_exchanges_updated_at
-                AFTER UPDATE ON exchanges
-                BEGIN
-                    UPDATE exchanges SET updated_at = CURRENT_TIMESTAMP 
-                    WHERE id = NEW.id AND user_id = NEW.user_id;
-                END
-        "#,
-        )
-        .execute(&mut *tx)
-        .await
-        .context("Failed to recreate 'update_exchanges_updated_at' trigger")?;
+    // 更新用户密码
+    pub async fn update_user_password(&self, user_id: &str, password_hash: &str) -> Result<()> {
+        on_pool!(&self.pool, |pool| {
+            let result = sqlx::query(sql(pool, "UPDATE users SET password_hash = ? WHERE id = ?"))
+                .bind(password_hash)
+                .bind(user_id)
+                .execute(pool)
+                .await
+                .context("Failed to update user password")?;
 
-        tx.commit()
-            .await
-            .context("Failed to commit migration transaction")?;
+            if result.rows_affected() == 0 {
+                anyhow::bail!("user '{}' not found", user_id);
+            }
+
+            Ok(())
+        })
+    }
 
-        log::info!("✅ exchanges表迁移完成");
+    // 更新用户角色
+    pub async fn update_user_role(&self, user_id: &str, role: Role) -> Result<()> {
+        on_pool!(&self.pool, |pool| {
+            let result = sqlx::query(sql(pool, "UPDATE users SET role = ? WHERE id = ?"))
+                .bind(role.as_str())
+                .bind(user_id)
+                .execute(pool)
+                .await
+                .context("Failed to update user role")?;
 
-        Ok(())
+            if result.rows_affected() == 0 {
+                anyhow::bail!("user '{}' not found", user_id);
+            }
+
+            Ok(())
+        })
     }
 
-    pub async fn create_user(&self, user: &User) -> Result<()> {
-        sqlx::query(
-            r#"INSERT INTO users (id, email, password_hash, otp_secret, otp_verified)
-            VALUES (?, ?, ?, ?, ?)"#,
-        )
-        .bind(&user.id)
-        .bind(&user.email)
-        .bind(&user.password_hash)
-        .bind(&user.otp_secret)
-        .bind(user.otp_verified)
-        .execute(&self.pool)
-        .await
-        .context("failed to create user")?;
+    // 获取全部用户（按注册时间）
+    pub async fn get_all_users(&self) -> Result<Vec<User>> {
+        on_pool!(&self.pool, |pool| {
+            let users =
+                sqlx::query_as::<_, User>(sql(pool, "SELECT * FROM users ORDER BY created_at, id"))
+                    .fetch_all(pool)
+                    .await
+                    .context("Failed to fetch all users")?;
+
+            Ok(users)
+        })
+    }
 
-        Ok(())
+    // 更新用户语言偏好
+    pub async fn update_user_locale(&self, user_id: &str, locale: Locale) -> Result<()> {
+        on_pool!(&self.pool, |pool| {
+            sqlx::query(sql(pool, "UPDATE users SET locale = ? WHERE id = ?"))
+                .bind(locale.as_str())
+                .bind(user_id)
+                .execute(pool)
+                .await
+                .context("Failed to update user locale")?;
+
+            Ok(())
+        })
     }
 
-    pub async fn ensure_admin_user(&self) -> Result<()> {
-        let result = sqlx::query(
-            r#"
-                INSERT OR IGNORE INTO users (id, email, password_hash, otp_secret, otp_verified)
-                VALUES ('admin', 'admin@localhost', '', '', 1)
-            "#,
-        )
-        .execute(&self.pool)
-        .await
-        .context("Failed to ensure admin user exists")?;
+    // 更新用户时区
+    pub async fn update_user_timezone(&self, user_id: &str, timezone: &str) -> Result<()> {
+        on_pool!(&self.pool, |pool| {
+            let tz = timezone::parse_tz(timezone)?;
+            sqlx::query(sql(pool, "UPDATE users SET timezone = ? WHERE id = ?"))
+                .bind(tz.name())
+                .bind(user_id)
+                .execute(pool)
+                .await
+                .context("Failed to update user timezone")?;
 
-        if result.rows_affected() > 0 {
-            log::info!("Admin user did not exist and was created.");
-        } else {
-            log::info!("Admin user already exists.");
-        }
+            Ok(())
+        })
+    }
 
-        Ok(())
+    // 更新用户的组合保证金占用上限
+    pub async fn update_user_margin_ceiling(&self, user_id: &str, max_pct: f64) -> Result<()> {
+        on_pool!(&self.pool, |pool| {
+            if !(0.0..=100.0).contains(&max_pct) {
+                anyhow::bail!("margin ceiling must be between 0 and 100, got {}", max_pct);
+            }
+            sqlx::query(sql(
+                pool,
+                "UPDATE users SET max_margin_usage_pct = ? WHERE id = ?",
+            ))
+            .bind(max_pct)
+            .bind(user_id)
+            .execute(pool)
+            .await
+            .context("Failed to update user margin ceiling")?;
+
+            Ok(())
+        })
     }
 
-    pub async fn get_user_by_email(&self, email: &str) -> Result<Option<User>> {
-        let user_result = sqlx::query_as::<_, User>("SELECT * FROM users WHERE email = ?")
-            .bind(email)
-            .fetch_optional(&self.pool)
-            .await;
+    pub async fn get_all_users_id(&self) -> Result<Vec<String>> {
+        on_pool!(&self.pool, |pool| {
+            let user_ids =
+                sqlx::query_scalar::<_, String>(sql(pool, "SELECT id FROM users ORDER BY id"))
+                    .fetch_all(pool)
+                    .await
+                    .context("Failed to fetch all user IDs from the database")?;
+
+            Ok(user_ids)
+        })
+    }
+
+    // 更新用户OTP验证状态
+    pub async fn update_user_ota_verified(&self, user_id: &str, verified: bool) -> Result<()> {
+        on_pool!(&self.pool, |pool| {
+            let result = sqlx::query(sql(pool, "UPDATE users SET otp_verified = ? WHERE id = ?"))
+                .bind(verified)
+                .bind(user_id)
+                .execute(pool)
+                .await
+                .context("Failed to update user OTP verification status")?;
 
-        match user_result {
-            Ok(user) => Ok(user),
-            Err(e) => {
-                // If an error occurs, wrap it with context for better debugging.
-                Err(e).context(format!("Failed to fetch user with email: {}", email))
+            if result.rows_affected() == 0 {
+                tracing::warn!(
+                    "Attempted to update OTP status for non-existent user_id: {}",
+                    user_id
+                );
             }
-        }
+
+            Ok(())
+        })
     }
 
-    pub async fn get_user_by_id(&self, user_id: &str) -> Result<Option<User>> {
-        let user_result = sqlx::query_as::<_, User>("SELECT * FROM users WHERE id = ?")
-            .bind(user_id)
-            .fetch_optional(&self.pool)
-            .await;
+    // 重置用户OTP：换上新密钥、标记为未验证，并作废全部恢复码
+    pub async fn reset_user_otp(&self, user_id: &str, otp_secret: &str) -> Result<()> {
+        on_pool!(&self.pool, |pool| {
+            let mut tx = pool.begin().await.context("Failed to begin transaction")?;
 
-        match user_result {
-            Ok(user) => Ok(user),
-            Err(e) => {
-                // If an error occurs, wrap it with context for better debugging.
-                Err(e).context(format!("Failed to fetch user with id: {}", user_id))
+            let result = sqlx::query(sql(
+                pool,
+                "UPDATE users SET otp_secret = ?, otp_verified = FALSE WHERE id = ?",
+            ))
+            .bind(otp_secret)
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await
+            .context("Failed to reset user OTP secret")?;
+            if result.rows_affected() == 0 {
+                anyhow::bail!("user '{}' not found", user_id);
             }
-        }
+
+            sqlx::query(sql(
+                pool,
+                "DELETE FROM otp_recovery_codes WHERE user_id = ?",
+            ))
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await
+            .context("Failed to delete OTP recovery codes")?;
+
+            tx.commit().await.context("Failed to commit OTP reset")?;
+            Ok(())
+        })
     }
 
-    pub async fn get_all_users_id(&self) -> Result<Vec<String>> {
-        let user_ids = sqlx::query_scalar::<_, String>("SELECT id FROM users ORDER BY id")
-            .fetch_all(&self.pool)
+    // 替换用户的OTP恢复码（只保存哈希），旧码全部作废
+    pub async fn replace_otp_recovery_codes(
+        &self,
+        user_id: &str,
+        code_hashes: &[String],
+    ) -> Result<()> {
+        on_pool!(&self.pool, |pool| {
+            let mut tx = pool.begin().await.context("Failed to begin transaction")?;
+
+            sqlx::query(sql(
+                pool,
+                "DELETE FROM otp_recovery_codes WHERE user_id = ?",
+            ))
+            .bind(user_id)
+            .execute(&mut *tx)
             .await
-            .context("Failed to fetch all user IDs from the database")?;
+            .context("Failed to delete OTP recovery codes")?;
+
+            let created_at = Utc::now();
+            for code_hash in code_hashes {
+                sqlx::query(sql(
+                    pool,
+                    r#"INSERT INTO otp_recovery_codes (user_id, code_hash, created_at)
+                VALUES (?, ?, ?) ON CONFLICT DO NOTHING"#,
+                ))
+                .bind(user_id)
+                .bind(code_hash)
+                .bind(created_at)
+                .execute(&mut *tx)
+                .await
+                .context("Failed to save OTP recovery code")?;
+            }
 
-        Ok(user_ids)
+            tx.commit()
+                .await
+                .context("Failed to commit OTP recovery codes")?;
+            Ok(())
+        })
     }
 
-    // 更新用户OTP验证状态
-    pub async fn update_user_ota_verified(&self, user_id: &str, verified: bool) -> Result<()> {
-        let result = sqlx::query("UPDATE users SET otp_verified = ? WHERE id =?")
+    // 使用一个恢复码：未使用过则标记为已用并返回 true，每个码只能用一次
+    pub async fn consume_otp_recovery_code(
+        &self,
+        user_id: &str,
+        code_hash: &str,
+        used_at: DateTime<Utc>,
+    ) -> Result<bool> {
+        on_pool!(&self.pool, |pool| {
+            let result = sqlx::query(sql(
+                pool,
+                r#"UPDATE otp_recovery_codes SET used_at = ?
+            WHERE user_id = ? AND code_hash = ? AND used_at IS NULL"#,
+            ))
+            .bind(used_at)
             .bind(user_id)
-            .bind(verified)
-            .execute(&self.pool)
+            .bind(code_hash)
+            .execute(pool)
             .await
-            .context("Failed to update user OTP verification status")?;
+            .context("Failed to consume OTP recovery code")?;
 
-        if result.rows_affected() == 0 {
-            log::warn!(
-                "Attempted to update OTP status for non-existent user_id: {}",
-                user_id
-            );
-        }
+            Ok(result.rows_affected() > 0)
+        })
+    }
 
-        Ok(())
+    // 用户剩余可用的恢复码数量
+    pub async fn count_otp_recovery_codes(&self, user_id: &str) -> Result<i64> {
+        on_pool!(&self.pool, |pool| {
+            let count: i64 = sqlx::query_scalar(sql(
+                pool,
+                "SELECT COUNT(*) FROM otp_recovery_codes WHERE user_id = ? AND used_at IS NULL",
+            ))
+            .bind(user_id)
+            .fetch_one(pool)
+            .await
+            .context("Failed to count OTP recovery codes")?;
+
+            Ok(count)
+        })
     }
 
     // 获取用户的AI模型配置
     pub async fn get_aimodels(&self, user_id: &str) -> Result<Vec<AIModelConfig>> {
-        let results = sqlx::query_as::<_, AIModelConfig>(
-            r#"SELECT id, user_id, name, provider, enabled, api_key,
+        on_pool!(&self.pool, |pool| {
+            let results = sqlx::query_as::<_, AIModelConfig>(sql(
+                pool,
+                r#"SELECT id, user_id, name, provider, enabled, api_key,
 		        COALESCE(custom_api_url, '') as custom_api_url,
 		        COALESCE(custom_model_name, '') as custom_model_name,
 		        created_at, updated_at
 		    FROM ai_models WHERE user_id = ? ORDER BY id"#,
-        )
-        .bind(user_id)
-        .fetch_all(&self.pool)
-        .await;
-
-        match results {
-            Ok(aimodels) => Ok(aimodels),
-            Err(e) => Err(e).context(format!(
-                "Failed to fetch aimodels with user_id: {}",
-                user_id
-            )),
-        }
+            ))
+            .bind(user_id)
+            .fetch_all(pool)
+            .await;
+
+            match results {
+                Ok(aimodels) => Ok(aimodels),
+                Err(e) => Err(e).context(format!(
+                    "Failed to fetch aimodels with user_id: {}",
+                    user_id
+                )),
+            }
+        })
     }
 
     // 更新AI模型配置，如果不存在则创建用户特定配置
@@ -546,26 +819,24 @@ impl Database {
         custom_api_url: &str,
         custom_model_name: &str,
     ) -> Result<()> {
-        let mut tx = self
-            .pool
-            .begin()
-            .await
-            .context("Failed to begin transaction")?;
-
-        // 先尝试精确匹配 ID（新版逻辑，支持多个相同 provider 的模型）
-        let maybe_id = sqlx::query_scalar::<_, String>(
-            "SELECT id FROM ai_models WHERE user_id = ? AND id = ? LIMIT 1",
-        )
-        .bind(user_id)
-        .bind(id)
-        .fetch_optional(&mut *tx)
-        .await?;
+        on_pool!(&self.pool, |pool| {
+            let mut tx = pool.begin().await.context("Failed to begin transaction")?;
+
+            // 先尝试精确匹配 ID（新版逻辑，支持多个相同 provider 的模型）
+            let maybe_id = sqlx::query_scalar::<_, String>(sql(
+                pool,
+                "SELECT id FROM ai_models WHERE user_id = ? AND id = ? LIMIT 1",
+            ))
+            .bind(user_id)
+            .bind(id)
+            .fetch_optional(&mut *tx)
+            .await?;
 
-        // 找到了现有配置（精确匹配 ID），更新它
-        if let Some(existing_id) = maybe_id {
-            sqlx::query(
-                r#"UPDATE ai_models SET enabled = ?, api_key = ?, custom_api_url = ?, custom_model_name = ?, updated_at = datetime('now')
-			        WHERE id = ? AND user_id = ?"#
+            // 找到了现有配置（精确匹配 ID），更新它
+            if let Some(existing_id) = maybe_id {
+                sqlx::query(
+                sql(pool, r#"UPDATE ai_models SET enabled = ?, api_key = ?, custom_api_url = ?, custom_model_name = ?, updated_at = CURRENT_TIMESTAMP
+			        WHERE id = ? AND user_id = ?"#)
             )
             .bind(enabled)
             .bind(api_key)
@@ -573,33 +844,35 @@ impl Database {
             .bind(custom_model_name)
             .bind(&existing_id)
             .bind(user_id)
-            .execute(&self.pool)
+            .execute(&mut *tx)
             .await?;
-            return Ok(());
-        }
+                tx.commit().await.context("failed to commit update model")?;
+                return Ok(());
+            }
 
-        // ID 不存在，尝试兼容旧逻辑：将 id 作为 provider 查找
-        let provider_as_id = id;
-        let maybe_id_by_provider = sqlx::query_scalar::<_, String>(
-            "SELECT id FROM ai_models WHERE user_id = ? AND provider = ? LIMIT 1",
-        )
-        .bind(user_id)
-        .bind(provider_as_id)
-        .fetch_optional(&mut *tx)
-        .await?;
+            // ID 不存在，尝试兼容旧逻辑：将 id 作为 provider 查找
+            let provider_as_id = id;
+            let maybe_id_by_provider = sqlx::query_scalar::<_, String>(sql(
+                pool,
+                "SELECT id FROM ai_models WHERE user_id = ? AND provider = ? LIMIT 1",
+            ))
+            .bind(user_id)
+            .bind(provider_as_id)
+            .fetch_optional(&mut *tx)
+            .await?;
 
-        // 找到了现有配置（通过 provider 匹配，兼容旧版），更新它
-        if let Some(existing_id) = maybe_id_by_provider {
-            log::info!(
-                "⚠️  使用旧版 provider 匹配更新模型: {} -> {}",
-                id,
-                &existing_id
-            );
-            sqlx::query(r#"
+            // 找到了现有配置（通过 provider 匹配，兼容旧版），更新它
+            if let Some(existing_id) = maybe_id_by_provider {
+                tracing::info!(
+                    "⚠️  使用旧版 provider 匹配更新模型: {} -> {}",
+                    id,
+                    &existing_id
+                );
+                sqlx::query(sql(pool, r#"
                 UPDATE ai_models 
-                SET enabled = ?, api_key = ?, custom_api_url = ?, custom_model_name = ?, updated_at = datetime('now')
+                SET enabled = ?, api_key = ?, custom_api_url = ?, custom_model_name = ?, updated_at = CURRENT_TIMESTAMP
                 WHERE id = ? AND user_id = ?
-                "#,)
+                "#),)
                 .bind(enabled)
                 .bind(api_key)
                 .bind(custom_api_url)
@@ -608,54 +881,56 @@ impl Database {
                 .bind(user_id)
                 .execute(&mut *tx)
                 .await?;
-            return Ok(());
-        }
+                tx.commit().await.context("failed to commit update model")?;
+                return Ok(());
+            }
 
-        // 没有找到任何现有配置，创建新的
-        // 推断 provider（从 id 中提取，或者直接使用 id）
-        let provider = if id == "deepseek" || id == "qwen" {
-            id.to_string()
-        } else {
-            id.split("_").last().unwrap_or(id).to_string()
-        };
+            // 没有找到任何现有配置，创建新的
+            // 推断 provider（从 id 中提取，或者直接使用 id）
+            let provider = if id == "deepseek" || id == "qwen" {
+                id.to_string()
+            } else {
+                id.split("_").last().unwrap_or(id).to_string()
+            };
 
-        // 获取模型的基本信息
-        let maybe_name = sqlx::query_scalar::<_, String>(
-            "SELECT name FROM ai_models WHERE provider = ? LIMIT 1",
-        )
-        .bind(&provider)
-        .fetch_optional(&mut *tx)
-        .await?;
+            // 获取模型的基本信息
+            let maybe_name = sqlx::query_scalar::<_, String>(sql(
+                pool,
+                "SELECT name FROM ai_models WHERE provider = ? LIMIT 1",
+            ))
+            .bind(&provider)
+            .fetch_optional(&mut *tx)
+            .await?;
 
-        let name = match maybe_name {
-            Some(n) => n,
-            None => match provider.as_str() {
-                "deepseek" => "Deepseek AI".to_string(),
-                "qwen" => "Qwen AI".to_string(),
-                p => format!("{} AI", p),
-            },
-        };
+            let name = match maybe_name {
+                Some(n) => n,
+                None => match provider.as_str() {
+                    "deepseek" => "Deepseek AI".to_string(),
+                    "qwen" => "Qwen AI".to_string(),
+                    p => format!("{} AI", p),
+                },
+            };
 
-        let new_model_id = if id == provider {
-            // If the input ID was just a provider, create a user-specific ID.
-            format!("{}_{}", user_id, provider)
-        } else {
-            // Otherwise, use the provided ID as is.
-            id.to_string()
-        };
+            let new_model_id = if id == provider {
+                // If the input ID was just a provider, create a user-specific ID.
+                format!("{}_{}", user_id, provider)
+            } else {
+                // Otherwise, use the provided ID as is.
+                id.to_string()
+            };
 
-        log::info!(
-            "✓ 创建新的 AI 模型配置: ID={}, Provider={}, Name={}",
-            &new_model_id,
-            provider,
-            &name
-        );
+            tracing::info!(
+                "✓ 创建新的 AI 模型配置: ID={}, Provider={}, Name={}",
+                &new_model_id,
+                provider,
+                &name
+            );
 
-        sqlx::query(
-            r#"
+            sqlx::query(
+            sql(pool, r#"
             INSERT INTO ai_models (id, user_id, name, provider, enabled, api_key, custom_api_url, custom_model_name, created_at, updated_at)
-		    VALUES (?, ?, ?, ?, ?, ?, ?, ?, datetime('now'), datetime('now'))
-            "#
+		    VALUES (?, ?, ?, ?, ?, ?, ?, ?, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP)
+            "#)
         )
         .bind(&new_model_id)
         .bind(user_id)
@@ -668,15 +943,18 @@ impl Database {
         .execute(&mut *tx)
         .await?;
 
-        tx.commit().await.context("failed to commit update model")?;
+            tx.commit().await.context("failed to commit update model")?;
 
-        Ok(())
+            Ok(())
+        })
     }
 
     pub async fn get_exchanges(&self, user_id: &str) -> Result<Vec<ExchangeConfig>> {
-        let ecs = sqlx::query_as::<_, ExchangeConfig>(
-            r#"
-            SELECT id, user_id, name, type, enabled, api_key, secret_key, testnet, 
+        on_pool!(&self.pool, |pool| {
+            let ecs = sqlx::query_as::<_, ExchangeConfig>(sql(
+                pool,
+                r#"
+            SELECT id, user_id, name, type AS exchange_type, enabled, api_key, secret_key, testnet,
 		       COALESCE(hyperliquid_wallet_addr, '') as hyperliquid_wallet_addr,
 		       COALESCE(aster_user, '') as aster_user,
 		       COALESCE(aster_signer, '') as aster_signer,
@@ -684,14 +962,16 @@ impl Database {
 		       created_at, updated_at 
 		FROM exchanges WHERE user_id = ? ORDER BY id
             "#,
-        )
-        .bind(user_id)
-        .fetch_all(&self.pool)
-        .await?;
+            ))
+            .bind(user_id)
+            .fetch_all(pool)
+            .await?;
 
-        Ok(ecs)
+            Ok(ecs)
+        })
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub async fn update_exchange(
         &self,
         user_id: &str,
@@ -705,61 +985,63 @@ impl Database {
         aster_signer: &str,
         aster_private_key: &str,
     ) -> Result<()> {
-        log::info!(
-            "🔧 UpdateExchange: userID={}, id={}, enabled={}",
-            user_id,
-            id,
-            enabled
-        );
-
-        let result = sqlx::query(
-            r#"
+        on_pool!(&self.pool, |pool| {
+            tracing::info!(
+                "🔧 UpdateExchange: userID={}, id={}, enabled={}",
+                user_id,
+                id,
+                enabled
+            );
+
+            let result = sqlx::query(
+            sql(pool, r#"
             UPDATE exchanges SET enabled = ?, api_key = ?, secret_key = ?, testnet = ?, 
-		       hyperliquid_wallet_addr = ?, aster_user = ?, aster_signer = ?, aster_private_key = ?, updated_at = datetime('now')
+		       hyperliquid_wallet_addr = ?, aster_user = ?, aster_signer = ?, aster_private_key = ?, updated_at = CURRENT_TIMESTAMP
 		    WHERE id = ? AND user_id = ?
-            "#)
+            "#))
             .bind(enabled)
             .bind(api_key)
             .bind(secret_key)
+            .bind(testnet)
             .bind(hyperliquid_wallet_addr)
             .bind(aster_user)
             .bind(aster_signer)
             .bind(aster_private_key)
             .bind(id)
             .bind(user_id)
-            .execute(&self.pool)
+            .execute(pool)
             .await?;
 
-        if result.rows_affected() > 0 {
-            log::info!("📊 UpdateExchange: 影响行数 = {}", result.rows_affected());
-        } else {
-            let (name, typ) = match id {
-                "binance" => ("Binance Futures", "cex"),
-                "hyperliquid" => ("Hyperliquid", "dex"),
-                "aster" => ("Aster DEX", "dex"),
-                _ => ("-", "cex"),
-            };
-
-            let final_name = if name == "-" {
-                format!("{} Exchange", id)
+            if result.rows_affected() > 0 {
+                tracing::info!("📊 UpdateExchange: 影响行数 = {}", result.rows_affected());
             } else {
-                name.to_string()
-            };
-
-            log::info!(
-                "🆕 UpdateExchange: 创建新记录 ID={}, name={}, type={}",
-                id,
-                name,
-                typ
-            );
-
-            // 创建用户特定的配置，使用原始的交易所ID
-            sqlx::query(
-                r#"
+                let (name, typ) = match id {
+                    "binance" => ("Binance Futures", "cex"),
+                    "hyperliquid" => ("Hyperliquid", "dex"),
+                    "aster" => ("Aster DEX", "dex"),
+                    _ => ("-", "cex"),
+                };
+
+                let final_name = if name == "-" {
+                    format!("{} Exchange", id)
+                } else {
+                    name.to_string()
+                };
+
+                tracing::info!(
+                    "🆕 UpdateExchange: 创建新记录 ID={}, name={}, type={}",
+                    id,
+                    name,
+                    typ
+                );
+
+                // 创建用户特定的配置，使用原始的交易所ID
+                sqlx::query(
+                sql(pool, r#"
                 INSERT INTO exchanges (id, user_id, name, type, enabled, api_key, secret_key, testnet, 
 			                       hyperliquid_wallet_addr, aster_user, aster_signer, aster_private_key, created_at, updated_at)
-			VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, datetime('now'), datetime('now'))
-                "#,
+			VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP)
+                "#),
             )
             .bind(id)
             .bind(user_id)
@@ -773,19 +1055,21 @@ impl Database {
             .bind(aster_user)
             .bind(aster_signer)
             .bind(aster_private_key)
-            .execute(&self.pool)
+            .execute(pool)
             .await
             .map(|_| {
-                log::info!("✅ UpdateExchange: created record successfully");
+                tracing::info!("✅ UpdateExchange: created record successfully");
             })
             .map_err(|e| {
-                log::error!("❌ UpdateExchange: failed to create record: {}", e);
+                tracing::error!("❌ UpdateExchange: failed to create record: {}", e);
                 e
             })?;
-        }
-        Ok(())
+            }
+            Ok(())
+        })
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub async fn create_ai_model(
         &self,
         user_id: &str,
@@ -796,25 +1080,29 @@ impl Database {
         api_key: &str,
         custom_api_url: &str,
     ) -> Result<()> {
-        sqlx::query(
-            r#"
-            INSERT OR IGNORE INTO ai_models (id, user_id, name, provider, enabled, api_key, custom_api_url) 
-		    VALUES (?, ?, ?, ?, ?, ?, ?)
+        on_pool!(&self.pool, |pool| {
+            sqlx::query(sql(
+                pool,
+                r#"
+            INSERT INTO ai_models (id, user_id, name, provider, enabled, api_key, custom_api_url) 
+		    VALUES (?, ?, ?, ?, ?, ?, ?) ON CONFLICT DO NOTHING
             "#,
-        )
-        .bind(id)
-        .bind(user_id)
-        .bind(name)
-        .bind(provider)
-        .bind(enabled)
-        .bind(api_key)
-        .bind(custom_api_url)
-        .execute(&self.pool)
-        .await?;
+            ))
+            .bind(id)
+            .bind(user_id)
+            .bind(name)
+            .bind(provider)
+            .bind(enabled)
+            .bind(api_key)
+            .bind(custom_api_url)
+            .execute(pool)
+            .await?;
 
-        Ok(())
+            Ok(())
+        })
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub async fn create_exchange(
         &self,
         user_id: &str,
@@ -830,11 +1118,12 @@ impl Database {
         aster_signer: &str,
         aster_private_key: &str,
     ) -> Result<()> {
-        sqlx::query(
-            r#"
-            INSERT OR IGNORE INTO exchanges (id, user_id, name, type, enabled, api_key, secret_key, testnet, hyperliquid_wallet_addr, aster_user, aster_signer, aster_private_key) 
-		    VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
-            "#
+        on_pool!(&self.pool, |pool| {
+            sqlx::query(
+            sql(pool, r#"
+            INSERT INTO exchanges (id, user_id, name, type, enabled, api_key, secret_key, testnet, hyperliquid_wallet_addr, aster_user, aster_signer, aster_private_key) 
+		    VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?) ON CONFLICT DO NOTHING
+            "#)
         )
         .bind(id)
         .bind(user_id)
@@ -848,98 +1137,176 @@ impl Database {
         .bind(aster_user)
         .bind(aster_signer)
         .bind(aster_private_key)
-        .execute(&self.pool)
+        .execute(pool)
         .await?;
 
-        Ok(())
+            Ok(())
+        })
     }
 
     pub async fn create_trader(&self, trader: &TraderRecord) -> Result<()> {
-        sqlx::query(
-            r#"
-            INSERT INTO traders (id, user_id, name, ai_model_id, exchange_id, initial_balance, scan_interval_minutes, is_running, btc_eth_leverage, altcoin_leverage, trading_symbols, use_coin_pool, use_oi_top, custom_prompt, override_base_prompt, system_prompt_template, is_cross_margin)
-		    VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
-            "#
+        on_pool!(&self.pool, |pool| {
+            sqlx::query(
+            sql(pool, r#"
+            INSERT INTO traders (id, user_id, name, ai_model_id, exchange_id, initial_balance, scan_interval_minutes, is_running, btc_eth_leverage, altcoin_leverage, trading_symbols, use_coin_pool, use_oi_top, custom_prompt, override_base_prompt, system_prompt_template, is_cross_margin, quote_assets, stop_loss_cooldown_minutes, watch_only, approval_threshold_usd, approval_ttl_minutes, timeframes, position_sizing, experiment_id, variant, liquidation_alert_pct, margin_ratio_alert_pct, auto_deleverage_pct, custom_coins, strategy, trading_windows)
+		    VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            "#)
         )
         .bind(&trader.id)
         .bind(&trader.user_id)
         .bind(&trader.name)
         .bind(&trader.ai_model_id)
         .bind(&trader.exchange_id)
-        .bind(&trader.initial_balance)
-        .bind(&trader.scan_interval_minutes)
-        .bind(&trader.is_running)
-        .bind(&trader.btc_eth_leverage)
-        .bind(&trader.altcoin_leverage)
+        .bind(trader.initial_balance)
+        .bind(trader.scan_interval_minutes)
+        .bind(trader.is_running)
+        .bind(trader.btc_eth_leverage)
+        .bind(trader.altcoin_leverage)
         .bind(&trader.trading_symbols)
-        .bind(&trader.use_coin_pool)
-        .bind(&trader.use_oi_top)
+        .bind(trader.use_coin_pool)
+        .bind(trader.use_oi_top)
         .bind(&trader.custom_prompt)
-        .bind(&trader.override_base_prompt)
+        .bind(trader.override_base_prompt)
         .bind(&trader.system_prompt_template)
-        .bind(&trader.is_cross_margin)
-        .execute(&self.pool)
+        .bind(trader.is_cross_margin)
+        .bind(&trader.quote_assets)
+        .bind(trader.stop_loss_cooldown_minutes)
+        .bind(trader.watch_only)
+        .bind(trader.approval_threshold_usd)
+        .bind(trader.approval_ttl_minutes)
+        .bind(&trader.timeframes)
+        .bind(&trader.position_sizing)
+        .bind(&trader.experiment_id)
+        .bind(&trader.variant)
+        .bind(trader.liquidation_alert_pct)
+        .bind(trader.margin_ratio_alert_pct)
+        .bind(trader.auto_deleverage_pct)
+        .bind(&trader.custom_coins)
+        .bind(&trader.strategy)
+        .bind(&trader.trading_windows)
+        .execute(pool)
         .await?;
 
-        Ok(())
+            Ok(())
+        })
     }
 
     pub async fn get_traders(&self, user_id: &str) -> Result<Vec<TraderRecord>> {
-        let trs = sqlx::query_as::<_, TraderRecord>(
-            r#"
-            SELECT id, user_id, name, ai_model_id, exchange_id, initial_balance, scan_interval_minutes, is_running,
-		       COALESCE(btc_eth_leverage, 5) as btc_eth_leverage, COALESCE(altcoin_leverage, 5) as altcoin_leverage,
-		       COALESCE(trading_symbols, '') as trading_symbols,
-		       COALESCE(use_coin_pool, 0) as use_coin_pool, COALESCE(use_oi_top, 0) as use_oi_top,
-		       COALESCE(custom_prompt, '') as custom_prompt, COALESCE(override_base_prompt, 0) as override_base_prompt,
-		       COALESCE(system_prompt_template, 'default') as system_prompt_template,
-		       COALESCE(is_cross_margin, 1) as is_cross_margin, created_at, updated_at
-		    FROM traders WHERE user_id = ? ORDER BY created_at DESC
-            "#
-        ).bind(user_id).fetch_all(&self.pool).await?;
+        on_pool!(&self.pool, |pool| {
+            let mut conn = pool.acquire().await?;
+            fetch_traders(&mut DbConn::from(&mut *conn), user_id).await
+        })
+    }
+
+    // 获取所有用户中标记为运行中的交易员（启动时用于崩溃恢复）
+    pub async fn get_running_traders(&self) -> Result<Vec<TraderRecord>> {
+        on_pool!(&self.pool, |pool| {
+            let user_ids = sqlx::query_scalar::<_, String>(sql(
+                pool,
+                "SELECT DISTINCT user_id FROM traders WHERE is_running = TRUE",
+            ))
+            .fetch_all(pool)
+            .await
+            .context("Failed to fetch running traders")?;
+
+            let mut running = Vec::new();
+            for user_id in user_ids {
+                running.extend(
+                    self.get_traders(&user_id)
+                        .await?
+                        .into_iter()
+                        .filter(|t| t.is_running),
+                );
+            }
 
-        Ok(trs)
+            Ok(running)
+        })
     }
 
-    pub async fn update_trader_status(&self, user_id: &str, is_running: bool) -> Result<()> {
-        sqlx::query("UPDATE traders SET is_running = ? WHERE id = ? AND user_id = ?")
-            .bind(user_id)
+    // 获取单个交易员
+    pub async fn get_trader(&self, user_id: &str, id: &str) -> Result<Option<TraderRecord>> {
+        let traders = self.get_traders(user_id).await?;
+
+        Ok(traders.into_iter().find(|t| t.id == id))
+    }
+
+    pub async fn update_trader_status(
+        &self,
+        user_id: &str,
+        id: &str,
+        is_running: bool,
+    ) -> Result<()> {
+        on_pool!(&self.pool, |pool| {
+            let result = sqlx::query(sql(
+                pool,
+                "UPDATE traders SET is_running = ? WHERE id = ? AND user_id = ?",
+            ))
             .bind(is_running)
-            .execute(&self.pool)
+            .bind(id)
+            .bind(user_id)
+            .execute(pool)
             .await?;
 
-        Ok(())
+            if result.rows_affected() == 0 {
+                anyhow::bail!("trader '{}' not found for user '{}'", id, user_id);
+            }
+
+            Ok(())
+        })
     }
 
     pub async fn update_trader(&self, trader: &TraderRecord) -> Result<()> {
-        sqlx::query(
-            r#"
+        on_pool!(&self.pool, |pool| {
+            sqlx::query(sql(
+                pool,
+                r#"
             UPDATE traders SET
 			name = ?, ai_model_id = ?, exchange_id = ?, initial_balance = ?,
 			scan_interval_minutes = ?, btc_eth_leverage = ?, altcoin_leverage = ?,
 			trading_symbols = ?, custom_prompt = ?, override_base_prompt = ?,
-			system_prompt_template = ?, is_cross_margin = ?, updated_at = CURRENT_TIMESTAMP
+			system_prompt_template = ?, is_cross_margin = ?, quote_assets = ?,
+			stop_loss_cooldown_minutes = ?, watch_only = ?, approval_threshold_usd = ?,
+			approval_ttl_minutes = ?, timeframes = ?, position_sizing = ?,
+			experiment_id = ?, variant = ?, liquidation_alert_pct = ?,
+			margin_ratio_alert_pct = ?, auto_deleverage_pct = ?, custom_coins = ?,
+			strategy = ?, trading_windows = ?, updated_at = CURRENT_TIMESTAMP
 		    WHERE id = ? AND user_id = ?
             "#,
-        )
-        .bind(&trader.name)
-        .bind(&trader.ai_model_id)
-        .bind(&trader.exchange_id)
-        .bind(&trader.initial_balance)
-        .bind(&trader.scan_interval_minutes)
-        .bind(&trader.btc_eth_leverage)
-        .bind(&trader.altcoin_leverage)
-        .bind(&trader.trading_symbols)
-        .bind(&trader.custom_prompt)
-        .bind(&trader.override_base_prompt)
-        .bind(&trader.system_prompt_template)
-        .bind(&trader.is_cross_margin)
-        .bind(&trader.id)
-        .bind(&trader.user_id)
-        .execute(&self.pool)
-        .await?;
+            ))
+            .bind(&trader.name)
+            .bind(&trader.ai_model_id)
+            .bind(&trader.exchange_id)
+            .bind(trader.initial_balance)
+            .bind(trader.scan_interval_minutes)
+            .bind(trader.btc_eth_leverage)
+            .bind(trader.altcoin_leverage)
+            .bind(&trader.trading_symbols)
+            .bind(&trader.custom_prompt)
+            .bind(trader.override_base_prompt)
+            .bind(&trader.system_prompt_template)
+            .bind(trader.is_cross_margin)
+            .bind(&trader.quote_assets)
+            .bind(trader.stop_loss_cooldown_minutes)
+            .bind(trader.watch_only)
+            .bind(trader.approval_threshold_usd)
+            .bind(trader.approval_ttl_minutes)
+            .bind(&trader.timeframes)
+            .bind(&trader.position_sizing)
+            .bind(&trader.experiment_id)
+            .bind(&trader.variant)
+            .bind(trader.liquidation_alert_pct)
+            .bind(trader.margin_ratio_alert_pct)
+            .bind(trader.auto_deleverage_pct)
+            .bind(&trader.custom_coins)
+            .bind(&trader.strategy)
+            .bind(&trader.trading_windows)
+            .bind(&trader.id)
+            .bind(&trader.user_id)
+            .execute(pool)
+            .await?;
 
-        Ok(())
+            Ok(())
+        })
     }
 
     pub async fn update_trader_custom_prompt(
@@ -949,115 +1316,453 @@ impl Database {
         custom_prompt: &str,
         override_base: bool,
     ) -> Result<()> {
-        sqlx::query("UPDATE traders SET custom_prompt = ?, override_base_prompt = ? WHERE id = ? AND user_id = ?")
-            .bind(user_id)
-            .bind(id)
+        on_pool!(&self.pool, |pool| {
+            sqlx::query(sql(pool, "UPDATE traders SET custom_prompt = ?, override_base_prompt = ? WHERE id = ? AND user_id = ?"))
             .bind(custom_prompt)
             .bind(override_base)
-            .execute(&self.pool)
+            .bind(id)
+            .bind(user_id)
+            .execute(pool)
             .await?;
 
-        Ok(())
+            Ok(())
+        })
     }
 
-    pub async fn delete_trader(&self, user_id: &str, id: &str) -> Result<()> {
-        sqlx::query("DELETE FROM traders WHERE id = ? AND user_id = ?")
+    // 更新交易员提示词相关字段（自定义提示词、是否覆盖基础提示词、系统提示词模板）
+    pub async fn update_trader_prompt(
+        &self,
+        user_id: &str,
+        id: &str,
+        custom_prompt: &str,
+        override_base: bool,
+        system_prompt_template: &str,
+    ) -> Result<()> {
+        on_pool!(&self.pool, |pool| {
+            sqlx::query(sql(pool, "UPDATE traders SET custom_prompt = ?, override_base_prompt = ?, system_prompt_template = ? WHERE id = ? AND user_id = ?"))
+            .bind(custom_prompt)
+            .bind(override_base)
+            .bind(system_prompt_template)
+            .bind(id)
+            .bind(user_id)
+            .execute(pool)
+            .await?;
+
+            Ok(())
+        })
+    }
+
+    // 记录一个新的提示词版本，版本号为该交易员当前最大版本号+1
+    #[allow(clippy::too_many_arguments)]
+    pub async fn record_prompt_version(
+        &self,
+        user_id: &str,
+        trader_id: &str,
+        custom_prompt: &str,
+        override_base: bool,
+        system_prompt_template: &str,
+        diff: &str,
+        author: &str,
+        note: &str,
+    ) -> Result<PromptVersion> {
+        on_pool!(&self.pool, |pool| {
+            let version = sqlx::query_as::<_, PromptVersion>(sql(
+                pool,
+                r#"INSERT INTO prompt_versions (user_id, trader_id, version, custom_prompt, override_base_prompt,
+                system_prompt_template, diff, author, note)
+            SELECT ?, ?, COALESCE(MAX(version), 0) + 1, ?, ?, ?, ?, ?, ?
+            FROM prompt_versions WHERE user_id = ? AND trader_id = ?
+            RETURNING *"#,
+            ))
+            .bind(user_id)
+            .bind(trader_id)
+            .bind(custom_prompt)
+            .bind(override_base)
+            .bind(system_prompt_template)
+            .bind(diff)
+            .bind(author)
+            .bind(note)
+            .bind(user_id)
+            .bind(trader_id)
+            .fetch_one(pool)
+            .await
+            .context("Failed to record prompt version")?;
+
+            Ok(version)
+        })
+    }
+
+    // 获取交易员的提示词版本历史（最新版本在前）
+    pub async fn get_prompt_versions(
+        &self,
+        user_id: &str,
+        trader_id: &str,
+    ) -> Result<Vec<PromptVersion>> {
+        on_pool!(&self.pool, |pool| {
+            let versions = sqlx::query_as::<_, PromptVersion>(sql(
+                pool,
+                "SELECT * FROM prompt_versions WHERE user_id = ? AND trader_id = ? ORDER BY version DESC",
+            ))
+            .bind(user_id)
+            .bind(trader_id)
+            .fetch_all(pool)
+            .await?;
+
+            Ok(versions)
+        })
+    }
+
+    // 获取交易员的指定提示词版本
+    pub async fn get_prompt_version(
+        &self,
+        user_id: &str,
+        trader_id: &str,
+        version: i64,
+    ) -> Result<Option<PromptVersion>> {
+        on_pool!(&self.pool, |pool| {
+            let version = sqlx::query_as::<_, PromptVersion>(sql(
+                pool,
+                "SELECT * FROM prompt_versions WHERE user_id = ? AND trader_id = ? AND version = ?",
+            ))
+            .bind(user_id)
+            .bind(trader_id)
+            .bind(version)
+            .fetch_optional(pool)
+            .await?;
+
+            Ok(version)
+        })
+    }
+
+    // 创建提示词模板；同名模板已存在时返回 false
+    pub async fn create_prompt_template(
+        &self,
+        user_id: &str,
+        name: &str,
+        content: &str,
+    ) -> Result<bool> {
+        on_pool!(&self.pool, |pool| {
+            let result = sqlx::query(sql(
+                pool,
+                "INSERT INTO prompt_templates (user_id, name, content) VALUES (?, ?, ?) ON CONFLICT DO NOTHING",
+            ))
+            .bind(user_id)
+            .bind(name)
+            .bind(content)
+            .execute(pool)
+            .await
+            .context("Failed to create prompt template")?;
+
+            Ok(result.rows_affected() > 0)
+        })
+    }
+
+    // 获取用户的全部提示词模板（按名称排序）
+    pub async fn get_prompt_templates(&self, user_id: &str) -> Result<Vec<PromptTemplate>> {
+        on_pool!(&self.pool, |pool| {
+            let templates = sqlx::query_as::<_, PromptTemplate>(sql(
+                pool,
+                "SELECT * FROM prompt_templates WHERE user_id = ? ORDER BY name",
+            ))
+            .bind(user_id)
+            .fetch_all(pool)
+            .await?;
+
+            Ok(templates)
+        })
+    }
+
+    // 按名称获取提示词模板
+    pub async fn get_prompt_template(
+        &self,
+        user_id: &str,
+        name: &str,
+    ) -> Result<Option<PromptTemplate>> {
+        on_pool!(&self.pool, |pool| {
+            let template = sqlx::query_as::<_, PromptTemplate>(sql(
+                pool,
+                "SELECT * FROM prompt_templates WHERE user_id = ? AND name = ?",
+            ))
+            .bind(user_id)
+            .bind(name)
+            .fetch_optional(pool)
+            .await?;
+
+            Ok(template)
+        })
+    }
+
+    // 更新提示词模板内容；模板不存在时返回 false
+    pub async fn update_prompt_template(
+        &self,
+        user_id: &str,
+        name: &str,
+        content: &str,
+    ) -> Result<bool> {
+        on_pool!(&self.pool, |pool| {
+            let result = sqlx::query(sql(
+                pool,
+                "UPDATE prompt_templates SET content = ?, updated_at = CURRENT_TIMESTAMP WHERE user_id = ? AND name = ?",
+            ))
+            .bind(content)
+            .bind(user_id)
+            .bind(name)
+            .execute(pool)
+            .await?;
+
+            Ok(result.rows_affected() > 0)
+        })
+    }
+
+    // 删除提示词模板；模板不存在时返回 false
+    pub async fn delete_prompt_template(&self, user_id: &str, name: &str) -> Result<bool> {
+        on_pool!(&self.pool, |pool| {
+            let result = sqlx::query(sql(
+                pool,
+                "DELETE FROM prompt_templates WHERE user_id = ? AND name = ?",
+            ))
             .bind(user_id)
+            .bind(name)
+            .execute(pool)
+            .await?;
+
+            Ok(result.rows_affected() > 0)
+        })
+    }
+
+    // 保存一条待审批的交易提案
+    pub async fn create_trade_proposal(&self, proposal: &TradeProposal) -> Result<()> {
+        on_pool!(&self.pool, |pool| {
+            sqlx::query(sql(
+                pool,
+                r#"INSERT INTO trade_proposals (id, user_id, trader_id, symbol, action, decision, notional_usd,
+                status, created_at, expires_at, decided_by, decided_at, result)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"#,
+            ))
+            .bind(&proposal.id)
+            .bind(&proposal.user_id)
+            .bind(&proposal.trader_id)
+            .bind(&proposal.symbol)
+            .bind(&proposal.action)
+            .bind(&proposal.decision)
+            .bind(proposal.notional_usd)
+            .bind(&proposal.status)
+            .bind(proposal.created_at)
+            .bind(proposal.expires_at)
+            .bind(&proposal.decided_by)
+            .bind(proposal.decided_at)
+            .bind(&proposal.result)
+            .execute(pool)
+            .await
+            .context("Failed to create trade proposal")?;
+
+            Ok(())
+        })
+    }
+
+    pub async fn get_trade_proposal(
+        &self,
+        user_id: &str,
+        trader_id: &str,
+        id: &str,
+    ) -> Result<Option<TradeProposal>> {
+        on_pool!(&self.pool, |pool| {
+            let proposal = sqlx::query_as::<_, TradeProposal>(sql(
+                pool,
+                "SELECT * FROM trade_proposals WHERE id = ? AND user_id = ? AND trader_id = ?",
+            ))
             .bind(id)
-            .execute(&self.pool)
+            .bind(user_id)
+            .bind(trader_id)
+            .fetch_optional(pool)
             .await?;
 
-        Ok(())
+            Ok(proposal)
+        })
     }
 
-    pub async fn get_trader_config(
+    // 获取交易员的交易提案（最新在前），status 为空时返回全部
+    pub async fn get_trade_proposals(
         &self,
         user_id: &str,
         trader_id: &str,
-    ) -> Result<(TraderRecord, AIModelConfig, ExchangeConfig)> {
-        let row  = sqlx::query(
-            r#"
-            SELECT 
-                t.id, t.user_id, t.name, t.ai_model_id, t.exchange_id, t.initial_balance, t.scan_interval_minutes, t.is_running, t.created_at, t.updated_at,
-                a.id, a.user_id, a.name, a.provider, a.enabled, a.api_key, a.created_at, a.updated_at,
-                e.id, e.user_id, e.name, e.type, e.enabled, e.api_key, e.secret_key, e.testnet,
-                COALESCE(e.hyperliquid_wallet_addr, '') as hyperliquid_wallet_addr,
-                COALESCE(e.aster_user, '') as aster_user,
-                COALESCE(e.aster_signer, '') as aster_signer,
-                COALESCE(e.aster_private_key, '') as aster_private_key,
-                e.created_at, e.updated_at
-            FROM traders t
-            JOIN ai_models a ON t.ai_model_id = a.id AND t.user_id = a.user_id
-            JOIN exchanges e ON t.exchange_id = e.id AND t.user_id = e.user_id
-            WHERE t.id = ? AND t.user_id = ?
-            "#,
-        ).bind(trader_id).bind(user_id).fetch_one(&self.pool).await?;
-
-        // Manually map the aliased columns to the structs
-        let trader = TraderRecord {
-            id: row.try_get("t_id")?,
-            user_id: row.try_get("t_user_id")?,
-            name: row.try_get("t_name")?,
-            ai_model_id: row.try_get("ai_model_id")?,
-            exchange_id: row.try_get("exchange_id")?,
-            initial_balance: row.try_get("initial_balance")?,
-            scan_interval_minutes: row.try_get("scan_interval_minutes")?,
-            is_running: row.try_get("is_running")?,
-            created_at: row.try_get("t_created_at")?,
-            updated_at: row.try_get("t_updated_at")?,
-            ..Default::default()
-        };
+        status: &str,
+    ) -> Result<Vec<TradeProposal>> {
+        on_pool!(&self.pool, |pool| {
+            let proposals = sqlx::query_as::<_, TradeProposal>(sql(
+                pool,
+                r#"SELECT * FROM trade_proposals
+            WHERE user_id = ? AND trader_id = ? AND (? = '' OR status = ?)
+            ORDER BY created_at DESC, id"#,
+            ))
+            .bind(user_id)
+            .bind(trader_id)
+            .bind(status)
+            .bind(status)
+            .fetch_all(pool)
+            .await?;
 
-        let ai_model = AIModelConfig {
-            id: row.try_get("a_id")?,
-            user_id: row.try_get("a_user_id")?,
-            name: row.try_get("a_name")?,
-            provider: row.try_get("provider")?,
-            enabled: row.try_get("a_enabled")?,
-            api_key: row.try_get("a_api_key")?,
-            created_at: row.try_get("a_created_at")?,
-            updated_at: row.try_get("a_updated_at")?,
-            ..Default::default()
-        };
+            Ok(proposals)
+        })
+    }
 
-        let exchange = ExchangeConfig {
-            id: row.try_get("e_id")?,
-            user_id: row.try_get("e_user_id")?,
-            name: row.try_get("e_name")?,
-            exchange_type: row.try_get("e_type")?,
-            enabled: row.try_get("e_enabled")?,
-            api_key: row.try_get("e_api_key")?,
-            secret_key: row.try_get("secret_key")?,
-            testnet: row.try_get("testnet")?,
-            hyperliquid_wallet_addr: row.try_get("hyperliquid_wallet_addr")?,
-            aster_user: row.try_get("aster_user")?,
-            aster_signer: row.try_get("aster_signer")?,
-            aster_private_key: row.try_get("aster_private_key")?,
-            created_at: row.try_get("e_created_at")?,
-            updated_at: row.try_get("e_updated_at")?,
-            ..Default::default()
-        };
+    // 审批或拒绝一条未过期的待审批提案，返回是否更新成功
+    pub async fn decide_trade_proposal(
+        &self,
+        user_id: &str,
+        trader_id: &str,
+        id: &str,
+        status: &str,
+        decided_by: &str,
+        now: DateTime<Utc>,
+    ) -> Result<bool> {
+        on_pool!(&self.pool, |pool| {
+            let result = sqlx::query(sql(
+                pool,
+                r#"UPDATE trade_proposals SET status = ?, decided_by = ?, decided_at = ?
+            WHERE id = ? AND user_id = ? AND trader_id = ? AND status = 'pending' AND expires_at > ?"#,
+            ))
+            .bind(status)
+            .bind(decided_by)
+            .bind(now)
+            .bind(id)
+            .bind(user_id)
+            .bind(trader_id)
+            .bind(now)
+            .execute(pool)
+            .await
+            .context("Failed to decide trade proposal")?;
 
-        Ok((trader, ai_model, exchange))
+            Ok(result.rows_affected() > 0)
+        })
     }
 
-    pub async fn get_system_config(&self, key: &str) -> Result<String> {
-        let config: String = sqlx::query_scalar("SELECT value FROM system_config WHERE key = ?")
-            .bind(key)
-            .fetch_one(&self.pool)
+    // 将交易员已过期的待审批提案标记为 expired，返回数量
+    pub async fn expire_trade_proposals(
+        &self,
+        user_id: &str,
+        trader_id: &str,
+        now: DateTime<Utc>,
+    ) -> Result<u64> {
+        on_pool!(&self.pool, |pool| {
+            let result = sqlx::query(sql(
+                pool,
+                r#"UPDATE trade_proposals SET status = 'expired'
+            WHERE user_id = ? AND trader_id = ? AND status = 'pending' AND expires_at <= ?"#,
+            ))
+            .bind(user_id)
+            .bind(trader_id)
+            .bind(now)
+            .execute(pool)
+            .await?;
+
+            Ok(result.rows_affected())
+        })
+    }
+
+    // 将提案从 from 状态改为 to 状态（执行前认领、执行后写入结果），返回是否更新成功
+    pub async fn transition_trade_proposal(
+        &self,
+        id: &str,
+        from: &str,
+        to: &str,
+        result: &str,
+    ) -> Result<bool> {
+        on_pool!(&self.pool, |pool| {
+            let updated = sqlx::query(sql(
+                pool,
+                "UPDATE trade_proposals SET status = ?, result = ? WHERE id = ? AND status = ?",
+            ))
+            .bind(to)
+            .bind(result)
+            .bind(id)
+            .bind(from)
+            .execute(pool)
+            .await?;
+
+            Ok(updated.rows_affected() > 0)
+        })
+    }
+
+    pub async fn delete_trader(&self, user_id: &str, id: &str) -> Result<()> {
+        on_pool!(&self.pool, |pool| {
+            sqlx::query(sql(
+                pool,
+                "DELETE FROM traders WHERE id = ? AND user_id = ?",
+            ))
+            .bind(id)
+            .bind(user_id)
+            .execute(pool)
             .await?;
 
-        Ok(config)
+            Ok(())
+        })
+    }
+
+    // 获取交易员及其关联的AI模型和交易所配置
+    pub async fn get_trader_config(
+        &self,
+        user_id: &str,
+        trader_id: &str,
+    ) -> Result<(TraderRecord, AIModelConfig, ExchangeConfig)> {
+        let trader = self
+            .get_trader(user_id, trader_id)
+            .await?
+            .with_context(|| format!("交易员不存在: {}", trader_id))?;
+        let ai_model = self
+            .get_aimodels(user_id)
+            .await?
+            .into_iter()
+            .find(|m| m.id == trader.ai_model_id)
+            .with_context(|| format!("AI模型不存在: {}", trader.ai_model_id))?;
+        let exchange = self
+            .get_exchanges(user_id)
+            .await?
+            .into_iter()
+            .find(|e| e.id == trader.exchange_id)
+            .with_context(|| format!("交易所不存在: {}", trader.exchange_id))?;
+
+        Ok((trader, ai_model, exchange))
+    }
+
+    pub async fn get_system_config(&self, key: &str) -> Result<String> {
+        on_pool!(&self.pool, |pool| {
+            let config: String =
+                sqlx::query_scalar(sql(pool, "SELECT value FROM system_config WHERE key = ?"))
+                    .bind(key)
+                    .fetch_one(pool)
+                    .await?;
+
+            Ok(config)
+        })
     }
 
     pub async fn set_system_config(&self, key: &str, value: &str) -> Result<()> {
-        sqlx::query("INSERT OR REPLACE INTO system_config (key, value) VALUES (?, ?)")
+        on_pool!(&self.pool, |pool| {
+            sqlx::query(sql(
+                pool,
+                r#"INSERT INTO system_config (key, value) VALUES (?, ?)
+            ON CONFLICT(key) DO UPDATE SET value = excluded.value"#,
+            ))
             .bind(key)
             .bind(value)
-            .execute(&self.pool)
+            .execute(pool)
             .await?;
 
-        Ok(())
+            Ok(())
+        })
+    }
+
+    // 删除系统配置项
+    pub async fn delete_system_config(&self, key: &str) -> Result<()> {
+        on_pool!(&self.pool, |pool| {
+            sqlx::query(sql(pool, "DELETE FROM system_config WHERE key = ?"))
+                .bind(key)
+                .execute(pool)
+                .await?;
+
+            Ok(())
+        })
     }
 
     pub async fn create_user_signal_source(
@@ -1066,32 +1771,41 @@ impl Database {
         coin_pool_url: &str,
         oi_top_url: &str,
     ) -> Result<()> {
-        sqlx::query(
-            r#"
-            INSERT OR REPLACE INTO user_signal_sources (user_id, coin_pool_url, oi_top_url, updated_at)
+        on_pool!(&self.pool, |pool| {
+            sqlx::query(sql(
+                pool,
+                r#"
+            INSERT INTO user_signal_sources (user_id, coin_pool_url, oi_top_url, updated_at)
 		    VALUES (?, ?, ?, CURRENT_TIMESTAMP)
-        "#)
-        .bind(user_id)
-        .bind(coin_pool_url)
-        .bind(oi_top_url)
-        .execute(&self.pool)
-        .await?;
+		    ON CONFLICT(user_id) DO UPDATE SET coin_pool_url = excluded.coin_pool_url,
+		        oi_top_url = excluded.oi_top_url, updated_at = excluded.updated_at
+        "#,
+            ))
+            .bind(user_id)
+            .bind(coin_pool_url)
+            .bind(oi_top_url)
+            .execute(pool)
+            .await?;
 
-        Ok(())
+            Ok(())
+        })
     }
 
     pub async fn get_user_signal_source(&self, user_id: &str) -> Result<UserSignalSource> {
-        let usr = sqlx::query_as::<_, UserSignalSource>(
-            r#"
+        on_pool!(&self.pool, |pool| {
+            let usr = sqlx::query_as::<_, UserSignalSource>(sql(
+                pool,
+                r#"
             SELECT id, user_id, coin_pool_url, oi_top_url, created_at, updated_at
 		    FROM user_signal_sources WHERE user_id = ?
             "#,
-        )
-        .bind(user_id)
-        .fetch_one(&self.pool)
-        .await?;
+            ))
+            .bind(user_id)
+            .fetch_one(pool)
+            .await?;
 
-        Ok(usr)
+            Ok(usr)
+        })
     }
 
     pub async fn update_user_signal_source(
@@ -1100,184 +1814,2936 @@ impl Database {
         coin_pool_url: &str,
         oi_top_url: &str,
     ) -> Result<()> {
-        sqlx::query(
-            r#"
+        on_pool!(&self.pool, |pool| {
+            sqlx::query(
+            sql(pool, r#"
             UPDATE user_signal_sources SET coin_pool_url = ?, oi_top_url = ?, updated_at = CURRENT_TIMESTAMP
 		    WHERE user_id = ?
-            "#
+            "#)
         )
         .bind(coin_pool_url)
         .bind(oi_top_url)
         .bind(user_id)
-        .execute(&self.pool)
+        .execute(pool)
+        .await?;
+
+            Ok(())
+        })
+    }
+
+    // 保存交易员的决策过滤器（覆盖已有的）
+    pub async fn set_decision_filter(
+        &self,
+        user_id: &str,
+        trader_id: &str,
+        wasm: &[u8],
+    ) -> Result<()> {
+        on_pool!(&self.pool, |pool| {
+            let digest: String = Sha256::digest(wasm)
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect();
+            sqlx::query(sql(
+                pool,
+                r#"INSERT INTO decision_filters (trader_id, user_id, wasm, sha256)
+            VALUES (?, ?, ?, ?)
+            ON CONFLICT(trader_id) DO UPDATE SET user_id = excluded.user_id, wasm = excluded.wasm,
+                sha256 = excluded.sha256, created_at = CURRENT_TIMESTAMP"#,
+            ))
+            .bind(trader_id)
+            .bind(user_id)
+            .bind(wasm)
+            .bind(digest)
+            .execute(pool)
+            .await
+            .context("Failed to save decision filter")?;
+
+            Ok(())
+        })
+    }
+
+    // 获取交易员的决策过滤器模块
+    pub async fn get_decision_filter(
+        &self,
+        user_id: &str,
+        trader_id: &str,
+    ) -> Result<Option<Vec<u8>>> {
+        on_pool!(&self.pool, |pool| {
+            let wasm = sqlx::query_scalar::<_, Vec<u8>>(sql(
+                pool,
+                "SELECT wasm FROM decision_filters WHERE trader_id = ? AND user_id = ?",
+            ))
+            .bind(trader_id)
+            .bind(user_id)
+            .fetch_optional(pool)
+            .await?;
+
+            Ok(wasm)
+        })
+    }
+
+    pub async fn delete_decision_filter(&self, user_id: &str, trader_id: &str) -> Result<()> {
+        on_pool!(&self.pool, |pool| {
+            sqlx::query(sql(
+                pool,
+                "DELETE FROM decision_filters WHERE trader_id = ? AND user_id = ?",
+            ))
+            .bind(trader_id)
+            .bind(user_id)
+            .execute(pool)
+            .await?;
+
+            Ok(())
+        })
+    }
+
+    // 注册定时任务：已存在则保留用户修改过的定义，返回当前记录
+    pub async fn register_scheduled_job(
+        &self,
+        name: &str,
+        schedule: &str,
+        jitter_secs: i64,
+    ) -> Result<ScheduledJob> {
+        on_pool!(&self.pool, |pool| {
+            sqlx::query(
+            sql(pool, "INSERT INTO scheduled_jobs (name, schedule, jitter_secs) VALUES (?, ?, ?) ON CONFLICT DO NOTHING"),
+        )
+        .bind(name)
+        .bind(schedule)
+        .bind(jitter_secs)
+        .execute(pool)
+        .await
+        .with_context(|| format!("Failed to register job {}", name))?;
+
+            let job = sqlx::query_as::<_, ScheduledJob>(sql(
+                pool,
+                "SELECT * FROM scheduled_jobs WHERE name = ?",
+            ))
+            .bind(name)
+            .fetch_one(pool)
+            .await?;
+
+            Ok(job)
+        })
+    }
+
+    pub async fn get_scheduled_jobs(&self) -> Result<Vec<ScheduledJob>> {
+        on_pool!(&self.pool, |pool| {
+            let jobs = sqlx::query_as::<_, ScheduledJob>(sql(
+                pool,
+                "SELECT * FROM scheduled_jobs ORDER BY name",
+            ))
+            .fetch_all(pool)
+            .await?;
+
+            Ok(jobs)
+        })
+    }
+
+    // 修改定时任务定义
+    pub async fn update_scheduled_job(
+        &self,
+        name: &str,
+        schedule: &str,
+        enabled: bool,
+        jitter_secs: i64,
+    ) -> Result<()> {
+        on_pool!(&self.pool, |pool| {
+            let result = sqlx::query(
+            sql(pool, "UPDATE scheduled_jobs SET schedule = ?, enabled = ?, jitter_secs = ? WHERE name = ?"),
+        )
+        .bind(schedule)
+        .bind(enabled)
+        .bind(jitter_secs)
+        .bind(name)
+        .execute(pool)
+        .await?;
+
+            if result.rows_affected() == 0 {
+                anyhow::bail!("job '{}' not found", name);
+            }
+
+            Ok(())
+        })
+    }
+
+    // 记录一次任务运行结果
+    pub async fn record_job_run(
+        &self,
+        name: &str,
+        started_at: DateTime<Utc>,
+        finished_at: DateTime<Utc>,
+        error: Option<&str>,
+    ) -> Result<()> {
+        on_pool!(&self.pool, |pool| {
+            let duration_ms = (finished_at - started_at).num_milliseconds();
+            sqlx::query(sql(
+                pool,
+                r#"
+            UPDATE scheduled_jobs SET
+                last_started_at = ?, last_finished_at = ?, last_duration_ms = ?,
+                last_outcome = ?, last_error = ?,
+                run_count = run_count + 1,
+                failure_count = failure_count + ?
+            WHERE name = ?
+            "#,
+            ))
+            .bind(started_at)
+            .bind(finished_at)
+            .bind(duration_ms)
+            .bind(if error.is_some() { "failed" } else { "success" })
+            .bind(error)
+            .bind(error.is_some() as i64)
+            .bind(name)
+            .execute(pool)
+            .await?;
+
+            Ok(())
+        })
+    }
+
+    // 记录一次因上次仍在运行而跳过的触发
+    pub async fn record_job_skipped(&self, name: &str) -> Result<()> {
+        on_pool!(&self.pool, |pool| {
+            sqlx::query(
+            sql(pool, "UPDATE scheduled_jobs SET skipped_count = skipped_count + 1, last_outcome = 'skipped' WHERE name = ?"),
+        )
+        .bind(name)
+        .execute(pool)
+        .await?;
+
+            Ok(())
+        })
+    }
+
+    // 记录一笔资金划转；带交易所流水号的重复记录会被忽略，返回是否新增
+    pub async fn record_transfer(&self, transfer: &AccountTransfer) -> Result<bool> {
+        on_pool!(&self.pool, |pool| {
+            let result = sqlx::query(
+            sql(pool, r#"INSERT INTO account_transfers (user_id, trader_id, amount, asset, occurred_at, source, external_id, note)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?) ON CONFLICT DO NOTHING"#),
+        )
+        .bind(&transfer.user_id)
+        .bind(&transfer.trader_id)
+        .bind(money::to_f64(transfer.amount))
+        .bind(&transfer.asset)
+        .bind(transfer.occurred_at)
+        .bind(&transfer.source)
+        .bind(&transfer.external_id)
+        .bind(&transfer.note)
+        .execute(pool)
+        .await
+        .context("Failed to record account transfer")?;
+
+            Ok(result.rows_affected() > 0)
+        })
+    }
+
+    // 获取交易员的资金划转记录（按时间升序）
+    pub async fn get_transfers(
+        &self,
+        user_id: &str,
+        trader_id: &str,
+    ) -> Result<Vec<AccountTransfer>> {
+        on_pool!(&self.pool, |pool| {
+            let mut conn = pool.acquire().await?;
+            fetch_transfers(&mut DbConn::from(&mut *conn), user_id, trader_id).await
+        })
+    }
+
+    // 记录一笔成交
+    pub async fn record_trade(&self, trade: &Trade) -> Result<i64> {
+        on_pool!(&self.pool, |pool| {
+            let id: i64 = sqlx::query_scalar(
+            sql(pool, r#"INSERT INTO trades (user_id, trader_id, symbol, side, action, quantity, price, fee, order_id, group_id, expected_price, fill_latency_ms, variant, executed_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            RETURNING id"#),
+        )
+        .bind(&trade.user_id)
+        .bind(&trade.trader_id)
+        .bind(&trade.symbol)
+        .bind(&trade.side)
+        .bind(&trade.action)
+        .bind(money::to_f64(trade.quantity))
+        .bind(money::to_f64(trade.price))
+        .bind(money::to_f64(trade.fee))
+        .bind(trade.order_id)
+        .bind(&trade.group_id)
+        .bind(money::to_f64(trade.expected_price))
+        .bind(trade.fill_latency_ms)
+        .bind(&trade.variant)
+        .bind(trade.executed_at)
+        .fetch_one(pool)
+        .await
+        .context("Failed to record trade")?;
+
+            Ok(id)
+        })
+    }
+
+    // 获取交易员自 since 起的成交记录（按时间升序）
+    pub async fn get_trades(
+        &self,
+        user_id: &str,
+        trader_id: &str,
+        since: Option<DateTime<Utc>>,
+    ) -> Result<Vec<Trade>> {
+        on_pool!(&self.pool, |pool| {
+            let trades = sqlx::query_as::<_, Trade>(
+            sql(pool, r#"SELECT * FROM trades WHERE user_id = ? AND trader_id = ? AND executed_at >= COALESCE(?, executed_at)
+            ORDER BY executed_at, id"#),
+        )
+        .bind(user_id)
+        .bind(trader_id)
+        .bind(since)
+        .fetch_all(pool)
+        .await
+        .context("Failed to fetch trades")?;
+
+            Ok(trades)
+        })
+    }
+
+    // 保存一条持仓对账差异
+    pub async fn record_reconciliation_event(&self, event: &ReconciliationEvent) -> Result<i64> {
+        on_pool!(&self.pool, |pool| {
+            let id: i64 = sqlx::query_scalar(sql(
+                pool,
+                r#"INSERT INTO reconciliation_events (user_id, trader_id, symbol, side, recorded_quantity, exchange_quantity, trade_id, detected_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+            RETURNING id"#,
+            ))
+            .bind(&event.user_id)
+            .bind(&event.trader_id)
+            .bind(&event.symbol)
+            .bind(&event.side)
+            .bind(money::to_f64(event.recorded_quantity))
+            .bind(money::to_f64(event.exchange_quantity))
+            .bind(event.trade_id)
+            .bind(event.detected_at)
+            .fetch_one(pool)
+            .await
+            .context("Failed to record reconciliation event")?;
+
+            Ok(id)
+        })
+    }
+
+    // 获取交易员最近的持仓对账差异（按时间倒序）
+    pub async fn get_reconciliation_events(
+        &self,
+        user_id: &str,
+        trader_id: &str,
+        limit: i64,
+    ) -> Result<Vec<ReconciliationEvent>> {
+        on_pool!(&self.pool, |pool| {
+            let events = sqlx::query_as::<_, ReconciliationEvent>(sql(
+                pool,
+                r#"SELECT * FROM reconciliation_events WHERE user_id = ? AND trader_id = ?
+            ORDER BY detected_at DESC, id DESC LIMIT ?"#,
+            ))
+            .bind(user_id)
+            .bind(trader_id)
+            .bind(limit)
+            .fetch_all(pool)
+            .await
+            .context("Failed to fetch reconciliation events")?;
+
+            Ok(events)
+        })
+    }
+
+    // 保存一条登录锁定记录
+    pub async fn record_auth_lockout(&self, event: &AuthLockout) -> Result<i64> {
+        on_pool!(&self.pool, |pool| {
+            let id: i64 = sqlx::query_scalar(sql(
+                pool,
+                r#"INSERT INTO auth_lockouts (attempt, kind, subject, ip, failures, strike, locked_at, locked_until)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+            RETURNING id"#,
+            ))
+            .bind(&event.attempt)
+            .bind(&event.kind)
+            .bind(&event.subject)
+            .bind(&event.ip)
+            .bind(event.failures)
+            .bind(event.strike)
+            .bind(event.locked_at)
+            .bind(event.locked_until)
+            .fetch_one(pool)
+            .await
+            .context("Failed to record auth lockout")?;
+
+            Ok(id)
+        })
+    }
+
+    // 获取最近的登录锁定记录（按时间倒序）
+    pub async fn get_auth_lockouts(&self, limit: i64) -> Result<Vec<AuthLockout>> {
+        on_pool!(&self.pool, |pool| {
+            let events = sqlx::query_as::<_, AuthLockout>(sql(
+                pool,
+                "SELECT * FROM auth_lockouts ORDER BY locked_at DESC, id DESC LIMIT ?",
+            ))
+            .bind(limit)
+            .fetch_all(pool)
+            .await
+            .context("Failed to fetch auth lockouts")?;
+
+            Ok(events)
+        })
+    }
+
+    // 保存一条变更审计记录
+    pub async fn save_audit_log(&self, entry: &AuditLogEntry) -> Result<i64> {
+        on_pool!(&self.pool, |pool| {
+            let id: i64 = sqlx::query_scalar(sql(
+                pool,
+                r#"INSERT INTO audit_log (user_id, source, action, target, old_value, new_value, created_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?)
+            RETURNING id"#,
+            ))
+            .bind(&entry.user_id)
+            .bind(&entry.source)
+            .bind(&entry.action)
+            .bind(&entry.target)
+            .bind(&entry.old_value)
+            .bind(&entry.new_value)
+            .bind(entry.created_at)
+            .fetch_one(pool)
+            .await
+            .context("Failed to save audit log entry")?;
+
+            Ok(id)
+        })
+    }
+
+    // 按操作人和目标前缀（为空表示不限）获取最近的变更审计记录（按时间倒序）
+    pub async fn get_audit_log(
+        &self,
+        user_id: &str,
+        target_prefix: &str,
+        limit: i64,
+    ) -> Result<Vec<AuditLogEntry>> {
+        on_pool!(&self.pool, |pool| {
+            let entries = sqlx::query_as::<_, AuditLogEntry>(sql(
+                pool,
+                r#"SELECT * FROM audit_log
+            WHERE (? = '' OR user_id = ?) AND substr(target, 1, length(?)) = ?
+            ORDER BY created_at DESC, id DESC LIMIT ?"#,
+            ))
+            .bind(user_id)
+            .bind(user_id)
+            .bind(target_prefix)
+            .bind(target_prefix)
+            .bind(limit)
+            .fetch_all(pool)
+            .await
+            .context("Failed to fetch audit log")?;
+
+            Ok(entries)
+        })
+    }
+
+    // 保存一条账户权益快照
+    pub async fn save_pnl_snapshot(&self, snapshot: &PnlSnapshot) -> Result<()> {
+        on_pool!(&self.pool, |pool| {
+            sqlx::query(
+            sql(pool, r#"INSERT INTO pnl_snapshots (user_id, trader_id, taken_at, total_equity, available_balance, unrealized_pnl, margin_used, position_count)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?)"#),
+        )
+        .bind(&snapshot.user_id)
+        .bind(&snapshot.trader_id)
+        .bind(snapshot.taken_at)
+        .bind(money::to_f64(snapshot.total_equity))
+        .bind(money::to_f64(snapshot.available_balance))
+        .bind(money::to_f64(snapshot.unrealized_pnl))
+        .bind(money::to_f64(snapshot.margin_used))
+        .bind(snapshot.position_count)
+        .execute(pool)
+        .await
+        .context("Failed to save pnl snapshot")?;
+
+            Ok(())
+        })
+    }
+
+    // 获取交易员自 since 起的权益快照（按时间升序）
+    pub async fn get_pnl_snapshots(
+        &self,
+        user_id: &str,
+        trader_id: &str,
+        since: Option<DateTime<Utc>>,
+    ) -> Result<Vec<PnlSnapshot>> {
+        on_pool!(&self.pool, |pool| {
+            let snapshots = sqlx::query_as::<_, PnlSnapshot>(
+            sql(pool, r#"SELECT * FROM pnl_snapshots WHERE user_id = ? AND trader_id = ? AND taken_at >= COALESCE(?, taken_at)
+            ORDER BY taken_at, id"#),
+        )
+        .bind(user_id)
+        .bind(trader_id)
+        .bind(since)
+        .fetch_all(pool)
+        .await
+        .context("Failed to fetch pnl snapshots")?;
+
+            Ok(snapshots)
+        })
+    }
+
+    // 获取交易员在 range 内按时间分桶的权益曲线及回撤（剔除资金划转的影响）
+    pub async fn get_equity_curve(
+        &self,
+        user_id: &str,
+        trader_id: &str,
+        range: EquityRange,
+    ) -> Result<EquityCurve> {
+        let snapshots = self
+            .get_pnl_snapshots(user_id, trader_id, Some(range.since))
+            .await?;
+        let transfers = self.get_transfers(user_id, trader_id).await?;
+        Ok(performance::equity_curve(&snapshots, &transfers, &range))
+    }
+
+    // 新建暂停窗口
+    pub async fn create_pause_window(
+        &self,
+        user_id: &str,
+        trader_id: &str,
+        pause_at: DateTime<Utc>,
+        resume_at: Option<DateTime<Utc>>,
+        reason: &str,
+    ) -> Result<PauseWindow> {
+        on_pool!(&self.pool, |pool| {
+            let id: i64 = sqlx::query_scalar(
+            sql(pool, r#"INSERT INTO trader_pause_windows (user_id, trader_id, pause_at, resume_at, reason)
+            VALUES (?, ?, ?, ?, ?)
+            RETURNING id"#),
+        )
+        .bind(user_id)
+        .bind(trader_id)
+        .bind(pause_at)
+        .bind(resume_at)
+        .bind(reason)
+        .fetch_one(pool)
+        .await
+        .context("Failed to create pause window")?;
+
+            self.get_pause_window(user_id, id)
+                .await?
+                .context("pause window vanished after insert")
+        })
+    }
+
+    // 保存一条决策记录（同一记录重复保存时覆盖，例如平仓后回写开仓记录）
+    #[allow(clippy::too_many_arguments)]
+    pub async fn save_decision_record(
+        &self,
+        user_id: &str,
+        trader_id: &str,
+        id: &str,
+        cycle_number: i32,
+        timestamp: DateTime<Utc>,
+        payload: &[u8],
+        encrypted: bool,
+    ) -> Result<()> {
+        on_pool!(&self.pool, |pool| {
+            sqlx::query(
+            sql(pool, r#"INSERT INTO decision_records (id, user_id, trader_id, cycle_number, timestamp, payload, encrypted)
+            VALUES (?, ?, ?, ?, ?, ?, ?)
+            ON CONFLICT(trader_id, id) DO UPDATE SET payload = excluded.payload, encrypted = excluded.encrypted"#),
+        )
+        .bind(id)
+        .bind(user_id)
+        .bind(trader_id)
+        .bind(cycle_number)
+        .bind(timestamp)
+        .bind(payload)
+        .bind(encrypted)
+        .execute(pool)
+        .await
+        .context("Failed to save decision record")?;
+
+            Ok(())
+        })
+    }
+
+    // 获取交易员最近N条决策记录的原始内容（按时间正序）
+    pub async fn get_decision_records(
+        &self,
+        user_id: &str,
+        trader_id: &str,
+        limit: i64,
+    ) -> Result<Vec<Vec<u8>>> {
+        on_pool!(&self.pool, |pool| {
+            let mut payloads = sqlx::query_scalar::<_, Vec<u8>>(sql(
+                pool,
+                r#"SELECT payload FROM decision_records WHERE user_id = ? AND trader_id = ?
+            ORDER BY timestamp DESC, cycle_number DESC LIMIT ?"#,
+            ))
+            .bind(user_id)
+            .bind(trader_id)
+            .bind(limit)
+            .fetch_all(pool)
+            .await?;
+            payloads.reverse();
+
+            Ok(payloads)
+        })
+    }
+
+    // 按ID获取交易员的一条决策记录的原始内容
+    pub async fn get_decision_record(
+        &self,
+        user_id: &str,
+        trader_id: &str,
+        id: &str,
+    ) -> Result<Option<Vec<u8>>> {
+        on_pool!(&self.pool, |pool| {
+            let payload = sqlx::query_scalar::<_, Vec<u8>>(sql(
+                pool,
+                r#"SELECT payload FROM decision_records WHERE user_id = ? AND trader_id = ? AND id = ?"#,
+            ))
+            .bind(user_id)
+            .bind(trader_id)
+            .bind(id)
+            .fetch_optional(pool)
+            .await?;
+
+            Ok(payload)
+        })
+    }
+
+    // 保存一个模型评测场景
+    pub async fn save_eval_scenario(&self, scenario: &EvalScenario) -> Result<()> {
+        on_pool!(&self.pool, |pool| {
+            sqlx::query(
+            sql(pool, r#"INSERT INTO eval_scenarios (id, user_id, trader_id, captured_at, system_prompt, user_prompt, entry_prices, exit_prices, settled_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?) ON CONFLICT DO NOTHING"#),
+        )
+        .bind(&scenario.id)
+        .bind(&scenario.user_id)
+        .bind(&scenario.trader_id)
+        .bind(scenario.captured_at)
+        .bind(&scenario.system_prompt)
+        .bind(&scenario.user_prompt)
+        .bind(&scenario.entry_prices)
+        .bind(&scenario.exit_prices)
+        .bind(scenario.settled_at)
+        .execute(pool)
+        .await
+        .context("Failed to save eval scenario")?;
+
+            Ok(())
+        })
+    }
+
+    // 获取在 before 之前采集、仍未结算的评测场景
+    pub async fn get_unsettled_eval_scenarios(
+        &self,
+        before: DateTime<Utc>,
+    ) -> Result<Vec<EvalScenario>> {
+        on_pool!(&self.pool, |pool| {
+            let scenarios = sqlx::query_as::<_, EvalScenario>(sql(
+                pool,
+                r#"SELECT * FROM eval_scenarios WHERE exit_prices IS NULL AND captured_at <= ?
+            ORDER BY captured_at"#,
+            ))
+            .bind(before)
+            .fetch_all(pool)
+            .await?;
+
+            Ok(scenarios)
+        })
+    }
+
+    // 写入评测场景的结算价格
+    pub async fn settle_eval_scenario(&self, id: &str, exit_prices: &str) -> Result<()> {
+        on_pool!(&self.pool, |pool| {
+            sqlx::query(
+            sql(pool, "UPDATE eval_scenarios SET exit_prices = ?, settled_at = CURRENT_TIMESTAMP WHERE id = ?"),
+        )
+        .bind(exit_prices)
+        .bind(id)
+        .execute(pool)
+        .await?;
+
+            Ok(())
+        })
+    }
+
+    // 获取用户最近N个已结算的评测场景（按采集时间正序）
+    pub async fn get_settled_eval_scenarios(
+        &self,
+        user_id: &str,
+        limit: i64,
+    ) -> Result<Vec<EvalScenario>> {
+        on_pool!(&self.pool, |pool| {
+            let mut scenarios = sqlx::query_as::<_, EvalScenario>(sql(
+                pool,
+                r#"SELECT * FROM eval_scenarios WHERE user_id = ? AND exit_prices IS NOT NULL
+            ORDER BY captured_at DESC LIMIT ?"#,
+            ))
+            .bind(user_id)
+            .bind(limit)
+            .fetch_all(pool)
+            .await?;
+            scenarios.reverse();
+
+            Ok(scenarios)
+        })
+    }
+
+    // 删除早于 before 的评测场景
+    pub async fn prune_eval_scenarios(&self, before: DateTime<Utc>) -> Result<u64> {
+        on_pool!(&self.pool, |pool| {
+            let result = sqlx::query(sql(
+                pool,
+                "DELETE FROM eval_scenarios WHERE captured_at < ?",
+            ))
+            .bind(before)
+            .execute(pool)
+            .await?;
+
+            Ok(result.rows_affected())
+        })
+    }
+
+    // 保存一次AI方向判断
+    pub async fn save_decision_call(&self, call: &DecisionCall) -> Result<()> {
+        on_pool!(&self.pool, |pool| {
+            sqlx::query(sql(
+                pool,
+                r#"INSERT INTO decision_calls (id, user_id, trader_id, model, symbol, action, entry_price, decided_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?) ON CONFLICT DO NOTHING"#,
+            ))
+            .bind(&call.id)
+            .bind(&call.user_id)
+            .bind(&call.trader_id)
+            .bind(&call.model)
+            .bind(&call.symbol)
+            .bind(&call.action)
+            .bind(call.entry_price)
+            .bind(call.decided_at)
+            .execute(pool)
+            .await
+            .context("Failed to save decision call")?;
+
+            Ok(())
+        })
+    }
+
+    // 获取在 before 之前做出、仍未评分的方向判断
+    pub async fn get_unsettled_decision_calls(
+        &self,
+        before: DateTime<Utc>,
+    ) -> Result<Vec<DecisionCall>> {
+        on_pool!(&self.pool, |pool| {
+            let calls = sqlx::query_as::<_, DecisionCall>(sql(
+                pool,
+                r#"SELECT * FROM decision_calls WHERE settled_at IS NULL AND decided_at <= ?
+            ORDER BY decided_at"#,
+            ))
+            .bind(before)
+            .fetch_all(pool)
+            .await?;
+
+            Ok(calls)
+        })
+    }
+
+    // 写入方向判断的评分结果
+    pub async fn settle_decision_call(
+        &self,
+        id: &str,
+        exit_price: f64,
+        return_pct: f64,
+        correct: bool,
+        settled_at: DateTime<Utc>,
+    ) -> Result<()> {
+        on_pool!(&self.pool, |pool| {
+            sqlx::query(sql(
+                pool,
+                r#"UPDATE decision_calls SET exit_price = ?, return_pct = ?, correct = ?, settled_at = ?
+            WHERE id = ? AND settled_at IS NULL"#,
+            ))
+            .bind(exit_price)
+            .bind(return_pct)
+            .bind(correct)
+            .bind(settled_at)
+            .bind(id)
+            .execute(pool)
+            .await?;
+
+            Ok(())
+        })
+    }
+
+    // 获取用户在 since 之后做出且已评分的方向判断（按时间正序）
+    pub async fn get_settled_decision_calls(
+        &self,
+        user_id: &str,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<DecisionCall>> {
+        on_pool!(&self.pool, |pool| {
+            let calls = sqlx::query_as::<_, DecisionCall>(sql(
+                pool,
+                r#"SELECT * FROM decision_calls WHERE user_id = ? AND decided_at >= ? AND settled_at IS NOT NULL
+            ORDER BY decided_at"#,
+            ))
+            .bind(user_id)
+            .bind(since)
+            .fetch_all(pool)
+            .await?;
+
+            Ok(calls)
+        })
+    }
+
+    // 删除早于 before 的方向判断
+    pub async fn prune_decision_calls(&self, before: DateTime<Utc>) -> Result<u64> {
+        on_pool!(&self.pool, |pool| {
+            let result = sqlx::query(sql(pool, "DELETE FROM decision_calls WHERE decided_at < ?"))
+                .bind(before)
+                .execute(pool)
+                .await?;
+
+            Ok(result.rows_affected())
+        })
+    }
+
+    // 保存一次锦标赛结果（report 为JSON）
+    pub async fn save_tournament_report(
+        &self,
+        user_id: &str,
+        run_at: DateTime<Utc>,
+        report: &str,
+    ) -> Result<()> {
+        on_pool!(&self.pool, |pool| {
+            sqlx::query(sql(
+                pool,
+                "INSERT INTO tournament_reports (user_id, run_at, report) VALUES (?, ?, ?)",
+            ))
+            .bind(user_id)
+            .bind(run_at)
+            .bind(report)
+            .execute(pool)
+            .await
+            .context("Failed to save tournament report")?;
+
+            Ok(())
+        })
+    }
+
+    // 保存一条下单审计记录
+    pub async fn save_execution_audit(&self, entry: &ExecutionAudit) -> Result<i64> {
+        on_pool!(&self.pool, |pool| {
+            let id: i64 = sqlx::query_scalar(
+            sql(pool, r#"INSERT INTO execution_audit (user_id, trader_id, exchange, method, endpoint, request, status, response, error, latency_ms, created_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            RETURNING id"#),
+        )
+        .bind(&entry.user_id)
+        .bind(&entry.trader_id)
+        .bind(&entry.exchange)
+        .bind(&entry.method)
+        .bind(&entry.endpoint)
+        .bind(&entry.request)
+        .bind(entry.status)
+        .bind(&entry.response)
+        .bind(&entry.error)
+        .bind(entry.latency_ms)
+        .bind(entry.created_at)
+        .fetch_one(pool)
+        .await
+        .context("Failed to save execution audit")?;
+
+            Ok(id)
+        })
+    }
+
+    // 获取交易员最近N条下单审计记录（最新的在前）
+    pub async fn get_execution_audit(
+        &self,
+        user_id: &str,
+        trader_id: &str,
+        limit: i64,
+    ) -> Result<Vec<ExecutionAudit>> {
+        on_pool!(&self.pool, |pool| {
+            let entries = sqlx::query_as::<_, ExecutionAudit>(sql(
+                pool,
+                r#"SELECT * FROM execution_audit WHERE user_id = ? AND trader_id = ?
+            ORDER BY created_at DESC, id DESC LIMIT ?"#,
+            ))
+            .bind(user_id)
+            .bind(trader_id)
+            .bind(limit)
+            .fetch_all(pool)
+            .await?;
+
+            Ok(entries)
+        })
+    }
+
+    // 删除早于 before 的审计记录，并只保留最新的 max_rows 条
+    pub async fn prune_execution_audit(&self, before: DateTime<Utc>, max_rows: i64) -> Result<u64> {
+        on_pool!(&self.pool, |pool| {
+            let expired = sqlx::query(sql(
+                pool,
+                "DELETE FROM execution_audit WHERE created_at < ?",
+            ))
+            .bind(before)
+            .execute(pool)
+            .await?;
+            let overflow = sqlx::query(sql(
+                pool,
+                r#"DELETE FROM execution_audit WHERE id NOT IN
+            (SELECT id FROM execution_audit ORDER BY id DESC LIMIT ?)"#,
+            ))
+            .bind(max_rows)
+            .execute(pool)
+            .await?;
+
+            Ok(expired.rows_affected() + overflow.rows_affected())
+        })
+    }
+
+    // 累加一次 AI 调用的用量
+    pub async fn add_ai_usage(
+        &self,
+        user_id: &str,
+        trader_id: &str,
+        day: NaiveDate,
+        tokens: i64,
+        cost_usd: f64,
+    ) -> Result<()> {
+        on_pool!(&self.pool, |pool| {
+            sqlx::query(
+            sql(pool, r#"INSERT INTO ai_usage (user_id, trader_id, day, calls, tokens, cost_usd) VALUES (?, ?, ?, 1, ?, ?)
+            ON CONFLICT(user_id, trader_id, day) DO UPDATE SET
+                calls = ai_usage.calls + 1, tokens = ai_usage.tokens + excluded.tokens,
+                cost_usd = ai_usage.cost_usd + excluded.cost_usd"#),
+        )
+        .bind(user_id)
+        .bind(trader_id)
+        .bind(day.to_string())
+        .bind(tokens)
+        .bind(cost_usd)
+        .execute(pool)
+        .await
+        .context("Failed to record AI usage")?;
+
+            Ok(())
+        })
+    }
+
+    // 获取用户自 since 起（含当天）所有交易员的 AI 估算费用
+    pub async fn get_ai_spend(&self, user_id: &str, since: NaiveDate) -> Result<f64> {
+        on_pool!(&self.pool, |pool| {
+            let spend = sqlx::query_scalar::<_, f64>(sql(
+                pool,
+                "SELECT COALESCE(SUM(cost_usd), 0.0) FROM ai_usage WHERE user_id = ? AND day >= ?",
+            ))
+            .bind(user_id)
+            .bind(since.to_string())
+            .fetch_one(pool)
+            .await?;
+
+            Ok(spend)
+        })
+    }
+
+    // 获取用户某天（UTC）各交易员的 AI 用量
+    pub async fn get_ai_usage(&self, user_id: &str, day: NaiveDate) -> Result<Vec<AiUsage>> {
+        on_pool!(&self.pool, |pool| {
+            let usage = sqlx::query_as::<_, AiUsage>(sql(
+                pool,
+                "SELECT * FROM ai_usage WHERE user_id = ? AND day = ? ORDER BY trader_id",
+            ))
+            .bind(user_id)
+            .bind(day.to_string())
+            .fetch_all(pool)
+            .await
+            .context("Failed to fetch AI usage")?;
+
+            Ok(usage)
+        })
+    }
+
+    // 保存交易员某天的报告，重新生成时覆盖旧报告
+    pub async fn save_daily_report(&self, report: &DailyReport) -> Result<()> {
+        on_pool!(&self.pool, |pool| {
+            sqlx::query(sql(
+                pool,
+                r#"INSERT INTO daily_reports (user_id, trader_id, day, trades, closed_trades, wins, realized_pnl, fees,
+                best_symbol, best_pnl, worst_symbol, worst_pnl, ai_calls, ai_cost_usd, generated_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            ON CONFLICT(trader_id, day) DO UPDATE SET
+                trades = excluded.trades, closed_trades = excluded.closed_trades, wins = excluded.wins,
+                realized_pnl = excluded.realized_pnl, fees = excluded.fees,
+                best_symbol = excluded.best_symbol, best_pnl = excluded.best_pnl,
+                worst_symbol = excluded.worst_symbol, worst_pnl = excluded.worst_pnl,
+                ai_calls = excluded.ai_calls, ai_cost_usd = excluded.ai_cost_usd,
+                generated_at = excluded.generated_at"#,
+            ))
+            .bind(&report.user_id)
+            .bind(&report.trader_id)
+            .bind(&report.day)
+            .bind(report.trades)
+            .bind(report.closed_trades)
+            .bind(report.wins)
+            .bind(money::to_f64(report.realized_pnl))
+            .bind(money::to_f64(report.fees))
+            .bind(&report.best_symbol)
+            .bind(money::to_f64(report.best_pnl))
+            .bind(&report.worst_symbol)
+            .bind(money::to_f64(report.worst_pnl))
+            .bind(report.ai_calls)
+            .bind(report.ai_cost_usd)
+            .bind(report.generated_at)
+            .execute(pool)
+            .await
+            .context("Failed to save daily report")?;
+
+            Ok(())
+        })
+    }
+
+    // 获取交易员最近的每日报告（按日期倒序）
+    pub async fn get_daily_reports(
+        &self,
+        user_id: &str,
+        trader_id: &str,
+        limit: i64,
+    ) -> Result<Vec<DailyReport>> {
+        on_pool!(&self.pool, |pool| {
+            let reports = sqlx::query_as::<_, DailyReport>(sql(
+                pool,
+                r#"SELECT * FROM daily_reports WHERE user_id = ? AND trader_id = ?
+            ORDER BY day DESC LIMIT ?"#,
+            ))
+            .bind(user_id)
+            .bind(trader_id)
+            .bind(limit)
+            .fetch_all(pool)
+            .await
+            .context("Failed to fetch daily reports")?;
+
+            Ok(reports)
+        })
+    }
+
+    // 获取用户最近一次锦标赛结果
+    pub async fn get_latest_tournament_report(&self, user_id: &str) -> Result<Option<String>> {
+        on_pool!(&self.pool, |pool| {
+            let report = sqlx::query_scalar::<_, String>(
+            sql(pool, "SELECT report FROM tournament_reports WHERE user_id = ? ORDER BY run_at DESC, id DESC LIMIT 1"),
+        )
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await?;
+
+            Ok(report)
+        })
+    }
+
+    pub async fn get_pause_window(&self, user_id: &str, id: i64) -> Result<Option<PauseWindow>> {
+        on_pool!(&self.pool, |pool| {
+            let window = sqlx::query_as::<_, PauseWindow>(sql(
+                pool,
+                "SELECT * FROM trader_pause_windows WHERE id = ? AND user_id = ?",
+            ))
+            .bind(id)
+            .bind(user_id)
+            .fetch_optional(pool)
+            .await?;
+
+            Ok(window)
+        })
+    }
+
+    // 获取交易员的暂停窗口（按暂停时间排序）
+    pub async fn get_pause_windows(
+        &self,
+        user_id: &str,
+        trader_id: &str,
+    ) -> Result<Vec<PauseWindow>> {
+        on_pool!(&self.pool, |pool| {
+            let mut conn = pool.acquire().await?;
+            fetch_pause_windows(&mut DbConn::from(&mut *conn), user_id, trader_id).await
+        })
+    }
+
+    // 在同一个读事务中执行多条查询，所有查询看到同一个数据快照，
+    // 不会读到两次查询之间并发写入的半截数据（如有持仓却没有对应成交）
+    pub async fn read_snapshot<T, F>(&self, f: F) -> Result<T>
+    where
+        F: for<'c> FnOnce(DbConn<'c>) -> ReadFuture<'c, T>,
+    {
+        on_pool!(&self.pool, |pool| {
+            let mut tx = pool.begin().await?;
+            // Postgres 默认 READ COMMITTED，每条语句各看各的快照，需显式提升隔离级别；
+            // SQLite 的读事务本身就是一致快照
+            if backend(pool) == Backend::Postgres {
+                sqlx::query("SET TRANSACTION ISOLATION LEVEL REPEATABLE READ, READ ONLY")
+                    .execute(&mut *tx)
+                    .await?;
+            }
+            let result = f(DbConn::from(&mut *tx)).await;
+            // 只读事务，无论成功与否都回滚
+            tx.rollback().await?;
+            result
+        })
+    }
+
+    // 交易员看板数据：配置、资金划转与暂停窗口，来自同一快照
+    pub async fn get_trader_snapshot(
+        &self,
+        user_id: &str,
+        trader_id: &str,
+    ) -> Result<Option<TraderSnapshot>> {
+        let (user_id, trader_id) = (user_id.to_string(), trader_id.to_string());
+        self.read_snapshot(move |mut conn| {
+            Box::pin(async move {
+                let Some(trader) = fetch_traders(&mut conn, &user_id)
+                    .await?
+                    .into_iter()
+                    .find(|t| t.id == trader_id)
+                else {
+                    return Ok(None);
+                };
+                let transfers = fetch_transfers(&mut conn, &user_id, &trader_id).await?;
+                let pause_windows = fetch_pause_windows(&mut conn, &user_id, &trader_id).await?;
+                Ok(Some(TraderSnapshot {
+                    trader,
+                    transfers,
+                    pause_windows,
+                }))
+            })
+        })
+        .await
+    }
+
+    // 所有到期需要执行的窗口：待暂停且已到暂停时间，或已暂停且已到恢复时间
+    pub async fn get_due_pause_windows(&self, now: DateTime<Utc>) -> Result<Vec<PauseWindow>> {
+        on_pool!(&self.pool, |pool| {
+            let windows = sqlx::query_as::<_, PauseWindow>(sql(
+                pool,
+                r#"SELECT * FROM trader_pause_windows
+            WHERE (state = 'pending' AND pause_at <= ?)
+               OR (state = 'paused' AND resume_at IS NOT NULL AND resume_at <= ?)
+            ORDER BY pause_at, id"#,
+            ))
+            .bind(now)
+            .bind(now)
+            .fetch_all(pool)
+            .await?;
+
+            Ok(windows)
+        })
+    }
+
+    pub async fn update_pause_window_state(
+        &self,
+        id: i64,
+        state: &str,
+        was_running: bool,
+    ) -> Result<()> {
+        on_pool!(&self.pool, |pool| {
+            sqlx::query(sql(
+                pool,
+                "UPDATE trader_pause_windows SET state = ?, was_running = ? WHERE id = ?",
+            ))
+            .bind(state)
+            .bind(was_running)
+            .bind(id)
+            .execute(pool)
+            .await
+            .context("Failed to update pause window")?;
+
+            Ok(())
+        })
+    }
+
+    // 合并所有交易员的 trading_symbols 与 custom_coins（逗号分隔），按各自的计价资产偏好标准化、
+    // 去重，并剔除交易所未上架的币种；全部为空时返回默认币种
+    pub async fn get_custom_coins(&self) -> Result<Vec<String>> {
+        let rows: Vec<(String, String, String, String)> = on_pool!(&self.pool, |pool| {
+            sqlx::query_as(sql(
+                pool,
+                r#"SELECT COALESCE(trading_symbols, ''), COALESCE(custom_coins, ''),
+                   COALESCE(exchange_id, ''), COALESCE(quote_assets, '')
+            FROM traders ORDER BY created_at, id"#,
+            ))
+            .fetch_all(pool)
+            .await
+            .context("Failed to fetch custom coins")?
+        });
+
+        let mut seen = HashSet::new();
+        let mut symbols: Vec<String> = Vec::new();
+        for (trading_symbols, custom_coins, exchange_id, quote_assets) in &rows {
+            let quotes = symbols::quotes_for(exchange_id, quote_assets);
+            for list in [trading_symbols, custom_coins] {
+                for coin in symbols::parse_list(list, &quotes) {
+                    if !seen.insert(coin.clone()) {
+                        continue;
+                    }
+                    if symbols::is_listed(&coin) {
+                        symbols.push(coin);
+                    } else {
+                        tracing::warn!("⚠️ 自定义币种 {} 未在交易所上架，已忽略", coin);
+                    }
+                }
+            }
+        }
+        if !symbols.is_empty() {
+            return Ok(symbols);
+        }
+
+        let default_json = self
+            .get_system_config("default_coins")
+            .await
+            .unwrap_or_default();
+        if let Ok(parsed) = serde_json::from_str::<Vec<String>>(&default_json) {
+            return Ok(parsed);
+        }
+        tracing::warn!("⚠️ 解析 default_coins 配置失败 or empty，使用硬编码默认值");
+        Ok(vec![
+            "BTCUSDT".to_string(),
+            "ETHUSDT".to_string(),
+            "SOLUSDT".to_string(),
+            "BNBUSDT".to_string(),
+        ])
+    }
+
+    pub async fn close(&self) -> Result<()> {
+        on_pool!(&self.pool, |pool| pool.close().await);
+        Ok(())
+    }
+
+    pub async fn load_beta_codes_from_file(
+        &self,
+        file_path: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        on_pool!(&self.pool, |pool| {
+            let content =
+                fs::read_to_string(file_path).map_err(|e| format!("读取内测码文件失败: {}", e))?;
+
+            let codes: Vec<&str> = content
+                .lines()
+                .map(|line| line.trim())
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .collect();
+
+            let total_codes = codes.len();
+
+            let mut tx = pool
+                .begin()
+                .await
+                .map_err(|e| format!("开始事务失败: {}", e))?;
+
+            let mut inserted_count = 0;
+            for code in codes {
+                let result = sqlx::query(sql(
+                    pool,
+                    "INSERT INTO beta_codes (code) VALUES (?) ON CONFLICT DO NOTHING",
+                ))
+                .bind(code)
+                .execute(&mut *tx) // Execute inside the transaction
+                .await;
+
+                match result {
+                    Ok(res) => {
+                        if res.rows_affected() > 0 {
+                            inserted_count += 1;
+                        }
+                    }
+                    Err(e) => {
+                        // Log error but continue processing other codes
+                        tracing::error!("插入内测码 {} 失败: {:?}", code, e);
+                    }
+                }
+            }
+
+            tx.commit()
+                .await
+                .map_err(|e| format!("提交事务失败: {}", e))?;
+
+            tracing::info!(
+                "✅ 成功加载 {} 个内测码到数据库 (总计 {} 个)",
+                inserted_count,
+                total_codes
+            );
+
+            Ok(())
+        })
+    }
+
+    pub async fn validate_beta_code(&self, code: &str) -> Result<bool> {
+        on_pool!(&self.pool, |pool| {
+            let result = sqlx::query(sql(pool, "SELECT used FROM beta_codes WHERE code = ?"))
+                .bind(code)
+                .execute(pool)
+                .await;
+
+            match result {
+                Ok(res) => {
+                    if res.rows_affected() > 0 {
+                        return Ok(true);
+                    }
+                }
+                Err(_) => return Ok(false),
+            }
+
+            Ok(false)
+        })
+    }
+
+    // 将内测码标记为已被该邮箱使用；码无效或已被使用时返回 false
+    pub async fn user_beta_code(&self, code: &str, user_email: &str) -> Result<bool> {
+        on_pool!(&self.pool, |pool| {
+            let result = sqlx::query(sql(
+                pool,
+                r#"
+            UPDATE beta_codes SET used = TRUE, used_by = ?, used_at = CURRENT_TIMESTAMP 
+		    WHERE code = ? AND used = FALSE
+        "#,
+            ))
+            .bind(user_email)
+            .bind(code)
+            .execute(pool)
+            .await?;
+
+            Ok(result.rows_affected() > 0)
+        })
+    }
+
+    // 批量写入内测码，返回新增数量
+    pub async fn insert_beta_codes(&self, codes: &[String]) -> Result<u64> {
+        on_pool!(&self.pool, |pool| {
+            let mut tx = pool.begin().await?;
+            let mut inserted = 0;
+            for code in codes {
+                let res = sqlx::query(sql(
+                    pool,
+                    "INSERT INTO beta_codes (code) VALUES (?) ON CONFLICT DO NOTHING",
+                ))
+                .bind(code)
+                .execute(&mut *tx)
+                .await
+                .with_context(|| format!("Failed to insert beta code {}", code))?;
+                inserted += res.rows_affected();
+            }
+            tx.commit().await.context("Failed to commit beta codes")?;
+
+            Ok(inserted)
+        })
+    }
+
+    // 在线备份数据库到指定文件（目标文件不能已存在）
+    pub async fn backup_to(&self, dest_path: &str) -> Result<()> {
+        let DbPool::Sqlite(pool) = &self.pool else {
+            anyhow::bail!("Online backup is only supported for SQLite; use pg_dump for Postgres");
+        };
+        sqlx::query("VACUUM INTO ?")
+            .bind(dest_path)
+            .execute(pool)
+            .await
+            .with_context(|| format!("Failed to back up database to '{}'", dest_path))?;
+
+        Ok(())
+    }
+
+    pub async fn get_beta_code_stats(&self) -> Result<(i64, i64)> {
+        on_pool!(&self.pool, |pool| {
+            let total: i64 = sqlx::query_scalar(sql(pool, "SELECT COUNT(*) FROM beta_codes"))
+                .fetch_one(pool)
+                .await?;
+
+            // 2. Get Used count
+            let used: i64 = sqlx::query_scalar(sql(
+                pool,
+                "SELECT COUNT(*) FROM beta_codes WHERE used = TRUE",
+            ))
+            .fetch_one(pool)
+            .await?;
+
+            Ok((total, used))
+        })
+    }
+}
+
+// SQLite 建表语句（最初的表结构，之后新增的列见 SQLITE_ADDED_COLUMNS）
+const SQLITE_TABLES: &[&str] = &[
+    // AI模型配置表
+    r#"
+    CREATE TABLE IF NOT EXISTS ai_models (
+        id TEXT PRIMARY KEY,
+        user_id TEXT NOT NULL DEFAULT 'default',
+        name TEXT NOT NULL,
+        provider TEXT NOT NULL,
+        enabled BOOLEAN DEFAULT 0,
+        api_key TEXT DEFAULT '',
+        created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+        updated_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+        FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+    )
+    "#,
+    // 交易所配置表
+    r#"
+    CREATE TABLE IF NOT EXISTS exchanges (
+        id TEXT PRIMARY KEY,
+        user_id TEXT NOT NULL DEFAULT 'default',
+        name TEXT NOT NULL,
+        type TEXT NOT NULL, -- 'cex' or 'dex'
+        enabled BOOLEAN DEFAULT 0,
+        api_key TEXT DEFAULT '',
+        secret_key TEXT DEFAULT '',
+        testnet BOOLEAN DEFAULT 0,
+        -- Hyperliquid 特定字段
+        hyperliquid_wallet_addr TEXT DEFAULT '',
+        -- Aster 特定字段
+        aster_user TEXT DEFAULT '',
+        aster_signer TEXT DEFAULT '',
+        aster_private_key TEXT DEFAULT '',
+        created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+        updated_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+        FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+    )
+    "#,
+    // 用户信号源配置表
+    r#"
+    CREATE TABLE IF NOT EXISTS user_signal_sources (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        user_id TEXT NOT NULL,
+        coin_pool_url TEXT DEFAULT '',
+        oi_top_url TEXT DEFAULT '',
+        created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+        updated_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+        FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+        UNIQUE(user_id)
+    )
+    "#,
+    // 交易员配置表
+    r#"
+    CREATE TABLE IF NOT EXISTS traders (
+        id TEXT PRIMARY KEY,
+        user_id TEXT NOT NULL DEFAULT 'default',
+        name TEXT NOT NULL,
+        ai_model_id TEXT NOT NULL,
+        exchange_id TEXT NOT NULL,
+        initial_balance REAL NOT NULL,
+        scan_interval_minutes INTEGER DEFAULT 3,
+        is_running BOOLEAN DEFAULT 0,
+        btc_eth_leverage INTEGER DEFAULT 5,
+        altcoin_leverage INTEGER DEFAULT 5,
+        trading_symbols TEXT DEFAULT '',
+        use_coin_pool BOOLEAN DEFAULT 0,
+        use_oi_top BOOLEAN DEFAULT 0,
+        created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+        updated_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+        FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+        FOREIGN KEY (ai_model_id) REFERENCES ai_models(id),
+        FOREIGN KEY (exchange_id) REFERENCES exchanges(id)
+    )
+    "#,
+    // 用户表
+    r#"
+    CREATE TABLE IF NOT EXISTS users (
+        id TEXT PRIMARY KEY,
+        email TEXT UNIQUE NOT NULL,
+        password_hash TEXT NOT NULL,
+        otp_secret TEXT,
+        otp_verified BOOLEAN DEFAULT 0,
+        created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+        updated_at DATETIME DEFAULT CURRENT_TIMESTAMP
+    )
+    "#,
+    // 系统配置表
+    r#"
+    CREATE TABLE IF NOT EXISTS system_config (
+        key TEXT PRIMARY KEY,
+        value TEXT NOT NULL,
+        updated_at DATETIME DEFAULT CURRENT_TIMESTAMP
+    )
+    "#,
+    // 决策过滤器表（用户上传的WASM模块，每个交易员最多一个）
+    r#"
+    CREATE TABLE IF NOT EXISTS decision_filters (
+        trader_id TEXT PRIMARY KEY,
+        user_id TEXT NOT NULL,
+        wasm BLOB NOT NULL,
+        sha256 TEXT NOT NULL,
+        created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+        FOREIGN KEY (trader_id) REFERENCES traders(id) ON DELETE CASCADE
+    )
+    "#,
+    // 定时任务表（任务定义 + 最近一次运行状态）
+    r#"
+    CREATE TABLE IF NOT EXISTS scheduled_jobs (
+        name TEXT PRIMARY KEY,
+        schedule TEXT NOT NULL,
+        enabled BOOLEAN DEFAULT 1,
+        jitter_secs INTEGER DEFAULT 0,
+        last_started_at DATETIME DEFAULT NULL,
+        last_finished_at DATETIME DEFAULT NULL,
+        last_duration_ms INTEGER DEFAULT NULL,
+        last_outcome TEXT DEFAULT NULL,
+        last_error TEXT DEFAULT NULL,
+        run_count INTEGER DEFAULT 0,
+        failure_count INTEGER DEFAULT 0,
+        skipped_count INTEGER DEFAULT 0
+    )
+    "#,
+    // 资金划转表（充值为正、提现为负），用于剔除出入金对收益率的影响
+    r#"
+    CREATE TABLE IF NOT EXISTS account_transfers (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        user_id TEXT NOT NULL,
+        trader_id TEXT NOT NULL,
+        amount REAL NOT NULL,
+        asset TEXT NOT NULL DEFAULT 'USDT',
+        occurred_at DATETIME NOT NULL,
+        source TEXT NOT NULL DEFAULT 'manual', -- 'manual' or 'income'
+        external_id TEXT DEFAULT NULL, -- 交易所流水号，用于去重
+        note TEXT DEFAULT '',
+        created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+        UNIQUE (trader_id, external_id),
+        FOREIGN KEY (trader_id) REFERENCES traders(id) ON DELETE CASCADE
+    )
+    "#,
+    // 交易员定时暂停/恢复窗口
+    r#"
+    CREATE TABLE IF NOT EXISTS trader_pause_windows (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        user_id TEXT NOT NULL,
+        trader_id TEXT NOT NULL,
+        pause_at DATETIME NOT NULL,
+        resume_at DATETIME DEFAULT NULL, -- NULL 表示暂停后不自动恢复
+        reason TEXT DEFAULT '',
+        state TEXT NOT NULL DEFAULT 'pending', -- pending / paused / done / cancelled
+        was_running BOOLEAN DEFAULT 0, -- 暂停时交易员是否在运行，决定到期后是否恢复
+        created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+        FOREIGN KEY (trader_id) REFERENCES traders(id) ON DELETE CASCADE
+    )
+    "#,
+    // 决策记录（payload 为记录JSON，启用加密时为按用户密钥加密的密文）
+    r#"
+    CREATE TABLE IF NOT EXISTS decision_records (
+        id TEXT NOT NULL,
+        user_id TEXT NOT NULL,
+        trader_id TEXT NOT NULL,
+        cycle_number INTEGER NOT NULL,
+        timestamp DATETIME NOT NULL,
+        payload BLOB NOT NULL,
+        encrypted BOOLEAN DEFAULT 0,
+        created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+        PRIMARY KEY (trader_id, id),
+        FOREIGN KEY (trader_id) REFERENCES traders(id) ON DELETE CASCADE
+    )
+    "#,
+    // 模型评测场景（冻结的提示词与当时价格，到期后补上结算价格）
+    r#"
+    CREATE TABLE IF NOT EXISTS eval_scenarios (
+        id TEXT PRIMARY KEY,
+        user_id TEXT NOT NULL,
+        trader_id TEXT NOT NULL,
+        captured_at DATETIME NOT NULL,
+        system_prompt TEXT NOT NULL,
+        user_prompt TEXT NOT NULL,
+        entry_prices TEXT NOT NULL, -- JSON: {symbol: price}
+        exit_prices TEXT DEFAULT NULL, -- JSON，NULL 表示尚未结算
+        settled_at DATETIME DEFAULT NULL,
+        FOREIGN KEY (trader_id) REFERENCES traders(id) ON DELETE CASCADE
+    )
+    "#,
+    // 模型评测锦标赛结果
+    r#"
+    CREATE TABLE IF NOT EXISTS tournament_reports (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        user_id TEXT NOT NULL,
+        run_at DATETIME NOT NULL,
+        report TEXT NOT NULL
+    )
+    "#,
+    // 成交记录（每笔成交一行，手续费为估算值或交易所返回值）
+    r#"
+    CREATE TABLE IF NOT EXISTS trades (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        user_id TEXT NOT NULL,
+        trader_id TEXT NOT NULL,
+        symbol TEXT NOT NULL,
+        side TEXT NOT NULL, -- buy / sell
+        action TEXT NOT NULL, -- open_long / open_short / close_long / close_short
+        quantity REAL NOT NULL,
+        price REAL NOT NULL,
+        fee REAL DEFAULT 0,
+        order_id INTEGER DEFAULT 0,
+        group_id TEXT DEFAULT '', -- 配对交易各条腿共用的组ID
+        executed_at DATETIME NOT NULL,
+        FOREIGN KEY (trader_id) REFERENCES traders(id) ON DELETE CASCADE
+    )
+    "#,
+    // 账户权益快照（每个决策周期一条）
+    r#"
+    CREATE TABLE IF NOT EXISTS pnl_snapshots (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        user_id TEXT NOT NULL,
+        trader_id TEXT NOT NULL,
+        taken_at DATETIME NOT NULL,
+        total_equity REAL NOT NULL,
+        available_balance REAL DEFAULT 0,
+        unrealized_pnl REAL DEFAULT 0,
+        margin_used REAL DEFAULT 0,
+        position_count INTEGER DEFAULT 0,
+        FOREIGN KEY (trader_id) REFERENCES traders(id) ON DELETE CASCADE
+    )
+    "#,
+    // 下单审计记录（原始请求/响应，已去除签名等敏感字段）
+    r#"
+    CREATE TABLE IF NOT EXISTS execution_audit (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        user_id TEXT DEFAULT '',
+        trader_id TEXT DEFAULT '',
+        exchange TEXT NOT NULL,
+        method TEXT NOT NULL,
+        endpoint TEXT NOT NULL,
+        request TEXT NOT NULL, -- JSON: {参数: 值}
+        status INTEGER DEFAULT 0, -- HTTP 状态码，0 表示未收到响应
+        response TEXT DEFAULT '',
+        error TEXT DEFAULT '',
+        latency_ms INTEGER DEFAULT 0,
+        created_at DATETIME NOT NULL
+    )
+    "#,
+    // AI 调用用量（按用户、交易员、日期汇总，费用为估算值）
+    r#"
+    CREATE TABLE IF NOT EXISTS ai_usage (
+        user_id TEXT NOT NULL,
+        trader_id TEXT NOT NULL,
+        day TEXT NOT NULL, -- YYYY-MM-DD (UTC)
+        calls INTEGER DEFAULT 0,
+        tokens INTEGER DEFAULT 0,
+        cost_usd REAL DEFAULT 0,
+        PRIMARY KEY (user_id, trader_id, day)
+    )
+    "#,
+    // 内测码表
+    r#"
+    CREATE TABLE IF NOT EXISTS beta_codes (
+        code TEXT PRIMARY KEY,
+        used BOOLEAN DEFAULT 0,
+        used_by TEXT DEFAULT '',
+        used_at DATETIME DEFAULT NULL,
+        created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+    )
+    "#,
+];
+
+// 触发器：自动更新 updated_at
+const SQLITE_TRIGGERS: &[&str] = &[
+    r#"
+    CREATE TRIGGER IF NOT EXISTS update_users_updated_at
+			AFTER UPDATE ON users
+			BEGIN
+				UPDATE users SET updated_at = CURRENT_TIMESTAMP WHERE id = NEW.id;
+			END
+    "#,
+    r#"
+    CREATE TRIGGER IF NOT EXISTS update_ai_models_updated_at
+			AFTER UPDATE ON ai_models
+			BEGIN
+				UPDATE ai_models SET updated_at = CURRENT_TIMESTAMP WHERE id = NEW.id;
+			END
+    "#,
+    r#"
+    CREATE TRIGGER IF NOT EXISTS update_exchanges_updated_at
+			AFTER UPDATE ON exchanges
+			BEGIN
+				UPDATE exchanges SET updated_at = CURRENT_TIMESTAMP WHERE id = NEW.id;
+			END
+    "#,
+    r#"
+    CREATE TRIGGER IF NOT EXISTS update_traders_updated_at
+			AFTER UPDATE ON traders
+			BEGIN
+				UPDATE traders SET updated_at = CURRENT_TIMESTAMP WHERE id = NEW.id;
+			END
+    "#,
+    r#"
+    CREATE TRIGGER IF NOT EXISTS update_user_signal_sources_updated_at
+			AFTER UPDATE ON user_signal_sources
+			BEGIN
+				UPDATE user_signal_sources SET updated_at = CURRENT_TIMESTAMP WHERE id = NEW.id;
+			END
+    "#,
+    r#"
+    CREATE TRIGGER IF NOT EXISTS update_system_config_updated_at
+			AFTER UPDATE ON system_config
+			BEGIN
+				UPDATE system_config SET updated_at = CURRENT_TIMESTAMP WHERE key = NEW.key;
+			END
+    "#,
+];
+
+// Postgres 建表语句：与 SQLite 的表结构一致（含历次 ALTER 新增的列），
+// 不声明外键——默认数据属于并不存在的 'default' 用户
+const POSTGRES_TABLES: &[&str] = &[
+    // AI模型配置表
+    r#"
+    CREATE TABLE IF NOT EXISTS ai_models (
+        id TEXT PRIMARY KEY,
+        user_id TEXT NOT NULL DEFAULT 'default',
+        name TEXT NOT NULL,
+        provider TEXT NOT NULL,
+        enabled BOOLEAN DEFAULT FALSE,
+        api_key TEXT DEFAULT '',
+        custom_api_url TEXT DEFAULT '',
+        custom_model_name TEXT DEFAULT '',
+        created_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP,
+        updated_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP
+    )
+    "#,
+    // 交易所配置表（复合主键）
+    r#"
+    CREATE TABLE IF NOT EXISTS exchanges (
+        id TEXT NOT NULL,
+        user_id TEXT NOT NULL DEFAULT 'default',
+        name TEXT NOT NULL,
+        type TEXT NOT NULL,
+        enabled BOOLEAN DEFAULT FALSE,
+        api_key TEXT DEFAULT '',
+        secret_key TEXT DEFAULT '',
+        testnet BOOLEAN DEFAULT FALSE,
+        hyperliquid_wallet_addr TEXT DEFAULT '',
+        aster_user TEXT DEFAULT '',
+        aster_signer TEXT DEFAULT '',
+        aster_private_key TEXT DEFAULT '',
+        created_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP,
+        updated_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP,
+        PRIMARY KEY (id, user_id)
+    )
+    "#,
+    // 用户信号源配置表
+    r#"
+    CREATE TABLE IF NOT EXISTS user_signal_sources (
+        id SERIAL PRIMARY KEY,
+        user_id TEXT NOT NULL UNIQUE,
+        coin_pool_url TEXT DEFAULT '',
+        oi_top_url TEXT DEFAULT '',
+        created_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP,
+        updated_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP
+    )
+    "#,
+    // 交易员配置表
+    r#"
+    CREATE TABLE IF NOT EXISTS traders (
+        id TEXT PRIMARY KEY,
+        user_id TEXT NOT NULL DEFAULT 'default',
+        name TEXT NOT NULL,
+        ai_model_id TEXT NOT NULL,
+        exchange_id TEXT NOT NULL,
+        initial_balance DOUBLE PRECISION NOT NULL,
+        scan_interval_minutes INTEGER DEFAULT 3,
+        is_running BOOLEAN DEFAULT FALSE,
+        btc_eth_leverage INTEGER DEFAULT 5,
+        altcoin_leverage INTEGER DEFAULT 5,
+        trading_symbols TEXT DEFAULT '',
+        use_coin_pool BOOLEAN DEFAULT FALSE,
+        use_oi_top BOOLEAN DEFAULT FALSE,
+        custom_prompt TEXT DEFAULT '',
+        override_base_prompt BOOLEAN DEFAULT FALSE,
+        is_cross_margin BOOLEAN DEFAULT TRUE,
+        use_default_coins BOOLEAN DEFAULT TRUE,
+        custom_coins TEXT DEFAULT '',
+        system_prompt_template TEXT DEFAULT 'default',
+        quote_assets TEXT DEFAULT '',
+        stop_loss_cooldown_minutes INTEGER DEFAULT 30,
+        watch_only BOOLEAN DEFAULT FALSE,
+        created_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP,
+        updated_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP
+    )
+    "#,
+    // 用户表
+    r#"
+    CREATE TABLE IF NOT EXISTS users (
+        id TEXT PRIMARY KEY,
+        email TEXT UNIQUE NOT NULL,
+        password_hash TEXT NOT NULL,
+        otp_secret TEXT,
+        otp_verified BOOLEAN DEFAULT FALSE,
+        locale TEXT DEFAULT 'en',
+        timezone TEXT DEFAULT 'UTC',
+        max_margin_usage_pct DOUBLE PRECISION DEFAULT 80,
+        created_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP,
+        updated_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP
+    )
+    "#,
+    // 系统配置表
+    r#"
+    CREATE TABLE IF NOT EXISTS system_config (
+        key TEXT PRIMARY KEY,
+        value TEXT NOT NULL,
+        updated_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP
+    )
+    "#,
+    // 决策过滤器表
+    r#"
+    CREATE TABLE IF NOT EXISTS decision_filters (
+        trader_id TEXT PRIMARY KEY,
+        user_id TEXT NOT NULL,
+        wasm BYTEA NOT NULL,
+        sha256 TEXT NOT NULL,
+        created_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP
+    )
+    "#,
+    // 定时任务表
+    r#"
+    CREATE TABLE IF NOT EXISTS scheduled_jobs (
+        name TEXT PRIMARY KEY,
+        schedule TEXT NOT NULL,
+        enabled BOOLEAN DEFAULT TRUE,
+        jitter_secs BIGINT DEFAULT 0,
+        last_started_at TIMESTAMPTZ DEFAULT NULL,
+        last_finished_at TIMESTAMPTZ DEFAULT NULL,
+        last_duration_ms BIGINT DEFAULT NULL,
+        last_outcome TEXT DEFAULT NULL,
+        last_error TEXT DEFAULT NULL,
+        run_count BIGINT DEFAULT 0,
+        failure_count BIGINT DEFAULT 0,
+        skipped_count BIGINT DEFAULT 0
+    )
+    "#,
+    // 资金划转表
+    r#"
+    CREATE TABLE IF NOT EXISTS account_transfers (
+        id BIGSERIAL PRIMARY KEY,
+        user_id TEXT NOT NULL,
+        trader_id TEXT NOT NULL,
+        amount DOUBLE PRECISION NOT NULL,
+        asset TEXT NOT NULL DEFAULT 'USDT',
+        occurred_at TIMESTAMPTZ NOT NULL,
+        source TEXT NOT NULL DEFAULT 'manual',
+        external_id TEXT DEFAULT NULL,
+        note TEXT DEFAULT '',
+        created_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP,
+        UNIQUE (trader_id, external_id)
+    )
+    "#,
+    // 交易员定时暂停/恢复窗口
+    r#"
+    CREATE TABLE IF NOT EXISTS trader_pause_windows (
+        id BIGSERIAL PRIMARY KEY,
+        user_id TEXT NOT NULL,
+        trader_id TEXT NOT NULL,
+        pause_at TIMESTAMPTZ NOT NULL,
+        resume_at TIMESTAMPTZ DEFAULT NULL,
+        reason TEXT DEFAULT '',
+        state TEXT NOT NULL DEFAULT 'pending',
+        was_running BOOLEAN DEFAULT FALSE,
+        created_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP
+    )
+    "#,
+    // 决策记录
+    r#"
+    CREATE TABLE IF NOT EXISTS decision_records (
+        id TEXT NOT NULL,
+        user_id TEXT NOT NULL,
+        trader_id TEXT NOT NULL,
+        cycle_number INTEGER NOT NULL,
+        timestamp TIMESTAMPTZ NOT NULL,
+        payload BYTEA NOT NULL,
+        encrypted BOOLEAN DEFAULT FALSE,
+        created_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP,
+        PRIMARY KEY (trader_id, id)
+    )
+    "#,
+    // 模型评测场景
+    r#"
+    CREATE TABLE IF NOT EXISTS eval_scenarios (
+        id TEXT PRIMARY KEY,
+        user_id TEXT NOT NULL,
+        trader_id TEXT NOT NULL,
+        captured_at TIMESTAMPTZ NOT NULL,
+        system_prompt TEXT NOT NULL,
+        user_prompt TEXT NOT NULL,
+        entry_prices TEXT NOT NULL,
+        exit_prices TEXT DEFAULT NULL,
+        settled_at TIMESTAMPTZ DEFAULT NULL
+    )
+    "#,
+    // 模型评测锦标赛结果
+    r#"
+    CREATE TABLE IF NOT EXISTS tournament_reports (
+        id BIGSERIAL PRIMARY KEY,
+        user_id TEXT NOT NULL,
+        run_at TIMESTAMPTZ NOT NULL,
+        report TEXT NOT NULL
+    )
+    "#,
+    // 成交记录
+    r#"
+    CREATE TABLE IF NOT EXISTS trades (
+        id BIGSERIAL PRIMARY KEY,
+        user_id TEXT NOT NULL,
+        trader_id TEXT NOT NULL,
+        symbol TEXT NOT NULL,
+        side TEXT NOT NULL,
+        action TEXT NOT NULL,
+        quantity DOUBLE PRECISION NOT NULL,
+        price DOUBLE PRECISION NOT NULL,
+        fee DOUBLE PRECISION DEFAULT 0,
+        order_id BIGINT DEFAULT 0,
+        group_id TEXT DEFAULT '',
+        executed_at TIMESTAMPTZ NOT NULL
+    )
+    "#,
+    // 账户权益快照
+    r#"
+    CREATE TABLE IF NOT EXISTS pnl_snapshots (
+        id BIGSERIAL PRIMARY KEY,
+        user_id TEXT NOT NULL,
+        trader_id TEXT NOT NULL,
+        taken_at TIMESTAMPTZ NOT NULL,
+        total_equity DOUBLE PRECISION NOT NULL,
+        available_balance DOUBLE PRECISION DEFAULT 0,
+        unrealized_pnl DOUBLE PRECISION DEFAULT 0,
+        margin_used DOUBLE PRECISION DEFAULT 0,
+        position_count INTEGER DEFAULT 0
+    )
+    "#,
+    // 下单审计记录
+    r#"
+    CREATE TABLE IF NOT EXISTS execution_audit (
+        id BIGSERIAL PRIMARY KEY,
+        user_id TEXT DEFAULT '',
+        trader_id TEXT DEFAULT '',
+        exchange TEXT NOT NULL,
+        method TEXT NOT NULL,
+        endpoint TEXT NOT NULL,
+        request TEXT NOT NULL,
+        status BIGINT DEFAULT 0,
+        response TEXT DEFAULT '',
+        error TEXT DEFAULT '',
+        latency_ms BIGINT DEFAULT 0,
+        created_at TIMESTAMPTZ NOT NULL
+    )
+    "#,
+    // AI 调用用量
+    r#"
+    CREATE TABLE IF NOT EXISTS ai_usage (
+        user_id TEXT NOT NULL,
+        trader_id TEXT NOT NULL,
+        day TEXT NOT NULL,
+        calls BIGINT DEFAULT 0,
+        tokens BIGINT DEFAULT 0,
+        cost_usd DOUBLE PRECISION DEFAULT 0,
+        PRIMARY KEY (user_id, trader_id, day)
+    )
+    "#,
+    // 内测码表
+    r#"
+    CREATE TABLE IF NOT EXISTS beta_codes (
+        code TEXT PRIMARY KEY,
+        used BOOLEAN DEFAULT FALSE,
+        used_by TEXT DEFAULT '',
+        used_at TIMESTAMPTZ DEFAULT NULL,
+        created_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP
+    )
+    "#,
+    // 触发器函数：自动更新 updated_at
+    r#"
+    CREATE OR REPLACE FUNCTION set_updated_at() RETURNS TRIGGER AS $$
+    BEGIN
+        NEW.updated_at = CURRENT_TIMESTAMP;
+        RETURN NEW;
+    END;
+    $$ LANGUAGE plpgsql
+    "#,
+];
+
+// 带 updated_at 触发器的表
+const POSTGRES_UPDATED_AT_TABLES: &[&str] = &[
+    "users",
+    "ai_models",
+    "exchanges",
+    "traders",
+    "user_signal_sources",
+    "system_config",
+];
+
+// 数据库迁移：按版本号顺序执行，只升不降，已应用的版本记录在 schema_version 表
+struct Migration {
+    version: i64,
+    name: &'static str,
+    run: for<'c> fn(DbConn<'c>) -> MigrationFuture<'c>,
+}
+
+type MigrationFuture<'c> = Pin<Box<dyn Future<Output = Result<()>> + Send + 'c>>;
+
+// 新的表结构变更只能以新版本号追加到末尾，已发布的迁移不得修改
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "initial_schema",
+        run: initial_schema,
+    },
+    Migration {
+        version: 2,
+        name: "legacy_columns",
+        run: legacy_columns,
+    },
+    Migration {
+        version: 3,
+        name: "exchanges_composite_key",
+        run: exchanges_composite_key,
+    },
+    Migration {
+        version: 4,
+        name: "trade_execution_quality",
+        run: trade_execution_quality,
+    },
+    Migration {
+        version: 5,
+        name: "prompt_versions",
+        run: prompt_versions,
+    },
+    Migration {
+        version: 6,
+        name: "trade_proposals",
+        run: trade_proposals,
+    },
+    Migration {
+        version: 7,
+        name: "decision_calls",
+        run: decision_calls,
+    },
+    Migration {
+        version: 8,
+        name: "otp_recovery_codes",
+        run: otp_recovery_codes,
+    },
+    Migration {
+        version: 9,
+        name: "user_roles",
+        run: user_roles,
+    },
+    Migration {
+        version: 10,
+        name: "trader_timeframes",
+        run: trader_timeframes,
+    },
+    Migration {
+        version: 11,
+        name: "trader_position_sizing",
+        run: trader_position_sizing,
+    },
+    Migration {
+        version: 12,
+        name: "prompt_templates",
+        run: prompt_templates,
+    },
+    Migration {
+        version: 13,
+        name: "prompt_variants",
+        run: prompt_variants,
+    },
+    Migration {
+        version: 14,
+        name: "reconciliation_events",
+        run: reconciliation_events,
+    },
+    Migration {
+        version: 15,
+        name: "trader_margin_alerts",
+        run: trader_margin_alerts,
+    },
+    Migration {
+        version: 16,
+        name: "daily_reports",
+        run: daily_reports,
+    },
+    Migration {
+        version: 17,
+        name: "auth_lockouts",
+        run: auth_lockouts,
+    },
+    Migration {
+        version: 18,
+        name: "audit_log",
+        run: audit_log,
+    },
+    Migration {
+        version: 19,
+        name: "trader_strategy",
+        run: trader_strategy,
+    },
+    Migration {
+        version: 20,
+        name: "trader_trading_windows",
+        run: trader_trading_windows,
+    },
+];
+
+/// Schema version a fully migrated database is at.
+pub fn latest_schema_version() -> i64 {
+    MIGRATIONS.last().map(|m| m.version).unwrap_or_default()
+}
+
+const SQLITE_SCHEMA_VERSION: &str = r#"
+    CREATE TABLE IF NOT EXISTS schema_version (
+        version INTEGER PRIMARY KEY,
+        name TEXT NOT NULL,
+        applied_at DATETIME DEFAULT CURRENT_TIMESTAMP
+    )
+"#;
+
+const POSTGRES_SCHEMA_VERSION: &str = r#"
+    CREATE TABLE IF NOT EXISTS schema_version (
+        version BIGINT PRIMARY KEY,
+        name TEXT NOT NULL,
+        applied_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP
+    )
+"#;
+
+impl DbConn<'_> {
+    fn backend(&self) -> Backend {
+        match self {
+            DbConn::Sqlite(_) => Backend::Sqlite,
+            DbConn::Postgres(_) => Backend::Postgres,
+        }
+    }
+}
+
+async fn execute(conn: &mut DbConn<'_>, query: &str) -> Result<()> {
+    on_conn!(conn, |c| sqlx::query(query)
+        .execute(&mut **c)
+        .await
+        .map(|_| ()))
+    .with_context(|| format!("Failed to execute: {}", query.trim()))
+}
+
+// Postgres 上在事务内加锁，直到事务结束；SQLite 的写事务本身即互斥
+async fn lock_schema(conn: &mut DbConn<'_>) -> Result<()> {
+    if conn.backend() == Backend::Postgres {
+        execute(
+            conn,
+            "SELECT pg_advisory_xact_lock(hashtext('aitrading_schema'))",
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+async fn has_column(conn: &mut DbConn<'_>, table: &str, column: &str) -> Result<bool> {
+    let count: i64 = match conn {
+        DbConn::Sqlite(c) => {
+            sqlx::query_scalar("SELECT COUNT(*) FROM pragma_table_info(?) WHERE name = ?")
+                .bind(table)
+                .bind(column)
+                .fetch_one(&mut **c)
+                .await?
+        }
+        DbConn::Postgres(c) => {
+            sqlx::query_scalar(
+                r#"SELECT COUNT(*) FROM information_schema.columns
+                WHERE table_schema = current_schema() AND table_name = $1 AND column_name = $2"#,
+            )
+            .bind(table)
+            .bind(column)
+            .fetch_one(&mut **c)
+            .await?
+        }
+    };
+    Ok(count > 0)
+}
+
+// 1: 建表与 updated_at 触发器。已有数据库的表保持原样，缺少的列由后续迁移补齐
+fn initial_schema(mut conn: DbConn<'_>) -> MigrationFuture<'_> {
+    Box::pin(async move {
+        match conn.backend() {
+            Backend::Sqlite => {
+                for query in SQLITE_TABLES.iter().chain(SQLITE_TRIGGERS) {
+                    execute(&mut conn, query).await?;
+                }
+            }
+            Backend::Postgres => {
+                for query in POSTGRES_TABLES {
+                    execute(&mut conn, query).await?;
+                }
+                for table in POSTGRES_UPDATED_AT_TABLES {
+                    execute(
+                        &mut conn,
+                        &format!("DROP TRIGGER IF EXISTS update_{table}_updated_at ON {table}"),
+                    )
+                    .await?;
+                    execute(
+                        &mut conn,
+                        &format!(
+                            "CREATE TRIGGER update_{table}_updated_at BEFORE UPDATE ON {table} \
+                             FOR EACH ROW EXECUTE FUNCTION set_updated_at()"
+                        ),
+                    )
+                    .await?;
+                }
+            }
+        }
+        Ok(())
+    })
+}
+
+// 早期版本陆续追加的列：(表, 列, 定义)。Postgres 建表时已包含这些列
+const SQLITE_ADDED_COLUMNS: &[(&str, &str, &str)] = &[
+    ("exchanges", "hyperliquid_wallet_addr", "TEXT DEFAULT ''"),
+    ("exchanges", "aster_user", "TEXT DEFAULT ''"),
+    ("exchanges", "aster_signer", "TEXT DEFAULT ''"),
+    ("exchanges", "aster_private_key", "TEXT DEFAULT ''"),
+    ("traders", "custom_prompt", "TEXT DEFAULT ''"),
+    ("traders", "override_base_prompt", "BOOLEAN DEFAULT 0"),
+    ("traders", "is_cross_margin", "BOOLEAN DEFAULT 1"),
+    ("traders", "use_default_coins", "BOOLEAN DEFAULT 1"),
+    ("traders", "custom_coins", "TEXT DEFAULT ''"),
+    ("traders", "btc_eth_leverage", "INTEGER DEFAULT 5"),
+    ("traders", "altcoin_leverage", "INTEGER DEFAULT 5"),
+    ("traders", "trading_symbols", "TEXT DEFAULT ''"),
+    ("traders", "use_coin_pool", "BOOLEAN DEFAULT 0"),
+    ("traders", "use_oi_top", "BOOLEAN DEFAULT 0"),
+    (
+        "traders",
+        "system_prompt_template",
+        "TEXT DEFAULT 'default'",
+    ),
+    ("traders", "quote_assets", "TEXT DEFAULT ''"),
+    (
+        "traders",
+        "stop_loss_cooldown_minutes",
+        "INTEGER DEFAULT 30",
+    ),
+    ("traders", "watch_only", "BOOLEAN DEFAULT 0"),
+    ("ai_models", "custom_api_url", "TEXT DEFAULT ''"),
+    ("ai_models", "custom_model_name", "TEXT DEFAULT ''"),
+    ("users", "locale", "TEXT DEFAULT 'en'"),
+    ("users", "timezone", "TEXT DEFAULT 'UTC'"),
+    ("users", "max_margin_usage_pct", "REAL DEFAULT 80"),
+];
+
+// 2: 补齐早期版本通过 ALTER 追加的列，已存在的列跳过
+fn legacy_columns(mut conn: DbConn<'_>) -> MigrationFuture<'_> {
+    Box::pin(async move {
+        if conn.backend() == Backend::Postgres {
+            return Ok(());
+        }
+        for &(table, column, definition) in SQLITE_ADDED_COLUMNS {
+            if !has_column(&mut conn, table, column).await? {
+                execute(
+                    &mut conn,
+                    &format!("ALTER TABLE {table} ADD COLUMN {column} {definition}"),
+                )
+                .await?;
+            }
+        }
+        Ok(())
+    })
+}
+
+// exchanges 表的全部列，按新表的列顺序
+const EXCHANGE_COLUMNS: &str = "id, user_id, name, type, enabled, api_key, secret_key, testnet, \
+    hyperliquid_wallet_addr, aster_user, aster_signer, aster_private_key, created_at, updated_at";
+
+// 3: SQLite 的 exchanges 表改为 (id, user_id) 复合主键，使每个用户都能配置同一交易所。
+// Postgres 建表时已是复合主键
+fn exchanges_composite_key(mut conn: DbConn<'_>) -> MigrationFuture<'_> {
+    Box::pin(async move {
+        let DbConn::Sqlite(c) = &mut conn else {
+            return Ok(());
+        };
+        let pk_count: i64 =
+            sqlx::query_scalar("SELECT COUNT(*) FROM pragma_table_info('exchanges') WHERE pk > 0")
+                .fetch_one(&mut **c)
+                .await?;
+        if pk_count >= 2 {
+            return Ok(());
+        }
+
+        tracing::info!("🔄 开始迁移exchanges表...");
+        execute(
+            &mut conn,
+            r#"
+            CREATE TABLE exchanges_new (
+                id TEXT NOT NULL,
+                user_id TEXT NOT NULL DEFAULT 'default',
+                name TEXT NOT NULL,
+                type TEXT NOT NULL,
+                enabled BOOLEAN DEFAULT 0,
+                api_key TEXT DEFAULT '',
+                secret_key TEXT DEFAULT '',
+                testnet BOOLEAN DEFAULT 0,
+                hyperliquid_wallet_addr TEXT DEFAULT '',
+                aster_user TEXT DEFAULT '',
+                aster_signer TEXT DEFAULT '',
+                aster_private_key TEXT DEFAULT '',
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                updated_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                PRIMARY KEY (id, user_id),
+                FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+            )
+            "#,
+        )
+        .await?;
+        // 按列名复制：旧表中后加的列排在末尾，与新表顺序不同
+        execute(
+            &mut conn,
+            &format!(
+                "INSERT INTO exchanges_new ({EXCHANGE_COLUMNS}) SELECT {EXCHANGE_COLUMNS} FROM exchanges"
+            ),
+        )
+        .await?;
+        execute(&mut conn, "DROP TABLE exchanges").await?;
+        execute(&mut conn, "ALTER TABLE exchanges_new RENAME TO exchanges").await?;
+        // 删除旧表时触发器也一并删除，需重新创建
+        execute(
+            &mut conn,
+            r#"
+            CREATE TRIGGER IF NOT EXISTS update_exchanges_updated_at
+                AFTER UPDATE ON exchanges
+                BEGIN
+                    UPDATE exchanges SET updated_at = CURRENT_TIMESTAMP
+                    WHERE id = NEW.id AND user_id = NEW.user_id;
+                END
+            "#,
+        )
         .await?;
-
+        tracing::info!("✅ exchanges表迁移完成");
         Ok(())
-    }
+    })
+}
 
-    pub async fn get_custom_coins(&self) -> Result<Vec<String>> {
-        let query =
-            "SELECT GROUP_CONCAT(custom_coins SEPARATOR ',') FROM traders WHERE custom_coins != ''";
+// 4: trades 表记录决策时的参考价格与成交耗时，用于统计滑点和延迟
+fn trade_execution_quality(mut conn: DbConn<'_>) -> MigrationFuture<'_> {
+    Box::pin(async move {
+        let columns: &[(&str, &str)] = match conn.backend() {
+            Backend::Sqlite => &[
+                ("expected_price", "REAL DEFAULT 0"),
+                ("fill_latency_ms", "INTEGER DEFAULT 0"),
+            ],
+            Backend::Postgres => &[
+                ("expected_price", "DOUBLE PRECISION DEFAULT 0"),
+                ("fill_latency_ms", "BIGINT DEFAULT 0"),
+            ],
+        };
+        for &(column, definition) in columns {
+            if !has_column(&mut conn, "trades", column).await? {
+                execute(
+                    &mut conn,
+                    &format!("ALTER TABLE trades ADD COLUMN {column} {definition}"),
+                )
+                .await?;
+            }
+        }
+        Ok(())
+    })
+}
 
-        let raw_result: Option<String> = match sqlx::query_scalar(query).fetch_one(&self.pool).await
-        {
-            Ok(res) => res, // Can be None (if NULL) or Some(String)
-            Err(e) => {
-                log::error!("Error fetching custom_coins: {:?}", e);
-                None
+// 5: 交易员提示词的版本历史，每次修改记录一条（含diff与修改人）
+fn prompt_versions(mut conn: DbConn<'_>) -> MigrationFuture<'_> {
+    Box::pin(async move {
+        let query = match conn.backend() {
+            Backend::Sqlite => {
+                r#"
+                CREATE TABLE IF NOT EXISTS prompt_versions (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    user_id TEXT NOT NULL,
+                    trader_id TEXT NOT NULL,
+                    version INTEGER NOT NULL, -- 按交易员递增，从1开始
+                    custom_prompt TEXT NOT NULL DEFAULT '',
+                    override_base_prompt BOOLEAN NOT NULL DEFAULT 0,
+                    system_prompt_template TEXT NOT NULL DEFAULT 'default',
+                    diff TEXT NOT NULL DEFAULT '', -- 相对上一版本的unified diff
+                    author TEXT NOT NULL DEFAULT '',
+                    note TEXT NOT NULL DEFAULT '',
+                    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                    UNIQUE (user_id, trader_id, version),
+                    FOREIGN KEY (trader_id) REFERENCES traders(id) ON DELETE CASCADE
+                )
+                "#
+            }
+            Backend::Postgres => {
+                r#"
+                CREATE TABLE IF NOT EXISTS prompt_versions (
+                    id BIGSERIAL PRIMARY KEY,
+                    user_id TEXT NOT NULL,
+                    trader_id TEXT NOT NULL,
+                    version BIGINT NOT NULL,
+                    custom_prompt TEXT NOT NULL DEFAULT '',
+                    override_base_prompt BOOLEAN NOT NULL DEFAULT FALSE,
+                    system_prompt_template TEXT NOT NULL DEFAULT 'default',
+                    diff TEXT NOT NULL DEFAULT '',
+                    author TEXT NOT NULL DEFAULT '',
+                    note TEXT NOT NULL DEFAULT '',
+                    created_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP,
+                    UNIQUE (user_id, trader_id, version)
+                )
+                "#
             }
         };
+        execute(&mut conn, query).await
+    })
+}
 
-        let symbol_str = raw_result.unwrap_or_default();
-        let mut symbols: Vec<String> = Vec::new();
-
-        if symbol_str.is_empty() {
-            let default_json = self
-                .get_system_config("default_coins")
-                .await
-                .unwrap_or_default();
-
-            if let Ok(parsed) = serde_json::from_str::<Vec<String>>(&default_json) {
-                symbols = parsed;
-            } else {
-                log::warn!("⚠️ 解析 default_coins 配置失败 or empty，使用硬编码默认值");
-                symbols = vec![
-                    "BTCUSDT".to_string(),
-                    "ETHUSDT".to_string(),
-                    "SOLUSDT".to_string(),
-                    "BNBUSDT".to_string(),
-                ];
+// 6: 人工审批模式：交易员的审批阈值与有效期，以及待审批的交易提案
+fn trade_proposals(mut conn: DbConn<'_>) -> MigrationFuture<'_> {
+    Box::pin(async move {
+        let (columns, table): (&[(&str, &str)], &str) = match conn.backend() {
+            Backend::Sqlite => (
+                &[
+                    ("approval_threshold_usd", "REAL DEFAULT 0"),
+                    ("approval_ttl_minutes", "INTEGER DEFAULT 30"),
+                ],
+                r#"
+                CREATE TABLE IF NOT EXISTS trade_proposals (
+                    id TEXT PRIMARY KEY,
+                    user_id TEXT NOT NULL,
+                    trader_id TEXT NOT NULL,
+                    symbol TEXT NOT NULL,
+                    action TEXT NOT NULL,
+                    decision TEXT NOT NULL, -- 待执行决策的JSON
+                    notional_usd REAL NOT NULL DEFAULT 0,
+                    status TEXT NOT NULL DEFAULT 'pending', -- pending / approved / rejected / expired / executing / executed / failed
+                    created_at DATETIME NOT NULL,
+                    expires_at DATETIME NOT NULL,
+                    decided_by TEXT NOT NULL DEFAULT '', -- 审批人
+                    decided_at DATETIME DEFAULT NULL,
+                    result TEXT NOT NULL DEFAULT '', -- 执行结果或失败原因
+                    FOREIGN KEY (trader_id) REFERENCES traders(id) ON DELETE CASCADE
+                )
+                "#,
+            ),
+            Backend::Postgres => (
+                &[
+                    ("approval_threshold_usd", "DOUBLE PRECISION DEFAULT 0"),
+                    ("approval_ttl_minutes", "INTEGER DEFAULT 30"),
+                ],
+                r#"
+                CREATE TABLE IF NOT EXISTS trade_proposals (
+                    id TEXT PRIMARY KEY,
+                    user_id TEXT NOT NULL,
+                    trader_id TEXT NOT NULL,
+                    symbol TEXT NOT NULL,
+                    action TEXT NOT NULL,
+                    decision TEXT NOT NULL,
+                    notional_usd DOUBLE PRECISION NOT NULL DEFAULT 0,
+                    status TEXT NOT NULL DEFAULT 'pending',
+                    created_at TIMESTAMPTZ NOT NULL,
+                    expires_at TIMESTAMPTZ NOT NULL,
+                    decided_by TEXT NOT NULL DEFAULT '',
+                    decided_at TIMESTAMPTZ DEFAULT NULL,
+                    result TEXT NOT NULL DEFAULT ''
+                )
+                "#,
+            ),
+        };
+        for &(column, definition) in columns {
+            if !has_column(&mut conn, "traders", column).await? {
+                execute(
+                    &mut conn,
+                    &format!("ALTER TABLE traders ADD COLUMN {column} {definition}"),
+                )
+                .await?;
             }
+        }
+        execute(&mut conn, table).await
+    })
+}
 
-            return Ok(symbols);
+// 7: AI方向判断的事后评分，与是否执行无关
+fn decision_calls(mut conn: DbConn<'_>) -> MigrationFuture<'_> {
+    Box::pin(async move {
+        let queries: &[&str] = match conn.backend() {
+            Backend::Sqlite => &[
+                r#"
+                CREATE TABLE IF NOT EXISTS decision_calls (
+                    id TEXT PRIMARY KEY,
+                    user_id TEXT NOT NULL,
+                    trader_id TEXT NOT NULL,
+                    model TEXT NOT NULL, -- 提供商/模型，如 deepseek/deepseek-chat
+                    symbol TEXT NOT NULL,
+                    action TEXT NOT NULL, -- open_long / open_short / close_long / close_short
+                    entry_price REAL NOT NULL,
+                    decided_at DATETIME NOT NULL,
+                    exit_price REAL DEFAULT NULL, -- 评分时的价格，NULL 表示尚未评分
+                    return_pct REAL DEFAULT NULL, -- 按判断方向计的价格变动（%）
+                    correct BOOLEAN DEFAULT NULL,
+                    settled_at DATETIME DEFAULT NULL,
+                    FOREIGN KEY (trader_id) REFERENCES traders(id) ON DELETE CASCADE
+                )
+                "#,
+                "CREATE INDEX IF NOT EXISTS idx_decision_calls_user_decided ON decision_calls (user_id, decided_at)",
+            ],
+            Backend::Postgres => &[
+                r#"
+                CREATE TABLE IF NOT EXISTS decision_calls (
+                    id TEXT PRIMARY KEY,
+                    user_id TEXT NOT NULL,
+                    trader_id TEXT NOT NULL,
+                    model TEXT NOT NULL,
+                    symbol TEXT NOT NULL,
+                    action TEXT NOT NULL,
+                    entry_price DOUBLE PRECISION NOT NULL,
+                    decided_at TIMESTAMPTZ NOT NULL,
+                    exit_price DOUBLE PRECISION DEFAULT NULL,
+                    return_pct DOUBLE PRECISION DEFAULT NULL,
+                    correct BOOLEAN DEFAULT NULL,
+                    settled_at TIMESTAMPTZ DEFAULT NULL
+                )
+                "#,
+                "CREATE INDEX IF NOT EXISTS idx_decision_calls_user_decided ON decision_calls (user_id, decided_at)",
+            ],
+        };
+        for query in queries {
+            execute(&mut conn, query).await?;
         }
+        Ok(())
+    })
+}
 
-        let mut seen = HashSet::new();
-        for s in symbol_str.split(",") {
-            if s.trim().is_empty() {
-                continue;
+// 8: OTP 恢复码，只保存 SHA-256 哈希；used_at 非空表示已使用
+fn otp_recovery_codes(mut conn: DbConn<'_>) -> MigrationFuture<'_> {
+    Box::pin(async move {
+        let query = match conn.backend() {
+            Backend::Sqlite => {
+                r#"
+                CREATE TABLE IF NOT EXISTS otp_recovery_codes (
+                    user_id TEXT NOT NULL,
+                    code_hash TEXT NOT NULL,
+                    created_at DATETIME NOT NULL,
+                    used_at DATETIME DEFAULT NULL,
+                    PRIMARY KEY (user_id, code_hash),
+                    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+                )
+                "#
             }
-
-            let coin = normalize(s);
-            if seen.insert(coin.clone()) {
-                symbols.push(coin);
+            Backend::Postgres => {
+                r#"
+                CREATE TABLE IF NOT EXISTS otp_recovery_codes (
+                    user_id TEXT NOT NULL,
+                    code_hash TEXT NOT NULL,
+                    created_at TIMESTAMPTZ NOT NULL,
+                    used_at TIMESTAMPTZ DEFAULT NULL,
+                    PRIMARY KEY (user_id, code_hash)
+                )
+                "#
             }
-        }
-
-        Ok(symbols)
-    }
+        };
+        execute(&mut conn, query).await
+    })
+}
 
-    pub async fn close(&self) -> Result<()> {
-        Ok(self.pool.close().await)
-    }
+// 9: users 表增加角色列，内置 admin 用户为管理员
+fn user_roles(mut conn: DbConn<'_>) -> MigrationFuture<'_> {
+    Box::pin(async move {
+        if !has_column(&mut conn, "users", "role").await? {
+            execute(
+                &mut conn,
+                "ALTER TABLE users ADD COLUMN role TEXT NOT NULL DEFAULT 'user'",
+            )
+            .await?;
+        }
+        execute(
+            &mut conn,
+            "UPDATE users SET role = 'admin' WHERE id = 'admin'",
+        )
+        .await
+    })
+}
 
-    pub async fn load_beta_codes_from_file(
-        &self,
-        file_path: &str,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        let content =
-            fs::read_to_string(file_path).map_err(|e| format!("读取内测码文件失败: {}", e))?;
+// 10: 交易员可配置的额外K线周期
+fn trader_timeframes(mut conn: DbConn<'_>) -> MigrationFuture<'_> {
+    Box::pin(async move {
+        if !has_column(&mut conn, "traders", "timeframes").await? {
+            execute(
+                &mut conn,
+                "ALTER TABLE traders ADD COLUMN timeframes TEXT NOT NULL DEFAULT ''",
+            )
+            .await?;
+        }
+        Ok(())
+    })
+}
 
-        let codes: Vec<&str> = content
-            .lines()
-            .map(|line| line.trim())
-            .filter(|line| !line.is_empty() && !line.starts_with('#'))
-            .collect();
+// 11: 交易员的仓位计算方式
+fn trader_position_sizing(mut conn: DbConn<'_>) -> MigrationFuture<'_> {
+    Box::pin(async move {
+        if !has_column(&mut conn, "traders", "position_sizing").await? {
+            execute(
+                &mut conn,
+                "ALTER TABLE traders ADD COLUMN position_sizing TEXT NOT NULL DEFAULT ''",
+            )
+            .await?;
+        }
+        Ok(())
+    })
+}
 
-        let total_codes = codes.len();
+// 12: 用户自定义的系统提示词模板，交易员通过 system_prompt_template 按名称引用
+fn prompt_templates(mut conn: DbConn<'_>) -> MigrationFuture<'_> {
+    Box::pin(async move {
+        let query = match conn.backend() {
+            Backend::Sqlite => {
+                r#"
+                CREATE TABLE IF NOT EXISTS prompt_templates (
+                    user_id TEXT NOT NULL,
+                    name TEXT NOT NULL,
+                    content TEXT NOT NULL, -- 可包含 {{symbol_list}} 等变量
+                    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                    updated_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                    PRIMARY KEY (user_id, name)
+                )
+                "#
+            }
+            Backend::Postgres => {
+                r#"
+                CREATE TABLE IF NOT EXISTS prompt_templates (
+                    user_id TEXT NOT NULL,
+                    name TEXT NOT NULL,
+                    content TEXT NOT NULL,
+                    created_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP,
+                    updated_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP,
+                    PRIMARY KEY (user_id, name)
+                )
+                "#
+            }
+        };
+        execute(&mut conn, query).await
+    })
+}
 
-        let mut tx = self
-            .pool
-            .begin()
-            .await
-            .map_err(|e| format!("开始事务失败: {}", e))?;
+// 13: 提示词A/B测试：交易员所属的实验与变体，成交记录标注变体
+fn prompt_variants(mut conn: DbConn<'_>) -> MigrationFuture<'_> {
+    Box::pin(async move {
+        for (table, column) in [
+            ("traders", "experiment_id"),
+            ("traders", "variant"),
+            ("trades", "variant"),
+        ] {
+            if !has_column(&mut conn, table, column).await? {
+                let query =
+                    format!("ALTER TABLE {table} ADD COLUMN {column} TEXT NOT NULL DEFAULT ''");
+                execute(&mut conn, &query).await?;
+            }
+        }
+        Ok(())
+    })
+}
 
-        let mut inserted_count = 0;
-        for code in codes {
-            let result = sqlx::query("INSERT OR IGNORE INTO beta_codes (code) VALUES (?)")
-                .bind(code)
-                .execute(&mut *tx) // Execute inside the transaction
-                .await;
+// 14: 交易所持仓与成交账本不一致的记录，trade_id 指向补记的成交
+fn reconciliation_events(mut conn: DbConn<'_>) -> MigrationFuture<'_> {
+    Box::pin(async move {
+        let queries: &[&str] = match conn.backend() {
+            Backend::Sqlite => &[
+                r#"
+                CREATE TABLE IF NOT EXISTS reconciliation_events (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    user_id TEXT NOT NULL,
+                    trader_id TEXT NOT NULL,
+                    symbol TEXT NOT NULL,
+                    side TEXT NOT NULL, -- long / short
+                    recorded_quantity REAL NOT NULL, -- 账本中的持仓数量
+                    exchange_quantity REAL NOT NULL, -- 交易所报告的持仓数量
+                    trade_id INTEGER NOT NULL DEFAULT 0,
+                    detected_at DATETIME NOT NULL,
+                    FOREIGN KEY (trader_id) REFERENCES traders(id) ON DELETE CASCADE
+                )
+                "#,
+                "CREATE INDEX IF NOT EXISTS idx_reconciliation_events_trader ON reconciliation_events (trader_id, detected_at)",
+            ],
+            Backend::Postgres => &[
+                r#"
+                CREATE TABLE IF NOT EXISTS reconciliation_events (
+                    id BIGSERIAL PRIMARY KEY,
+                    user_id TEXT NOT NULL,
+                    trader_id TEXT NOT NULL,
+                    symbol TEXT NOT NULL,
+                    side TEXT NOT NULL,
+                    recorded_quantity DOUBLE PRECISION NOT NULL,
+                    exchange_quantity DOUBLE PRECISION NOT NULL,
+                    trade_id BIGINT NOT NULL DEFAULT 0,
+                    detected_at TIMESTAMPTZ NOT NULL
+                )
+                "#,
+                "CREATE INDEX IF NOT EXISTS idx_reconciliation_events_trader ON reconciliation_events (trader_id, detected_at)",
+            ],
+        };
+        for query in queries {
+            execute(&mut conn, query).await?;
+        }
+        Ok(())
+    })
+}
 
-            match result {
-                Ok(res) => {
-                    if res.rows_affected() > 0 {
-                        inserted_count += 1;
-                    }
-                }
-                Err(e) => {
-                    // Log error but continue processing other codes
-                    log::error!("插入内测码 {} 失败: {:?}", code, e);
-                }
+// 15: 交易员的强平距离/保证金率告警阈值与自动减仓比例
+fn trader_margin_alerts(mut conn: DbConn<'_>) -> MigrationFuture<'_> {
+    Box::pin(async move {
+        let real = match conn.backend() {
+            Backend::Sqlite => "REAL DEFAULT 0",
+            Backend::Postgres => "DOUBLE PRECISION DEFAULT 0",
+        };
+        for column in [
+            "liquidation_alert_pct",
+            "margin_ratio_alert_pct",
+            "auto_deleverage_pct",
+        ] {
+            if !has_column(&mut conn, "traders", column).await? {
+                execute(
+                    &mut conn,
+                    &format!("ALTER TABLE traders ADD COLUMN {column} {real}"),
+                )
+                .await?;
             }
         }
+        Ok(())
+    })
+}
 
-        tx.commit()
-            .await
-            .map_err(|e| format!("提交事务失败: {}", e))?;
+// 16: 每日交易报告，每个交易员每天（UTC）一条
+fn daily_reports(mut conn: DbConn<'_>) -> MigrationFuture<'_> {
+    Box::pin(async move {
+        let query = match conn.backend() {
+            Backend::Sqlite => {
+                r#"
+                CREATE TABLE IF NOT EXISTS daily_reports (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    user_id TEXT NOT NULL,
+                    trader_id TEXT NOT NULL,
+                    day TEXT NOT NULL, -- YYYY-MM-DD (UTC)
+                    trades INTEGER NOT NULL DEFAULT 0,
+                    closed_trades INTEGER NOT NULL DEFAULT 0,
+                    wins INTEGER NOT NULL DEFAULT 0,
+                    realized_pnl REAL NOT NULL DEFAULT 0, -- 已扣除开平仓手续费
+                    fees REAL NOT NULL DEFAULT 0,
+                    best_symbol TEXT NOT NULL DEFAULT '',
+                    best_pnl REAL NOT NULL DEFAULT 0,
+                    worst_symbol TEXT NOT NULL DEFAULT '',
+                    worst_pnl REAL NOT NULL DEFAULT 0,
+                    ai_calls INTEGER NOT NULL DEFAULT 0,
+                    ai_cost_usd REAL NOT NULL DEFAULT 0,
+                    generated_at DATETIME NOT NULL,
+                    UNIQUE (trader_id, day),
+                    FOREIGN KEY (trader_id) REFERENCES traders(id) ON DELETE CASCADE
+                )
+                "#
+            }
+            Backend::Postgres => {
+                r#"
+                CREATE TABLE IF NOT EXISTS daily_reports (
+                    id BIGSERIAL PRIMARY KEY,
+                    user_id TEXT NOT NULL,
+                    trader_id TEXT NOT NULL,
+                    day TEXT NOT NULL,
+                    trades BIGINT NOT NULL DEFAULT 0,
+                    closed_trades BIGINT NOT NULL DEFAULT 0,
+                    wins BIGINT NOT NULL DEFAULT 0,
+                    realized_pnl DOUBLE PRECISION NOT NULL DEFAULT 0,
+                    fees DOUBLE PRECISION NOT NULL DEFAULT 0,
+                    best_symbol TEXT NOT NULL DEFAULT '',
+                    best_pnl DOUBLE PRECISION NOT NULL DEFAULT 0,
+                    worst_symbol TEXT NOT NULL DEFAULT '',
+                    worst_pnl DOUBLE PRECISION NOT NULL DEFAULT 0,
+                    ai_calls BIGINT NOT NULL DEFAULT 0,
+                    ai_cost_usd DOUBLE PRECISION NOT NULL DEFAULT 0,
+                    generated_at TIMESTAMPTZ NOT NULL,
+                    UNIQUE (trader_id, day)
+                )
+                "#
+            }
+        };
+        execute(&mut conn, query).await
+    })
+}
 
-        log::info!(
-            "✅ 成功加载 {} 个内测码到数据库 (总计 {} 个)",
-            inserted_count,
-            total_codes
-        );
+// 17: 登录/两步验证失败次数过多导致的锁定记录，供审计
+fn auth_lockouts(mut conn: DbConn<'_>) -> MigrationFuture<'_> {
+    Box::pin(async move {
+        let queries: &[&str] = match conn.backend() {
+            Backend::Sqlite => &[
+                r#"
+                CREATE TABLE IF NOT EXISTS auth_lockouts (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    attempt TEXT NOT NULL, -- login / otp
+                    kind TEXT NOT NULL, -- account / ip
+                    subject TEXT NOT NULL, -- 邮箱、用户ID或IP
+                    ip TEXT NOT NULL DEFAULT '', -- 触发锁定的请求来源
+                    failures INTEGER NOT NULL,
+                    strike INTEGER NOT NULL, -- 连续第几次锁定
+                    locked_at DATETIME NOT NULL,
+                    locked_until DATETIME NOT NULL
+                )
+                "#,
+                "CREATE INDEX IF NOT EXISTS idx_auth_lockouts_locked_at ON auth_lockouts (locked_at)",
+            ],
+            Backend::Postgres => &[
+                r#"
+                CREATE TABLE IF NOT EXISTS auth_lockouts (
+                    id BIGSERIAL PRIMARY KEY,
+                    attempt TEXT NOT NULL,
+                    kind TEXT NOT NULL,
+                    subject TEXT NOT NULL,
+                    ip TEXT NOT NULL DEFAULT '',
+                    failures BIGINT NOT NULL,
+                    strike BIGINT NOT NULL,
+                    locked_at TIMESTAMPTZ NOT NULL,
+                    locked_until TIMESTAMPTZ NOT NULL
+                )
+                "#,
+                "CREATE INDEX IF NOT EXISTS idx_auth_lockouts_locked_at ON auth_lockouts (locked_at)",
+            ],
+        };
+        for query in queries {
+            execute(&mut conn, query).await?;
+        }
+        Ok(())
+    })
+}
 
+// 18: 配置与交易状态变更的审计记录，old/new 为去除密钥后的 JSON
+fn audit_log(mut conn: DbConn<'_>) -> MigrationFuture<'_> {
+    Box::pin(async move {
+        let queries: &[&str] = match conn.backend() {
+            Backend::Sqlite => &[
+                r#"
+                CREATE TABLE IF NOT EXISTS audit_log (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    user_id TEXT NOT NULL DEFAULT '', -- 操作人，CLI/系统变更为空
+                    source TEXT NOT NULL, -- api / cli / system
+                    action TEXT NOT NULL, -- 如 "PUT /api/exchanges/{id}"、"trader.start"
+                    target TEXT NOT NULL, -- 如 "exchanges/binance"
+                    old_value TEXT NOT NULL DEFAULT '',
+                    new_value TEXT NOT NULL DEFAULT '',
+                    created_at DATETIME NOT NULL
+                )
+                "#,
+                "CREATE INDEX IF NOT EXISTS idx_audit_log_created_at ON audit_log (created_at)",
+            ],
+            Backend::Postgres => &[
+                r#"
+                CREATE TABLE IF NOT EXISTS audit_log (
+                    id BIGSERIAL PRIMARY KEY,
+                    user_id TEXT NOT NULL DEFAULT '',
+                    source TEXT NOT NULL,
+                    action TEXT NOT NULL,
+                    target TEXT NOT NULL,
+                    old_value TEXT NOT NULL DEFAULT '',
+                    new_value TEXT NOT NULL DEFAULT '',
+                    created_at TIMESTAMPTZ NOT NULL
+                )
+                "#,
+                "CREATE INDEX IF NOT EXISTS idx_audit_log_created_at ON audit_log (created_at)",
+            ],
+        };
+        for query in queries {
+            execute(&mut conn, query).await?;
+        }
         Ok(())
-    }
+    })
+}
 
-    pub async fn validate_beta_code(&self, code: &str) -> Result<bool> {
-        let result = sqlx::query("SELECT used FROM beta_codes WHERE code = ?")
-            .bind(code)
-            .execute(&self.pool)
-            .await;
+// 19: 交易员选用的决策策略（替换或复核AI决策）
+fn trader_strategy(mut conn: DbConn<'_>) -> MigrationFuture<'_> {
+    Box::pin(async move {
+        if !has_column(&mut conn, "traders", "strategy").await? {
+            execute(
+                &mut conn,
+                "ALTER TABLE traders ADD COLUMN strategy TEXT NOT NULL DEFAULT ''",
+            )
+            .await?;
+        }
+        Ok(())
+    })
+}
 
-        match result {
-            Ok(res) => {
-                if res.rows_affected() > 0 {
-                    return Ok(true);
-                }
-            }
-            Err(_) => return Ok(false),
+// 20: 交易员允许开仓的时段（JSON数组，按用户时区解释）
+fn trader_trading_windows(mut conn: DbConn<'_>) -> MigrationFuture<'_> {
+    Box::pin(async move {
+        if !has_column(&mut conn, "traders", "trading_windows").await? {
+            execute(
+                &mut conn,
+                "ALTER TABLE traders ADD COLUMN trading_windows TEXT NOT NULL DEFAULT ''",
+            )
+            .await?;
         }
+        Ok(())
+    })
+}
 
-        Ok(false)
-    }
+// 以下查询可在连接池或读事务连接上执行
 
-    pub async fn user_beta_code(
-        &self,
-        code: &str,
-        user_email: &str,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        let result = sqlx::query(
-            r#"
-            UPDATE beta_codes SET used = 1, used_by = ?, used_at = CURRENT_TIMESTAMP 
-		    WHERE code = ? AND used = 0
-        "#,
-        )
-        .bind(user_email)
-        .bind(code)
-        .execute(&self.pool)
-        .await?;
+async fn fetch_traders(conn: &mut DbConn<'_>, user_id: &str) -> Result<Vec<TraderRecord>> {
+    let trs = on_conn!(conn, |c| {
+        sqlx::query_as::<_, TraderRecord>(
+            sql(*c, r#"
+            SELECT id, user_id, name, ai_model_id, exchange_id, initial_balance, scan_interval_minutes, is_running,
+		       COALESCE(btc_eth_leverage, 5) as btc_eth_leverage, COALESCE(altcoin_leverage, 5) as altcoin_leverage,
+		       COALESCE(trading_symbols, '') as trading_symbols,
+		       COALESCE(use_coin_pool, FALSE) as use_coin_pool, COALESCE(use_oi_top, FALSE) as use_oi_top,
+		       COALESCE(custom_prompt, '') as custom_prompt, COALESCE(override_base_prompt, FALSE) as override_base_prompt,
+		       COALESCE(system_prompt_template, 'default') as system_prompt_template,
+		       COALESCE(is_cross_margin, TRUE) as is_cross_margin, COALESCE(quote_assets, '') as quote_assets,
+		       COALESCE(stop_loss_cooldown_minutes, 30) as stop_loss_cooldown_minutes,
+		       COALESCE(watch_only, FALSE) as watch_only,
+		       COALESCE(approval_threshold_usd, 0) as approval_threshold_usd,
+		       COALESCE(approval_ttl_minutes, 30) as approval_ttl_minutes,
+		       COALESCE(timeframes, '') as timeframes,
+		       COALESCE(position_sizing, '') as position_sizing,
+		       COALESCE(experiment_id, '') as experiment_id, COALESCE(variant, '') as variant,
+		       COALESCE(liquidation_alert_pct, 0) as liquidation_alert_pct,
+		       COALESCE(margin_ratio_alert_pct, 0) as margin_ratio_alert_pct,
+		       COALESCE(auto_deleverage_pct, 0) as auto_deleverage_pct,
+		       COALESCE(custom_coins, '') as custom_coins,
+		       COALESCE(strategy, '') as strategy,
+		       COALESCE(trading_windows, '') as trading_windows,
+		       created_at, updated_at
+		    FROM traders WHERE user_id = ? ORDER BY created_at DESC
+            "#)
+        ).bind(user_id).fetch_all(&mut **c).await
+    })?;
 
-        if result.rows_affected() == 0 {
-            return Err("内测码无效或已被使用".into());
-        }
+    Ok(trs)
+}
 
-        Ok(())
-    }
+async fn fetch_transfers(
+    conn: &mut DbConn<'_>,
+    user_id: &str,
+    trader_id: &str,
+) -> Result<Vec<AccountTransfer>> {
+    let transfers = on_conn!(conn, |c| sqlx::query_as::<_, AccountTransfer>(
+            sql(*c, r#"SELECT id, user_id, trader_id, amount, asset, occurred_at, source, external_id, COALESCE(note, '') as note, created_at
+            FROM account_transfers WHERE user_id = ? AND trader_id = ? ORDER BY occurred_at, id"#),
+        )
+        .bind(user_id)
+        .bind(trader_id)
+        .fetch_all(&mut **c)
+        .await)
+        .context("Failed to fetch account transfers")?;
 
-    pub async fn get_beta_code_stats(&self) -> Result<(i64, i64)> {
-        let total: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM beta_codes")
-            .fetch_one(&self.pool)
-            .await?;
+    Ok(transfers)
+}
 
-        // 2. Get Used count
-        let used: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM beta_codes WHERE used = 1")
-            .fetch_one(&self.pool)
-            .await?;
+async fn fetch_pause_windows(
+    conn: &mut DbConn<'_>,
+    user_id: &str,
+    trader_id: &str,
+) -> Result<Vec<PauseWindow>> {
+    let windows = on_conn!(conn, |c| {
+        sqlx::query_as::<_, PauseWindow>(
+            sql(*c, "SELECT * FROM trader_pause_windows WHERE user_id = ? AND trader_id = ? ORDER BY pause_at, id"),
+        )
+        .bind(user_id)
+        .bind(trader_id)
+        .fetch_all(&mut **c)
+        .await
+    })?;
 
-        Ok((total, used))
-    }
+    Ok(windows)
 }
 
 // User 用户配置
@@ -1294,6 +4760,26 @@ pub struct User {
 
     pub otp_verified: bool,
 
+    // 界面/通知语言 ("en" / "zh")
+    #[sqlx(default)]
+    #[serde(default)]
+    pub locale: String,
+
+    // IANA 时区，决定"每日"边界与报表时间
+    #[sqlx(default)]
+    #[serde(default)]
+    pub timezone: String,
+
+    // 该用户所有交易员合计保证金占用上限 (占账户净值的百分比，<=0 表示不限制)
+    #[sqlx(default)]
+    #[serde(default)]
+    pub max_margin_usage_pct: f64,
+
+    // 角色 ("user" / "admin")，决定能否调用管理接口
+    #[sqlx(default)]
+    #[serde(default)]
+    pub role: String,
+
     #[serde(skip_serializing_if = "Option::is_none")]
     pub created_at: Option<DateTime<Utc>>,
 
@@ -1379,6 +4865,51 @@ pub struct TraderRecord {
     pub override_base_prompt: bool,     // 是否覆盖基础prompt
     pub system_prompt_template: String, // 是否为全仓模式（true=全仓，false=逐仓）
     pub is_cross_margin: bool,
+    #[sqlx(default)]
+    #[serde(default)]
+    pub quote_assets: String, // 计价资产偏好，逗号分隔（如 "USDC,USDT"），为空则使用交易所默认
+    #[sqlx(default)]
+    #[serde(default)]
+    pub stop_loss_cooldown_minutes: i32, // 止损后同币种禁止再开仓的分钟数，0 表示不限制
+    #[sqlx(default)]
+    #[serde(default)]
+    pub watch_only: bool, // 只读观察模式：从不下单，只记录AI建议与账户的实际交易
+    #[sqlx(default)]
+    #[serde(default)]
+    pub approval_threshold_usd: f64, // 名义价值超过该值的开仓需人工审批，0 表示不启用
+    #[sqlx(default)]
+    #[serde(default)]
+    pub approval_ttl_minutes: i32, // 待审批提案的有效期（分钟）
+    #[sqlx(default)]
+    #[serde(default)]
+    pub timeframes: String, // 额外K线周期，逗号分隔（如 "15m,1h,1d"），为空则只用3m与4h
+    #[sqlx(default)]
+    #[serde(default)]
+    pub position_sizing: String, // 仓位计算方式（如 "fixed:2"、"atr:1:2"、"kelly:0.5:10"），为空则采用AI给出的仓位
+    #[sqlx(default)]
+    #[serde(default)]
+    pub experiment_id: String, // 所属提示词A/B实验（对照组交易员的ID），为空表示不参与实验
+    #[sqlx(default)]
+    #[serde(default)]
+    pub variant: String, // 实验中的变体名称
+    #[sqlx(default)]
+    #[serde(default)]
+    pub liquidation_alert_pct: f64, // 标记价格距强平价小于该百分比时告警，0 表示不启用
+    #[sqlx(default)]
+    #[serde(default)]
+    pub margin_ratio_alert_pct: f64, // 保证金率（从开仓价到强平价已走过的比例）达到该百分比时告警，0 表示不启用
+    #[sqlx(default)]
+    #[serde(default)]
+    pub auto_deleverage_pct: f64, // 触发告警时自动平掉的仓位百分比，0 表示只告警
+    #[sqlx(default)]
+    #[serde(default)]
+    pub custom_coins: String, // 额外关注的币种，逗号分隔，与 trading_symbols 一起计入自定义币种列表
+    #[sqlx(default)]
+    #[serde(default)]
+    pub strategy: String, // 已注册的决策策略名称，为空则只用AI决策
+    #[sqlx(default)]
+    #[serde(default)]
+    pub trading_windows: String, // 允许开仓的时段，JSON数组（见 timezone::SessionWindow），为空则不限制
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
@@ -1394,6 +4925,316 @@ pub struct UserSignalSource {
     pub update_at: DateTime<Utc>,
 }
 
+impl User {
+    pub fn locale(&self) -> Locale {
+        Locale::parse(&self.locale)
+    }
+
+    pub fn role(&self) -> Role {
+        Role::parse(&self.role)
+    }
+
+    pub fn tz(&self) -> Tz {
+        timezone::tz_or_utc(&self.timezone)
+    }
+}
+
+// ScheduledJob 定时任务定义及最近运行状态
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct ScheduledJob {
+    pub name: String,
+    pub schedule: String,
+    pub enabled: bool,
+    pub jitter_secs: i64,
+    pub last_started_at: Option<DateTime<Utc>>,
+    pub last_finished_at: Option<DateTime<Utc>>,
+    pub last_duration_ms: Option<i64>,
+    pub last_outcome: Option<String>, // success / failed / skipped
+    pub last_error: Option<String>,
+    pub run_count: i64,
+    pub failure_count: i64,
+    pub skipped_count: i64,
+}
+
+// AccountTransfer 充值/提现记录（amount 为正表示充值，为负表示提现）
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, Default)]
+pub struct AccountTransfer {
+    #[serde(default)]
+    pub id: i64,
+    pub user_id: String,
+    pub trader_id: String,
+    #[sqlx(try_from = "f64")]
+    pub amount: Decimal,
+    pub asset: String,
+    pub occurred_at: DateTime<Utc>,
+    pub source: String, // manual / income
+    pub external_id: Option<String>,
+    pub note: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+// TraderSnapshot 交易员看板数据（同一读事务内读取）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TraderSnapshot {
+    pub trader: TraderRecord,
+    pub transfers: Vec<AccountTransfer>,
+    pub pause_windows: Vec<PauseWindow>,
+}
+
+// PauseWindow 交易员定时暂停窗口
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct PauseWindow {
+    pub id: i64,
+    pub user_id: String,
+    pub trader_id: String,
+    pub pause_at: DateTime<Utc>,
+    pub resume_at: Option<DateTime<Utc>>,
+    pub reason: String,
+    pub state: String, // pending / paused / done / cancelled
+    pub was_running: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+// PromptVersion 交易员提示词的一个历史版本
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct PromptVersion {
+    pub id: i64,
+    pub user_id: String,
+    pub trader_id: String,
+    pub version: i64, // 按交易员递增，从1开始
+    pub custom_prompt: String,
+    pub override_base_prompt: bool,
+    pub system_prompt_template: String,
+    pub diff: String, // 相对上一版本的unified diff，首个版本为空
+    pub author: String,
+    pub note: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+// PromptTemplate 用户自定义的系统提示词模板
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct PromptTemplate {
+    pub user_id: String,
+    pub name: String, // 同一用户内唯一，不能与内置模板重名
+    pub content: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+// TradeProposal 等待人工审批的交易提案
+#[derive(Debug, Clone, Default, Serialize, Deserialize, FromRow)]
+pub struct TradeProposal {
+    pub id: String,
+    pub user_id: String,
+    pub trader_id: String,
+    pub symbol: String,
+    pub action: String,
+    pub decision: String, // 待执行决策的JSON
+    pub notional_usd: f64,
+    pub status: String, // pending / approved / rejected / expired / executing / executed / failed
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub decided_by: String, // 审批人
+    pub decided_at: Option<DateTime<Utc>>,
+    pub result: String, // 执行结果或失败原因
+}
+
+// DecisionCall AI的一次方向判断及其事后评分
+#[derive(Debug, Clone, Default, Serialize, Deserialize, FromRow)]
+pub struct DecisionCall {
+    pub id: String,
+    pub user_id: String,
+    pub trader_id: String,
+    pub model: String, // 提供商/模型
+    pub symbol: String,
+    pub action: String, // open_long / open_short / close_long / close_short
+    pub entry_price: f64,
+    pub decided_at: DateTime<Utc>,
+    pub exit_price: Option<f64>, // 评分时的价格，None 表示尚未评分
+    pub return_pct: Option<f64>, // 按判断方向计的价格变动（%）
+    pub correct: Option<bool>,
+    pub settled_at: Option<DateTime<Utc>>,
+}
+
+// EvalScenario 模型评测场景（价格为 {symbol: price} 的JSON）
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct EvalScenario {
+    pub id: String,
+    pub user_id: String,
+    pub trader_id: String,
+    pub captured_at: DateTime<Utc>,
+    pub system_prompt: String,
+    pub user_prompt: String,
+    pub entry_prices: String,
+    pub exit_prices: Option<String>,
+    pub settled_at: Option<DateTime<Utc>>,
+}
+
+// Trade 一笔成交
+#[derive(Debug, Clone, Default, Serialize, Deserialize, FromRow)]
+pub struct Trade {
+    #[serde(default)]
+    pub id: i64,
+    pub user_id: String,
+    pub trader_id: String,
+    pub symbol: String,
+    pub side: String,   // buy / sell
+    pub action: String, // open_long / open_short / close_long / close_short
+    #[sqlx(try_from = "f64")]
+    pub quantity: Decimal,
+    #[sqlx(try_from = "f64")]
+    pub price: Decimal,
+    #[sqlx(try_from = "f64")]
+    pub fee: Decimal,
+    pub order_id: i64,
+    pub group_id: String,
+    #[sqlx(default, try_from = "f64")]
+    #[serde(default)]
+    pub expected_price: Decimal, // 决策时的参考价格，0 表示未记录
+    #[sqlx(default)]
+    #[serde(default)]
+    pub fill_latency_ms: i64, // 从首次下单到最后一笔成交的耗时（毫秒）
+    #[sqlx(default)]
+    #[serde(default)]
+    pub variant: String, // 成交时交易员所属的提示词变体
+    pub executed_at: DateTime<Utc>,
+}
+
+// PnlSnapshot 某一时刻的账户权益快照
+#[derive(Debug, Clone, Default, Serialize, Deserialize, FromRow)]
+pub struct PnlSnapshot {
+    #[serde(default)]
+    pub id: i64,
+    pub user_id: String,
+    pub trader_id: String,
+    pub taken_at: DateTime<Utc>,
+    #[sqlx(try_from = "f64")]
+    pub total_equity: Decimal,
+    #[sqlx(try_from = "f64")]
+    pub available_balance: Decimal,
+    #[sqlx(try_from = "f64")]
+    pub unrealized_pnl: Decimal,
+    #[sqlx(try_from = "f64")]
+    pub margin_used: Decimal,
+    pub position_count: i32,
+}
+
+// AiUsage 某交易员一天的 AI 调用用量（费用为估算值）
+#[derive(Debug, Clone, Default, Serialize, Deserialize, FromRow)]
+pub struct AiUsage {
+    pub user_id: String,
+    pub trader_id: String,
+    pub day: String, // YYYY-MM-DD (UTC)
+    pub calls: i64,
+    pub tokens: i64,
+    pub cost_usd: f64,
+}
+
+// DailyReport 交易员一天（UTC）的成交、已实现盈亏与AI费用汇总
+#[derive(Debug, Clone, Default, Serialize, Deserialize, FromRow)]
+pub struct DailyReport {
+    #[serde(default)]
+    pub id: i64,
+    pub user_id: String,
+    pub trader_id: String,
+    pub day: String, // YYYY-MM-DD (UTC)
+    pub trades: i64,
+    pub closed_trades: i64, // 平仓成交笔数
+    pub wins: i64,          // 盈利的平仓笔数
+    #[sqlx(try_from = "f64")]
+    pub realized_pnl: Decimal, // 已扣除开平仓手续费
+    #[sqlx(try_from = "f64")]
+    pub fees: Decimal,
+    pub best_symbol: String, // 盈利最多的平仓，无则为空
+    #[sqlx(try_from = "f64")]
+    pub best_pnl: Decimal,
+    pub worst_symbol: String, // 亏损最多的平仓，无则为空
+    #[sqlx(try_from = "f64")]
+    pub worst_pnl: Decimal,
+    pub ai_calls: i64,
+    pub ai_cost_usd: f64,
+    pub generated_at: DateTime<Utc>,
+}
+
+impl DailyReport {
+    // 胜率（0-1），当天没有平仓时为 0
+    pub fn win_rate(&self) -> f64 {
+        if self.closed_trades == 0 {
+            0.0
+        } else {
+            self.wins as f64 / self.closed_trades as f64
+        }
+    }
+}
+
+// ReconciliationEvent 一次交易所持仓与成交账本的差异及其补记成交
+#[derive(Debug, Clone, Default, Serialize, Deserialize, FromRow)]
+pub struct ReconciliationEvent {
+    #[serde(default)]
+    pub id: i64,
+    pub user_id: String,
+    pub trader_id: String,
+    pub symbol: String,
+    pub side: String, // long / short
+    #[sqlx(try_from = "f64")]
+    pub recorded_quantity: Decimal,
+    #[sqlx(try_from = "f64")]
+    pub exchange_quantity: Decimal,
+    pub trade_id: i64, // 补记的成交ID
+    pub detected_at: DateTime<Utc>,
+}
+
+// AuthLockout 一次因失败次数过多而锁定账户或IP的记录
+#[derive(Debug, Clone, Default, Serialize, Deserialize, FromRow)]
+pub struct AuthLockout {
+    #[serde(default)]
+    pub id: i64,
+    pub attempt: String, // login / otp
+    pub kind: String,    // account / ip
+    pub subject: String,
+    pub ip: String,
+    pub failures: i64,
+    pub strike: i64, // 连续第几次锁定，锁定时长随之翻倍
+    pub locked_at: DateTime<Utc>,
+    pub locked_until: DateTime<Utc>,
+}
+
+// AuditLogEntry 一次配置或交易状态变更
+#[derive(Debug, Clone, Default, Serialize, Deserialize, FromRow)]
+pub struct AuditLogEntry {
+    #[serde(default)]
+    pub id: i64,
+    pub user_id: String,
+    pub source: String, // api / cli / system
+    pub action: String,
+    pub target: String,
+    pub old_value: String, // 去除密钥后的 JSON，未知时为空
+    pub new_value: String,
+    pub created_at: DateTime<Utc>,
+}
+
+// ExecutionAudit 一次下单相关请求的原始载荷
+#[derive(Debug, Clone, Default, Serialize, Deserialize, FromRow)]
+pub struct ExecutionAudit {
+    pub id: i64,
+    pub user_id: String,
+    pub trader_id: String,
+    pub exchange: String,
+    pub method: String,
+    pub endpoint: String,
+    pub request: String,
+    pub status: i64, // HTTP 状态码，0 表示未收到响应
+    pub response: String,
+    pub error: String,
+    pub latency_ms: i64,
+    pub created_at: DateTime<Utc>,
+}
+
 pub fn generate_otp_secret() -> String {
     let mut secret_bytes = [0u8; 20];
 
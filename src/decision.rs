@@ -0,0 +1,522 @@
+//! Trading decisions and the context they are made from.
+//!
+//! These types are shared by every decision source (the LLM, compiled-in
+//! strategies, filters) so the execution, risk and logging stack only ever
+//! deals with one shape.
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use thiserror::Error;
+
+use crate::data;
+use crate::logger::PerformanceAnalysis;
+use crate::types::Data;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    OpenLong,
+    OpenShort,
+    CloseLong,
+    CloseShort,
+    Hold,
+    Wait,
+}
+
+impl Action {
+    pub fn is_open(self) -> bool {
+        matches!(self, Action::OpenLong | Action::OpenShort)
+    }
+
+    pub fn is_close(self) -> bool {
+        matches!(self, Action::CloseLong | Action::CloseShort)
+    }
+
+    /// The serialized name, e.g. "open_long".
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Action::OpenLong => "open_long",
+            Action::OpenShort => "open_short",
+            Action::CloseLong => "close_long",
+            Action::CloseShort => "close_short",
+            Action::Hold => "hold",
+            Action::Wait => "wait",
+        }
+    }
+
+    /// The same action on the other side: open long ↔ open short, close long ↔ close short.
+    pub fn opposite(self) -> Self {
+        match self {
+            Action::OpenLong => Action::OpenShort,
+            Action::OpenShort => Action::OpenLong,
+            Action::CloseLong => Action::CloseShort,
+            Action::CloseShort => Action::CloseLong,
+            other => other,
+        }
+    }
+}
+
+/// The second leg of a pair trade: the opposite side of `symbol`, sized at
+/// `ratio` times the first leg's notional.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PairLeg {
+    pub symbol: String,
+    #[serde(default = "default_ratio")]
+    pub ratio: f64,
+}
+
+fn default_ratio() -> f64 {
+    1.0
+}
+
+/// A single action for one symbol.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Decision {
+    pub symbol: String,
+    pub action: Action,
+    #[serde(default, skip_serializing_if = "is_zero_i32")]
+    pub leverage: i32,
+    #[serde(default, skip_serializing_if = "is_zero_f64")]
+    pub position_size_usd: f64,
+    #[serde(default, skip_serializing_if = "is_zero_f64")]
+    pub stop_loss: f64,
+    #[serde(default, skip_serializing_if = "is_zero_f64")]
+    pub take_profit: f64,
+    /// 0-100.
+    #[serde(default, skip_serializing_if = "is_zero_i32")]
+    pub confidence: i32,
+    #[serde(default, skip_serializing_if = "is_zero_f64")]
+    pub risk_usd: f64,
+    #[serde(default)]
+    pub reasoning: String,
+    /// Makes this a pair trade; both legs are opened and closed together.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pair: Option<PairLeg>,
+}
+
+impl Decision {
+    pub fn new(symbol: &str, action: Action) -> Self {
+        Self {
+            symbol: symbol.to_string(),
+            action,
+            leverage: 0,
+            position_size_usd: 0.0,
+            stop_loss: 0.0,
+            take_profit: 0.0,
+            confidence: 0,
+            risk_usd: 0.0,
+            reasoning: String::new(),
+            pair: None,
+        }
+    }
+
+    /// The second leg of a pair trade as a decision of its own. Stop-loss and
+    /// take-profit are prices of the first leg and do not carry over.
+    pub fn hedge(&self) -> Option<Decision> {
+        let pair = self.pair.as_ref()?;
+        Some(Decision {
+            leverage: self.leverage,
+            position_size_usd: self.position_size_usd * pair.ratio,
+            confidence: self.confidence,
+            reasoning: self.reasoning.clone(),
+            ..Decision::new(&pair.symbol, self.action.opposite())
+        })
+    }
+
+    /// Each leg as a plain decision: this one without its pair, then the hedge.
+    pub fn legs(&self) -> Vec<Decision> {
+        let first = Decision {
+            pair: None,
+            ..self.clone()
+        };
+        std::iter::once(first).chain(self.hedge()).collect()
+    }
+
+    /// The symbol of each leg: this one, then the pair's, if any.
+    pub fn symbols(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.symbol.as_str()).chain(self.pair.as_ref().map(|p| p.symbol.as_str()))
+    }
+
+    /// Combined notional of all legs, in USD.
+    pub fn notional_usd(&self) -> f64 {
+        self.position_size_usd * (1.0 + self.pair.as_ref().map_or(0.0, |p| p.ratio))
+    }
+}
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum DecisionError {
+    #[error("no JSON decision array in the AI response")]
+    NoJson,
+    #[error("invalid decision JSON: {0}")]
+    Json(String),
+    /// One entry of the array, by position, that cannot be executed.
+    #[error("decision #{index} ({symbol}): {reason}")]
+    Invalid {
+        index: usize,
+        symbol: String,
+        reason: String,
+    },
+}
+
+/// What a decision may ask for. Zero or empty means no limit, except for
+/// `positions`: with none held there is nothing to close.
+#[derive(Debug, Clone, Default)]
+pub struct Limits {
+    /// Symbols the trader may act on.
+    pub symbols: Vec<String>,
+    pub btc_eth_leverage: i32,
+    pub altcoin_leverage: i32,
+    /// Account equity; an open's notional may not exceed it times the leverage.
+    pub equity: f64,
+    /// Open positions as `(symbol, side)`; closes must match one.
+    pub positions: Vec<(String, String)>,
+    /// Symbols whose indicators are still warming up; nothing may be opened
+    /// on them.
+    pub warming_up: Vec<String>,
+    /// Symbols whose market data is degraded (served from a fallback source or
+    /// a snapshot); only existing positions may be managed on them.
+    pub degraded: Vec<String>,
+}
+
+impl Limits {
+    /// The limits for a cycle: candidate coins and held symbols, the trader's
+    /// leverage caps and the current account.
+    pub fn from_context(ctx: &Context) -> Self {
+        let mut symbols: Vec<String> = ctx
+            .candidate_coins
+            .iter()
+            .chain(ctx.positions.iter().map(|p| &p.symbol))
+            .map(|s| data::normalize(s))
+            .collect();
+        symbols.sort();
+        symbols.dedup();
+        Self {
+            symbols,
+            btc_eth_leverage: ctx.btc_eth_leverage,
+            altcoin_leverage: ctx.altcoin_leverage,
+            equity: ctx.account.total_equity,
+            positions: ctx
+                .positions
+                .iter()
+                .map(|p| (data::normalize(&p.symbol), p.side.clone()))
+                .collect(),
+            warming_up: ctx
+                .market_data
+                .values()
+                .filter(|d| d.warming_up)
+                .map(|d| data::normalize(&d.symbol))
+                .collect(),
+            degraded: ctx
+                .market_data
+                .values()
+                .filter(|d| d.degraded)
+                .map(|d| data::normalize(&d.symbol))
+                .collect(),
+        }
+    }
+
+    fn max_leverage(&self, symbol: &str) -> i32 {
+        match symbol {
+            "BTCUSDT" | "ETHUSDT" => self.btc_eth_leverage,
+            _ => self.altcoin_leverage,
+        }
+    }
+
+    /// Why `d` cannot be executed, if it cannot.
+    pub fn check(&self, d: &Decision) -> Result<(), String> {
+        if !self.symbols.is_empty() && !self.symbols.contains(&d.symbol) {
+            return Err("symbol is not in the trader's coin list".to_string());
+        }
+        if !(0..=100).contains(&d.confidence) {
+            return Err(format!("confidence {} is outside 0-100", d.confidence));
+        }
+        if let Some(pair) = &d.pair {
+            if pair.symbol == d.symbol {
+                return Err("pair leg is the same symbol".to_string());
+            }
+            if !self.symbols.is_empty() && !self.symbols.contains(&pair.symbol) {
+                return Err(format!(
+                    "pair symbol {} is not in the trader's coin list",
+                    pair.symbol
+                ));
+            }
+            if !pair.ratio.is_finite() || pair.ratio <= 0.0 {
+                return Err(format!("pair ratio {} is not positive", pair.ratio));
+            }
+        }
+        if d.action.is_close() {
+            for leg in d.legs() {
+                let side = if leg.action == Action::CloseLong {
+                    "long"
+                } else {
+                    "short"
+                };
+                if !self
+                    .positions
+                    .iter()
+                    .any(|(s, p)| *s == leg.symbol && p == side)
+                {
+                    return Err(format!(
+                        "no open {} position on {} to close",
+                        side, leg.symbol
+                    ));
+                }
+            }
+        }
+        if !d.action.is_open() {
+            return Ok(());
+        }
+        if let Some(leg) = d
+            .legs()
+            .iter()
+            .find(|l| self.warming_up.contains(&l.symbol))
+        {
+            return Err(format!(
+                "indicators for {} are still warming up",
+                leg.symbol
+            ));
+        }
+        if let Some(leg) = d.legs().iter().find(|l| self.degraded.contains(&l.symbol)) {
+            return Err(format!(
+                "market data for {} is degraded; only existing positions may be managed",
+                leg.symbol
+            ));
+        }
+
+        let max_leverage = match &d.pair {
+            Some(pair) => min_limit(
+                self.max_leverage(&d.symbol),
+                self.max_leverage(&pair.symbol),
+            ),
+            None => self.max_leverage(&d.symbol),
+        };
+        if d.leverage < 1 || (max_leverage > 0 && d.leverage > max_leverage) {
+            return Err(format!(
+                "leverage {} is outside 1-{}",
+                d.leverage,
+                if max_leverage > 0 {
+                    max_leverage.to_string()
+                } else {
+                    "∞".to_string()
+                }
+            ));
+        }
+        if !d.position_size_usd.is_finite() || d.position_size_usd <= 0.0 {
+            return Err(format!(
+                "position size {} USD is not positive",
+                d.position_size_usd
+            ));
+        }
+        if self.equity > 0.0 && d.notional_usd() > self.equity * d.leverage as f64 {
+            return Err(format!(
+                "position size {:.2} USD exceeds equity {:.2} x {}x leverage",
+                d.notional_usd(),
+                self.equity,
+                d.leverage
+            ));
+        }
+        for (name, price) in [("stop-loss", d.stop_loss), ("take-profit", d.take_profit)] {
+            if !price.is_finite() || price < 0.0 {
+                return Err(format!("{} {} is not a price", name, price));
+            }
+        }
+        if d.stop_loss > 0.0 && d.take_profit > 0.0 {
+            let long = d.action == Action::OpenLong;
+            if long && d.stop_loss >= d.take_profit {
+                return Err("long stop-loss is not below its take-profit".to_string());
+            }
+            if !long && d.stop_loss <= d.take_profit {
+                return Err("short stop-loss is not above its take-profit".to_string());
+            }
+        }
+        Ok(())
+    }
+}
+
+// The tighter of two limits where zero means none.
+fn min_limit(a: i32, b: i32) -> i32 {
+    match (a, b) {
+        (0, b) => b,
+        (a, 0) => a,
+        (a, b) => a.min(b),
+    }
+}
+
+/// A model response split into its reasoning and its decisions.
+#[derive(Debug, Clone, Default)]
+pub struct ParsedResponse {
+    /// Text before the decision array.
+    pub cot_trace: String,
+    /// Entries that parsed and passed the limits.
+    pub decisions: Vec<Decision>,
+    /// Entries that were dropped, and why.
+    pub rejected: Vec<DecisionError>,
+}
+
+// Finds the decision array: the last JSON array of objects in the response,
+// so arrays quoted in the reasoning or a code fence around it do not matter.
+fn find_array(response: &str) -> Result<(usize, Vec<Value>), DecisionError> {
+    let mut found = None;
+    // Brackets inside an array already found belong to it.
+    let mut inside_until = 0;
+    for (start, _) in response.match_indices('[') {
+        if start < inside_until {
+            continue;
+        }
+        let mut values = serde_json::Deserializer::from_str(&response[start..]).into_iter();
+        if let Some(Ok(Value::Array(items))) = values.next()
+            && items.iter().all(Value::is_object)
+        {
+            inside_until = start + values.byte_offset();
+            found = Some((start, items));
+        }
+    }
+    if let Some(found) = found {
+        return Ok(found);
+    }
+    match (response.find('['), response.rfind(']')) {
+        (Some(start), Some(end)) if start < end => {
+            match serde_json::from_str::<Value>(&response[start..=end]) {
+                Err(e) => Err(DecisionError::Json(e.to_string())),
+                Ok(_) => Err(DecisionError::Json(
+                    "expected an array of decision objects".to_string(),
+                )),
+            }
+        }
+        _ => Err(DecisionError::NoJson),
+    }
+}
+
+fn parse_entry(index: usize, value: Value) -> Result<Decision, DecisionError> {
+    let symbol = value["symbol"].as_str().unwrap_or_default().to_string();
+    let mut d: Decision = serde_json::from_value(value).map_err(|e| DecisionError::Invalid {
+        index,
+        symbol: symbol.clone(),
+        reason: e.to_string(),
+    })?;
+    if d.symbol.trim().is_empty() {
+        return Err(DecisionError::Invalid {
+            index,
+            symbol,
+            reason: "missing symbol".to_string(),
+        });
+    }
+    d.symbol = data::normalize(d.symbol.trim());
+    if let Some(pair) = &mut d.pair {
+        pair.symbol = data::normalize(pair.symbol.trim());
+    }
+    Ok(d)
+}
+
+/// Parses a model response and checks every decision against `limits`.
+/// Only a response without a usable decision array is an error; entries that
+/// fail to parse or break a limit are dropped and listed in `rejected`.
+pub fn parse_response(response: &str, limits: &Limits) -> Result<ParsedResponse, DecisionError> {
+    let (start, items) = find_array(response)?;
+    let mut parsed = ParsedResponse {
+        cot_trace: response[..start]
+            .trim_end()
+            .trim_end_matches("```json")
+            .trim()
+            .to_string(),
+        ..Default::default()
+    };
+    for (index, value) in items.into_iter().enumerate() {
+        let d = match parse_entry(index, value) {
+            Ok(d) => d,
+            Err(e) => {
+                parsed.rejected.push(e);
+                continue;
+            }
+        };
+        match limits.check(&d) {
+            Ok(()) => parsed.decisions.push(d),
+            Err(reason) => parsed.rejected.push(DecisionError::Invalid {
+                index,
+                symbol: d.symbol,
+                reason,
+            }),
+        }
+    }
+    Ok(parsed)
+}
+
+/// Takes the JSON decision array out of a model response that may wrap it in
+/// reasoning text or a code fence. Any malformed entry fails the whole parse.
+pub fn parse_decisions(response: &str) -> Result<Vec<Decision>, DecisionError> {
+    let (_, items) = find_array(response)?;
+    items
+        .into_iter()
+        .enumerate()
+        .map(|(index, value)| parse_entry(index, value))
+        .collect()
+}
+
+fn is_zero_i32(v: &i32) -> bool {
+    *v == 0
+}
+
+fn is_zero_f64(v: &f64) -> bool {
+    *v == 0.0
+}
+
+/// Output of one decision cycle.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FullDecision {
+    pub user_prompt: String,
+    pub cot_trace: String,
+    pub decisions: Vec<Decision>,
+    pub timestamp: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AccountInfo {
+    pub total_equity: f64,
+    pub available_balance: f64,
+    pub total_pnl: f64,
+    pub total_pnl_pct: f64,
+    pub margin_used: f64,
+    pub margin_used_pct: f64,
+    pub position_count: i32,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PositionInfo {
+    pub symbol: String,
+    /// "long" or "short".
+    pub side: String,
+    pub entry_price: f64,
+    pub mark_price: f64,
+    pub quantity: f64,
+    pub leverage: i32,
+    pub unrealized_pnl: f64,
+    pub unrealized_pnl_pct: f64,
+    pub liquidation_price: f64,
+    pub margin_used: f64,
+    /// Milliseconds since epoch when the position was opened, if known.
+    pub update_time: i64,
+}
+
+/// Everything a decision source gets to see for one cycle.
+#[derive(Debug, Default)]
+pub struct Context {
+    pub current_time: DateTime<Utc>,
+    pub runtime_minutes: i64,
+    pub call_count: i32,
+    pub account: AccountInfo,
+    pub positions: Vec<PositionInfo>,
+    pub candidate_coins: Vec<String>,
+    pub market_data: HashMap<String, Data>,
+    pub performance: Option<PerformanceAnalysis>,
+    pub btc_eth_leverage: i32,
+    pub altcoin_leverage: i32,
+}
+
+impl Context {
+    pub fn position(&self, symbol: &str) -> Option<&PositionInfo> {
+        self.positions.iter().find(|p| p.symbol == symbol)
+    }
+}
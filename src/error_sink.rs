@@ -0,0 +1,241 @@
+use std::collections::BTreeMap;
+use std::sync::{Arc, RwLock};
+
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+use reqwest::Url;
+use serde::Serialize;
+use serde_json::json;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::api_client::shared_client;
+
+// Registered sinks; every captured event is fanned out to all of them.
+static SINKS: Lazy<RwLock<Vec<Arc<dyn ErrorSink>>>> = Lazy::new(|| RwLock::new(Vec::new()));
+
+#[derive(Error, Debug)]
+pub enum SinkError {
+    #[error("Invalid Sentry DSN: {0}")]
+    InvalidDsn(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    Panic,
+    AiCall,
+    OrderRejected,
+    Other,
+}
+
+/// A failure worth surfacing to operators, with enough context to triage it.
+#[derive(Debug, Clone, Serialize)]
+pub struct ErrorEvent {
+    pub kind: ErrorKind,
+    pub message: String,
+    pub user_id: Option<String>,
+    pub trader_id: Option<String>,
+    pub symbol: Option<String>,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl ErrorEvent {
+    pub fn new(kind: ErrorKind, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+            user_id: None,
+            trader_id: None,
+            symbol: None,
+            timestamp: Utc::now(),
+        }
+    }
+
+    pub fn user(mut self, user_id: &str) -> Self {
+        self.user_id = Some(user_id.to_string());
+        self
+    }
+
+    pub fn trader(mut self, trader_id: &str) -> Self {
+        self.trader_id = Some(trader_id.to_string());
+        self
+    }
+
+    pub fn symbol(mut self, symbol: &str) -> Self {
+        self.symbol = Some(symbol.to_string());
+        self
+    }
+}
+
+/// Destination for captured errors (Sentry, a log line, a test collector...).
+pub trait ErrorSink: Send + Sync {
+    fn capture(&self, event: &ErrorEvent);
+}
+
+/// Adds a sink to the global registry.
+pub fn register_sink(sink: Arc<dyn ErrorSink>) {
+    SINKS.write().unwrap_or_else(|e| e.into_inner()).push(sink);
+}
+
+/// Sends an event to every registered sink.
+pub fn capture(event: ErrorEvent) {
+    let sinks = SINKS.read().unwrap_or_else(|e| e.into_inner());
+    for sink in sinks.iter() {
+        sink.capture(&event);
+    }
+}
+
+/// Reports a failed AI call for a trader.
+pub fn capture_ai_failure(trader_id: &str, error: &dyn std::fmt::Display) {
+    capture(ErrorEvent::new(ErrorKind::AiCall, error.to_string()).trader(trader_id));
+}
+
+/// Reports an order the exchange refused.
+pub fn capture_order_rejection(trader_id: &str, symbol: &str, error: &dyn std::fmt::Display) {
+    capture(
+        ErrorEvent::new(ErrorKind::OrderRejected, error.to_string())
+            .trader(trader_id)
+            .symbol(symbol),
+    );
+}
+
+/// Installs a panic hook that forwards panics to the registered sinks
+/// before running the default hook.
+pub fn install_panic_hook() {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let payload = info
+            .payload()
+            .downcast_ref::<&str>()
+            .map(|s| s.to_string())
+            .or_else(|| info.payload().downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "unknown panic".to_string());
+        let location = info
+            .location()
+            .map(|l| format!(" at {}:{}", l.file(), l.line()))
+            .unwrap_or_default();
+
+        capture(ErrorEvent::new(
+            ErrorKind::Panic,
+            format!("{}{}", payload, location),
+        ));
+        default_hook(info);
+    }));
+}
+
+/// Writes events to the tracing output. Registered by default so nothing is lost
+/// when no external service is configured.
+pub struct TracingSink;
+
+impl ErrorSink for TracingSink {
+    fn capture(&self, event: &ErrorEvent) {
+        tracing::error!(
+            kind = ?event.kind,
+            trader_id = event.trader_id.as_deref().unwrap_or(""),
+            symbol = event.symbol.as_deref().unwrap_or(""),
+            "❌ {}",
+            event.message
+        );
+    }
+}
+
+/// Minimal Sentry client speaking the store endpoint over the shared HTTP client.
+pub struct SentrySink {
+    store_url: Url,
+    auth_header: String,
+    environment: String,
+}
+
+impl SentrySink {
+    /// Parses a DSN of the form `https://<public_key>@<host>/<project_id>`.
+    pub fn from_dsn(dsn: &str, environment: &str) -> Result<Self, SinkError> {
+        let url = Url::parse(dsn).map_err(|e| SinkError::InvalidDsn(e.to_string()))?;
+        let key = url.username();
+        if key.is_empty() {
+            return Err(SinkError::InvalidDsn("missing public key".into()));
+        }
+        let project_id = url
+            .path_segments()
+            .and_then(|mut segments| segments.next_back())
+            .filter(|id| !id.is_empty())
+            .ok_or_else(|| SinkError::InvalidDsn("missing project id".into()))?;
+        let host = url
+            .host_str()
+            .ok_or_else(|| SinkError::InvalidDsn("missing host".into()))?;
+        let port = url.port().map(|p| format!(":{}", p)).unwrap_or_default();
+
+        let store_url = Url::parse(&format!(
+            "{}://{}{}/api/{}/store/",
+            url.scheme(),
+            host,
+            port,
+            project_id
+        ))
+        .map_err(|e| SinkError::InvalidDsn(e.to_string()))?;
+
+        Ok(Self {
+            store_url,
+            auth_header: format!(
+                "Sentry sentry_version=7, sentry_client=aitrading/{}, sentry_key={}",
+                env!("CARGO_PKG_VERSION"),
+                key
+            ),
+            environment: environment.to_string(),
+        })
+    }
+
+    fn payload(&self, event: &ErrorEvent) -> serde_json::Value {
+        let mut tags = BTreeMap::new();
+        tags.insert("kind", serde_json::to_value(event.kind).unwrap_or_default());
+        if let Some(trader_id) = &event.trader_id {
+            tags.insert("trader_id", json!(trader_id));
+        }
+        if let Some(symbol) = &event.symbol {
+            tags.insert("symbol", json!(symbol));
+        }
+
+        json!({
+            "event_id": Uuid::new_v4().simple().to_string(),
+            "timestamp": event.timestamp.to_rfc3339(),
+            "level": if event.kind == ErrorKind::Panic { "fatal" } else { "error" },
+            "platform": "other",
+            "logger": "aitrading",
+            "environment": self.environment,
+            "message": { "formatted": event.message },
+            "user": event.user_id.as_ref().map(|id| json!({ "id": id })),
+            "tags": tags,
+        })
+    }
+}
+
+impl ErrorSink for SentrySink {
+    fn capture(&self, event: &ErrorEvent) {
+        let request = shared_client()
+            .post(self.store_url.clone())
+            .header("X-Sentry-Auth", &self.auth_header)
+            .json(&self.payload(event));
+
+        let send = async move {
+            if let Err(e) = request.send().await {
+                tracing::warn!("⚠️ Failed to report error to Sentry: {}", e);
+            }
+        };
+
+        // Inside the runtime we can fire and forget; a panic outside of it (e.g. during
+        // startup) still gets reported by driving a throwaway runtime to completion.
+        match tokio::runtime::Handle::try_current() {
+            Ok(handle) => {
+                handle.spawn(send);
+            }
+            Err(_) => {
+                if let Ok(rt) = tokio::runtime::Builder::new_current_thread()
+                    .enable_all()
+                    .build()
+                {
+                    rt.block_on(send);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,272 @@
+//! Exchange credential checks.
+//!
+//! [`validate_credentials`] makes one harmless signed call per adapter (an
+//! account or balance query, never an order) and reports what it learned about
+//! the key: whether it authenticates, whether futures trading is enabled, and
+//! whether it is read-only. Users run it from the API when saving keys, so a bad
+//! key shows up there instead of in the first live cycle.
+
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json::{Value, json};
+
+use crate::api_client::{ApiClient, BinanceApiError, EndpointClass, client_for, timeout_for};
+use crate::aster::{self, AsterClient};
+use crate::database::ExchangeConfig;
+use crate::hyperliquid::{HYPERLIQUID_TESTNET_URL, HYPERLIQUID_URL};
+use crate::rate_limit::SendLimited;
+use crate::secrets;
+use crate::types::AccountBalance;
+
+// Binance codes for a key that is unknown, lacks permissions or is IP-restricted.
+const CODE_REJECTED_KEY: i64 = -2015;
+const CODE_BAD_SIGNATURE: i64 = -1022;
+const CODE_KEY_FORMAT: i64 = -2014;
+
+/// What a credential check found. Fields the exchange cannot tell are `None`.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CredentialCheck {
+    pub exchange: String,
+    /// The signed call succeeded.
+    pub auth_ok: bool,
+    pub futures_enabled: Option<bool>,
+    /// The key can read the account but not trade.
+    pub read_only: Option<bool>,
+    pub ip_restricted: Option<bool>,
+    pub withdrawals_enabled: Option<bool>,
+    /// Total wallet balance in the quote asset, when auth succeeded.
+    pub balance: Option<f64>,
+    pub error: Option<String>,
+    pub warnings: Vec<String>,
+}
+
+impl CredentialCheck {
+    fn new(exchange: &str) -> Self {
+        Self {
+            exchange: exchange.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn fail(mut self, error: impl ToString) -> Self {
+        self.auth_ok = false;
+        self.error = Some(error.to_string());
+        self
+    }
+
+    /// Whether the key is good enough to run a live trader.
+    pub fn can_trade(&self) -> bool {
+        self.auth_ok && self.futures_enabled != Some(false) && self.read_only != Some(true)
+    }
+}
+
+/// Checks an exchange account's credentials with a signed read-only call.
+pub async fn validate_credentials(exchange: &ExchangeConfig) -> CredentialCheck {
+    let exchange = &match secrets::resolve_exchange(exchange).await {
+        Ok(exchange) => exchange,
+        Err(e) => {
+            tracing::warn!("⚠️ 交易所 {} 凭证读取失败: {}", exchange.id, e);
+            return CredentialCheck::new(&exchange.id).fail(e);
+        }
+    };
+    let check = match exchange.id.as_str() {
+        "binance" => validate_binance(ApiClient::for_exchange(exchange), exchange.testnet).await,
+        "aster" => match AsterClient::for_exchange(exchange) {
+            Ok(client) => validate_aster(client).await,
+            Err(e) => CredentialCheck::new("aster").fail(e),
+        },
+        "hyperliquid" => {
+            let base_url = if exchange.testnet {
+                HYPERLIQUID_TESTNET_URL
+            } else {
+                HYPERLIQUID_URL
+            };
+            validate_hyperliquid(
+                base_url,
+                &exchange.hyperliquid_wallet_addr,
+                &exchange.api_key,
+            )
+            .await
+        }
+        other => CredentialCheck::new(other)
+            .fail(format!("credential check not supported for '{}'", other)),
+    };
+    if check.auth_ok {
+        tracing::info!("🔑 交易所 {} 凭证验证通过", exchange.id);
+    } else {
+        tracing::warn!(
+            "⚠️ 交易所 {} 凭证验证失败: {}",
+            exchange.id,
+            check.error.as_deref().unwrap_or_default()
+        );
+    }
+    check
+}
+
+fn usdt_balance(balances: &[AccountBalance]) -> Option<f64> {
+    balances
+        .iter()
+        .find(|b| b.asset == "USDT")
+        .and_then(|b| b.balance.parse().ok())
+}
+
+/// Binance: the futures balance proves the key works for futures, the key's
+/// permissions (not available on the testnet) tell the rest.
+pub async fn validate_binance(client: ApiClient, testnet: bool) -> CredentialCheck {
+    let mut check = CredentialCheck::new("binance");
+    match client.get_balances().await {
+        Ok(balances) => {
+            check.auth_ok = true;
+            check.futures_enabled = Some(true);
+            check.balance = usdt_balance(&balances);
+        }
+        Err(e) => {
+            let error = match e.downcast_ref::<BinanceApiError>().map(|e| e.code) {
+                Some(CODE_REJECTED_KEY) => {
+                    "API key rejected: it is invalid, lacks futures permission or the server IP is not whitelisted".to_string()
+                }
+                Some(CODE_BAD_SIGNATURE) => "signature rejected: the secret key is wrong".to_string(),
+                Some(CODE_KEY_FORMAT) => "API key format is invalid".to_string(),
+                _ => e.to_string(),
+            };
+            return check.fail(error);
+        }
+    }
+
+    if testnet {
+        return check;
+    }
+    match client.get_api_restrictions().await {
+        Ok(r) => {
+            check.futures_enabled = Some(r.enable_futures);
+            check.read_only = Some(!r.enable_futures && !r.enable_spot_and_margin_trading);
+            check.ip_restricted = Some(r.ip_restrict);
+            check.withdrawals_enabled = Some(r.enable_withdrawals);
+            if !r.enable_futures {
+                check
+                    .warnings
+                    .push("futures trading is not enabled for this key".to_string());
+            }
+            if r.enable_withdrawals {
+                check.warnings.push(
+                    "withdrawals are enabled for this key; a trading key should not need them"
+                        .to_string(),
+                );
+            }
+            if !r.ip_restrict {
+                check
+                    .warnings
+                    .push("the key is not restricted to trusted IPs".to_string());
+            }
+        }
+        Err(e) => check
+            .warnings
+            .push(format!("could not read the key's permissions: {}", e)),
+    }
+    check
+}
+
+/// Aster: the API wallet must match its private key (checked when the client
+/// is built) and be authorized for the main wallet, which the balance proves.
+pub async fn validate_aster(client: AsterClient) -> CredentialCheck {
+    let mut check = CredentialCheck::new("aster");
+    match client.get_balances().await {
+        Ok(balances) => {
+            check.auth_ok = true;
+            check.futures_enabled = Some(true);
+            check.read_only = Some(false);
+            check.withdrawals_enabled = Some(false);
+            check.balance = usdt_balance(&balances);
+            check
+        }
+        Err(e) => check.fail(e),
+    }
+}
+
+async fn hyperliquid_info<T: DeserializeOwned>(base_url: &str, body: Value) -> anyhow::Result<T> {
+    let resp = client_for(EndpointClass::Trading)
+        .post(format!("{}/info", base_url.trim_end_matches('/')))
+        .timeout(timeout_for(EndpointClass::Trading))
+        .json(&body)
+        .send_limited()
+        .await?;
+    let status = resp.status();
+    let text = resp.text().await?;
+    if !status.is_success() {
+        anyhow::bail!("Hyperliquid API error {}: {}", status.as_u16(), text);
+    }
+    Ok(serde_json::from_str(&text)?)
+}
+
+/// Hyperliquid signs with a wallet key, so nothing is sent to authenticate:
+/// the private key must belong to the wallet itself or to an API (agent)
+/// wallet approved for it, and the wallet's clearinghouse state gives the
+/// balance. Without a private key the account can only be watched.
+pub async fn validate_hyperliquid(
+    base_url: &str,
+    wallet: &str,
+    private_key: &str,
+) -> CredentialCheck {
+    let mut check = CredentialCheck::new("hyperliquid");
+    let wallet = wallet.trim().to_lowercase();
+    if wallet.is_empty() {
+        return check.fail("no wallet address configured");
+    }
+    check.futures_enabled = Some(true);
+    check.ip_restricted = Some(false);
+
+    if private_key.trim().is_empty() {
+        check.read_only = Some(true);
+        check.warnings.push(
+            "no private key configured: the account can be watched but not traded".to_string(),
+        );
+    } else {
+        let signer = match aster::address_for_key(private_key) {
+            Ok(address) => address,
+            Err(e) => return check.fail(e),
+        };
+        if signer == wallet {
+            check.withdrawals_enabled = Some(true);
+            check.warnings.push(
+                "the wallet's own private key is configured; an API wallet cannot withdraw and is safer"
+                    .to_string(),
+            );
+        } else {
+            let role: Value =
+                match hyperliquid_info(base_url, json!({ "type": "userRole", "user": signer }))
+                    .await
+                {
+                    Ok(role) => role,
+                    Err(e) => return check.fail(e),
+                };
+            let approved_for = role["data"]["user"].as_str().map(str::to_lowercase);
+            if role["role"] != "agent" || approved_for.as_deref() != Some(wallet.as_str()) {
+                return check.fail(format!(
+                    "private key belongs to {}, which is neither the wallet nor an API wallet approved for it",
+                    signer
+                ));
+            }
+            check.withdrawals_enabled = Some(false);
+        }
+        check.read_only = Some(false);
+    }
+
+    match hyperliquid_info::<Value>(
+        base_url,
+        json!({ "type": "clearinghouseState", "user": wallet }),
+    )
+    .await
+    {
+        Ok(state) => {
+            check.auth_ok = check.read_only == Some(false);
+            check.balance = state["marginSummary"]["accountValue"]
+                .as_str()
+                .and_then(|v| v.parse().ok());
+            if check.read_only == Some(true) {
+                check.error = Some("no private key configured".to_string());
+            }
+            check
+        }
+        Err(e) => check.fail(e),
+    }
+}
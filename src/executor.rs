@@ -0,0 +1,679 @@
+//! Turns a cycle's decisions into exchange orders.
+//!
+//! Venues implement [`TradeExecutor`]; [`Executor`] drives it: closes run
+//! before opens so freed margin is available, position sizes in USD are
+//! converted to quantities at the current price and rounded to the symbol's
+//! [`SymbolMeta`], orders below its minimum notional are refused before they
+//! are sent, partially filled orders are topped up (or closed out) for a few
+//! rounds, and transient failures go to a [`RetryQueue`] to be re-validated
+//! and retried. The legs of a pair trade are executed as a unit and share a
+//! group id. Every order ends up as an execution entry in the cycle's
+//! [`DecisionRecord`], and orders the exchange rejects outright are reported
+//! to the [`error_sink`].
+
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+use chrono::Utc;
+use serde::Serialize;
+use thiserror::Error;
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+use crate::decision::{Action, Decision, PositionInfo};
+use crate::error_sink;
+use crate::logger::{DecisionLogger, DecisionRecord};
+use crate::maintenance;
+use crate::money::{self, Decimal};
+use crate::retry_queue::{self, OrderIntent, RetryPolicy, RetryQueue};
+use crate::symbol_meta::SymbolMeta;
+
+/// Orders sent for one decision before a partial fill is accepted as is.
+const MAX_FILL_ROUNDS: u32 = 3;
+/// Remainders smaller than this are not worth another order, whatever the
+/// venue's own minimum.
+const MIN_ORDER_NOTIONAL_USD: f64 = 5.0;
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum ExecutorError {
+    #[error("Exchange error: {0}")]
+    Exchange(String),
+    #[error("No {1} position on {0}")]
+    NoPosition(String, String),
+    #[error("Invalid order: {0}")]
+    InvalidOrder(String),
+}
+
+impl ExecutorError {
+    /// Whether the order may succeed if simply sent again.
+    pub fn is_transient(&self) -> bool {
+        matches!(self, ExecutorError::Exchange(e) if retry_queue::is_transient(e))
+    }
+
+    /// Whether the exchange refused the order for good, e.g. for lack of margin.
+    pub fn is_rejection(&self) -> bool {
+        matches!(self, ExecutorError::Exchange(_)) && !self.is_transient()
+    }
+}
+
+pub type Result<T> = std::result::Result<T, ExecutorError>;
+
+/// What an exchange reports back for one order.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct OrderFill {
+    pub order_id: i64,
+    pub symbol: String,
+    pub requested_quantity: Decimal,
+    pub filled_quantity: Decimal,
+    /// Average fill price.
+    pub price: Decimal,
+    /// Milliseconds from the first order to the last fill; set by the executor.
+    pub fill_latency_ms: i64,
+}
+
+impl OrderFill {
+    pub fn is_partial(&self) -> bool {
+        self.filled_quantity < self.requested_quantity
+    }
+}
+
+/// Order placement on one venue. `side` is "long" or "short".
+pub trait TradeExecutor: Send {
+    fn open_long(
+        &mut self,
+        symbol: &str,
+        quantity: Decimal,
+        leverage: i32,
+    ) -> impl Future<Output = Result<OrderFill>> + Send;
+
+    fn open_short(
+        &mut self,
+        symbol: &str,
+        quantity: Decimal,
+        leverage: i32,
+    ) -> impl Future<Output = Result<OrderFill>> + Send;
+
+    /// Closes `quantity` of the position on `side`; 0 closes all of it.
+    fn close(
+        &mut self,
+        symbol: &str,
+        side: &str,
+        quantity: Decimal,
+    ) -> impl Future<Output = Result<OrderFill>> + Send;
+
+    fn set_leverage(
+        &mut self,
+        symbol: &str,
+        leverage: i32,
+    ) -> impl Future<Output = Result<()>> + Send;
+
+    fn get_positions(&mut self) -> impl Future<Output = Result<Vec<PositionInfo>>> + Send;
+
+    /// Current mark price, used to size orders.
+    fn get_price(&mut self, symbol: &str) -> impl Future<Output = Result<f64>> + Send;
+
+    /// The symbol's order rules, used to round quantities and to keep orders
+    /// above its minimum notional. `None` when the venue has no such rules.
+    fn symbol_meta(
+        &mut self,
+        _symbol: &str,
+    ) -> impl Future<Output = Result<Option<SymbolMeta>>> + Send {
+        async { Ok(None) }
+    }
+
+    /// Places stop-loss / take-profit orders for a position; 0 means none.
+    /// Venues that attach protection some other way can keep the default.
+    fn set_protective_orders(
+        &mut self,
+        _symbol: &str,
+        _side: &str,
+        _quantity: Decimal,
+        _stop_loss: f64,
+        _take_profit: f64,
+    ) -> impl Future<Output = Result<()>> + Send {
+        async { Ok(()) }
+    }
+
+    /// Cancels the stop-loss / take-profit orders left on `symbol`, so that a
+    /// stale stop cannot fire against a later position.
+    fn cancel_protective_orders(
+        &mut self,
+        _symbol: &str,
+    ) -> impl Future<Output = Result<()>> + Send {
+        async { Ok(()) }
+    }
+}
+
+/// The outcome of one decision.
+#[derive(Debug, Clone)]
+pub struct Execution {
+    pub decision: Decision,
+    /// `Ok(None)` for hold / wait.
+    pub result: Result<Option<OrderFill>>,
+    /// Set when the failure was queued for a retry.
+    pub queued_for_retry: bool,
+    /// Shared by the legs of a pair trade.
+    pub group_id: Option<String>,
+}
+
+/// A cycle's decisions and the record their executions are written to.
+#[derive(Debug)]
+pub struct Batch {
+    pub decisions: Vec<Decision>,
+    pub record: DecisionRecord,
+}
+
+pub struct Executor<E> {
+    exchange: E,
+    retries: RetryQueue<OrderIntent>,
+    // 上报被拒订单时标注的交易员
+    trader_id: String,
+}
+
+impl<E: TradeExecutor> Executor<E> {
+    pub fn new(exchange: E, policy: RetryPolicy) -> Self {
+        Self {
+            exchange,
+            retries: RetryQueue::new(policy),
+            trader_id: String::new(),
+        }
+    }
+
+    /// Attributes the orders the exchange rejects to `trader_id` when they
+    /// are reported to the [`error_sink`].
+    pub fn for_trader(mut self, trader_id: &str) -> Self {
+        self.trader_id = trader_id.to_string();
+        self
+    }
+
+    pub fn exchange(&self) -> &E {
+        &self.exchange
+    }
+
+    pub fn exchange_mut(&mut self) -> &mut E {
+        &mut self.exchange
+    }
+
+    /// Orders waiting for a retry.
+    pub fn pending_retries(&self) -> usize {
+        self.retries.len()
+    }
+
+    /// Executes decisions, closes first, and records each order in `record`.
+    pub async fn execute(
+        &mut self,
+        decisions: &[Decision],
+        record: &mut DecisionRecord,
+    ) -> Vec<Execution> {
+        let mut ordered: Vec<&Decision> = decisions.iter().collect();
+        ordered.sort_by_key(|d| !d.action.is_close());
+
+        let mut executions = Vec::with_capacity(ordered.len());
+        for d in ordered {
+            if d.pair.is_some() {
+                executions.extend(self.execute_pair(d, record).await);
+            } else {
+                executions.push(self.execute_leg(d, true, None, record).await);
+            }
+        }
+        executions
+    }
+
+    // Executes one order decision and records it; transient failures are
+    // queued for a retry when `retry` is set.
+    async fn execute_leg(
+        &mut self,
+        d: &Decision,
+        retry: bool,
+        group_id: Option<&str>,
+        record: &mut DecisionRecord,
+    ) -> Execution {
+        let reference_price = self.exchange.get_price(&d.symbol).await.unwrap_or(0.0);
+        let result = self.execute_one(d).await;
+        self.report_rejection(d, &result);
+        let mut queued_for_retry = false;
+        if retry
+            && let Err(e) = &result
+            && e.is_transient()
+        {
+            let intent = OrderIntent {
+                decision: d.clone(),
+                reference_price,
+            };
+            if let Some(evicted) = self.retries.push(&intent.key(), intent, &e.to_string()) {
+                tracing::warn!("🗑️ 重试队列已满，丢弃 {}", evicted.key);
+            }
+            queued_for_retry = true;
+        }
+        Self::record(record, d, &result, queued_for_retry);
+        if let Some(group_id) = group_id {
+            record.group_last_execution(group_id);
+        }
+        Execution {
+            decision: d.clone(),
+            result,
+            queued_for_retry,
+            group_id: group_id.map(str::to_string),
+        }
+    }
+
+    /// Executes both legs of a pair trade. Opens are all or nothing: the
+    /// second leg is only sent once the first filled, and if it fails the
+    /// first is closed again. Closes are sent for both legs regardless, and a
+    /// close that failed transiently is queued for a retry on its own.
+    async fn execute_pair(&mut self, d: &Decision, record: &mut DecisionRecord) -> Vec<Execution> {
+        let group_id = Uuid::new_v4().to_string();
+        let mut legs = d.legs().into_iter();
+        let first = legs.next().unwrap_or_else(|| d.clone());
+        let Some(second) = legs.next() else {
+            return vec![self.execute_leg(&first, true, None, record).await];
+        };
+        let is_close = d.action.is_close();
+
+        let mut executions = vec![
+            self.execute_leg(&first, is_close, Some(&group_id), record)
+                .await,
+        ];
+        if !is_close && executions[0].result.is_err() {
+            let reason = format!("not sent, pair leg {} failed", first.symbol);
+            record.log(format!(
+                "✗ {} {:?}: {}",
+                second.symbol, second.action, reason
+            ));
+            executions.push(Execution {
+                decision: second,
+                result: Err(ExecutorError::InvalidOrder(reason)),
+                queued_for_retry: false,
+                group_id: Some(group_id),
+            });
+            return executions;
+        }
+
+        let hedge = self
+            .execute_leg(&second, is_close, Some(&group_id), record)
+            .await;
+        let unwind = !is_close && hedge.result.is_err();
+        executions.push(hedge);
+        if unwind {
+            tracing::warn!(
+                "↩️ 组合交易 {}/{} 第二腿失败，平掉已成交的 {}",
+                first.symbol,
+                second.symbol,
+                first.symbol
+            );
+            let filled = match &executions[0].result {
+                Ok(Some(fill)) => fill.filled_quantity,
+                _ => Decimal::ZERO,
+            };
+            let undo = self.unwind(&first, filled, &group_id, record).await;
+            if let Err(e) = &undo.result {
+                tracing::error!("❌ 组合交易回滚失败，{} 仓位未对冲: {}", first.symbol, e);
+            }
+            executions.push(undo);
+        }
+        executions
+    }
+
+    // Closes just the `filled` quantity a pair's first leg opened, leaving any
+    // position the account already held in that symbol alone. Not queued for
+    // a retry: replaying the close decision would close the whole position.
+    async fn unwind(
+        &mut self,
+        leg: &Decision,
+        filled: Decimal,
+        group_id: &str,
+        record: &mut DecisionRecord,
+    ) -> Execution {
+        let action = if leg.action == Action::OpenLong {
+            Action::CloseLong
+        } else {
+            Action::CloseShort
+        };
+        let close = Decision {
+            reasoning: "unwinding pair trade whose other leg failed".to_string(),
+            ..Decision::new(&leg.symbol, action)
+        };
+        let result = if filled > Decimal::ZERO {
+            let side = side(action).unwrap_or("long");
+            self.exchange
+                .close(&leg.symbol, side, filled)
+                .await
+                .map(Some)
+        } else {
+            Ok(None)
+        };
+        self.report_rejection(&close, &result);
+        Self::record(record, &close, &result, false);
+        record.group_last_execution(group_id);
+        Execution {
+            decision: close,
+            result,
+            queued_for_retry: false,
+            group_id: Some(group_id.to_string()),
+        }
+    }
+
+    // 交易所明确拒绝（非临时故障）的订单上报错误收集
+    fn report_rejection(&self, d: &Decision, result: &Result<Option<OrderFill>>) {
+        if let Err(e) = result
+            && e.is_rejection()
+        {
+            error_sink::capture_order_rejection(&self.trader_id, &d.symbol, e);
+        }
+    }
+
+    /// Retries queued orders that are due, after checking they still make sense.
+    pub async fn retry_due(&mut self, mut record: Option<&mut DecisionRecord>) -> Vec<Execution> {
+        let due = self.retries.take_due(Utc::now());
+        if due.is_empty() {
+            return Vec::new();
+        }
+        let positions = self.exchange.get_positions().await.unwrap_or_default();
+        let max_drift = self.retries.policy().max_price_drift_pct;
+
+        let mut executions = Vec::new();
+        for pending in due {
+            let d = pending.item.decision.clone();
+            let price = match self.exchange.get_price(&d.symbol).await {
+                Ok(price) => price,
+                Err(e) => {
+                    self.retries.requeue(pending, &e.to_string());
+                    continue;
+                }
+            };
+            let position = positions
+                .iter()
+                .find(|p| p.symbol == d.symbol && Some(p.side.as_str()) == side(d.action));
+            if let Err(stale) = retry_queue::revalidate(&pending.item, price, position, max_drift) {
+                tracing::info!("⏭️ {} 不再适合重试，放弃: {:?}", pending.key, stale);
+                continue;
+            }
+
+            tracing::info!("🔁 重试 {} (第 {} 次)", pending.key, pending.attempts + 1);
+            let result = self.execute_one(&d).await;
+            self.report_rejection(&d, &result);
+            let mut queued_for_retry = false;
+            if let Err(e) = &result
+                && e.is_transient()
+            {
+                queued_for_retry = self.retries.requeue(pending, &e.to_string());
+            }
+            if let Some(record) = record.as_deref_mut() {
+                Self::record(record, &d, &result, queued_for_retry);
+            }
+            executions.push(Execution {
+                decision: d,
+                result,
+                queued_for_retry,
+                group_id: None,
+            });
+        }
+        executions
+    }
+
+    /// Executes batches as they arrive and writes their records to `logger`;
+    /// in between, retries queued orders. Ends when the sender is dropped.
+    pub async fn run(mut self, mut batches: mpsc::Receiver<Batch>, mut logger: DecisionLogger) {
+        let period = self.retries.policy().base_delay.max(Duration::from_secs(1));
+        let mut tick = tokio::time::interval(period);
+        tick.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+        loop {
+            tokio::select! {
+                batch = batches.recv() => {
+                    let Some(Batch { decisions, mut record }) = batch else {
+                        break;
+                    };
+                    self.execute(&decisions, &mut record).await;
+                    if let Err(e) = logger.log_decision(&mut record) {
+                        tracing::warn!("⚠️ 保存决策记录失败: {:#}", e);
+                    }
+                }
+                _ = tick.tick(), if !self.retries.is_empty() && !maintenance::is_active() => {
+                    self.retry_due(None).await;
+                }
+            }
+        }
+        tracing::info!("🛑 执行器已停止，丢弃 {} 个待重试订单", self.retries.len());
+    }
+
+    async fn execute_one(&mut self, d: &Decision) -> Result<Option<OrderFill>> {
+        match d.action {
+            Action::OpenLong | Action::OpenShort => self.open(d).await.map(Some),
+            Action::CloseLong | Action::CloseShort => self.close(d).await.map(Some),
+            Action::Hold | Action::Wait => Ok(None),
+        }
+    }
+
+    async fn open(&mut self, d: &Decision) -> Result<OrderFill> {
+        let side = side(d.action).unwrap_or("long");
+        if d.position_size_usd < MIN_ORDER_NOTIONAL_USD {
+            return Err(ExecutorError::InvalidOrder(format!(
+                "{} position size {:.2} USD is below the minimum",
+                d.symbol, d.position_size_usd
+            )));
+        }
+        let price = self.exchange.get_price(&d.symbol).await?;
+        if price <= 0.0 {
+            return Err(ExecutorError::InvalidOrder(format!(
+                "no valid price for {}",
+                d.symbol
+            )));
+        }
+        // Orders the venue would reject for their size are refused up front.
+        let meta = self.exchange.symbol_meta(&d.symbol).await?;
+        let min_notional = meta.as_ref().map_or(MIN_ORDER_NOTIONAL_USD, |m| {
+            money::to_f64(m.min_notional).max(MIN_ORDER_NOTIONAL_USD)
+        });
+        let round_quantity = |quantity: Decimal| {
+            meta.as_ref()
+                .map_or(quantity, |m| m.round_quantity(quantity))
+        };
+        let requested = round_quantity(money::from_f64(d.position_size_usd / price));
+        if money::to_f64(requested) * price < min_notional {
+            return Err(ExecutorError::InvalidOrder(format!(
+                "{} order of {} ({:.2} USD) is below the minimum notional of {} USD",
+                d.symbol,
+                requested,
+                money::to_f64(requested) * price,
+                min_notional
+            )));
+        }
+        let leverage = d.leverage.max(1);
+        self.exchange.set_leverage(&d.symbol, leverage).await?;
+
+        let mut fill = OrderFill {
+            order_id: 0,
+            symbol: d.symbol.clone(),
+            requested_quantity: requested,
+            filled_quantity: Decimal::ZERO,
+            price: Decimal::ZERO,
+            fill_latency_ms: 0,
+        };
+        let started = Instant::now();
+        for round in 0..MAX_FILL_ROUNDS {
+            let remaining = round_quantity(requested - fill.filled_quantity);
+            if money::to_f64(remaining) * price < min_notional {
+                break;
+            }
+            let part = match d.action {
+                Action::OpenShort => {
+                    self.exchange
+                        .open_short(&d.symbol, remaining, leverage)
+                        .await
+                }
+                _ => {
+                    self.exchange
+                        .open_long(&d.symbol, remaining, leverage)
+                        .await
+                }
+            };
+            // A failed top-up keeps what was already filled.
+            let part = match part {
+                Ok(part) => part,
+                Err(e) if round == 0 => return Err(e),
+                Err(e) => {
+                    tracing::warn!("⚠️ {} 补单失败，保留已成交部分: {}", d.symbol, e);
+                    break;
+                }
+            };
+            merge_fill(&mut fill, &part, started);
+            if !part.is_partial() || part.filled_quantity <= Decimal::ZERO {
+                break;
+            }
+            tracing::info!(
+                "🧩 {} 部分成交 {:.6}/{:.6}，补单剩余部分",
+                d.symbol,
+                fill.filled_quantity,
+                requested
+            );
+        }
+
+        if fill.filled_quantity > Decimal::ZERO && (d.stop_loss > 0.0 || d.take_profit > 0.0) {
+            // 先撤掉旧的止损止盈，再按本次决策重新挂单
+            if let Err(e) = self.exchange.cancel_protective_orders(&d.symbol).await {
+                tracing::warn!("⚠️ {} 撤销旧止损止盈失败: {}", d.symbol, e);
+            }
+            if let Err(e) = self
+                .exchange
+                .set_protective_orders(
+                    &d.symbol,
+                    side,
+                    fill.filled_quantity,
+                    d.stop_loss,
+                    d.take_profit,
+                )
+                .await
+            {
+                tracing::warn!("⚠️ {} 设置止损止盈失败: {}", d.symbol, e);
+            }
+        }
+        Ok(fill)
+    }
+
+    async fn close(&mut self, d: &Decision) -> Result<OrderFill> {
+        let side = side(d.action).unwrap_or("long");
+        let held = self.position_quantity(&d.symbol, side).await?;
+        if held <= 0.0 {
+            return Err(ExecutorError::NoPosition(
+                d.symbol.clone(),
+                side.to_string(),
+            ));
+        }
+
+        let mut fill = OrderFill {
+            order_id: 0,
+            symbol: d.symbol.clone(),
+            requested_quantity: money::from_f64(held),
+            filled_quantity: Decimal::ZERO,
+            price: Decimal::ZERO,
+            fill_latency_ms: 0,
+        };
+        let started = Instant::now();
+        for round in 0..MAX_FILL_ROUNDS {
+            let part = match self.exchange.close(&d.symbol, side, Decimal::ZERO).await {
+                Ok(part) => part,
+                Err(e) if round == 0 => return Err(e),
+                Err(e) => {
+                    tracing::warn!("⚠️ {} 剩余仓位平仓失败: {}", d.symbol, e);
+                    break;
+                }
+            };
+            merge_fill(&mut fill, &part, started);
+            if !part.is_partial() {
+                break;
+            }
+            // Close whatever is still open, however small: a dust position
+            // left behind would block the next open.
+            if self.position_quantity(&d.symbol, side).await? <= 0.0 {
+                break;
+            }
+            tracing::info!("🧩 {} 平仓部分成交，继续平剩余仓位", d.symbol);
+        }
+        // 仓位已平完时撤掉它的止损止盈，避免旧挂单作用于之后的新仓位
+        if self.position_quantity(&d.symbol, side).await? <= 0.0
+            && let Err(e) = self.exchange.cancel_protective_orders(&d.symbol).await
+        {
+            tracing::warn!("⚠️ {} 撤销止损止盈失败: {}", d.symbol, e);
+        }
+        Ok(fill)
+    }
+
+    async fn position_quantity(&mut self, symbol: &str, side: &str) -> Result<f64> {
+        Ok(self
+            .exchange
+            .get_positions()
+            .await?
+            .iter()
+            .find(|p| p.symbol == symbol && p.side == side)
+            .map_or(0.0, |p| p.quantity.abs()))
+    }
+
+    fn record(
+        record: &mut DecisionRecord,
+        d: &Decision,
+        result: &Result<Option<OrderFill>>,
+        queued_for_retry: bool,
+    ) {
+        match result {
+            Ok(Some(fill)) => {
+                record.record_execution(
+                    d.action,
+                    &d.symbol,
+                    fill.filled_quantity,
+                    d.leverage,
+                    fill.price,
+                    fill.order_id,
+                    None,
+                );
+                let partial = if fill.is_partial() { " (partial)" } else { "" };
+                record.log(format!(
+                    "✓ {} {:?} {:.6} @ {:.4}{}",
+                    d.symbol, d.action, fill.filled_quantity, fill.price, partial
+                ));
+            }
+            Ok(None) => {}
+            Err(e) => {
+                let error = e.to_string();
+                record.record_execution(
+                    d.action,
+                    &d.symbol,
+                    Decimal::ZERO,
+                    d.leverage,
+                    Decimal::ZERO,
+                    0,
+                    Some(&error),
+                );
+                let retry = if queued_for_retry {
+                    ", queued for retry"
+                } else {
+                    ""
+                };
+                record.log(format!("✗ {} {:?}: {}{}", d.symbol, d.action, error, retry));
+            }
+        }
+    }
+}
+
+fn side(action: Action) -> Option<&'static str> {
+    match action {
+        Action::OpenLong | Action::CloseLong => Some("long"),
+        Action::OpenShort | Action::CloseShort => Some("short"),
+        Action::Hold | Action::Wait => None,
+    }
+}
+
+// Adds one order's fill to the running total of a decision; `started` is when
+// its first order went out.
+fn merge_fill(total: &mut OrderFill, part: &OrderFill, started: Instant) {
+    let filled = total.filled_quantity + part.filled_quantity;
+    if filled > Decimal::ZERO {
+        total.price =
+            (total.price * total.filled_quantity + part.price * part.filled_quantity) / filled;
+    }
+    total.filled_quantity = filled;
+    total.order_id = part.order_id;
+    if part.filled_quantity > Decimal::ZERO {
+        total.fill_latency_ms = started.elapsed().as_millis() as i64;
+    }
+}
@@ -0,0 +1,132 @@
+//! A/B tests of trader prompts.
+//!
+//! A variant is a stopped clone of a trader with identical settings and a
+//! different prompt. The first clone turns the original into the experiment's
+//! `control`, and every trader in the experiment carries the control's id as
+//! its `experiment_id`. Decision records and trades are tagged with the
+//! variant that produced them, so [`compare`] only counts what each trader did
+//! while it was part of the experiment.
+
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::database::{Database, TraderRecord};
+use crate::logger::{self, PerformanceAnalysis, RecordKey};
+use crate::money::Decimal;
+
+/// Variant name given to the trader an experiment was started from.
+pub const CONTROL_VARIANT: &str = "control";
+
+const MAX_VARIANT_LEN: usize = 32;
+
+/// Whether `variant` can name a variant: 1-32 letters, digits, '-' or '_'.
+pub fn is_valid_variant(variant: &str) -> bool {
+    !variant.is_empty()
+        && variant.len() <= MAX_VARIANT_LEN
+        && variant
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Puts `trader` in an experiment of its own as the control. Returns false if
+/// it already takes part in one.
+pub fn enroll(trader: &mut TraderRecord) -> bool {
+    if !trader.experiment_id.is_empty() {
+        return false;
+    }
+    trader.experiment_id = trader.id.clone();
+    trader.variant = CONTROL_VARIANT.to_string();
+    true
+}
+
+/// A stopped copy of `source` in the same experiment, named after `variant`.
+/// The prompt is still the source's; the caller sets the variant's prompt.
+pub fn clone_variant(source: &TraderRecord, variant: &str) -> TraderRecord {
+    let experiment_id = if source.experiment_id.is_empty() {
+        source.id.clone()
+    } else {
+        source.experiment_id.clone()
+    };
+    TraderRecord {
+        id: Uuid::new_v4().to_string(),
+        name: format!("{} ({})", source.name, variant),
+        is_running: false,
+        experiment_id,
+        variant: variant.to_string(),
+        ..source.clone()
+    }
+}
+
+/// The traders of `user_id` taking part in `experiment_id`.
+pub async fn members(
+    db: &Database,
+    user_id: &str,
+    experiment_id: &str,
+) -> anyhow::Result<Vec<TraderRecord>> {
+    Ok(db
+        .get_traders(user_id)
+        .await?
+        .into_iter()
+        .filter(|t| !experiment_id.is_empty() && t.experiment_id == experiment_id)
+        .collect())
+}
+
+/// One variant's results in a comparison.
+#[derive(Debug, Clone, Serialize)]
+pub struct VariantReport {
+    pub trader_id: String,
+    pub trader_name: String,
+    pub variant: String,
+    pub system_prompt_template: String,
+    /// Decision cycles run as this variant.
+    pub cycles: usize,
+    /// Trades opened and closed as this variant.
+    pub total_trades: i32,
+    /// Percentage of closed trades that made money.
+    pub win_rate: f64,
+    pub total_pnl: Decimal,
+    /// Per-cycle, not annualized; see [`PerformanceAnalysis`].
+    pub sharpe_ratio: f64,
+}
+
+impl VariantReport {
+    fn new(trader: &TraderRecord, cycles: usize, analysis: &PerformanceAnalysis) -> Self {
+        Self {
+            trader_id: trader.id.clone(),
+            trader_name: trader.name.clone(),
+            variant: trader.variant.clone(),
+            system_prompt_template: trader.system_prompt_template.clone(),
+            cycles,
+            total_trades: analysis.total_trades(),
+            win_rate: analysis.win_rate(),
+            total_pnl: analysis.total_pnl(),
+            sharpe_ratio: analysis.sharpe_ratio(),
+        }
+    }
+}
+
+/// Runs the performance analyzer over each variant's stored decision
+/// records. The control comes first, the other variants by name.
+pub async fn compare(
+    db: &Database,
+    user_id: &str,
+    experiment_id: &str,
+    key: Option<&RecordKey>,
+) -> anyhow::Result<Vec<VariantReport>> {
+    let mut reports = Vec::new();
+    for trader in members(db, user_id, experiment_id).await? {
+        let records: Vec<_> = logger::load_records(db, user_id, &trader.id, i64::MAX as usize, key)
+            .await?
+            .into_iter()
+            .filter(|r| r.variant() == trader.variant)
+            .collect();
+        let analysis = PerformanceAnalysis::from_records(&[], &records);
+        reports.push(VariantReport::new(&trader, records.len(), &analysis));
+    }
+    reports.sort_by(|a, b| {
+        (a.variant != CONTROL_VARIANT)
+            .cmp(&(b.variant != CONTROL_VARIANT))
+            .then_with(|| a.variant.cmp(&b.variant))
+    });
+    Ok(reports)
+}
@@ -0,0 +1,698 @@
+//! Account takeout.
+//!
+//! Collects everything stored for one user (profile, model and exchange
+//! settings, traders with their prompt history, decisions, fills, equity
+//! snapshots and transfers) so it can be moved elsewhere or analyzed in
+//! external tools. API keys and private keys are blanked; everything else is
+//! exported as stored.
+//!
+//! The API returns a [`Takeout`] as a single JSON document. On disk
+//! ([`write_dir`]) the same data is split into:
+//!
+//! ```text
+//! manifest.json                  format, version, export time, file list
+//! account.json                   {"user", "ai_models", "exchanges"}
+//! traders/<id>/trader.json       {"trader", "performance", "prompt_versions", "pause_windows"}
+//! traders/<id>/decisions.json    decision records, oldest first
+//! traders/<id>/trades.csv        executed_at,symbol,side,action,quantity,price,fee,order_id,group_id,expected_price,fill_latency_ms
+//! traders/<id>/equity.csv        taken_at,total_equity,available_balance,unrealized_pnl,margin_used,position_count
+//! traders/<id>/transfers.csv     occurred_at,amount,asset,source,external_id,note
+//! ```
+//!
+//! CSV files have a header row, use RFC 3339 UTC timestamps and quote fields
+//! as in RFC 4180.
+//!
+//! A single history of one trader (decision cycles, fills or equity
+//! snapshots) can also be exported on its own through [`history`], limited to
+//! a date range, as CSV or, with the `parquet` feature, as Parquet for pandas
+//! and similar tools.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::database::{
+    AIModelConfig, AccountTransfer, Database, ExchangeConfig, PauseWindow, PnlSnapshot,
+    PromptVersion, Trade, TraderRecord, User,
+};
+use crate::logger::{self, DecisionRecord, RecordKey};
+use crate::money::Decimal;
+use crate::performance::{self, TraderStats};
+
+/// Value of the `format` field of every takeout.
+pub const EXPORT_FORMAT: &str = "aitrading.export";
+/// Current takeout version.
+pub const EXPORT_VERSION: u32 = 1;
+
+#[derive(Error, Debug)]
+pub enum ExportError {
+    #[error("User {0} not found")]
+    UserNotFound(String),
+    #[error("Trader {0} not found")]
+    TraderNotFound(String),
+    #[error("Export destination {0} already exists")]
+    DestinationExists(PathBuf),
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Database error: {0}")]
+    Database(#[from] anyhow::Error),
+    #[error("{0} export is not enabled in this build")]
+    FormatDisabled(&'static str),
+    #[error("Parquet error: {0}")]
+    Parquet(String),
+}
+
+pub type Result<T> = std::result::Result<T, ExportError>;
+
+/// Everything stored for one user.
+#[derive(Debug, Serialize)]
+pub struct Takeout {
+    pub format: String,
+    pub version: u32,
+    pub exported_at: DateTime<Utc>,
+    pub user: User,
+    pub ai_models: Vec<AIModelConfig>,
+    pub exchanges: Vec<ExchangeConfig>,
+    pub traders: Vec<TraderTakeout>,
+}
+
+/// One trader's configuration and history.
+#[derive(Debug, Serialize)]
+pub struct TraderTakeout {
+    pub trader: TraderRecord,
+    /// Over the trader's whole history.
+    pub performance: TraderStats,
+    pub prompt_versions: Vec<PromptVersion>,
+    pub pause_windows: Vec<PauseWindow>,
+    pub decisions: Vec<DecisionRecord>,
+    pub trades: Vec<Trade>,
+    pub equity: Vec<PnlSnapshot>,
+    pub transfers: Vec<AccountTransfer>,
+}
+
+/// Reads a user's data. Encrypted decision records are decrypted with `key`;
+/// records that cannot be read are left out.
+pub async fn collect(db: &Database, user_id: &str, key: Option<&RecordKey>) -> Result<Takeout> {
+    let user = db
+        .get_user_by_id(user_id)
+        .await?
+        .ok_or_else(|| ExportError::UserNotFound(user_id.to_string()))?;
+    let ai_models = db
+        .get_aimodels(user_id)
+        .await?
+        .into_iter()
+        .map(|m| AIModelConfig {
+            api_key: String::new(),
+            ..m
+        })
+        .collect();
+    let exchanges = db
+        .get_exchanges(user_id)
+        .await?
+        .into_iter()
+        .map(|e| ExchangeConfig {
+            api_key: String::new(),
+            secret_key: String::new(),
+            aster_private_key: String::new(),
+            ..e
+        })
+        .collect();
+
+    let mut traders = Vec::new();
+    for trader in db.get_traders(user_id).await? {
+        let id = trader.id.clone();
+        let trades = db.get_trades(user_id, &id, None).await?;
+        let equity = db.get_pnl_snapshots(user_id, &id, None).await?;
+        let transfers = db.get_transfers(user_id, &id).await?;
+        traders.push(TraderTakeout {
+            performance: performance::trader_stats(&equity, &transfers, &trades),
+            prompt_versions: db.get_prompt_versions(user_id, &id).await?,
+            pause_windows: db.get_pause_windows(user_id, &id).await?,
+            decisions: logger::load_records(db, user_id, &id, i64::MAX as usize, key).await?,
+            trader,
+            trades,
+            equity,
+            transfers,
+        });
+    }
+
+    Ok(Takeout {
+        format: EXPORT_FORMAT.to_string(),
+        version: EXPORT_VERSION,
+        exported_at: Utc::now(),
+        user,
+        ai_models,
+        exchanges,
+        traders,
+    })
+}
+
+/// Writes a takeout as a directory of JSON and CSV files (see the module
+/// docs). `dir` must not exist yet. Returns the files written, relative to
+/// `dir`.
+pub fn write_dir(takeout: &Takeout, dir: &Path) -> Result<Vec<PathBuf>> {
+    if dir.exists() {
+        return Err(ExportError::DestinationExists(dir.to_path_buf()));
+    }
+    let mut files: Vec<(PathBuf, Vec<u8>)> = vec![(
+        PathBuf::from("account.json"),
+        serde_json::to_vec_pretty(&serde_json::json!({
+            "user": takeout.user,
+            "ai_models": takeout.ai_models,
+            "exchanges": takeout.exchanges,
+        }))?,
+    )];
+    for t in &takeout.traders {
+        let base = Path::new("traders").join(file_name(&t.trader.id));
+        files.push((
+            base.join("trader.json"),
+            serde_json::to_vec_pretty(&serde_json::json!({
+                "trader": t.trader,
+                "performance": t.performance,
+                "prompt_versions": t.prompt_versions,
+                "pause_windows": t.pause_windows,
+            }))?,
+        ));
+        files.push((
+            base.join("decisions.json"),
+            serde_json::to_vec_pretty(&t.decisions)?,
+        ));
+        files.push((base.join("trades.csv"), trades_csv(&t.trades).into_bytes()));
+        files.push((base.join("equity.csv"), equity_csv(&t.equity).into_bytes()));
+        files.push((
+            base.join("transfers.csv"),
+            transfers_csv(&t.transfers).into_bytes(),
+        ));
+    }
+
+    let mut written: Vec<PathBuf> = files.iter().map(|(path, _)| path.clone()).collect();
+    files.push((
+        PathBuf::from("manifest.json"),
+        serde_json::to_vec_pretty(&serde_json::json!({
+            "format": takeout.format,
+            "version": takeout.version,
+            "exported_at": takeout.exported_at,
+            "user_id": takeout.user.id,
+            "files": written,
+        }))?,
+    ));
+    written.push(PathBuf::from("manifest.json"));
+
+    for (path, data) in files {
+        let path = dir.join(path);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, data)?;
+    }
+    Ok(written)
+}
+
+/// Fills as CSV, one row per fill.
+pub fn trades_csv(trades: &[Trade]) -> String {
+    trades_table(trades).to_csv()
+}
+
+/// Equity snapshots as CSV, one row per snapshot.
+pub fn equity_csv(snapshots: &[PnlSnapshot]) -> String {
+    equity_table(snapshots).to_csv()
+}
+
+/// Deposits (positive) and withdrawals (negative) as CSV.
+pub fn transfers_csv(transfers: &[AccountTransfer]) -> String {
+    transfers_table(transfers).to_csv()
+}
+
+/// A trader's history that can be exported on its own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Dataset {
+    /// One row per decision cycle.
+    Decisions,
+    Trades,
+    Equity,
+}
+
+impl Dataset {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Dataset::Decisions => "decisions",
+            Dataset::Trades => "trades",
+            Dataset::Equity => "equity",
+        }
+    }
+}
+
+impl FromStr for Dataset {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "decisions" => Ok(Dataset::Decisions),
+            "trades" => Ok(Dataset::Trades),
+            "equity" => Ok(Dataset::Equity),
+            other => Err(format!(
+                "unknown dataset '{}'; use decisions, trades or equity",
+                other
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FileFormat {
+    #[default]
+    Csv,
+    /// Needs the `parquet` feature.
+    Parquet,
+}
+
+impl FileFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            FileFormat::Csv => "csv",
+            FileFormat::Parquet => "parquet",
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            FileFormat::Csv => "text/csv; charset=utf-8",
+            FileFormat::Parquet => "application/vnd.apache.parquet",
+        }
+    }
+}
+
+impl FromStr for FileFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(FileFormat::Csv),
+            "parquet" => Ok(FileFormat::Parquet),
+            other => Err(format!("unknown format '{}'; use csv or parquet", other)),
+        }
+    }
+}
+
+/// Rows taken at or after `since` and at or before `until`; a missing end is
+/// unbounded.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+pub struct DateRange {
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+}
+
+impl DateRange {
+    pub fn contains(&self, at: DateTime<Utc>) -> bool {
+        self.since.is_none_or(|since| at >= since) && self.until.is_none_or(|until| at <= until)
+    }
+}
+
+/// Reads one of a trader's histories within `range`, oldest first. Encrypted
+/// decision records are decrypted with `key`.
+pub async fn history(
+    db: &Database,
+    user_id: &str,
+    trader_id: &str,
+    dataset: Dataset,
+    range: DateRange,
+    key: Option<&RecordKey>,
+) -> Result<Table> {
+    if db.get_trader(user_id, trader_id).await?.is_none() {
+        return Err(ExportError::TraderNotFound(trader_id.to_string()));
+    }
+    let table = match dataset {
+        Dataset::Decisions => {
+            let mut records =
+                logger::load_records(db, user_id, trader_id, i64::MAX as usize, key).await?;
+            records.retain(|r| range.contains(r.timestamp()));
+            records.sort_by_key(|r| r.timestamp());
+            decisions_table(&records)
+        }
+        Dataset::Trades => {
+            let mut trades = db.get_trades(user_id, trader_id, range.since).await?;
+            trades.retain(|t| range.contains(t.executed_at));
+            trades_table(&trades)
+        }
+        Dataset::Equity => {
+            let mut snapshots = db
+                .get_pnl_snapshots(user_id, trader_id, range.since)
+                .await?;
+            snapshots.retain(|s| range.contains(s.taken_at));
+            equity_table(&snapshots)
+        }
+    };
+    Ok(table)
+}
+
+/// Decision cycles, one row each: account state, the AI's reasoning and
+/// decisions, and what was executed. Prompts are left out.
+pub fn decisions_table(records: &[DecisionRecord]) -> Table {
+    Table::new(
+        &[
+            ("id", Kind::Text),
+            ("timestamp", Kind::Time),
+            ("cycle_number", Kind::Int),
+            ("success", Kind::Bool),
+            ("error_message", Kind::Text),
+            ("total_equity", Kind::Float),
+            ("available_balance", Kind::Float),
+            ("position_count", Kind::Int),
+            ("margin_used_pct", Kind::Float),
+            ("candidate_coins", Kind::Text),
+            ("cot_trace", Kind::Text),
+            ("decision_json", Kind::Text),
+            ("executions", Kind::Text),
+        ],
+        records.iter().map(|r| {
+            vec![
+                Cell::Text(r.id().to_string()),
+                Cell::Time(r.timestamp()),
+                Cell::Int(r.cycle_number().into()),
+                Cell::Bool(r.is_success()),
+                Cell::Text(r.error_message().to_string()),
+                Cell::Float(r.total_equity()),
+                Cell::Float(r.available_balance()),
+                Cell::Int(r.position_count().into()),
+                Cell::Float(r.margin_used_pct()),
+                Cell::Text(r.candidate_coins().join(" ")),
+                Cell::Text(r.cot_trace().to_string()),
+                Cell::Text(r.decision_json().to_string()),
+                Cell::Text(r.execution_summary()),
+            ]
+        }),
+    )
+}
+
+pub fn trades_table(trades: &[Trade]) -> Table {
+    Table::new(
+        &[
+            ("executed_at", Kind::Time),
+            ("symbol", Kind::Text),
+            ("side", Kind::Text),
+            ("action", Kind::Text),
+            ("quantity", Kind::Decimal),
+            ("price", Kind::Decimal),
+            ("fee", Kind::Decimal),
+            ("order_id", Kind::Int),
+            ("group_id", Kind::Text),
+            ("expected_price", Kind::Decimal),
+            ("fill_latency_ms", Kind::Int),
+        ],
+        trades.iter().map(|t| {
+            vec![
+                Cell::Time(t.executed_at),
+                Cell::Text(t.symbol.clone()),
+                Cell::Text(t.side.clone()),
+                Cell::Text(t.action.clone()),
+                Cell::Decimal(t.quantity),
+                Cell::Decimal(t.price),
+                Cell::Decimal(t.fee),
+                Cell::Int(t.order_id),
+                Cell::Text(t.group_id.clone()),
+                Cell::Decimal(t.expected_price),
+                Cell::Int(t.fill_latency_ms),
+            ]
+        }),
+    )
+}
+
+pub fn equity_table(snapshots: &[PnlSnapshot]) -> Table {
+    Table::new(
+        &[
+            ("taken_at", Kind::Time),
+            ("total_equity", Kind::Decimal),
+            ("available_balance", Kind::Decimal),
+            ("unrealized_pnl", Kind::Decimal),
+            ("margin_used", Kind::Decimal),
+            ("position_count", Kind::Int),
+        ],
+        snapshots.iter().map(|s| {
+            vec![
+                Cell::Time(s.taken_at),
+                Cell::Decimal(s.total_equity),
+                Cell::Decimal(s.available_balance),
+                Cell::Decimal(s.unrealized_pnl),
+                Cell::Decimal(s.margin_used),
+                Cell::Int(s.position_count.into()),
+            ]
+        }),
+    )
+}
+
+pub fn transfers_table(transfers: &[AccountTransfer]) -> Table {
+    Table::new(
+        &[
+            ("occurred_at", Kind::Time),
+            ("amount", Kind::Decimal),
+            ("asset", Kind::Text),
+            ("source", Kind::Text),
+            ("external_id", Kind::Text),
+            ("note", Kind::Text),
+        ],
+        transfers.iter().map(|t| {
+            vec![
+                Cell::Time(t.occurred_at),
+                Cell::Decimal(t.amount),
+                Cell::Text(t.asset.clone()),
+                Cell::Text(t.source.clone()),
+                Cell::Text(t.external_id.clone().unwrap_or_default()),
+                Cell::Text(t.note.clone()),
+            ]
+        }),
+    )
+}
+
+/// Type of a column. CSV writes every value as text; Parquet keeps decimals
+/// as doubles and timestamps as UTC milliseconds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Text,
+    Decimal,
+    Float,
+    Int,
+    Bool,
+    Time,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Cell {
+    Text(String),
+    Decimal(Decimal),
+    Float(f64),
+    Int(i64),
+    Bool(bool),
+    Time(DateTime<Utc>),
+}
+
+impl Cell {
+    fn to_csv(&self) -> String {
+        match self {
+            Cell::Text(s) => csv_field(s),
+            Cell::Decimal(d) => d.to_string(),
+            Cell::Float(f) => f.to_string(),
+            Cell::Int(i) => i.to_string(),
+            Cell::Bool(b) => b.to_string(),
+            Cell::Time(t) => t.to_rfc3339(),
+        }
+    }
+}
+
+/// Rows of one history, ready to be written as CSV or Parquet.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Table {
+    columns: Vec<(&'static str, Kind)>,
+    rows: Vec<Vec<Cell>>,
+}
+
+impl Table {
+    fn new(columns: &[(&'static str, Kind)], rows: impl Iterator<Item = Vec<Cell>>) -> Self {
+        Self {
+            columns: columns.to_vec(),
+            rows: rows.collect(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    /// The header line followed by chunks of at most `rows_per_chunk` rows,
+    /// each ending in a newline, so large histories can be streamed.
+    pub fn into_csv_chunks(self, rows_per_chunk: usize) -> impl Iterator<Item = String> {
+        let header = self.csv_header();
+        let per_chunk = rows_per_chunk.max(1);
+        let mut rows = self.rows.into_iter().peekable();
+        let chunks = std::iter::from_fn(move || {
+            rows.peek()?;
+            Some(csv_rows(rows.by_ref().take(per_chunk)))
+        });
+        std::iter::once(header).chain(chunks)
+    }
+
+    pub fn to_csv(&self) -> String {
+        let mut out = self.csv_header();
+        out.push_str(&csv_rows(self.rows.iter()));
+        out
+    }
+
+    fn csv_header(&self) -> String {
+        let names: Vec<&str> = self.columns.iter().map(|(name, _)| *name).collect();
+        let mut out = names.join(",");
+        out.push('\n');
+        out
+    }
+
+    /// The whole table as a Parquet file with a single row group.
+    #[cfg(feature = "parquet")]
+    pub fn to_parquet(&self) -> Result<Vec<u8>> {
+        parquet_file::write(&self.columns, &self.rows)
+            .map_err(|e| ExportError::Parquet(e.to_string()))
+    }
+
+    #[cfg(not(feature = "parquet"))]
+    pub fn to_parquet(&self) -> Result<Vec<u8>> {
+        Err(ExportError::FormatDisabled("parquet"))
+    }
+
+    pub fn encode(&self, format: FileFormat) -> Result<Vec<u8>> {
+        match format {
+            FileFormat::Csv => Ok(self.to_csv().into_bytes()),
+            FileFormat::Parquet => self.to_parquet(),
+        }
+    }
+}
+
+#[cfg(feature = "parquet")]
+mod parquet_file {
+    use std::sync::Arc;
+
+    use parquet::data_type::{BoolType, ByteArray, ByteArrayType, DoubleType, Int64Type};
+    use parquet::errors::Result;
+    use parquet::file::properties::WriterProperties;
+    use parquet::file::writer::SerializedFileWriter;
+    use parquet::schema::parser::parse_message_type;
+
+    use super::{Cell, Kind};
+    use crate::money;
+
+    pub(super) fn write(columns: &[(&'static str, Kind)], rows: &[Vec<Cell>]) -> Result<Vec<u8>> {
+        let fields: Vec<String> = columns
+            .iter()
+            .map(|(name, kind)| match kind {
+                Kind::Text => format!("REQUIRED BYTE_ARRAY {} (STRING);", name),
+                Kind::Decimal | Kind::Float => format!("REQUIRED DOUBLE {};", name),
+                Kind::Int => format!("REQUIRED INT64 {};", name),
+                Kind::Bool => format!("REQUIRED BOOLEAN {};", name),
+                Kind::Time => format!("REQUIRED INT64 {} (TIMESTAMP(MILLIS,true));", name),
+            })
+            .collect();
+        let schema = Arc::new(parse_message_type(&format!(
+            "message history {{ {} }}",
+            fields.join(" ")
+        ))?);
+
+        let mut out = Vec::new();
+        let mut writer = SerializedFileWriter::new(
+            &mut out,
+            schema,
+            Arc::new(WriterProperties::builder().build()),
+        )?;
+        let mut group = writer.next_row_group()?;
+        let mut index = 0;
+        while let Some(mut column) = group.next_column()? {
+            let cells = rows.iter().map(|row| &row[index]);
+            match columns[index].1 {
+                Kind::Text => {
+                    let values: Vec<ByteArray> = cells
+                        .map(|c| match c {
+                            Cell::Text(s) => ByteArray::from(s.as_str()),
+                            _ => ByteArray::from(""),
+                        })
+                        .collect();
+                    column
+                        .typed::<ByteArrayType>()
+                        .write_batch(&values, None, None)?;
+                }
+                Kind::Decimal | Kind::Float => {
+                    let values: Vec<f64> = cells
+                        .map(|c| match c {
+                            Cell::Decimal(d) => money::to_f64(*d),
+                            Cell::Float(f) => *f,
+                            _ => 0.0,
+                        })
+                        .collect();
+                    column
+                        .typed::<DoubleType>()
+                        .write_batch(&values, None, None)?;
+                }
+                Kind::Int | Kind::Time => {
+                    let values: Vec<i64> = cells
+                        .map(|c| match c {
+                            Cell::Int(i) => *i,
+                            Cell::Time(t) => t.timestamp_millis(),
+                            _ => 0,
+                        })
+                        .collect();
+                    column
+                        .typed::<Int64Type>()
+                        .write_batch(&values, None, None)?;
+                }
+                Kind::Bool => {
+                    let values: Vec<bool> = cells.map(|c| matches!(c, Cell::Bool(true))).collect();
+                    column
+                        .typed::<BoolType>()
+                        .write_batch(&values, None, None)?;
+                }
+            }
+            column.close()?;
+            index += 1;
+        }
+        group.close()?;
+        writer.close()?;
+        Ok(out)
+    }
+}
+
+fn csv_rows<R: AsRef<[Cell]>>(rows: impl Iterator<Item = R>) -> String {
+    let mut out = String::new();
+    for row in rows {
+        let fields: Vec<String> = row.as_ref().iter().map(Cell::to_csv).collect();
+        out.push_str(&fields.join(","));
+        out.push('\n');
+    }
+    out
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+// Trader ids are used as directory names; keep them to a portable alphabet.
+fn file_name(id: &str) -> String {
+    id.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,215 @@
+//! Secondary public market data venues used when Binance is unreachable.
+//!
+//! Both return candles oldest → newest in the same `Kline` shape as Binance so the
+//! regular indicator pipeline can run on them unchanged.
+
+use serde::Deserialize;
+
+use crate::api_client::{EndpointClass, client_for, timeout_for};
+use crate::data::{MarketError, RawMarketData};
+use crate::rate_limit::SendLimited;
+use crate::types::{Kline, OIData};
+
+const BYBIT_URL: &str = "https://api.bybit.com";
+const OKX_URL: &str = "https://www.okx.com";
+
+// --- Bybit (v5, linear perpetuals) ---
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BybitResponse<T> {
+    ret_code: i64,
+    ret_msg: String,
+    result: T,
+}
+
+#[derive(Deserialize)]
+struct BybitList<T> {
+    list: Vec<T>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BybitTicker {
+    funding_rate: String,
+    open_interest: String,
+}
+
+async fn bybit_get<T: for<'de> Deserialize<'de>>(
+    path: &str,
+    query: &[(&str, &str)],
+) -> Result<T, MarketError> {
+    let resp = client_for(EndpointClass::MarketData)
+        .get(format!("{}{}", BYBIT_URL, path))
+        .timeout(timeout_for(EndpointClass::MarketData))
+        .query(query)
+        .send_limited()
+        .await?
+        .json::<BybitResponse<T>>()
+        .await?;
+    if resp.ret_code != 0 {
+        return Err(MarketError::InsufficientData(format!(
+            "Bybit error {}: {}",
+            resp.ret_code, resp.ret_msg
+        )));
+    }
+    Ok(resp.result)
+}
+
+async fn bybit_klines(symbol: &str, interval: &str, limit: u16) -> Result<Vec<Kline>, MarketError> {
+    // Rows: [startTime, open, high, low, close, volume, turnover], newest first.
+    let result: BybitList<Vec<String>> = bybit_get(
+        "/v5/market/kline",
+        &[
+            ("category", "linear"),
+            ("symbol", symbol),
+            ("interval", interval),
+            ("limit", &limit.to_string()),
+        ],
+    )
+    .await?;
+
+    let mut klines = result
+        .list
+        .iter()
+        .map(|row| parse_row(row, 6))
+        .collect::<Result<Vec<_>, _>>()?;
+    klines.reverse();
+    Ok(klines)
+}
+
+/// Fetches candles, funding and open interest for a Binance-style symbol from Bybit.
+pub async fn fetch_bybit(symbol: &str) -> Result<RawMarketData, MarketError> {
+    let ticker_query = [("category", "linear"), ("symbol", symbol)];
+    let (klines_3m, klines_4h, tickers) = tokio::try_join!(
+        bybit_klines(symbol, "3", 50),
+        bybit_klines(symbol, "240", 60),
+        bybit_get::<BybitList<BybitTicker>>("/v5/market/tickers", &ticker_query)
+    )?;
+
+    let ticker = tickers.list.first();
+    let funding_rate = ticker.and_then(|t| t.funding_rate.parse().ok());
+    let open_interest = ticker
+        .and_then(|t| t.open_interest.parse::<f64>().ok())
+        .map(|oi| OIData {
+            latest: oi,
+            average: oi * 0.999,
+        });
+
+    Ok(RawMarketData {
+        klines_3m,
+        klines_4h,
+        open_interest,
+        funding_rate,
+        mark_price: None,
+    })
+}
+
+// --- OKX (v5, USDT-margined swaps) ---
+
+#[derive(Deserialize)]
+struct OkxResponse<T> {
+    code: String,
+    msg: String,
+    data: Vec<T>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct OkxFunding {
+    funding_rate: String,
+}
+
+async fn okx_get<T: for<'de> Deserialize<'de>>(
+    path: &str,
+    query: &[(&str, &str)],
+) -> Result<Vec<T>, MarketError> {
+    let resp = client_for(EndpointClass::MarketData)
+        .get(format!("{}{}", OKX_URL, path))
+        .timeout(timeout_for(EndpointClass::MarketData))
+        .query(query)
+        .send_limited()
+        .await?
+        .json::<OkxResponse<T>>()
+        .await?;
+    if resp.code != "0" {
+        return Err(MarketError::InsufficientData(format!(
+            "OKX error {}: {}",
+            resp.code, resp.msg
+        )));
+    }
+    Ok(resp.data)
+}
+
+/// Converts "BTCUSDT" into OKX's "BTC-USDT-SWAP".
+fn okx_inst_id(symbol: &str) -> String {
+    let base = symbol.strip_suffix("USDT").unwrap_or(symbol);
+    format!("{}-USDT-SWAP", base)
+}
+
+async fn okx_klines(inst_id: &str, bar: &str, limit: u16) -> Result<Vec<Kline>, MarketError> {
+    // Rows: [ts, o, h, l, c, vol, volCcy, volCcyQuote, confirm], newest first.
+    let rows: Vec<Vec<String>> = okx_get(
+        "/api/v5/market/candles",
+        &[
+            ("instId", inst_id),
+            ("bar", bar),
+            ("limit", &limit.to_string()),
+        ],
+    )
+    .await?;
+
+    let mut klines = rows
+        .iter()
+        .map(|row| parse_row(row, 7))
+        .collect::<Result<Vec<_>, _>>()?;
+    klines.reverse();
+    Ok(klines)
+}
+
+/// Fetches candles and funding for a Binance-style symbol from OKX.
+/// OKX reports open interest in contracts, so it is left out rather than mixed in.
+pub async fn fetch_okx(symbol: &str) -> Result<RawMarketData, MarketError> {
+    let inst_id = okx_inst_id(symbol);
+    let funding_query = [("instId", inst_id.as_str())];
+    let (klines_3m, klines_4h, funding) = tokio::try_join!(
+        okx_klines(&inst_id, "3m", 50),
+        okx_klines(&inst_id, "4H", 60),
+        okx_get::<OkxFunding>("/api/v5/public/funding-rate", &funding_query)
+    )?;
+
+    Ok(RawMarketData {
+        klines_3m,
+        klines_4h,
+        open_interest: None,
+        funding_rate: funding.first().and_then(|f| f.funding_rate.parse().ok()),
+        mark_price: None,
+    })
+}
+
+/// Parses `[ts, open, high, low, close, volume, ..., quote_volume@quote_idx]`.
+fn parse_row(row: &[String], quote_idx: usize) -> Result<Kline, MarketError> {
+    if row.len() <= quote_idx {
+        return Err(MarketError::InsufficientData(format!(
+            "Candle row has {} fields",
+            row.len()
+        )));
+    }
+    let open_time: i64 = row[0]
+        .parse()
+        .map_err(|_| MarketError::InsufficientData(format!("Invalid timestamp '{}'", row[0])))?;
+
+    Ok(Kline {
+        open_time,
+        open: row[1].parse()?,
+        high: row[2].parse()?,
+        low: row[3].parse()?,
+        close: row[4].parse()?,
+        volume: row[5].parse()?,
+        close_time: open_time,
+        quote_volume: row[quote_idx].parse().unwrap_or(0.0),
+        trades: 0,
+        taker_buy_base_volume: 0.0,
+        taker_buy_quote_volume: 0.0,
+    })
+}
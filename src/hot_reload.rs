@@ -0,0 +1,286 @@
+//! Hot reload of settings from the config file and system_config.
+//!
+//! The leverage defaults, the risk limits and the default coin list can be
+//! changed while the process runs. The `config_reload` scheduler job calls
+//! [`Reloader::poll`], which re-reads the config file when its modification
+//! time changes and the matching system_config rows every time, and applies
+//! whatever changed in either. At startup system_config overrides the file;
+//! afterwards the latest edit wins, whichever source it came from. Every
+//! change is logged as a `config_changed` event.
+//!
+//! Nothing is pushed to running traders: each reads [`current`] at the start
+//! of its next cycle. Leverage defaults apply to traders created afterwards.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::RwLock;
+use std::time::SystemTime;
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::config::{self, Config, ConfigKey};
+use crate::database::Database;
+use crate::universe;
+
+const FILE: &str = "file";
+const SYSTEM_CONFIG: &str = "system_config";
+
+static SETTINGS: Lazy<RwLock<Settings>> = Lazy::new(|| RwLock::new(Settings::default()));
+
+/// Settings that take effect without a restart. Field names match the
+/// system_config keys.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Settings {
+    /// Leverage given to new traders on BTC/ETH and on other coins.
+    pub btc_eth_leverage: i32,
+    pub altcoin_leverage: i32,
+    /// Loss since the start of the day, in percent of that day's opening
+    /// equity, at which a trader stops opening positions; 0 disables.
+    pub max_daily_loss: f64,
+    /// Drop from the highest equity, in percent, at which a trader stops
+    /// opening positions; 0 disables.
+    pub max_drawdown: f64,
+    /// How long new entries stay stopped after a limit is hit.
+    pub stop_trading_minutes: i32,
+    /// Symbols traded by traders that have none configured.
+    pub default_coins: Vec<String>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self::from_config(&Config::default())
+    }
+}
+
+impl Settings {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            btc_eth_leverage: config.leverage.btc_eth_leverage,
+            altcoin_leverage: config.leverage.altcoin_leverage,
+            max_daily_loss: config.max_daily_loss,
+            max_drawdown: config.max_drawdown,
+            stop_trading_minutes: config.stop_trading_minutes,
+            default_coins: config.default_coins.clone(),
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.btc_eth_leverage <= 0 || self.altcoin_leverage <= 0 {
+            return Err("leverage must be positive".to_string());
+        }
+        let limits = [self.max_daily_loss, self.max_drawdown];
+        if limits.iter().any(|l| !l.is_finite() || *l < 0.0) {
+            return Err("loss limits must be zero or positive".to_string());
+        }
+        if self.stop_trading_minutes < 0 {
+            return Err("stop_trading_minutes may not be negative".to_string());
+        }
+        Ok(())
+    }
+
+    fn fields(&self) -> Fields {
+        match serde_json::to_value(self) {
+            Ok(Value::Object(map)) => map.into_iter().collect(),
+            _ => Fields::new(),
+        }
+    }
+
+    fn from_fields(fields: &Fields) -> Result<Self, String> {
+        let map = fields.clone().into_iter().collect();
+        let settings: Settings =
+            serde_json::from_value(Value::Object(map)).map_err(|e| e.to_string())?;
+        settings.validate()?;
+        Ok(settings)
+    }
+}
+
+type Fields = BTreeMap<String, Value>;
+
+/// The settings in effect.
+pub fn current() -> Settings {
+    SETTINGS.read().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+/// Replaces the settings in effect.
+pub fn set(settings: Settings) {
+    universe::set_default_coins(settings.default_coins.clone());
+    *SETTINGS.write().unwrap_or_else(|e| e.into_inner()) = settings;
+}
+
+/// One setting that was changed by a reload.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SettingChange {
+    pub key: String,
+    pub old: Value,
+    pub new: Value,
+    /// "file" or "system_config".
+    pub source: &'static str,
+}
+
+/// Tracks what each source last said, so only edits are applied.
+pub struct Reloader {
+    path: PathBuf,
+    // Decrypts the file when it is encrypted.
+    key: ConfigKey,
+    modified: Option<SystemTime>,
+    file: Fields,
+    stored: Fields,
+    // Invalid system_config values already warned about.
+    rejected: BTreeMap<String, String>,
+}
+
+impl Reloader {
+    /// Loads the settings from the config file at `path`, overridden by
+    /// system_config, and puts them in effect.
+    pub async fn start(path: impl Into<PathBuf>, db: &Database) -> Self {
+        Self::start_with_key(path, ConfigKey::from_env(), db).await
+    }
+
+    /// [`start`](Self::start) for a config file that may be encrypted with
+    /// `key`; pass a [resolved](ConfigKey::resolve) key so reloads never prompt.
+    pub async fn start_with_key(path: impl Into<PathBuf>, key: ConfigKey, db: &Database) -> Self {
+        let mut reloader = Self {
+            path: path.into(),
+            key,
+            modified: None,
+            file: Fields::new(),
+            stored: Fields::new(),
+            rejected: BTreeMap::new(),
+        };
+        let changes = reloader.poll_quietly(db).await;
+        tracing::info!(
+            "⚙️ 已加载可热更新的配置（{} 项来自文件或数据库）",
+            changes.len()
+        );
+        reloader
+    }
+
+    /// Applies edits made since the last poll and logs each of them.
+    pub async fn poll(&mut self, db: &Database) -> Vec<SettingChange> {
+        let changes = self.poll_quietly(db).await;
+        for change in &changes {
+            tracing::info!(
+                event = "config_changed",
+                key = %change.key,
+                old = %change.old,
+                new = %change.new,
+                source = change.source,
+                "🔄 配置 {} 已更新: {} → {}（来源: {}）",
+                change.key,
+                change.old,
+                change.new,
+                change.source
+            );
+        }
+        changes
+    }
+
+    async fn poll_quietly(&mut self, db: &Database) -> Vec<SettingChange> {
+        let mut current = current().fields();
+        let mut changes = Vec::new();
+        if let Some(file) = self.read_file() {
+            merge(&mut self.file, file, FILE, &mut current, &mut changes);
+        }
+        let stored = self.read_stored(db, &current).await;
+        merge(
+            &mut self.stored,
+            stored,
+            SYSTEM_CONFIG,
+            &mut current,
+            &mut changes,
+        );
+        if !changes.is_empty() {
+            match Settings::from_fields(&current) {
+                Ok(settings) => set(settings),
+                Err(e) => {
+                    tracing::warn!("⚠️ 配置更新无效，已忽略: {}", e);
+                    changes.clear();
+                }
+            }
+        }
+        changes
+    }
+
+    // The file's settings if it changed since the last read and is valid.
+    fn read_file(&mut self) -> Option<Fields> {
+        let modified = std::fs::metadata(&self.path)
+            .and_then(|m| m.modified())
+            .ok()?;
+        if self.modified == Some(modified) {
+            return None;
+        }
+        self.modified = Some(modified);
+        match config::load_config_with_key(&self.path.to_string_lossy(), &self.key) {
+            Ok(config) => Some(Settings::from_config(&config).fields()),
+            Err(e) => {
+                tracing::warn!(
+                    "⚠️ 配置文件 {} 无效，保留当前配置: {}",
+                    self.path.display(),
+                    e
+                );
+                None
+            }
+        }
+    }
+
+    // The system_config rows for each setting, where they parse as its type.
+    async fn read_stored(&mut self, db: &Database, current: &Fields) -> Fields {
+        let mut fields = Fields::new();
+        for key in current.keys() {
+            let Ok(raw) = db.get_system_config(key).await else {
+                continue;
+            };
+            let parsed = serde_json::from_str::<Value>(&raw).ok().filter(|value| {
+                let mut candidate = current.clone();
+                candidate.insert(key.clone(), value.clone());
+                Settings::from_fields(&candidate).is_ok()
+            });
+            match parsed {
+                Some(value) => {
+                    self.rejected.remove(key);
+                    fields.insert(key.clone(), value);
+                }
+                None if self.rejected.get(key) != Some(&raw) => {
+                    tracing::warn!("⚠️ system_config 中 {} 的值无效，已忽略: {}", key, raw);
+                    self.rejected.insert(key.clone(), raw);
+                }
+                None => {}
+            }
+        }
+        fields
+    }
+}
+
+// Applies the keys of `latest` that changed since `seen` to `current`.
+fn merge(
+    seen: &mut Fields,
+    latest: Fields,
+    source: &'static str,
+    current: &mut Fields,
+    changes: &mut Vec<SettingChange>,
+) {
+    for (key, value) in latest {
+        if seen.get(&key) == Some(&value) {
+            continue;
+        }
+        seen.insert(key.clone(), value.clone());
+        let old = current.insert(key.clone(), value.clone());
+        if old.as_ref() == Some(&value) {
+            continue;
+        }
+        match changes.iter_mut().find(|c| c.key == key) {
+            Some(change) => {
+                change.new = value;
+                change.source = source;
+            }
+            None => changes.push(SettingChange {
+                key,
+                old: old.unwrap_or(Value::Null),
+                new: value,
+                source,
+            }),
+        }
+    }
+}
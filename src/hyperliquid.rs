@@ -0,0 +1,430 @@
+//! Market data and accounts from Hyperliquid's public info API.
+//!
+//! Traders on Hyperliquid read candles, mark price, funding and open interest
+//! from the venue itself: Binance prices can drift from Hyperliquid's mark,
+//! which is what positions there are margined and liquidated against. Every
+//! request is a POST to `/info`; candles come back in the same `Kline` shape as
+//! Binance so the regular indicator pipeline runs on them unchanged.
+//!
+//! [`HyperliquidAccount`] reads a wallet's balance and positions the same way.
+//! Orders are not supported, so Hyperliquid traders run watch-only.
+
+use once_cell::sync::Lazy;
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::api_client::{EndpointClass, client_for, timeout_for};
+use crate::cache::BoundedCache;
+use crate::data::{MarketError, RawMarketData};
+use crate::database::ExchangeConfig;
+use crate::decision::PositionInfo;
+use crate::executor::{self, ExecutorError, OrderFill, TradeExecutor};
+use crate::money::Decimal;
+use crate::rate_limit::SendLimited;
+use crate::types::{Kline, OIData};
+
+/// Mainnet info API.
+pub const HYPERLIQUID_URL: &str = "https://api.hyperliquid.xyz";
+/// Testnet info API.
+pub const HYPERLIQUID_TESTNET_URL: &str = "https://api.hyperliquid-testnet.xyz";
+
+const ORDERS_UNSUPPORTED: &str =
+    "Hyperliquid order placement is not supported; run the trader watch-only";
+
+// Quote suffixes of our symbols; Hyperliquid names perpetuals by coin alone.
+const QUOTE_SUFFIXES: &[&str] = &["USDT", "USDC"];
+
+// Hyperliquid funding is paid hourly; prompts and risk checks assume Binance's
+// 8-hour rate.
+const FUNDING_PERIODS_PER_8H: f64 = 8.0;
+
+// Contexts of every perpetual per base URL. One request covers all symbols, so
+// a scan over many symbols shares it.
+static ASSET_CONTEXTS: Lazy<BoundedCache<String, Arc<HashMap<String, AssetContext>>>> =
+    Lazy::new(|| BoundedCache::new(4, Duration::from_secs(10)));
+
+/// Live state of one perpetual.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AssetContext {
+    pub mark_price: f64,
+    /// Funding per 8 hours, comparable with Binance's rate.
+    pub funding_rate: f64,
+    /// Open interest in coins.
+    pub open_interest: f64,
+}
+
+#[derive(Deserialize)]
+struct Meta {
+    universe: Vec<Asset>,
+}
+
+#[derive(Deserialize)]
+struct Asset {
+    name: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawAssetContext {
+    funding: String,
+    open_interest: String,
+    mark_px: String,
+}
+
+#[derive(Deserialize)]
+struct Candle {
+    t: i64,
+    #[serde(rename = "T")]
+    close_time: i64,
+    o: String,
+    h: String,
+    l: String,
+    c: String,
+    v: String,
+    #[serde(default)]
+    n: i64,
+}
+
+/// Hyperliquid's coin name for a symbol: "BTCUSDT" and "BTCUSDC" are both "BTC".
+pub fn coin(symbol: &str) -> String {
+    let symbol = symbol.trim().to_uppercase();
+    QUOTE_SUFFIXES
+        .iter()
+        .find_map(|quote| symbol.strip_suffix(quote).filter(|base| !base.is_empty()))
+        .unwrap_or(&symbol)
+        .to_string()
+}
+
+async fn info<T: DeserializeOwned>(base_url: &str, body: Value) -> Result<T, MarketError> {
+    let resp = client_for(EndpointClass::MarketData)
+        .post(format!("{}/info", base_url.trim_end_matches('/')))
+        .timeout(timeout_for(EndpointClass::MarketData))
+        .json(&body)
+        .send_limited()
+        .await?;
+    let status = resp.status();
+    let text = resp.text().await?;
+    if !status.is_success() {
+        return Err(MarketError::InsufficientData(format!(
+            "Hyperliquid error {}: {}",
+            status.as_u16(),
+            text
+        )));
+    }
+    Ok(serde_json::from_str(&text)?)
+}
+
+// Length of a candle interval ("3m", "4h", "1d", "1w") in milliseconds.
+fn interval_millis(interval: &str) -> Result<i64, MarketError> {
+    let unsupported = || MarketError::UnsupportedTimeframe(interval.to_string());
+    let split = interval.len().checked_sub(1).ok_or_else(unsupported)?;
+    let (count, unit) = interval.split_at(split);
+    let count: i64 = count.parse().map_err(|_| unsupported())?;
+    let unit = match unit {
+        "m" => 60_000,
+        "h" => 60 * 60_000,
+        "d" => 24 * 60 * 60_000,
+        "w" => 7 * 24 * 60 * 60_000,
+        _ => return Err(unsupported()),
+    };
+    Ok(count * unit)
+}
+
+/// The last `limit` candles of `coin`, oldest first. The newest one may still
+/// be forming, as with Binance.
+pub async fn candles(
+    base_url: &str,
+    coin: &str,
+    interval: &str,
+    limit: u16,
+) -> Result<Vec<Kline>, MarketError> {
+    let end = chrono::Utc::now().timestamp_millis();
+    let start = end - interval_millis(interval)? * i64::from(limit);
+    let candles: Vec<Candle> = info(
+        base_url,
+        json!({
+            "type": "candleSnapshot",
+            "req": { "coin": coin, "interval": interval, "startTime": start, "endTime": end },
+        }),
+    )
+    .await?;
+
+    let mut klines = candles
+        .iter()
+        .map(|c| {
+            let close: f64 = c.c.parse()?;
+            let volume: f64 = c.v.parse()?;
+            Ok(Kline {
+                open_time: c.t,
+                open: c.o.parse()?,
+                high: c.h.parse()?,
+                low: c.l.parse()?,
+                close,
+                volume,
+                close_time: c.close_time,
+                // Not reported; estimated at the close.
+                quote_volume: volume * close,
+                trades: c.n,
+                taker_buy_base_volume: 0.0,
+                taker_buy_quote_volume: 0.0,
+            })
+        })
+        .collect::<Result<Vec<_>, MarketError>>()?;
+    klines.sort_by_key(|k| k.open_time);
+    let excess = klines.len().saturating_sub(limit as usize);
+    klines.drain(..excess);
+    Ok(klines)
+}
+
+async fn fetch_asset_contexts(
+    base_url: &str,
+) -> Result<HashMap<String, AssetContext>, MarketError> {
+    let (meta, contexts): (Meta, Vec<RawAssetContext>) =
+        info(base_url, json!({ "type": "metaAndAssetCtxs" })).await?;
+    meta.universe
+        .into_iter()
+        .zip(contexts)
+        .map(|(asset, ctx)| {
+            let context = AssetContext {
+                mark_price: ctx.mark_px.parse()?,
+                funding_rate: ctx.funding.parse::<f64>()? * FUNDING_PERIODS_PER_8H,
+                open_interest: ctx.open_interest.parse()?,
+            };
+            Ok((asset.name, context))
+        })
+        .collect()
+}
+
+/// Mark price, funding and open interest of `coin`.
+pub async fn asset_context(base_url: &str, coin: &str) -> Result<AssetContext, MarketError> {
+    let contexts = ASSET_CONTEXTS
+        .get_or_try_insert(base_url.to_string(), || async {
+            fetch_asset_contexts(base_url).await.map(Arc::new)
+        })
+        .await?;
+    contexts
+        .get(coin)
+        .copied()
+        .ok_or_else(|| MarketError::NotListed(coin.to_string()))
+}
+
+/// Fetches candles, mark price, funding and open interest for a Binance-style
+/// symbol from the Hyperliquid info API at `base_url`.
+pub async fn fetch_from(base_url: &str, symbol: &str) -> Result<RawMarketData, MarketError> {
+    let coin = coin(symbol);
+    let (klines_3m, klines_4h, context) = tokio::try_join!(
+        candles(base_url, &coin, "3m", 50),
+        candles(base_url, &coin, "4h", 60),
+        asset_context(base_url, &coin)
+    )?;
+
+    Ok(RawMarketData {
+        klines_3m,
+        klines_4h,
+        open_interest: Some(OIData {
+            latest: context.open_interest,
+            average: context.open_interest * 0.999,
+        }),
+        funding_rate: Some(context.funding_rate),
+        mark_price: Some(context.mark_price),
+    })
+}
+
+/// [`fetch_from`] against mainnet.
+pub async fn fetch(symbol: &str) -> Result<RawMarketData, MarketError> {
+    fetch_from(HYPERLIQUID_URL, symbol).await
+}
+
+// --- Accounts ---
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ClearinghouseState {
+    margin_summary: MarginSummary,
+    withdrawable: String,
+    #[serde(default)]
+    asset_positions: Vec<AssetPosition>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct MarginSummary {
+    account_value: String,
+}
+
+#[derive(Deserialize)]
+struct AssetPosition {
+    position: Position,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Position {
+    coin: String,
+    /// Signed size; negative for shorts.
+    szi: String,
+    entry_px: String,
+    position_value: String,
+    unrealized_pnl: String,
+    leverage: Leverage,
+    liquidation_px: Option<String>,
+    margin_used: String,
+}
+
+#[derive(Deserialize)]
+struct Leverage {
+    value: i32,
+}
+
+/// Account value and what can be withdrawn from it, in USDC.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WalletBalance {
+    pub account_value: f64,
+    pub withdrawable: f64,
+}
+
+/// A Hyperliquid wallet read through the info API. It places no orders, so
+/// it only backs watch-only traders.
+pub struct HyperliquidAccount {
+    base_url: String,
+    wallet: String,
+}
+
+impl HyperliquidAccount {
+    pub fn new(wallet: &str) -> Self {
+        Self {
+            base_url: HYPERLIQUID_URL.to_string(),
+            wallet: wallet.trim().to_lowercase(),
+        }
+    }
+
+    /// The account of a Hyperliquid entry in the exchanges table.
+    pub fn for_exchange(exchange: &ExchangeConfig) -> Self {
+        let account = Self::new(&exchange.hyperliquid_wallet_addr);
+        if exchange.testnet {
+            account.with_base_url(HYPERLIQUID_TESTNET_URL)
+        } else {
+            account
+        }
+    }
+
+    pub fn with_base_url(mut self, base_url: &str) -> Self {
+        self.base_url = base_url.trim_end_matches('/').to_string();
+        self
+    }
+
+    async fn state(&self) -> Result<ClearinghouseState, MarketError> {
+        info(
+            &self.base_url,
+            json!({ "type": "clearinghouseState", "user": self.wallet }),
+        )
+        .await
+    }
+
+    pub async fn balance(&self) -> Result<WalletBalance, MarketError> {
+        let state = self.state().await?;
+        Ok(WalletBalance {
+            account_value: state.margin_summary.account_value.parse()?,
+            withdrawable: state.withdrawable.parse()?,
+        })
+    }
+
+    /// Open positions, with symbols in our "BTCUSDT" form.
+    pub async fn positions(&self) -> Result<Vec<PositionInfo>, MarketError> {
+        let state = self.state().await?;
+        state
+            .asset_positions
+            .into_iter()
+            .map(|p| position_info(p.position))
+            .filter(|p| !matches!(p, Ok(p) if p.quantity == 0.0))
+            .collect()
+    }
+}
+
+fn position_info(position: Position) -> Result<PositionInfo, MarketError> {
+    let size: f64 = position.szi.parse()?;
+    let quantity = size.abs();
+    let unrealized_pnl: f64 = position.unrealized_pnl.parse()?;
+    let margin_used: f64 = position.margin_used.parse()?;
+    let position_value: f64 = position.position_value.parse()?;
+    Ok(PositionInfo {
+        symbol: format!("{}USDT", position.coin),
+        side: if size < 0.0 { "short" } else { "long" }.to_string(),
+        entry_price: position.entry_px.parse()?,
+        mark_price: if quantity > 0.0 {
+            position_value / quantity
+        } else {
+            0.0
+        },
+        quantity,
+        leverage: position.leverage.value.max(1),
+        unrealized_pnl,
+        unrealized_pnl_pct: if margin_used > 0.0 {
+            unrealized_pnl / margin_used * 100.0
+        } else {
+            0.0
+        },
+        liquidation_price: match position.liquidation_px {
+            Some(price) => price.parse()?,
+            None => 0.0,
+        },
+        margin_used,
+        update_time: 0,
+    })
+}
+
+fn exchange_error(e: MarketError) -> ExecutorError {
+    ExecutorError::Exchange(e.to_string())
+}
+
+fn refused() -> ExecutorError {
+    ExecutorError::InvalidOrder(ORDERS_UNSUPPORTED.to_string())
+}
+
+impl TradeExecutor for HyperliquidAccount {
+    async fn open_long(
+        &mut self,
+        _symbol: &str,
+        _quantity: Decimal,
+        _leverage: i32,
+    ) -> executor::Result<OrderFill> {
+        Err(refused())
+    }
+
+    async fn open_short(
+        &mut self,
+        _symbol: &str,
+        _quantity: Decimal,
+        _leverage: i32,
+    ) -> executor::Result<OrderFill> {
+        Err(refused())
+    }
+
+    async fn close(
+        &mut self,
+        _symbol: &str,
+        _side: &str,
+        _quantity: Decimal,
+    ) -> executor::Result<OrderFill> {
+        Err(refused())
+    }
+
+    async fn set_leverage(&mut self, _symbol: &str, _leverage: i32) -> executor::Result<()> {
+        Err(refused())
+    }
+
+    async fn get_positions(&mut self) -> executor::Result<Vec<PositionInfo>> {
+        self.positions().await.map_err(exchange_error)
+    }
+
+    async fn get_price(&mut self, symbol: &str) -> executor::Result<f64> {
+        asset_context(&self.base_url, &coin(symbol))
+            .await
+            .map(|context| context.mark_price)
+            .map_err(exchange_error)
+    }
+}
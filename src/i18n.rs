@@ -0,0 +1,242 @@
+//! Localized user-facing text for API responses and notifications.
+//!
+//! Logs stay as they are; anything a user can read goes through [`Msg`] so
+//! clients get a consistent language instead of whatever the code path used.
+
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Locale {
+    #[default]
+    En,
+    Zh,
+}
+
+impl Locale {
+    /// Parses a language tag such as "zh", "zh-CN" or "en_US"; anything
+    /// unrecognized falls back to English.
+    pub fn parse(tag: &str) -> Self {
+        let primary = tag
+            .trim()
+            .split(['-', '_'])
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        match primary.as_str() {
+            "zh" => Locale::Zh,
+            _ => Locale::En,
+        }
+    }
+
+    /// Picks the first supported language from an `Accept-Language` header.
+    pub fn from_accept_language(header: &str) -> Option<Self> {
+        header
+            .split(',')
+            .map(|part| part.split(';').next().unwrap_or_default().trim())
+            .find_map(|tag| match tag.get(..2).map(str::to_ascii_lowercase) {
+                Some(p) if p == "zh" => Some(Locale::Zh),
+                Some(p) if p == "en" => Some(Locale::En),
+                _ => None,
+            })
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Locale::En => "en",
+            Locale::Zh => "zh",
+        }
+    }
+}
+
+/// Catalog entries. Placeholders are written as `{name}` and filled by [`render`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Msg {
+    InvalidRequest,
+    InternalError,
+    Unauthorized,
+    Forbidden,
+    InvalidCredentials,
+    EmailAlreadyRegistered,
+    UserNotFound,
+    InvalidOtp,
+    OtpNotVerified,
+    OtpAlreadyEnabled,
+    InvalidBetaCode,
+    TraderNotFound,
+    TraderStarted,
+    TraderStopped,
+    TraderRunning,
+    AiModelNotFound,
+    ExchangeNotFound,
+    OrderRejected,
+    AiCallFailed,
+    MarketDataDegraded,
+    TraderRecovered,
+    PositionReconciled,
+    MarginAlert,
+    TradeExecuted,
+    SymbolUnavailable,
+    SymbolNotListed,
+    PauseWindowNotFound,
+    PromptVersionNotFound,
+    PromptTemplateNotFound,
+    PromptTemplateExists,
+    PromptTemplateInUse,
+    VariantExists,
+    ProposalNotFound,
+    ProposalNotPending,
+    DefaultCoinsChanged,
+    DailyReport,
+    InvalidStrategyFile,
+    UntrustedStrategySigner,
+    MaintenanceMode,
+    QuotaExceeded,
+    NoAccountSnapshot,
+    DecisionRecordNotFound,
+    TooManyAttempts,
+}
+
+impl Msg {
+    fn texts(self) -> (&'static str, &'static str) {
+        match self {
+            Msg::InvalidRequest => ("Invalid request", "请求参数错误"),
+            Msg::InternalError => ("Internal server error", "服务器内部错误"),
+            Msg::Unauthorized => ("Not logged in or session expired", "未登录或登录已过期"),
+            Msg::Forbidden => ("Permission denied", "没有权限"),
+            Msg::InvalidCredentials => ("Incorrect email or password", "邮箱或密码错误"),
+            Msg::EmailAlreadyRegistered => ("Email is already registered", "邮箱已被注册"),
+            Msg::UserNotFound => ("User not found", "用户不存在"),
+            Msg::InvalidOtp => ("Invalid verification code", "验证码错误"),
+            Msg::OtpNotVerified => (
+                "Two-factor authentication has not been set up",
+                "尚未完成两步验证设置",
+            ),
+            Msg::OtpAlreadyEnabled => (
+                "Two-factor authentication is already enabled",
+                "已启用两步验证",
+            ),
+            Msg::InvalidBetaCode => (
+                "Beta code is invalid or already used",
+                "内测码无效或已被使用",
+            ),
+            Msg::TraderNotFound => ("Trader not found", "交易员不存在"),
+            Msg::TraderStarted => ("Trader {name} started", "交易员 {name} 已启动"),
+            Msg::TraderStopped => ("Trader {name} stopped", "交易员 {name} 已停止"),
+            Msg::TraderRunning => ("Stop the trader before deleting it", "请先停止交易员再删除"),
+            Msg::AiModelNotFound => ("AI model not found", "AI模型不存在"),
+            Msg::ExchangeNotFound => ("Exchange not found", "交易所不存在"),
+            Msg::OrderRejected => (
+                "Order for {symbol} was rejected: {reason}",
+                "{symbol} 下单被拒绝：{reason}",
+            ),
+            Msg::AiCallFailed => (
+                "AI call failed for trader {name}: {reason}",
+                "交易员 {name} 调用AI失败：{reason}",
+            ),
+            Msg::MarketDataDegraded => (
+                "Market data is degraded; only existing positions are being managed",
+                "行情数据降级，仅管理现有持仓",
+            ),
+            Msg::TraderRecovered => (
+                "{name} recovered at cycle #{cycle} with {positions} positions; {rearmed} re-armed, {unprotected} without a stop",
+                "交易员 {name} 已在第 {cycle} 周期恢复，持仓 {positions} 个，重设止损止盈 {rearmed} 个，{unprotected} 个没有止损",
+            ),
+            Msg::PositionReconciled => (
+                "{symbol} {side} recorded {recorded} but the exchange holds {held}; recorded {action} {quantity}",
+                "{symbol} {side} 账本记录 {recorded}，交易所实际持有 {held}，已补记 {action} {quantity}",
+            ),
+            Msg::MarginAlert => (
+                "{symbol} {side} is {distance}% from liquidation (mark {mark}, liquidation {liquidation}), margin ratio {ratio}%",
+                "{symbol} {side} 距强平 {distance}%（标记价 {mark}，强平价 {liquidation}），保证金率 {ratio}%",
+            ),
+            Msg::TradeExecuted => (
+                "{name}: {side} {quantity} {symbol} @ {price} ({action})",
+                "{name}：{side} {quantity} {symbol} @ {price}（{action}）",
+            ),
+            Msg::PauseWindowNotFound => ("Pause window not found", "暂停窗口不存在"),
+            Msg::PromptVersionNotFound => ("Prompt version not found", "提示词版本不存在"),
+            Msg::PromptTemplateNotFound => ("Prompt template not found", "提示词模板不存在"),
+            Msg::PromptTemplateExists => (
+                "A prompt template with this name already exists",
+                "同名提示词模板已存在",
+            ),
+            Msg::PromptTemplateInUse => (
+                "The prompt template is used by a trader",
+                "提示词模板正在被交易员使用",
+            ),
+            Msg::VariantExists => (
+                "The experiment already has a variant with this name",
+                "实验中已存在同名变体",
+            ),
+            Msg::ProposalNotFound => ("Trade proposal not found", "交易提案不存在"),
+            Msg::ProposalNotPending => (
+                "Trade proposal was already decided or has expired",
+                "交易提案已处理或已过期",
+            ),
+            Msg::SymbolUnavailable => (
+                "{symbol} is no longer trading ({status}); trader {name} will not open new positions on it",
+                "{symbol} 已停止交易（{status}），交易员 {name} 将不再对其开仓",
+            ),
+            Msg::SymbolNotListed => (
+                "{symbol} is not listed on the exchange",
+                "{symbol} 未在交易所上架",
+            ),
+            Msg::InvalidStrategyFile => (
+                "The strategy file is invalid or was modified after signing",
+                "策略文件无效或签名后被修改",
+            ),
+            Msg::UntrustedStrategySigner => (
+                "The strategy file is signed by an unknown key {fingerprint}; confirm the fingerprint with its author and import again with trust_signer set to it",
+                "策略文件由未知密钥 {fingerprint} 签名，请与作者核对指纹后将 trust_signer 设为该指纹再次导入",
+            ),
+            Msg::MaintenanceMode => (
+                "The system is in maintenance mode; changes are disabled",
+                "系统维护中，暂时无法修改",
+            ),
+            Msg::QuotaExceeded => ("This exceeds your account limits", "超出账户配额限制"),
+            Msg::NoAccountSnapshot => (
+                "No account snapshot yet; let the trader run one cycle first",
+                "暂无账户快照，请先让交易员运行一个周期",
+            ),
+            Msg::DecisionRecordNotFound => ("Decision record not found", "决策记录不存在"),
+            Msg::TooManyAttempts => (
+                "Too many failed attempts; try again in {seconds}s",
+                "失败次数过多，请 {seconds} 秒后再试",
+            ),
+            Msg::DefaultCoinsChanged => (
+                "Default coins updated for trader {name}: added {added}; removed {removed}",
+                "交易员 {name} 的默认币种已更新：新增 {added}；移除 {removed}",
+            ),
+            Msg::DailyReport => (
+                "{name} on {day}: {trades} trades, realized PnL {pnl} USDT, win rate {win_rate}%, best {best}, worst {worst}, AI cost ${ai_cost}",
+                "{name} {day}：成交 {trades} 笔，已实现盈亏 {pnl} USDT，胜率 {win_rate}%，最佳 {best}，最差 {worst}，AI 费用 ${ai_cost}",
+            ),
+        }
+    }
+
+    /// Raw catalog text, placeholders included.
+    pub fn text(self, locale: Locale) -> &'static str {
+        let (en, zh) = self.texts();
+        match locale {
+            Locale::En => en,
+            Locale::Zh => zh,
+        }
+    }
+}
+
+/// Returns a message that has no placeholders.
+pub fn t(locale: Locale, msg: Msg) -> &'static str {
+    msg.text(locale)
+}
+
+/// Returns a message with `{name}` placeholders replaced by `args`.
+pub fn render(locale: Locale, msg: Msg, args: &[(&str, &dyn Display)]) -> String {
+    let mut out = msg.text(locale).to_string();
+    for (name, value) in args {
+        out = out.replace(&format!("{{{}}}", name), &value.to_string());
+    }
+    out
+}
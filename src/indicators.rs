@@ -0,0 +1,428 @@
+//! Stateful, incremental technical indicators.
+//!
+//! Each indicator consumes one candle at a time in O(1), so a rolling candle buffer
+//! (e.g. fed from the websocket cache) can keep indicators current without
+//! recomputing over the whole history. The results match the batch formulas:
+//! EMA seeded with an SMA, RSI and ATR with Wilder's smoothing, Bollinger Bands
+//! with the population standard deviation, VWAP reset at each UTC day.
+
+use std::collections::VecDeque;
+
+use chrono::{DateTime, NaiveDate};
+use serde::{Deserialize, Serialize};
+
+use crate::types::Kline;
+
+/// Exponential moving average seeded with the simple average of the first `period` values.
+#[derive(Debug, Clone)]
+pub struct Ema {
+    period: usize,
+    multiplier: f64,
+    count: usize,
+    seed_sum: f64,
+    value: f64,
+}
+
+impl Ema {
+    pub fn new(period: usize) -> Self {
+        Self {
+            period,
+            multiplier: 2.0 / (period as f64 + 1.0),
+            count: 0,
+            seed_sum: 0.0,
+            value: 0.0,
+        }
+    }
+
+    pub fn update(&mut self, price: f64) {
+        self.count += 1;
+        if self.count < self.period {
+            self.seed_sum += price;
+        } else if self.count == self.period {
+            self.seed_sum += price;
+            self.value = self.seed_sum / self.period as f64;
+        } else {
+            self.value = (price - self.value) * self.multiplier + self.value;
+        }
+    }
+
+    /// Returns the current EMA once at least `period` values have been seen.
+    pub fn value(&self) -> Option<f64> {
+        (self.count >= self.period).then_some(self.value)
+    }
+}
+
+/// MACD line (EMA12 - EMA26).
+#[derive(Debug, Clone)]
+pub struct Macd {
+    fast: Ema,
+    slow: Ema,
+}
+
+impl Macd {
+    pub fn new() -> Self {
+        Self {
+            fast: Ema::new(12),
+            slow: Ema::new(26),
+        }
+    }
+
+    pub fn update(&mut self, price: f64) {
+        self.fast.update(price);
+        self.slow.update(price);
+    }
+
+    pub fn value(&self) -> Option<f64> {
+        Some(self.fast.value()? - self.slow.value()?)
+    }
+}
+
+impl Default for Macd {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Relative strength index with Wilder's smoothing.
+#[derive(Debug, Clone)]
+pub struct Rsi {
+    period: usize,
+    prev_close: Option<f64>,
+    changes: usize,
+    avg_gain: f64,
+    avg_loss: f64,
+}
+
+impl Rsi {
+    pub fn new(period: usize) -> Self {
+        Self {
+            period,
+            prev_close: None,
+            changes: 0,
+            avg_gain: 0.0,
+            avg_loss: 0.0,
+        }
+    }
+
+    pub fn update(&mut self, close: f64) {
+        let Some(prev) = self.prev_close.replace(close) else {
+            return;
+        };
+        let change = close - prev;
+        let (gain, loss) = if change > 0.0 {
+            (change, 0.0)
+        } else {
+            (0.0, -change)
+        };
+
+        self.changes += 1;
+        let period = self.period as f64;
+        if self.changes <= self.period {
+            // Accumulate the seed sums, then turn them into averages.
+            self.avg_gain += gain;
+            self.avg_loss += loss;
+            if self.changes == self.period {
+                self.avg_gain /= period;
+                self.avg_loss /= period;
+            }
+        } else {
+            self.avg_gain = (self.avg_gain * (period - 1.0) + gain) / period;
+            self.avg_loss = (self.avg_loss * (period - 1.0) + loss) / period;
+        }
+    }
+
+    /// Returns the RSI once `period` price changes (i.e. `period + 1` closes) have been seen.
+    pub fn value(&self) -> Option<f64> {
+        if self.changes < self.period {
+            return None;
+        }
+        if self.avg_loss == 0.0 {
+            return Some(100.0);
+        }
+        let rs = self.avg_gain / self.avg_loss;
+        Some(100.0 - (100.0 / (1.0 + rs)))
+    }
+}
+
+/// Average true range with Wilder's smoothing.
+#[derive(Debug, Clone)]
+pub struct Atr {
+    period: usize,
+    prev_close: Option<f64>,
+    ranges: usize,
+    value: f64,
+}
+
+impl Atr {
+    pub fn new(period: usize) -> Self {
+        Self {
+            period,
+            prev_close: None,
+            ranges: 0,
+            value: 0.0,
+        }
+    }
+
+    pub fn update(&mut self, kline: &Kline) {
+        let Some(prev_close) = self.prev_close.replace(kline.close) else {
+            return; // No TR for the first candle
+        };
+        let tr = (kline.high - kline.low)
+            .max((kline.high - prev_close).abs())
+            .max((kline.low - prev_close).abs());
+
+        self.ranges += 1;
+        let period = self.period as f64;
+        if self.ranges < self.period {
+            self.value += tr;
+        } else if self.ranges == self.period {
+            self.value = (self.value + tr) / period;
+        } else {
+            self.value = (self.value * (period - 1.0) + tr) / period;
+        }
+    }
+
+    pub fn value(&self) -> Option<f64> {
+        (self.ranges >= self.period).then_some(self.value)
+    }
+}
+
+/// Bollinger Bands: an SMA with bands `multiplier` standard deviations away.
+#[derive(Debug, Clone)]
+pub struct Bollinger {
+    period: usize,
+    multiplier: f64,
+    window: VecDeque<f64>,
+    sum: f64,
+    sum_sq: f64,
+}
+
+/// One reading of [`Bollinger`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Bands {
+    pub upper: f64,
+    pub middle: f64,
+    pub lower: f64,
+}
+
+impl Bollinger {
+    pub fn new(period: usize, multiplier: f64) -> Self {
+        Self {
+            period,
+            multiplier,
+            window: VecDeque::with_capacity(period + 1),
+            sum: 0.0,
+            sum_sq: 0.0,
+        }
+    }
+
+    pub fn update(&mut self, close: f64) {
+        self.window.push_back(close);
+        self.sum += close;
+        self.sum_sq += close * close;
+        if self.window.len() > self.period
+            && let Some(old) = self.window.pop_front()
+        {
+            self.sum -= old;
+            self.sum_sq -= old * old;
+        }
+    }
+
+    /// Returns the bands once `period` closes have been seen.
+    pub fn value(&self) -> Option<Bands> {
+        if self.window.len() < self.period {
+            return None;
+        }
+        let n = self.period as f64;
+        let middle = self.sum / n;
+        // Rolling sums can drift slightly below zero on flat prices.
+        let std_dev = (self.sum_sq / n - middle * middle).max(0.0).sqrt();
+        Some(Bands {
+            upper: middle + self.multiplier * std_dev,
+            middle,
+            lower: middle - self.multiplier * std_dev,
+        })
+    }
+}
+
+/// Stochastic oscillator: %K over `k_period` candles and %D as its
+/// `d_period` simple average.
+#[derive(Debug, Clone)]
+pub struct Stochastic {
+    k_period: usize,
+    d_period: usize,
+    ranges: VecDeque<(f64, f64)>,
+    k_values: VecDeque<f64>,
+}
+
+impl Stochastic {
+    pub fn new(k_period: usize, d_period: usize) -> Self {
+        Self {
+            k_period,
+            d_period,
+            ranges: VecDeque::with_capacity(k_period + 1),
+            k_values: VecDeque::with_capacity(d_period + 1),
+        }
+    }
+
+    pub fn update(&mut self, kline: &Kline) {
+        self.ranges.push_back((kline.high, kline.low));
+        if self.ranges.len() > self.k_period {
+            self.ranges.pop_front();
+        }
+        if self.ranges.len() < self.k_period {
+            return;
+        }
+        let high = self.ranges.iter().map(|r| r.0).fold(f64::MIN, f64::max);
+        let low = self.ranges.iter().map(|r| r.1).fold(f64::MAX, f64::min);
+        // A flat range has no position within it; call it the middle.
+        let k = if high > low {
+            (kline.close - low) / (high - low) * 100.0
+        } else {
+            50.0
+        };
+        self.k_values.push_back(k);
+        if self.k_values.len() > self.d_period {
+            self.k_values.pop_front();
+        }
+    }
+
+    /// Returns `(%K, %D)` once there are enough candles for %D.
+    pub fn value(&self) -> Option<(f64, f64)> {
+        if self.k_values.len() < self.d_period {
+            return None;
+        }
+        let k = *self.k_values.back()?;
+        let d = self.k_values.iter().sum::<f64>() / self.d_period as f64;
+        Some((k, d))
+    }
+}
+
+/// Volume-weighted average of the typical price since the start of the
+/// candle's UTC day.
+#[derive(Debug, Clone, Default)]
+pub struct Vwap {
+    session: Option<NaiveDate>,
+    price_volume: f64,
+    volume: f64,
+}
+
+impl Vwap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn update(&mut self, kline: &Kline) {
+        let session = DateTime::from_timestamp_millis(kline.open_time).map(|t| t.date_naive());
+        if session != self.session {
+            self.session = session;
+            self.price_volume = 0.0;
+            self.volume = 0.0;
+        }
+        let typical = (kline.high + kline.low + kline.close) / 3.0;
+        self.price_volume += typical * kline.volume;
+        self.volume += kline.volume;
+    }
+
+    /// Returns the VWAP once the session has traded any volume.
+    pub fn value(&self) -> Option<f64> {
+        (self.volume > 0.0).then(|| self.price_volume / self.volume)
+    }
+}
+
+/// The indicator set used for prompt context, updated one candle at a time.
+#[derive(Debug, Clone)]
+pub struct IndicatorSet {
+    pub ema20: Ema,
+    pub ema50: Ema,
+    pub macd: Macd,
+    pub rsi7: Rsi,
+    pub rsi14: Rsi,
+    pub atr3: Atr,
+    pub atr14: Atr,
+    pub bb20: Bollinger,
+    pub stoch14: Stochastic,
+    pub vwap: Vwap,
+}
+
+impl IndicatorSet {
+    /// Names of the indicators this set computes, as referenced by exported strategies.
+    pub const NAMES: &'static [&'static str] = &[
+        "ema20", "ema50", "macd", "rsi7", "rsi14", "atr3", "atr14", "bb20", "stoch14", "vwap",
+    ];
+
+    pub fn new() -> Self {
+        Self {
+            ema20: Ema::new(20),
+            ema50: Ema::new(50),
+            macd: Macd::new(),
+            rsi7: Rsi::new(7),
+            rsi14: Rsi::new(14),
+            atr3: Atr::new(3),
+            atr14: Atr::new(14),
+            bb20: Bollinger::new(20, 2.0),
+            stoch14: Stochastic::new(14, 3),
+            vwap: Vwap::new(),
+        }
+    }
+
+    pub fn update(&mut self, kline: &Kline) {
+        self.ema20.update(kline.close);
+        self.ema50.update(kline.close);
+        self.macd.update(kline.close);
+        self.rsi7.update(kline.close);
+        self.rsi14.update(kline.close);
+        self.atr3.update(kline);
+        self.atr14.update(kline);
+        self.bb20.update(kline.close);
+        self.stoch14.update(kline);
+        self.vwap.update(kline);
+    }
+
+    /// True once every indicator in the set has a value. Before that some of
+    /// them are still unseeded and read as zero. VWAP is left out: it resets
+    /// every day and has no value on candles without volume.
+    pub fn is_ready(&self) -> bool {
+        self.ema20.value().is_some()
+            && self.ema50.value().is_some()
+            && self.macd.value().is_some()
+            && self.rsi7.value().is_some()
+            && self.rsi14.value().is_some()
+            && self.atr3.value().is_some()
+            && self.atr14.value().is_some()
+            && self.bb20.value().is_some()
+            && self.stoch14.value().is_some()
+    }
+}
+
+impl Default for IndicatorSet {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// --- One-shot helpers over a full candle slice ---
+
+pub fn ema(klines: &[Kline], period: usize) -> f64 {
+    let mut ema = Ema::new(period);
+    klines.iter().for_each(|k| ema.update(k.close));
+    ema.value().unwrap_or(0.0)
+}
+
+pub fn macd(klines: &[Kline]) -> f64 {
+    let mut macd = Macd::new();
+    klines.iter().for_each(|k| macd.update(k.close));
+    macd.value().unwrap_or(0.0)
+}
+
+pub fn rsi(klines: &[Kline], period: usize) -> f64 {
+    let mut rsi = Rsi::new(period);
+    klines.iter().for_each(|k| rsi.update(k.close));
+    rsi.value().unwrap_or(0.0)
+}
+
+pub fn atr(klines: &[Kline], period: usize) -> f64 {
+    let mut atr = Atr::new(period);
+    klines.iter().for_each(|k| atr.update(k));
+    atr.value().unwrap_or(0.0)
+}
@@ -0,0 +1,75 @@
+pub mod accuracy;
+pub mod ai;
+pub mod ai_cache;
+pub mod api_client;
+pub mod approval;
+pub mod aster;
+pub mod audit;
+pub mod audit_log;
+pub mod auth;
+pub mod bundle;
+pub mod cache;
+pub mod calendar;
+pub mod config;
+pub mod cooldown;
+pub mod cost_model;
+pub mod crypto;
+pub mod currency;
+pub mod daily_report;
+pub mod data;
+pub mod database;
+pub mod decision;
+pub mod error_sink;
+pub mod exchange;
+pub mod executor;
+pub mod experiment;
+pub mod export;
+pub mod fallback;
+pub mod hot_reload;
+pub mod hyperliquid;
+pub mod i18n;
+pub mod indicators;
+pub mod logger;
+pub mod login_guard;
+pub mod loss_limits;
+pub mod maintenance;
+pub mod margin_governor;
+pub mod margin_monitor;
+pub mod money;
+pub mod notify;
+pub mod otp;
+pub mod overview;
+pub mod pause;
+pub mod performance;
+pub mod profiler;
+pub mod prompt;
+pub mod prompt_history;
+pub mod prompt_template;
+pub mod quota;
+pub mod rate_limit;
+pub mod recovery;
+pub mod replay;
+pub mod retry_queue;
+pub mod risk_override;
+pub mod runner;
+pub mod scheduler;
+pub mod secrets;
+pub mod server;
+pub mod sim;
+pub mod sizing;
+pub mod strategy;
+pub mod stream;
+pub mod stress;
+pub mod symbol_meta;
+pub mod symbol_watch;
+pub mod symbols;
+pub mod telemetry;
+#[cfg(any(test, feature = "testkit"))]
+pub mod testkit;
+pub mod timezone;
+pub mod tournament;
+pub mod types;
+pub mod universe;
+#[cfg(feature = "wasm-filters")]
+pub mod wasm_filter;
+pub mod watch_only;
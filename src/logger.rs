@@ -1,18 +1,34 @@
 use std::error::Error;
 
+use std::io::{Read, Write};
 use std::str::FromStr;
 use std::time::{Duration, SystemTime};
-use std::{fs, path::Path};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
 
 use anyhow::Result;
-use chrono::{DateTime, Utc};
-use glob::glob;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
 use serde::{Deserialize, Serialize};
 use serde_json::{Value, json};
 use std::collections::HashMap;
 
+use crate::crypto;
+use crate::database::Database;
+use crate::decision::{self, Decision};
+use crate::money::{self, Decimal};
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct DecisionRecord {
+    // 记录格式版本，读取旧版本记录时先经 upgrade_record 升级
+    schema_version: u32,
+    // 记录ID（即日志文件名，不含扩展名），供平仓结果回溯到开/平仓决策
+    #[serde(default)]
+    id: String,
     timestamp: DateTime<Utc>,
     cycle_number: i32,
     system_prompt: String,
@@ -26,14 +42,17 @@ pub struct DecisionRecord {
     execution_log: Vec<String>,
     success: bool,
     error_message: String,
+    // 交易员参与提示词A/B实验时的变体名称
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    variant: String,
 }
 
 // AccountSnapshot 账户状态快照
 #[derive(Debug, Serialize, Deserialize)]
 struct AccountSnapshot {
-    total_balance: f64,
-    available_balance: f64,
-    total_unrealized_profit: f64,
+    total_balance: Decimal,
+    available_balance: Decimal,
+    total_unrealized_profit: Decimal,
     position_count: i32,
     margin_used_pct: f64,
 }
@@ -43,12 +62,12 @@ struct AccountSnapshot {
 struct PositionSnapshot {
     symbol: String,
     side: String,
-    position_amt: f64,
-    entry_price: f64,
-    mark_price: f64,
-    unrealized_profit: f64,
+    position_amt: Decimal,
+    entry_price: Decimal,
+    mark_price: Decimal,
+    unrealized_profit: Decimal,
     leverage: f64,
-    liquidation_price: f64,
+    liquidation_price: Decimal,
 }
 
 // DecisionAction 决策动作
@@ -56,16 +75,29 @@ struct PositionSnapshot {
 struct DecisionAction {
     action: Action,
     symbol: String,
-    quantity: f64,
+    quantity: Decimal,
     leverage: i32,
-    price: f64,
+    price: Decimal,
     order_id: i64,
     timestamp: DateTime<Utc>,
     success: bool,
     error: String,
+    // 平仓动作：对应开仓所在的记录ID
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    opened_by: String,
+    // 开仓动作：之后平掉该仓位的记录ID
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    closed_by: String,
+    // 组合交易（配对交易）各条腿共用的组ID
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    group_id: String,
+    // 人工审批模式下批准该交易的用户
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    approved_by: String,
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[allow(clippy::upper_case_acronyms)]
 enum Action {
     #[serde(rename = "open_short")]
     OPENSHORT,
@@ -77,10 +109,326 @@ enum Action {
     CLOSELONG,
 }
 
+impl Action {
+    fn to_decision_action(&self) -> decision::Action {
+        match self {
+            Action::OPENSHORT => decision::Action::OpenShort,
+            Action::OPENLONG => decision::Action::OpenLong,
+            Action::CLOSESHORT => decision::Action::CloseShort,
+            Action::CLOSELONG => decision::Action::CloseLong,
+        }
+    }
+
+    fn side(&self) -> &'static str {
+        match self {
+            Action::OPENLONG | Action::CLOSELONG => "long",
+            Action::OPENSHORT | Action::CLOSESHORT => "short",
+        }
+    }
+
+    fn is_open(&self) -> bool {
+        matches!(self, Action::OPENLONG | Action::OPENSHORT)
+    }
+}
+
+/// Version of the decision record format written by this build.
+///
+/// 1. The original format.
+/// 2. Adds the record `id` and the `opened_by`/`closed_by` links between
+///    opening and closing actions.
+pub const DECISION_SCHEMA_VERSION: u32 = 2;
+
+// 各版本的升级函数：UPGRADES[i] 把第 i+1 版记录升级为第 i+2 版
+const UPGRADES: [fn(&mut serde_json::Map<String, Value>); DECISION_SCHEMA_VERSION as usize - 1] =
+    [upgrade_v1_to_v2];
+
+// 把任意已知版本的记录JSON逐级升级到当前版本；未标注版本的记录视为第1版
+fn upgrade_record(mut value: Value) -> Result<Value> {
+    let record = value
+        .as_object_mut()
+        .ok_or_else(|| anyhow::anyhow!("决策记录不是JSON对象"))?;
+    let version = record
+        .get("schema_version")
+        .and_then(Value::as_u64)
+        .unwrap_or(1) as u32;
+    if version == 0 || version > DECISION_SCHEMA_VERSION {
+        anyhow::bail!(
+            "决策记录格式版本 {} 不受支持（当前版本 {}）",
+            version,
+            DECISION_SCHEMA_VERSION
+        );
+    }
+    for upgrade in &UPGRADES[version as usize - 1..] {
+        upgrade(record);
+    }
+    record.insert("schema_version".to_string(), json!(DECISION_SCHEMA_VERSION));
+    Ok(value)
+}
+
+// 第1版 → 第2版：补齐记录ID（读取文件时由文件名填写）；无法识别的动作（如早期的
+// partial_close / update_stop_loss）移入执行日志，避免整条记录无法解析
+fn upgrade_v1_to_v2(record: &mut serde_json::Map<String, Value>) {
+    record.entry("id").or_insert_with(|| json!(""));
+    record.entry("system_prompt").or_insert_with(|| json!(""));
+
+    let known = ["open_long", "open_short", "close_long", "close_short"];
+    let mut dropped = Vec::new();
+    if let Some(Value::Array(actions)) = record.get_mut("decisions") {
+        actions.retain(|a| {
+            let action = a.get("action").and_then(Value::as_str).unwrap_or_default();
+            if known.contains(&action) {
+                return true;
+            }
+            dropped.push(format!(
+                "legacy action {} {} (not replayable)",
+                action,
+                a.get("symbol").and_then(Value::as_str).unwrap_or_default()
+            ));
+            false
+        });
+    }
+    if !dropped.is_empty()
+        && let Some(Value::Array(log)) = record.get_mut("execution_log")
+    {
+        log.extend(dropped.into_iter().map(Value::String));
+    }
+}
+
+// 解析一条明文记录JSON（任意已知版本）
+fn parse_record(json: &[u8]) -> Result<DecisionRecord> {
+    let value = upgrade_record(serde_json::from_slice(json)?)?;
+    Ok(serde_json::from_value(value)?)
+}
+
+// 回溯平仓对应开仓记录时最多查找的周期数
+const ATTRIBUTION_LOOKBACK_CYCLES: usize = 500;
+
+// 日志目录中的记录索引，每行一条，按写入顺序
+const INDEX_FILE: &str = "index.jsonl";
+const RECORD_EXT: &str = ".json";
+const COMPRESSED_EXT: &str = ".json.gz";
+
+/// When decision record files are compressed and deleted.
+///
+/// The newest `keep_recent` records always stay plain JSON, since the cycle
+/// reads them for attribution and performance. Older ones are gzipped once
+/// they are older than `compress_after`, or, oldest first, while the plain
+/// files together exceed `max_active_bytes`. Compressed records older than
+/// `retention` are deleted. A zero value turns the respective rule off.
+/// Encrypted records are compressed too but shrink little.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct RotationParams {
+    #[serde(with = "humantime_serde")]
+    pub compress_after: Duration,
+    pub max_active_bytes: u64,
+    pub keep_recent: usize,
+    #[serde(with = "humantime_serde")]
+    pub retention: Duration,
+}
+
+impl Default for RotationParams {
+    fn default() -> Self {
+        Self {
+            compress_after: Duration::from_secs(7 * 86400),
+            max_active_bytes: 64 * 1024 * 1024,
+            keep_recent: ATTRIBUTION_LOOKBACK_CYCLES,
+            retention: Duration::ZERO,
+        }
+    }
+}
+
+// 索引中的一条记录；bytes 为写入时的文件大小，回写关联后可能略有出入
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct IndexEntry {
+    id: String,
+    at: DateTime<Utc>,
+    bytes: u64,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    compressed: bool,
+    // 记录中尚未关联平仓的开仓（币种:方向）；扫描重建或旧版本的索引没有此项
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    open: Option<Vec<String>>,
+}
+
+// 记录中成功且尚未关联平仓的开仓，以 币种:方向 表示
+fn unclosed_opens(record: &DecisionRecord) -> Vec<String> {
+    record
+        .decisions
+        .iter()
+        .filter(|a| a.success && a.action.is_open() && a.closed_by.is_empty())
+        .map(|a| position_key(&a.symbol, a.action.side()))
+        .collect()
+}
+
+fn position_key(symbol: &str, side: &str) -> String {
+    format!("{}:{}", symbol, side)
+}
+
+// 文件名对应的记录ID与是否已压缩；非记录文件返回 None
+fn record_file_id(name: &str) -> Option<(&str, bool)> {
+    if name == INDEX_FILE {
+        return None;
+    }
+    if let Some(id) = name.strip_suffix(COMPRESSED_EXT) {
+        return Some((id, true));
+    }
+    name.strip_suffix(RECORD_EXT).map(|id| (id, false))
+}
+
+// 从 decision_YYYYMMDD_HHMMSS_cycleN 形式的ID中取出记录时间
+fn time_from_id(id: &str) -> Option<DateTime<Utc>> {
+    let stamp = id.strip_prefix("decision_")?.get(..15)?;
+    NaiveDateTime::parse_from_str(stamp, "%Y%m%d_%H%M%S")
+        .ok()
+        .map(|t| t.and_utc())
+}
+
+fn gzip(data: &[u8]) -> std::io::Result<Vec<u8>> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(data)?;
+    encoder.finish()
+}
+
+fn gunzip(data: &[u8]) -> std::io::Result<Vec<u8>> {
+    let mut out = Vec::new();
+    GzDecoder::new(data).read_to_end(&mut out)?;
+    Ok(out)
+}
+
+/// Header of an encrypted decision record.
+pub const ENCRYPTED_RECORD_MAGIC: &[u8] = b"AITLOG1\0";
+// 由主密钥材料派生主密钥时使用的固定盐
+const RECORD_KEY_SALT: &[u8] = b"aitrading-decision-logs";
+
+/// Master key for decision logs at rest. Each user's records are sealed with
+/// their own key derived from it, so one user's key opens no one else's logs.
+#[derive(Clone)]
+pub struct RecordCipher {
+    master: [u8; 32],
+}
+
+impl RecordCipher {
+    pub fn from_secret(secret: &[u8]) -> Self {
+        Self {
+            master: crypto::derive_key(secret, RECORD_KEY_SALT),
+        }
+    }
+
+    pub fn user_key(&self, user_id: &str) -> RecordKey {
+        RecordKey(crypto::derive_subkey(
+            &self.master,
+            format!("decision-log:{}", user_id).as_bytes(),
+        ))
+    }
+}
+
+impl std::fmt::Debug for RecordCipher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("RecordCipher(***)")
+    }
+}
+
+/// One user's decision log key.
+#[derive(Clone)]
+pub struct RecordKey([u8; 32]);
+
+impl std::fmt::Debug for RecordKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("RecordKey(***)")
+    }
+}
+
+// 序列化一条记录；有密钥时加密
+fn encode_record(record: &DecisionRecord, key: Option<&RecordKey>) -> Result<Vec<u8>> {
+    let json = serde_json::to_vec_pretty(record)?;
+    let Some(key) = key else {
+        return Ok(json);
+    };
+    let sealed = crypto::seal(&key.0, &json)?;
+    let mut out = Vec::with_capacity(ENCRYPTED_RECORD_MAGIC.len() + sealed.len());
+    out.extend_from_slice(ENCRYPTED_RECORD_MAGIC);
+    out.extend_from_slice(&sealed);
+    Ok(out)
+}
+
+// 解析一条记录；明文与密文均可读取，密文需要密钥
+fn decode_record(data: &[u8], key: Option<&RecordKey>) -> Result<DecisionRecord> {
+    match data.strip_prefix(ENCRYPTED_RECORD_MAGIC) {
+        Some(sealed) => {
+            let key = key.ok_or_else(|| anyhow::anyhow!("记录已加密，但未提供密钥"))?;
+            let json = crypto::open(&key.0, sealed)?;
+            parse_record(&json)
+        }
+        None => parse_record(data),
+    }
+}
+
+// 把决策记录写入数据库；有密钥时加密
+pub async fn store_record(
+    db: &Database,
+    user_id: &str,
+    trader_id: &str,
+    record: &DecisionRecord,
+    key: Option<&RecordKey>,
+) -> Result<()> {
+    let payload = encode_record(record, key)?;
+    db.save_decision_record(
+        user_id,
+        trader_id,
+        &record.id,
+        record.cycle_number,
+        record.timestamp,
+        &payload,
+        key.is_some(),
+    )
+    .await
+}
+
+// 从数据库读取最近N条决策记录，透明解密；无法解密的记录跳过
+pub async fn load_records(
+    db: &Database,
+    user_id: &str,
+    trader_id: &str,
+    n: usize,
+    key: Option<&RecordKey>,
+) -> Result<Vec<DecisionRecord>> {
+    let payloads = db
+        .get_decision_records(user_id, trader_id, n as i64)
+        .await?;
+    Ok(payloads
+        .iter()
+        .filter_map(|data| match decode_record(data, key) {
+            Ok(record) => Some(record),
+            Err(e) => {
+                tracing::warn!("⚠ 无法读取交易员 {} 的决策记录: {:#}", trader_id, e);
+                None
+            }
+        })
+        .collect())
+}
+
+// 按ID从数据库读取一条决策记录，透明解密
+pub async fn load_record(
+    db: &Database,
+    user_id: &str,
+    trader_id: &str,
+    id: &str,
+    key: Option<&RecordKey>,
+) -> Result<Option<DecisionRecord>> {
+    db.get_decision_record(user_id, trader_id, id)
+        .await?
+        .map(|data| decode_record(&data, key))
+        .transpose()
+}
+
 #[derive(Debug)]
-struct DecisionLogger {
+pub struct DecisionLogger {
     log_dir: String,
     cycle_number: i32,
+    // 设置后，记录按用户密钥加密落盘
+    key: Option<RecordKey>,
+    rotation: RotationParams,
 }
 
 impl DecisionLogger {
@@ -92,15 +440,33 @@ impl DecisionLogger {
         };
 
         if let Err(e) = fs::create_dir_all(target_dir) {
-            log::error!("⚠ 创建日志目录失败: {}", e);
+            tracing::error!("⚠ 创建日志目录失败: {}", e);
         }
 
         DecisionLogger {
             log_dir: target_dir.to_string(),
             cycle_number: 0_i32,
+            key: None,
+            rotation: RotationParams::default(),
         }
     }
 
+    // 设置旧记录的压缩与删除规则
+    pub fn with_rotation(mut self, rotation: RotationParams) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    // 启用落盘加密；已有的明文记录仍可读取
+    pub fn with_key(mut self, key: RecordKey) -> Self {
+        self.key = Some(key);
+        self
+    }
+
+    pub fn key(&self) -> Option<&RecordKey> {
+        self.key.as_ref()
+    }
+
     pub fn log_decision(&mut self, record: &mut DecisionRecord) -> Result<()> {
         self.cycle_number += 1;
         record.cycle_number = self.cycle_number;
@@ -108,75 +474,226 @@ impl DecisionLogger {
 
         // 生成文件名：decision_YYYYMMDD_HHMMSS_cycleN.json
         let time_str = record.timestamp.format("%Y%m%d_%H%M%S").to_string();
-        let file_name = format!("decision_{}_cycle{}.json", time_str, record.cycle_number);
-        let file_path = Path::new(&self.log_dir).join(&file_name);
+        record.id = format!("decision_{}_cycle{}", time_str, record.cycle_number);
+        let file_name = format!("{}.json", record.id);
+        let file_path = self.record_path(&record.id);
 
-        // 序列化为JSON（带缩进，方便阅读）
-        let data = serde_json::to_string_pretty(record)?;
+        // 平仓动作与其开仓记录双向关联
+        self.link_closes(record);
 
-        // 写入文件
-        fs::write(&file_path, data)?;
+        // 序列化为JSON（带缩进，方便阅读），启用加密时为密文
+        let data = encode_record(record, self.key.as_ref())?;
 
-        log::info!("📝 决策记录已保存: {}", file_name);
+        // 写入文件
+        fs::write(&file_path, &data)?;
+        self.append_index(IndexEntry {
+            id: record.id.clone(),
+            at: record.timestamp,
+            bytes: data.len() as u64,
+            compressed: false,
+            open: Some(unclosed_opens(record)),
+        })?;
+
+        tracing::info!("📝 决策记录已保存: {}", file_name);
+        if let Err(e) = self.rotate() {
+            tracing::warn!("⚠ 压缩旧决策记录失败: {}", e);
+        }
         Ok(())
     }
 
-    pub fn get_latest_records(&self, n: usize) -> Result<Vec<DecisionRecord>, Box<dyn Error>> {
-        let read_dir =
-            fs::read_dir(&self.log_dir).map_err(|e| format!("读取日志目录失败: {}", e))?;
-        let mut entries: Vec<_> = read_dir
-            .filter_map(|file| file.ok())
-            .filter(|entry| match entry.file_type() {
-                Ok(ft) => ft.is_file(),
-                Err(_) => false,
+    fn index_path(&self) -> PathBuf {
+        Path::new(&self.log_dir).join(INDEX_FILE)
+    }
+
+    // 读取索引；没有索引（旧版本写入的目录）时返回 None
+    fn read_index(&self) -> Option<Vec<IndexEntry>> {
+        let text = fs::read_to_string(self.index_path()).ok()?;
+        Some(
+            text.lines()
+                .filter(|line| !line.trim().is_empty())
+                .filter_map(|line| match serde_json::from_str(line) {
+                    Ok(entry) => Some(entry),
+                    Err(e) => {
+                        tracing::warn!("⚠ 跳过无法解析的索引行: {}", e);
+                        None
+                    }
+                })
+                .collect(),
+        )
+    }
+
+    // 扫描目录重建索引，按记录ID（即时间与周期）排序
+    fn scan(&self) -> Vec<IndexEntry> {
+        let Ok(read_dir) = fs::read_dir(&self.log_dir) else {
+            return Vec::new();
+        };
+        let mut entries: Vec<IndexEntry> = read_dir
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                let metadata = entry.metadata().ok().filter(|m| m.is_file())?;
+                let name = entry.file_name().to_string_lossy().into_owned();
+                let (id, compressed) = record_file_id(&name)?;
+                let at = time_from_id(id)
+                    .or_else(|| metadata.modified().ok().map(DateTime::<Utc>::from))
+                    .unwrap_or_default();
+                Some(IndexEntry {
+                    id: id.to_string(),
+                    at,
+                    bytes: metadata.len(),
+                    compressed,
+                    open: None,
+                })
             })
             .collect();
-        entries.sort_by_key(|entry| entry.file_name());
+        entries.sort_by(|a, b| a.id.cmp(&b.id));
+        entries
+    }
 
-        let start_index = entries.len().saturating_sub(n);
-        let target_entry = &entries[start_index..];
+    // 按写入顺序排列的全部记录；没有索引时扫描目录
+    fn entries(&self) -> Vec<IndexEntry> {
+        self.read_index().unwrap_or_else(|| self.scan())
+    }
 
-        let mut records = Vec::new();
-        for entry in target_entry {
-            let path = entry.path();
-            let content = match fs::read_to_string(&path) {
-                Ok(c) => c,
-                Err(_) => continue,
-            };
+    // 先写临时文件再改名，避免读到写了一半的索引
+    fn write_index(&self, entries: &[IndexEntry]) -> Result<()> {
+        let mut text = String::new();
+        for entry in entries {
+            text.push_str(&serde_json::to_string(entry)?);
+            text.push('\n');
+        }
+        let tmp = self.index_path().with_extension("jsonl.tmp");
+        fs::write(&tmp, text)?;
+        fs::rename(&tmp, self.index_path())?;
+        Ok(())
+    }
 
-            let record: DecisionRecord = match serde_json::from_str(&content) {
-                Ok(r) => r,
-                Err(_) => continue,
+    // 追加一条索引；还没有索引时先扫描目录建立（已包含刚写入的记录）
+    fn append_index(&self, entry: IndexEntry) -> Result<()> {
+        if !self.index_path().exists() {
+            return self.write_index(&self.scan());
+        }
+        let mut file = fs::OpenOptions::new()
+            .append(true)
+            .open(self.index_path())?;
+        writeln!(file, "{}", serde_json::to_string(&entry)?)?;
+        Ok(())
+    }
+
+    fn compressed_path(&self, id: &str) -> PathBuf {
+        Path::new(&self.log_dir).join(format!("{}{}", id, COMPRESSED_EXT))
+    }
+
+    /// Compresses and deletes old records according to the rotation
+    /// settings. Runs after every logged decision.
+    pub fn rotate(&self) -> Result<()> {
+        let params = self.rotation;
+        let mut entries = self.entries();
+        let now = Utc::now();
+        let mut changed = false;
+
+        let compress_before = (!params.compress_after.is_zero())
+            .then(|| now - chrono::Duration::from_std(params.compress_after).unwrap_or_default());
+        let mut active_bytes: u64 = entries
+            .iter()
+            .filter(|e| !e.compressed)
+            .map(|e| e.bytes)
+            .sum();
+        let eligible = entries.len().saturating_sub(params.keep_recent);
+        let mut compressed = 0;
+        for entry in entries[..eligible].iter_mut().filter(|e| !e.compressed) {
+            let too_old = compress_before.is_some_and(|before| entry.at < before);
+            let too_big = params.max_active_bytes > 0 && active_bytes > params.max_active_bytes;
+            if !too_old && !too_big {
+                continue;
+            }
+            let path = self.record_path(&entry.id);
+            let packed = fs::read(&path).and_then(|data| {
+                let packed = gzip(&data)?;
+                fs::write(self.compressed_path(&entry.id), &packed)?;
+                fs::remove_file(&path)?;
+                Ok(packed)
+            });
+            let packed = match packed {
+                Ok(packed) => packed,
+                Err(e) => {
+                    tracing::warn!("⚠ 压缩决策记录 {} 失败: {}", entry.id, e);
+                    continue;
+                }
             };
+            active_bytes = active_bytes.saturating_sub(entry.bytes);
+            entry.bytes = packed.len() as u64;
+            entry.compressed = true;
+            compressed += 1;
+            changed = true;
+        }
 
-            records.push(record);
+        let mut removed = 0;
+        if !params.retention.is_zero() {
+            let before = now - chrono::Duration::from_std(params.retention).unwrap_or_default();
+            entries.retain(|entry| {
+                if !entry.compressed || entry.at >= before {
+                    return true;
+                }
+                match fs::remove_file(self.compressed_path(&entry.id)) {
+                    Ok(()) => {
+                        removed += 1;
+                        false
+                    }
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => false,
+                    Err(e) => {
+                        tracing::warn!("⚠ 删除过期记录 {} 失败: {}", entry.id, e);
+                        true
+                    }
+                }
+            });
+            changed |= removed > 0;
         }
 
-        Ok(records)
+        if changed {
+            self.write_index(&entries)?;
+        }
+        if compressed > 0 || removed > 0 {
+            tracing::info!(
+                "🗜️ 决策记录已压缩 {} 条，删除过期 {} 条",
+                compressed,
+                removed
+            );
+        }
+        Ok(())
     }
 
-    // 获取指定日期的所有记录
-    pub fn get_record_by_date(
-        &self,
-        date: DateTime<Utc>,
-    ) -> Result<Vec<DecisionRecord>, Box<dyn Error>> {
-        let date_str = date.format("%Y%m%d_%H%M%S").to_string();
-        let pattern = format!("{}/decision_{}_*.json", &self.log_dir, date_str);
+    pub fn get_latest_records(&self, n: usize) -> Result<Vec<DecisionRecord>, Box<dyn Error>> {
+        if !Path::new(&self.log_dir).is_dir() {
+            return Err(format!("读取日志目录失败: {} 不存在", self.log_dir).into());
+        }
+        let entries = self.entries();
+        let start_index = entries.len().saturating_sub(n);
 
         let mut records = Vec::new();
-        for entry in glob(&pattern).map_err(|e| format!("Glob pattern error: {}", e))? {
-            if let Ok(path) = entry {
-                if let Ok(content) = fs::read_to_string(path) {
-                    if let Ok(record) = serde_json::from_str(&content) {
-                        records.push(record);
-                    }
-                }
+        for entry in &entries[start_index..] {
+            if let Some(record) = self.get_record(&entry.id) {
+                records.push(record);
             }
         }
 
         Ok(records)
     }
 
+    // 获取指定日期的所有记录
+    pub fn get_record_by_date(
+        &self,
+        date: DateTime<Utc>,
+    ) -> Result<Vec<DecisionRecord>, Box<dyn Error>> {
+        let prefix = format!("decision_{}_", date.format("%Y%m%d_%H%M%S"));
+
+        Ok(self
+            .entries()
+            .iter()
+            .filter(|entry| entry.id.starts_with(&prefix))
+            .filter_map(|entry| self.get_record(&entry.id))
+            .collect())
+    }
+
     // 清理N天前的旧记录
     pub fn clean_old_records(&self, days: u64) -> Result<(), Box<dyn Error>> {
         let cutoff_time = SystemTime::now()
@@ -195,7 +712,7 @@ impl DecisionLogger {
             };
 
             let path = entry.path();
-            if path.is_dir() {
+            if path.is_dir() || entry.file_name() == INDEX_FILE {
                 continue;
             }
 
@@ -216,7 +733,7 @@ impl DecisionLogger {
                         .map(|s| s.to_string_lossy())
                         .unwrap_or_else(|| "unknow".into());
 
-                    log::error!("⚠ 删除旧记录失败 {}: {}\n", file_name, e);
+                    tracing::error!("⚠ 删除旧记录失败 {}: {}\n", file_name, e);
                     continue;
                 }
                 removed_count += 1;
@@ -224,38 +741,288 @@ impl DecisionLogger {
         }
 
         if removed_count > 0 {
-            log::info!("🗑️ 已清理 {} 条旧记录（{}天前）", removed_count, days);
+            if self.index_path().exists() {
+                self.write_index(&self.scan())?;
+            }
+            tracing::info!("🗑️ 已清理 {} 条旧记录（{}天前）", removed_count, days);
         }
 
         Ok(())
     }
 
-    // 获取统计信息
-    pub fn get_statistics(&self) -> Result<Statistics, Box<dyn Error>> {
-        let cur_dir =
-            fs::read_dir(&self.log_dir).map_err(|e| format!("读取日志目录失败: {}", e))?;
+    // 从已有日志恢复：周期编号接着上次继续，而不是从0开始
+    pub fn resume(log_dir: &str) -> Self {
+        Self::resume_with_key(log_dir, None)
+    }
 
-        let mut stats = Statistics::default();
+    // 同 resume，日志已加密时需要提供密钥
+    pub fn resume_with_key(log_dir: &str, key: Option<RecordKey>) -> Self {
+        let mut logger = Self::new(log_dir);
+        logger.key = key;
+        logger.cycle_number = logger
+            .get_latest_records(1)
+            .ok()
+            .and_then(|records| records.last().map(|r| r.cycle_number))
+            .unwrap_or(0);
+        logger
+    }
 
-        for entry_res in cur_dir {
-            let entry = match entry_res {
-                Ok(e) => e,
-                Err(_) => continue,
+    pub fn cycle_number(&self) -> i32 {
+        self.cycle_number
+    }
+
+    // 最近N个周期里成功执行、且之后未被平仓的开仓决策（按币种），用于恢复本地止盈止损
+    pub fn last_open_decisions(&self, lookback_cycles: usize) -> HashMap<String, Decision> {
+        let records = self.get_latest_records(lookback_cycles).unwrap_or_default();
+
+        let mut open: HashMap<String, Decision> = HashMap::new();
+        for record in &records {
+            let proposed: Vec<Decision> =
+                serde_json::from_str(&record.decision_json).unwrap_or_default();
+
+            for action in record.decisions.iter().filter(|a| a.success) {
+                match action.action {
+                    Action::OPENLONG | Action::OPENSHORT => {
+                        let wanted = action.action.to_decision_action();
+                        if let Some(d) = proposed
+                            .iter()
+                            .find(|d| d.symbol == action.symbol && d.action == wanted)
+                        {
+                            open.insert(action.symbol.clone(), d.clone());
+                        }
+                    }
+                    Action::CLOSELONG | Action::CLOSESHORT => {
+                        open.remove(&action.symbol);
+                    }
+                }
+            }
+        }
+
+        open
+    }
+
+    fn record_path(&self, id: &str) -> PathBuf {
+        Path::new(&self.log_dir).join(format!("{}.json", id))
+    }
+
+    // 读取并（必要时）解压、解密一个记录文件；目录中的非记录文件忽略
+    fn read_record(&self, path: &Path) -> Option<DecisionRecord> {
+        let name = path.file_name()?.to_string_lossy().into_owned();
+        let (id, compressed) = record_file_id(&name)?;
+        let mut data = fs::read(path).ok()?;
+        if compressed {
+            data = match gunzip(&data) {
+                Ok(data) => data,
+                Err(e) => {
+                    tracing::warn!("⚠ 无法解压决策记录 {}: {}", path.display(), e);
+                    return None;
+                }
             };
+        }
+        match decode_record(&data, self.key.as_ref()) {
+            Ok(mut record) => {
+                // 第1版记录没有ID，ID即文件名
+                if record.id.is_empty() {
+                    record.id = id.to_string();
+                }
+                Some(record)
+            }
+            Err(e) => {
+                if data.starts_with(ENCRYPTED_RECORD_MAGIC) || data.starts_with(b"{") {
+                    tracing::warn!("⚠ 无法读取决策记录 {}: {:#}", path.display(), e);
+                }
+                None
+            }
+        }
+    }
 
-            let path = entry.path();
-            if path.is_dir() {
+    // 按ID读取单条决策记录，已压缩的也可读取
+    pub fn get_record(&self, id: &str) -> Option<DecisionRecord> {
+        let path = self.record_path(id);
+        if path.exists() {
+            self.read_record(&path)
+        } else {
+            self.read_record(&self.compressed_path(id))
+        }
+    }
+
+    // 回写一条已有记录，保持其压缩状态
+    fn rewrite_record(&self, record: &DecisionRecord) -> Result<()> {
+        let data = encode_record(record, self.key.as_ref())?;
+        let path = self.record_path(&record.id);
+        if path.exists() || !self.compressed_path(&record.id).exists() {
+            fs::write(path, data)?;
+        } else {
+            fs::write(self.compressed_path(&record.id), gzip(&data)?)?;
+        }
+        Ok(())
+    }
+
+    // 为本次记录中成功的平仓动作找到开仓记录，写入 opened_by，并回写开仓记录的 closed_by。
+    // 按索引中记下的未平仓开仓查找，只读取被关联的那条记录
+    fn link_closes(&self, record: &mut DecisionRecord) {
+        let closes: Vec<usize> = record
+            .decisions
+            .iter()
+            .enumerate()
+            .filter(|(_, a)| a.success && !a.action.is_open())
+            .map(|(i, _)| i)
+            .collect();
+        if closes.is_empty() {
+            return;
+        }
+
+        let mut entries = self.entries();
+        let start = entries.len().saturating_sub(ATTRIBUTION_LOOKBACK_CYCLES);
+        let mut index_changed = false;
+
+        for i in closes {
+            let (symbol, side) = {
+                let close = &record.decisions[i];
+                (close.symbol.clone(), close.action.side())
+            };
+            let key = position_key(&symbol, side);
+
+            // 最近一次成功且尚未关联平仓的同币种同方向开仓
+            let mut found = None;
+            for entry in entries[start..].iter_mut().rev() {
+                if entry.open.is_none() {
+                    // 索引里没有未平仓信息的记录，读取一次补上
+                    let Some(open_record) = self.get_record(&entry.id) else {
+                        continue;
+                    };
+                    entry.open = Some(unclosed_opens(&open_record));
+                    index_changed = true;
+                }
+                if entry.open.as_ref().is_some_and(|open| open.contains(&key)) {
+                    found = Some(entry);
+                    break;
+                }
+            }
+            let Some(entry) = found else {
+                continue;
+            };
+            let Some(mut open_record) = self.get_record(&entry.id) else {
+                continue;
+            };
+            let Some(idx) = open_record.decisions.iter().rposition(|a| {
+                a.success
+                    && a.action.is_open()
+                    && a.action.side() == side
+                    && a.symbol == symbol
+                    && a.closed_by.is_empty()
+            }) else {
+                entry.open = Some(unclosed_opens(&open_record));
+                index_changed = true;
                 continue;
+            };
+
+            record.decisions[i].opened_by = open_record.id.clone();
+            open_record.decisions[idx].closed_by = record.id.clone();
+            entry.open = Some(unclosed_opens(&open_record));
+            index_changed = true;
+
+            if let Err(e) = self.rewrite_record(&open_record) {
+                tracing::warn!("⚠ 回写开仓记录 {} 失败: {}", open_record.id, e);
             }
+        }
 
-            let data = match fs::read(&path) {
-                Ok(d) => d,
-                Err(_) => continue,
-            };
+        if index_changed && let Err(e) = self.write_index(&entries) {
+            tracing::warn!("⚠ 更新决策记录索引失败: {}", e);
+        }
+    }
 
-            let record: DecisionRecord = match serde_json::from_slice(&data) {
-                Ok(dr) => dr,
-                Err(_) => continue,
+    // 最近N个周期内已平仓交易的开/平仓决策及其理由，供复盘提示词和交易日志引用
+    pub fn trade_attributions(&self, lookback_cycles: usize) -> Vec<TradeAttribution> {
+        let records = self.get_latest_records(lookback_cycles).unwrap_or_default();
+
+        let mut attributions = Vec::new();
+        for record in &records {
+            for close in record
+                .decisions
+                .iter()
+                .filter(|a| a.success && !a.opened_by.is_empty())
+            {
+                let open_record = self.get_record(&close.opened_by);
+                // 组合交易以开仓时的组ID为准
+                let group_id = open_record
+                    .as_ref()
+                    .and_then(|r| {
+                        r.decisions.iter().find(|a| {
+                            a.action.is_open()
+                                && a.symbol == close.symbol
+                                && a.action.side() == close.action.side()
+                                && a.closed_by == record.id
+                        })
+                    })
+                    .map(|open| open.group_id.clone())
+                    .unwrap_or_default();
+                let open_reasoning = open_record
+                    .as_ref()
+                    .and_then(|r| {
+                        let wanted = if close.action.side() == "long" {
+                            decision::Action::OpenLong
+                        } else {
+                            decision::Action::OpenShort
+                        };
+                        r.reasoning_for(&close.symbol, wanted)
+                    })
+                    .unwrap_or_default();
+                let close_reasoning = record
+                    .reasoning_for(&close.symbol, close.action.to_decision_action())
+                    .unwrap_or_default();
+
+                attributions.push(TradeAttribution {
+                    symbol: close.symbol.clone(),
+                    side: close.action.side().to_string(),
+                    open_record_id: close.opened_by.clone(),
+                    close_record_id: record.id.clone(),
+                    open_reasoning,
+                    close_reasoning,
+                    group_id,
+                });
+            }
+        }
+
+        attributions
+    }
+
+    // 最近N个周期内已平仓交易的持仓时长中位数，没有已平仓交易时返回 None
+    pub fn typical_hold(&self, lookback_cycles: usize) -> Option<chrono::Duration> {
+        let records = self.get_latest_records(lookback_cycles).unwrap_or_default();
+
+        let mut holds: Vec<chrono::Duration> = records
+            .iter()
+            .flat_map(|record| {
+                record
+                    .decisions
+                    .iter()
+                    .filter(|a| a.success && !a.opened_by.is_empty())
+                    .filter_map(|close| {
+                        let opened = self.get_record(&close.opened_by)?;
+                        Some(record.timestamp - opened.timestamp)
+                    })
+            })
+            .collect();
+        if holds.is_empty() {
+            return None;
+        }
+        holds.sort();
+        Some(holds[holds.len() / 2])
+    }
+
+    // 获取统计信息
+    pub fn get_statistics(&self) -> Result<Statistics, Box<dyn Error>> {
+        if !Path::new(&self.log_dir).is_dir() {
+            return Err(format!("读取日志目录失败: {} 不存在", self.log_dir).into());
+        }
+
+        let mut stats = Statistics::default();
+
+        for entry in self.entries() {
+            let record = match self.get_record(&entry.id) {
+                Some(dr) => dr,
+                None => continue,
             };
 
             stats.total_cycles += 1;
@@ -279,6 +1046,7 @@ impl DecisionLogger {
         Ok(stats)
     }
 
+    // 分析最近 lookback_cycles 个周期内平仓的交易；开仓可能早于窗口，向前多读两倍周期补齐
     pub fn analyze_performance(
         &self,
         lookback_cycles: usize,
@@ -287,144 +1055,403 @@ impl DecisionLogger {
             .get_latest_records(lookback_cycles)
             .map_err(|e| format!("读取历史记录失败: {}", e))?;
 
-        let mut analysis = PerformanceAnalysis::default();
-        if records.len() == 0 {
-            return Ok(analysis);
+        if records.is_empty() {
+            return Ok(PerformanceAnalysis::default());
         }
 
-        let mut open_positions: HashMap<String, HashMap<String, Value>> = HashMap::new();
         let all_records = self.get_latest_records(lookback_cycles * 3)?;
-        if all_records.len() > records.len() {
-            for record in &all_records {
-                for action in &record.decisions {
-                    if !action.success {
-                        continue;
-                    }
+        let earlier = all_records.len().saturating_sub(records.len());
+        let mut analysis = PerformanceAnalysis::from_records(&all_records[..earlier], &records);
+        analysis.attach_reasoning(&self.trade_attributions(lookback_cycles));
+        Ok(analysis)
+    }
+}
 
-                    let mut side = "";
-                    if action.action == Action::OPENLONG || action.action == Action::CLOSELONG {
-                        side = "long";
-                    } else if action.action == Action::OPENSHORT
-                        || action.action == Action::CLOSESHORT
-                    {
-                        side = "short";
-                    }
+impl PerformanceAnalysis {
+    // 为近期交易填上开/平仓决策的理由
+    pub fn attach_reasoning(&mut self, attributions: &[TradeAttribution]) {
+        for trade in &mut self.recent_trades {
+            if let Some(a) = attributions.iter().find(|a| {
+                a.symbol == trade.symbol
+                    && a.open_record_id == trade.open_record_id
+                    && a.close_record_id == trade.close_record_id
+            }) {
+                trade.open_reasoning = a.open_reasoning.clone();
+                trade.close_reasoning = a.close_reasoning.clone();
+            }
+        }
+    }
 
-                    let pos_key = format!("{}_{}", &action.symbol, side);
+    // 分析 records 中平仓的交易；earlier 为更早的记录，仅用于找回窗口之前的开仓
+    pub fn from_records(earlier: &[DecisionRecord], records: &[DecisionRecord]) -> Self {
+        let mut analysis = PerformanceAnalysis::default();
 
-                    match action.action {
-                        Action::OPENLONG | Action::OPENSHORT => {
-                            open_positions.insert(
-                                pos_key,
-                                HashMap::from([
-                                    ("side".to_string(), json!(side)),
-                                    ("open_price".to_string(), json!(action.price)),
-                                    ("open_time".to_string(), json!(action.timestamp)),
-                                    ("quantity".to_string(), json!(action.quantity)),
-                                    ("leverage".to_string(), json!(action.leverage)),
-                                ]),
-                            );
-                        }
-                        Action::CLOSELONG | Action::CLOSESHORT => {
-                            open_positions.remove(&pos_key);
-                        }
-                    }
+        // 预填充窗口之前仍未平仓的持仓
+        let mut open_positions: HashMap<String, OpenPosition> = HashMap::new();
+        for record in earlier {
+            for action in record.decisions.iter().filter(|a| a.success) {
+                let pos_key = format!("{}_{}", action.symbol, action.action.side());
+                if action.action.is_open() {
+                    open_positions.insert(pos_key, OpenPosition::new(record, action));
+                } else {
+                    open_positions.remove(&pos_key);
                 }
             }
         }
 
-        for record in &records {
-            for action in &record.decisions {
-                if !action.success {
+        let mut trades = Vec::new();
+        for record in records {
+            for action in record.decisions.iter().filter(|a| a.success) {
+                let pos_key = format!("{}_{}", action.symbol, action.action.side());
+                if action.action.is_open() {
+                    open_positions.insert(pos_key, OpenPosition::new(record, action));
                     continue;
                 }
+                // 查找对应的开仓记录（可能来自预填充或当前窗口）
+                let Some(open_pos) = open_positions.remove(&pos_key) else {
+                    continue;
+                };
+                let quantity = if action.quantity > Decimal::ZERO {
+                    action.quantity
+                } else {
+                    open_pos.quantity
+                };
+                let pnl = match action.action {
+                    Action::CLOSELONG => quantity * (action.price - open_pos.price),
+                    _ => quantity * (open_pos.price - action.price),
+                };
+
+                // 计算盈亏百分比（相对保证金）
+                let position_value = quantity * open_pos.price;
+                let margin_used = position_value / Decimal::from(open_pos.leverage.max(1));
+                let pnl_pct = if margin_used > Decimal::ZERO {
+                    money::to_f64(pnl / margin_used * Decimal::ONE_HUNDRED)
+                } else {
+                    0.0
+                };
+
+                trades.push(TradeOutcome {
+                    symbol: action.symbol.clone(),
+                    side: Side::from_str(action.action.side()).unwrap_or_default(),
+                    quantity,
+                    leverage: open_pos.leverage,
+                    open_price: open_pos.price,
+                    close_price: action.price,
+                    position_value,
+                    margin_used,
+                    pn_l: pnl,
+                    pn_l_pct: pnl_pct,
+                    duration: format_hold(action.timestamp - open_pos.time),
+                    open_time: open_pos.time,
+                    close_time: action.timestamp,
+                    was_stop_loss: false,
+                    open_record_id: open_pos.record_id,
+                    close_record_id: record.id.clone(),
+                    group_id: action.group_id.clone(),
+                    ..Default::default()
+                });
+            }
+        }
 
-                let mut side = "";
-                if action.action == Action::OPENLONG || action.action == Action::OPENSHORT {
-                    side = "long";
-                } else if action.action == Action::OPENSHORT || action.action == Action::CLOSESHORT
-                {
-                    side = "short";
-                }
+        analysis.add_trades(&trades);
+        let equity: Vec<f64> = records
+            .iter()
+            .map(|r| money::to_f64(r.account_state.total_balance))
+            .filter(|e| *e > 0.0)
+            .collect();
+        analysis.add_equity_stats(&equity);
 
-                let pos_key = format!("{}_{}", &action.symbol, side);
+        // 只保留最近的交易
+        let skip = trades.len().saturating_sub(RECENT_TRADES);
+        analysis.recent_trades = trades.into_iter().skip(skip).collect();
 
-                match action.action {
-                    Action::OPENLONG | Action::OPENSHORT => {
-                        open_positions.insert(
-                            pos_key,
-                            HashMap::from([
-                                ("side".to_string(), json!(side)),
-                                ("open_price".to_string(), json!(action.price)),
-                                ("open_time".to_string(), json!(action.timestamp)),
-                                ("quantity".to_string(), json!(action.quantity)),
-                                ("leverage".to_string(), json!(action.leverage)),
-                            ]),
-                        );
-                    }
-                    Action::CLOSELONG | Action::CLOSESHORT => {
-                        // 查找对应的开仓记录（可能来自预填充或当前窗口）
-                        if let Some(open_pos) = open_positions.get(&pos_key) {
-                            let open_price = open_pos["open_price"]
-                                .as_f64()
-                                .expect("open_price must be a float");
-
-                            let open_time = open_pos["open_time"]
-                                .as_str()
-                                .expect("open_time must be a string")
-                                .parse::<chrono::DateTime<chrono::Utc>>()
-                                .expect("invalid time format");
-
-                            let side = open_pos["side"]
-                                .as_str()
-                                .expect("side must be a string")
-                                .to_string();
-
-                            let quantity = open_pos["quantity"]
-                                .as_f64()
-                                .expect("quantity must be a float");
-
-                            let leverage = open_pos["leverage"]
-                                .as_i64()
-                                .expect("leverage must be an integer")
-                                as i32;
-
-                            let mut pnl = 0_f64;
-                            if side == "long" {
-                                pnl = quantity * (action.price - open_price);
-                            } else {
-                                pnl = quantity * (open_price - action.price);
-                            }
-
-                            // 计算盈亏百分比（相对保证金）
-                            let position_value = quantity * open_price;
-                            let margin_used = position_value / f64::from(leverage);
-                            let mut pnl_pct = 0_f64;
-                            if margin_used > 0_f64 {
-                                pnl_pct = (pnl / margin_used) * 100_f64;
-                            }
-
-                            let outcome = TradeOutcome {
-                                symbol: action.symbol.to_string(),
-                                side: Side::from_str(side.as_str()).unwrap(),
-                                quantity: quantity,
-                                leverage: leverage,
-                                open_price: open_price,
-                                close_price: action.price,
-                                
-                            }
-                        }
-                    }
+        analysis
+    }
+}
+
+impl DecisionRecord {
+    // 新建一条周期记录；周期编号、时间和ID在 log_decision 时填写
+    pub fn new(
+        system_prompt: &str,
+        input_prompt: &str,
+        cot_trace: &str,
+        decision_json: &str,
+    ) -> Self {
+        Self {
+            schema_version: DECISION_SCHEMA_VERSION,
+            id: String::new(),
+            timestamp: Utc::now(),
+            cycle_number: 0,
+            system_prompt: system_prompt.to_string(),
+            input_prompt: input_prompt.to_string(),
+            cot_trace: cot_trace.to_string(),
+            decision_json: decision_json.to_string(),
+            account_state: AccountSnapshot {
+                total_balance: Decimal::ZERO,
+                available_balance: Decimal::ZERO,
+                total_unrealized_profit: Decimal::ZERO,
+                position_count: 0,
+                margin_used_pct: 0.0,
+            },
+            positions: Vec::new(),
+            candidate_coins: Vec::new(),
+            decisions: Vec::new(),
+            execution_log: Vec::new(),
+            success: true,
+            error_message: String::new(),
+            variant: String::new(),
+        }
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn cycle_number(&self) -> i32 {
+        self.cycle_number
+    }
+
+    pub fn timestamp(&self) -> DateTime<Utc> {
+        self.timestamp
+    }
+
+    pub fn is_success(&self) -> bool {
+        self.success
+    }
+
+    pub fn error_message(&self) -> &str {
+        &self.error_message
+    }
+
+    // 产生该记录的提示词变体，不参与实验时为空
+    pub fn variant(&self) -> &str {
+        &self.variant
+    }
+
+    pub fn set_variant(&mut self, variant: &str) {
+        self.variant = variant.to_string();
+    }
+
+    // 记录决策时的账户与持仓快照
+    pub fn set_account(
+        &mut self,
+        account: &decision::AccountInfo,
+        positions: &[decision::PositionInfo],
+    ) {
+        self.account_state = AccountSnapshot {
+            total_balance: money::from_f64(account.total_equity),
+            available_balance: money::from_f64(account.available_balance),
+            total_unrealized_profit: positions
+                .iter()
+                .map(|p| money::from_f64(p.unrealized_pnl))
+                .sum(),
+            position_count: account.position_count,
+            margin_used_pct: account.margin_used_pct,
+        };
+        self.positions = positions
+            .iter()
+            .map(|p| PositionSnapshot {
+                symbol: p.symbol.clone(),
+                side: p.side.clone(),
+                position_amt: money::from_f64(p.quantity),
+                entry_price: money::from_f64(p.entry_price),
+                mark_price: money::from_f64(p.mark_price),
+                unrealized_profit: money::from_f64(p.unrealized_pnl),
+                leverage: p.leverage as f64,
+                liquidation_price: money::from_f64(p.liquidation_price),
+            })
+            .collect();
+    }
+
+    // 决策时的账户净值
+    pub fn total_equity(&self) -> f64 {
+        money::to_f64(self.account_state.total_balance)
+    }
+
+    // 决策时的可用余额
+    pub fn available_balance(&self) -> f64 {
+        money::to_f64(self.account_state.available_balance)
+    }
+
+    // 决策时的持仓数与保证金使用率（%）
+    pub fn position_count(&self) -> i32 {
+        self.account_state.position_count
+    }
+
+    pub fn margin_used_pct(&self) -> f64 {
+        self.account_state.margin_used_pct
+    }
+
+    pub fn candidate_coins(&self) -> &[String] {
+        &self.candidate_coins
+    }
+
+    pub fn system_prompt(&self) -> &str {
+        &self.system_prompt
+    }
+
+    pub fn input_prompt(&self) -> &str {
+        &self.input_prompt
+    }
+
+    pub fn cot_trace(&self) -> &str {
+        &self.cot_trace
+    }
+
+    pub fn decision_json(&self) -> &str {
+        &self.decision_json
+    }
+
+    // 执行结果的一行摘要，如 "open_long BTCUSDT 0.01 @ 100; close_short ETHUSDT failed: ..."
+    pub fn execution_summary(&self) -> String {
+        self.decisions
+            .iter()
+            .map(|a| {
+                let action = a.action.to_decision_action().as_str();
+                if a.success {
+                    format!("{} {} {} @ {}", action, a.symbol, a.quantity, a.price)
+                } else {
+                    format!("{} {} failed: {}", action, a.symbol, a.error)
                 }
-            }
+            })
+            .collect::<Vec<_>>()
+            .join("; ")
+    }
+
+    // 决策时的持仓快照（还原为 PositionInfo，保证金按开仓价与杠杆估算）
+    pub fn positions(&self) -> Vec<decision::PositionInfo> {
+        self.positions
+            .iter()
+            .map(|p| {
+                let leverage = (p.leverage.round() as i32).max(1);
+                let margin_used = p.position_amt * p.entry_price / Decimal::from(leverage);
+                decision::PositionInfo {
+                    symbol: p.symbol.clone(),
+                    side: p.side.clone(),
+                    entry_price: money::to_f64(p.entry_price),
+                    mark_price: money::to_f64(p.mark_price),
+                    quantity: money::to_f64(p.position_amt),
+                    leverage,
+                    unrealized_pnl: money::to_f64(p.unrealized_profit),
+                    unrealized_pnl_pct: if margin_used > Decimal::ZERO {
+                        money::to_f64(p.unrealized_profit / margin_used * Decimal::ONE_HUNDRED)
+                    } else {
+                        0.0
+                    },
+                    liquidation_price: money::to_f64(p.liquidation_price),
+                    margin_used: money::to_f64(margin_used),
+                    update_time: 0,
+                }
+            })
+            .collect()
+    }
+
+    pub fn set_candidate_coins(&mut self, coins: Vec<String>) {
+        self.candidate_coins = coins;
+    }
+
+    // 记录一次下单结果；hold / wait 不产生订单，忽略
+    #[allow(clippy::too_many_arguments)]
+    pub fn record_execution(
+        &mut self,
+        action: decision::Action,
+        symbol: &str,
+        quantity: Decimal,
+        leverage: i32,
+        price: Decimal,
+        order_id: i64,
+        error: Option<&str>,
+    ) {
+        let action = match action {
+            decision::Action::OpenLong => Action::OPENLONG,
+            decision::Action::OpenShort => Action::OPENSHORT,
+            decision::Action::CloseLong => Action::CLOSELONG,
+            decision::Action::CloseShort => Action::CLOSESHORT,
+            decision::Action::Hold | decision::Action::Wait => return,
+        };
+        self.decisions.push(DecisionAction {
+            action,
+            symbol: symbol.to_string(),
+            quantity,
+            leverage,
+            price,
+            order_id,
+            timestamp: Utc::now(),
+            success: error.is_none(),
+            error: error.unwrap_or_default().to_string(),
+            opened_by: String::new(),
+            closed_by: String::new(),
+            group_id: String::new(),
+            approved_by: String::new(),
+        });
+    }
+
+    // 已记录的执行数，配合 set_approved_by 标记之后的执行
+    pub fn execution_count(&self) -> usize {
+        self.decisions.len()
+    }
+
+    // 将第 from 条起的执行记录标记为由 approver 审批
+    pub fn set_approved_by(&mut self, from: usize, approver: &str) {
+        for action in self.decisions.iter_mut().skip(from) {
+            action.approved_by = approver.to_string();
+        }
+    }
+
+    // 将最近一条执行记录标记为组合交易的一条腿
+    pub fn group_last_execution(&mut self, group_id: &str) {
+        if let Some(last) = self.decisions.last_mut() {
+            last.group_id = group_id.to_string();
         }
+    }
+
+    pub fn log(&mut self, line: impl Into<String>) {
+        self.execution_log.push(line.into());
+    }
 
-        {}
+    // 标记本周期失败（如AI调用失败）
+    pub fn set_error(&mut self, error: &str) {
+        self.success = false;
+        self.error_message = error.to_string();
     }
+
+    // 记录被校验拒绝的决策：写入错误信息和执行日志，本周期其余决策照常执行
+    pub fn record_rejected(&mut self, rejected: &[decision::DecisionError]) {
+        if rejected.is_empty() {
+            return;
+        }
+        let reasons: Vec<String> = rejected.iter().map(|e| e.to_string()).collect();
+        for reason in &reasons {
+            self.log(format!("⛔ 决策被拒绝: {}", reason));
+        }
+        self.error_message = reasons.join("; ");
+    }
+
+    // 从AI输出的决策JSON中取出某币种某动作的理由
+    fn reasoning_for(&self, symbol: &str, action: decision::Action) -> Option<String> {
+        let proposed: Vec<Decision> = serde_json::from_str(&self.decision_json).ok()?;
+        proposed
+            .into_iter()
+            .find(|d| d.symbol == symbol && d.action == action)
+            .map(|d| d.reasoning)
+    }
+}
+
+/// A closed trade linked back to the decisions that opened and closed it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TradeAttribution {
+    pub symbol: String,
+    pub side: String,
+    pub open_record_id: String,
+    pub close_record_id: String,
+    pub open_reasoning: String,
+    pub close_reasoning: String,
+    /// Group id shared by the legs of a pair trade; empty for single trades.
+    pub group_id: String,
 }
 
 #[derive(Debug, Serialize, Deserialize, Default)]
-struct Statistics {
+pub struct Statistics {
     pub total_cycles: i32,
     pub successful_cycles: i32,
     pub failed_cycles: i32,
@@ -436,21 +1463,35 @@ struct Statistics {
 struct TradeOutcome {
     symbol: String,
     side: Side,
-    quantity: f64,
+    quantity: Decimal,
     leverage: i32,
-    open_price: f64,
-    close_price: f64,
-    position_value: f64,
-    margin_used: f64,
-    pn_l: f64,
+    open_price: Decimal,
+    close_price: Decimal,
+    position_value: Decimal,
+    margin_used: Decimal,
+    pn_l: Decimal,
     pn_l_pct: f64,
     duration: String,
     open_time: DateTime<Utc>,
     close_time: DateTime<Utc>,
     was_stop_loss: bool,
+    // 开仓 / 平仓所在的决策记录ID
+    #[serde(default)]
+    open_record_id: String,
+    #[serde(default)]
+    close_record_id: String,
+    // 组合交易各条腿共用的组ID
+    #[serde(default)]
+    group_id: String,
+    // 开仓 / 平仓决策的理由，供复盘提示词引用
+    #[serde(default)]
+    open_reasoning: String,
+    #[serde(default)]
+    close_reasoning: String,
 }
 
 #[derive(Debug, Deserialize, Serialize, Default)]
+#[allow(clippy::upper_case_acronyms)]
 enum Side {
     #[default]
     SHORT,
@@ -468,22 +1509,240 @@ impl FromStr for Side {
     }
 }
 
+// 仍未平仓的开仓动作
+struct OpenPosition {
+    price: Decimal,
+    time: DateTime<Utc>,
+    quantity: Decimal,
+    leverage: i32,
+    record_id: String,
+}
+
+impl OpenPosition {
+    fn new(record: &DecisionRecord, action: &DecisionAction) -> Self {
+        Self {
+            price: action.price,
+            time: action.timestamp,
+            quantity: action.quantity,
+            leverage: action.leverage,
+            record_id: record.id.clone(),
+        }
+    }
+}
+
+// 绩效分析中保留的最近交易数
+const RECENT_TRADES: usize = 10;
+// 只有盈利、没有亏损时的盈亏比
+const PROFIT_FACTOR_CAP: f64 = 999.0;
+
+// 提示词中引用的决策理由最多保留的字符数
+const QUOTED_REASONING_CHARS: usize = 160;
+
+// 单行引用决策理由，过长时截断
+fn quote(reasoning: &str) -> String {
+    let line = reasoning.split_whitespace().collect::<Vec<_>>().join(" ");
+    match line.char_indices().nth(QUOTED_REASONING_CHARS) {
+        Some((cut, _)) => format!("\"{}…\"", &line[..cut]),
+        None => format!("\"{}\"", line),
+    }
+}
+
+// 持仓时长，如 2h05m
+fn format_hold(held: chrono::Duration) -> String {
+    let minutes = held.num_minutes().max(0);
+    format!("{}h{:02}m", minutes / 60, minutes % 60)
+}
+
 #[derive(Debug, Deserialize, Serialize, Default)]
-struct PerformanceAnalysis {
+pub struct PerformanceAnalysis {
     total_trades: i32,
     winning_trades: i32,
     losing_trades: i32,
     win_rate: f64,
-    avg_win: f64,
-    avg_loss: f64,
+    avg_win: Decimal,
+    avg_loss: Decimal,
     profit_factor: f64,
+    // 以下风险指标按周期间的账户权益变化计算，不做年化
     sharpe_ratio: f64,
+    #[serde(default)]
+    sortino_ratio: f64,
+    // 最大回撤（%）
+    #[serde(default)]
+    max_drawdown_pct: f64,
+    // 平均持仓时长（分钟）
+    #[serde(default)]
+    avg_holding_minutes: f64,
     recent_trades: Vec<TradeOutcome>,
     symbol_stats: HashMap<String, SymbolPerformance>,
     best_symbol: String,
     worst_symbol: String,
 }
 
+impl PerformanceAnalysis {
+    // 汇总已配对的开/平仓交易：胜率、盈亏比、平均持仓时长与各币种统计
+    fn add_trades(&mut self, trades: &[TradeOutcome]) {
+        let mut total_win = Decimal::ZERO;
+        let mut total_loss = Decimal::ZERO;
+        let mut total_minutes = 0.0;
+        for t in trades {
+            let minutes = (t.close_time - t.open_time).num_seconds() as f64 / 60.0;
+            self.total_trades += 1;
+            total_minutes += minutes;
+            let stats = self
+                .symbol_stats
+                .entry(t.symbol.clone())
+                .or_insert_with(|| SymbolPerformance {
+                    symbol: t.symbol.clone(),
+                    ..Default::default()
+                });
+            stats.total_trades += 1;
+            stats.total_pn_l += t.pn_l;
+            stats.avg_holding_minutes += minutes;
+            if t.pn_l > Decimal::ZERO {
+                self.winning_trades += 1;
+                total_win += t.pn_l;
+                stats.winning_trades += 1;
+            } else if t.pn_l < Decimal::ZERO {
+                self.losing_trades += 1;
+                total_loss += t.pn_l;
+                stats.losing_trades += 1;
+            }
+        }
+        if self.total_trades == 0 {
+            return;
+        }
+
+        self.win_rate = f64::from(self.winning_trades) / f64::from(self.total_trades) * 100.0;
+        if self.winning_trades > 0 {
+            self.avg_win = total_win / Decimal::from(self.winning_trades);
+        }
+        if self.losing_trades > 0 {
+            self.avg_loss = total_loss / Decimal::from(self.losing_trades);
+        }
+        self.profit_factor = if total_loss < Decimal::ZERO {
+            money::to_f64(total_win / -total_loss)
+        } else if total_win > Decimal::ZERO {
+            PROFIT_FACTOR_CAP
+        } else {
+            0.0
+        };
+        self.avg_holding_minutes = total_minutes / f64::from(self.total_trades);
+
+        for stats in self.symbol_stats.values_mut() {
+            let n = f64::from(stats.total_trades);
+            stats.win_rate = f64::from(stats.winning_trades) / n * 100.0;
+            stats.avg_pn_l = stats.total_pn_l / Decimal::from(stats.total_trades);
+            stats.avg_holding_minutes /= n;
+        }
+        let mut ranked: Vec<&SymbolPerformance> = self.symbol_stats.values().collect();
+        ranked.sort_by(|a, b| {
+            b.total_pn_l
+                .cmp(&a.total_pn_l)
+                .then_with(|| a.symbol.cmp(&b.symbol))
+        });
+        self.best_symbol = ranked[0].symbol.clone();
+        self.worst_symbol = ranked[ranked.len() - 1].symbol.clone();
+    }
+
+    // 由各周期的账户权益计算夏普、索提诺比率与最大回撤
+    fn add_equity_stats(&mut self, equity: &[f64]) {
+        let mut peak = 0.0_f64;
+        for &e in equity {
+            peak = peak.max(e);
+            self.max_drawdown_pct = self.max_drawdown_pct.max((peak - e) / peak * 100.0);
+        }
+
+        let returns: Vec<f64> = equity.windows(2).map(|w| (w[1] - w[0]) / w[0]).collect();
+        if returns.len() < 2 {
+            return;
+        }
+        let n = returns.len() as f64;
+        let mean = returns.iter().sum::<f64>() / n;
+        let std_dev = (returns.iter().map(|r| (r - mean).powi(2)).sum::<f64>() / n).sqrt();
+        let downside_dev = (returns.iter().map(|r| r.min(0.0).powi(2)).sum::<f64>() / n).sqrt();
+        if std_dev > 0.0 {
+            self.sharpe_ratio = mean / std_dev;
+        }
+        if downside_dev > 0.0 {
+            self.sortino_ratio = mean / downside_dev;
+        }
+    }
+
+    // 已平仓交易数
+    pub fn total_trades(&self) -> i32 {
+        self.total_trades
+    }
+
+    // 胜率（%）
+    pub fn win_rate(&self) -> f64 {
+        self.win_rate
+    }
+
+    // 已平仓交易的盈亏合计
+    pub fn total_pnl(&self) -> Decimal {
+        self.symbol_stats.values().map(|s| s.total_pn_l).sum()
+    }
+
+    // 按周期权益变化计算的夏普比率
+    pub fn sharpe_ratio(&self) -> f64 {
+        self.sharpe_ratio
+    }
+
+    // 平均盈利与平均亏损之比；没有亏损交易时为 None
+    pub fn payoff_ratio(&self) -> Option<f64> {
+        (self.avg_loss < Decimal::ZERO).then(|| money::to_f64(self.avg_win / -self.avg_loss))
+    }
+
+    // 写入AI提示词的近期表现摘要
+    pub fn prompt_summary(&self) -> String {
+        if self.total_trades == 0 {
+            return "No closed trades yet.".to_string();
+        }
+        let mut s = format!(
+            "Trades {} ({} won, {} lost) | Win rate {:.1}% | Avg win {:+.2} | Avg loss {:+.2} | Profit factor {:.2} | Sharpe {:.2} | Sortino {:.2} | Max drawdown {:.2}% | Avg hold {}",
+            self.total_trades,
+            self.winning_trades,
+            self.losing_trades,
+            self.win_rate,
+            self.avg_win,
+            self.avg_loss,
+            self.profit_factor,
+            self.sharpe_ratio,
+            self.sortino_ratio,
+            self.max_drawdown_pct,
+            format_hold(chrono::Duration::seconds(
+                (self.avg_holding_minutes * 60.0) as i64
+            ))
+        );
+        if !self.best_symbol.is_empty() {
+            s.push_str(&format!(
+                "\nBest symbol: {} | Worst symbol: {}",
+                self.best_symbol, self.worst_symbol
+            ));
+        }
+        for t in self.recent_trades.iter().rev().take(5) {
+            s.push_str(&format!(
+                "\n- {} {:?} {:.4} → {:.4} | {:+.2} USDT ({:+.2}%) | {}{}",
+                t.symbol,
+                t.side,
+                t.open_price,
+                t.close_price,
+                t.pn_l,
+                t.pn_l_pct,
+                t.duration,
+                if t.was_stop_loss { " | stop-loss" } else { "" }
+            ));
+            if !t.open_reasoning.is_empty() {
+                s.push_str(&format!("\n  Opened: {}", quote(&t.open_reasoning)));
+            }
+            if !t.close_reasoning.is_empty() {
+                s.push_str(&format!("\n  Closed: {}", quote(&t.close_reasoning)));
+            }
+        }
+        s
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize, Default)]
 struct SymbolPerformance {
     symbol: String,
@@ -491,6 +1750,8 @@ struct SymbolPerformance {
     winning_trades: i32,
     losing_trades: i32,
     win_rate: f64,
-    total_pn_l: f64,
-    avg_pn_l: f64,
+    total_pn_l: Decimal,
+    avg_pn_l: Decimal,
+    #[serde(default)]
+    avg_holding_minutes: f64,
 }
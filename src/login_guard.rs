@@ -0,0 +1,410 @@
+//! Brute-force protection for logins and two-factor codes.
+//!
+//! Failed attempts are counted per account and per client IP over a sliding
+//! window. Reaching a limit locks that account or IP out; each further
+//! lockout within the memory period doubles the previous one, up to
+//! [`GuardParams::max_lockout`]. A successful attempt clears the account's
+//! counters but not the IP's, so one good login cannot reset a password
+//! spray from the same address.
+//!
+//! Counters live in process memory, or in Redis when `redis_url` is set and
+//! the `redis` feature is enabled, so several API servers share them. If
+//! Redis cannot be reached the in-memory counters are used instead. Every
+//! lockout is written to the `auth_lockouts` table for later review.
+
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+use crate::database::{AuthLockout, Database};
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct GuardParams {
+    /// Failed attempts on one account before it is locked; 0 disables.
+    pub max_account_failures: u32,
+    /// Failed attempts from one IP, across accounts, before it is locked; 0 disables.
+    pub max_ip_failures: u32,
+    /// How long failed attempts count towards a limit.
+    #[serde(with = "humantime_serde")]
+    pub window: Duration,
+    /// Length of the first lockout; doubles with each repeat.
+    #[serde(with = "humantime_serde")]
+    pub base_lockout: Duration,
+    /// Upper bound on a lockout. Repeats are forgotten once this long
+    /// (plus `window`) has passed without one.
+    #[serde(with = "humantime_serde")]
+    pub max_lockout: Duration,
+    /// Take the client IP from `X-Forwarded-For`. Only safe behind a
+    /// reverse proxy that sets the header itself.
+    pub trust_forwarded_for: bool,
+    /// Shared counter store, e.g. "redis://127.0.0.1/". Needs the `redis`
+    /// feature.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub redis_url: Option<String>,
+}
+
+impl Default for GuardParams {
+    fn default() -> Self {
+        Self {
+            max_account_failures: 5,
+            max_ip_failures: 20,
+            window: Duration::from_secs(15 * 60),
+            base_lockout: Duration::from_secs(60),
+            max_lockout: Duration::from_secs(60 * 60),
+            trust_forwarded_for: false,
+            redis_url: None,
+        }
+    }
+}
+
+impl GuardParams {
+    // The lockout for the `strike`th lockout in a row, counting from 1.
+    fn lockout(&self, strike: u32) -> Duration {
+        self.base_lockout
+            .saturating_mul(1 << strike.saturating_sub(1).min(16))
+            .min(self.max_lockout)
+    }
+
+    fn strike_memory(&self) -> Duration {
+        self.window.saturating_add(self.max_lockout)
+    }
+}
+
+/// What is being guessed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Attempt {
+    /// Email and password.
+    Login,
+    /// A two-factor or recovery code.
+    Otp,
+}
+
+impl Attempt {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Attempt::Login => "login",
+            Attempt::Otp => "otp",
+        }
+    }
+}
+
+// Counters of one account or IP.
+#[derive(Debug)]
+struct Counter {
+    failures: u32,
+    window_start: Instant,
+    strikes: u32,
+    last_strike: Option<Instant>,
+    locked_until: Option<Instant>,
+}
+
+static PARAMS: Lazy<RwLock<GuardParams>> = Lazy::new(|| RwLock::new(GuardParams::default()));
+static COUNTERS: Lazy<Mutex<HashMap<String, Counter>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+#[cfg(feature = "redis")]
+static REDIS: Lazy<RwLock<Option<redis::Client>>> = Lazy::new(|| RwLock::new(None));
+
+/// Replaces the limits, e.g. from the config file, and connects the Redis
+/// store if one is configured.
+pub fn set_params(params: GuardParams) {
+    #[cfg(feature = "redis")]
+    {
+        let client = params
+            .redis_url
+            .as_deref()
+            .and_then(|url| match redis::Client::open(url) {
+                Ok(client) => Some(client),
+                Err(e) => {
+                    tracing::warn!("⚠️ 登录防护的 Redis 地址无效，改用内存计数: {}", e);
+                    None
+                }
+            });
+        *REDIS.write().unwrap_or_else(|e| e.into_inner()) = client;
+    }
+    #[cfg(not(feature = "redis"))]
+    if params.redis_url.is_some() {
+        tracing::warn!("⚠️ 未启用 redis 特性，登录防护改用内存计数");
+    }
+    *PARAMS.write().unwrap_or_else(|e| e.into_inner()) = params;
+}
+
+fn params() -> GuardParams {
+    PARAMS.read().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+/// Whether the client IP may be taken from `X-Forwarded-For`.
+pub fn trust_forwarded_for() -> bool {
+    PARAMS
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .trust_forwarded_for
+}
+
+/// Forgets all in-memory counters and lockouts.
+pub fn reset() {
+    COUNTERS.lock().unwrap_or_else(|e| e.into_inner()).clear();
+}
+
+// The counters an attempt touches, with their limits: (kind, subject, key, limit).
+fn subjects(
+    attempt: Attempt,
+    account: &str,
+    ip: Option<IpAddr>,
+    params: &GuardParams,
+) -> Vec<(&'static str, String, String, u32)> {
+    let mut subjects = Vec::new();
+    if params.max_account_failures > 0 {
+        subjects.push((
+            "account",
+            account.to_string(),
+            format!("{}:account:{}", attempt.as_str(), account),
+            params.max_account_failures,
+        ));
+    }
+    if let Some(ip) = ip.filter(|_| params.max_ip_failures > 0) {
+        subjects.push((
+            "ip",
+            ip.to_string(),
+            format!("{}:ip:{}", attempt.as_str(), ip),
+            params.max_ip_failures,
+        ));
+    }
+    subjects
+}
+
+/// How much longer the account or IP is locked out of `attempt`, if it is.
+pub async fn locked_for(attempt: Attempt, account: &str, ip: Option<IpAddr>) -> Option<Duration> {
+    let params = params();
+    let mut longest = None;
+    for (_, _, key, _) in subjects(attempt, account, ip, &params) {
+        let remaining = store::locked_for(&key).await;
+        longest = longest.max(remaining);
+    }
+    longest
+}
+
+/// Counts a failed attempt against the account and IP. Returns the lockout
+/// if this failure started one; it is also recorded in the database.
+pub async fn failed(
+    db: &Database,
+    attempt: Attempt,
+    account: &str,
+    ip: Option<IpAddr>,
+) -> Option<Duration> {
+    let params = params();
+    let mut longest = None;
+    for (kind, subject, key, limit) in subjects(attempt, account, ip, &params) {
+        let Some((strike, lockout)) = store::fail(&key, limit, &params).await else {
+            continue;
+        };
+        tracing::warn!(
+            "🔒 {} {} 的{}失败次数过多，锁定 {}s（第 {} 次）",
+            kind,
+            subject,
+            attempt.as_str(),
+            lockout.as_secs(),
+            strike
+        );
+        let locked_at = Utc::now();
+        let event = AuthLockout {
+            id: 0,
+            attempt: attempt.as_str().to_string(),
+            kind: kind.to_string(),
+            subject,
+            ip: ip.map(|ip| ip.to_string()).unwrap_or_default(),
+            failures: limit.into(),
+            strike: strike.into(),
+            locked_at,
+            locked_until: locked_until(locked_at, lockout),
+        };
+        if let Err(e) = db.record_auth_lockout(&event).await {
+            tracing::warn!("⚠️ 保存登录锁定记录失败: {}", e);
+        }
+        longest = longest.max(Some(lockout));
+    }
+    longest
+}
+
+/// Clears the account's failed attempts after it got in.
+pub async fn succeeded(attempt: Attempt, account: &str) {
+    let key = format!("{}:account:{}", attempt.as_str(), account);
+    store::clear(&key).await;
+}
+
+fn locked_until(at: DateTime<Utc>, lockout: Duration) -> DateTime<Utc> {
+    chrono::Duration::from_std(lockout)
+        .ok()
+        .and_then(|d| at.checked_add_signed(d))
+        .unwrap_or(at)
+}
+
+mod memory {
+    use super::*;
+
+    fn counters() -> std::sync::MutexGuard<'static, HashMap<String, Counter>> {
+        COUNTERS.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub(super) fn locked_for(key: &str) -> Option<Duration> {
+        let until = counters().get(key)?.locked_until?;
+        until
+            .checked_duration_since(Instant::now())
+            .filter(|d| !d.is_zero())
+    }
+
+    pub(super) fn fail(key: &str, limit: u32, params: &GuardParams) -> Option<(u32, Duration)> {
+        let now = Instant::now();
+        let mut counters = counters();
+        let counter = counters.entry(key.to_string()).or_insert(Counter {
+            failures: 0,
+            window_start: now,
+            strikes: 0,
+            last_strike: None,
+            locked_until: None,
+        });
+        if now.duration_since(counter.window_start) > params.window {
+            counter.failures = 0;
+            counter.window_start = now;
+        }
+        if counter
+            .last_strike
+            .is_some_and(|at| now.duration_since(at) > params.strike_memory())
+        {
+            counter.strikes = 0;
+        }
+        counter.failures += 1;
+        if counter.failures < limit {
+            return None;
+        }
+
+        counter.failures = 0;
+        counter.strikes += 1;
+        counter.last_strike = Some(now);
+        let lockout = params.lockout(counter.strikes);
+        counter.locked_until = Some(now + lockout);
+        Some((counter.strikes, lockout))
+    }
+
+    pub(super) fn clear(key: &str) {
+        counters().remove(key);
+    }
+}
+
+#[cfg(feature = "redis")]
+mod shared {
+    use super::*;
+    use redis::{AsyncCommands, RedisResult};
+
+    const PREFIX: &str = "aitrading:auth:";
+    const TIMEOUT: Duration = Duration::from_secs(1);
+
+    pub(super) fn client() -> Option<redis::Client> {
+        REDIS.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    async fn connect(client: &redis::Client) -> RedisResult<redis::aio::MultiplexedConnection> {
+        let config = redis::AsyncConnectionConfig::new()
+            .set_connection_timeout(TIMEOUT)
+            .set_response_timeout(TIMEOUT);
+        client
+            .get_multiplexed_async_connection_with_config(&config)
+            .await
+    }
+
+    fn millis(d: Duration) -> i64 {
+        i64::try_from(d.as_millis()).unwrap_or(i64::MAX).max(1)
+    }
+
+    pub(super) async fn locked_for(
+        client: &redis::Client,
+        key: &str,
+    ) -> RedisResult<Option<Duration>> {
+        let mut conn = connect(client).await?;
+        let ttl: i64 = conn.pttl(format!("{PREFIX}{key}:locked")).await?;
+        Ok((ttl > 0).then(|| Duration::from_millis(ttl as u64)))
+    }
+
+    pub(super) async fn fail(
+        client: &redis::Client,
+        key: &str,
+        limit: u32,
+        params: &GuardParams,
+    ) -> RedisResult<Option<(u32, Duration)>> {
+        let mut conn = connect(client).await?;
+        let failures_key = format!("{PREFIX}{key}:failures");
+        let failures: u32 = conn.incr(&failures_key, 1).await?;
+        if failures == 1 {
+            let _: () = conn.pexpire(&failures_key, millis(params.window)).await?;
+        }
+        if failures < limit {
+            return Ok(None);
+        }
+
+        let strikes_key = format!("{PREFIX}{key}:strikes");
+        let strikes: u32 = conn.incr(&strikes_key, 1).await?;
+        let _: () = conn
+            .pexpire(&strikes_key, millis(params.strike_memory()))
+            .await?;
+        let lockout = params.lockout(strikes);
+        let _: () = conn
+            .pset_ex(format!("{PREFIX}{key}:locked"), 1, millis(lockout) as u64)
+            .await?;
+        let _: () = conn.del(&failures_key).await?;
+        Ok(Some((strikes, lockout)))
+    }
+
+    pub(super) async fn clear(client: &redis::Client, key: &str) -> RedisResult<()> {
+        let mut conn = connect(client).await?;
+        conn.del(&[
+            format!("{PREFIX}{key}:failures"),
+            format!("{PREFIX}{key}:strikes"),
+            format!("{PREFIX}{key}:locked"),
+        ])
+        .await
+    }
+}
+
+// Redis when configured and reachable, process memory otherwise.
+mod store {
+    use super::*;
+
+    pub(super) async fn locked_for(key: &str) -> Option<Duration> {
+        #[cfg(feature = "redis")]
+        if let Some(client) = shared::client() {
+            match shared::locked_for(&client, key).await {
+                Ok(remaining) => return remaining,
+                Err(e) => tracing::warn!("⚠️ 读取 Redis 登录锁定失败，改用内存计数: {}", e),
+            }
+        }
+        memory::locked_for(key)
+    }
+
+    pub(super) async fn fail(
+        key: &str,
+        limit: u32,
+        params: &GuardParams,
+    ) -> Option<(u32, Duration)> {
+        #[cfg(feature = "redis")]
+        if let Some(client) = shared::client() {
+            match shared::fail(&client, key, limit, params).await {
+                Ok(lockout) => return lockout,
+                Err(e) => tracing::warn!("⚠️ 写入 Redis 登录失败计数失败，改用内存计数: {}", e),
+            }
+        }
+        memory::fail(key, limit, params)
+    }
+
+    pub(super) async fn clear(key: &str) {
+        #[cfg(feature = "redis")]
+        if let Some(client) = shared::client()
+            && let Err(e) = shared::clear(&client, key).await
+        {
+            tracing::warn!("⚠️ 清除 Redis 登录失败计数失败: {}", e);
+        }
+        memory::clear(key);
+    }
+}
@@ -0,0 +1,165 @@
+//! Daily loss and drawdown limits.
+//!
+//! At the start of every cycle the trader's equity is checked against
+//! `max_daily_loss` (percent lost since the first cycle of the trader's local
+//! day) and `max_drawdown` (percent below the highest equity seen since the
+//! process started). Once either is hit, the trader opens nothing for
+//! `stop_trading_minutes`, or for as long as the limit stays breached;
+//! closing and managing positions is unaffected. The limits come from
+//! [`hot_reload`](crate::hot_reload), so edits apply from the next cycle.
+
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use once_cell::sync::Lazy;
+use serde::Serialize;
+
+use crate::decision::Decision;
+use crate::hot_reload::Settings;
+
+#[derive(Debug, Clone)]
+struct Track {
+    day: NaiveDate,
+    day_open: f64,
+    peak: f64,
+    halted_until: Option<DateTime<Utc>>,
+    breach: Option<Breach>,
+}
+
+// Equity marks and halts per trader.
+static TRACKS: Lazy<RwLock<HashMap<String, Track>>> = Lazy::new(|| RwLock::new(HashMap::new()));
+
+/// A limit the trader's equity is past.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Breach {
+    /// Percent lost since the day opened.
+    DailyLoss(f64),
+    /// Percent below the equity peak.
+    Drawdown(f64),
+}
+
+impl std::fmt::Display for Breach {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Breach::DailyLoss(pct) => write!(f, "daily loss {:.2}%", pct),
+            Breach::Drawdown(pct) => write!(f, "drawdown {:.2}%", pct),
+        }
+    }
+}
+
+/// Records the trader's equity at the start of a cycle on local day `day`
+/// and returns the limit it breaches, if any.
+pub fn observe(
+    trader_id: &str,
+    equity: f64,
+    day: NaiveDate,
+    now: DateTime<Utc>,
+    settings: &Settings,
+) -> Option<Breach> {
+    if equity <= 0.0 {
+        return None;
+    }
+    let mut tracks = TRACKS.write().unwrap_or_else(|e| e.into_inner());
+    let track = tracks.entry(trader_id.to_string()).or_insert(Track {
+        day,
+        day_open: equity,
+        peak: equity,
+        halted_until: None,
+        breach: None,
+    });
+    if track.day != day {
+        track.day = day;
+        track.day_open = equity;
+    }
+    track.peak = track.peak.max(equity);
+
+    let daily_loss = (track.day_open - equity) / track.day_open * 100.0;
+    let drawdown = (track.peak - equity) / track.peak * 100.0;
+    track.breach = if settings.max_daily_loss > 0.0 && daily_loss >= settings.max_daily_loss {
+        Some(Breach::DailyLoss(daily_loss))
+    } else if settings.max_drawdown > 0.0 && drawdown >= settings.max_drawdown {
+        Some(Breach::Drawdown(drawdown))
+    } else {
+        None
+    };
+    let breach = track.breach?;
+    let until = now + Duration::minutes(settings.stop_trading_minutes.max(0) as i64);
+    if track.halted_until.is_none_or(|t| t < now) {
+        tracing::warn!(
+            "🛑 交易员 {} 触发风控限制（{}），暂停开仓至 {}",
+            trader_id,
+            breach,
+            until
+        );
+    }
+    track.halted_until = Some(until);
+    Some(breach)
+}
+
+/// Whether the trader may not open positions right now.
+pub fn is_halted(trader_id: &str) -> bool {
+    state(trader_id).halted
+}
+
+/// A trader's standing against its limits as of its last cycle.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct LimitState {
+    pub halted: bool,
+    /// The limit breached at the last cycle, if any.
+    pub breach: Option<Breach>,
+    pub halted_until: Option<DateTime<Utc>>,
+}
+
+/// The trader's limit state; all clear before its first cycle.
+pub fn state(trader_id: &str) -> LimitState {
+    let tracks = TRACKS.read().unwrap_or_else(|e| e.into_inner());
+    let Some(track) = tracks.get(trader_id) else {
+        return LimitState::default();
+    };
+    let now = Utc::now();
+    LimitState {
+        halted: track.breach.is_some() || track.halted_until.is_some_and(|u| u > now),
+        breach: track.breach,
+        halted_until: track.halted_until.filter(|u| *u > now),
+    }
+}
+
+/// Drops opening decisions while the trader is halted.
+pub fn drop_entries(trader_id: &str, decisions: Vec<Decision>) -> Vec<Decision> {
+    if !is_halted(trader_id) {
+        return decisions;
+    }
+    decisions
+        .into_iter()
+        .filter(|d| {
+            if d.action.is_open() {
+                tracing::warn!("🛑 风控暂停开仓中，拒绝 {} {:?}", d.symbol, d.action);
+            }
+            !d.action.is_open()
+        })
+        .collect()
+}
+
+/// A line for the prompt while new entries are stopped.
+pub fn prompt_annotation(trader_id: &str) -> Option<String> {
+    let tracks = TRACKS.read().unwrap_or_else(|e| e.into_inner());
+    let track = tracks.get(trader_id)?;
+    let reason = match track.breach {
+        Some(breach) => format!("{} limit reached", breach),
+        None => match track.halted_until {
+            Some(until) if until > Utc::now() => {
+                format!(
+                    "loss limit hit recently, until {} UTC",
+                    until.format("%H:%M")
+                )
+            }
+            _ => return None,
+        },
+    };
+    Some(format!(
+        "🛑 Risk limit: do not open new positions ({}); closing is allowed.",
+        reason
+    ))
+}
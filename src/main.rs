@@ -1,11 +1,359 @@
-mod api_client;
-mod auth;
-mod config;
-mod data;
-mod database;
-mod logger;
-mod types;
-
-fn main() {
-    println!("Hello, world!");
+mod cli;
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Context;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use clap::Parser;
+use rand::RngCore;
+
+use aitrading::config::{Config, ConfigKey};
+use aitrading::database::Database;
+use aitrading::error_sink::{self, SentrySink, TracingSink};
+use aitrading::runner::{Runner, RunnerConfig};
+use aitrading::scheduler::Scheduler;
+use aitrading::server::{self, AppState, Listen};
+use aitrading::{
+    accuracy, ai_cache, api_client, audit, auth, calendar, config, currency, daily_report, data,
+    hot_reload, login_guard, maintenance, notify, pause, profiler, rate_limit, recovery,
+    risk_override, secrets, sim, strategy, stream, symbol_watch, symbols, telemetry, tournament,
+    universe,
+};
+use cli::{Cli, Command};
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+
+    // The log format has to be known before anything logs, so load the config first.
+    // A config that cannot be read or parsed stops startup rather than running on defaults.
+    let mut config_key = cli
+        .config_key_file
+        .clone()
+        .map(ConfigKey::KeyFile)
+        .unwrap_or_else(ConfigKey::from_env);
+    // 加密配置只在启动时询问一次密码，热加载沿用同一把密钥
+    if config::is_encrypted_config(&cli.config).unwrap_or(false) {
+        config_key = config_key.resolve()?;
+    }
+    let mut config = config::load_config_with_key(&cli.config, &config_key)
+        .with_context(|| format!("failed to load config {}", cli.config))?;
+    let log_format = config.log_format;
+    telemetry::init(log_format).expect("failed to initialize tracing");
+    error_sink::register_sink(Arc::new(TracingSink));
+    error_sink::install_panic_hook();
+    strategy::register_builtin();
+    secrets::register_builtin();
+    secrets::resolve_config(&mut config).await?;
+    api_client::set_timeouts(config.http_timeouts);
+    api_client::set_proxies(&config.proxies)?;
+    rate_limit::set_limits(config.rate_limits.clone());
+    login_guard::set_params(config.login_guard.clone());
+    ai_cache::set_params(config.ai_response_cache);
+    data::set_fallback_source(config.market_data_fallback);
+    symbols::set_exchange_quotes(&config.quote_assets);
+    calendar::set_params(config.calendar.clone());
+    sim::set_seed(config.simulation_seed);
+    currency::set_reporting_currency(&config.reporting_currency);
+    risk_override::set_webhook_secret(config.risk_webhook_secret.as_deref());
+    if let Some(dsn) = &config.sentry_dsn {
+        match SentrySink::from_dsn(dsn, "production") {
+            Ok(sink) => error_sink::register_sink(Arc::new(sink)),
+            Err(e) => tracing::warn!("⚠️ Sentry disabled: {}", e),
+        }
+    }
+    for (name, e) in notify::configure(&config.notifications) {
+        tracing::warn!("⚠️ Notifier {} disabled: {}", name, e);
+    }
+
+    match cli.command {
+        None | Some(Command::Run) => run_server(&config, &cli.config, config_key, &cli.db).await?,
+        Some(command) => cli::run(command, &cli.db).await?,
+    }
+
+    Ok(())
+}
+
+async fn run_server(
+    config: &Config,
+    config_path: &str,
+    config_key: ConfigKey,
+    db_path: &str,
+) -> anyhow::Result<()> {
+    let db_params = config.database.clone();
+    let db = Database::connect(db_path, &db_params).await?;
+
+    let jwt_secret = match db.get_system_config("jwt_secret").await {
+        Ok(secret) if !secret.is_empty() => secret,
+        _ => {
+            let mut bytes = [0u8; 32];
+            rand::thread_rng().fill_bytes(&mut bytes);
+            let secret = BASE64.encode(bytes);
+            db.set_system_config("jwt_secret", &secret).await?;
+            tracing::info!("🔑 已生成新的JWT密钥");
+            secret
+        }
+    };
+    auth::set_jwt_secret(&jwt_secret);
+    if db
+        .get_system_config("admin_mode")
+        .await
+        .is_ok_and(|v| v == "true")
+    {
+        // 管理权限只来自令牌中的角色；旧的全局开关不再放行请求
+        tracing::warn!("⚠️ admin_mode 已不再生效，请用管理员账户登录 (admin@localhost)");
+    }
+
+    audit::set_database(db.clone());
+    let reloader = hot_reload::Reloader::start_with_key(config_path, config_key, &db).await;
+    maintenance::sync(&db).await?;
+    risk_override::sync(&db).await?;
+
+    let scheduler = Scheduler::new(db.clone());
+    let watch_db = db.clone();
+    scheduler
+        .register(
+            "symbol_watch",
+            "@every 10m",
+            Duration::from_secs(30),
+            move || {
+                let db = watch_db.clone();
+                async move {
+                    let client = api_client::ApiClient::new();
+                    let alerts = symbol_watch::check(&db, &client).await?;
+                    symbol_watch::alert_users(&db, &alerts).await;
+                    Ok(())
+                }
+            },
+        )
+        .await?;
+    let maintenance_db = db.clone();
+    scheduler
+        .register("maintenance", "@every 30s", Duration::ZERO, move || {
+            let db = maintenance_db.clone();
+            async move {
+                maintenance::sync(&db).await?;
+                Ok(())
+            }
+        })
+        .await?;
+    let risk_db = db.clone();
+    scheduler
+        .register("risk_overrides", "@every 30s", Duration::ZERO, move || {
+            let db = risk_db.clone();
+            async move {
+                risk_override::sync(&db).await?;
+                Ok(())
+            }
+        })
+        .await?;
+    let reload_db = db.clone();
+    let reloader = Arc::new(tokio::sync::Mutex::new(reloader));
+    scheduler
+        .register("config_reload", "@every 30s", Duration::ZERO, move || {
+            let db = reload_db.clone();
+            let reloader = reloader.clone();
+            async move {
+                reloader.lock().await.poll(&db).await;
+                Ok(())
+            }
+        })
+        .await?;
+    let pause_db = db.clone();
+    scheduler
+        .register("trader_pauses", "@every 1m", Duration::ZERO, move || {
+            let db = pause_db.clone();
+            async move {
+                pause::apply_due(&db).await?;
+                Ok(())
+            }
+        })
+        .await?;
+
+    let settle_db = db.clone();
+    let tournament_params = config.tournament;
+    scheduler
+        .register(
+            "tournament_settle",
+            "@every 15m",
+            Duration::from_secs(60),
+            move || {
+                let db = settle_db.clone();
+                async move {
+                    tournament::settle_due(&db, &tournament_params).await?;
+                    Ok(())
+                }
+            },
+        )
+        .await?;
+    let accuracy_db = db.clone();
+    let accuracy_params = config.decision_accuracy;
+    scheduler
+        .register(
+            "decision_accuracy_settle",
+            "@every 15m",
+            Duration::from_secs(60),
+            move || {
+                let db = accuracy_db.clone();
+                async move {
+                    accuracy::settle_due(&db, &accuracy_params).await?;
+                    Ok(())
+                }
+            },
+        )
+        .await?;
+    let tournament_db = db.clone();
+    scheduler
+        .register(
+            "model_tournament",
+            "0 4 * * *",
+            Duration::from_secs(300),
+            move || {
+                let db = tournament_db.clone();
+                async move {
+                    tournament::run_all(&db, &tournament_params).await?;
+                    Ok(())
+                }
+            },
+        )
+        .await?;
+
+    let audit_db = db.clone();
+    let audit_params = config.execution_audit;
+    scheduler
+        .register(
+            "execution_audit_prune",
+            "@every 1h",
+            Duration::from_secs(60),
+            move || {
+                let db = audit_db.clone();
+                async move {
+                    audit::prune(&db, &audit_params).await?;
+                    Ok(())
+                }
+            },
+        )
+        .await?;
+
+    let calendar_params = config.calendar.clone();
+    if !calendar_params.url.is_empty() {
+        let calendar_db = db.clone();
+        scheduler
+            .register(
+                "economic_calendar",
+                "@every 30m",
+                Duration::from_secs(30),
+                move || {
+                    let db = calendar_db.clone();
+                    let params = calendar_params.clone();
+                    async move {
+                        calendar::refresh(&params).await?;
+                        calendar::schedule_blackouts(&db, &params, chrono::Utc::now()).await?;
+                        Ok(())
+                    }
+                },
+            )
+            .await?;
+    }
+
+    let universe_params = config.universe.clone();
+    if universe_params.enabled {
+        let universe_db = db.clone();
+        let schedule = universe_params.schedule.clone();
+        scheduler
+            .register(
+                "universe_refresh",
+                &schedule,
+                Duration::from_secs(300),
+                move || {
+                    let db = universe_db.clone();
+                    let params = universe_params.clone();
+                    async move {
+                        let client = api_client::ApiClient::new();
+                        if let Some(change) = universe::refresh(&db, &client, &params).await? {
+                            universe::notify_traders(&db, &change).await?;
+                        }
+                        Ok(())
+                    }
+                },
+            )
+            .await?;
+    }
+
+    let report_params = config.daily_report.clone();
+    if report_params.enabled {
+        let report_db = db.clone();
+        scheduler
+            .register(
+                "daily_report",
+                &report_params.schedule,
+                Duration::ZERO,
+                move || {
+                    let db = report_db.clone();
+                    async move {
+                        daily_report::run(&db).await?;
+                        Ok(())
+                    }
+                },
+            )
+            .await?;
+    }
+
+    let runner_config = RunnerConfig::from_config(config)?;
+    let cipher = runner_config.cipher.clone();
+    let market_stream = stream::start(config.market_stream.clone());
+    let mut runner = Runner::new(db.clone(), runner_config);
+    // 上次退出时仍在运行的交易员视为异常中断，启动时恢复其止损止盈
+    match recovery::interrupted_traders(&db).await {
+        Ok(traders) => runner.recover(&traders),
+        Err(e) => tracing::warn!("⚠️ 读取中断的交易员失败: {:#}", e),
+    }
+    let runner = runner.spawn();
+
+    let listen = match config.api_server_socket.clone() {
+        Some(path) => Listen::Unix(path),
+        None => Listen::Tcp(SocketAddr::from(([0, 0, 0, 0], config.api_server_port))),
+    };
+    let app = server::router(AppState {
+        db: db.clone(),
+        scheduler: Some(scheduler),
+        cipher,
+    });
+
+    server::serve(&listen, app, shutdown_signal()).await?;
+
+    if let Err(e) = runner.shutdown().await {
+        tracing::warn!("⚠️ 停止交易员失败: {}", e);
+    }
+    if let Some(task) = market_stream {
+        task.abort();
+    }
+    db.close().await?;
+    profiler::dump(Some("profile_report.json"));
+    Ok(())
+}
+
+// Resolves on Ctrl-C, or on SIGTERM where there is one.
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{SignalKind, signal};
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => {}
+                    _ = terminate.recv() => {}
+                }
+            }
+            Err(_) => {
+                let _ = tokio::signal::ctrl_c().await;
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = tokio::signal::ctrl_c().await;
+    tracing::info!("🛑 收到退出信号，正在关闭...");
 }
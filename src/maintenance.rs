@@ -0,0 +1,123 @@
+//! Maintenance mode.
+//!
+//! While it is on, trader cycles are skipped and the API only serves reads, so
+//! backups, schema migrations and upgrades see no writes. The state is stored
+//! in system_config and re-read by the `maintenance` scheduler job, so the CLI
+//! can toggle a running server. With a deadline set, trading resumes on its own.
+
+use std::sync::RwLock;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::database::Database;
+
+const CONFIG_KEY: &str = "maintenance";
+
+static STATE: Lazy<RwLock<Option<Maintenance>>> = Lazy::new(|| RwLock::new(None));
+
+#[derive(Error, Debug)]
+pub enum MaintenanceError {
+    #[error("Invalid maintenance duration")]
+    InvalidDuration,
+    #[error("Database error: {0}")]
+    Database(#[from] anyhow::Error),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Maintenance {
+    pub reason: String,
+    pub since: DateTime<Utc>,
+    /// Automatic resume time; `None` waits for an explicit resume.
+    pub until: Option<DateTime<Utc>>,
+}
+
+impl Maintenance {
+    fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.until.is_some_and(|until| until <= now)
+    }
+}
+
+fn set(state: Option<Maintenance>) {
+    *STATE.write().unwrap_or_else(|e| e.into_inner()) = state;
+}
+
+/// The active maintenance window, if any.
+pub fn current() -> Option<Maintenance> {
+    STATE
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+        .filter(|m| !m.is_expired(Utc::now()))
+}
+
+pub fn is_active() -> bool {
+    current().is_some()
+}
+
+/// Turns maintenance mode on, optionally resuming after `duration`.
+pub async fn enable(
+    db: &Database,
+    reason: &str,
+    duration: Option<Duration>,
+) -> Result<Maintenance, MaintenanceError> {
+    let since = Utc::now();
+    let until = match duration {
+        Some(d) => Some(
+            since + chrono::Duration::from_std(d).map_err(|_| MaintenanceError::InvalidDuration)?,
+        ),
+        None => None,
+    };
+    let maintenance = Maintenance {
+        reason: reason.to_string(),
+        since,
+        until,
+    };
+    let json = serde_json::to_string(&maintenance).map_err(anyhow::Error::from)?;
+    db.set_system_config(CONFIG_KEY, &json).await?;
+    set(Some(maintenance.clone()));
+    tracing::warn!("🛠️ 进入维护模式: {} (预计恢复 {:?})", reason, until);
+    Ok(maintenance)
+}
+
+/// Turns maintenance mode off. Returns whether it was on.
+pub async fn resume(db: &Database) -> Result<bool, MaintenanceError> {
+    let was_active = is_active();
+    db.delete_system_config(CONFIG_KEY).await?;
+    set(None);
+    if was_active {
+        tracing::info!("✅ 退出维护模式");
+    }
+    Ok(was_active)
+}
+
+/// Reloads the stored state, picking up changes made by other processes, and
+/// clears a window whose deadline has passed.
+pub async fn sync(db: &Database) -> Result<(), MaintenanceError> {
+    let stored: Option<Maintenance> = match db.get_system_config(CONFIG_KEY).await {
+        Ok(json) => serde_json::from_str(&json).ok(),
+        Err(e) if matches!(e.downcast_ref(), Some(sqlx::Error::RowNotFound)) => None,
+        // Keep the current state rather than resuming on a read error.
+        Err(e) => return Err(e.into()),
+    };
+    match stored {
+        Some(m) if m.is_expired(Utc::now()) => {
+            db.delete_system_config(CONFIG_KEY).await?;
+            set(None);
+            tracing::info!("✅ 维护窗口已到期，自动恢复: {}", m.reason);
+        }
+        stored => {
+            if stored.is_some() != is_active() {
+                match &stored {
+                    Some(m) => tracing::warn!("🛠️ 进入维护模式: {}", m.reason),
+                    None => tracing::info!("✅ 退出维护模式"),
+                }
+            }
+            set(stored);
+        }
+    }
+    Ok(())
+}
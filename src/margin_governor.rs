@@ -0,0 +1,204 @@
+//! Portfolio-level margin governor.
+//!
+//! Traders of the same user usually share one exchange account. After syncing
+//! its account every trader reports the margin of the positions its own ledger
+//! holds (see [`own_margin`]), and it is forgotten when it stops. Before new
+//! entries are executed the governor checks the user's combined usage against
+//! their ceiling and drops or downsizes entries that would push it over.
+//! Margin granted to an entry is reserved until that trader's next report, so
+//! traders deciding in the same minute cannot both spend the same headroom.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::RwLock;
+
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+use serde::Serialize;
+
+use crate::decision::{Decision, PositionInfo};
+use crate::money::{self, Decimal};
+
+// Entries downsized below this notional are dropped instead; exchanges reject
+// tiny orders anyway.
+const MIN_ENTRY_NOTIONAL_USD: f64 = 10.0;
+
+static USERS: Lazy<RwLock<HashMap<String, UserMargin>>> = Lazy::new(|| RwLock::new(HashMap::new()));
+
+#[derive(Debug, Default)]
+struct UserMargin {
+    traders: HashMap<String, TraderMargin>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct TraderMargin {
+    margin_used: f64,
+    reserved: f64,
+    equity: f64,
+    updated_at: Option<DateTime<Utc>>,
+}
+
+/// Combined margin usage of one user's traders.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct MarginUsage {
+    pub margin_used: f64,
+    /// Margin granted to entries that are not reflected in a report yet.
+    pub reserved: f64,
+    pub equity: f64,
+    pub usage_pct: f64,
+    pub traders: usize,
+}
+
+/// Records a trader's current margin usage and account equity, replacing any
+/// reservation made for its previous entries.
+pub fn report(user_id: &str, trader_id: &str, margin_used: f64, equity: f64) {
+    let mut users = USERS.write().unwrap_or_else(|e| e.into_inner());
+    users
+        .entry(user_id.to_string())
+        .or_default()
+        .traders
+        .insert(
+            trader_id.to_string(),
+            TraderMargin {
+                margin_used,
+                reserved: 0.0,
+                equity,
+                updated_at: Some(Utc::now()),
+            },
+        );
+}
+
+/// Margin of the `positions` a trader's `ledger` accounts for. On a shared
+/// account the exchange reports every trader's positions; each trader counts
+/// only its own quantity, pro rata when several hold the same position.
+pub fn own_margin(positions: &[PositionInfo], ledger: &BTreeMap<(String, String), Decimal>) -> f64 {
+    positions
+        .iter()
+        .filter(|p| p.quantity != 0.0)
+        .filter_map(|p| {
+            let held = ledger.get(&(p.symbol.clone(), p.side.clone()))?;
+            let share = (money::to_f64(*held) / p.quantity.abs()).min(1.0);
+            Some(p.margin_used * share)
+        })
+        .sum()
+}
+
+/// Forgets a trader, e.g. when it is stopped or deleted.
+pub fn remove_trader(user_id: &str, trader_id: &str) {
+    let mut users = USERS.write().unwrap_or_else(|e| e.into_inner());
+    if let Some(user) = users.get_mut(user_id) {
+        user.traders.remove(trader_id);
+        if user.traders.is_empty() {
+            users.remove(user_id);
+        }
+    }
+}
+
+/// Current combined usage for a user.
+pub fn usage(user_id: &str) -> MarginUsage {
+    let users = USERS.read().unwrap_or_else(|e| e.into_inner());
+    users
+        .get(user_id)
+        .map(UserMargin::usage)
+        .unwrap_or_default()
+}
+
+/// Applies the user's margin ceiling to a trader's decisions. Closes and holds
+/// pass through; entries are downsized to the remaining headroom or dropped.
+/// Entries are refused until some trader of the user has reported equity. A
+/// ceiling of zero or less disables the governor.
+pub fn govern(
+    user_id: &str,
+    trader_id: &str,
+    max_usage_pct: f64,
+    decisions: Vec<Decision>,
+) -> Vec<Decision> {
+    if max_usage_pct <= 0.0 {
+        return decisions;
+    }
+
+    let mut users = USERS.write().unwrap_or_else(|e| e.into_inner());
+    let user = users.entry(user_id.to_string()).or_default();
+    let usage = user.usage();
+    let mut headroom = usage.equity * max_usage_pct / 100.0 - usage.margin_used - usage.reserved;
+    let mut granted = 0.0;
+
+    let mut allowed = Vec::with_capacity(decisions.len());
+    for mut d in decisions {
+        if !d.action.is_open() {
+            allowed.push(d);
+            continue;
+        }
+
+        // A pair trade needs margin for both legs.
+        let leverage = d.leverage.max(1) as f64;
+        let required = d.notional_usd() / leverage;
+        if required <= headroom {
+            headroom -= required;
+            granted += required;
+            allowed.push(d);
+            continue;
+        }
+
+        let notional = headroom.max(0.0) * leverage;
+        if notional < MIN_ENTRY_NOTIONAL_USD {
+            tracing::warn!(
+                "🛑 用户 {} 保证金占用已达上限 ({:.1}%/{:.1}%)，拒绝 {} {:?}",
+                user_id,
+                usage.usage_pct,
+                max_usage_pct,
+                d.symbol,
+                d.action
+            );
+            continue;
+        }
+
+        tracing::warn!(
+            "⚖️ 用户 {} 保证金余量不足，{} 仓位由 {:.2} 缩减至 {:.2} USD",
+            user_id,
+            d.symbol,
+            d.notional_usd(),
+            notional
+        );
+        d.position_size_usd *= notional / d.notional_usd();
+        granted += headroom;
+        headroom = 0.0;
+        allowed.push(d);
+    }
+
+    if granted > 0.0 {
+        user.traders
+            .entry(trader_id.to_string())
+            .or_default()
+            .reserved += granted;
+    }
+    allowed
+}
+
+impl UserMargin {
+    fn usage(&self) -> MarginUsage {
+        let margin_used = self.traders.values().map(|t| t.margin_used).sum();
+        let reserved = self.traders.values().map(|t| t.reserved).sum();
+        // Traders on a shared account all see the same equity; the most recent
+        // report is the best estimate.
+        let equity = self
+            .traders
+            .values()
+            .filter(|t| t.updated_at.is_some())
+            .max_by_key(|t| t.updated_at)
+            .map(|t| t.equity)
+            .unwrap_or_default();
+        let usage_pct = if equity > 0.0 {
+            (margin_used + reserved) / equity * 100.0
+        } else {
+            0.0
+        };
+
+        MarginUsage {
+            margin_used,
+            reserved,
+            equity,
+            usage_pct,
+            traders: self.traders.len(),
+        }
+    }
+}
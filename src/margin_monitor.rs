@@ -0,0 +1,185 @@
+//! Liquidation distance and margin ratio monitoring.
+//!
+//! While a trader waits for its next cycle its positions are checked every
+//! [`MonitorParams::interval`] against the latest mark price, from the
+//! market stream when it runs and from the exchange otherwise. Two measures
+//! are taken per position: the distance from the mark to the liquidation
+//! price, in percent of the mark, and the margin ratio, the share of the way
+//! from entry to liquidation the mark has already travelled (0 at entry, 100
+//! at liquidation). Positions the exchange reports no liquidation price for
+//! are measured against the isolated-margin estimate from entry and leverage.
+//!
+//! Crossing one of the trader's thresholds (`liquidation_alert_pct`,
+//! `margin_ratio_alert_pct`) raises a `margin_alert` event once; it is raised
+//! again only after the position has recovered. With `auto_deleverage_pct`
+//! set, each crossing also closes that share of the position.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::RwLock;
+use std::time::Duration;
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+
+use crate::database::TraderRecord;
+use crate::decision::PositionInfo;
+
+/// Group id of the trades closed by auto-deleveraging.
+pub const DELEVERAGE_GROUP: &str = "deleverage";
+
+// A position as (symbol, side).
+type PositionKey = (String, String);
+
+// Positions past a threshold per trader; alerts fire when one enters the set.
+static BREACHED: Lazy<RwLock<HashMap<String, HashSet<PositionKey>>>> =
+    Lazy::new(|| RwLock::new(HashMap::new()));
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[serde(default)]
+pub struct MonitorParams {
+    /// Time between checks of each running trader's positions; zero turns
+    /// monitoring off.
+    #[serde(with = "humantime_serde")]
+    pub interval: Duration,
+}
+
+impl Default for MonitorParams {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(30),
+        }
+    }
+}
+
+/// A trader's alert thresholds, all in percent; 0 disables each.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Thresholds {
+    /// Alert when the mark is closer than this to the liquidation price.
+    pub liquidation_alert_pct: f64,
+    /// Alert when the margin ratio reaches this.
+    pub margin_ratio_alert_pct: f64,
+    /// Share of the position closed when an alert fires.
+    pub auto_deleverage_pct: f64,
+}
+
+impl Thresholds {
+    pub fn from_trader(trader: &TraderRecord) -> Self {
+        Self {
+            liquidation_alert_pct: trader.liquidation_alert_pct,
+            margin_ratio_alert_pct: trader.margin_ratio_alert_pct,
+            auto_deleverage_pct: trader.auto_deleverage_pct,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.liquidation_alert_pct > 0.0 || self.margin_ratio_alert_pct > 0.0
+    }
+
+    pub fn is_valid(&self) -> bool {
+        [
+            self.liquidation_alert_pct,
+            self.margin_ratio_alert_pct,
+            self.auto_deleverage_pct,
+        ]
+        .iter()
+        .all(|pct| pct.is_finite() && (0.0..=100.0).contains(pct))
+    }
+
+    /// Whether `level` is past either alert threshold.
+    pub fn breached(&self, level: &MarginLevel) -> bool {
+        (self.liquidation_alert_pct > 0.0 && level.distance_pct <= self.liquidation_alert_pct)
+            || (self.margin_ratio_alert_pct > 0.0
+                && level.margin_ratio_pct >= self.margin_ratio_alert_pct)
+    }
+}
+
+/// How close one position is to liquidation.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MarginLevel {
+    pub symbol: String,
+    pub side: String,
+    pub quantity: f64,
+    pub mark_price: f64,
+    pub liquidation_price: f64,
+    /// Percent of the mark price between it and the liquidation price.
+    pub distance_pct: f64,
+    /// Percent of the way from entry to liquidation already travelled.
+    pub margin_ratio_pct: f64,
+}
+
+impl std::fmt::Display for MarginLevel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} is {:.2}% from liquidation (mark {}, liquidation {}), margin ratio {:.1}%",
+            self.symbol,
+            self.side,
+            self.distance_pct,
+            self.mark_price,
+            self.liquidation_price,
+            self.margin_ratio_pct
+        )
+    }
+}
+
+/// Measures `position` at `mark`, or at its own mark price without one.
+/// Returns `None` for positions without the prices to measure them.
+pub fn measure(position: &PositionInfo, mark: Option<f64>) -> Option<MarginLevel> {
+    let mark = mark.filter(|m| *m > 0.0).unwrap_or(position.mark_price);
+    let entry = position.entry_price;
+    let short = position.side == "short";
+    let liquidation = if position.liquidation_price > 0.0 {
+        position.liquidation_price
+    } else if position.leverage > 0 {
+        let margin = 1.0 / position.leverage as f64;
+        entry * if short { 1.0 + margin } else { 1.0 - margin }
+    } else {
+        0.0
+    };
+    if position.quantity == 0.0 || mark <= 0.0 || entry <= 0.0 || liquidation <= 0.0 {
+        return None;
+    }
+    let (to_liquidation, entry_to_liquidation) = if short {
+        (liquidation - mark, liquidation - entry)
+    } else {
+        (mark - liquidation, entry - liquidation)
+    };
+    let margin_ratio = if entry_to_liquidation > 0.0 {
+        (1.0 - to_liquidation / entry_to_liquidation) * 100.0
+    } else {
+        100.0
+    };
+    Some(MarginLevel {
+        symbol: position.symbol.clone(),
+        side: position.side.clone(),
+        quantity: position.quantity.abs(),
+        mark_price: mark,
+        liquidation_price: liquidation,
+        distance_pct: (to_liquidation / mark * 100.0).max(0.0),
+        margin_ratio_pct: margin_ratio.clamp(0.0, 100.0),
+    })
+}
+
+/// The levels among a trader's current `levels` that have just crossed a
+/// threshold. Positions back within the thresholds, or gone, are re-armed.
+pub fn crossed(
+    trader_id: &str,
+    levels: &[MarginLevel],
+    thresholds: &Thresholds,
+) -> Vec<MarginLevel> {
+    let mut breached = BREACHED.write().unwrap_or_else(|e| e.into_inner());
+    let previous = breached.remove(trader_id).unwrap_or_default();
+    let mut now = HashSet::new();
+    let mut crossed = Vec::new();
+    for level in levels.iter().filter(|l| thresholds.breached(l)) {
+        let key = (level.symbol.clone(), level.side.clone());
+        if !previous.contains(&key) {
+            crossed.push(level.clone());
+        }
+        now.insert(key);
+    }
+    if !now.is_empty() {
+        breached.insert(trader_id.to_string(), now);
+    }
+    crossed
+}
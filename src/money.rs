@@ -0,0 +1,34 @@
+//! Decimal monetary amounts.
+//!
+//! Order quantities, fill prices, fees, transfers and equity snapshots are
+//! [`Decimal`] so sizing and PnL are exact. Market data, indicators and the
+//! AI's decisions stay `f64`; values cross over through [`from_f64`] and
+//! [`to_f64`]. Amounts serialize as JSON numbers, as they did when they were
+//! floats, and are stored in the existing REAL / DOUBLE PRECISION columns.
+
+pub use rust_decimal::Decimal;
+use rust_decimal::RoundingStrategy;
+use rust_decimal::prelude::{FromPrimitive, ToPrimitive};
+
+/// The shortest decimal that round-trips to `value` (`0.1` stays `0.1`).
+/// NaN and infinities become zero.
+pub fn from_f64(value: f64) -> Decimal {
+    Decimal::from_f64(value).unwrap_or_default()
+}
+
+pub fn to_f64(value: Decimal) -> f64 {
+    value.to_f64().unwrap_or_default()
+}
+
+/// Parses an exchange's decimal string, zero when empty or malformed.
+pub fn parse(value: &str) -> Decimal {
+    value.trim().parse().unwrap_or_default()
+}
+
+/// Truncates `quantity` to `decimals` places, as exchanges accept order
+/// quantities.
+pub fn round_down(quantity: Decimal, decimals: u32) -> Decimal {
+    quantity
+        .round_dp_with_strategy(decimals, RoundingStrategy::ToZero)
+        .normalize()
+}
@@ -0,0 +1,386 @@
+//! Outbound notifications for trade and alert events.
+//!
+//! Sinks implement [`Notifier`] and are registered globally; [`notify`] fans an
+//! event out to every sink that wants its kind, in the background. Discord and
+//! generic HTTP webhooks are built in and configured through
+//! `Config::notifications`. Both post a JSON payload rendered from a template
+//! whose `{{path}}` placeholders are looked up in the serialized [`Event`],
+//! e.g. `{{message}}` or `{{details.price}}`.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value, json};
+use thiserror::Error;
+
+use crate::api_client::shared_client;
+use crate::database::Trade;
+
+const SEND_TIMEOUT: Duration = Duration::from_secs(10);
+
+static NOTIFIERS: Lazy<RwLock<Vec<Arc<dyn Notifier>>>> = Lazy::new(|| RwLock::new(Vec::new()));
+
+#[derive(Error, Debug)]
+pub enum NotifyError {
+    #[error("Invalid webhook URL: {0}")]
+    InvalidUrl(String),
+    #[error("Request failed: {0}")]
+    Request(#[from] reqwest::Error),
+    #[error("Webhook returned {0}: {1}")]
+    Status(u16, String),
+}
+
+pub type NotifyFuture<'a> = Pin<Box<dyn Future<Output = Result<(), NotifyError>> + Send + 'a>>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    /// An order filled.
+    Trade,
+    /// Something a user should look at, e.g. a delisted symbol.
+    Alert,
+}
+
+/// Something that happened to a trader, as seen by notification sinks.
+#[derive(Debug, Clone, Serialize)]
+pub struct Event {
+    pub kind: EventKind,
+    /// Machine-readable event name, e.g. "trade" or "symbol_unavailable".
+    pub name: String,
+    pub message: String,
+    pub user_id: Option<String>,
+    pub trader_id: Option<String>,
+    pub symbol: Option<String>,
+    /// Event-specific values, e.g. a fill's price and quantity.
+    pub details: Map<String, Value>,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl Event {
+    pub fn new(kind: EventKind, name: &str, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            name: name.to_string(),
+            message: message.into(),
+            user_id: None,
+            trader_id: None,
+            symbol: None,
+            details: Map::new(),
+            timestamp: Utc::now(),
+        }
+    }
+
+    pub fn alert(name: &str, message: impl Into<String>) -> Self {
+        Self::new(EventKind::Alert, name, message)
+    }
+
+    /// A recorded fill of the trader named `trader_name`.
+    pub fn trade(trader_name: &str, trade: &Trade) -> Self {
+        let message = format!(
+            "{}: {} {} {} @ {} ({})",
+            trader_name, trade.side, trade.quantity, trade.symbol, trade.price, trade.action
+        );
+        Self::new(EventKind::Trade, "trade", message)
+            .user(&trade.user_id)
+            .trader(&trade.trader_id)
+            .symbol(&trade.symbol)
+            .detail("trader_name", trader_name)
+            .detail("action", &trade.action)
+            .detail("side", &trade.side)
+            .detail("quantity", trade.quantity)
+            .detail("price", trade.price)
+            .detail("fee", trade.fee)
+            .detail("order_id", trade.order_id)
+    }
+
+    /// Replaces the message, e.g. with one in the user's language.
+    pub fn message(mut self, message: impl Into<String>) -> Self {
+        self.message = message.into();
+        self
+    }
+
+    pub fn user(mut self, user_id: &str) -> Self {
+        self.user_id = Some(user_id.to_string());
+        self
+    }
+
+    pub fn trader(mut self, trader_id: &str) -> Self {
+        self.trader_id = Some(trader_id.to_string());
+        self
+    }
+
+    pub fn symbol(mut self, symbol: &str) -> Self {
+        self.symbol = Some(symbol.to_string());
+        self
+    }
+
+    pub fn detail(mut self, key: &str, value: impl Serialize) -> Self {
+        self.details.insert(
+            key.to_string(),
+            serde_json::to_value(value).unwrap_or_default(),
+        );
+        self
+    }
+}
+
+/// Destination for events (a chat webhook, an HTTP endpoint, a test collector...).
+pub trait Notifier: Send + Sync {
+    /// Used in logs when a send fails.
+    fn name(&self) -> &str;
+
+    /// Whether the notifier wants events of `kind`.
+    fn accepts(&self, _kind: EventKind) -> bool {
+        true
+    }
+
+    fn send<'a>(&'a self, event: &'a Event) -> NotifyFuture<'a>;
+}
+
+/// Adds a notifier to the global registry.
+pub fn register(notifier: Arc<dyn Notifier>) {
+    NOTIFIERS
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .push(notifier);
+}
+
+/// Replaces the registered notifiers with the configured ones. Entries that
+/// cannot be built are skipped and returned with their error.
+pub fn configure(settings: &[NotifierSettings]) -> Vec<(String, NotifyError)> {
+    let mut notifiers: Vec<Arc<dyn Notifier>> = Vec::new();
+    let mut failed = Vec::new();
+    for s in settings {
+        match WebhookNotifier::from_settings(s) {
+            Ok(n) => notifiers.push(Arc::new(n)),
+            Err(e) => failed.push((s.display_name().to_string(), e)),
+        }
+    }
+    *NOTIFIERS.write().unwrap_or_else(|e| e.into_inner()) = notifiers;
+    failed
+}
+
+fn notifiers_for(kind: EventKind) -> Vec<Arc<dyn Notifier>> {
+    NOTIFIERS
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter()
+        .filter(|n| n.accepts(kind))
+        .cloned()
+        .collect()
+}
+
+/// Sends `event` to every notifier that accepts it and waits for all of them.
+/// Returns each notifier's name with the outcome.
+pub async fn dispatch(event: &Event) -> Vec<(String, Result<(), NotifyError>)> {
+    let notifiers = notifiers_for(event.kind);
+    let sends = notifiers.iter().map(|n| n.send(event));
+    let results = futures_util::future::join_all(sends).await;
+    notifiers
+        .iter()
+        .map(|n| n.name().to_string())
+        .zip(results)
+        .collect()
+}
+
+/// Sends `event` in the background; failures are logged.
+pub fn notify(event: Event) {
+    if notifiers_for(event.kind).is_empty() {
+        return;
+    }
+    let Ok(handle) = tokio::runtime::Handle::try_current() else {
+        return;
+    };
+    handle.spawn(async move {
+        for (name, result) in dispatch(&event).await {
+            if let Err(e) = result {
+                tracing::warn!("⚠️ 通知发送失败 ({}): {}", name, e);
+            }
+        }
+    });
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NotifierKind {
+    /// A Discord channel webhook.
+    Discord,
+    /// Any HTTP endpoint accepting a JSON POST.
+    Webhook,
+}
+
+/// One notification sink in the config, e.g.
+/// `{"kind": "discord", "url": "https://discord.com/api/webhooks/...", "events": ["trade"]}`.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct NotifierSettings {
+    pub kind: NotifierKind,
+    pub url: String,
+    /// Shown in logs; defaults to the kind.
+    #[serde(default)]
+    pub name: String,
+    /// Extra request headers, e.g. an `Authorization` token.
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    /// JSON payload with `{{path}}` placeholders. Defaults to a chat message
+    /// for Discord and to the whole event for webhooks.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub template: Option<Value>,
+    /// Event kinds to send; empty sends all.
+    #[serde(default)]
+    pub events: Vec<EventKind>,
+}
+
+impl NotifierSettings {
+    fn display_name(&self) -> &str {
+        match (self.name.as_str(), self.kind) {
+            ("", NotifierKind::Discord) => "discord",
+            ("", NotifierKind::Webhook) => "webhook",
+            (name, _) => name,
+        }
+    }
+}
+
+// Webhook URLs and headers usually carry credentials.
+impl fmt::Debug for NotifierSettings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let host = Url::parse(&self.url)
+            .ok()
+            .and_then(|u| u.host_str().map(str::to_string))
+            .unwrap_or_default();
+        f.debug_struct("NotifierSettings")
+            .field("kind", &self.kind)
+            .field("name", &self.name)
+            .field("host", &host)
+            .field("headers", &self.headers.keys().collect::<Vec<_>>())
+            .field("template", &self.template)
+            .field("events", &self.events)
+            .finish()
+    }
+}
+
+/// Posts rendered JSON payloads to a Discord or generic webhook.
+pub struct WebhookNotifier {
+    name: String,
+    url: Url,
+    headers: BTreeMap<String, String>,
+    template: Option<Value>,
+    events: Vec<EventKind>,
+}
+
+impl WebhookNotifier {
+    pub fn from_settings(settings: &NotifierSettings) -> Result<Self, NotifyError> {
+        let url = Url::parse(&settings.url)
+            .map_err(|e| NotifyError::InvalidUrl(format!("{}: {}", settings.display_name(), e)))?;
+        if !matches!(url.scheme(), "http" | "https") {
+            return Err(NotifyError::InvalidUrl(format!(
+                "{}: unsupported scheme {}",
+                settings.display_name(),
+                url.scheme()
+            )));
+        }
+        let template = settings.template.clone().or_else(|| match settings.kind {
+            NotifierKind::Discord => Some(json!({ "content": "**{{name}}** {{message}}" })),
+            NotifierKind::Webhook => None,
+        });
+        Ok(Self {
+            name: settings.display_name().to_string(),
+            url,
+            headers: settings.headers.clone(),
+            template,
+            events: settings.events.clone(),
+        })
+    }
+
+    /// The JSON body posted for `event`.
+    pub fn payload(&self, event: &Event) -> Value {
+        let event = serde_json::to_value(event).unwrap_or_default();
+        match &self.template {
+            Some(template) => render_template(template, &event),
+            None => event,
+        }
+    }
+}
+
+impl Notifier for WebhookNotifier {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn accepts(&self, kind: EventKind) -> bool {
+        self.events.is_empty() || self.events.contains(&kind)
+    }
+
+    fn send<'a>(&'a self, event: &'a Event) -> NotifyFuture<'a> {
+        Box::pin(async move {
+            let mut request = shared_client()
+                .post(self.url.clone())
+                .timeout(SEND_TIMEOUT)
+                .json(&self.payload(event));
+            for (name, value) in &self.headers {
+                request = request.header(name, value);
+            }
+            let response = request.send().await?;
+            let status = response.status();
+            if !status.is_success() {
+                let body = response.text().await.unwrap_or_default();
+                return Err(NotifyError::Status(status.as_u16(), body));
+            }
+            Ok(())
+        })
+    }
+}
+
+/// Fills `{{path}}` placeholders in every string of `template` from `event`.
+/// A string that is exactly one placeholder takes the value with its JSON
+/// type; placeholders inside text are replaced by the value as text. Unknown
+/// paths render as null or nothing.
+pub fn render_template(template: &Value, event: &Value) -> Value {
+    match template {
+        Value::String(s) => render_string(s, event),
+        Value::Array(items) => {
+            Value::Array(items.iter().map(|v| render_template(v, event)).collect())
+        }
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .map(|(k, v)| (k.clone(), render_template(v, event)))
+                .collect(),
+        ),
+        other => other.clone(),
+    }
+}
+
+fn render_string(s: &str, event: &Value) -> Value {
+    if let Some(path) = s.strip_prefix("{{").and_then(|r| r.strip_suffix("}}"))
+        && !path.contains("{{")
+    {
+        return lookup(event, path.trim()).cloned().unwrap_or(Value::Null);
+    }
+
+    let mut out = String::new();
+    let mut rest = s;
+    while let Some(start) = rest.find("{{") {
+        let Some(len) = rest[start..].find("}}") else {
+            break;
+        };
+        out.push_str(&rest[..start]);
+        match lookup(event, rest[start + 2..start + len].trim()) {
+            Some(Value::String(v)) => out.push_str(v),
+            Some(Value::Null) | None => {}
+            Some(v) => out.push_str(&v.to_string()),
+        }
+        rest = &rest[start + len + 2..];
+    }
+    out.push_str(rest);
+    Value::String(out)
+}
+
+fn lookup<'a>(value: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('.').try_fold(value, |v, key| v.get(key))
+}
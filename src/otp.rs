@@ -0,0 +1,157 @@
+//! Two-factor enrollment and recovery.
+//!
+//! Enrolling generates a new TOTP secret together with a set of one-time
+//! recovery codes, of which only the hashes are stored. The secret takes
+//! effect once the user confirms it with a code from their authenticator.
+//! Wherever a TOTP code is asked for, an unused recovery code is accepted
+//! instead and used up. A user who lost their authenticator re-enrolls after
+//! proving who they are with their password and a recovery code; that replaces
+//! the secret and invalidates every old code.
+
+use chrono::Utc;
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::auth::{self, AuthError};
+use crate::database::{Database, User};
+
+#[derive(Error, Debug)]
+pub enum OtpError {
+    #[error("Invalid verification code")]
+    InvalidCode,
+    #[error("Invalid password")]
+    InvalidPassword,
+    #[error("Two-factor authentication is already set up")]
+    AlreadyEnrolled,
+    #[error("Two-factor authentication has not been set up")]
+    NotEnrolled,
+    #[error("User {0} not found")]
+    UserNotFound(String),
+    #[error(transparent)]
+    Auth(#[from] AuthError),
+    #[error("Database error: {0}")]
+    Database(#[from] anyhow::Error),
+}
+
+pub type Result<T> = std::result::Result<T, OtpError>;
+
+/// What a user needs to set up their authenticator. The recovery codes are
+/// shown once and cannot be retrieved later.
+#[derive(Debug, Clone, Serialize)]
+pub struct Enrollment {
+    pub secret: String,
+    pub qr_code_url: String,
+    pub recovery_codes: Vec<String>,
+}
+
+/// How a second factor was proven.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SecondFactor {
+    Totp,
+    RecoveryCode,
+}
+
+/// Starts two-factor enrollment for a user who has not completed it yet.
+/// Restarting an unconfirmed enrollment replaces its secret and codes.
+pub async fn enroll(db: &Database, user: &User) -> Result<Enrollment> {
+    if user.otp_verified {
+        return Err(OtpError::AlreadyEnrolled);
+    }
+    start(db, user).await
+}
+
+/// Completes enrollment with a code from the new authenticator.
+pub async fn confirm(db: &Database, user_id: &str, code: &str) -> Result<User> {
+    let mut user = db
+        .get_user_by_id(user_id)
+        .await?
+        .ok_or_else(|| OtpError::UserNotFound(user_id.to_string()))?;
+    if user.otp_verified {
+        return Err(OtpError::AlreadyEnrolled);
+    }
+    if user.otp_secret.is_empty() {
+        return Err(OtpError::NotEnrolled);
+    }
+    if !auth::verify_otp(&user.otp_secret, code) {
+        return Err(OtpError::InvalidCode);
+    }
+    db.update_user_ota_verified(&user.id, true).await?;
+    user.otp_verified = true;
+    tracing::info!("🔐 用户 {} 已启用两步验证", user.email);
+    Ok(user)
+}
+
+/// Checks a second factor: a current TOTP code, or an unused recovery code,
+/// which is used up.
+pub async fn verify(db: &Database, user: &User, code: &str) -> Result<SecondFactor> {
+    if auth::verify_otp(&user.otp_secret, code.trim()) {
+        return Ok(SecondFactor::Totp);
+    }
+    if code.trim().is_empty()
+        || !db
+            .consume_otp_recovery_code(&user.id, &auth::hash_recovery_code(code), Utc::now())
+            .await?
+    {
+        return Err(OtpError::InvalidCode);
+    }
+    let remaining = db.count_otp_recovery_codes(&user.id).await?;
+    tracing::warn!(
+        "🔑 用户 {} 使用了一个恢复码，剩余 {} 个",
+        user.email,
+        remaining
+    );
+    Ok(SecondFactor::RecoveryCode)
+}
+
+/// Replaces an enrolled user's recovery codes after checking a second factor.
+pub async fn regenerate_recovery_codes(
+    db: &Database,
+    user: &User,
+    code: &str,
+) -> Result<Vec<String>> {
+    if !user.otp_verified {
+        return Err(OtpError::NotEnrolled);
+    }
+    verify(db, user, code).await?;
+    issue_recovery_codes(db, user).await
+}
+
+/// Resets a user's two-factor setup once they have proven who they are with
+/// their password and, if enrolled, a TOTP or recovery code. The old secret
+/// and codes stop working; the new secret has to be confirmed like a first
+/// enrollment.
+pub async fn re_enroll(
+    db: &Database,
+    user: &User,
+    password: &str,
+    code: &str,
+) -> Result<Enrollment> {
+    if !auth::check_password(password, &user.password_hash) {
+        return Err(OtpError::InvalidPassword);
+    }
+    if user.otp_verified {
+        verify(db, user, code).await?;
+    }
+    let enrollment = start(db, user).await?;
+    tracing::info!("🔐 用户 {} 已重置两步验证", user.email);
+    Ok(enrollment)
+}
+
+async fn start(db: &Database, user: &User) -> Result<Enrollment> {
+    let secret = auth::generate_otp_secret()?;
+    db.reset_user_otp(&user.id, &secret).await?;
+    let recovery_codes = issue_recovery_codes(db, user).await?;
+    Ok(Enrollment {
+        qr_code_url: auth::get_otp_qrcode_url(&secret, &user.email),
+        secret,
+        recovery_codes,
+    })
+}
+
+async fn issue_recovery_codes(db: &Database, user: &User) -> Result<Vec<String>> {
+    let codes = auth::generate_recovery_codes();
+    let hashes: Vec<String> = codes.iter().map(|c| auth::hash_recovery_code(c)).collect();
+    db.replace_otp_recovery_codes(&user.id, &hashes).await?;
+    Ok(codes)
+}
@@ -0,0 +1,139 @@
+//! One user's traders at a glance.
+//!
+//! Gathers what the dashboard shows for every trader of a user into one
+//! read-only response: its settings, its positions and account as of its last
+//! cycle, today's PnL, its risk state, a summary of its last decision and
+//! today's AI spend, plus the user-wide risk override and margin usage.
+//! Nothing here calls an exchange or the AI; live state is only as fresh as
+//! the last cycle.
+//!
+//! "Today" is the user's local day for PnL (net of transfers, from the equity
+//! snapshots taken since local midnight) and the UTC day for AI spend, which
+//! is metered per UTC day.
+
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::Serialize;
+
+use crate::database::{AiUsage, Database, TraderRecord};
+use crate::decision::PositionInfo;
+use crate::logger::{self, DecisionRecord, RecordKey};
+use crate::loss_limits::{self, LimitState};
+use crate::margin_governor::{self, MarginUsage};
+use crate::money::Decimal;
+use crate::performance::{self, EquityPoint, ReturnSummary};
+use crate::risk_override::{self, UserOverride};
+use crate::timezone;
+
+#[derive(Debug, Serialize)]
+pub struct Overview {
+    pub generated_at: DateTime<Utc>,
+    /// The user's local day PnL is counted over.
+    pub day: NaiveDate,
+    pub traders: Vec<TraderOverview>,
+    /// Sum of the traders' PnL today.
+    pub pnl_today: Decimal,
+    pub ai_cost_today_usd: f64,
+    pub risk_override: UserOverride,
+    pub margin: MarginUsage,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TraderOverview {
+    pub trader: TraderRecord,
+    /// Equity at the last cycle; 0 before the first.
+    pub equity: f64,
+    pub positions: Vec<PositionInfo>,
+    /// `None` until the trader has an equity snapshot today.
+    pub pnl_today: Option<ReturnSummary>,
+    pub loss_limits: LimitState,
+    pub last_decision: Option<DecisionSummary>,
+    pub ai_usage_today: AiUsage,
+}
+
+/// The gist of a trader's last cycle.
+#[derive(Debug, Clone, Serialize)]
+pub struct DecisionSummary {
+    pub cycle_number: i32,
+    pub timestamp: DateTime<Utc>,
+    pub success: bool,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub error_message: String,
+    /// What was executed, e.g. "open_long BTCUSDT 0.01 @ 100".
+    pub executions: String,
+}
+
+impl From<&DecisionRecord> for DecisionSummary {
+    fn from(record: &DecisionRecord) -> Self {
+        Self {
+            cycle_number: record.cycle_number(),
+            timestamp: record.timestamp(),
+            success: record.is_success(),
+            error_message: record.error_message().to_string(),
+            executions: record.execution_summary(),
+        }
+    }
+}
+
+/// Reads the overview of `user_id`'s traders at `now`. Encrypted decision
+/// records are decrypted with `key`.
+pub async fn collect(
+    db: &Database,
+    user_id: &str,
+    key: Option<&RecordKey>,
+    now: DateTime<Utc>,
+) -> anyhow::Result<Overview> {
+    let tz = db
+        .get_user_by_id(user_id)
+        .await?
+        .map(|u| u.tz())
+        .unwrap_or(chrono_tz::UTC);
+    let day = timezone::local_date(tz, now);
+    let (day_start, _) = timezone::day_bounds(tz, now);
+    let usage = db.get_ai_usage(user_id, now.date_naive()).await?;
+
+    let mut traders = Vec::new();
+    for trader in db.get_traders(user_id).await? {
+        let last = logger::load_records(db, user_id, &trader.id, 1, key)
+            .await?
+            .into_iter()
+            .next();
+        let snapshots = db
+            .get_pnl_snapshots(user_id, &trader.id, Some(day_start))
+            .await?;
+        let transfers = db.get_transfers(user_id, &trader.id).await?;
+        let points: Vec<EquityPoint> = snapshots.iter().map(EquityPoint::from).collect();
+        let ai_usage_today = usage
+            .iter()
+            .find(|u| u.trader_id == trader.id)
+            .cloned()
+            .unwrap_or_else(|| AiUsage {
+                user_id: user_id.to_string(),
+                trader_id: trader.id.clone(),
+                day: now.date_naive().to_string(),
+                ..Default::default()
+            });
+        traders.push(TraderOverview {
+            equity: last.as_ref().map_or(0.0, |r| r.total_equity()),
+            positions: last.as_ref().map(|r| r.positions()).unwrap_or_default(),
+            pnl_today: performance::summarize(&points, &transfers),
+            loss_limits: loss_limits::state(&trader.id),
+            last_decision: last.as_ref().map(DecisionSummary::from),
+            ai_usage_today,
+            trader,
+        });
+    }
+
+    Ok(Overview {
+        generated_at: now,
+        day,
+        pnl_today: traders
+            .iter()
+            .filter_map(|t| t.pnl_today.as_ref())
+            .map(|r| r.pnl)
+            .sum(),
+        ai_cost_today_usd: usage.iter().map(|u| u.cost_usd).sum(),
+        risk_override: risk_override::for_user(user_id),
+        margin: margin_governor::usage(user_id),
+        traders,
+    })
+}
@@ -0,0 +1,154 @@
+//! Scheduled pause/resume windows for traders.
+//!
+//! A window stops a trader at `pause_at` and, if `resume_at` is set, starts it
+//! again at that time (e.g. pause Friday 22:00 UTC, resume Monday 00:00 UTC).
+//! Windows are stored in the database and applied by the `trader_pauses`
+//! scheduler job, so they survive restarts. A trader that was already stopped
+//! when its window began is left stopped when the window ends.
+
+use chrono::{DateTime, Utc};
+use thiserror::Error;
+
+use crate::audit_log;
+use crate::database::{Database, PauseWindow};
+
+pub const PENDING: &str = "pending";
+pub const PAUSED: &str = "paused";
+pub const DONE: &str = "done";
+pub const CANCELLED: &str = "cancelled";
+
+#[derive(Error, Debug)]
+pub enum PauseError {
+    #[error("resume_at must be after pause_at")]
+    InvalidWindow,
+    #[error("Pause window {0} not found")]
+    NotFound(i64),
+    #[error("Pause window {0} is already {1}")]
+    AlreadyFinished(i64, String),
+    #[error("Database error: {0}")]
+    Database(#[from] anyhow::Error),
+}
+
+/// Schedules a new window for a trader.
+pub async fn schedule(
+    db: &Database,
+    user_id: &str,
+    trader_id: &str,
+    pause_at: DateTime<Utc>,
+    resume_at: Option<DateTime<Utc>>,
+    reason: &str,
+) -> Result<PauseWindow, PauseError> {
+    if resume_at.is_some_and(|resume| resume <= pause_at) {
+        return Err(PauseError::InvalidWindow);
+    }
+    let window = db
+        .create_pause_window(user_id, trader_id, pause_at, resume_at, reason)
+        .await?;
+    tracing::info!(
+        "⏸️ 交易员 {} 已安排暂停窗口 #{} ({} → {:?})",
+        trader_id,
+        window.id,
+        pause_at,
+        resume_at
+    );
+    Ok(window)
+}
+
+/// Cancels a window. Cancelling an active pause resumes the trader right away
+/// if it was running before.
+pub async fn cancel(db: &Database, user_id: &str, id: i64) -> Result<PauseWindow, PauseError> {
+    let mut window = db
+        .get_pause_window(user_id, id)
+        .await?
+        .ok_or(PauseError::NotFound(id))?;
+
+    match window.state.as_str() {
+        PENDING => {}
+        PAUSED => {
+            if window.was_running {
+                db.update_trader_status(&window.user_id, &window.trader_id, true)
+                    .await?;
+                audit_log::trader_status(db, "", audit_log::SOURCE_SYSTEM, &window.trader_id, true)
+                    .await;
+                tracing::info!("▶️ 交易员 {} 暂停已取消，恢复运行", window.trader_id);
+            }
+        }
+        state => return Err(PauseError::AlreadyFinished(id, state.to_string())),
+    }
+
+    db.update_pause_window_state(id, CANCELLED, window.was_running)
+        .await?;
+    window.state = CANCELLED.to_string();
+    Ok(window)
+}
+
+/// Applies every window that is due: pauses traders whose window has started
+/// and resumes those whose window has ended. Returns how many windows changed.
+pub async fn apply_due(db: &Database) -> anyhow::Result<usize> {
+    let now = Utc::now();
+    let mut applied = 0;
+    for window in db.get_due_pause_windows(now).await? {
+        // One broken window (e.g. its trader was deleted) must not hold up the rest.
+        match apply(db, &window, now).await {
+            Ok(()) => applied += 1,
+            Err(e) => tracing::warn!("⚠️ 执行暂停窗口 #{} 失败: {:#}", window.id, e),
+        }
+    }
+    Ok(applied)
+}
+
+async fn apply(db: &Database, window: &PauseWindow, now: DateTime<Utc>) -> anyhow::Result<()> {
+    match window.state.as_str() {
+        PENDING => {
+            let was_running = db
+                .get_trader(&window.user_id, &window.trader_id)
+                .await?
+                .is_some_and(|t| t.is_running);
+            if was_running {
+                db.update_trader_status(&window.user_id, &window.trader_id, false)
+                    .await?;
+                audit_log::trader_status(
+                    db,
+                    "",
+                    audit_log::SOURCE_SYSTEM,
+                    &window.trader_id,
+                    false,
+                )
+                .await;
+            }
+            tracing::info!(
+                "⏸️ 交易员 {} 进入暂停窗口 #{} {}",
+                window.trader_id,
+                window.id,
+                window.reason
+            );
+
+            // A window whose end has already passed (e.g. the process was down)
+            // is finished in the same pass.
+            if window.resume_at.is_some_and(|r| r <= now) {
+                return finish(db, window, was_running).await;
+            }
+            db.update_pause_window_state(window.id, PAUSED, was_running)
+                .await?;
+        }
+        PAUSED => finish(db, window, window.was_running).await?,
+        _ => {}
+    }
+    Ok(())
+}
+
+async fn finish(db: &Database, window: &PauseWindow, was_running: bool) -> anyhow::Result<()> {
+    if was_running {
+        db.update_trader_status(&window.user_id, &window.trader_id, true)
+            .await?;
+        audit_log::trader_status(db, "", audit_log::SOURCE_SYSTEM, &window.trader_id, true).await;
+    }
+    db.update_pause_window_state(window.id, DONE, was_running)
+        .await?;
+    tracing::info!(
+        "▶️ 交易员 {} 暂停窗口 #{} 结束",
+        window.trader_id,
+        window.id
+    );
+    Ok(())
+}
@@ -0,0 +1,573 @@
+//! Account returns that are not distorted by deposits and withdrawals.
+//!
+//! A balance that goes from 1000 to 1500 after a 500 deposit made no money.
+//! Transfers are recorded per trader (detected from the exchange's income
+//! history or entered manually), valued in the equity's asset when they are
+//! recorded, and removed from equity changes; the
+//! time-weighted return chains the per-period returns between equity
+//! observations so the timing and size of flows does not skew the result.
+//!
+//! Equity curves bucket the snapshots for charting and track the running
+//! drawdown, also net of transfers so a withdrawal is not a loss.
+//!
+//! Execution quality compares each fill with the price the trader saw when it
+//! decided, and with how long the order took to fill.
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Duration, TimeZone, Timelike, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+
+use crate::currency::{self, CurrencyError};
+use crate::database::{AccountTransfer, Database, PnlSnapshot, Trade};
+use crate::money::{self, Decimal};
+use crate::types::IncomeRecord;
+
+/// Income type of transfers in and out of the futures wallet.
+pub const INCOME_TRANSFER: &str = "TRANSFER";
+/// Asset equity snapshots are valued in; only transfers in it count as flows.
+pub const EQUITY_ASSET: &str = "USDT";
+/// Most points [`EquityRange::last_days`] gives a curve.
+const MAX_CURVE_POINTS: i64 = 500;
+/// Bucket widths in minutes a curve picks from when none is asked for.
+const CURVE_BUCKETS: [i64; 7] = [1, 5, 15, 60, 240, 720, 1440];
+
+/// Account equity observed at a point in time.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct EquityPoint {
+    pub at: DateTime<Utc>,
+    pub equity: Decimal,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ReturnSummary {
+    pub start_equity: Decimal,
+    pub end_equity: Decimal,
+    /// Deposits minus withdrawals over the period.
+    pub net_deposits: Decimal,
+    /// Equity change that came from trading.
+    pub pnl: Decimal,
+    /// `pnl` relative to the starting equity plus deposits, in percent.
+    pub simple_return_pct: f64,
+    /// Time-weighted return over the period, in percent.
+    pub time_weighted_return_pct: f64,
+}
+
+/// Picks the deposits and withdrawals out of an income history, in the asset
+/// each was made in.
+pub fn transfers_from_income(
+    user_id: &str,
+    trader_id: &str,
+    income: &[IncomeRecord],
+) -> Vec<AccountTransfer> {
+    income
+        .iter()
+        .filter(|r| r.income_type == INCOME_TRANSFER)
+        .filter_map(|r| {
+            let amount = r
+                .income
+                .trim()
+                .parse::<Decimal>()
+                .ok()
+                .filter(|a| !a.is_zero())?;
+            let occurred_at = Utc.timestamp_millis_opt(r.time).single()?;
+            Some(AccountTransfer {
+                user_id: user_id.to_string(),
+                trader_id: trader_id.to_string(),
+                amount,
+                asset: r.asset.clone(),
+                occurred_at,
+                source: "income".to_string(),
+                external_id: Some(r.tran_id.to_string()),
+                note: r.info.clone(),
+                ..Default::default()
+            })
+        })
+        .collect()
+}
+
+/// Values a transfer in [`EQUITY_ASSET`] at the current rate, keeping the
+/// original amount in its note. Transfers already in it are returned as is.
+pub async fn in_equity_asset(
+    mut transfer: AccountTransfer,
+) -> Result<AccountTransfer, CurrencyError> {
+    let asset = transfer.asset.trim().to_uppercase();
+    if asset == EQUITY_ASSET {
+        transfer.asset = asset;
+        return Ok(transfer);
+    }
+    let value = currency::convert(money::to_f64(transfer.amount), &asset, EQUITY_ASSET).await?;
+    let original = format!("{} {}", transfer.amount, asset);
+    transfer.note = if transfer.note.is_empty() {
+        original
+    } else {
+        format!("{} ({})", transfer.note, original)
+    };
+    transfer.amount = money::from_f64(value);
+    transfer.asset = EQUITY_ASSET.to_string();
+    Ok(transfer)
+}
+
+/// Stores transfers found in an income history, skipping ones already recorded.
+/// Returns how many were new.
+pub async fn sync_income_transfers(
+    db: &Database,
+    user_id: &str,
+    trader_id: &str,
+    income: &[IncomeRecord],
+) -> anyhow::Result<usize> {
+    let mut added = 0;
+    for transfer in transfers_from_income(user_id, trader_id, income) {
+        let transfer = match in_equity_asset(transfer.clone()).await {
+            Ok(transfer) => transfer,
+            Err(e) => {
+                tracing::warn!(
+                    "⚠️ 交易员 {} 的资金划转 {} {} 无法折算，已跳过: {}",
+                    trader_id,
+                    transfer.amount,
+                    transfer.asset,
+                    e
+                );
+                continue;
+            }
+        };
+        if db.record_transfer(&transfer).await? {
+            tracing::info!(
+                "💸 交易员 {} 检测到资金划转 {:+.2} {}",
+                trader_id,
+                transfer.amount,
+                transfer.asset
+            );
+            added += 1;
+        }
+    }
+    Ok(added)
+}
+
+// Transfers in `(from, to]` that the equity series can account for.
+fn flows_between(
+    transfers: &[AccountTransfer],
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> impl Iterator<Item = Decimal> {
+    transfers
+        .iter()
+        .filter(move |t| t.occurred_at > from && t.occurred_at <= to)
+        .filter(|t| t.asset.trim().eq_ignore_ascii_case(EQUITY_ASSET))
+        .map(|t| t.amount)
+}
+
+/// Sum of transfers in [`EQUITY_ASSET`] in `(from, to]`.
+pub fn net_flows(transfers: &[AccountTransfer], from: DateTime<Utc>, to: DateTime<Utc>) -> Decimal {
+    flows_between(transfers, from, to).sum()
+}
+
+/// Time-weighted return, as a fraction, over equity points sorted by time.
+///
+/// Flows between two observations are assumed to arrive at the end of that
+/// period, so each sub-period return is `(end - flows) / start - 1`. Periods
+/// that start from zero equity have no defined return and are skipped.
+pub fn time_weighted_return(points: &[EquityPoint], transfers: &[AccountTransfer]) -> Option<f64> {
+    if points.len() < 2 {
+        return None;
+    }
+
+    let mut growth = 1.0;
+    for pair in points.windows(2) {
+        let (start, end) = (pair[0], pair[1]);
+        if start.equity <= Decimal::ZERO {
+            continue;
+        }
+        let flows = net_flows(transfers, start.at, end.at);
+        growth *= money::to_f64((end.equity - flows) / start.equity);
+    }
+    Some(growth - 1.0)
+}
+
+/// Summarizes returns over the span of `points`, net of transfers.
+pub fn summarize(points: &[EquityPoint], transfers: &[AccountTransfer]) -> Option<ReturnSummary> {
+    let (first, last) = (points.first()?, points.last()?);
+    let net_deposits = net_flows(transfers, first.at, last.at);
+    let deposits: Decimal = flows_between(transfers, first.at, last.at)
+        .filter(|amount| *amount > Decimal::ZERO)
+        .sum();
+
+    let pnl = last.equity - first.equity - net_deposits;
+    let capital = first.equity + deposits;
+    let simple_return_pct = if capital > Decimal::ZERO {
+        money::to_f64(pnl / capital * Decimal::ONE_HUNDRED)
+    } else {
+        0.0
+    };
+
+    Some(ReturnSummary {
+        start_equity: first.equity,
+        end_equity: last.equity,
+        net_deposits,
+        pnl,
+        simple_return_pct,
+        time_weighted_return_pct: time_weighted_return(points, transfers).unwrap_or(0.0) * 100.0,
+    })
+}
+
+/// Headline numbers for one trader over a period.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct TraderStats {
+    /// From the equity snapshots; `None` until there are any.
+    pub returns: Option<ReturnSummary>,
+    pub trades: usize,
+    /// Traded notional in the quote asset.
+    pub volume: Decimal,
+    pub fees: Decimal,
+}
+
+impl From<&PnlSnapshot> for EquityPoint {
+    fn from(snapshot: &PnlSnapshot) -> Self {
+        Self {
+            at: snapshot.taken_at,
+            equity: snapshot.total_equity,
+        }
+    }
+}
+
+/// Summarizes a trader's equity snapshots, transfers and fills.
+pub fn trader_stats(
+    snapshots: &[PnlSnapshot],
+    transfers: &[AccountTransfer],
+    trades: &[Trade],
+) -> TraderStats {
+    let points: Vec<EquityPoint> = snapshots.iter().map(EquityPoint::from).collect();
+    TraderStats {
+        returns: summarize(&points, transfers),
+        trades: trades.len(),
+        volume: trades.iter().map(|t| t.price * t.quantity).sum(),
+        fees: trades.iter().map(|t| t.fee).sum(),
+    }
+}
+
+/// The span of an equity curve and the width of its buckets.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EquityRange {
+    pub since: DateTime<Utc>,
+    pub until: DateTime<Utc>,
+    pub bucket: Duration,
+}
+
+impl EquityRange {
+    /// The last `days` up to now, in the narrowest bucket that keeps the
+    /// curve within [`MAX_CURVE_POINTS`].
+    pub fn last_days(days: i64) -> Self {
+        let until = Utc::now();
+        let minutes = days.max(1) * 24 * 60;
+        let bucket = CURVE_BUCKETS
+            .into_iter()
+            .find(|b| minutes / b <= MAX_CURVE_POINTS)
+            .unwrap_or(CURVE_BUCKETS[CURVE_BUCKETS.len() - 1]);
+        Self {
+            since: until - Duration::days(days.max(1)),
+            until,
+            bucket: Duration::minutes(bucket),
+        }
+    }
+
+    // Start of the bucket `at` falls in, counted from the Unix epoch.
+    fn bucket_start(&self, at: DateTime<Utc>) -> DateTime<Utc> {
+        let width = self.bucket.num_seconds().max(1);
+        let start = at.timestamp().div_euclid(width) * width;
+        Utc.timestamp_opt(start, 0).single().unwrap_or(at)
+    }
+}
+
+/// Equity at the end of one bucket.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct CurvePoint {
+    /// Start of the bucket.
+    pub at: DateTime<Utc>,
+    /// The last equity snapshot in the bucket.
+    pub equity: Decimal,
+    /// Highest equity so far, moved by the transfers since it was reached.
+    pub peak: Decimal,
+    /// How far `equity` is below `peak`, in percent.
+    pub drawdown_pct: f64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct EquityCurve {
+    pub bucket_minutes: i64,
+    pub points: Vec<CurvePoint>,
+    /// Deepest drawdown across every snapshot in the range, in percent.
+    pub max_drawdown_pct: f64,
+    /// Drawdown at the last snapshot, in percent.
+    pub current_drawdown_pct: f64,
+}
+
+/// Buckets `snapshots` (sorted by time) over `range`, tracking the running
+/// drawdown from every snapshot rather than only the bucket closes.
+pub fn equity_curve(
+    snapshots: &[PnlSnapshot],
+    transfers: &[AccountTransfer],
+    range: &EquityRange,
+) -> EquityCurve {
+    let mut curve = EquityCurve {
+        bucket_minutes: range.bucket.num_minutes(),
+        ..Default::default()
+    };
+    let in_range = snapshots
+        .iter()
+        .filter(|s| s.taken_at >= range.since && s.taken_at <= range.until);
+    // The peak is kept net of transfers since the first snapshot, so deposits
+    // do not raise it and withdrawals do not count as drawdown.
+    let mut first: Option<DateTime<Utc>> = None;
+    let mut net_peak: Option<Decimal> = None;
+    for snapshot in in_range {
+        let start = *first.get_or_insert(snapshot.taken_at);
+        let flows = net_flows(transfers, start, snapshot.taken_at);
+        let net = snapshot.total_equity - flows;
+        let peak = net_peak.map_or(net, |p| p.max(net));
+        net_peak = Some(peak);
+        let peak = peak + flows;
+        let drawdown_pct = if peak > Decimal::ZERO && snapshot.total_equity < peak {
+            money::to_f64((peak - snapshot.total_equity) / peak * Decimal::ONE_HUNDRED)
+        } else {
+            0.0
+        };
+        curve.max_drawdown_pct = curve.max_drawdown_pct.max(drawdown_pct);
+        curve.current_drawdown_pct = drawdown_pct;
+
+        let point = CurvePoint {
+            at: range.bucket_start(snapshot.taken_at),
+            equity: snapshot.total_equity,
+            peak,
+            drawdown_pct,
+        };
+        match curve.points.last_mut() {
+            Some(last) if last.at == point.at => *last = point,
+            _ => curve.points.push(point),
+        }
+    }
+    curve
+}
+
+/// Slippage and fill latency over a set of fills.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ExecutionStats {
+    pub fills: usize,
+    /// Average slippage against the decision-time price, in basis points;
+    /// positive means a worse price than expected.
+    pub avg_slippage_bps: f64,
+    pub worst_slippage_bps: f64,
+    /// What slippage cost in the quote asset; negative when it paid.
+    pub slippage_cost: Decimal,
+    pub avg_latency_ms: f64,
+    pub max_latency_ms: i64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ExecutionQuality {
+    pub overall: ExecutionStats,
+    pub by_symbol: BTreeMap<String, ExecutionStats>,
+    /// Keyed by hour of day (0-23) in the user's timezone.
+    pub by_hour: BTreeMap<u32, ExecutionStats>,
+}
+
+impl ExecutionStats {
+    fn add(&mut self, slippage_bps: f64, cost: Decimal, latency_ms: i64) {
+        let n = self.fills as f64;
+        self.avg_slippage_bps = (self.avg_slippage_bps * n + slippage_bps) / (n + 1.0);
+        self.avg_latency_ms = (self.avg_latency_ms * n + latency_ms as f64) / (n + 1.0);
+        self.worst_slippage_bps = if self.fills == 0 {
+            slippage_bps
+        } else {
+            self.worst_slippage_bps.max(slippage_bps)
+        };
+        self.max_latency_ms = self.max_latency_ms.max(latency_ms);
+        self.slippage_cost += cost;
+        self.fills += 1;
+    }
+}
+
+/// Slippage of one fill in basis points, signed so that positive is adverse.
+/// `None` for fills recorded without an expected price.
+pub fn slippage_bps(trade: &Trade) -> Option<f64> {
+    if trade.expected_price <= Decimal::ZERO || trade.price <= Decimal::ZERO {
+        return None;
+    }
+    let bps = money::to_f64(
+        (trade.price - trade.expected_price) / trade.expected_price * Decimal::from(10_000),
+    );
+    Some(if trade.side == "buy" { bps } else { -bps })
+}
+
+/// Aggregates slippage and latency per symbol and per hour of day. Fills
+/// without an expected price are left out.
+pub fn execution_quality(trades: &[Trade], tz: Tz) -> ExecutionQuality {
+    let mut quality = ExecutionQuality::default();
+    for trade in trades {
+        let Some(bps) = slippage_bps(trade) else {
+            continue;
+        };
+        let cost = (trade.price - trade.expected_price) * trade.quantity;
+        let cost = if trade.side == "buy" { cost } else { -cost };
+        let hour = trade.executed_at.with_timezone(&tz).hour();
+        for stats in [
+            &mut quality.overall,
+            quality.by_symbol.entry(trade.symbol.clone()).or_default(),
+            quality.by_hour.entry(hour).or_default(),
+        ] {
+            stats.add(bps, cost, trade.fill_latency_ms);
+        }
+    }
+    quality
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(hour: i64) -> DateTime<Utc> {
+        Utc.timestamp_opt(1_700_000_000, 0).unwrap() + Duration::hours(hour)
+    }
+
+    fn point(hour: i64, equity: i64) -> EquityPoint {
+        EquityPoint {
+            at: at(hour),
+            equity: Decimal::from(equity),
+        }
+    }
+
+    fn transfer(hour: i64, amount: i64, asset: &str) -> AccountTransfer {
+        AccountTransfer {
+            amount: Decimal::from(amount),
+            asset: asset.to_string(),
+            occurred_at: at(hour),
+            ..Default::default()
+        }
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-9,
+            "{} != {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn periods_without_flows_chain_their_returns() {
+        let points = [point(0, 1000), point(1, 1100), point(2, 1210)];
+        assert_close(time_weighted_return(&points, &[]).unwrap(), 0.21);
+    }
+
+    #[test]
+    fn a_deposit_is_removed_from_its_period() {
+        // (1600 - 500) / 1000 = 1.1, then 1760 / 1600 = 1.1.
+        let points = [point(0, 1000), point(2, 1600), point(4, 1760)];
+        let transfers = [transfer(1, 500, "USDT")];
+        assert_close(time_weighted_return(&points, &transfers).unwrap(), 0.21);
+
+        let summary = summarize(&points, &transfers).unwrap();
+        assert_eq!(summary.net_deposits, Decimal::from(500));
+        assert_eq!(summary.pnl, Decimal::from(260));
+        // 260 on 1000 + 500 of capital.
+        assert_close(summary.simple_return_pct, 260.0 / 1500.0 * 100.0);
+        assert_close(summary.time_weighted_return_pct, 21.0);
+    }
+
+    #[test]
+    fn a_withdrawal_is_added_back_to_its_period() {
+        // (900 + 200) / 1000 = 1.1, then 990 / 900 = 1.1.
+        let points = [point(0, 1000), point(2, 900), point(4, 990)];
+        let transfers = [transfer(1, -200, "USDT")];
+        assert_close(time_weighted_return(&points, &transfers).unwrap(), 0.21);
+
+        let summary = summarize(&points, &transfers).unwrap();
+        assert_eq!(summary.net_deposits, Decimal::from(-200));
+        assert_eq!(summary.pnl, Decimal::from(190));
+        assert_close(summary.simple_return_pct, 19.0);
+    }
+
+    #[test]
+    fn flows_on_a_period_boundary_belong_to_the_period_they_end() {
+        // The deposit at hour 2 lands in (0, 2]: (1600 - 500) / 1000 = 1.1.
+        let points = [point(0, 1000), point(2, 1600), point(4, 1760)];
+        let transfers = [transfer(2, 500, "USDT")];
+        assert_close(time_weighted_return(&points, &transfers).unwrap(), 0.21);
+    }
+
+    #[test]
+    fn periods_from_zero_or_negative_equity_are_skipped() {
+        let points = [point(0, 0), point(1, 100), point(2, 110)];
+        let transfers = [transfer(1, 100, "USDT")];
+        assert_close(time_weighted_return(&points, &transfers).unwrap(), 0.1);
+
+        let points = [point(0, -50), point(1, 100), point(2, 120)];
+        assert_close(time_weighted_return(&points, &[]).unwrap(), 0.2);
+
+        let summary = summarize(&[point(0, 0), point(1, 0)], &[]).unwrap();
+        assert_eq!(summary.simple_return_pct, 0.0);
+        assert_eq!(summary.time_weighted_return_pct, 0.0);
+    }
+
+    #[test]
+    fn short_series_have_no_return() {
+        assert_eq!(time_weighted_return(&[], &[]), None);
+        assert_eq!(time_weighted_return(&[point(0, 1000)], &[]), None);
+        assert_eq!(summarize(&[], &[]), None);
+    }
+
+    #[test]
+    fn only_transfers_in_the_equity_asset_are_flows() {
+        let transfers = [
+            transfer(1, 500, "USDT"),
+            transfer(1, 2, "BTC"),
+            transfer(1, -100, "usdt"),
+        ];
+        assert_eq!(net_flows(&transfers, at(0), at(2)), Decimal::from(400));
+    }
+
+    #[test]
+    fn income_transfers_keep_their_asset() {
+        let record = |income_type: &str, income: &str, asset: &str, tran_id: i64| IncomeRecord {
+            symbol: String::new(),
+            income_type: income_type.to_string(),
+            income: income.to_string(),
+            asset: asset.to_string(),
+            time: at(1).timestamp_millis(),
+            tran_id,
+            info: String::new(),
+        };
+        let income = [
+            record(INCOME_TRANSFER, "500", "USDT", 1),
+            record(INCOME_TRANSFER, "-0.5", "BNB", 2),
+            record(INCOME_TRANSFER, "0", "USDT", 3),
+            record("REALIZED_PNL", "12.5", "USDT", 4),
+        ];
+        let transfers = transfers_from_income("user", "trader", &income);
+        let found: Vec<(Decimal, &str)> = transfers
+            .iter()
+            .map(|t| (t.amount, t.asset.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            [(Decimal::from(500), "USDT"), (Decimal::new(-5, 1), "BNB")]
+        );
+        assert_eq!(transfers[1].external_id.as_deref(), Some("2"));
+    }
+
+    #[tokio::test]
+    async fn transfers_are_valued_in_the_equity_asset() {
+        currency::set_price("BNBUSDT", 600.0);
+        let bnb = in_equity_asset(transfer(1, -2, "bnb")).await.unwrap();
+        assert_eq!(
+            (bnb.amount, bnb.asset.as_str()),
+            (Decimal::from(-1200), "USDT")
+        );
+        assert_eq!(bnb.note, "-2 BNB");
+
+        let usdt = in_equity_asset(transfer(1, 100, "USDT")).await.unwrap();
+        assert_eq!(usdt.amount, Decimal::from(100));
+        assert!(usdt.note.is_empty());
+    }
+}
@@ -0,0 +1,222 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::fs;
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+use serde::Serialize;
+
+// Samples kept per stage for percentile estimates.
+const MAX_SAMPLES_PER_STAGE: usize = 1024;
+// Number of slowest cycles retained for inspection.
+const TOP_N_CYCLES: usize = 20;
+
+static PROFILER: Lazy<Mutex<ProfilerState>> = Lazy::new(|| Mutex::new(ProfilerState::default()));
+
+#[derive(Default)]
+struct ProfilerState {
+    stages: BTreeMap<&'static str, StageSamples>,
+    slowest_cycles: Vec<CycleProfile>,
+}
+
+#[derive(Default)]
+struct StageSamples {
+    recent: VecDeque<Duration>,
+    count: u64,
+    total: Duration,
+    max: Duration,
+}
+
+impl StageSamples {
+    fn record(&mut self, elapsed: Duration) {
+        if self.recent.len() == MAX_SAMPLES_PER_STAGE {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(elapsed);
+        self.count += 1;
+        self.total += elapsed;
+        self.max = self.max.max(elapsed);
+    }
+
+    fn stats(&self) -> StageStats {
+        let mut sorted: Vec<Duration> = self.recent.iter().copied().collect();
+        sorted.sort_unstable();
+        let pct = |p: f64| -> f64 {
+            if sorted.is_empty() {
+                return 0.0;
+            }
+            let idx = ((sorted.len() - 1) as f64 * p).round() as usize;
+            as_ms(sorted[idx])
+        };
+
+        StageStats {
+            count: self.count,
+            mean_ms: if self.count > 0 {
+                as_ms(self.total) / self.count as f64
+            } else {
+                0.0
+            },
+            p50_ms: pct(0.50),
+            p90_ms: pct(0.90),
+            p99_ms: pct(0.99),
+            max_ms: as_ms(self.max),
+        }
+    }
+}
+
+fn as_ms(d: Duration) -> f64 {
+    d.as_secs_f64() * 1000.0
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StageStats {
+    pub count: u64,
+    pub mean_ms: f64,
+    pub p50_ms: f64,
+    pub p90_ms: f64,
+    pub p99_ms: f64,
+    pub max_ms: f64,
+}
+
+/// Timing breakdown of one trader cycle.
+#[derive(Debug, Clone, Serialize)]
+pub struct CycleProfile {
+    pub trader_id: String,
+    pub cycle_number: i32,
+    pub started_at: DateTime<Utc>,
+    pub total_ms: f64,
+    pub stages: Vec<(String, f64)>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProfileReport {
+    pub generated_at: DateTime<Utc>,
+    pub stages: BTreeMap<String, StageStats>,
+    pub slowest_cycles: Vec<CycleProfile>,
+}
+
+/// Records one timing sample for a stage.
+pub fn record_stage(stage: &'static str, elapsed: Duration) {
+    let mut state = PROFILER.lock().unwrap_or_else(|e| e.into_inner());
+    state.stages.entry(stage).or_default().record(elapsed);
+}
+
+/// Starts a guard that records the elapsed time of `stage` when dropped.
+pub fn time_stage(stage: &'static str) -> StageTimer {
+    StageTimer {
+        stage,
+        started: Instant::now(),
+    }
+}
+
+pub struct StageTimer {
+    stage: &'static str,
+    started: Instant,
+}
+
+impl Drop for StageTimer {
+    fn drop(&mut self) {
+        record_stage(self.stage, self.started.elapsed());
+    }
+}
+
+/// Collects the stage timings of a single cycle; `finish` files it under the
+/// "cycle" stage and keeps it if it is among the slowest seen so far.
+pub struct CycleTimer {
+    trader_id: String,
+    cycle_number: i32,
+    started_at: DateTime<Utc>,
+    started: Instant,
+    stages: Vec<(String, f64)>,
+}
+
+impl CycleTimer {
+    pub fn new(trader_id: &str, cycle_number: i32) -> Self {
+        Self {
+            trader_id: trader_id.to_string(),
+            cycle_number,
+            started_at: Utc::now(),
+            started: Instant::now(),
+            stages: Vec::new(),
+        }
+    }
+
+    /// Awaits `fut` and records how long it took as `stage`.
+    pub async fn time<F: Future>(&mut self, stage: &'static str, fut: F) -> F::Output {
+        let started = Instant::now();
+        let output = fut.await;
+        self.add(stage, started.elapsed());
+        output
+    }
+
+    pub fn add(&mut self, stage: &'static str, elapsed: Duration) {
+        record_stage(stage, elapsed);
+        self.stages.push((stage.to_string(), as_ms(elapsed)));
+    }
+
+    pub fn finish(self) -> Duration {
+        let total = self.started.elapsed();
+        record_stage("cycle", total);
+
+        let profile = CycleProfile {
+            trader_id: self.trader_id,
+            cycle_number: self.cycle_number,
+            started_at: self.started_at,
+            total_ms: as_ms(total),
+            stages: self.stages,
+        };
+
+        let mut state = PROFILER.lock().unwrap_or_else(|e| e.into_inner());
+        let slowest = &mut state.slowest_cycles;
+        slowest.push(profile);
+        slowest.sort_by(|a, b| b.total_ms.total_cmp(&a.total_ms));
+        slowest.truncate(TOP_N_CYCLES);
+
+        total
+    }
+}
+
+/// Snapshot of all stage distributions and the slowest cycles.
+pub fn report() -> ProfileReport {
+    let state = PROFILER.lock().unwrap_or_else(|e| e.into_inner());
+    ProfileReport {
+        generated_at: Utc::now(),
+        stages: state
+            .stages
+            .iter()
+            .map(|(name, samples)| (name.to_string(), samples.stats()))
+            .collect(),
+        slowest_cycles: state.slowest_cycles.clone(),
+    }
+}
+
+/// Logs the current report and, if `path` is given, writes it there as JSON.
+/// Intended to be called on shutdown.
+pub fn dump(path: Option<&str>) {
+    let report = report();
+    for (stage, stats) in &report.stages {
+        tracing::info!(
+            "⏱️ {:<16} n={} mean={:.1}ms p50={:.1}ms p90={:.1}ms p99={:.1}ms max={:.1}ms",
+            stage,
+            stats.count,
+            stats.mean_ms,
+            stats.p50_ms,
+            stats.p90_ms,
+            stats.p99_ms,
+            stats.max_ms
+        );
+    }
+
+    if let Some(path) = path {
+        match serde_json::to_string_pretty(&report) {
+            Ok(json) => {
+                if let Err(e) = fs::write(path, json) {
+                    tracing::warn!("⚠️ Failed to write profile report to {}: {}", path, e);
+                }
+            }
+            Err(e) => tracing::warn!("⚠️ Failed to serialize profile report: {}", e),
+        }
+    }
+}
@@ -0,0 +1,193 @@
+//! Builds the prompts a decision cycle sends to the AI.
+//!
+//! The system prompt comes from the trader's settings: the template named by
+//! `system_prompt_template` (built in, or the user's own; see
+//! [`prompt_template`](crate::prompt_template)) with its variables filled in,
+//! the trader's `custom_prompt` appended to it (or replacing it when
+//! `override_base_prompt` is set), and the output format the decision parser
+//! expects. The user prompt renders a decision
+//! [`Context`]. Both are pure functions of their inputs, with symbols and
+//! positions in a fixed order and all times taken from the context, so the
+//! same context always yields the same string and a logged `input_prompt` can
+//! be replayed exactly.
+
+use std::fmt::Write;
+
+use crate::data;
+use crate::database::TraderRecord;
+use crate::decision::{Context, PositionInfo};
+use crate::prompt_template;
+
+pub const DEFAULT_TEMPLATE: &str = "default";
+
+const DEFAULT_RULES: &str = "\
+You are a professional cryptocurrency perpetual futures trader managing a real account.
+
+# Hard rules
+1. Risk/reward: only open a position whose take-profit is at least 3x as far as its stop-loss.
+2. At most 3 positions at the same time.
+3. Leverage: {{leverage}}.
+4. Keep total margin usage below 90% of equity.
+5. Every new position needs a stop-loss and a take-profit.
+
+# Approach
+- Trade the trend on the 4-hour context; use the 3-minute series for timing.
+- Prefer waiting over forcing a trade. Fees and funding are real costs.
+- Cut positions whose reason for entry no longer holds.";
+
+const CONSERVATIVE_RULES: &str = "\
+You are a cautious cryptocurrency perpetual futures trader whose first goal is not to lose money.
+
+# Hard rules
+1. Risk/reward: only open a position whose take-profit is at least 3x as far as its stop-loss.
+2. At most 2 positions at the same time.
+3. Leverage: {{leverage}}.
+4. Keep total margin usage below 50% of equity and risk at most 1% of equity per trade.
+5. Every new position needs a stop-loss and a take-profit.
+
+# Approach
+- Only trade clear trends confirmed on both the 4-hour and the 3-minute data.
+- When in doubt, wait.";
+
+const OUTPUT_FORMAT: &str = "\
+# Output format
+Think step by step first, then end with a JSON array of decisions, one per symbol you act on:
+[{\"symbol\": \"BTCUSDT\", \"action\": \"open_long\", \"leverage\": 5, \"position_size_usd\": 500, \
+\"stop_loss\": 95000, \"take_profit\": 105000, \"confidence\": 80, \"risk_usd\": 25, \"reasoning\": \"...\"}]
+`action` is one of open_long, open_short, close_long, close_short, hold, wait.
+Closes only need symbol, action and reasoning. Reply with [] when there is nothing to do.
+For a pair trade add \"pair\": {\"symbol\": \"BTCUSDT\", \"ratio\": 1.0} to take the opposite side of that symbol \
+at ratio times the notional; both legs are opened and closed together.";
+
+/// Names of the built-in system prompt templates.
+pub fn template_names() -> &'static [&'static str] {
+    &[DEFAULT_TEMPLATE, "conservative"]
+}
+
+/// The text of a built-in template; an empty name is the default.
+pub fn builtin_template(name: &str) -> Option<&'static str> {
+    match name {
+        "" | DEFAULT_TEMPLATE => Some(DEFAULT_RULES),
+        "conservative" => Some(CONSERVATIVE_RULES),
+        _ => None,
+    }
+}
+
+/// The system prompt for a trader whose template reads `template`.
+pub fn system_prompt(trader: &TraderRecord, template: &str, ctx: &Context) -> String {
+    let custom = trader.custom_prompt.trim();
+    let mut prompt = if trader.override_base_prompt && !custom.is_empty() {
+        custom.to_string()
+    } else {
+        let vars = prompt_template::variables(trader, ctx);
+        let mut base = prompt_template::render(template, &vars).unwrap_or_else(|e| {
+            tracing::warn!("⚠️ 系统提示词模板渲染失败: {}", e);
+            template.to_string()
+        });
+        if !custom.is_empty() {
+            base.push_str("\n\n# Trader's own strategy\n");
+            base.push_str(custom);
+        }
+        base
+    };
+    prompt.push_str("\n\n");
+    prompt.push_str(OUTPUT_FORMAT);
+    prompt
+}
+
+/// The user prompt for one cycle. `notes` are extra lines from the risk
+/// filters (cooldowns, costs) shown after the account.
+pub fn user_prompt(ctx: &Context, notes: &[String]) -> String {
+    let mut s = String::new();
+    let _ = writeln!(
+        s,
+        "Time: {} | Runtime: {} min | Cycle: #{}\n",
+        ctx.current_time.format("%Y-%m-%d %H:%M:%S UTC"),
+        ctx.runtime_minutes,
+        ctx.call_count
+    );
+
+    let a = &ctx.account;
+    let available_pct = if a.total_equity > 0.0 {
+        a.available_balance / a.total_equity * 100.0
+    } else {
+        0.0
+    };
+    let _ = writeln!(s, "## Account");
+    let _ = writeln!(
+        s,
+        "Equity {:.2} USDT | Available {:.2} ({:.1}%) | PnL {:+.2}% | Margin used {:.1}% | Positions {}\n",
+        a.total_equity,
+        a.available_balance,
+        available_pct,
+        a.total_pnl_pct,
+        a.margin_used_pct,
+        a.position_count
+    );
+
+    if !notes.is_empty() {
+        let _ = writeln!(s, "## Notes");
+        for note in notes {
+            let _ = writeln!(s, "{}", note.trim_end());
+        }
+        let _ = writeln!(s);
+    }
+
+    let mut positions: Vec<&PositionInfo> = ctx.positions.iter().collect();
+    positions.sort_by(|a, b| (&a.symbol, &a.side).cmp(&(&b.symbol, &b.side)));
+    let _ = writeln!(s, "## Open positions");
+    if positions.is_empty() {
+        let _ = writeln!(s, "None\n");
+    }
+    for (i, p) in positions.iter().enumerate() {
+        let _ = write!(
+            s,
+            "{}. {} {} | entry {:.4} mark {:.4} | qty {:.4} | {}x | uPnL {:+.2} ({:+.2}%) | liq {:.4}",
+            i + 1,
+            p.symbol,
+            p.side.to_uppercase(),
+            p.entry_price,
+            p.mark_price,
+            p.quantity,
+            p.leverage,
+            p.unrealized_pnl,
+            p.unrealized_pnl_pct,
+            p.liquidation_price
+        );
+        if p.update_time > 0 {
+            let held = ctx.current_time.timestamp_millis() - p.update_time;
+            if held >= 0 {
+                let minutes = held / 60_000;
+                let _ = write!(s, " | held {}h{:02}m", minutes / 60, minutes % 60);
+            }
+        }
+        let _ = writeln!(s, "\n");
+        if let Some(data) = ctx.market_data.get(&p.symbol) {
+            let _ = writeln!(s, "{}", data::format(data));
+        }
+    }
+
+    let held: Vec<&str> = positions.iter().map(|p| p.symbol.as_str()).collect();
+    let mut candidates: Vec<&String> = ctx
+        .candidate_coins
+        .iter()
+        .filter(|c| !held.contains(&c.as_str()))
+        .collect();
+    candidates.sort();
+    candidates.dedup();
+    let _ = writeln!(s, "## Candidate coins ({})\n", candidates.len());
+    for symbol in candidates {
+        let Some(data) = ctx.market_data.get(symbol) else {
+            continue;
+        };
+        let _ = writeln!(s, "=== {} ===", symbol);
+        let _ = writeln!(s, "{}", data::format(data));
+    }
+
+    if let Some(performance) = &ctx.performance {
+        let _ = writeln!(s, "## Recent performance");
+        let _ = writeln!(s, "{}", performance.prompt_summary());
+    }
+
+    s
+}
@@ -0,0 +1,261 @@
+//! Version history for trader prompts.
+//!
+//! Every edit to a trader's `custom_prompt`, `override_base_prompt` or
+//! `system_prompt_template` goes through [`update`], which stores the new
+//! prompt as a `prompt_versions` row together with a git-style diff against the
+//! previous version and the author. The first edit also records the prompt the
+//! trader had before, so there is always a version to roll back to. Rolling back
+//! is itself a new version, keeping the history append-only.
+
+use serde::{Deserialize, Serialize};
+use similar::TextDiff;
+use thiserror::Error;
+
+use crate::database::{Database, PromptVersion, TraderRecord};
+use crate::prompt_template;
+
+#[derive(Error, Debug)]
+pub enum PromptHistoryError {
+    #[error("Trader {0} not found")]
+    TraderNotFound(String),
+    #[error("Prompt version {0} not found")]
+    VersionNotFound(i64),
+    #[error("Prompt template {0} not found")]
+    TemplateNotFound(String),
+    #[error("Database error: {0}")]
+    Database(#[from] anyhow::Error),
+}
+
+/// The prompt-related fields of a trader.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PromptState {
+    pub custom_prompt: String,
+    pub override_base_prompt: bool,
+    pub system_prompt_template: String,
+}
+
+impl From<&TraderRecord> for PromptState {
+    fn from(trader: &TraderRecord) -> Self {
+        Self {
+            custom_prompt: trader.custom_prompt.clone(),
+            override_base_prompt: trader.override_base_prompt,
+            system_prompt_template: trader.system_prompt_template.clone(),
+        }
+    }
+}
+
+impl From<&PromptVersion> for PromptState {
+    fn from(version: &PromptVersion) -> Self {
+        Self {
+            custom_prompt: version.custom_prompt.clone(),
+            override_base_prompt: version.override_base_prompt,
+            system_prompt_template: version.system_prompt_template.clone(),
+        }
+    }
+}
+
+/// An edit to a trader's prompt. Fields left out keep their current value.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct PromptChange {
+    #[serde(default)]
+    pub custom_prompt: Option<String>,
+    #[serde(default)]
+    pub override_base_prompt: Option<bool>,
+    #[serde(default)]
+    pub system_prompt_template: Option<String>,
+    /// Free-form description of why the prompt changed.
+    #[serde(default)]
+    pub note: String,
+}
+
+impl PromptChange {
+    fn apply(&self, current: &PromptState) -> PromptState {
+        PromptState {
+            custom_prompt: self
+                .custom_prompt
+                .clone()
+                .unwrap_or_else(|| current.custom_prompt.clone()),
+            override_base_prompt: self
+                .override_base_prompt
+                .unwrap_or(current.override_base_prompt),
+            system_prompt_template: self
+                .system_prompt_template
+                .clone()
+                .unwrap_or_else(|| current.system_prompt_template.clone()),
+        }
+    }
+}
+
+/// Git-style description of the change from `old` to `new`: one line per
+/// changed setting, followed by a unified diff of the custom prompt.
+pub fn diff(old: &PromptState, new: &PromptState) -> String {
+    let mut out = String::new();
+    if old.system_prompt_template != new.system_prompt_template {
+        out.push_str(&format!(
+            "system_prompt_template: {} -> {}\n",
+            old.system_prompt_template, new.system_prompt_template
+        ));
+    }
+    if old.override_base_prompt != new.override_base_prompt {
+        out.push_str(&format!(
+            "override_base_prompt: {} -> {}\n",
+            old.override_base_prompt, new.override_base_prompt
+        ));
+    }
+    if old.custom_prompt != new.custom_prompt {
+        let text = TextDiff::from_lines(&old.custom_prompt, &new.custom_prompt);
+        out.push_str(
+            &text
+                .unified_diff()
+                .header("a/custom_prompt", "b/custom_prompt")
+                .to_string(),
+        );
+    }
+    out
+}
+
+/// Applies `change` to the trader and records it as a new version. Returns the
+/// version matching the trader's prompt afterwards; an edit that changes
+/// nothing records nothing and returns the latest version.
+pub async fn update(
+    db: &Database,
+    user_id: &str,
+    trader_id: &str,
+    change: &PromptChange,
+    author: &str,
+) -> Result<PromptVersion, PromptHistoryError> {
+    let current = current_state(db, user_id, trader_id).await?;
+    let next = change.apply(&current);
+    if next.system_prompt_template != current.system_prompt_template
+        && !prompt_template::exists(db, user_id, &next.system_prompt_template).await?
+    {
+        return Err(PromptHistoryError::TemplateNotFound(
+            next.system_prompt_template,
+        ));
+    }
+    save(
+        db,
+        user_id,
+        trader_id,
+        &current,
+        &next,
+        author,
+        &change.note,
+    )
+    .await
+}
+
+/// Restores the prompt of `version`, recorded as a new version.
+pub async fn rollback(
+    db: &Database,
+    user_id: &str,
+    trader_id: &str,
+    version: i64,
+    author: &str,
+) -> Result<PromptVersion, PromptHistoryError> {
+    let target = db
+        .get_prompt_version(user_id, trader_id, version)
+        .await?
+        .ok_or(PromptHistoryError::VersionNotFound(version))?;
+    let current = current_state(db, user_id, trader_id).await?;
+    let note = format!("rollback to v{version}");
+    save(
+        db,
+        user_id,
+        trader_id,
+        &current,
+        &PromptState::from(&target),
+        author,
+        &note,
+    )
+    .await
+}
+
+async fn current_state(
+    db: &Database,
+    user_id: &str,
+    trader_id: &str,
+) -> Result<PromptState, PromptHistoryError> {
+    db.get_trader(user_id, trader_id)
+        .await?
+        .map(|t| PromptState::from(&t))
+        .ok_or_else(|| PromptHistoryError::TraderNotFound(trader_id.to_string()))
+}
+
+async fn save(
+    db: &Database,
+    user_id: &str,
+    trader_id: &str,
+    current: &PromptState,
+    next: &PromptState,
+    author: &str,
+    note: &str,
+) -> Result<PromptVersion, PromptHistoryError> {
+    let latest = db
+        .get_prompt_versions(user_id, trader_id)
+        .await?
+        .into_iter()
+        .next();
+    // The prompt a trader was created with, or one changed without going
+    // through this module, is recorded first so the edit diffs against it.
+    let latest = match latest {
+        Some(v) if PromptState::from(&v) == *current => v,
+        latest => {
+            let previous = latest.as_ref().map(PromptState::from);
+            record(
+                db,
+                user_id,
+                trader_id,
+                previous.as_ref(),
+                current,
+                "",
+                "baseline",
+            )
+            .await?
+        }
+    };
+    if next == current {
+        return Ok(latest);
+    }
+
+    db.update_trader_prompt(
+        user_id,
+        trader_id,
+        &next.custom_prompt,
+        next.override_base_prompt,
+        &next.system_prompt_template,
+    )
+    .await?;
+    let version = record(db, user_id, trader_id, Some(current), next, author, note).await?;
+    tracing::info!(
+        "📝 交易员 {} 提示词已更新为 v{} (修改人: {})",
+        trader_id,
+        version.version,
+        author
+    );
+    Ok(version)
+}
+
+async fn record(
+    db: &Database,
+    user_id: &str,
+    trader_id: &str,
+    previous: Option<&PromptState>,
+    state: &PromptState,
+    author: &str,
+    note: &str,
+) -> Result<PromptVersion, PromptHistoryError> {
+    let diff = previous.map(|p| diff(p, state)).unwrap_or_default();
+    Ok(db
+        .record_prompt_version(
+            user_id,
+            trader_id,
+            &state.custom_prompt,
+            state.override_base_prompt,
+            &state.system_prompt_template,
+            &diff,
+            author,
+            note,
+        )
+        .await?)
+}
@@ -0,0 +1,150 @@
+//! Named system prompt templates and the variables they can use.
+//!
+//! A trader's `system_prompt_template` names either a built-in template (see
+//! [`prompt::template_names`]) or one of its owner's `prompt_templates` rows.
+//! Templates are plain text with `{{variable}}` placeholders, filled in every
+//! cycle:
+//!
+//! - `{{symbol_list}}`: the symbols the trader may act on this cycle.
+//! - `{{leverage}}`: the trader's leverage caps in one phrase.
+//! - `{{btc_eth_leverage}}`, `{{altcoin_leverage}}`: the caps as numbers.
+//! - `{{risk_limits}}`: the hard limits decisions are validated against.
+//! - `{{performance_summary}}`: recent closed-trade statistics.
+//!
+//! Unknown variables are rejected when a template is saved, so rendering a
+//! stored template does not fail.
+
+use std::collections::HashMap;
+
+use thiserror::Error;
+
+use crate::database::{Database, TraderRecord};
+use crate::decision::{Context, Limits};
+use crate::prompt;
+
+/// Variables a template may contain.
+pub const VARIABLES: &[&str] = &[
+    "symbol_list",
+    "leverage",
+    "btc_eth_leverage",
+    "altcoin_leverage",
+    "risk_limits",
+    "performance_summary",
+];
+
+const MAX_NAME_LEN: usize = 64;
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum TemplateError {
+    #[error("unknown template variable {{{{{0}}}}}")]
+    UnknownVariable(String),
+    #[error("unclosed {{{{ at byte {0}")]
+    Unclosed(usize),
+    #[error("invalid template name '{0}': use 1-64 letters, digits, '-' or '_'")]
+    InvalidName(String),
+    #[error("'{0}' is a built-in template")]
+    Reserved(String),
+    #[error("template is empty")]
+    Empty,
+}
+
+/// Replaces each `{{name}}` in `template` with `vars[name]`. Whitespace inside
+/// the braces is ignored.
+pub fn render(template: &str, vars: &HashMap<&str, String>) -> Result<String, TemplateError> {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let Some(end) = after.find("}}") else {
+            return Err(TemplateError::Unclosed(template.len() - rest.len() + start));
+        };
+        let name = after[..end].trim();
+        let value = vars
+            .get(name)
+            .ok_or_else(|| TemplateError::UnknownVariable(name.to_string()))?;
+        out.push_str(value);
+        rest = &after[end + 2..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// Checks a user template before it is stored.
+pub fn validate(name: &str, content: &str) -> Result<(), TemplateError> {
+    let name_ok = !name.is_empty()
+        && name.len() <= MAX_NAME_LEN
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !name_ok {
+        return Err(TemplateError::InvalidName(name.to_string()));
+    }
+    if prompt::builtin_template(name).is_some() {
+        return Err(TemplateError::Reserved(name.to_string()));
+    }
+    if content.trim().is_empty() {
+        return Err(TemplateError::Empty);
+    }
+    let placeholders = VARIABLES.iter().map(|v| (*v, String::new())).collect();
+    render(content, &placeholders).map(|_| ())
+}
+
+/// The variables for a trader's cycle.
+pub fn variables(trader: &TraderRecord, ctx: &Context) -> HashMap<&'static str, String> {
+    let limits = Limits::from_context(ctx);
+    let symbol_list = if limits.symbols.is_empty() {
+        "none".to_string()
+    } else {
+        limits.symbols.join(", ")
+    };
+    let leverage = format!(
+        "at most {}x on BTC/ETH and at most {}x on other coins",
+        trader.btc_eth_leverage, trader.altcoin_leverage
+    );
+    let risk_limits = format!(
+        "- Leverage {}.\n\
+         - An entry's notional may not exceed equity times its leverage.\n\
+         - Only symbols in the list may be traded; closes must match an open position.",
+        leverage
+    );
+    let performance_summary = ctx
+        .performance
+        .as_ref()
+        .map(|p| p.prompt_summary())
+        .unwrap_or_else(|| "No closed trades yet.".to_string());
+    HashMap::from([
+        ("symbol_list", symbol_list),
+        ("leverage", leverage),
+        ("btc_eth_leverage", trader.btc_eth_leverage.to_string()),
+        ("altcoin_leverage", trader.altcoin_leverage.to_string()),
+        ("risk_limits", risk_limits),
+        ("performance_summary", performance_summary),
+    ])
+}
+
+/// Whether `name` can be used as a template by `user_id`'s traders.
+pub async fn exists(db: &Database, user_id: &str, name: &str) -> anyhow::Result<bool> {
+    if name.is_empty() || prompt::builtin_template(name).is_some() {
+        return Ok(true);
+    }
+    Ok(db.get_prompt_template(user_id, name).await?.is_some())
+}
+
+/// The text of the trader's template. A user template that has since been
+/// deleted falls back to the default.
+pub async fn resolve(db: &Database, trader: &TraderRecord) -> anyhow::Result<String> {
+    let name = trader.system_prompt_template.as_str();
+    if let Some(builtin) = prompt::builtin_template(name) {
+        return Ok(builtin.to_string());
+    }
+    match db.get_prompt_template(&trader.user_id, name).await? {
+        Some(template) => Ok(template.content),
+        None => {
+            tracing::warn!("⚠️ 未知的系统提示词模板 {}，使用默认模板", name);
+            Ok(prompt::builtin_template(prompt::DEFAULT_TEMPLATE)
+                .unwrap_or_default()
+                .to_string())
+        }
+    }
+}
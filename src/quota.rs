@@ -0,0 +1,200 @@
+//! Per-user resource quotas for hosted multi-user deployments.
+//!
+//! A quota caps how many traders a user may have, how often they may scan,
+//! how many symbols each trader may trade and how much AI spend a user may run
+//! up per calendar month. Limits are stored in `system_config`: the JSON under
+//! `quota_defaults` applies to every user, and `quota:<user_id>` replaces it
+//! for one user. Zero means unlimited, which is also the default, so
+//! self-hosted installs are unaffected. Limits are checked when a trader is
+//! created and when it is started.
+//!
+//! Providers are not asked for their token usage; spend is estimated from the
+//! prompt and response lengths at a flat price per million tokens.
+
+use chrono::{DateTime, Datelike, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::database::{Database, TraderRecord};
+
+pub const DEFAULTS_KEY: &str = "quota_defaults";
+/// Rough price used to estimate AI spend, in USD per million tokens.
+pub const AI_USD_PER_MILLION_TOKENS: f64 = 2.0;
+// Characters per token used for the estimate.
+const CHARS_PER_TOKEN: usize = 4;
+
+#[derive(Error, Debug)]
+pub enum QuotaError {
+    #[error("Trader limit reached: at most {0} traders per user")]
+    TooManyTraders(u32),
+    #[error("Scan interval must be at least {0} minutes")]
+    ScanTooFrequent(i32),
+    #[error("Too many trading symbols: at most {0} per trader")]
+    TooManySymbols(usize),
+    #[error("Monthly AI spend limit of ${0:.2} reached")]
+    AiBudgetExhausted(f64),
+    #[error("Invalid quota: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Database error: {0}")]
+    Database(#[from] anyhow::Error),
+}
+
+/// Limits for one user. Zero means unlimited.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Quota {
+    pub max_traders: u32,
+    pub min_scan_interval_minutes: i32,
+    /// Estimated AI spend per calendar month (UTC), in USD.
+    pub max_ai_spend_usd: f64,
+    /// Trading symbols per trader.
+    pub max_symbols: usize,
+}
+
+impl Quota {
+    /// Checks the settings of a single trader.
+    pub fn check_trader(&self, trader: &TraderRecord) -> Result<(), QuotaError> {
+        if self.min_scan_interval_minutes > 0
+            && trader.scan_interval_minutes < self.min_scan_interval_minutes
+        {
+            return Err(QuotaError::ScanTooFrequent(self.min_scan_interval_minutes));
+        }
+        let symbols = trader
+            .trading_symbols
+            .split(',')
+            .filter(|s| !s.trim().is_empty())
+            .count();
+        if self.max_symbols > 0 && symbols > self.max_symbols {
+            return Err(QuotaError::TooManySymbols(self.max_symbols));
+        }
+        Ok(())
+    }
+}
+
+fn user_key(user_id: &str) -> String {
+    format!("quota:{}", user_id)
+}
+
+async fn load(db: &Database, key: &str) -> Result<Option<Quota>, QuotaError> {
+    match db.get_system_config(key).await {
+        Ok(json) if !json.trim().is_empty() => Ok(Some(serde_json::from_str(&json)?)),
+        _ => Ok(None),
+    }
+}
+
+/// The quota applied to users without an override.
+pub async fn defaults(db: &Database) -> Result<Quota, QuotaError> {
+    Ok(load(db, DEFAULTS_KEY).await?.unwrap_or_default())
+}
+
+/// A user's own override, if any.
+pub async fn user_override(db: &Database, user_id: &str) -> Result<Option<Quota>, QuotaError> {
+    load(db, &user_key(user_id)).await
+}
+
+/// The quota in effect for a user.
+pub async fn for_user(db: &Database, user_id: &str) -> Result<Quota, QuotaError> {
+    match user_override(db, user_id).await? {
+        Some(quota) => Ok(quota),
+        None => defaults(db).await,
+    }
+}
+
+pub async fn set_defaults(db: &Database, quota: &Quota) -> Result<(), QuotaError> {
+    db.set_system_config(DEFAULTS_KEY, &serde_json::to_string(quota)?)
+        .await?;
+    tracing::info!("📏 默认配额已更新: {:?}", quota);
+    Ok(())
+}
+
+/// Sets a user's override, or removes it so the defaults apply again.
+pub async fn set_user_override(
+    db: &Database,
+    user_id: &str,
+    quota: Option<&Quota>,
+) -> Result<(), QuotaError> {
+    match quota {
+        Some(quota) => {
+            db.set_system_config(&user_key(user_id), &serde_json::to_string(quota)?)
+                .await?
+        }
+        None => db.delete_system_config(&user_key(user_id)).await?,
+    }
+    tracing::info!("📏 用户 {} 的配额已更新: {:?}", user_id, quota);
+    Ok(())
+}
+
+/// Checks a trader about to be created against its owner's quota.
+pub async fn check_create(db: &Database, trader: &TraderRecord) -> Result<(), QuotaError> {
+    let quota = for_user(db, &trader.user_id).await?;
+    if quota.max_traders > 0 {
+        let others = db
+            .get_traders(&trader.user_id)
+            .await?
+            .iter()
+            .filter(|t| t.id != trader.id)
+            .count();
+        if others >= quota.max_traders as usize {
+            return Err(QuotaError::TooManyTraders(quota.max_traders));
+        }
+    }
+    quota.check_trader(trader)
+}
+
+/// Checks a trader about to be started against its owner's quota.
+pub async fn check_start(db: &Database, trader: &TraderRecord) -> Result<(), QuotaError> {
+    let quota = for_user(db, &trader.user_id).await?;
+    quota.check_trader(trader)?;
+    if quota.max_ai_spend_usd > 0.0 {
+        let spent = monthly_ai_spend(db, &trader.user_id, Utc::now()).await?;
+        if spent >= quota.max_ai_spend_usd {
+            return Err(QuotaError::AiBudgetExhausted(quota.max_ai_spend_usd));
+        }
+    }
+    Ok(())
+}
+
+fn month_start(now: DateTime<Utc>) -> NaiveDate {
+    let today = now.date_naive();
+    today.with_day(1).unwrap_or(today)
+}
+
+/// Estimated AI spend of a user in the calendar month of `now`.
+pub async fn monthly_ai_spend(
+    db: &Database,
+    user_id: &str,
+    now: DateTime<Utc>,
+) -> Result<f64, QuotaError> {
+    Ok(db.get_ai_spend(user_id, month_start(now)).await?)
+}
+
+/// Estimated tokens and USD cost of one AI call.
+pub fn estimate_cost(prompt_chars: usize, response_chars: usize) -> (i64, f64) {
+    let tokens = (prompt_chars + response_chars).div_ceil(CHARS_PER_TOKEN);
+    (
+        tokens as i64,
+        tokens as f64 * AI_USD_PER_MILLION_TOKENS / 1_000_000.0,
+    )
+}
+
+/// Adds one AI call to the trader's usage. Failures are logged, not returned.
+pub async fn record_ai_call(
+    db: &Database,
+    trader: &TraderRecord,
+    prompt_chars: usize,
+    response_chars: usize,
+) {
+    let (tokens, cost) = estimate_cost(prompt_chars, response_chars);
+    if let Err(e) = db
+        .add_ai_usage(
+            &trader.user_id,
+            &trader.id,
+            Utc::now().date_naive(),
+            tokens,
+            cost,
+        )
+        .await
+    {
+        tracing::warn!("⚠️ 记录 AI 用量失败: {:#}", e);
+    }
+}
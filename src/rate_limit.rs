@@ -0,0 +1,311 @@
+//! Rate limiting and backoff for exchange and market data requests.
+//!
+//! Requests sent with [`SendLimited::send_limited`] first take their weight
+//! from the target host's per-minute budget, waiting for the next minute
+//! when it is spent. Binance reports the weight an IP has used in
+//! `X-MBX-USED-WEIGHT-1M`, which keeps the budget honest when other clients
+//! share the address. 429 and 5xx responses are retried with exponential
+//! backoff (5xx only for GETs, since a failed order may still have been
+//! placed), and a 418 ban or a long Retry-After blocks the host until it
+//! lifts. The requests still go through the shared, pooled clients from
+//! [`crate::api_client::client_for`].
+
+use chrono::Utc;
+use once_cell::sync::Lazy;
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use reqwest::{Method, RequestBuilder, Response, StatusCode, Url};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Mutex, RwLock};
+use std::time::{Duration, Instant};
+use thiserror::Error;
+
+const USED_WEIGHT_HEADER: &str = "x-mbx-used-weight-1m";
+/// How long a 418 ban lasts when Binance does not say.
+const DEFAULT_BAN: Duration = Duration::from_secs(120);
+
+#[derive(Error, Debug)]
+pub enum RateLimitError {
+    #[error("Rate limited by {host} for another {}s", retry_after.as_secs().max(1))]
+    Blocked { host: String, retry_after: Duration },
+    #[error("HTTP error: {0}")]
+    Request(#[from] reqwest::Error),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct RateLimits {
+    /// Request weight per minute by host, e.g. `{"fapi.binance.com": 2000}`.
+    pub weight_per_minute: HashMap<String, u32>,
+    /// Budget for hosts not listed above.
+    pub default_weight_per_minute: u32,
+    /// Retries of a 429 or 5xx response.
+    pub max_retries: u32,
+    /// Delay before the first retry; doubles with each attempt.
+    #[serde(with = "humantime_serde")]
+    pub base_delay: Duration,
+    /// Longest a request waits for a budget, backoff or ban before failing.
+    #[serde(with = "humantime_serde")]
+    pub max_wait: Duration,
+}
+
+impl Default for RateLimits {
+    fn default() -> Self {
+        // A little under the published limits, leaving room for other
+        // clients on the same IP.
+        let weight_per_minute = [
+            ("fapi.binance.com", 2000),
+            ("testnet.binancefuture.com", 2000),
+            ("api.binance.com", 5000),
+            ("fapi.asterdex.com", 2000),
+        ]
+        .into_iter()
+        .map(|(host, weight)| (host.to_string(), weight))
+        .collect();
+        Self {
+            weight_per_minute,
+            default_weight_per_minute: 1000,
+            max_retries: 3,
+            base_delay: Duration::from_millis(500),
+            max_wait: Duration::from_secs(10),
+        }
+    }
+}
+
+impl RateLimits {
+    fn limit(&self, host: &str) -> u32 {
+        self.weight_per_minute
+            .get(host)
+            .copied()
+            .unwrap_or(self.default_weight_per_minute)
+    }
+}
+
+// Weight used by a host in the current minute.
+#[derive(Debug, Default)]
+struct Budget {
+    minute: i64,
+    used: u32,
+    blocked_until: Option<Instant>,
+}
+
+static LIMITS: Lazy<RwLock<RateLimits>> = Lazy::new(|| RwLock::new(RateLimits::default()));
+static BUDGETS: Lazy<Mutex<HashMap<String, Budget>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Replaces the rate limits, e.g. from the config file.
+pub fn set_limits(limits: RateLimits) {
+    *LIMITS.write().unwrap_or_else(|e| e.into_inner()) = limits;
+}
+
+fn limits() -> RateLimits {
+    LIMITS.read().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+/// Weight used against `host` (with its port, if not the default) in the
+/// current minute, as far as we know.
+pub fn used_weight(host: &str) -> u32 {
+    let minute = current_minute();
+    BUDGETS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(host)
+        .filter(|b| b.minute == minute)
+        .map_or(0, |b| b.used)
+}
+
+/// Forgets all budgets and bans.
+pub fn reset() {
+    BUDGETS.lock().unwrap_or_else(|e| e.into_inner()).clear();
+}
+
+/// Binance's request weight for an endpoint; 1 for anything not listed.
+pub fn weight(url: &Url) -> u32 {
+    let param = |name: &str| {
+        url.query_pairs()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.into_owned())
+    };
+    let path = url.path();
+    let has_symbol = param("symbol").is_some();
+    if path.ends_with("/klines") {
+        match param("limit")
+            .and_then(|l| l.parse::<u32>().ok())
+            .unwrap_or(500)
+        {
+            0..100 => 1,
+            100..500 => 2,
+            500..=1000 => 5,
+            _ => 10,
+        }
+    } else if path.ends_with("/ticker/24hr") || path.ends_with("/openOrders") {
+        if has_symbol { 1 } else { 40 }
+    } else if path.ends_with("/ticker/price") {
+        if has_symbol { 1 } else { 2 }
+    } else if path.ends_with("/premiumIndex") {
+        if has_symbol { 1 } else { 10 }
+    } else if path.ends_with("/income") {
+        30
+    } else if path.ends_with("/positionRisk")
+        || path.ends_with("/balance")
+        || path.ends_with("/account")
+    {
+        5
+    } else {
+        1
+    }
+}
+
+/// Sending through the host's rate limit.
+#[allow(async_fn_in_trait)]
+pub trait SendLimited {
+    async fn send_limited(self) -> Result<Response, RateLimitError>;
+}
+
+impl SendLimited for RequestBuilder {
+    async fn send_limited(self) -> Result<Response, RateLimitError> {
+        let (client, request) = self.build_split();
+        let request = request?;
+        let Some(host) = host_key(request.url()) else {
+            return Ok(client.execute(request).await?);
+        };
+        let limits = limits();
+        let weight = weight(request.url());
+        let retry_5xx = request.method() == Method::GET;
+
+        let mut attempt = 0;
+        loop {
+            acquire(&host, weight, &limits).await?;
+            let Some(retry) = request.try_clone() else {
+                return Ok(client.execute(request).await?);
+            };
+            let resp = client.execute(retry).await?;
+            record_used_weight(&host, resp.headers());
+
+            let status = resp.status();
+            let delay = match status {
+                StatusCode::IM_A_TEAPOT => {
+                    let ban = retry_after(resp.headers()).unwrap_or(DEFAULT_BAN);
+                    tracing::error!("🚫 {} 已封禁本机 IP，{}s 内暂停请求", host, ban.as_secs());
+                    block(&host, ban);
+                    return Ok(resp);
+                }
+                StatusCode::TOO_MANY_REQUESTS => {
+                    backoff(&limits, attempt).max(retry_after(resp.headers()).unwrap_or_default())
+                }
+                s if s.is_server_error() && retry_5xx => backoff(&limits, attempt),
+                _ => return Ok(resp),
+            };
+            if attempt >= limits.max_retries || delay > limits.max_wait {
+                if status == StatusCode::TOO_MANY_REQUESTS {
+                    block(&host, delay);
+                }
+                return Ok(resp);
+            }
+            attempt += 1;
+            tracing::warn!(
+                "🚦 {} 返回 HTTP {}，{:?} 后第 {} 次重试",
+                host,
+                status.as_u16(),
+                delay,
+                attempt
+            );
+            if status == StatusCode::TOO_MANY_REQUESTS {
+                // Everyone else waits too, rather than adding to the overload.
+                block(&host, delay);
+            } else {
+                tokio::time::sleep(delay).await;
+            }
+        }
+    }
+}
+
+// Budgets are per host; a non-default port counts as another host.
+fn host_key(url: &Url) -> Option<String> {
+    let host = url.host_str()?;
+    Some(match url.port() {
+        Some(port) => format!("{}:{}", host, port),
+        None => host.to_string(),
+    })
+}
+
+fn backoff(limits: &RateLimits, attempt: u32) -> Duration {
+    limits.base_delay.saturating_mul(1 << attempt.min(16))
+}
+
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    headers
+        .get(RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+        .map(Duration::from_secs)
+}
+
+fn current_minute() -> i64 {
+    Utc::now().timestamp_millis().div_euclid(60_000)
+}
+
+// Takes `weight` from the host's budget, waiting for the next minute or the
+// end of a ban when that is within `max_wait`.
+async fn acquire(host: &str, weight: u32, limits: &RateLimits) -> Result<(), RateLimitError> {
+    let started = Instant::now();
+    loop {
+        let wait = {
+            let mut budgets = BUDGETS.lock().unwrap_or_else(|e| e.into_inner());
+            let budget = budgets.entry(host.to_string()).or_default();
+            let now = Instant::now();
+            match budget.blocked_until {
+                Some(until) if until > now => until - now,
+                _ => {
+                    budget.blocked_until = None;
+                    let minute = current_minute();
+                    if budget.minute != minute {
+                        budget.minute = minute;
+                        budget.used = 0;
+                    }
+                    if budget.used == 0 || budget.used + weight <= limits.limit(host) {
+                        budget.used += weight;
+                        return Ok(());
+                    }
+                    let into_minute = Utc::now().timestamp_millis().rem_euclid(60_000) as u64;
+                    Duration::from_millis(60_000 - into_minute)
+                }
+            }
+        };
+        if started.elapsed() + wait > limits.max_wait {
+            return Err(RateLimitError::Blocked {
+                host: host.to_string(),
+                retry_after: wait,
+            });
+        }
+        tracing::debug!("🚦 {} 请求权重已用尽，等待 {:?}", host, wait);
+        tokio::time::sleep(wait).await;
+    }
+}
+
+fn block(host: &str, duration: Duration) {
+    let mut budgets = BUDGETS.lock().unwrap_or_else(|e| e.into_inner());
+    let budget = budgets.entry(host.to_string()).or_default();
+    let until = Instant::now() + duration;
+    budget.blocked_until = Some(budget.blocked_until.map_or(until, |b| b.max(until)));
+}
+
+fn record_used_weight(host: &str, headers: &HeaderMap) {
+    let Some(used) = headers
+        .get(USED_WEIGHT_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.trim().parse::<u32>().ok())
+    else {
+        return;
+    };
+    let minute = current_minute();
+    let mut budgets = BUDGETS.lock().unwrap_or_else(|e| e.into_inner());
+    let budget = budgets.entry(host.to_string()).or_default();
+    if budget.minute != minute {
+        budget.minute = minute;
+        budget.used = 0;
+    }
+    budget.used = budget.used.max(used);
+}
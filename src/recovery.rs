@@ -0,0 +1,268 @@
+//! Startup recovery for traders that were running when the process died.
+//!
+//! A trader still flagged `is_running` at startup did not shut down cleanly.
+//! Instead of starting it cold, its state is rebuilt from the exchange and its
+//! decision log: live positions are reconciled against the last opening
+//! decisions, local SL/TP levels are re-armed from them, and cycle numbering
+//! continues where it stopped.
+//!
+//! Whenever a trader starts, clean restart or not, its ledger (the fills in
+//! the trades table) is also reconciled against the positions the exchange
+//! reports, since a crash between an order filling and its fill being stored
+//! leaves the two apart. See [`reconcile_ledger`]. Positions also change
+//! outside the bot while it runs (manual closes, liquidations), so running
+//! traders repeat this every [`ReconcileParams::interval`] and before each
+//! cycle.
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::database::{Database, Trade, TraderRecord};
+use crate::decision::{Action, PositionInfo};
+use crate::logger::{DecisionLogger, RecordKey};
+use crate::money::{self, Decimal};
+
+/// Group id of the trades written to bring a ledger in line with the exchange.
+pub const RECONCILIATION_GROUP: &str = "reconciliation";
+
+// Differences below this are float noise in the exchange's quantities.
+const QUANTITY_TOLERANCE: Decimal = Decimal::from_parts(1, 0, 0, false, 8);
+
+/// Periodic reconciliation of running traders' ledgers.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[serde(default)]
+pub struct ReconcileParams {
+    /// Time between checks while a trader waits for its next cycle; zero
+    /// only checks on start and before each cycle.
+    #[serde(with = "humantime_serde")]
+    pub interval: Duration,
+}
+
+impl Default for ReconcileParams {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(300),
+        }
+    }
+}
+
+/// A position whose recorded quantity differs from what the exchange holds,
+/// with the trade that closes the gap.
+#[derive(Debug, Clone)]
+pub struct Discrepancy {
+    pub side: String,
+    pub recorded: Decimal,
+    pub held: Decimal,
+    pub correction: Trade,
+}
+
+// How far back in the decision log to look for the decisions that opened the
+// positions that are still live.
+const RECOVERY_LOOKBACK_CYCLES: usize = 200;
+
+/// Stop-loss / take-profit levels to watch locally for an open position.
+#[derive(Debug, Clone, Serialize)]
+pub struct ProtectiveLevels {
+    pub symbol: String,
+    pub side: String,
+    pub stop_loss: Option<f64>,
+    pub take_profit: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RecoveredTrader {
+    pub trader_id: String,
+    /// Last cycle number found in the log; the next cycle continues from here.
+    pub cycle_number: i32,
+    pub positions: Vec<PositionInfo>,
+    /// Levels re-armed for positions that have a matching opening decision.
+    pub protective: Vec<ProtectiveLevels>,
+    /// Live positions with no matching decision (opened manually or log lost).
+    pub unprotected: Vec<String>,
+    /// Symbols the log says are open but the exchange does not (closed while down).
+    pub closed_while_down: Vec<String>,
+}
+
+/// Traders that were running when the process last stopped.
+pub async fn interrupted_traders(db: &Database) -> anyhow::Result<Vec<TraderRecord>> {
+    db.get_running_traders().await
+}
+
+/// Rebuilds a trader's state from its decision log and the positions currently
+/// held on the exchange. Returns the logger to keep using (cycle numbering
+/// resumed) together with a summary, and logs a `trader_recovered` event.
+pub fn recover_trader(
+    trader_id: &str,
+    log_dir: &str,
+    key: Option<RecordKey>,
+    live_positions: Vec<PositionInfo>,
+) -> (DecisionLogger, RecoveredTrader) {
+    let logger = DecisionLogger::resume_with_key(log_dir, key);
+    let recovered = recover_from_log(trader_id, &logger, live_positions);
+    (logger, recovered)
+}
+
+/// [`recover_trader`] for a trader whose logger is already resumed.
+pub fn recover_from_log(
+    trader_id: &str,
+    logger: &DecisionLogger,
+    live_positions: Vec<PositionInfo>,
+) -> RecoveredTrader {
+    let mut open_decisions = logger.last_open_decisions(RECOVERY_LOOKBACK_CYCLES);
+
+    let mut protective = Vec::new();
+    let mut unprotected = Vec::new();
+    for position in &live_positions {
+        let expected = if position.side == "short" {
+            Action::OpenShort
+        } else {
+            Action::OpenLong
+        };
+        match open_decisions.remove(&position.symbol) {
+            Some(d) if d.action == expected => protective.push(ProtectiveLevels {
+                symbol: position.symbol.clone(),
+                side: position.side.clone(),
+                stop_loss: Some(d.stop_loss).filter(|p| *p > 0.0),
+                take_profit: Some(d.take_profit).filter(|p| *p > 0.0),
+            }),
+            _ => unprotected.push(position.symbol.clone()),
+        }
+    }
+
+    let mut closed_while_down: Vec<String> = open_decisions.into_keys().collect();
+    closed_while_down.sort();
+
+    let recovered = RecoveredTrader {
+        trader_id: trader_id.to_string(),
+        cycle_number: logger.cycle_number(),
+        positions: live_positions,
+        protective,
+        unprotected,
+        closed_while_down,
+    };
+
+    tracing::info!(
+        event = "trader_recovered",
+        trader_id,
+        cycle = recovered.cycle_number,
+        positions = recovered.positions.len(),
+        rearmed = recovered.protective.len(),
+        "♻️ 交易员 {} 已从崩溃中恢复 (周期 #{}, {} 个持仓)",
+        trader_id,
+        recovered.cycle_number,
+        recovered.positions.len()
+    );
+    if !recovered.unprotected.is_empty() {
+        tracing::warn!(
+            "⚠️ 交易员 {} 以下持仓没有找到对应决策，未恢复止盈止损: {:?}",
+            trader_id,
+            recovered.unprotected
+        );
+    }
+    if !recovered.closed_while_down.is_empty() {
+        tracing::info!(
+            "ℹ️ 交易员 {} 以下持仓在停机期间已平仓: {:?}",
+            trader_id,
+            recovered.closed_while_down
+        );
+    }
+
+    recovered
+}
+
+/// Open quantity per (symbol, side) according to a trader's fills.
+pub fn ledger_positions(trades: &[Trade]) -> BTreeMap<(String, String), Decimal> {
+    let mut trades: Vec<&Trade> = trades.iter().collect();
+    trades.sort_by_key(|t| (t.executed_at, t.id));
+    let mut open: BTreeMap<(String, String), Decimal> = BTreeMap::new();
+    for trade in trades {
+        let (side, sign) = match trade.action.as_str() {
+            "open_long" => ("long", Decimal::ONE),
+            "close_long" => ("long", -Decimal::ONE),
+            "open_short" => ("short", Decimal::ONE),
+            "close_short" => ("short", -Decimal::ONE),
+            _ => continue,
+        };
+        let quantity = open
+            .entry((trade.symbol.clone(), side.to_string()))
+            .or_default();
+        *quantity = (*quantity + sign * trade.quantity).max(Decimal::ZERO);
+    }
+    open.retain(|_, quantity| *quantity > Decimal::ZERO);
+    open
+}
+
+/// The positions where `trades` do not add up to the `live` ones, each with
+/// the trade that corrects it: an open for quantity the ledger is missing, a
+/// close for quantity the exchange no longer holds. Opens are priced at the position's entry price, closes at its mark
+/// price or, once it is gone, at `last_price` for the symbol (0 if unknown).
+pub fn reconcile_ledger(
+    trader: &TraderRecord,
+    trades: &[Trade],
+    live: &[PositionInfo],
+    last_price: impl Fn(&str) -> Option<f64>,
+) -> Vec<Discrepancy> {
+    let mut ledger = ledger_positions(trades);
+    let mut exchange: BTreeMap<(String, String), &PositionInfo> = BTreeMap::new();
+    for position in live.iter().filter(|p| p.quantity != 0.0) {
+        exchange.insert((position.symbol.clone(), position.side.clone()), position);
+        ledger
+            .entry((position.symbol.clone(), position.side.clone()))
+            .or_default();
+    }
+
+    let mut discrepancies = Vec::new();
+    for ((symbol, side), recorded) in ledger {
+        let position = exchange.get(&(symbol.clone(), side.clone()));
+        let held = position.map_or(Decimal::ZERO, |p| money::from_f64(p.quantity.abs()));
+        if (held - recorded).abs() < QUANTITY_TOLERANCE {
+            continue;
+        }
+        let (action, quantity, price) = if held > recorded {
+            let open = if side == "short" {
+                Action::OpenShort
+            } else {
+                Action::OpenLong
+            };
+            (
+                open,
+                held - recorded,
+                position.map_or(0.0, |p| p.entry_price),
+            )
+        } else {
+            let close = if side == "short" {
+                Action::CloseShort
+            } else {
+                Action::CloseLong
+            };
+            let price = match position {
+                Some(p) => p.mark_price,
+                None => last_price(&symbol).unwrap_or_default(),
+            };
+            (close, recorded - held, price)
+        };
+        let buy = matches!(action, Action::OpenLong | Action::CloseShort);
+        let correction = Trade {
+            user_id: trader.user_id.clone(),
+            trader_id: trader.id.clone(),
+            symbol,
+            side: if buy { "buy" } else { "sell" }.to_string(),
+            action: action.as_str().to_string(),
+            quantity,
+            price: money::from_f64(price),
+            group_id: RECONCILIATION_GROUP.to_string(),
+            variant: trader.variant.clone(),
+            executed_at: chrono::Utc::now(),
+            ..Default::default()
+        };
+        discrepancies.push(Discrepancy {
+            side,
+            recorded,
+            held,
+            correction,
+        });
+    }
+    discrepancies
+}
@@ -0,0 +1,261 @@
+//! Decision replay for debugging prompt changes.
+//!
+//! A replay takes a stored decision record, renders the system prompt again
+//! from the trader's current template and settings, sends it with the
+//! record's logged user prompt to a model (the trader's own, or a canned
+//! response) and compares the decisions that come back with the ones the
+//! record holds. Nothing is executed and nothing is written to the record.
+//!
+//! The template variables are filled in from the record's account and
+//! position snapshot. The record does not keep the recent-performance summary
+//! the cycle saw, so `{{performance_summary}}` renders as if there were no
+//! closed trades yet.
+
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::ai::{self, AiError, AiFuture, AiProvider};
+use crate::database::{Database, TraderRecord};
+use crate::decision::{self, AccountInfo, Context, Decision, Limits};
+use crate::logger::{self, DecisionRecord, RecordKey};
+use crate::prompt;
+use crate::prompt_template;
+use crate::quota;
+use crate::secrets::{self, SecretError};
+
+#[derive(Error, Debug)]
+pub enum ReplayError {
+    #[error("Trader {0} not found")]
+    TraderNotFound(String),
+    #[error("Decision record {0} not found")]
+    RecordNotFound(String),
+    #[error("AI error: {0}")]
+    Ai(#[from] AiError),
+    #[error("Secret error: {0}")]
+    Secret(#[from] SecretError),
+    #[error("Database error: {0}")]
+    Database(#[from] anyhow::Error),
+}
+
+pub type Result<T> = std::result::Result<T, ReplayError>;
+
+/// A provider that answers every prompt with the same text, for replaying
+/// without calling a model.
+#[derive(Debug, Clone)]
+pub struct CannedResponse(pub String);
+
+impl AiProvider for CannedResponse {
+    fn name(&self) -> String {
+        "canned".to_string()
+    }
+
+    fn chat_completion<'a>(&'a mut self, _: &'a str, _: &'a str) -> AiFuture<'a, String> {
+        Box::pin(async move { Ok(self.0.clone()) })
+    }
+}
+
+/// How one symbol's decision differs between the record and the replay.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DecisionDiff {
+    pub symbol: String,
+    /// `None` when only the replay acts on the symbol.
+    pub original: Option<Decision>,
+    /// `None` when only the record acts on the symbol.
+    pub replayed: Option<Decision>,
+    /// Fields that differ when both act on the symbol; reasoning is ignored.
+    pub changed: Vec<&'static str>,
+}
+
+/// Outcome of replaying one decision record.
+#[derive(Debug, Clone, Serialize)]
+pub struct Replay {
+    pub record_id: String,
+    pub model: String,
+    /// The system prompt as rendered now.
+    pub system_prompt: String,
+    /// Whether it differs from the one the record was made with.
+    pub system_prompt_changed: bool,
+    pub response: String,
+    /// Why the response held no usable decision array, if it did not.
+    pub error: Option<String>,
+    pub original: Vec<Decision>,
+    pub replayed: Vec<Decision>,
+    /// Entries of the response dropped by the decision limits.
+    pub rejected: Vec<String>,
+    /// Symbols whose decisions differ; empty when the replay agrees.
+    pub diff: Vec<DecisionDiff>,
+}
+
+// The decision context as far as the record preserves it.
+fn context(trader: &TraderRecord, record: &DecisionRecord) -> Context {
+    let positions = record.positions();
+    let margin_used = positions.iter().map(|p| p.margin_used).sum();
+    Context {
+        current_time: record.timestamp(),
+        call_count: record.cycle_number(),
+        account: AccountInfo {
+            total_equity: record.total_equity(),
+            available_balance: record.available_balance(),
+            margin_used,
+            margin_used_pct: record.margin_used_pct(),
+            position_count: record.position_count(),
+            ..Default::default()
+        },
+        positions,
+        candidate_coins: record.candidate_coins().to_vec(),
+        btc_eth_leverage: trader.btc_eth_leverage,
+        altcoin_leverage: trader.altcoin_leverage,
+        ..Default::default()
+    }
+}
+
+fn changed_fields(a: &Decision, b: &Decision) -> Vec<&'static str> {
+    [
+        ("action", a.action != b.action),
+        ("leverage", a.leverage != b.leverage),
+        (
+            "position_size_usd",
+            a.position_size_usd != b.position_size_usd,
+        ),
+        ("stop_loss", a.stop_loss != b.stop_loss),
+        ("take_profit", a.take_profit != b.take_profit),
+        ("confidence", a.confidence != b.confidence),
+        ("risk_usd", a.risk_usd != b.risk_usd),
+        ("pair", a.pair != b.pair),
+    ]
+    .into_iter()
+    .filter_map(|(field, differs)| differs.then_some(field))
+    .collect()
+}
+
+/// Compares two decision lists symbol by symbol. Several decisions on one
+/// symbol are matched in order. Symbols appear in the order they are first
+/// seen, original decisions first.
+pub fn diff(original: &[Decision], replayed: &[Decision]) -> Vec<DecisionDiff> {
+    let mut symbols: Vec<&str> = Vec::new();
+    for d in original.iter().chain(replayed) {
+        if !symbols.contains(&d.symbol.as_str()) {
+            symbols.push(&d.symbol);
+        }
+    }
+
+    let mut diffs = Vec::new();
+    for symbol in symbols {
+        let mut before = original.iter().filter(|d| d.symbol == symbol);
+        let mut after = replayed.iter().filter(|d| d.symbol == symbol);
+        loop {
+            let (a, b) = (before.next(), after.next());
+            let changed = match (a, b) {
+                (None, None) => break,
+                (Some(a), Some(b)) => changed_fields(a, b),
+                _ => Vec::new(),
+            };
+            if a.is_some() && b.is_some() && changed.is_empty() {
+                continue;
+            }
+            diffs.push(DecisionDiff {
+                symbol: symbol.to_string(),
+                original: a.cloned(),
+                replayed: b.cloned(),
+                changed,
+            });
+        }
+    }
+    diffs
+}
+
+/// Replays `record` through `ai`, with the system prompt rendered from
+/// `template` and the trader's current settings. Only a failed model call is
+/// an error; an unusable response is reported in [`Replay::error`].
+pub async fn run(
+    ai: &mut dyn AiProvider,
+    trader: &TraderRecord,
+    template: &str,
+    record: &DecisionRecord,
+) -> Result<Replay> {
+    let ctx = context(trader, record);
+    let system_prompt = prompt::system_prompt(trader, template, &ctx);
+    let response = ai
+        .chat_completion(&system_prompt, record.input_prompt())
+        .await?;
+
+    let original = if record.decision_json().is_empty() {
+        Vec::new()
+    } else {
+        serde_json::from_str(record.decision_json()).unwrap_or_else(|e| {
+            tracing::warn!("⚠️ 决策记录 {} 的决策无法解析: {}", record.id(), e);
+            Vec::new()
+        })
+    };
+    let (replayed, rejected, error) =
+        match decision::parse_response(&response, &Limits::from_context(&ctx)) {
+            Ok(parsed) => (
+                parsed.decisions,
+                parsed.rejected.iter().map(|e| e.to_string()).collect(),
+                None,
+            ),
+            Err(e) => (Vec::new(), Vec::new(), Some(e.to_string())),
+        };
+
+    Ok(Replay {
+        record_id: record.id().to_string(),
+        model: ai.name(),
+        system_prompt_changed: system_prompt != record.system_prompt(),
+        system_prompt,
+        response,
+        error,
+        diff: diff(&original, &replayed),
+        original,
+        replayed,
+        rejected,
+    })
+}
+
+/// Replays one of the trader's stored records with its current template.
+/// With a `canned` response no model is called; otherwise the trader's own
+/// model answers and the call counts against its AI usage.
+pub async fn for_record(
+    db: &Database,
+    user_id: &str,
+    trader_id: &str,
+    record_id: &str,
+    key: Option<&RecordKey>,
+    canned: Option<&str>,
+) -> Result<Replay> {
+    let trader = db
+        .get_trader(user_id, trader_id)
+        .await?
+        .ok_or_else(|| ReplayError::TraderNotFound(trader_id.to_string()))?;
+    let record = logger::load_record(db, user_id, trader_id, record_id, key)
+        .await?
+        .ok_or_else(|| ReplayError::RecordNotFound(record_id.to_string()))?;
+    let template = prompt_template::resolve(db, &trader).await?;
+
+    let replay = match canned {
+        Some(response) => {
+            let mut ai = CannedResponse(response.to_string());
+            run(&mut ai, &trader, &template, &record).await?
+        }
+        None => {
+            let (_, model, _) = db.get_trader_config(user_id, trader_id).await?;
+            let mut ai = ai::from_model_config(&secrets::resolve_model(&model).await?)?;
+            let replay = run(ai.as_mut(), &trader, &template, &record).await?;
+            quota::record_ai_call(
+                db,
+                &trader,
+                replay.system_prompt.len() + record.input_prompt().len(),
+                replay.response.len(),
+            )
+            .await;
+            replay
+        }
+    };
+    tracing::info!(
+        "🔁 交易员 {} 的决策记录 {} 已重放（{}），{} 处差异",
+        trader_id,
+        record_id,
+        replay.model,
+        replay.diff.len()
+    );
+    Ok(replay)
+}
@@ -0,0 +1,245 @@
+//! Bounded retry queue for order executions that failed transiently.
+//!
+//! A timeout or rate limit should not throw away the AI's intended action
+//! until the next cycle. The failed order is queued with the price it was
+//! decided at and retried with exponential backoff, but only after it has been
+//! re-validated: if the price has drifted too far or the position no longer
+//! matches what the order expects, the retry is dropped instead. A newer
+//! intent for the same key replaces the queued one, and when the queue is full
+//! the oldest entry is evicted.
+
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::decision::{Action, Decision, PositionInfo};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[serde(default)]
+pub struct RetryPolicy {
+    /// Queued orders per trader.
+    pub capacity: usize,
+    /// Retries after the first failure.
+    pub max_attempts: u32,
+    /// Delay before the first retry; doubles with each attempt.
+    #[serde(with = "humantime_serde")]
+    pub base_delay: Duration,
+    /// An order older than this is no longer what the AI intended.
+    #[serde(with = "humantime_serde")]
+    pub max_age: Duration,
+    /// Largest price move since the decision, in percent, that still allows a retry.
+    pub max_price_drift_pct: f64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            capacity: 16,
+            max_attempts: 3,
+            base_delay: Duration::from_secs(2),
+            max_age: Duration::from_secs(120),
+            max_price_drift_pct: 0.5,
+        }
+    }
+}
+
+/// Whether an execution error is worth retrying: timeouts, connection
+/// failures, rate limits and 5xx responses are; rejections are not.
+pub fn is_transient(error: &str) -> bool {
+    let e = error.to_lowercase();
+    [
+        "timed out",
+        "timeout",
+        "connection",
+        "rate limit",
+        "too many requests",
+        "429",
+        "418",
+        "502",
+        "503",
+        "504",
+        "-1001", // Binance: internal error / disconnected
+        "-1003", // Binance: too many requests
+        "-1007", // Binance: timeout waiting for backend
+    ]
+    .iter()
+    .any(|needle| e.contains(needle))
+}
+
+#[derive(Debug, Clone)]
+pub struct Pending<T> {
+    pub key: String,
+    pub item: T,
+    /// Retries already made.
+    pub attempts: u32,
+    pub first_failed_at: DateTime<Utc>,
+    pub next_attempt_at: DateTime<Utc>,
+    pub last_error: String,
+}
+
+/// A FIFO of failed items awaiting retry.
+#[derive(Debug)]
+pub struct RetryQueue<T> {
+    policy: RetryPolicy,
+    items: VecDeque<Pending<T>>,
+}
+
+impl<T> RetryQueue<T> {
+    pub fn new(policy: RetryPolicy) -> Self {
+        Self {
+            policy,
+            items: VecDeque::new(),
+        }
+    }
+
+    pub fn policy(&self) -> &RetryPolicy {
+        &self.policy
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Queues an item after its first failure, replacing any queued item with
+    /// the same key. Returns the item evicted to make room, if any.
+    pub fn push(&mut self, key: &str, item: T, error: &str) -> Option<Pending<T>> {
+        if self.policy.capacity == 0 {
+            return None;
+        }
+        self.items.retain(|p| p.key != key);
+
+        let now = Utc::now();
+        let evicted = if self.items.len() >= self.policy.capacity {
+            self.items.pop_front()
+        } else {
+            None
+        };
+        self.items.push_back(Pending {
+            key: key.to_string(),
+            item,
+            attempts: 0,
+            first_failed_at: now,
+            next_attempt_at: now + self.backoff(0),
+            last_error: error.to_string(),
+        });
+        evicted
+    }
+
+    /// Removes and returns the items due for a retry. Items past their maximum
+    /// age are discarded on the way.
+    pub fn take_due(&mut self, now: DateTime<Utc>) -> Vec<Pending<T>> {
+        let max_age = chrono::Duration::from_std(self.policy.max_age).unwrap_or_default();
+        let mut due = Vec::new();
+        let mut kept = VecDeque::with_capacity(self.items.len());
+        for p in self.items.drain(..) {
+            if now - p.first_failed_at > max_age {
+                tracing::warn!("⌛ 重试队列中的 {} 已过期，放弃重试", p.key);
+            } else if p.next_attempt_at <= now {
+                due.push(p);
+            } else {
+                kept.push_back(p);
+            }
+        }
+        self.items = kept;
+        due
+    }
+
+    /// Puts an item back after another transient failure. Returns false, and
+    /// drops it, once it has used all its attempts.
+    pub fn requeue(&mut self, mut pending: Pending<T>, error: &str) -> bool {
+        pending.attempts += 1;
+        pending.last_error = error.to_string();
+        if pending.attempts >= self.policy.max_attempts {
+            tracing::warn!(
+                "❌ {} 重试 {} 次仍失败，放弃: {}",
+                pending.key,
+                pending.attempts,
+                error
+            );
+            return false;
+        }
+        pending.next_attempt_at = Utc::now() + self.backoff(pending.attempts);
+        if self.items.len() >= self.policy.capacity {
+            self.items.pop_front();
+        }
+        self.items.push_back(pending);
+        true
+    }
+
+    /// Drops everything queued, e.g. when the trader stops.
+    pub fn clear(&mut self) {
+        self.items.clear();
+    }
+
+    fn backoff(&self, attempts: u32) -> chrono::Duration {
+        let delay = self.policy.base_delay.saturating_mul(1 << attempts.min(16));
+        chrono::Duration::from_std(delay).unwrap_or_default()
+    }
+}
+
+/// An order the executor failed to place.
+#[derive(Debug, Clone, Serialize)]
+pub struct OrderIntent {
+    pub decision: Decision,
+    /// Mark price when the decision was made.
+    pub reference_price: f64,
+}
+
+impl OrderIntent {
+    /// Queue key: one pending order per symbol and action.
+    pub fn key(&self) -> String {
+        format!("{}:{:?}", self.decision.symbol, self.decision.action)
+    }
+}
+
+/// Why a queued order was not retried.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Stale {
+    PriceDrift { drift_pct: f64 },
+    AlreadyOpen,
+    NothingToClose,
+}
+
+/// Re-validates an order against the current price and position before it is
+/// retried. Opens are dropped when a position on that side already exists
+/// (the first attempt may have gone through); closes when there is nothing to
+/// close. Price drift only matters for opens, closes are always worth retrying.
+pub fn revalidate(
+    intent: &OrderIntent,
+    current_price: f64,
+    position: Option<&PositionInfo>,
+    max_price_drift_pct: f64,
+) -> Result<(), Stale> {
+    let side = match intent.decision.action {
+        Action::OpenLong | Action::CloseLong => "long",
+        Action::OpenShort | Action::CloseShort => "short",
+        Action::Hold | Action::Wait => return Ok(()),
+    };
+    let has_position = position.is_some_and(|p| p.side == side && p.quantity != 0.0);
+
+    if intent.decision.action.is_close() {
+        return if has_position {
+            Ok(())
+        } else {
+            Err(Stale::NothingToClose)
+        };
+    }
+
+    if has_position {
+        return Err(Stale::AlreadyOpen);
+    }
+    if intent.reference_price > 0.0 {
+        let drift_pct =
+            (current_price - intent.reference_price).abs() / intent.reference_price * 100.0;
+        if drift_pct > max_price_drift_pct {
+            return Err(Stale::PriceDrift { drift_pct });
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,420 @@
+//! Risk overrides pushed by an external risk system.
+//!
+//! Institutional risk infrastructure posts directives to
+//! `POST /api/risk/directives`: halt a user, cap a user's leverage, or
+//! blacklist a symbol for one user or for everyone. Requests carry an
+//! HMAC-SHA256 of `"{timestamp}.{body}"` under the shared
+//! `risk_webhook_secret` in `X-Risk-Signature`, and the Unix timestamp in
+//! `X-Risk-Timestamp`; requests more than five minutes off are refused, and
+//! a signature already accepted within that window is refused again, so a
+//! captured request cannot be replayed.
+//!
+//! Overrides are stored in system_config and re-read by the `risk_overrides`
+//! scheduler job. Every trader applies them from its next cycle: a halted
+//! user opens nothing, entries above the leverage cap are lowered to it and
+//! entries on blacklisted symbols are dropped. Closes always go through.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::{Mutex, RwLock};
+
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use thiserror::Error;
+
+use crate::data::normalize;
+use crate::database::Database;
+use crate::decision::Decision;
+use crate::notify::{self, Event};
+
+const CONFIG_KEY: &str = "risk_overrides";
+/// How far a request's timestamp may be from the server clock.
+pub const MAX_CLOCK_SKEW_SECS: i64 = 300;
+
+static STATE: Lazy<RwLock<Overrides>> = Lazy::new(|| RwLock::new(Overrides::default()));
+static WEBHOOK_SECRET: Lazy<RwLock<Option<Vec<u8>>>> = Lazy::new(|| RwLock::new(None));
+// Signatures accepted within the clock-skew window, with their timestamps.
+static SEEN: Lazy<Mutex<HashMap<Vec<u8>, i64>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+#[derive(Error, Debug)]
+pub enum RiskOverrideError {
+    #[error("Risk webhook is not configured")]
+    Disabled,
+    #[error("Invalid risk webhook signature")]
+    InvalidSignature,
+    #[error("Risk webhook timestamp is too far from the server clock")]
+    StaleTimestamp,
+    #[error("Risk webhook request was already received")]
+    Replayed,
+    #[error("Invalid directive: {0}")]
+    InvalidDirective(String),
+    #[error("Database error: {0}")]
+    Database(#[from] anyhow::Error),
+}
+
+/// One instruction from the risk system.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum Directive {
+    HaltUser {
+        user_id: String,
+        #[serde(default)]
+        reason: String,
+    },
+    ResumeUser {
+        user_id: String,
+    },
+    /// `max_leverage` 0 lifts the cap.
+    CapLeverage {
+        user_id: String,
+        max_leverage: i32,
+    },
+    /// Without `user_id` the symbol is blocked for every user.
+    BlacklistSymbol {
+        #[serde(default)]
+        user_id: Option<String>,
+        symbol: String,
+    },
+    UnblacklistSymbol {
+        #[serde(default)]
+        user_id: Option<String>,
+        symbol: String,
+    },
+}
+
+impl Directive {
+    fn user_id(&self) -> Option<&str> {
+        match self {
+            Directive::HaltUser { user_id, .. }
+            | Directive::ResumeUser { user_id }
+            | Directive::CapLeverage { user_id, .. } => Some(user_id),
+            Directive::BlacklistSymbol { user_id, .. }
+            | Directive::UnblacklistSymbol { user_id, .. } => user_id.as_deref(),
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            Directive::HaltUser { reason, .. } if reason.is_empty() => {
+                "trading halted by risk control".to_string()
+            }
+            Directive::HaltUser { reason, .. } => {
+                format!("trading halted by risk control: {}", reason)
+            }
+            Directive::ResumeUser { .. } => "risk control halt lifted".to_string(),
+            Directive::CapLeverage { max_leverage, .. } if *max_leverage == 0 => {
+                "risk control leverage cap lifted".to_string()
+            }
+            Directive::CapLeverage { max_leverage, .. } => {
+                format!("leverage capped at {}x by risk control", max_leverage)
+            }
+            Directive::BlacklistSymbol { symbol, .. } => {
+                format!("{} blacklisted by risk control", normalize(symbol))
+            }
+            Directive::UnblacklistSymbol { symbol, .. } => {
+                format!(
+                    "{} removed from the risk control blacklist",
+                    normalize(symbol)
+                )
+            }
+        }
+    }
+}
+
+/// Body of a webhook request.
+#[derive(Debug, Clone, Deserialize)]
+pub struct DirectiveBatch {
+    pub directives: Vec<Directive>,
+}
+
+/// Overrides in force for one user.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct UserOverride {
+    pub halted: bool,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub halt_reason: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_leverage: Option<i32>,
+    #[serde(default)]
+    pub symbols: BTreeSet<String>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+impl UserOverride {
+    fn is_empty(&self) -> bool {
+        !self.halted && self.max_leverage.is_none() && self.symbols.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Overrides {
+    /// Keyed by user id.
+    #[serde(default)]
+    pub users: BTreeMap<String, UserOverride>,
+    /// Symbols blacklisted for every user.
+    #[serde(default)]
+    pub symbols: BTreeSet<String>,
+}
+
+impl Overrides {
+    fn apply(&mut self, directive: &Directive, now: DateTime<Utc>) {
+        if let Some(user_id) = directive.user_id() {
+            let user = self.users.entry(user_id.to_string()).or_default();
+            match directive {
+                Directive::HaltUser { reason, .. } => {
+                    user.halted = true;
+                    user.halt_reason = reason.clone();
+                }
+                Directive::ResumeUser { .. } => {
+                    user.halted = false;
+                    user.halt_reason.clear();
+                }
+                Directive::CapLeverage { max_leverage, .. } => {
+                    user.max_leverage = Some(*max_leverage).filter(|l| *l > 0);
+                }
+                Directive::BlacklistSymbol { symbol, .. } => {
+                    user.symbols.insert(normalize(symbol));
+                }
+                Directive::UnblacklistSymbol { symbol, .. } => {
+                    user.symbols.remove(&normalize(symbol));
+                }
+            }
+            user.updated_at = Some(now);
+            if user.is_empty() {
+                self.users.remove(user_id);
+            }
+            return;
+        }
+        match directive {
+            Directive::BlacklistSymbol { symbol, .. } => {
+                self.symbols.insert(normalize(symbol));
+            }
+            Directive::UnblacklistSymbol { symbol, .. } => {
+                self.symbols.remove(&normalize(symbol));
+            }
+            _ => {}
+        }
+    }
+
+    /// The user's overrides, with the global blacklist merged in.
+    pub fn for_user(&self, user_id: &str) -> UserOverride {
+        let mut user = self.users.get(user_id).cloned().unwrap_or_default();
+        user.symbols.extend(self.symbols.iter().cloned());
+        user
+    }
+}
+
+/// Sets the shared webhook secret; `None` disables the endpoint.
+pub fn set_webhook_secret(secret: Option<&str>) {
+    *WEBHOOK_SECRET.write().unwrap_or_else(|e| e.into_inner()) = secret
+        .filter(|s| !s.is_empty())
+        .map(|s| s.as_bytes().to_vec());
+}
+
+fn mac(secret: &[u8], timestamp: &str, body: &[u8]) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC accepts keys of any length");
+    mac.update(timestamp.as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    mac
+}
+
+/// Hex signature of a request, as the risk system computes it.
+pub fn sign(secret: &str, timestamp: &str, body: &[u8]) -> String {
+    hex::encode(
+        mac(secret.as_bytes(), timestamp, body)
+            .finalize()
+            .into_bytes(),
+    )
+}
+
+/// Checks a request's signature and freshness against the configured secret,
+/// and that the same signed request was not accepted before.
+pub fn verify_signature(
+    timestamp: &str,
+    body: &[u8],
+    signature: &str,
+    now: DateTime<Utc>,
+) -> Result<(), RiskOverrideError> {
+    let secret = WEBHOOK_SECRET
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+        .ok_or(RiskOverrideError::Disabled)?;
+    let sent_at: i64 = timestamp
+        .trim()
+        .parse()
+        .map_err(|_| RiskOverrideError::InvalidSignature)?;
+    if (now.timestamp() - sent_at).abs() > MAX_CLOCK_SKEW_SECS {
+        return Err(RiskOverrideError::StaleTimestamp);
+    }
+    let signature = signature.trim();
+    let signature = signature.strip_prefix("sha256=").unwrap_or(signature);
+    let signature = hex::decode(signature).map_err(|_| RiskOverrideError::InvalidSignature)?;
+    mac(&secret, timestamp.trim(), body)
+        .verify_slice(&signature)
+        .map_err(|_| RiskOverrideError::InvalidSignature)?;
+
+    let mut seen = SEEN.lock().unwrap_or_else(|e| e.into_inner());
+    // 超出时间窗口的签名已会因时间戳被拒绝，无需再记
+    seen.retain(|_, at| (now.timestamp() - *at).abs() <= MAX_CLOCK_SKEW_SECS);
+    if seen.insert(signature, sent_at).is_some() {
+        return Err(RiskOverrideError::Replayed);
+    }
+    Ok(())
+}
+
+fn set(overrides: Overrides) {
+    *STATE.write().unwrap_or_else(|e| e.into_inner()) = overrides;
+}
+
+/// Every override in force.
+pub fn current() -> Overrides {
+    STATE.read().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+/// Overrides in force for a user, including the global blacklist.
+pub fn for_user(user_id: &str) -> UserOverride {
+    STATE
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .for_user(user_id)
+}
+
+/// Validates and applies a batch of directives, stores the result and tells
+/// each affected user. Nothing is applied if any directive is invalid.
+pub async fn apply(
+    db: &Database,
+    directives: &[Directive],
+) -> Result<Overrides, RiskOverrideError> {
+    for directive in directives {
+        match directive {
+            Directive::CapLeverage { max_leverage, .. } if *max_leverage < 0 => {
+                return Err(RiskOverrideError::InvalidDirective(format!(
+                    "max_leverage must not be negative, got {}",
+                    max_leverage
+                )));
+            }
+            Directive::BlacklistSymbol { symbol, .. }
+            | Directive::UnblacklistSymbol { symbol, .. }
+                if symbol.trim().is_empty() =>
+            {
+                return Err(RiskOverrideError::InvalidDirective(
+                    "symbol must not be empty".to_string(),
+                ));
+            }
+            _ => {}
+        }
+        if let Some(user_id) = directive.user_id()
+            && db.get_user_by_id(user_id).await?.is_none()
+        {
+            return Err(RiskOverrideError::InvalidDirective(format!(
+                "unknown user {}",
+                user_id
+            )));
+        }
+    }
+
+    // Start from the stored state so directives from other processes are kept.
+    sync(db).await?;
+    let mut overrides = current();
+    let now = Utc::now();
+    for directive in directives {
+        overrides.apply(directive, now);
+    }
+    let json = serde_json::to_string(&overrides).map_err(anyhow::Error::from)?;
+    db.set_system_config(CONFIG_KEY, &json).await?;
+    set(overrides.clone());
+
+    for directive in directives {
+        let message = directive.describe();
+        tracing::warn!(
+            "🛡️ 外部风控指令: {} (用户: {})",
+            message,
+            directive.user_id().unwrap_or("全部")
+        );
+        let mut event = Event::alert("risk_override", message)
+            .detail("directive", directive)
+            .detail("applied_at", now);
+        if let Some(user_id) = directive.user_id() {
+            event = event.user(user_id);
+        }
+        notify::notify(event);
+    }
+    Ok(overrides)
+}
+
+/// Reloads the stored overrides, picking up changes made by other processes.
+pub async fn sync(db: &Database) -> Result<(), RiskOverrideError> {
+    let stored = match db.get_system_config(CONFIG_KEY).await {
+        Ok(json) => serde_json::from_str(&json).map_err(anyhow::Error::from)?,
+        Err(e) if matches!(e.downcast_ref(), Some(sqlx::Error::RowNotFound)) => {
+            Overrides::default()
+        }
+        // Keep the current overrides rather than lifting them on a read error.
+        Err(e) => return Err(e.into()),
+    };
+    set(stored);
+    Ok(())
+}
+
+/// Drops or adjusts a user's entries under the overrides in force; closes are
+/// always let through.
+pub fn filter(user_id: &str, decisions: Vec<Decision>) -> Vec<Decision> {
+    let user = for_user(user_id);
+    decisions
+        .into_iter()
+        .filter_map(|mut d| {
+            if !d.action.is_open() {
+                return Some(d);
+            }
+            if user.halted {
+                tracing::warn!(
+                    "🛡️ 用户 {} 已被外部风控暂停，拒绝开仓 {}",
+                    user_id,
+                    d.symbol
+                );
+                return None;
+            }
+            if let Some(symbol) = d.symbols().find(|s| user.symbols.contains(&normalize(s))) {
+                tracing::warn!("🛡️ {} 已被外部风控列入黑名单，拒绝开仓", symbol);
+                return None;
+            }
+            if let Some(max) = user.max_leverage
+                && d.leverage > max
+            {
+                tracing::warn!(
+                    "🛡️ {} 杠杆 {}x 超过外部风控上限，降至 {}x",
+                    d.symbol,
+                    d.leverage,
+                    max
+                );
+                d.leverage = max;
+            }
+            Some(d)
+        })
+        .collect()
+}
+
+/// A prompt note listing the user's overrides, `None` when there are none.
+pub fn prompt_annotation(user_id: &str) -> Option<String> {
+    let user = for_user(user_id);
+    let mut lines = Vec::new();
+    if user.halted {
+        lines
+            .push("New positions are halted by risk control; only closes are allowed.".to_string());
+    }
+    if let Some(max) = user.max_leverage {
+        lines.push(format!("Risk control caps leverage at {}x.", max));
+    }
+    if !user.symbols.is_empty() {
+        let symbols: Vec<&str> = user.symbols.iter().map(String::as_str).collect();
+        lines.push(format!(
+            "Risk control blacklisted {}; do not open positions on them.",
+            symbols.join(", ")
+        ));
+    }
+    (!lines.is_empty()).then(|| lines.join("\n"))
+}
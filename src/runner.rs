@@ -0,0 +1,1896 @@
+//! Runs live traders.
+//!
+//! [`Runner`] starts a task for every trader flagged `is_running`. Each task
+//! ticks every `scan_interval_minutes`, or with [`CycleAlignment`] shortly
+//! after each close of a candle that long, and runs one [`TraderCycle`]: fetch
+//! market data for the trader's symbols, build the prompts, ask the AI, apply
+//! the risk filters, execute, and write a [`DecisionRecord`]. Each cycle also
+//! stores an equity snapshot and every fill in the database. Traders are
+//! started and stopped through a [`RunnerHandle`]; the runner also follows the
+//! `is_running` flag in the database, so pause windows and the CLI take effect
+//! within a minute. Traders over their owner's [`quota`] are not started.
+//! Before a symbol's first cycle, on start or when it joins the trader's list,
+//! its candle history is warmed up so indicators are seeded; entries on symbols
+//! whose data is still warming up are rejected. Entries above a trader's
+//! approval threshold wait for a human through [`approval`]. Every directional
+//! call is recorded for [`accuracy`] scoring, and the model's hit rate so far
+//! is shown in its prompt. Directives from an external risk system
+//! ([`risk_override`]) restrict entries from the next cycle on. Entry sizes
+//! follow the trader's [`sizing`] method. A trader with a [`strategy`] has it
+//! replace the AI, or review the AI's decisions, before the risk filters; its
+//! decisions are validated like the AI's. A trader's uploaded decision filter
+//! runs last, after the built-in risk filters; entries are refused when it
+//! cannot run.
+//! Stopping a trader never interrupts a cycle in progress. Shutting the runner
+//! down abandons cycles still waiting for the AI, writing their record, but
+//! lets order placement that has begun finish; the traders stay flagged
+//! running. On start, before every cycle and periodically in between, a
+//! trader's recorded fills are reconciled against the exchange's positions
+//! through [`recovery`], so closes and liquidations made outside the bot are
+//! booked before the AI next sees the account; positions closed by their
+//! stop-loss that way put the symbol on [`cooldown`]. Traders the process
+//! died under are recovered on their first start, re-arming their positions'
+//! stop-loss / take-profit. Between cycles,
+//! positions are also watched for nearing liquidation through
+//! [`margin_monitor`].
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::future::Future;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio::sync::{mpsc, oneshot, watch};
+use tokio::task::JoinHandle;
+use tracing::Instrument;
+
+use crate::accuracy::{self, AccuracyParams};
+use crate::ai::{self, AiError, AiProvider};
+use crate::ai_cache;
+use crate::api_client::ApiClient;
+use crate::aster::{AsterClient, AsterError};
+use crate::audit_log;
+use crate::config::Config;
+use crate::cost_model::{self, CostParams};
+use crate::data::{self, MarketError};
+use crate::database::{
+    AIModelConfig, Database, ExchangeConfig, PnlSnapshot, ReconciliationEvent, Trade, TraderRecord,
+};
+use crate::decision::{
+    self, AccountInfo, Action, Context, Decision, DecisionError, Limits, ParsedResponse,
+    PositionInfo,
+};
+use crate::executor::{self, Execution, Executor, TradeExecutor};
+use crate::hyperliquid::HyperliquidAccount;
+use crate::i18n::{self, Locale, Msg};
+use crate::logger::{DecisionLogger, DecisionRecord, RecordCipher, RotationParams};
+use crate::margin_monitor::{self, MarginLevel, MonitorParams, Thresholds};
+use crate::money::{self, Decimal};
+use crate::notify::{self, Event};
+use crate::profiler::CycleTimer;
+use crate::quota::{self, QuotaError};
+use crate::recovery::{self, ReconcileParams, RecoveredTrader};
+use crate::retry_queue::RetryPolicy;
+use crate::scheduler::Schedule;
+use crate::secrets::{self, SecretError};
+use crate::sizing::{self, PositionSizing};
+use crate::strategy::{self, Strategy, StrategyMode};
+use crate::types::{AccountBalance, Data, IncomeRecord, MarketDataSource, TimeframeData};
+#[cfg(feature = "wasm-filters")]
+use crate::wasm_filter::WasmFilter;
+use crate::watch_only::{self, Comparison, ObservedTrade, ReadOnly};
+use crate::{
+    approval, calendar, cooldown, currency, error_sink, hot_reload, loss_limits, maintenance,
+    margin_governor, performance, prompt, prompt_template, risk_override, stream, symbol_watch,
+    timezone, tournament, universe,
+};
+
+/// How often running tasks are compared with the `is_running` flags.
+const SYNC_INTERVAL: Duration = Duration::from_secs(60);
+// Decision records looked at to estimate how long positions are held.
+const HOLD_LOOKBACK_CYCLES: usize = 200;
+// Decision records whose closed trades make up the recent performance in the prompt.
+const PERFORMANCE_LOOKBACK_CYCLES: usize = 100;
+// Decision records searched for the decision that opened a position the
+// exchange has closed, to tell whether its stop-loss closed it.
+const STOP_LOSS_LOOKBACK_CYCLES: usize = 200;
+// Error recorded for a cycle cut short by shutdown.
+const INTERRUPTED: &str = "cycle interrupted by shutdown";
+// Currency the runner's venues margin positions in.
+const MARGIN_CURRENCY: &str = "USDT";
+
+#[derive(Error, Debug)]
+pub enum RunnerError {
+    #[error("Trader configuration: {0}")]
+    Config(#[from] anyhow::Error),
+    #[error("Exchange '{0}' is not supported by the runner")]
+    UnsupportedExchange(String),
+    #[error("Exchange '{0}' can only run watch-only traders")]
+    WatchOnlyExchange(String),
+    #[error(transparent)]
+    Ai(#[from] AiError),
+    #[error(transparent)]
+    Aster(#[from] AsterError),
+    #[error(transparent)]
+    Quota(#[from] QuotaError),
+    #[error(transparent)]
+    Secret(#[from] SecretError),
+    #[error("Runner has shut down")]
+    Closed,
+}
+
+/// Wallet balance in the margin currency (USDT), with other collateral
+/// converted at live prices.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Balance {
+    pub wallet: f64,
+    pub available: f64,
+}
+
+/// A venue a trader can run against: an order executor that can also report
+/// the account balance and, unless overridden, reads market data for its
+/// [`market_source`](Self::market_source) from the shared market data cache
+/// and warms symbols up through [`data::warm_up_from`].
+pub trait Venue: TradeExecutor + 'static {
+    fn get_balance(&mut self) -> impl Future<Output = executor::Result<Balance>> + Send;
+
+    /// Transfers in and out of the account since `start_time` (ms), from the
+    /// venue's income history. Venues without one report none.
+    fn get_transfers(
+        &mut self,
+        start_time: Option<i64>,
+    ) -> impl Future<Output = executor::Result<Vec<IncomeRecord>>> + Send {
+        let _ = start_time;
+        async { Ok(Vec::new()) }
+    }
+
+    /// Where the trader's market data comes from. Venues with their own
+    /// market data API return themselves, so prices match the mark their
+    /// positions are margined against.
+    fn market_source(&self) -> MarketDataSource {
+        MarketDataSource::Binance
+    }
+
+    fn get_market_data(
+        &mut self,
+        symbol: &str,
+    ) -> impl Future<Output = Result<Data, MarketError>> + Send {
+        let source = self.market_source();
+        let symbol = symbol.to_string();
+        async move { data::get_from(source, &symbol).await }
+    }
+
+    /// Market data for several symbols at once, keyed by symbol. Venues that
+    /// override [`get_market_data`](Self::get_market_data) must override this
+    /// too.
+    fn get_market_data_many(
+        &mut self,
+        symbols: &[String],
+    ) -> impl Future<Output = HashMap<String, Result<Data, MarketError>>> + Send {
+        let source = self.market_source();
+        let symbols = symbols.to_vec();
+        async move {
+            let symbols: Vec<&str> = symbols.iter().map(String::as_str).collect();
+            data::get_many_from(source, &symbols).await
+        }
+    }
+
+    /// Market data for a symbol the trader has not decided on yet, after
+    /// loading enough history to seed its indicators.
+    fn warm_up(&mut self, symbol: &str) -> impl Future<Output = Result<Data, MarketError>> + Send {
+        let source = self.market_source();
+        let symbol = symbol.to_string();
+        async move { data::warm_up_from(source, &symbol).await }
+    }
+
+    /// The trader's extra timeframes for a symbol; any that fail to load are
+    /// left out.
+    fn get_timeframes(
+        &mut self,
+        symbol: &str,
+        intervals: &[String],
+    ) -> impl Future<Output = BTreeMap<String, TimeframeData>> + Send {
+        let source = self.market_source();
+        let symbol = symbol.to_string();
+        let intervals = intervals.to_vec();
+        async move { data::get_timeframes_from(source, &symbol, &intervals).await }
+    }
+}
+
+async fn collateral_balance(balances: &[AccountBalance]) -> Balance {
+    let valuation = currency::value_balances(balances, MARGIN_CURRENCY).await;
+    Balance {
+        wallet: valuation.wallet,
+        available: valuation.available,
+    }
+}
+
+impl Venue for ApiClient {
+    async fn get_balance(&mut self) -> executor::Result<Balance> {
+        let balances = self
+            .get_balances()
+            .await
+            .map_err(|e| executor::ExecutorError::Exchange(e.to_string()))?;
+        Ok(collateral_balance(&balances).await)
+    }
+
+    async fn get_transfers(
+        &mut self,
+        start_time: Option<i64>,
+    ) -> executor::Result<Vec<IncomeRecord>> {
+        self.get_income(Some(performance::INCOME_TRANSFER), start_time)
+            .await
+            .map_err(|e| executor::ExecutorError::Exchange(e.to_string()))
+    }
+}
+
+impl Venue for AsterClient {
+    async fn get_balance(&mut self) -> executor::Result<Balance> {
+        let balances = self
+            .get_balances()
+            .await
+            .map_err(|e| executor::ExecutorError::Exchange(e.to_string()))?;
+        Ok(collateral_balance(&balances).await)
+    }
+
+    fn market_source(&self) -> MarketDataSource {
+        MarketDataSource::Aster
+    }
+}
+
+impl Venue for HyperliquidAccount {
+    async fn get_balance(&mut self) -> executor::Result<Balance> {
+        let balance = self
+            .balance()
+            .await
+            .map_err(|e| executor::ExecutorError::Exchange(e.to_string()))?;
+        // USDC, counted one to one with the USDT the other venues margin in.
+        Ok(Balance {
+            wallet: balance.account_value,
+            available: balance.withdrawable,
+        })
+    }
+
+    fn market_source(&self) -> MarketDataSource {
+        MarketDataSource::Hyperliquid
+    }
+}
+
+impl<E: Venue> Venue for ReadOnly<E> {
+    async fn get_balance(&mut self) -> executor::Result<Balance> {
+        self.0.get_balance().await
+    }
+
+    async fn get_transfers(
+        &mut self,
+        start_time: Option<i64>,
+    ) -> executor::Result<Vec<IncomeRecord>> {
+        self.0.get_transfers(start_time).await
+    }
+
+    fn market_source(&self) -> MarketDataSource {
+        self.0.market_source()
+    }
+
+    async fn get_market_data(&mut self, symbol: &str) -> Result<Data, MarketError> {
+        self.0.get_market_data(symbol).await
+    }
+
+    async fn get_market_data_many(
+        &mut self,
+        symbols: &[String],
+    ) -> HashMap<String, Result<Data, MarketError>> {
+        self.0.get_market_data_many(symbols).await
+    }
+
+    async fn warm_up(&mut self, symbol: &str) -> Result<Data, MarketError> {
+        self.0.warm_up(symbol).await
+    }
+
+    async fn get_timeframes(
+        &mut self,
+        symbol: &str,
+        intervals: &[String],
+    ) -> BTreeMap<String, TimeframeData> {
+        self.0.get_timeframes(symbol, intervals).await
+    }
+}
+
+/// Aligns trader cycles to candle closes. Without it a trader ticks every
+/// `scan_interval_minutes` from whenever it started, so the AI often sees a
+/// half-formed candle.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[serde(default)]
+pub struct CycleAlignment {
+    pub enabled: bool,
+    /// Wait after each close so the exchange has published the closed candle.
+    #[serde(with = "humantime_serde")]
+    pub delay: Duration,
+}
+
+impl Default for CycleAlignment {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            delay: Duration::from_secs(5),
+        }
+    }
+}
+
+impl CycleAlignment {
+    /// When a trader cycling every `interval` runs.
+    pub fn schedule(&self, interval: Duration) -> Schedule {
+        if self.enabled {
+            Schedule::Aligned {
+                interval,
+                delay: self.delay,
+            }
+        } else {
+            Schedule::Every(interval)
+        }
+    }
+}
+
+/// Settings shared by every trader the runner starts.
+#[derive(Debug, Clone)]
+pub struct RunnerConfig {
+    /// Each trader logs to a subdirectory named after its id.
+    pub log_dir: String,
+    pub cipher: Option<RecordCipher>,
+    pub log_rotation: RotationParams,
+    pub order_retry: RetryPolicy,
+    pub cost_params: CostParams,
+    pub accuracy: AccuracyParams,
+    pub alignment: CycleAlignment,
+    pub reconciliation: ReconcileParams,
+    pub margin_monitor: MonitorParams,
+}
+
+impl Default for RunnerConfig {
+    fn default() -> Self {
+        Self {
+            log_dir: "decision_logs".to_string(),
+            cipher: None,
+            log_rotation: RotationParams::default(),
+            order_retry: RetryPolicy::default(),
+            cost_params: CostParams::default(),
+            accuracy: AccuracyParams::default(),
+            alignment: CycleAlignment::default(),
+            reconciliation: ReconcileParams::default(),
+            margin_monitor: MonitorParams::default(),
+        }
+    }
+}
+
+impl RunnerConfig {
+    pub fn from_config(config: &Config) -> Result<Self, RunnerError> {
+        Ok(Self {
+            cipher: config
+                .decision_log_cipher()
+                .map_err(|e| RunnerError::Config(e.into()))?,
+            log_rotation: config.decision_log_rotation,
+            order_retry: config.order_retry,
+            cost_params: config.cost_model,
+            accuracy: config.decision_accuracy,
+            alignment: config.cycle_alignment,
+            reconciliation: config.position_reconciliation,
+            margin_monitor: config.margin_monitor,
+            ..Default::default()
+        })
+    }
+
+    /// The decision logger for a trader, resuming its cycle numbering.
+    pub fn logger(&self, trader: &TraderRecord) -> DecisionLogger {
+        let dir = Path::new(&self.log_dir).join(&trader.id);
+        let key = self.cipher.as_ref().map(|c| c.user_key(&trader.user_id));
+        DecisionLogger::resume_with_key(&dir.to_string_lossy(), key)
+            .with_rotation(self.log_rotation)
+    }
+}
+
+/// What one cycle did: the record it wrote, and the decisions it worked
+/// through on the way.
+#[derive(Debug)]
+pub struct CycleReport {
+    pub record: DecisionRecord,
+    /// Decisions the AI proposed that passed validation.
+    pub proposed: Vec<Decision>,
+    /// AI decisions dropped by parsing and validation.
+    pub rejected: Vec<DecisionError>,
+    /// Decisions left after the risk filters.
+    pub approved: Vec<Decision>,
+    /// Orders sent this cycle, retries included.
+    pub executions: Vec<Execution>,
+    /// Watch-only: trades the account owner made since the previous cycle.
+    pub observed: Vec<ObservedTrade>,
+    /// Watch-only: how the previous cycle's suggestions compare with `observed`.
+    pub comparison: Option<Comparison>,
+}
+
+// A report's fields gathered while the cycle's record is still being written.
+#[derive(Default)]
+struct CycleOutcome {
+    proposed: Vec<Decision>,
+    rejected: Vec<DecisionError>,
+    approved: Vec<Decision>,
+    executions: Vec<Execution>,
+    observed: Vec<ObservedTrade>,
+    comparison: Option<Comparison>,
+}
+
+impl CycleOutcome {
+    fn into_report(self, record: DecisionRecord) -> CycleReport {
+        CycleReport {
+            record,
+            proposed: self.proposed,
+            rejected: self.rejected,
+            approved: self.approved,
+            executions: self.executions,
+            observed: self.observed,
+            comparison: self.comparison,
+        }
+    }
+}
+
+/// One trader's decision cycle against a venue.
+pub struct TraderCycle<V> {
+    db: Database,
+    trader: TraderRecord,
+    executor: Executor<V>,
+    ai: Box<dyn AiProvider>,
+    logger: DecisionLogger,
+    cost_params: CostParams,
+    accuracy: AccuracyParams,
+    started_at: DateTime<Utc>,
+    call_count: i32,
+    // 已同步的资金划转中最新的时间（毫秒），下次从这里继续读取
+    transfers_synced_to: Option<i64>,
+    // Watch-only state carried to the next cycle.
+    last_positions: Option<Vec<PositionInfo>>,
+    last_suggestions: Vec<Decision>,
+    // Symbols already warmed up.
+    warmed_up: HashSet<String>,
+    // Whether the user was told the market data is degraded.
+    degraded: bool,
+    // Flips to true when the runner shuts down.
+    interrupt: Option<watch::Receiver<bool>>,
+    // The trader's decision filter, compiled, with the module it came from.
+    #[cfg(feature = "wasm-filters")]
+    decision_filter: Option<(Vec<u8>, WasmFilter)>,
+}
+
+impl<V: Venue> TraderCycle<V> {
+    pub fn new(
+        db: Database,
+        trader: TraderRecord,
+        venue: V,
+        ai: Box<dyn AiProvider>,
+        logger: DecisionLogger,
+        config: &RunnerConfig,
+    ) -> Self {
+        let executor = Executor::new(venue, config.order_retry).for_trader(&trader.id);
+        Self {
+            db,
+            trader,
+            executor,
+            ai,
+            logger,
+            cost_params: config.cost_params,
+            accuracy: config.accuracy,
+            started_at: Utc::now(),
+            transfers_synced_to: None,
+            call_count: 0,
+            last_positions: None,
+            last_suggestions: Vec::new(),
+            warmed_up: HashSet::new(),
+            degraded: false,
+            interrupt: None,
+            #[cfg(feature = "wasm-filters")]
+            decision_filter: None,
+        }
+    }
+
+    pub fn trader(&self) -> &TraderRecord {
+        &self.trader
+    }
+
+    // Tells the user once when market data turns degraded, and again only
+    // after it has recovered.
+    fn report_degraded(&mut self, ctx: &Context, locale: Locale) {
+        let mut degraded: Vec<&str> = ctx
+            .market_data
+            .iter()
+            .filter(|(_, d)| d.degraded)
+            .map(|(symbol, _)| symbol.as_str())
+            .collect();
+        let was_degraded = std::mem::replace(&mut self.degraded, !degraded.is_empty());
+        if degraded.is_empty() || was_degraded {
+            return;
+        }
+        degraded.sort();
+        notify::notify(
+            Event::alert(
+                "market_data_degraded",
+                i18n::t(locale, Msg::MarketDataDegraded),
+            )
+            .user(&self.trader.user_id)
+            .trader(&self.trader.id)
+            .detail("symbols", &degraded),
+        );
+    }
+
+    // Lets the test harness change settings between cycles.
+    #[cfg(any(test, feature = "testkit"))]
+    pub(crate) fn trader_mut(&mut self) -> &mut TraderRecord {
+        &mut self.trader
+    }
+
+    fn interrupted(&self) -> bool {
+        self.interrupt.as_ref().is_some_and(|rx| *rx.borrow())
+    }
+
+    /// Picks up a trader that was running when the process died: re-arms the
+    /// stop-loss / take-profit of live positions from the decisions that
+    /// opened them and sends a `trader_recovered` alert. Watch-only traders
+    /// place no orders, so nothing is re-armed for them.
+    pub async fn recover(&mut self) -> anyhow::Result<RecoveredTrader> {
+        let venue = self.executor.exchange_mut();
+        let positions = venue.get_positions().await?;
+        let recovered = recovery::recover_from_log(&self.trader.id, &self.logger, positions);
+
+        let mut rearmed = 0;
+        if !self.trader.watch_only {
+            for levels in &recovered.protective {
+                let Some(position) = recovered
+                    .positions
+                    .iter()
+                    .find(|p| p.symbol == levels.symbol && p.side == levels.side)
+                else {
+                    continue;
+                };
+                match venue
+                    .set_protective_orders(
+                        &levels.symbol,
+                        &levels.side,
+                        money::from_f64(position.quantity.abs()),
+                        levels.stop_loss.unwrap_or_default(),
+                        levels.take_profit.unwrap_or_default(),
+                    )
+                    .await
+                {
+                    Ok(()) => rearmed += 1,
+                    Err(e) => tracing::warn!(
+                        "⚠️ 交易员 {} 恢复 {} 止损止盈失败: {}",
+                        self.trader.id,
+                        levels.symbol,
+                        e
+                    ),
+                }
+            }
+        }
+
+        let message = i18n::render(
+            user_locale(&self.db, &self.trader.user_id).await,
+            Msg::TraderRecovered,
+            &[
+                ("name", &self.trader.name),
+                ("cycle", &recovered.cycle_number),
+                ("positions", &recovered.positions.len()),
+                ("rearmed", &rearmed),
+                ("unprotected", &recovered.unprotected.len()),
+            ],
+        );
+        notify::notify(
+            Event::alert("trader_recovered", message)
+                .user(&self.trader.user_id)
+                .trader(&self.trader.id)
+                .detail("cycle", recovered.cycle_number)
+                .detail("positions", recovered.positions.len())
+                .detail("rearmed", rearmed)
+                .detail("unprotected", &recovered.unprotected)
+                .detail("closed_while_down", &recovered.closed_while_down),
+        );
+        Ok(recovered)
+    }
+
+    /// Brings the trader's recorded fills in line with the positions the
+    /// exchange holds, storing a correcting trade and a reconciliation event
+    /// for each position that was off, and returns the events. A position the
+    /// exchange closed through its stop-loss starts the symbol's [`cooldown`].
+    pub async fn reconcile(&mut self) -> anyhow::Result<Vec<ReconciliationEvent>> {
+        if self.trader.watch_only {
+            return Ok(Vec::new());
+        }
+        let trades = self
+            .db
+            .get_trades(&self.trader.user_id, &self.trader.id, None)
+            .await?;
+        let venue = self.executor.exchange_mut();
+        let live = venue.get_positions().await?;
+        let mut prices = HashMap::new();
+        for (symbol, _) in recovery::ledger_positions(&trades).into_keys() {
+            if let Ok(data) = venue.get_market_data(&symbol).await {
+                prices.insert(symbol, data.current_price);
+            }
+        }
+        let discrepancies =
+            recovery::reconcile_ledger(&self.trader, &trades, &live, |s| prices.get(s).copied());
+        // Positions gone from the exchange may have been stopped out.
+        let opened_by = if discrepancies.iter().any(|d| d.held.is_zero()) {
+            self.logger.last_open_decisions(STOP_LOSS_LOOKBACK_CYCLES)
+        } else {
+            HashMap::new()
+        };
+        let locale = if discrepancies.is_empty() {
+            Locale::default()
+        } else {
+            user_locale(&self.db, &self.trader.user_id).await
+        };
+        let mut events = Vec::with_capacity(discrepancies.len());
+        for found in discrepancies {
+            let trade = &found.correction;
+            let trade_id = self.db.record_trade(trade).await?;
+            if found.held.is_zero()
+                && let Some(open) = opened_by.get(&trade.symbol)
+                && cooldown::is_stop_loss_exit(
+                    &found.side,
+                    open.stop_loss,
+                    money::to_f64(trade.price),
+                )
+            {
+                cooldown::record_stop_loss(&self.trader.id, &trade.symbol, trade.executed_at);
+            }
+            let mut event = ReconciliationEvent {
+                user_id: self.trader.user_id.clone(),
+                trader_id: self.trader.id.clone(),
+                symbol: trade.symbol.clone(),
+                side: found.side.clone(),
+                recorded_quantity: found.recorded,
+                exchange_quantity: found.held,
+                trade_id,
+                detected_at: trade.executed_at,
+                ..Default::default()
+            };
+            event.id = self.db.record_reconciliation_event(&event).await?;
+            let message = i18n::render(
+                locale,
+                Msg::PositionReconciled,
+                &[
+                    ("symbol", &event.symbol),
+                    ("side", &event.side),
+                    ("recorded", &event.recorded_quantity.normalize()),
+                    ("held", &event.exchange_quantity.normalize()),
+                    ("action", &trade.action),
+                    ("quantity", &trade.quantity.normalize()),
+                ],
+            );
+            tracing::warn!(
+                "⚖️ 交易员 {} 账本与交易所持仓不一致，已补记 {} {} {}",
+                self.trader.id,
+                trade.action,
+                trade.symbol,
+                trade.quantity
+            );
+            notify::notify(
+                Event::alert("position_reconciled", message)
+                    .user(&event.user_id)
+                    .trader(&event.trader_id)
+                    .symbol(&event.symbol)
+                    .detail("action", &trade.action),
+            );
+            events.push(event);
+        }
+        Ok(events)
+    }
+
+    /// Checks the trader's positions against its margin alert thresholds,
+    /// alerting on (and, if set up, partially closing) those that just
+    /// crossed one, and returns them.
+    pub async fn monitor_margin(&mut self) -> anyhow::Result<Vec<MarginLevel>> {
+        let thresholds = Thresholds::from_trader(&self.trader);
+        if !thresholds.is_enabled() {
+            return Ok(Vec::new());
+        }
+        let positions = self.executor.exchange_mut().get_positions().await?;
+        let levels: Vec<_> = positions
+            .iter()
+            .filter_map(|p| margin_monitor::measure(p, stream::mark_price(&p.symbol)))
+            .collect();
+        let crossed = margin_monitor::crossed(&self.trader.id, &levels, &thresholds);
+        let locale = if crossed.is_empty() {
+            Locale::default()
+        } else {
+            user_locale(&self.db, &self.trader.user_id).await
+        };
+        for level in &crossed {
+            tracing::warn!(
+                "🚨 交易员 {} {} {} 接近强平：距强平 {:.2}%，保证金率 {:.1}%",
+                self.trader.id,
+                level.symbol,
+                level.side,
+                level.distance_pct,
+                level.margin_ratio_pct
+            );
+            let message = i18n::render(
+                locale,
+                Msg::MarginAlert,
+                &[
+                    ("symbol", &level.symbol),
+                    ("side", &level.side),
+                    ("distance", &format!("{:.2}", level.distance_pct)),
+                    ("mark", &level.mark_price),
+                    ("liquidation", &level.liquidation_price),
+                    ("ratio", &format!("{:.1}", level.margin_ratio_pct)),
+                ],
+            );
+            notify::notify(
+                Event::alert("margin_alert", message)
+                    .user(&self.trader.user_id)
+                    .trader(&self.trader.id)
+                    .symbol(&level.symbol)
+                    .detail("side", &level.side)
+                    .detail("mark_price", level.mark_price)
+                    .detail("liquidation_price", level.liquidation_price)
+                    .detail("distance_pct", level.distance_pct)
+                    .detail("margin_ratio_pct", level.margin_ratio_pct),
+            );
+            if thresholds.auto_deleverage_pct > 0.0 && !self.trader.watch_only {
+                self.deleverage(level, thresholds.auto_deleverage_pct).await;
+            }
+        }
+        Ok(crossed)
+    }
+
+    // Closes `pct` percent of the position at `level` and records the fill.
+    async fn deleverage(&mut self, level: &MarginLevel, pct: f64) {
+        let action = if level.side == "short" {
+            Action::CloseShort
+        } else {
+            Action::CloseLong
+        };
+        let quantity = money::from_f64(level.quantity * pct.min(100.0) / 100.0);
+        let result = self
+            .executor
+            .exchange_mut()
+            .close(&level.symbol, &level.side, quantity)
+            .await;
+        if let Err(e) = &result {
+            tracing::warn!("⚠️ {} 自动减仓失败: {}", level.symbol, e);
+        }
+        let execution = Execution {
+            decision: Decision {
+                reasoning: format!("auto-deleverage {}%: {}", pct, level),
+                ..Decision::new(&level.symbol, action)
+            },
+            result: result.map(Some),
+            queued_for_retry: false,
+            group_id: Some(margin_monitor::DELEVERAGE_GROUP.to_string()),
+        };
+        record_fills(
+            &self.db,
+            &self.trader,
+            user_locale(&self.db, &self.trader.user_id).await,
+            self.cost_params.taker_fee_pct,
+            &HashMap::new(),
+            &[execution],
+        )
+        .await;
+    }
+
+    // Traders without symbols of their own trade the default list.
+    fn symbols(&self) -> Vec<String> {
+        if universe::uses_defaults(&self.trader) {
+            return universe::default_coins();
+        }
+        self.trader
+            .trading_symbols
+            .split(',')
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(data::normalize)
+            .collect()
+    }
+
+    // Records deposits and withdrawals the venue reports since the last sync,
+    // so returns are measured net of them.
+    async fn sync_transfers(&mut self) {
+        let since = self.transfers_synced_to;
+        let income = match self.executor.exchange_mut().get_transfers(since).await {
+            Ok(income) => income,
+            Err(e) => {
+                tracing::warn!("⚠️ 读取资金划转记录失败: {}", e);
+                return;
+            }
+        };
+        // 起始时间包含在内，已记录的划转按 tran_id 去重
+        if let Some(latest) = income.iter().map(|r| r.time).max() {
+            self.transfers_synced_to = Some(latest);
+        }
+        if let Err(e) = performance::sync_income_transfers(
+            &self.db,
+            &self.trader.user_id,
+            &self.trader.id,
+            &income,
+        )
+        .await
+        {
+            tracing::warn!("⚠️ 保存资金划转记录失败: {:#}", e);
+        }
+    }
+
+    // Reads the account and market data; symbols without data are left out
+    // and reported in `warnings`.
+    async fn context(&mut self, warnings: &mut Vec<String>) -> anyhow::Result<Context> {
+        let symbols = self.symbols();
+        let venue = self.executor.exchange_mut();
+        let balance = venue.get_balance().await?;
+        let positions = venue.get_positions().await?;
+
+        let timeframes = data::parse_timeframes(&self.trader.timeframes).unwrap_or_else(|e| {
+            warnings.push(format!("⚠️ 额外K线周期配置无效: {}", e));
+            Vec::new()
+        });
+
+        // Symbols dropped from the list are warmed up again if they return.
+        self.warmed_up.retain(|s| symbols.contains(s));
+        let warm: Vec<String> = symbols
+            .iter()
+            .filter(|s| self.warmed_up.contains(*s))
+            .cloned()
+            .collect();
+        let mut fetched = venue.get_market_data_many(&warm).await;
+        let mut market_data = HashMap::new();
+        for symbol in &symbols {
+            let data = if let Some(data) = fetched.remove(symbol) {
+                data
+            } else {
+                let data = venue.warm_up(symbol).await;
+                if data.is_ok() {
+                    self.warmed_up.insert(symbol.clone());
+                }
+                data
+            };
+            match data {
+                Ok(mut data) => {
+                    if data.warming_up {
+                        warnings.push(format!("⏳ {} 指标预热中，暂不开仓", symbol));
+                    }
+                    if !timeframes.is_empty() {
+                        data.timeframes = venue.get_timeframes(symbol, &timeframes).await;
+                    }
+                    market_data.insert(symbol.clone(), data);
+                }
+                Err(e) => warnings.push(format!("⚠️ {} 市场数据获取失败: {}", symbol, e)),
+            }
+        }
+
+        let unrealized: f64 = positions.iter().map(|p| p.unrealized_pnl).sum();
+        let margin_used: f64 = positions.iter().map(|p| p.margin_used).sum();
+        let total_equity = balance.wallet + unrealized;
+        let total_pnl = total_equity - self.trader.initial_balance;
+        let pct = |v: f64, of: f64| if of > 0.0 { v / of * 100.0 } else { 0.0 };
+        let now = Utc::now();
+        Ok(Context {
+            current_time: now,
+            runtime_minutes: (now - self.started_at).num_minutes(),
+            call_count: self.call_count,
+            account: AccountInfo {
+                total_equity,
+                available_balance: balance.available,
+                total_pnl,
+                total_pnl_pct: pct(total_pnl, self.trader.initial_balance),
+                margin_used,
+                margin_used_pct: pct(margin_used, total_equity),
+                position_count: positions.len() as i32,
+            },
+            candidate_coins: symbols
+                .into_iter()
+                .filter(|s| market_data.contains_key(s))
+                .collect(),
+            positions,
+            market_data,
+            performance: self
+                .logger
+                .analyze_performance(PERFORMANCE_LOOKBACK_CYCLES)
+                .ok(),
+            btc_eth_leverage: self.trader.btc_eth_leverage,
+            altcoin_leverage: self.trader.altcoin_leverage,
+        })
+    }
+
+    // Runs the trader's uploaded decision filter, if it has one. When the
+    // filter cannot run, only decisions that reduce exposure go through.
+    async fn filter_decisions(
+        &mut self,
+        decisions: Vec<Decision>,
+        ctx: &Context,
+        record: &mut DecisionRecord,
+    ) -> Vec<Decision> {
+        let wasm = match self
+            .db
+            .get_decision_filter(&self.trader.user_id, &self.trader.id)
+            .await
+        {
+            Ok(Some(wasm)) => wasm,
+            Ok(None) => return decisions,
+            Err(e) => {
+                record.log(format!("⚠️ 读取决策过滤器失败，拒绝所有开仓: {:#}", e));
+                return without_entries(decisions);
+            }
+        };
+        #[cfg(feature = "wasm-filters")]
+        {
+            if self
+                .decision_filter
+                .as_ref()
+                .is_none_or(|(m, _)| *m != wasm)
+            {
+                match WasmFilter::from_bytes(&wasm) {
+                    Ok(filter) => self.decision_filter = Some((wasm, filter)),
+                    Err(e) => {
+                        self.decision_filter = None;
+                        record.log(format!("⚠️ 决策过滤器无效，拒绝所有开仓: {}", e));
+                        return without_entries(decisions);
+                    }
+                }
+            }
+            let Some((_, filter)) = &self.decision_filter else {
+                return without_entries(decisions);
+            };
+            let before = decisions.len();
+            let filtered = filter.apply_or_fail_closed(decisions, ctx);
+            if filtered.len() < before {
+                record.log(format!(
+                    "🧩 决策过滤器拦截了 {} 个决策",
+                    before - filtered.len()
+                ));
+            }
+            filtered
+        }
+        #[cfg(not(feature = "wasm-filters"))]
+        {
+            let _ = (wasm, ctx);
+            record.log("⚠️ 此版本未启用决策过滤器 (wasm-filters)，拒绝所有开仓".to_string());
+            without_entries(decisions)
+        }
+    }
+
+    /// Reconciles the ledger, then runs a cycle: what the trader's task does
+    /// each time its schedule fires. A failed reconciliation does not stop
+    /// the cycle.
+    pub async fn tick(&mut self) -> anyhow::Result<CycleReport> {
+        if let Err(e) = self.reconcile().await {
+            tracing::warn!("⚠️ 交易员 {} 持仓对账失败: {:#}", self.trader.id, e);
+        }
+        self.run_cycle_report().await
+    }
+
+    /// Runs one cycle and returns the record it wrote. Failures inside the
+    /// cycle (AI, parsing, orders) end up in the record; only a failure to
+    /// read the account or to write the record is returned as an error.
+    pub async fn run_cycle(&mut self) -> anyhow::Result<DecisionRecord> {
+        Ok(self.run_cycle_report().await?.record)
+    }
+
+    /// [`run_cycle`](Self::run_cycle), also returning what was decided and
+    /// executed along the way. Everything logged during the cycle carries
+    /// the `trader_id` and `cycle` of its `trader_cycle` span.
+    pub async fn run_cycle_report(&mut self) -> anyhow::Result<CycleReport> {
+        let cycle = self.logger.cycle_number() + 1;
+        let span = tracing::info_span!("trader_cycle", trader_id = %self.trader.id, cycle);
+        let mut timer = CycleTimer::new(&self.trader.id, cycle);
+        let report = self.cycle_report(&mut timer).instrument(span).await;
+        timer.finish();
+        report
+    }
+
+    async fn cycle_report(&mut self, timer: &mut CycleTimer) -> anyhow::Result<CycleReport> {
+        self.call_count += 1;
+        let mut warnings = Vec::new();
+        let ctx = timer.time("context", self.context(&mut warnings)).await?;
+        self.sync_transfers().await;
+        let user_id = self.trader.user_id.clone();
+        let trader_id = self.trader.id.clone();
+        save_snapshot(&self.db, &self.trader, &ctx).await;
+        // 共用账户时只计入本交易员账本中的持仓，避免同一持仓被多个交易员重复上报
+        match self.db.get_trades(&user_id, &trader_id, None).await {
+            Ok(trades) => margin_governor::report(
+                &user_id,
+                &trader_id,
+                margin_governor::own_margin(&ctx.positions, &recovery::ledger_positions(&trades)),
+                ctx.account.total_equity,
+            ),
+            Err(e) => tracing::warn!("⚠️ 读取成交记录失败，未上报保证金占用: {:#}", e),
+        }
+
+        // Settings edited since the last cycle take effect here.
+        let settings = hot_reload::current();
+        let user = self.db.get_user_by_id(&user_id).await.ok().flatten();
+        let tz = user.as_ref().map_or(chrono_tz::UTC, |u| u.tz());
+        let locale = user.as_ref().map_or(Locale::default(), |u| u.locale());
+        self.report_degraded(&ctx, locale);
+        if let Some(breach) = loss_limits::observe(
+            &trader_id,
+            ctx.account.total_equity,
+            timezone::local_date(tz, ctx.current_time),
+            ctx.current_time,
+            &settings,
+        ) {
+            warnings.push(format!("🛑 触发风控限制（{}），暂停开仓", breach));
+        }
+
+        let windows = timezone::parse_windows(&self.trader.trading_windows).unwrap_or_else(|e| {
+            tracing::warn!("⚠️ 交易时段配置无效，不限制开仓时段: {}", e);
+            Vec::new()
+        });
+
+        let cooldown_minutes = self.trader.stop_loss_cooldown_minutes.max(0) as u32;
+        let mut notes = Vec::new();
+        notes.extend(loss_limits::prompt_annotation(&trader_id));
+        notes.extend(timezone::prompt_annotation(&windows, tz, ctx.current_time));
+        notes.extend(cooldown::prompt_annotation(&trader_id, cooldown_minutes));
+        notes.extend(calendar::prompt_annotation(ctx.current_time));
+        notes.extend(risk_override::prompt_annotation(&user_id));
+        let hold = self
+            .logger
+            .typical_hold(HOLD_LOOKBACK_CYCLES)
+            .and_then(|h| h.to_std().ok())
+            .unwrap_or(self.cost_params.default_hold);
+        let no_averages = HashMap::new();
+        if let Some(costs) =
+            cost_model::prompt_annotation(&ctx.market_data, &no_averages, hold, &self.cost_params)
+        {
+            notes.push(costs);
+        }
+        let model = self.ai.name();
+        match accuracy::stats(&self.db, &user_id, Some(&model), self.accuracy.since()).await {
+            Ok(stats) => notes.extend(accuracy::prompt_annotation(&stats, &self.accuracy)),
+            Err(e) => tracing::warn!("⚠️ 读取方向判断命中率失败: {}", e),
+        }
+        let template = prompt_template::resolve(&self.db, &self.trader).await?;
+        let system_prompt = prompt::system_prompt(&self.trader, &template, &ctx);
+        let user_prompt = prompt::user_prompt(&ctx, &notes);
+
+        if let Err(e) = tournament::capture(
+            &self.db,
+            &user_id,
+            &trader_id,
+            &system_prompt,
+            &user_prompt,
+            &ctx.market_data,
+        )
+        .await
+        {
+            tracing::warn!("⚠️ 保存评测场景失败: {}", e);
+        }
+
+        // 配置了策略的交易员：替换模式不调用AI，增强模式由策略复核AI的决策
+        let strategy = match self.trader.strategy.as_str() {
+            "" => Ok(None),
+            name => strategy::get(name).map(Some),
+        };
+        let asks_ai = match &strategy {
+            Ok(None) => true,
+            Ok(Some(s)) => s.mode() == StrategyMode::Augment,
+            Err(_) => false,
+        };
+        let response = if asks_ai {
+            let interrupt = self.interrupt.clone();
+            let started = Instant::now();
+            let response = tokio::select! {
+                response = self.ai.chat_completion(&system_prompt, &user_prompt) => {
+                    response.map_err(|e| {
+                        error_sink::capture_ai_failure(&trader_id, &e);
+                        e.to_string()
+                    })
+                }
+                _ = shutting_down(interrupt) => Err(INTERRUPTED.to_string()),
+            };
+            timer.add("ai_call", started.elapsed());
+            if let Ok(response) = &response {
+                quota::record_ai_call(
+                    &self.db,
+                    &self.trader,
+                    system_prompt.len() + user_prompt.len(),
+                    response.len(),
+                )
+                .await;
+            }
+            response
+        } else {
+            Ok(String::new())
+        };
+        let limits = Limits::from_context(&ctx);
+        let parsed = if asks_ai {
+            response
+                .as_deref()
+                .map_err(|e| e.clone())
+                .and_then(|r| decision::parse_response(r, &limits).map_err(|e| e.to_string()))
+        } else {
+            Ok(ParsedResponse::default())
+        };
+        let parsed = match strategy {
+            Ok(None) => parsed,
+            Ok(Some(strategy)) => {
+                parsed.and_then(|parsed| decide_with(&*strategy, &ctx, &limits, parsed))
+            }
+            Err(e) => Err(e.to_string()),
+        };
+        let decision_json = match &parsed {
+            Ok(parsed) => serde_json::to_string(&parsed.decisions)?,
+            Err(_) => String::new(),
+        };
+        let mut record = DecisionRecord::new(
+            &system_prompt,
+            &user_prompt,
+            response.as_deref().unwrap_or_default(),
+            &decision_json,
+        );
+        record.set_variant(&self.trader.variant);
+        for warning in warnings {
+            record.log(warning);
+        }
+        // Orders already being placed finish, but none start once shutting down.
+        let stopping = self.interrupted();
+        let retried = if stopping {
+            Vec::new()
+        } else {
+            self.executor.retry_due(Some(&mut record)).await
+        };
+        record_fills(
+            &self.db,
+            &self.trader,
+            locale,
+            self.cost_params.taker_fee_pct,
+            &ctx.market_data,
+            &retried,
+        )
+        .await;
+        record.set_account(&ctx.account, &ctx.positions);
+        record.set_candidate_coins(ctx.candidate_coins.clone());
+        let mut report = CycleOutcome {
+            executions: retried,
+            ..CycleOutcome::default()
+        };
+        if self.trader.watch_only {
+            if let Some(before) = self.last_positions.replace(ctx.positions.clone()) {
+                let observed = watch_only::observe_trades(&before, &ctx.positions);
+                let comparison = watch_only::compare(&self.last_suggestions, &observed);
+                watch_only::record_observed(&mut record, &observed, &comparison);
+                report.observed = observed;
+                report.comparison = Some(comparison);
+            }
+            self.last_suggestions.clear();
+        }
+
+        let proposed = match parsed {
+            Ok(parsed) => {
+                record.record_rejected(&parsed.rejected);
+                report.rejected = parsed.rejected;
+                parsed.decisions
+            }
+            Err(e) => {
+                record.set_error(&e);
+                self.logger.log_decision(&mut record)?;
+                return Ok(report.into_report(record));
+            }
+        };
+        report.proposed = proposed.clone();
+
+        // 策略替换AI时，这些方向判断不属于模型
+        if asks_ai
+            && let Err(e) = accuracy::record(
+                &self.db,
+                &user_id,
+                &trader_id,
+                &model,
+                &proposed,
+                &ctx.market_data,
+            )
+            .await
+        {
+            tracing::warn!("⚠️ 保存方向判断失败: {}", e);
+        }
+
+        let max_margin_usage_pct = user.map_or(0.0, |u| u.max_margin_usage_pct);
+        let sizing = self.trader.position_sizing.parse().unwrap_or_else(|e| {
+            record.log(format!("⚠️ 仓位计算方式配置无效，采用AI仓位: {}", e));
+            PositionSizing::Ai
+        });
+        let approved = risk_override::filter(&user_id, proposed);
+        let approved = loss_limits::drop_entries(&trader_id, approved);
+        let approved =
+            timezone::drop_entries_outside_session(&windows, tz, ctx.current_time, approved);
+        let approved = sizing::apply(&sizing, approved, &ctx);
+        let approved = symbol_watch::drop_blocked_entries(approved);
+        let approved = cooldown::drop_cooling_entries(&trader_id, cooldown_minutes, approved);
+        let approved = cost_model::drop_uneconomic_entries(
+            approved,
+            &ctx.market_data,
+            &no_averages,
+            hold,
+            &self.cost_params,
+        );
+        let approved =
+            margin_governor::govern(&user_id, &trader_id, max_margin_usage_pct, approved);
+        let approved = self.filter_decisions(approved, &ctx, &mut record).await;
+        report.approved = approved.clone();
+
+        if self.trader.watch_only {
+            watch_only::record_suggestions(&mut record, &approved);
+            self.last_suggestions = approved;
+        } else if stopping {
+            record.set_error(INTERRUPTED);
+        } else {
+            let approved = approval::hold(&self.db, &self.trader, approved, &mut record).await;
+            let released = approval::release(&self.db, &self.trader, &mut record).await;
+            let started = Instant::now();
+            let executions = self.executor.execute(&approved, &mut record).await;
+            record_fills(
+                &self.db,
+                &self.trader,
+                locale,
+                self.cost_params.taker_fee_pct,
+                &ctx.market_data,
+                &executions,
+            )
+            .await;
+            report.executions.extend(executions);
+            for (proposal, decision) in released {
+                let first = record.execution_count();
+                let executions = self
+                    .executor
+                    .execute(std::slice::from_ref(&decision), &mut record)
+                    .await;
+                record.set_approved_by(first, &proposal.decided_by);
+                record_fills(
+                    &self.db,
+                    &self.trader,
+                    locale,
+                    self.cost_params.taker_fee_pct,
+                    &ctx.market_data,
+                    &executions,
+                )
+                .await;
+                approval::finish(&self.db, &proposal, &executions).await;
+                report.executions.extend(executions);
+            }
+            timer.add("execution", started.elapsed());
+        }
+
+        self.logger.log_decision(&mut record)?;
+        Ok(report.into_report(record))
+    }
+}
+
+// Hands the AI's decisions (none when the strategy replaces it) to the
+// trader's strategy and checks what it returns against the same limits.
+fn decide_with(
+    strategy: &dyn Strategy,
+    ctx: &Context,
+    limits: &Limits,
+    mut parsed: ParsedResponse,
+) -> Result<ParsedResponse, String> {
+    let decisions = strategy
+        .decide(ctx, std::mem::take(&mut parsed.decisions))
+        .map_err(|e| format!("{}: {}", strategy.name(), e))?;
+    for (index, d) in decisions.into_iter().enumerate() {
+        match limits.check(&d) {
+            Ok(()) => parsed.decisions.push(d),
+            Err(reason) => parsed.rejected.push(DecisionError::Invalid {
+                index,
+                symbol: d.symbol,
+                reason,
+            }),
+        }
+    }
+    Ok(parsed)
+}
+
+// Resolves once `interrupt` flips to true; never for cycles run on their own.
+async fn shutting_down(interrupt: Option<watch::Receiver<bool>>) {
+    if let Some(mut rx) = interrupt
+        && rx.wait_for(|stop| *stop).await.is_ok()
+    {
+        return;
+    }
+    std::future::pending().await
+}
+
+// Keeps the decisions that reduce exposure: closes, holds and waits.
+fn without_entries(decisions: Vec<Decision>) -> Vec<Decision> {
+    decisions
+        .into_iter()
+        .filter(|d| !d.action.is_open())
+        .collect()
+}
+
+// Stores the account state the cycle saw as an equity snapshot.
+async fn save_snapshot(db: &Database, trader: &TraderRecord, ctx: &Context) {
+    let snapshot = PnlSnapshot {
+        user_id: trader.user_id.clone(),
+        trader_id: trader.id.clone(),
+        taken_at: ctx.current_time,
+        total_equity: money::from_f64(ctx.account.total_equity),
+        available_balance: money::from_f64(ctx.account.available_balance),
+        unrealized_pnl: ctx
+            .positions
+            .iter()
+            .map(|p| money::from_f64(p.unrealized_pnl))
+            .sum(),
+        margin_used: money::from_f64(ctx.account.margin_used),
+        position_count: ctx.account.position_count,
+        ..Default::default()
+    };
+    if let Err(e) = db.save_pnl_snapshot(&snapshot).await {
+        tracing::warn!("⚠️ 保存权益快照失败: {:#}", e);
+    }
+}
+
+// Language the user reads notifications in.
+async fn user_locale(db: &Database, user_id: &str) -> Locale {
+    match db.get_user_by_id(user_id).await {
+        Ok(Some(user)) => user.locale(),
+        _ => Locale::default(),
+    }
+}
+
+// Writes filled orders to the trades table and tells the user about each
+// fill and rejected order in their language. Fees are estimated from the
+// taker fee, as order responses do not report them. The price the cycle saw
+// is stored as the expected price, to measure slippage against.
+async fn record_fills(
+    db: &Database,
+    trader: &TraderRecord,
+    locale: Locale,
+    taker_fee_pct: f64,
+    market_data: &HashMap<String, Data>,
+    executions: &[Execution],
+) {
+    for execution in executions {
+        let fill = match &execution.result {
+            Ok(Some(fill)) => fill,
+            Err(e) if e.is_rejection() => {
+                let symbol = &execution.decision.symbol;
+                let message = i18n::render(
+                    locale,
+                    Msg::OrderRejected,
+                    &[("symbol", symbol), ("reason", e)],
+                );
+                notify::notify(
+                    Event::alert("order_rejected", message)
+                        .user(&trader.user_id)
+                        .trader(&trader.id)
+                        .symbol(symbol)
+                        .detail("action", execution.decision.action.as_str()),
+                );
+                continue;
+            }
+            _ => continue,
+        };
+        if fill.filled_quantity <= Decimal::ZERO {
+            continue;
+        }
+        let action = execution.decision.action;
+        let buy = matches!(action, Action::OpenLong | Action::CloseShort);
+        let trade = Trade {
+            user_id: trader.user_id.clone(),
+            trader_id: trader.id.clone(),
+            symbol: fill.symbol.clone(),
+            side: if buy { "buy" } else { "sell" }.to_string(),
+            action: action.as_str().to_string(),
+            quantity: fill.filled_quantity,
+            price: fill.price,
+            fee: fill.filled_quantity * fill.price * money::from_f64(taker_fee_pct)
+                / Decimal::ONE_HUNDRED,
+            order_id: fill.order_id,
+            group_id: execution.group_id.clone().unwrap_or_default(),
+            expected_price: market_data
+                .get(&fill.symbol)
+                .map_or(Decimal::ZERO, |d| money::from_f64(d.current_price)),
+            fill_latency_ms: fill.fill_latency_ms,
+            variant: trader.variant.clone(),
+            executed_at: Utc::now(),
+            ..Default::default()
+        };
+        if let Err(e) = db.record_trade(&trade).await {
+            tracing::warn!("⚠️ 保存成交记录失败: {:#}", e);
+        }
+        let message = i18n::render(
+            locale,
+            Msg::TradeExecuted,
+            &[
+                ("name", &trader.name),
+                ("side", &trade.side),
+                ("quantity", &trade.quantity),
+                ("symbol", &trade.symbol),
+                ("price", &trade.price),
+                ("action", &trade.action),
+            ],
+        );
+        notify::notify(Event::trade(&trader.name, &trade).message(message));
+    }
+}
+
+/// Runs `cycle` on `schedule` until told to stop. A stop request that
+/// arrives during a cycle takes effect once the cycle is done. Interval
+/// traders run their first cycle right away, aligned ones at the next close.
+/// The ledger is reconciled on start, before every cycle and every
+/// `reconcile_every` in between; margins are checked every `monitor_every`.
+/// Either check is off if its period is zero.
+#[tracing::instrument(name = "trader", skip_all, fields(trader_id = %cycle.trader().id))]
+async fn run_trader<V: Venue>(
+    mut cycle: TraderCycle<V>,
+    schedule: Schedule,
+    reconcile_every: Duration,
+    monitor_every: Duration,
+    mut stop: oneshot::Receiver<()>,
+) {
+    let trader_id = cycle.trader().id.clone();
+    tracing::info!("▶️ 交易员 {} 已启动，调度: {:?}", trader_id, schedule);
+    reconcile(&mut cycle, &trader_id).await;
+    let mut reconciles = every(reconcile_every);
+    let mut margin_checks = every(monitor_every);
+    let mut next = match schedule {
+        Schedule::Every(_) => Some(Utc::now()),
+        _ => schedule.next_after(Utc::now(), None),
+    };
+
+    while let Some(at) = next {
+        let wait = (at - Utc::now()).to_std().unwrap_or_default();
+        tokio::select! {
+            _ = &mut stop => break,
+            _ = next_tick(&mut reconciles) => {
+                reconcile(&mut cycle, &trader_id).await;
+                continue;
+            }
+            _ = next_tick(&mut margin_checks) => {
+                if let Err(e) = cycle.monitor_margin().await {
+                    tracing::warn!("⚠️ 交易员 {} 保证金检查失败: {:#}", trader_id, e);
+                }
+                continue;
+            }
+            _ = tokio::time::sleep(wait) => {
+                if let Some(m) = maintenance::current() {
+                    tracing::info!("🛠️ 维护模式中，跳过交易员 {} 本周期: {}", trader_id, m.reason);
+                } else {
+                    run_once(&mut cycle, &trader_id).await;
+                }
+            }
+        }
+        // A cycle that overruns delays the next one instead of bunching them up.
+        next = schedule.next_after(Utc::now(), Some(at));
+    }
+    tracing::info!("⏹️ 交易员 {} 已停止", trader_id);
+}
+
+// Ticks every `period` from one period on; `None` for a zero period.
+fn every(period: Duration) -> Option<tokio::time::Interval> {
+    (!period.is_zero()).then(|| {
+        let start = tokio::time::Instant::now() + period;
+        let mut ticks = tokio::time::interval_at(start, period);
+        ticks.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        ticks
+    })
+}
+
+// Resolves at the next tick of `ticks`; never without one.
+async fn next_tick(ticks: &mut Option<tokio::time::Interval>) {
+    match ticks {
+        Some(ticks) => {
+            ticks.tick().await;
+        }
+        None => std::future::pending().await,
+    }
+}
+
+async fn reconcile<V: Venue>(cycle: &mut TraderCycle<V>, trader_id: &str) {
+    if let Err(e) = cycle.reconcile().await {
+        tracing::warn!("⚠️ 交易员 {} 持仓对账失败: {:#}", trader_id, e);
+    }
+}
+
+async fn run_once<V: Venue>(cycle: &mut TraderCycle<V>, trader_id: &str) {
+    match cycle.tick().await.map(|report| report.record) {
+        Ok(record) if record.is_success() => {
+            tracing::info!(
+                "✅ 交易员 {} 完成第 {} 个周期",
+                trader_id,
+                record.cycle_number()
+            );
+        }
+        Ok(record) => tracing::warn!(
+            "⚠️ 交易员 {} 第 {} 个周期出错: {}",
+            trader_id,
+            record.cycle_number(),
+            record.error_message()
+        ),
+        Err(e) => tracing::warn!("⚠️ 交易员 {} 周期失败: {:#}", trader_id, e),
+    }
+}
+
+enum Control {
+    Start {
+        user_id: String,
+        trader_id: String,
+        reply: oneshot::Sender<Result<(), RunnerError>>,
+    },
+    Stop {
+        user_id: String,
+        trader_id: String,
+        reply: oneshot::Sender<Result<(), RunnerError>>,
+    },
+    Running(oneshot::Sender<Vec<String>>),
+    Shutdown(oneshot::Sender<()>),
+}
+
+struct TraderTask {
+    user_id: String,
+    trader_name: String,
+    stop: oneshot::Sender<()>,
+    handle: JoinHandle<()>,
+}
+
+impl TraderTask {
+    async fn stop(self) {
+        let _ = self.stop.send(());
+        let _ = self.handle.await;
+    }
+}
+
+/// Owns the trader tasks. Configure it, then [`spawn`](Self::spawn) it.
+pub struct Runner {
+    db: Database,
+    config: RunnerConfig,
+    tasks: HashMap<String, TraderTask>,
+    interrupt: watch::Sender<bool>,
+    // Traders to recover when they are next started.
+    recovering: HashSet<String>,
+}
+
+impl Runner {
+    pub fn new(db: Database, config: RunnerConfig) -> Self {
+        Self {
+            db,
+            config,
+            tasks: HashMap::new(),
+            interrupt: watch::Sender::new(false),
+            recovering: HashSet::new(),
+        }
+    }
+
+    /// Marks traders that were running when the process died; each is
+    /// [recovered](TraderCycle::recover) before its first cycle.
+    pub fn recover(&mut self, traders: &[TraderRecord]) {
+        self.recovering.extend(traders.iter().map(|t| t.id.clone()));
+    }
+
+    /// Starts a task for an already built cycle, replacing any task the
+    /// trader has. Lets callers bring their own venue and AI. Traders marked
+    /// with [`recover`](Self::recover) are recovered first.
+    pub async fn launch<V: Venue>(&mut self, mut cycle: TraderCycle<V>) {
+        cycle.interrupt = Some(self.interrupt.subscribe());
+        let trader_id = cycle.trader().id.clone();
+        let user_id = cycle.trader().user_id.clone();
+        let trader_name = cycle.trader().name.clone();
+        if let Some(task) = self.tasks.remove(&trader_id) {
+            task.stop().await;
+        }
+        if self.recovering.remove(&trader_id)
+            && let Err(e) = cycle.recover().await
+        {
+            tracing::warn!("⚠️ 交易员 {} 崩溃恢复失败: {:#}", trader_id, e);
+        }
+        let minutes = cycle.trader().scan_interval_minutes.max(1) as u64;
+        let schedule = self
+            .config
+            .alignment
+            .schedule(Duration::from_secs(minutes * 60));
+        let (stop, stopped) = oneshot::channel();
+        let handle = tokio::spawn(run_trader(
+            cycle,
+            schedule,
+            self.config.reconciliation.interval,
+            self.config.margin_monitor.interval,
+            stopped,
+        ));
+        let message = i18n::render(
+            user_locale(&self.db, &user_id).await,
+            Msg::TraderStarted,
+            &[("name", &trader_name)],
+        );
+        notify::notify(
+            Event::alert("trader_started", message)
+                .user(&user_id)
+                .trader(&trader_id),
+        );
+        self.tasks.insert(
+            trader_id,
+            TraderTask {
+                user_id,
+                trader_name,
+                stop,
+                handle,
+            },
+        );
+    }
+
+    async fn start_trader(&mut self, user_id: &str, trader_id: &str) -> Result<(), RunnerError> {
+        let (trader, ai_model, exchange) = self.db.get_trader_config(user_id, trader_id).await?;
+        quota::check_start(&self.db, &trader).await?;
+        self.start_with(trader, &ai_model, &exchange).await
+    }
+
+    async fn start_with(
+        &mut self,
+        trader: TraderRecord,
+        ai_model: &AIModelConfig,
+        exchange: &ExchangeConfig,
+    ) -> Result<(), RunnerError> {
+        let ai_model = secrets::resolve_model(ai_model).await?;
+        let exchange = &secrets::resolve_exchange(exchange).await?;
+        let ai = ai_cache::wrap(ai::from_model_config(&ai_model)?);
+        match exchange.id.as_str() {
+            "binance" => {
+                let client =
+                    ApiClient::for_exchange(exchange).with_audit_owner(&trader.user_id, &trader.id);
+                self.launch_venue(trader, client, ai).await
+            }
+            "aster" => {
+                let client = AsterClient::for_exchange(exchange)?
+                    .with_audit_owner(&trader.user_id, &trader.id);
+                self.launch_venue(trader, client, ai).await
+            }
+            // 暂不支持 Hyperliquid 下单，只能观察账户
+            "hyperliquid" if !trader.watch_only => {
+                return Err(RunnerError::WatchOnlyExchange(exchange.id.clone()));
+            }
+            "hyperliquid" => {
+                let account = HyperliquidAccount::for_exchange(exchange);
+                self.launch_venue(trader, account, ai).await
+            }
+            other => return Err(RunnerError::UnsupportedExchange(other.to_string())),
+        }
+        Ok(())
+    }
+
+    async fn launch_venue<V: Venue>(
+        &mut self,
+        trader: TraderRecord,
+        venue: V,
+        ai: Box<dyn AiProvider>,
+    ) {
+        let logger = self.config.logger(&trader);
+        let db = self.db.clone();
+        if trader.watch_only {
+            let cycle = TraderCycle::new(db, trader, ReadOnly(venue), ai, logger, &self.config);
+            self.launch(cycle).await;
+        } else {
+            let cycle = TraderCycle::new(db, trader, venue, ai, logger, &self.config);
+            self.launch(cycle).await;
+        }
+    }
+
+    // Stops the trader's task; its margin no longer counts against the
+    // ceiling its siblings share.
+    async fn stop_trader(&mut self, trader_id: &str) {
+        if let Some(task) = self.tasks.remove(trader_id) {
+            let user_id = task.user_id.clone();
+            let message = i18n::render(
+                user_locale(&self.db, &user_id).await,
+                Msg::TraderStopped,
+                &[("name", &task.trader_name)],
+            );
+            task.stop().await;
+            margin_governor::remove_trader(&user_id, trader_id);
+            notify::notify(
+                Event::alert("trader_stopped", message)
+                    .user(&user_id)
+                    .trader(trader_id),
+            );
+        }
+    }
+
+    /// Starts traders flagged running that have no task and stops tasks whose
+    /// trader is no longer flagged running.
+    async fn sync(&mut self) {
+        let running = match self.db.get_running_traders().await {
+            Ok(running) => running,
+            Err(e) => {
+                tracing::warn!("⚠️ 读取运行中的交易员失败: {:#}", e);
+                return;
+            }
+        };
+        let stale: Vec<String> = self
+            .tasks
+            .keys()
+            .filter(|id| !running.iter().any(|t| &t.id == *id))
+            .cloned()
+            .collect();
+        for trader_id in stale {
+            self.stop_trader(&trader_id).await;
+        }
+        for trader in running {
+            if self.tasks.contains_key(&trader.id) {
+                continue;
+            }
+            match self.start_trader(&trader.user_id, &trader.id).await {
+                Ok(()) => {}
+                // Over quota: clear the flag rather than retrying every minute.
+                Err(RunnerError::Quota(e)) => {
+                    tracing::warn!("⚠️ 交易员 {} 超出配额，已停止: {}", trader.id, e);
+                    match self
+                        .db
+                        .update_trader_status(&trader.user_id, &trader.id, false)
+                        .await
+                    {
+                        Ok(()) => {
+                            audit_log::trader_status(
+                                &self.db,
+                                "",
+                                audit_log::SOURCE_SYSTEM,
+                                &trader.id,
+                                false,
+                            )
+                            .await
+                        }
+                        Err(e) => {
+                            tracing::warn!("⚠️ 更新交易员 {} 状态失败: {:#}", trader.id, e)
+                        }
+                    }
+                }
+                Err(e) => tracing::warn!("⚠️ 交易员 {} 启动失败: {}", trader.id, e),
+            }
+        }
+        // Tasks that ended on their own (e.g. panicked) are restarted next time.
+        self.tasks.retain(|trader_id, task| {
+            let finished = task.handle.is_finished();
+            if finished {
+                margin_governor::remove_trader(&task.user_id, trader_id);
+            }
+            !finished
+        });
+    }
+
+    /// Starts every running trader and hands control to a background task.
+    pub fn spawn(mut self) -> RunnerHandle {
+        let (tx, mut rx) = mpsc::channel(16);
+        tokio::spawn(async move {
+            let mut sync = tokio::time::interval(SYNC_INTERVAL);
+            sync.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            let done = loop {
+                tokio::select! {
+                    _ = sync.tick() => self.sync().await,
+                    control = rx.recv() => match control {
+                        Some(Control::Start { user_id, trader_id, reply }) => {
+                            let result = self.handle_start(&user_id, &trader_id).await;
+                            if result.is_ok() {
+                                audit_log::trader_status(
+                                    &self.db,
+                                    &user_id,
+                                    audit_log::SOURCE_API,
+                                    &trader_id,
+                                    true,
+                                )
+                                .await;
+                            }
+                            let _ = reply.send(result);
+                        }
+                        Some(Control::Stop { user_id, trader_id, reply }) => {
+                            let result = self
+                                .db
+                                .update_trader_status(&user_id, &trader_id, false)
+                                .await
+                                .map_err(RunnerError::from);
+                            self.stop_trader(&trader_id).await;
+                            if result.is_ok() {
+                                audit_log::trader_status(
+                                    &self.db,
+                                    &user_id,
+                                    audit_log::SOURCE_API,
+                                    &trader_id,
+                                    false,
+                                )
+                                .await;
+                            }
+                            let _ = reply.send(result);
+                        }
+                        Some(Control::Running(reply)) => {
+                            let mut ids: Vec<String> = self.tasks.keys().cloned().collect();
+                            ids.sort();
+                            let _ = reply.send(ids);
+                        }
+                        Some(Control::Shutdown(reply)) => break Some(reply),
+                        None => break None,
+                    },
+                }
+            };
+            self.shut_down().await;
+            if let Some(reply) = done {
+                let _ = reply.send(());
+            }
+        });
+        RunnerHandle { tx }
+    }
+
+    // Cuts cycles waiting for the AI short, waits for the rest, and keeps
+    // every stopped trader flagged running so it resumes on the next start.
+    async fn shut_down(&mut self) {
+        self.interrupt.send_replace(true);
+        let count = self.tasks.len();
+        for (trader_id, task) in self.tasks.drain() {
+            let user_id = task.user_id.clone();
+            task.stop().await;
+            if let Err(e) = self
+                .db
+                .update_trader_status(&user_id, &trader_id, true)
+                .await
+            {
+                tracing::warn!("⚠️ 保存交易员 {} 运行状态失败: {:#}", trader_id, e);
+            }
+        }
+        tracing::info!("🛑 运行器已停止 {} 个交易员", count);
+    }
+
+    async fn handle_start(&mut self, user_id: &str, trader_id: &str) -> Result<(), RunnerError> {
+        self.start_trader(user_id, trader_id).await?;
+        self.db
+            .update_trader_status(user_id, trader_id, true)
+            .await?;
+        Ok(())
+    }
+}
+
+/// Controls a spawned [`Runner`]. Cheap to clone.
+#[derive(Clone)]
+pub struct RunnerHandle {
+    tx: mpsc::Sender<Control>,
+}
+
+impl RunnerHandle {
+    async fn request<T>(
+        &self,
+        control: impl FnOnce(oneshot::Sender<T>) -> Control,
+    ) -> Result<T, RunnerError> {
+        let (reply, response) = oneshot::channel();
+        self.tx
+            .send(control(reply))
+            .await
+            .map_err(|_| RunnerError::Closed)?;
+        response.await.map_err(|_| RunnerError::Closed)
+    }
+
+    /// Starts a trader (restarting it if it runs) and flags it running.
+    pub async fn start(&self, user_id: &str, trader_id: &str) -> Result<(), RunnerError> {
+        self.request(|reply| Control::Start {
+            user_id: user_id.to_string(),
+            trader_id: trader_id.to_string(),
+            reply,
+        })
+        .await?
+    }
+
+    /// Flags a trader stopped and waits for its current cycle to finish.
+    pub async fn stop(&self, user_id: &str, trader_id: &str) -> Result<(), RunnerError> {
+        self.request(|reply| Control::Stop {
+            user_id: user_id.to_string(),
+            trader_id: trader_id.to_string(),
+            reply,
+        })
+        .await?
+    }
+
+    /// Ids of the traders that currently have a task.
+    pub async fn running(&self) -> Result<Vec<String>, RunnerError> {
+        self.request(Control::Running).await
+    }
+
+    /// Stops every trader. Cycles waiting for the AI are cut short and
+    /// recorded as interrupted; order placement in progress finishes. The
+    /// traders are flagged running so they start again with the process.
+    pub async fn shutdown(&self) -> Result<(), RunnerError> {
+        self.request(Control::Shutdown).await
+    }
+}
@@ -0,0 +1,367 @@
+//! Background job scheduler.
+//!
+//! Jobs are Rust handlers registered by name; their definitions (schedule,
+//! enabled flag, jitter) and last-run status live in the `scheduled_jobs` table,
+//! so edits and history survive restarts. A job never overlaps with itself: a
+//! trigger that fires while the previous run is still going is recorded as
+//! skipped.
+//!
+//! Schedules are either `@every <duration>` (e.g. `@every 15m`), `@aligned
+//! <duration> [+<delay>]` for candle closes (e.g. `@aligned 3m +5s` fires at
+//! :00:05, :03:05, ...), or a cron expression evaluated in UTC, with 5 fields
+//! (`0 3 * * *`) or with a leading seconds field (`0 0 3 * * *`).
+
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use rand::Rng;
+use serde::Serialize;
+use thiserror::Error;
+use tokio::sync::Notify;
+
+use crate::database::{Database, ScheduledJob};
+use crate::sim;
+
+pub type JobFuture = Pin<Box<dyn Future<Output = anyhow::Result<()>> + Send>>;
+pub type JobHandler = Arc<dyn Fn() -> JobFuture + Send + Sync>;
+
+#[derive(Error, Debug)]
+pub enum SchedulerError {
+    #[error("Invalid schedule '{0}': {1}")]
+    InvalidSchedule(String, String),
+    #[error("Job '{0}' is not registered")]
+    UnknownJob(String),
+    #[error("Job '{0}' is already running")]
+    AlreadyRunning(String),
+    #[error("Database error: {0}")]
+    Database(#[from] anyhow::Error),
+}
+
+#[derive(Debug, Clone)]
+pub enum Schedule {
+    Every(Duration),
+    /// Multiples of `interval` since the Unix epoch, which is where exchange
+    /// candles open, shifted by `delay`.
+    Aligned {
+        interval: Duration,
+        delay: Duration,
+    },
+    Cron(Box<cron::Schedule>),
+}
+
+impl FromStr for Schedule {
+    type Err = SchedulerError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let invalid = |e: String| SchedulerError::InvalidSchedule(s.to_string(), e);
+
+        if let Some(every) = s.strip_prefix("@every") {
+            let interval = humantime_serde::re::humantime::parse_duration(every.trim())
+                .map_err(|e| invalid(e.to_string()))?;
+            if interval.is_zero() {
+                return Err(invalid("interval must be positive".to_string()));
+            }
+            return Ok(Schedule::Every(interval));
+        }
+
+        if let Some(aligned) = s.strip_prefix("@aligned") {
+            let parse = |d: &str| {
+                humantime_serde::re::humantime::parse_duration(d.trim())
+                    .map_err(|e| invalid(e.to_string()))
+            };
+            let (interval, delay) = match aligned.split_once('+') {
+                Some((interval, delay)) => (parse(interval)?, parse(delay)?),
+                None => (parse(aligned)?, Duration::ZERO),
+            };
+            if interval.as_millis() == 0 {
+                return Err(invalid("interval must be positive".to_string()));
+            }
+            return Ok(Schedule::Aligned { interval, delay });
+        }
+
+        // The cron crate wants a seconds field; accept the usual 5-field form too.
+        let expr = if s.split_whitespace().count() == 5 {
+            format!("0 {}", s)
+        } else {
+            s.to_string()
+        };
+        let schedule = cron::Schedule::from_str(&expr).map_err(|e| invalid(e.to_string()))?;
+        Ok(Schedule::Cron(Box::new(schedule)))
+    }
+}
+
+impl Schedule {
+    /// Next fire time strictly after `after`, given when the job last started.
+    pub fn next_after(
+        &self,
+        after: DateTime<Utc>,
+        last_started: Option<DateTime<Utc>>,
+    ) -> Option<DateTime<Utc>> {
+        match self {
+            Schedule::Every(interval) => {
+                let interval = chrono::Duration::from_std(*interval).ok()?;
+                // Keep the cadence across restarts instead of firing on every boot.
+                Some(match last_started {
+                    Some(last) => (last + interval).max(after),
+                    None => after + interval,
+                })
+            }
+            Schedule::Aligned { interval, delay } => {
+                let step = i64::try_from(interval.as_millis()).ok()?;
+                let delay = i64::try_from(delay.as_millis()).ok()?;
+                let boundary = (after.timestamp_millis() - delay).div_euclid(step) + 1;
+                DateTime::from_timestamp_millis(boundary * step + delay)
+            }
+            Schedule::Cron(schedule) => schedule.after(&after).next(),
+        }
+    }
+}
+
+/// Current state of a job as reported by the API.
+#[derive(Debug, Clone, Serialize)]
+pub struct JobStatus {
+    #[serde(flatten)]
+    pub job: ScheduledJob,
+    pub running: bool,
+    pub next_run_at: Option<DateTime<Utc>>,
+}
+
+struct Job {
+    handler: JobHandler,
+    record: RwLock<ScheduledJob>,
+    next_run_at: Mutex<Option<DateTime<Utc>>>,
+    running: AtomicBool,
+    // Wakes the job's loop when its definition changes.
+    changed: Notify,
+}
+
+// Clears a job's running flag when its run ends, even if the handler panics.
+struct RunningGuard(Arc<Job>);
+
+impl Drop for RunningGuard {
+    fn drop(&mut self) {
+        self.0.running.store(false, Ordering::SeqCst);
+    }
+}
+
+pub struct Scheduler {
+    db: Database,
+    jobs: RwLock<BTreeMap<String, Arc<Job>>>,
+}
+
+impl Scheduler {
+    pub fn new(db: Database) -> Arc<Self> {
+        Arc::new(Self {
+            db,
+            jobs: RwLock::new(BTreeMap::new()),
+        })
+    }
+
+    /// Registers a handler and starts scheduling it. `default_schedule` and
+    /// `jitter` are only used the first time; afterwards the stored definition wins.
+    pub async fn register<F, Fut>(
+        self: &Arc<Self>,
+        name: &str,
+        default_schedule: &str,
+        jitter: Duration,
+        handler: F,
+    ) -> Result<(), SchedulerError>
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = anyhow::Result<()>> + Send + 'static,
+    {
+        default_schedule.parse::<Schedule>()?;
+        let record = self
+            .db
+            .register_scheduled_job(name, default_schedule, jitter.as_secs() as i64)
+            .await?;
+
+        let job = Arc::new(Job {
+            handler: Arc::new(move || Box::pin(handler()) as JobFuture),
+            record: RwLock::new(record),
+            next_run_at: Mutex::new(None),
+            running: AtomicBool::new(false),
+            changed: Notify::new(),
+        });
+        self.jobs
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(name.to_string(), job.clone());
+
+        tokio::spawn(self.clone().job_loop(job));
+        tracing::info!("⏰ 已注册定时任务 {}", name);
+        Ok(())
+    }
+
+    /// Status of every registered job, sorted by name.
+    pub fn status(&self) -> Vec<JobStatus> {
+        let jobs = self.jobs.read().unwrap_or_else(|e| e.into_inner());
+        jobs.values()
+            .map(|job| JobStatus {
+                job: job.record.read().unwrap_or_else(|e| e.into_inner()).clone(),
+                running: job.running.load(Ordering::SeqCst),
+                next_run_at: *job.next_run_at.lock().unwrap_or_else(|e| e.into_inner()),
+            })
+            .collect()
+    }
+
+    /// Changes a job's definition, persists it and reschedules the job.
+    pub async fn update(
+        &self,
+        name: &str,
+        schedule: &str,
+        enabled: bool,
+        jitter: Duration,
+    ) -> Result<(), SchedulerError> {
+        schedule.parse::<Schedule>()?;
+        let job = self.job(name)?;
+        let jitter_secs = jitter.as_secs() as i64;
+        self.db
+            .update_scheduled_job(name, schedule, enabled, jitter_secs)
+            .await?;
+
+        {
+            let mut record = job.record.write().unwrap_or_else(|e| e.into_inner());
+            record.schedule = schedule.to_string();
+            record.enabled = enabled;
+            record.jitter_secs = jitter_secs;
+        }
+        job.changed.notify_one();
+        Ok(())
+    }
+
+    /// Runs a job immediately, outside its schedule.
+    pub fn run_now(&self, name: &str) -> Result<(), SchedulerError> {
+        let job = self.job(name)?;
+        if !self.spawn_run(&job) {
+            return Err(SchedulerError::AlreadyRunning(name.to_string()));
+        }
+        Ok(())
+    }
+
+    fn job(&self, name: &str) -> Result<Arc<Job>, SchedulerError> {
+        self.jobs
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(name)
+            .cloned()
+            .ok_or_else(|| SchedulerError::UnknownJob(name.to_string()))
+    }
+
+    async fn job_loop(self: Arc<Self>, job: Arc<Job>) {
+        // Last time this loop fired, so an interval job whose run has not recorded
+        // its start yet (or was skipped) is not triggered again straight away.
+        let mut last_fired: Option<DateTime<Utc>> = None;
+        let name = job
+            .record
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .name
+            .clone();
+        let mut rng = sim::rng(&format!("scheduler:{}", name));
+        loop {
+            let (name, schedule, enabled, jitter_secs, last_started) = {
+                let r = job.record.read().unwrap_or_else(|e| e.into_inner());
+                (
+                    r.name.clone(),
+                    r.schedule.clone(),
+                    r.enabled,
+                    r.jitter_secs.max(0) as u64,
+                    r.last_started_at,
+                )
+            };
+
+            let next = match schedule.parse::<Schedule>() {
+                Ok(s) if enabled => s.next_after(Utc::now(), last_started.max(last_fired)),
+                Ok(_) => None,
+                Err(e) => {
+                    tracing::error!("❌ 定时任务 {} 的调度表达式无效: {}", name, e);
+                    None
+                }
+            };
+            *job.next_run_at.lock().unwrap_or_else(|e| e.into_inner()) = next;
+
+            let Some(next) = next else {
+                job.changed.notified().await;
+                continue;
+            };
+
+            let jitter = if jitter_secs > 0 {
+                Duration::from_millis(rng.gen_range(0..jitter_secs * 1000))
+            } else {
+                Duration::ZERO
+            };
+            let delay = (next - Utc::now()).to_std().unwrap_or_default() + jitter;
+
+            tokio::select! {
+                _ = tokio::time::sleep(delay) => {
+                    last_fired = Some(next);
+                    if !self.spawn_run(&job) {
+                        tracing::warn!("⚠️ 定时任务 {} 上次运行尚未结束，本次跳过", name);
+                        if let Err(e) = self.db.record_job_skipped(&name).await {
+                            tracing::warn!("⚠️ 记录任务跳过失败: {}", e);
+                        }
+                        let mut r = job.record.write().unwrap_or_else(|e| e.into_inner());
+                        r.skipped_count += 1;
+                        r.last_outcome = Some("skipped".to_string());
+                    }
+                }
+                _ = job.changed.notified() => {}
+            }
+        }
+    }
+
+    // Starts a run unless one is in progress; returns false if it was skipped.
+    fn spawn_run(&self, job: &Arc<Job>) -> bool {
+        if job.running.swap(true, Ordering::SeqCst) {
+            return false;
+        }
+
+        let job = job.clone();
+        let db = self.db.clone();
+        tokio::spawn(async move {
+            let name = job
+                .record
+                .read()
+                .unwrap_or_else(|e| e.into_inner())
+                .name
+                .clone();
+            let started_at = Utc::now();
+            let running = RunningGuard(job.clone());
+            let result = (job.handler)().await;
+            let finished_at = Utc::now();
+            drop(running);
+
+            let error = result.err().map(|e| format!("{:#}", e));
+            match &error {
+                None => tracing::debug!("✅ 定时任务 {} 完成", name),
+                Some(e) => tracing::error!("❌ 定时任务 {} 失败: {}", name, e),
+            }
+
+            {
+                let mut r = job.record.write().unwrap_or_else(|e| e.into_inner());
+                r.last_started_at = Some(started_at);
+                r.last_finished_at = Some(finished_at);
+                r.last_duration_ms = Some((finished_at - started_at).num_milliseconds());
+                r.last_outcome = Some(if error.is_some() { "failed" } else { "success" }.into());
+                r.last_error = error.clone();
+                r.run_count += 1;
+                r.failure_count += error.is_some() as i64;
+            }
+            if let Err(e) = db
+                .record_job_run(&name, started_at, finished_at, error.as_deref())
+                .await
+            {
+                tracing::warn!("⚠️ 保存任务运行记录失败: {}", e);
+            }
+        });
+        true
+    }
+}
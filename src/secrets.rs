@@ -0,0 +1,435 @@
+//! Secrets kept in external stores.
+//!
+//! Any key or secret in the config file or in the exchanges and AI models
+//! tables may be written as a reference instead of the raw value:
+//!
+//! - `vault://secret/data/aitrading#binance_secret` reads field
+//!   `binance_secret` of a HashiCorp Vault KV secret (v1 or v2) through
+//!   `VAULT_ADDR` with `VAULT_TOKEN` (and `VAULT_NAMESPACE`, if set).
+//! - `aws-sm://prod/binance` reads the secret string of an AWS Secrets
+//!   Manager secret; `aws-sm://prod/binance#secret_key` reads one field of a
+//!   JSON secret. Credentials and region come from the usual `AWS_*`
+//!   variables.
+//!
+//! Config references are resolved once at startup, table references whenever
+//! a trader starts or its credentials are checked, so the raw values only
+//! live in memory. Stores are pluggable: [`register`] a [`SecretProvider`]
+//! for another scheme.
+
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, RwLock};
+
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use once_cell::sync::Lazy;
+use serde_json::{Value, json};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+use crate::api_client::shared_client;
+use crate::config::Config;
+use crate::database::{AIModelConfig, ExchangeConfig};
+
+static PROVIDERS: Lazy<RwLock<BTreeMap<String, Arc<dyn SecretProvider>>>> =
+    Lazy::new(|| RwLock::new(BTreeMap::new()));
+
+#[derive(Error, Debug)]
+pub enum SecretError {
+    #[error("Invalid secret reference '{0}'")]
+    InvalidReference(String),
+    #[error("No secret provider for '{0}://'; check its environment variables")]
+    NoProvider(String),
+    #[error("{0} is not set")]
+    NotConfigured(&'static str),
+    #[error("Request failed: {0}")]
+    Request(#[from] reqwest::Error),
+    #[error("Secret store returned {0}: {1}")]
+    Status(u16, String),
+    #[error("Secret '{0}' has no field '{1}'")]
+    MissingField(String, String),
+    #[error("Secret '{0}' is not a single value; name a field with '#'")]
+    Ambiguous(String),
+    #[error("Secret '{0}' has no string value")]
+    NotString(String),
+    #[error("{0}: {1}")]
+    Field(String, Box<SecretError>),
+}
+
+pub type SecretFuture<'a> = Pin<Box<dyn Future<Output = Result<String, SecretError>> + Send + 'a>>;
+
+/// A store that secret references of one scheme are read from.
+pub trait SecretProvider: Send + Sync {
+    /// The scheme of the references it resolves, e.g. "vault".
+    fn scheme(&self) -> &str;
+
+    /// Reads the secret at `location`, or its `field` if one is named.
+    fn fetch<'a>(&'a self, location: &'a str, field: Option<&'a str>) -> SecretFuture<'a>;
+}
+
+/// Adds a provider, replacing any registered for the same scheme.
+pub fn register(provider: Arc<dyn SecretProvider>) {
+    PROVIDERS
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(provider.scheme().to_string(), provider);
+}
+
+/// Registers the Vault and AWS Secrets Manager providers whose environment
+/// variables are set.
+pub fn register_builtin() {
+    if let Ok(vault) = VaultProvider::from_env() {
+        register(Arc::new(vault));
+    }
+    if let Ok(aws) = AwsSecretsManager::from_env() {
+        register(Arc::new(aws));
+    }
+}
+
+/// A parsed `scheme://location#field` reference.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SecretRef {
+    pub scheme: String,
+    pub location: String,
+    pub field: Option<String>,
+}
+
+impl SecretRef {
+    /// Parses `value` if it looks like a reference. Raw keys never contain
+    /// "://", so anything else is taken literally.
+    pub fn parse(value: &str) -> Option<Result<Self, SecretError>> {
+        let (scheme, rest) = value.trim().split_once("://")?;
+        let valid_scheme = !scheme.is_empty()
+            && scheme
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-');
+        if !valid_scheme {
+            return None;
+        }
+        let (location, field) = match rest.split_once('#') {
+            Some((location, field)) => (location, Some(field)),
+            None => (rest, None),
+        };
+        if location.is_empty() || field.is_some_and(str::is_empty) {
+            return Some(Err(SecretError::InvalidReference(value.to_string())));
+        }
+        Some(Ok(Self {
+            scheme: scheme.to_string(),
+            location: location.to_string(),
+            field: field.map(str::to_string),
+        }))
+    }
+}
+
+/// Whether `value` is a secret reference rather than a raw secret.
+pub fn is_reference(value: &str) -> bool {
+    SecretRef::parse(value).is_some()
+}
+
+/// The secret `value` refers to, or `value` itself if it is not a reference.
+pub async fn resolve(value: &str) -> Result<String, SecretError> {
+    let reference = match SecretRef::parse(value) {
+        Some(reference) => reference?,
+        None => return Ok(value.to_string()),
+    };
+    let provider = PROVIDERS
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(&reference.scheme)
+        .cloned()
+        .ok_or_else(|| SecretError::NoProvider(reference.scheme.clone()))?;
+    provider
+        .fetch(&reference.location, reference.field.as_deref())
+        .await
+}
+
+async fn resolve_field(name: &str, value: &mut String) -> Result<(), SecretError> {
+    *value = resolve(value)
+        .await
+        .map_err(|e| SecretError::Field(name.to_string(), Box::new(e)))?;
+    Ok(())
+}
+
+/// Resolves the references among the traders' keys in `config`.
+pub async fn resolve_config(config: &mut Config) -> Result<(), SecretError> {
+    for trader in &mut config.traders {
+        let fields = [
+            ("binance_api_key", &mut trader.binance_api_key),
+            ("binance_secret_key", &mut trader.binance_secret_key),
+            (
+                "hyperliquid_private_key",
+                &mut trader.hyperliquid_private_key,
+            ),
+            ("aster_private_key", &mut trader.aster_private_key),
+            ("qwen_key", &mut trader.qwen_key),
+            ("deepseek_key", &mut trader.deepseek_key),
+            ("custom_api_key", &mut trader.custom_api_key),
+        ];
+        for (name, value) in fields {
+            if let Some(value) = value {
+                resolve_field(&format!("traders[{}].{}", trader.id, name), value).await?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// An exchange account with its keys resolved.
+pub async fn resolve_exchange(exchange: &ExchangeConfig) -> Result<ExchangeConfig, SecretError> {
+    let mut exchange = exchange.clone();
+    resolve_field("api_key", &mut exchange.api_key).await?;
+    resolve_field("secret_key", &mut exchange.secret_key).await?;
+    resolve_field("aster_private_key", &mut exchange.aster_private_key).await?;
+    Ok(exchange)
+}
+
+/// An AI model with its API key resolved.
+pub async fn resolve_model(model: &AIModelConfig) -> Result<AIModelConfig, SecretError> {
+    let mut model = model.clone();
+    resolve_field("api_key", &mut model.api_key).await?;
+    Ok(model)
+}
+
+// Picks `field` out of a secret's key/value data, or its only value.
+fn pick(location: &str, data: &Value, field: Option<&str>) -> Result<String, SecretError> {
+    let value = match (field, data) {
+        (Some(field), _) => data
+            .get(field)
+            .ok_or_else(|| SecretError::MissingField(location.to_string(), field.to_string()))?,
+        (None, Value::Object(map)) if map.len() == 1 => map.values().next().unwrap_or(data),
+        (None, _) => return Err(SecretError::Ambiguous(location.to_string())),
+    };
+    Ok(match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    })
+}
+
+async fn read_response(response: reqwest::Response) -> Result<Value, SecretError> {
+    let status = response.status();
+    let body = response.text().await?;
+    if !status.is_success() {
+        return Err(SecretError::Status(status.as_u16(), body));
+    }
+    serde_json::from_str(&body).map_err(|_| SecretError::Status(status.as_u16(), body))
+}
+
+/// HashiCorp Vault over its HTTP API. References name the API path below
+/// `/v1/`, so KV v2 paths include `data/`.
+pub struct VaultProvider {
+    addr: String,
+    token: String,
+    namespace: Option<String>,
+}
+
+impl VaultProvider {
+    pub fn new(addr: &str, token: &str) -> Self {
+        Self {
+            addr: addr.trim_end_matches('/').to_string(),
+            token: token.to_string(),
+            namespace: None,
+        }
+    }
+
+    pub fn with_namespace(mut self, namespace: &str) -> Self {
+        self.namespace = Some(namespace.to_string());
+        self
+    }
+
+    /// Reads `VAULT_ADDR`, `VAULT_TOKEN` and `VAULT_NAMESPACE`.
+    pub fn from_env() -> Result<Self, SecretError> {
+        let addr =
+            std::env::var("VAULT_ADDR").map_err(|_| SecretError::NotConfigured("VAULT_ADDR"))?;
+        let token =
+            std::env::var("VAULT_TOKEN").map_err(|_| SecretError::NotConfigured("VAULT_TOKEN"))?;
+        let vault = Self::new(&addr, &token);
+        Ok(match std::env::var("VAULT_NAMESPACE") {
+            Ok(namespace) if !namespace.is_empty() => vault.with_namespace(&namespace),
+            _ => vault,
+        })
+    }
+}
+
+impl SecretProvider for VaultProvider {
+    fn scheme(&self) -> &str {
+        "vault"
+    }
+
+    fn fetch<'a>(&'a self, location: &'a str, field: Option<&'a str>) -> SecretFuture<'a> {
+        Box::pin(async move {
+            let url = format!("{}/v1/{}", self.addr, location.trim_start_matches('/'));
+            let mut request = shared_client()
+                .get(&url)
+                .header("X-Vault-Token", &self.token);
+            if let Some(namespace) = &self.namespace {
+                request = request.header("X-Vault-Namespace", namespace);
+            }
+            let body = read_response(request.send().await?).await?;
+            // KV v2 nests the fields one level deeper than v1.
+            let data = match &body["data"]["data"] {
+                Value::Object(_) => &body["data"]["data"],
+                _ => &body["data"],
+            };
+            pick(location, data, field)
+        })
+    }
+}
+
+/// AWS credentials for signing requests.
+#[derive(Clone)]
+pub struct AwsCredentials {
+    pub access_key_id: String,
+    pub secret_access_key: String,
+    pub session_token: Option<String>,
+}
+
+/// AWS Secrets Manager's GetSecretValue, signed with Signature Version 4.
+pub struct AwsSecretsManager {
+    region: String,
+    credentials: AwsCredentials,
+    endpoint: String,
+}
+
+impl AwsSecretsManager {
+    pub fn new(region: &str, credentials: AwsCredentials) -> Self {
+        Self {
+            region: region.to_string(),
+            credentials,
+            endpoint: format!("https://secretsmanager.{}.amazonaws.com", region),
+        }
+    }
+
+    /// Sends requests to `endpoint` instead of the regional AWS one.
+    pub fn with_endpoint(mut self, endpoint: &str) -> Self {
+        self.endpoint = endpoint.trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Reads the `AWS_*` variables the AWS CLI uses.
+    pub fn from_env() -> Result<Self, SecretError> {
+        let var = |name: &'static str| {
+            std::env::var(name)
+                .ok()
+                .filter(|v| !v.is_empty())
+                .ok_or(SecretError::NotConfigured(name))
+        };
+        let region = var("AWS_REGION").or_else(|_| var("AWS_DEFAULT_REGION"))?;
+        let credentials = AwsCredentials {
+            access_key_id: var("AWS_ACCESS_KEY_ID")?,
+            secret_access_key: var("AWS_SECRET_ACCESS_KEY")?,
+            session_token: var("AWS_SESSION_TOKEN").ok(),
+        };
+        let aws = Self::new(&region, credentials);
+        Ok(
+            match var("AWS_ENDPOINT_URL_SECRETS_MANAGER").or_else(|_| var("AWS_ENDPOINT_URL")) {
+                Ok(endpoint) => aws.with_endpoint(&endpoint),
+                Err(_) => aws,
+            },
+        )
+    }
+
+    // The signed headers for a POST of `body`, per SigV4.
+    fn sign(&self, host: &str, body: &str, now: DateTime<Utc>) -> Vec<(String, String)> {
+        const TARGET: &str = "secretsmanager.GetSecretValue";
+        let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let date = now.format("%Y%m%d").to_string();
+        let mut headers = vec![
+            (
+                "content-type".to_string(),
+                "application/x-amz-json-1.1".to_string(),
+            ),
+            ("host".to_string(), host.to_string()),
+            ("x-amz-date".to_string(), amz_date.clone()),
+        ];
+        if let Some(token) = &self.credentials.session_token {
+            headers.push(("x-amz-security-token".to_string(), token.clone()));
+        }
+        headers.push(("x-amz-target".to_string(), TARGET.to_string()));
+
+        let signed_headers = headers
+            .iter()
+            .map(|(k, _)| k.as_str())
+            .collect::<Vec<_>>()
+            .join(";");
+        let canonical_headers: String = headers
+            .iter()
+            .map(|(k, v)| format!("{}:{}\n", k, v.trim()))
+            .collect();
+        let canonical_request = format!(
+            "POST\n/\n\n{}\n{}\n{}",
+            canonical_headers,
+            signed_headers,
+            hex::encode(Sha256::digest(body.as_bytes()))
+        );
+        let scope = format!("{}/{}/secretsmanager/aws4_request", date, self.region);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+            amz_date,
+            scope,
+            hex::encode(Sha256::digest(canonical_request.as_bytes()))
+        );
+        let key = format!("AWS4{}", self.credentials.secret_access_key);
+        let mut signing_key = key.into_bytes();
+        for part in [
+            date.as_str(),
+            &self.region,
+            "secretsmanager",
+            "aws4_request",
+        ] {
+            signing_key = hmac_sha256(&signing_key, part.as_bytes());
+        }
+        let signature = hex::encode(hmac_sha256(&signing_key, string_to_sign.as_bytes()));
+        headers.push((
+            "authorization".to_string(),
+            format!(
+                "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+                self.credentials.access_key_id, scope, signed_headers, signature
+            ),
+        ));
+        headers.retain(|(k, _)| k != "host");
+        headers
+    }
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC can take key of any size");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+impl SecretProvider for AwsSecretsManager {
+    fn scheme(&self) -> &str {
+        "aws-sm"
+    }
+
+    fn fetch<'a>(&'a self, location: &'a str, field: Option<&'a str>) -> SecretFuture<'a> {
+        Box::pin(async move {
+            let url = reqwest::Url::parse(&format!("{}/", self.endpoint))
+                .map_err(|_| SecretError::InvalidReference(self.endpoint.clone()))?;
+            let host = match (url.host_str(), url.port()) {
+                (Some(host), Some(port)) => format!("{}:{}", host, port),
+                (Some(host), None) => host.to_string(),
+                (None, _) => return Err(SecretError::InvalidReference(self.endpoint.clone())),
+            };
+            let body = json!({ "SecretId": location }).to_string();
+            let mut request = shared_client().post(url);
+            for (name, value) in self.sign(&host, &body, Utc::now()) {
+                request = request.header(name, value);
+            }
+            let response = read_response(request.body(body).send().await?).await?;
+            let secret = response["SecretString"]
+                .as_str()
+                .ok_or_else(|| SecretError::NotString(location.to_string()))?;
+            match field {
+                None => Ok(secret.to_string()),
+                Some(field) => {
+                    let data: Value = serde_json::from_str(secret).map_err(|_| {
+                        SecretError::MissingField(location.to_string(), field.to_string())
+                    })?;
+                    pick(location, &data, Some(field))
+                }
+            }
+        })
+    }
+}
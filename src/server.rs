@@ -0,0 +1,3022 @@
+//! HTTP API server.
+//!
+//! The same router can be served on a TCP port, on a unix domain socket (for a
+//! local reverse proxy without exposing a port), or driven in-process through
+//! [`EmbeddedClient`] when the engine is embedded in another Rust program.
+//! Dashboards and reports read through [`Database::analytics`], so they never
+//! compete with the trading loop for database connections.
+
+use std::future::Future;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use axum::body::{Body, Bytes};
+use axum::extract::{ConnectInfo, FromRequestParts, MatchedPath, Path, Query, State};
+use axum::http::request::Parts;
+use axum::http::{HeaderMap, HeaderValue, Method, Request, StatusCode, header};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{delete, get, post, put};
+use axum::{Json, Router};
+use http_body_util::BodyExt;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use thiserror::Error;
+use tower::ServiceExt;
+use uuid::Uuid;
+
+use crate::accuracy::{self, AccuracyStats};
+use crate::ai_cache;
+use crate::approval::{self, ApprovalError};
+use crate::audit_log;
+use crate::auth::{self, Role};
+use crate::bundle::{self, BundleError, SignedStrategy};
+use crate::database::{
+    AIModelConfig, AccountTransfer, AuditLogEntry, AuthLockout, DailyReport, Database,
+    ExchangeConfig, ExecutionAudit, PauseWindow, PromptTemplate, PromptVersion,
+    ReconciliationEvent, TradeProposal, TraderRecord, TraderSnapshot, User,
+};
+use crate::exchange::{self, CredentialCheck};
+use crate::experiment::{self, VariantReport};
+use crate::export::{self, Dataset, DateRange, ExportError, FileFormat, Takeout};
+use crate::i18n::{self, Locale, Msg};
+use crate::logger::{self, DecisionRecord, RecordCipher};
+use crate::login_guard::{self, Attempt};
+use crate::maintenance::{self, Maintenance, MaintenanceError};
+use crate::margin_governor::{self, MarginUsage};
+use crate::margin_monitor::Thresholds;
+use crate::money::Decimal;
+use crate::otp::{self, OtpError};
+use crate::overview::{self, Overview};
+use crate::pause::{self, PauseError};
+use crate::performance::{self, EquityCurve, EquityRange, ExecutionQuality, TraderStats};
+use crate::prompt_history::{self, PromptChange, PromptHistoryError};
+use crate::prompt_template;
+use crate::quota::{self, Quota, QuotaError};
+use crate::replay::{self, Replay, ReplayError};
+use crate::risk_override::{self, DirectiveBatch, Overrides, RiskOverrideError, UserOverride};
+use crate::scheduler::{JobStatus, Scheduler};
+use crate::secrets;
+use crate::sizing::PositionSizing;
+use crate::strategy;
+use crate::stress::{self, StressError, StressReport};
+use crate::symbols;
+use crate::timezone;
+use crate::tournament::{self, Report};
+use crate::{currency, data, hot_reload, profiler, prompt};
+
+#[derive(Error, Debug)]
+pub enum ServerError {
+    #[error("IO error: {0}")]
+    Io(#[from] io::Error),
+    #[error("HTTP error: {0}")]
+    Http(#[from] axum::http::Error),
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Request failed with {0}: {1}")]
+    Status(StatusCode, String),
+}
+
+/// Where the API server accepts connections.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Listen {
+    Tcp(SocketAddr),
+    Unix(PathBuf),
+}
+
+/// Shared state handed to every handler.
+#[derive(Clone)]
+pub struct AppState {
+    pub db: Database,
+    pub scheduler: Option<Arc<Scheduler>>,
+    /// Opens encrypted decision records; `None` when they are stored in plain text.
+    pub cipher: Option<RecordCipher>,
+}
+
+/// A localized JSON error response: `{"error": "..."}`.
+#[derive(Debug)]
+pub struct ApiError {
+    pub status: StatusCode,
+    pub message: String,
+}
+
+impl ApiError {
+    pub fn new(status: StatusCode, locale: Locale, msg: Msg) -> Self {
+        Self {
+            status,
+            message: i18n::t(locale, msg).to_string(),
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.status, Json(json!({ "error": self.message }))).into_response()
+    }
+}
+
+/// Locale requested by the client via `Accept-Language`, English by default.
+pub fn request_locale(headers: &HeaderMap) -> Locale {
+    headers
+        .get(header::ACCEPT_LANGUAGE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(Locale::from_accept_language)
+        .unwrap_or_default()
+}
+
+/// The authenticated caller, as named by the bearer token.
+#[derive(Debug, Clone)]
+pub struct AuthUser {
+    pub user_id: String,
+    pub email: String,
+    /// Taken from the token, so checking it needs no database round trip.
+    pub role: Role,
+}
+
+impl AuthUser {
+    pub fn is_admin(&self) -> bool {
+        self.role == Role::Admin
+    }
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for AuthUser {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let locale = request_locale(&parts.headers);
+        let token = parts
+            .headers
+            .get(header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "))
+            .ok_or_else(|| ApiError::new(StatusCode::UNAUTHORIZED, locale, Msg::Unauthorized))?;
+        let claims = auth::validate_jwt(token)
+            .map_err(|_| ApiError::new(StatusCode::UNAUTHORIZED, locale, Msg::Unauthorized))?
+            .claims;
+
+        Ok(AuthUser {
+            user_id: claims.user_id,
+            email: claims.email,
+            role: claims.role,
+        })
+    }
+}
+
+/// The caller's IP address: the first `X-Forwarded-For` entry when the login
+/// guard trusts it, else the TCP peer. `None` on a unix socket or in-process.
+#[derive(Debug, Clone, Copy)]
+pub struct ClientIp(pub Option<IpAddr>);
+
+impl<S: Send + Sync> FromRequestParts<S> for ClientIp {
+    type Rejection = std::convert::Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let forwarded = login_guard::trust_forwarded_for()
+            .then(|| parts.headers.get("x-forwarded-for"))
+            .flatten()
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.split(',').next())
+            .and_then(|ip| ip.trim().parse().ok());
+        let peer = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip());
+        Ok(ClientIp(forwarded.or(peer)))
+    }
+}
+
+pub fn router(state: AppState) -> Router {
+    Router::new()
+        .route("/api/health", get(health))
+        .route("/api/register", post(register))
+        .route("/api/login", post(login))
+        .route("/api/otp/setup", get(otp_setup))
+        .route("/api/otp/verify", post(otp_verify))
+        .route("/api/traders", get(list_traders).post(create_trader))
+        .route("/api/traders/import", post(import_trader))
+        .route(
+            "/api/traders/{id}",
+            get(get_trader).put(update_trader).delete(delete_trader),
+        )
+        .route("/api/traders/{id}/export", get(export_trader))
+        .route("/api/traders/{id}/decisions", get(list_decisions))
+        .route(
+            "/api/traders/{id}/decisions/{record_id}/replay",
+            post(replay_decision),
+        )
+        .route("/api/traders/{id}/performance", get(trader_performance))
+        .route("/api/traders/{id}/equity", get(equity_curve))
+        .route("/api/traders/{id}/history/{dataset}", get(download_history))
+        .route("/api/traders/{id}/stress", get(stress_test))
+        .route("/api/models", get(list_models))
+        .route("/api/models/{id}", put(update_model))
+        .route("/api/exchanges", get(list_exchanges))
+        .route("/api/exchanges/{id}", put(update_exchange))
+        .route("/api/jobs", get(jobs))
+        .route("/api/profile", get(profile))
+        .route("/api/export", get(export_account))
+        .route("/api/cache/stats", get(cache_stats))
+        .route("/api/margin", get(margin))
+        .route("/api/overview", get(user_overview))
+        .route("/api/maintenance", get(maintenance_status))
+        .route(
+            MAINTENANCE_ADMIN_PATH,
+            put(enable_maintenance).delete(resume_maintenance),
+        )
+        .route(RISK_DIRECTIVES_PATH, post(risk_directives))
+        .route("/api/risk/overrides", get(risk_overrides))
+        .route("/api/tournaments/latest", get(latest_tournament))
+        .route("/api/accuracy", get(decision_accuracy))
+        .route("/api/exchanges/{id}/validate", post(validate_exchange))
+        .route("/api/traders/{id}/dashboard", get(trader_dashboard))
+        .route(
+            "/api/traders/{id}/transfers",
+            get(list_transfers).post(add_transfer),
+        )
+        .route(
+            "/api/traders/{id}/pauses",
+            get(list_pauses).post(schedule_pause),
+        )
+        .route("/api/traders/{id}/pauses/{pause_id}", delete(cancel_pause))
+        .route("/api/traders/{id}/prompt", put(update_prompt))
+        .route(
+            "/api/traders/{id}/prompt/versions",
+            get(list_prompt_versions),
+        )
+        .route(
+            "/api/traders/{id}/prompt/versions/{version}",
+            get(get_prompt_version),
+        )
+        .route(
+            "/api/traders/{id}/prompt/versions/{version}/rollback",
+            post(rollback_prompt),
+        )
+        .route(
+            "/api/traders/{id}/variants",
+            get(variant_report).post(create_variant),
+        )
+        .route(
+            "/api/prompt-templates",
+            get(list_prompt_templates).post(create_prompt_template),
+        )
+        .route(
+            "/api/prompt-templates/{name}",
+            get(get_prompt_template)
+                .put(update_prompt_template)
+                .delete(delete_prompt_template),
+        )
+        .route("/api/traders/{id}/proposals", get(list_proposals))
+        .route(
+            "/api/traders/{id}/proposals/{proposal_id}/approve",
+            post(approve_proposal),
+        )
+        .route(
+            "/api/traders/{id}/proposals/{proposal_id}/reject",
+            post(reject_proposal),
+        )
+        .route("/api/traders/{id}/executions/audit", get(execution_audit))
+        .route(
+            "/api/traders/{id}/reconciliation",
+            get(reconciliation_events),
+        )
+        .route("/api/traders/{id}/daily-reports", get(daily_reports))
+        .route(
+            "/api/traders/{id}/performance/execution",
+            get(execution_quality),
+        )
+        .route("/api/admin/users", get(list_users))
+        .route("/api/admin/users/{user_id}/role", put(update_user_role))
+        .route(
+            "/api/admin/beta-codes",
+            get(beta_code_stats).post(add_beta_codes),
+        )
+        .route("/api/admin/system-config/{key}", put(put_system_config))
+        .route("/api/admin/auth-lockouts", get(auth_lockouts))
+        .route("/api/admin/audit-log", get(audit_log_entries))
+        .route(
+            "/api/admin/quotas",
+            get(get_default_quota).put(put_default_quota),
+        )
+        .route(
+            "/api/admin/quotas/{user_id}",
+            get(get_user_quota)
+                .put(put_user_quota)
+                .delete(delete_user_quota),
+        )
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            audit_mutations,
+        ))
+        .layer(middleware::from_fn(read_only_guard))
+        .with_state(state)
+}
+
+// Stays writable during maintenance so it can be turned off.
+const MAINTENANCE_ADMIN_PATH: &str = "/api/admin/maintenance";
+// Risk directives are accepted during maintenance too.
+const RISK_DIRECTIVES_PATH: &str = "/api/risk/directives";
+
+// Writes that change nothing worth auditing, or come before anyone is signed in.
+const UNAUDITED_ROUTES: &[&str] = &[
+    "/api/register",
+    "/api/login",
+    "/api/exchanges/{id}/validate",
+    "/api/traders/{id}/decisions/{record_id}/replay",
+];
+// Bodies of audited writes are buffered up to this size; larger ones are refused.
+const MAX_AUDITED_BODY: usize = 2 * 1024 * 1024;
+
+// Records every successful write in the audit log, with the request body as
+// the new value and whatever the handler captured as the old one.
+async fn audit_mutations(
+    State(state): State<AppState>,
+    request: Request<Body>,
+    next: Next,
+) -> Response {
+    let read = matches!(
+        *request.method(),
+        Method::GET | Method::HEAD | Method::OPTIONS
+    );
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|p| p.as_str().to_string())
+        .filter(|route| !read && !UNAUDITED_ROUTES.contains(&route.as_str()));
+    let Some(route) = route else {
+        return next.run(request).await;
+    };
+
+    let (mut parts, body) = request.into_parts();
+    // Signed webhooks have no user; the handler rejects anything else unsigned in.
+    let user_id = AuthUser::from_request_parts(&mut parts, &())
+        .await
+        .map(|u| u.user_id)
+        .unwrap_or_default();
+    let Ok(bytes) = axum::body::to_bytes(body, MAX_AUDITED_BODY).await else {
+        let locale = request_locale(&parts.headers);
+        return ApiError::new(StatusCode::PAYLOAD_TOO_LARGE, locale, Msg::InvalidRequest)
+            .into_response();
+    };
+    let new = serde_json::from_slice::<Value>(&bytes).ok();
+    let action = format!("{} {}", parts.method, route);
+    let target = parts.uri.path().trim_start_matches("/api/").to_string();
+
+    let request = Request::from_parts(parts, Body::from(bytes));
+    let (response, old) = audit_log::capture(next.run(request)).await;
+    if response.status().is_success() {
+        audit_log::record(
+            &state.db,
+            &user_id,
+            audit_log::SOURCE_API,
+            &action,
+            &target,
+            old,
+            new,
+        )
+        .await;
+    }
+    response
+}
+
+// Rejects writes with 503 while maintenance mode is on.
+async fn read_only_guard(request: Request<Body>, next: Next) -> Response {
+    let read = matches!(
+        *request.method(),
+        Method::GET | Method::HEAD | Method::OPTIONS
+    );
+    let maintenance = match maintenance::current() {
+        Some(m)
+            if !read
+                && request.uri().path() != MAINTENANCE_ADMIN_PATH
+                && request.uri().path() != RISK_DIRECTIVES_PATH =>
+        {
+            m
+        }
+        _ => return next.run(request).await,
+    };
+    let locale = request_locale(request.headers());
+    let mut response = ApiError::new(
+        StatusCode::SERVICE_UNAVAILABLE,
+        locale,
+        Msg::MaintenanceMode,
+    )
+    .into_response();
+    if let Some(until) = maintenance.until {
+        let secs = (until - chrono::Utc::now()).num_seconds().max(1);
+        if let Ok(value) = HeaderValue::from_str(&secs.to_string()) {
+            response.headers_mut().insert(header::RETRY_AFTER, value);
+        }
+    }
+    response
+}
+
+async fn health() -> Json<Value> {
+    Json(json!({ "status": "ok", "time": chrono::Utc::now() }))
+}
+
+async fn jobs(_user: AuthUser, State(state): State<AppState>) -> Json<Vec<JobStatus>> {
+    Json(
+        state
+            .scheduler
+            .as_ref()
+            .map(|s| s.status())
+            .unwrap_or_default(),
+    )
+}
+
+/// Stage timings and the slowest cycles of every trader; admins only.
+async fn profile(
+    user: AuthUser,
+    headers: HeaderMap,
+) -> Result<Json<profiler::ProfileReport>, ApiError> {
+    require_admin(&user, request_locale(&headers))?;
+    Ok(Json(profiler::report()))
+}
+
+async fn cache_stats(_user: AuthUser) -> Json<Value> {
+    let stats = data::cache_stats();
+    let ai = ai_cache::stats();
+    Json(json!({
+        "market_data": stats,
+        "hit_rate": stats.hit_rate(),
+        "ai_responses": ai,
+        "ai_hit_rate": ai.hit_rate(),
+    }))
+}
+
+#[derive(Serialize)]
+struct MarginStatus {
+    #[serde(flatten)]
+    usage: MarginUsage,
+    max_usage_pct: f64,
+    reporting_currency: String,
+    /// Combined equity in the reporting currency; `null` without a price.
+    reporting_equity: Option<f64>,
+}
+
+/// Everything the dashboard shows for the caller's traders; see [`overview`].
+async fn user_overview(
+    user: AuthUser,
+    headers: HeaderMap,
+    State(state): State<AppState>,
+) -> Result<Json<Overview>, ApiError> {
+    let locale = request_locale(&headers);
+    let key = state.cipher.as_ref().map(|c| c.user_key(&user.user_id));
+    overview::collect(
+        &state.db.analytics(),
+        &user.user_id,
+        key.as_ref(),
+        chrono::Utc::now(),
+    )
+    .await
+    .map(Json)
+    .map_err(|e| internal_error("获取总览数据", e, locale))
+}
+
+async fn margin(
+    user: AuthUser,
+    headers: HeaderMap,
+    State(state): State<AppState>,
+) -> Result<Json<MarginStatus>, ApiError> {
+    let locale = request_locale(&headers);
+    let max_usage_pct = match state.db.get_user_by_id(&user.user_id).await {
+        Ok(Some(u)) => u.max_margin_usage_pct,
+        Ok(None) => 0.0,
+        Err(e) => {
+            tracing::error!("❌ 获取用户失败: {}", e);
+            return Err(ApiError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                locale,
+                Msg::InternalError,
+            ));
+        }
+    };
+    let usage = margin_governor::usage(&user.user_id);
+    let reporting_equity = currency::to_reporting(usage.equity, "USDT").await.ok();
+    Ok(Json(MarginStatus {
+        usage,
+        max_usage_pct,
+        reporting_currency: currency::reporting_currency(),
+        reporting_equity,
+    }))
+}
+
+// Fails with 404 unless the trader belongs to the caller.
+async fn owned_trader(
+    state: &AppState,
+    user: &AuthUser,
+    id: &str,
+    locale: Locale,
+) -> Result<TraderRecord, ApiError> {
+    match state.db.get_trader(&user.user_id, id).await {
+        Ok(Some(trader)) => Ok(trader),
+        Ok(None) => Err(ApiError::new(
+            StatusCode::NOT_FOUND,
+            locale,
+            Msg::TraderNotFound,
+        )),
+        Err(e) => {
+            tracing::error!("❌ 获取交易员失败: {}", e);
+            Err(ApiError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                locale,
+                Msg::InternalError,
+            ))
+        }
+    }
+}
+
+// Logs a failed storage call and hides the details from the caller.
+fn internal_error(what: &str, e: impl std::fmt::Display, locale: Locale) -> ApiError {
+    tracing::error!("❌ {}失败: {}", what, e);
+    ApiError::new(
+        StatusCode::INTERNAL_SERVER_ERROR,
+        locale,
+        Msg::InternalError,
+    )
+}
+
+#[derive(Deserialize)]
+struct Credentials {
+    email: String,
+    password: String,
+    /// Required once two-factor authentication is set up; an unused recovery
+    /// code is accepted instead.
+    #[serde(default)]
+    otp_code: String,
+    /// Required to register while the system is in beta mode.
+    #[serde(default)]
+    beta_code: String,
+}
+
+/// A signed-in user and their bearer token.
+#[derive(Serialize)]
+struct Session {
+    token: String,
+    user: User,
+}
+
+const MIN_PASSWORD_LEN: usize = 8;
+
+// 429 while an account or IP is locked out after failed attempts.
+fn locked_out(lockout: std::time::Duration, locale: Locale) -> ApiError {
+    ApiError {
+        status: StatusCode::TOO_MANY_REQUESTS,
+        message: i18n::render(
+            locale,
+            Msg::TooManyAttempts,
+            &[("seconds", &lockout.as_secs().max(1))],
+        ),
+    }
+}
+
+fn session(user: User, locale: Locale) -> Result<Json<Session>, ApiError> {
+    let token = auth::generate_jwt(&user.id, &user.email, user.role())
+        .map_err(|e| internal_error("生成令牌", e, locale))?;
+    Ok(Json(Session { token, user }))
+}
+
+async fn register(
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Json(body): Json<Credentials>,
+) -> Result<(StatusCode, Json<Session>), ApiError> {
+    let locale = request_locale(&headers);
+    let email = body.email.trim().to_lowercase();
+    if !email.contains('@') || body.password.chars().count() < MIN_PASSWORD_LEN {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            locale,
+            Msg::InvalidRequest,
+        ));
+    }
+    match state.db.get_user_by_email(&email).await {
+        Ok(None) => {}
+        Ok(Some(_)) => {
+            return Err(ApiError::new(
+                StatusCode::CONFLICT,
+                locale,
+                Msg::EmailAlreadyRegistered,
+            ));
+        }
+        Err(e) => return Err(internal_error("获取用户", e, locale)),
+    }
+    let beta_mode = state
+        .db
+        .get_system_config("beta_mode")
+        .await
+        .is_ok_and(|v| v == "true");
+    if beta_mode {
+        let claimed = state
+            .db
+            .user_beta_code(body.beta_code.trim(), &email)
+            .await
+            .map_err(|e| internal_error("使用内测码", e, locale))?;
+        if !claimed {
+            return Err(ApiError::new(
+                StatusCode::BAD_REQUEST,
+                locale,
+                Msg::InvalidBetaCode,
+            ));
+        }
+    }
+
+    let password_hash =
+        auth::hash_password(&body.password).map_err(|e| internal_error("哈希密码", e, locale))?;
+    let user = User {
+        id: Uuid::new_v4().to_string(),
+        email,
+        password_hash,
+        locale: locale.as_str().to_string(),
+        ..Default::default()
+    };
+    state
+        .db
+        .create_user(&user)
+        .await
+        .map_err(|e| internal_error("创建用户", e, locale))?;
+    tracing::info!("👤 新用户注册: {}", user.email);
+    Ok((StatusCode::CREATED, session(user, locale)?))
+}
+
+async fn login(
+    headers: HeaderMap,
+    ClientIp(ip): ClientIp,
+    State(state): State<AppState>,
+    Json(body): Json<Credentials>,
+) -> Result<Json<Session>, ApiError> {
+    let locale = request_locale(&headers);
+    let email = body.email.trim().to_lowercase();
+    if let Some(lockout) = login_guard::locked_for(Attempt::Login, &email, ip).await {
+        return Err(locked_out(lockout, locale));
+    }
+    let user = match state.db.get_user_by_email(&email).await {
+        Ok(Some(user)) if auth::check_password(&body.password, &user.password_hash) => user,
+        Ok(_) => {
+            if let Some(lockout) = login_guard::failed(&state.db, Attempt::Login, &email, ip).await
+            {
+                return Err(locked_out(lockout, locale));
+            }
+            return Err(ApiError::new(
+                StatusCode::UNAUTHORIZED,
+                locale,
+                Msg::InvalidCredentials,
+            ));
+        }
+        Err(e) => return Err(internal_error("获取用户", e, locale)),
+    };
+    if user.otp_verified {
+        if let Some(lockout) = login_guard::locked_for(Attempt::Otp, &user.id, ip).await {
+            return Err(locked_out(lockout, locale));
+        }
+        match otp::verify(&state.db, &user, &body.otp_code).await {
+            Ok(_) => login_guard::succeeded(Attempt::Otp, &user.id).await,
+            Err(OtpError::InvalidCode) => {
+                if let Some(lockout) =
+                    login_guard::failed(&state.db, Attempt::Otp, &user.id, ip).await
+                {
+                    return Err(locked_out(lockout, locale));
+                }
+                return Err(ApiError::new(
+                    StatusCode::UNAUTHORIZED,
+                    locale,
+                    Msg::InvalidOtp,
+                ));
+            }
+            Err(e) => return Err(internal_error("校验两步验证", e, locale)),
+        }
+    }
+    login_guard::succeeded(Attempt::Login, &email).await;
+    session(user, locale)
+}
+
+/// Starts two-factor setup: a new secret, the authenticator QR code URL and
+/// one-time recovery codes. Takes effect once confirmed with a code.
+async fn otp_setup(
+    user: AuthUser,
+    headers: HeaderMap,
+    State(state): State<AppState>,
+) -> Result<Json<otp::Enrollment>, ApiError> {
+    let locale = request_locale(&headers);
+    let account = state
+        .db
+        .get_user_by_id(&user.user_id)
+        .await
+        .map_err(|e| internal_error("获取用户", e, locale))?
+        .ok_or_else(|| ApiError::new(StatusCode::NOT_FOUND, locale, Msg::UserNotFound))?;
+    otp::enroll(&state.db, &account)
+        .await
+        .map(Json)
+        .map_err(|e| otp_error(e, locale))
+}
+
+#[derive(Deserialize)]
+struct OtpInput {
+    code: String,
+}
+
+/// Confirms two-factor setup with a code from the new authenticator.
+async fn otp_verify(
+    user: AuthUser,
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    ClientIp(ip): ClientIp,
+    Json(input): Json<OtpInput>,
+) -> Result<Json<User>, ApiError> {
+    let locale = request_locale(&headers);
+    if let Some(lockout) = login_guard::locked_for(Attempt::Otp, &user.user_id, ip).await {
+        return Err(locked_out(lockout, locale));
+    }
+    match otp::confirm(&state.db, &user.user_id, input.code.trim()).await {
+        Ok(account) => {
+            login_guard::succeeded(Attempt::Otp, &user.user_id).await;
+            Ok(Json(account))
+        }
+        Err(OtpError::InvalidCode) => {
+            match login_guard::failed(&state.db, Attempt::Otp, &user.user_id, ip).await {
+                Some(lockout) => Err(locked_out(lockout, locale)),
+                None => Err(otp_error(OtpError::InvalidCode, locale)),
+            }
+        }
+        Err(e) => Err(otp_error(e, locale)),
+    }
+}
+
+fn otp_error(e: OtpError, locale: Locale) -> ApiError {
+    match e {
+        OtpError::InvalidCode => ApiError::new(StatusCode::BAD_REQUEST, locale, Msg::InvalidOtp),
+        OtpError::AlreadyEnrolled => {
+            ApiError::new(StatusCode::CONFLICT, locale, Msg::OtpAlreadyEnabled)
+        }
+        OtpError::NotEnrolled => {
+            ApiError::new(StatusCode::BAD_REQUEST, locale, Msg::OtpNotVerified)
+        }
+        OtpError::UserNotFound(_) => {
+            ApiError::new(StatusCode::NOT_FOUND, locale, Msg::UserNotFound)
+        }
+        e => internal_error("两步验证", e, locale),
+    }
+}
+
+/// Trader settings accepted on create and update. Fields left out keep their
+/// current value, or the default for a new trader.
+#[derive(Debug, Default, Deserialize)]
+struct TraderInput {
+    name: Option<String>,
+    ai_model_id: Option<String>,
+    exchange_id: Option<String>,
+    initial_balance: Option<f64>,
+    scan_interval_minutes: Option<i32>,
+    btc_eth_leverage: Option<i32>,
+    altcoin_leverage: Option<i32>,
+    trading_symbols: Option<String>,
+    use_coin_pool: Option<bool>,
+    use_oi_top: Option<bool>,
+    is_cross_margin: Option<bool>,
+    quote_assets: Option<String>,
+    stop_loss_cooldown_minutes: Option<i32>,
+    watch_only: Option<bool>,
+    approval_threshold_usd: Option<f64>,
+    approval_ttl_minutes: Option<i32>,
+    /// Comma-separated extra kline intervals, e.g. "15m,1h,1d".
+    timeframes: Option<String>,
+    /// Sizing method, e.g. "fixed:2", "atr:1:2" or "kelly:0.5:10".
+    position_sizing: Option<String>,
+    /// Alert thresholds and auto-deleverage share of
+    /// [`margin_monitor`](crate::margin_monitor), in percent.
+    liquidation_alert_pct: Option<f64>,
+    margin_ratio_alert_pct: Option<f64>,
+    auto_deleverage_pct: Option<f64>,
+    /// Comma-separated symbols to watch on top of `trading_symbols`.
+    custom_coins: Option<String>,
+    /// Name of a registered [`strategy`]; empty leaves decisions to the AI.
+    strategy: Option<String>,
+    /// JSON array of local-time windows in which entries may be opened.
+    trading_windows: Option<String>,
+    /// Prompt edits are versioned through [`prompt_history`].
+    #[serde(flatten)]
+    prompt: PromptChange,
+}
+
+impl TraderInput {
+    fn apply(self, trader: &mut TraderRecord) {
+        fn set<T>(field: &mut T, value: Option<T>) {
+            if let Some(value) = value {
+                *field = value;
+            }
+        }
+        set(&mut trader.name, self.name);
+        set(&mut trader.ai_model_id, self.ai_model_id);
+        set(&mut trader.exchange_id, self.exchange_id);
+        set(&mut trader.initial_balance, self.initial_balance);
+        set(
+            &mut trader.scan_interval_minutes,
+            self.scan_interval_minutes,
+        );
+        set(&mut trader.btc_eth_leverage, self.btc_eth_leverage);
+        set(&mut trader.altcoin_leverage, self.altcoin_leverage);
+        set(&mut trader.trading_symbols, self.trading_symbols);
+        set(&mut trader.use_coin_pool, self.use_coin_pool);
+        set(&mut trader.use_oi_top, self.use_oi_top);
+        set(&mut trader.is_cross_margin, self.is_cross_margin);
+        set(&mut trader.quote_assets, self.quote_assets);
+        set(
+            &mut trader.stop_loss_cooldown_minutes,
+            self.stop_loss_cooldown_minutes,
+        );
+        set(&mut trader.watch_only, self.watch_only);
+        set(
+            &mut trader.approval_threshold_usd,
+            self.approval_threshold_usd,
+        );
+        set(&mut trader.approval_ttl_minutes, self.approval_ttl_minutes);
+        set(&mut trader.timeframes, self.timeframes);
+        set(&mut trader.position_sizing, self.position_sizing);
+        set(
+            &mut trader.liquidation_alert_pct,
+            self.liquidation_alert_pct,
+        );
+        set(
+            &mut trader.margin_ratio_alert_pct,
+            self.margin_ratio_alert_pct,
+        );
+        set(&mut trader.auto_deleverage_pct, self.auto_deleverage_pct);
+        set(&mut trader.custom_coins, self.custom_coins);
+        set(&mut trader.strategy, self.strategy);
+        set(&mut trader.trading_windows, self.trading_windows);
+    }
+}
+
+// Checks a trader's settings and that its model and exchange are configured
+// for the user.
+async fn validate_trader(
+    state: &AppState,
+    trader: &TraderRecord,
+    locale: Locale,
+) -> Result<(), ApiError> {
+    let invalid = trader.name.trim().is_empty()
+        || trader.initial_balance <= 0.0
+        || trader.scan_interval_minutes <= 0
+        || trader.btc_eth_leverage <= 0
+        || trader.altcoin_leverage <= 0
+        || !trader.approval_threshold_usd.is_finite()
+        || trader.approval_threshold_usd < 0.0
+        || trader.approval_ttl_minutes < 0
+        || data::parse_timeframes(&trader.timeframes).is_err()
+        || trader.position_sizing.parse::<PositionSizing>().is_err()
+        || (!trader.strategy.is_empty() && strategy::get(&trader.strategy).is_err())
+        || timezone::parse_windows(&trader.trading_windows).is_err()
+        || !Thresholds::from_trader(trader).is_valid();
+    if invalid {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            locale,
+            Msg::InvalidRequest,
+        ));
+    }
+    let quotes = symbols::quotes_for(&trader.exchange_id, &trader.quote_assets);
+    if let Some(symbol) = [&trader.trading_symbols, &trader.custom_coins]
+        .into_iter()
+        .flat_map(|list| symbols::parse_list(list, &quotes))
+        .find(|s| !symbols::is_listed(s))
+    {
+        return Err(ApiError {
+            status: StatusCode::BAD_REQUEST,
+            message: i18n::render(locale, Msg::SymbolNotListed, &[("symbol", &symbol)]),
+        });
+    }
+    let models = state
+        .db
+        .get_aimodels(&trader.user_id)
+        .await
+        .map_err(|e| internal_error("获取AI模型配置", e, locale))?;
+    if !models.iter().any(|m| m.id == trader.ai_model_id) {
+        return Err(ApiError::new(
+            StatusCode::NOT_FOUND,
+            locale,
+            Msg::AiModelNotFound,
+        ));
+    }
+    let exchanges = state
+        .db
+        .get_exchanges(&trader.user_id)
+        .await
+        .map_err(|e| internal_error("获取交易所配置", e, locale))?;
+    if !exchanges.iter().any(|e| e.id == trader.exchange_id) {
+        return Err(ApiError::new(
+            StatusCode::NOT_FOUND,
+            locale,
+            Msg::ExchangeNotFound,
+        ));
+    }
+    let template_exists =
+        prompt_template::exists(&state.db, &trader.user_id, &trader.system_prompt_template)
+            .await
+            .map_err(|e| internal_error("获取提示词模板", e, locale))?;
+    if !template_exists {
+        return Err(ApiError::new(
+            StatusCode::NOT_FOUND,
+            locale,
+            Msg::PromptTemplateNotFound,
+        ));
+    }
+    Ok(())
+}
+
+fn trader_quota_error(e: QuotaError, locale: Locale) -> ApiError {
+    match e {
+        QuotaError::TooManyTraders(_)
+        | QuotaError::ScanTooFrequent(_)
+        | QuotaError::TooManySymbols(_)
+        | QuotaError::AiBudgetExhausted(_) => {
+            ApiError::new(StatusCode::FORBIDDEN, locale, Msg::QuotaExceeded)
+        }
+        e => quota_error(e, locale),
+    }
+}
+
+async fn list_traders(
+    user: AuthUser,
+    headers: HeaderMap,
+    State(state): State<AppState>,
+) -> Result<Json<Vec<TraderRecord>>, ApiError> {
+    let locale = request_locale(&headers);
+    state
+        .db
+        .get_traders(&user.user_id)
+        .await
+        .map(Json)
+        .map_err(|e| internal_error("获取交易员列表", e, locale))
+}
+
+async fn get_trader(
+    user: AuthUser,
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<TraderRecord>, ApiError> {
+    let locale = request_locale(&headers);
+    owned_trader(&state, &user, &id, locale).await.map(Json)
+}
+
+/// Creates a stopped trader.
+async fn create_trader(
+    user: AuthUser,
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Json(input): Json<TraderInput>,
+) -> Result<(StatusCode, Json<TraderRecord>), ApiError> {
+    let locale = request_locale(&headers);
+    let settings = hot_reload::current();
+    let mut trader = TraderRecord {
+        id: Uuid::new_v4().to_string(),
+        user_id: user.user_id.clone(),
+        scan_interval_minutes: 3,
+        btc_eth_leverage: settings.btc_eth_leverage,
+        altcoin_leverage: settings.altcoin_leverage,
+        is_cross_margin: true,
+        stop_loss_cooldown_minutes: 30,
+        approval_ttl_minutes: approval::DEFAULT_TTL_MINUTES,
+        system_prompt_template: "default".to_string(),
+        ..Default::default()
+    };
+    let prompt = input.prompt.clone();
+    input.apply(&mut trader);
+    trader.custom_prompt = prompt.custom_prompt.unwrap_or_default();
+    trader.override_base_prompt = prompt.override_base_prompt.unwrap_or_default();
+    if let Some(template) = prompt.system_prompt_template {
+        trader.system_prompt_template = template;
+    }
+
+    validate_trader(&state, &trader, locale).await?;
+    quota::check_create(&state.db, &trader)
+        .await
+        .map_err(|e| trader_quota_error(e, locale))?;
+    state
+        .db
+        .create_trader(&trader)
+        .await
+        .map_err(|e| internal_error("创建交易员", e, locale))?;
+    tracing::info!(
+        "🆕 用户 {} 创建交易员 {} ({})",
+        user.user_id,
+        trader.name,
+        trader.id
+    );
+    let trader = owned_trader(&state, &user, &trader.id, locale).await?;
+    Ok((StatusCode::CREATED, Json(trader)))
+}
+
+async fn update_trader(
+    user: AuthUser,
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(input): Json<TraderInput>,
+) -> Result<Json<TraderRecord>, ApiError> {
+    let locale = request_locale(&headers);
+    let mut trader = owned_trader(&state, &user, &id, locale).await?;
+    audit_log::before(&trader);
+    let prompt = input.prompt.clone();
+    input.apply(&mut trader);
+    validate_trader(&state, &trader, locale).await?;
+    quota::for_user(&state.db, &user.user_id)
+        .await
+        .and_then(|quota| quota.check_trader(&trader))
+        .map_err(|e| trader_quota_error(e, locale))?;
+    state
+        .db
+        .update_trader(&trader)
+        .await
+        .map_err(|e| internal_error("更新交易员", e, locale))?;
+    prompt_history::update(&state.db, &user.user_id, &id, &prompt, acting_user(&user))
+        .await
+        .map_err(|e| prompt_history_error(e, locale))?;
+    owned_trader(&state, &user, &id, locale).await.map(Json)
+}
+
+/// Exports the trader as a signed strategy file, without credentials.
+async fn export_trader(
+    user: AuthUser,
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<SignedStrategy>, ApiError> {
+    let locale = request_locale(&headers);
+    owned_trader(&state, &user, &id, locale).await?;
+    bundle::export_trader(&state.db, &user.user_id, &id)
+        .await
+        .map(Json)
+        .map_err(|e| internal_error("导出交易员", e, locale))
+}
+
+#[derive(Deserialize)]
+struct ImportInput {
+    strategy: SignedStrategy,
+    ai_model_id: String,
+    exchange_id: String,
+    /// Fingerprint of a signer the caller does not trust yet, confirmed with
+    /// its author; the key is trusted from then on.
+    #[serde(default)]
+    trust_signer: Option<String>,
+}
+
+/// Recreates a shared strategy file as a new, stopped trader.
+async fn import_trader(
+    user: AuthUser,
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Json(input): Json<ImportInput>,
+) -> Result<(StatusCode, Json<TraderRecord>), ApiError> {
+    let locale = request_locale(&headers);
+    let import = bundle::prepare_import(
+        &state.db,
+        &input.strategy,
+        &user.user_id,
+        &input.ai_model_id,
+        &input.exchange_id,
+        input.trust_signer.as_deref(),
+    )
+    .await
+    .map_err(|e| match e.downcast_ref::<BundleError>() {
+        Some(BundleError::UntrustedSigner(fingerprint)) => ApiError {
+            status: StatusCode::CONFLICT,
+            message: i18n::render(
+                locale,
+                Msg::UntrustedStrategySigner,
+                &[("fingerprint", fingerprint)],
+            ),
+        },
+        Some(_) => ApiError::new(StatusCode::BAD_REQUEST, locale, Msg::InvalidStrategyFile),
+        None => internal_error("导入交易员", e, locale),
+    })?;
+
+    // The bundled template is only stored once everything else checks out.
+    let mut trader = import.trader.clone();
+    if import.template.is_some() {
+        trader.system_prompt_template = prompt::DEFAULT_TEMPLATE.to_string();
+    }
+    validate_trader(&state, &trader, locale).await?;
+    quota::check_create(&state.db, &import.trader)
+        .await
+        .map_err(|e| trader_quota_error(e, locale))?;
+    import
+        .create(&state.db)
+        .await
+        .map_err(|e| internal_error("导入交易员", e, locale))?;
+    tracing::info!(
+        "📥 用户 {} 导入策略 {} 为交易员 {}（签名 {}）",
+        user.user_id,
+        input.strategy.strategy.name,
+        import.trader.id,
+        input.strategy.signer_fingerprint()
+    );
+    let trader = owned_trader(&state, &user, &import.trader.id, locale).await?;
+    Ok((StatusCode::CREATED, Json(trader)))
+}
+
+/// Deletes a stopped trader.
+async fn delete_trader(
+    user: AuthUser,
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    let locale = request_locale(&headers);
+    let trader = owned_trader(&state, &user, &id, locale).await?;
+    if trader.is_running {
+        return Err(ApiError::new(
+            StatusCode::CONFLICT,
+            locale,
+            Msg::TraderRunning,
+        ));
+    }
+    audit_log::before(&trader);
+    state
+        .db
+        .delete_trader(&user.user_id, &id)
+        .await
+        .map_err(|e| internal_error("删除交易员", e, locale))?;
+    tracing::info!("🗑️ 用户 {} 删除交易员 {}", user.user_id, id);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Deserialize)]
+struct DecisionQuery {
+    #[serde(default = "default_decision_limit")]
+    limit: usize,
+}
+
+fn default_decision_limit() -> usize {
+    50
+}
+
+/// The trader's most recent decision records, oldest first.
+async fn list_decisions(
+    user: AuthUser,
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Query(query): Query<DecisionQuery>,
+) -> Result<Json<Vec<DecisionRecord>>, ApiError> {
+    let locale = request_locale(&headers);
+    owned_trader(&state, &user, &id, locale).await?;
+    let key = state.cipher.as_ref().map(|c| c.user_key(&user.user_id));
+    logger::load_records(
+        &state.db.analytics(),
+        &user.user_id,
+        &id,
+        query.limit.clamp(1, 500),
+        key.as_ref(),
+    )
+    .await
+    .map(Json)
+    .map_err(|e| internal_error("获取决策记录", e, locale))
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct ReplayInput {
+    /// Canned model response to replay with instead of calling the model.
+    response: Option<String>,
+}
+
+/// Re-runs a stored decision with the trader's current prompt template and
+/// returns how the decisions differ. Nothing is executed.
+async fn replay_decision(
+    user: AuthUser,
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Path((id, record_id)): Path<(String, String)>,
+    Json(input): Json<ReplayInput>,
+) -> Result<Json<Replay>, ApiError> {
+    let locale = request_locale(&headers);
+    let trader = owned_trader(&state, &user, &id, locale).await?;
+    let key = state.cipher.as_ref().map(|c| c.user_key(&user.user_id));
+    match replay::for_record(
+        &state.db,
+        &user.user_id,
+        &id,
+        &record_id,
+        key.as_ref(),
+        input.response.as_deref(),
+    )
+    .await
+    {
+        Ok(replay) => Ok(Json(replay)),
+        Err(ReplayError::TraderNotFound(_)) => Err(ApiError::new(
+            StatusCode::NOT_FOUND,
+            locale,
+            Msg::TraderNotFound,
+        )),
+        Err(ReplayError::RecordNotFound(_)) => Err(ApiError::new(
+            StatusCode::NOT_FOUND,
+            locale,
+            Msg::DecisionRecordNotFound,
+        )),
+        Err(ReplayError::Ai(e)) => Err(ApiError {
+            status: StatusCode::BAD_GATEWAY,
+            message: i18n::render(
+                locale,
+                Msg::AiCallFailed,
+                &[("name", &trader.name), ("reason", &e.to_string())],
+            ),
+        }),
+        Err(e) => Err(internal_error("重放决策", e, locale)),
+    }
+}
+
+/// The caller's complete data as one JSON document; see [`export`] for the
+/// format.
+async fn export_account(
+    user: AuthUser,
+    headers: HeaderMap,
+    State(state): State<AppState>,
+) -> Result<Json<Takeout>, ApiError> {
+    let locale = request_locale(&headers);
+    let key = state.cipher.as_ref().map(|c| c.user_key(&user.user_id));
+    match export::collect(&state.db.analytics(), &user.user_id, key.as_ref()).await {
+        Ok(takeout) => Ok(Json(takeout)),
+        Err(ExportError::UserNotFound(_)) => Err(ApiError::new(
+            StatusCode::NOT_FOUND,
+            locale,
+            Msg::UserNotFound,
+        )),
+        Err(e) => Err(internal_error("导出账户数据", e, locale)),
+    }
+}
+
+#[derive(Deserialize)]
+struct HistoryQuery {
+    #[serde(default)]
+    format: FileFormat,
+    since: Option<chrono::DateTime<chrono::Utc>>,
+    until: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+// Rows per streamed CSV chunk.
+const HISTORY_CHUNK_ROWS: usize = 500;
+
+/// One of the trader's histories as a file download, limited to
+/// `since`..=`until`. CSV is streamed; Parquet needs the `parquet` feature.
+async fn download_history(
+    user: AuthUser,
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Path((id, dataset)): Path<(String, Dataset)>,
+    Query(query): Query<HistoryQuery>,
+) -> Result<Response, ApiError> {
+    let locale = request_locale(&headers);
+    owned_trader(&state, &user, &id, locale).await?;
+    let key = state.cipher.as_ref().map(|c| c.user_key(&user.user_id));
+    let range = DateRange {
+        since: query.since,
+        until: query.until,
+    };
+    let table = export::history(
+        &state.db.analytics(),
+        &user.user_id,
+        &id,
+        dataset,
+        range,
+        key.as_ref(),
+    )
+    .await
+    .map_err(|e| internal_error("导出历史数据", e, locale))?;
+    let body = match query.format {
+        FileFormat::Csv => Body::from_stream(futures_util::stream::iter(
+            table
+                .into_csv_chunks(HISTORY_CHUNK_ROWS)
+                .map(Ok::<_, std::convert::Infallible>),
+        )),
+        format => match table.encode(format) {
+            Ok(bytes) => Body::from(bytes),
+            Err(ExportError::FormatDisabled(_)) => {
+                return Err(ApiError::new(
+                    StatusCode::BAD_REQUEST,
+                    locale,
+                    Msg::InvalidRequest,
+                ));
+            }
+            Err(e) => return Err(internal_error("导出历史数据", e, locale)),
+        },
+    };
+    let file_name = format!(
+        "{}-{}.{}",
+        id.replace(
+            |c: char| !c.is_ascii_alphanumeric() && c != '-' && c != '_',
+            "_"
+        ),
+        dataset.as_str(),
+        query.format.extension()
+    );
+    Response::builder()
+        .header(header::CONTENT_TYPE, query.format.content_type())
+        .header(
+            header::CONTENT_DISPOSITION,
+            format!("attachment; filename=\"{}\"", file_name),
+        )
+        .body(body)
+        .map_err(|e| internal_error("导出历史数据", e, locale))
+}
+
+#[derive(Deserialize)]
+struct StressQuery {
+    btc_eth_leverage: Option<i32>,
+    altcoin_leverage: Option<i32>,
+}
+
+/// Replays the trader's open positions through crash scenarios, optionally
+/// re-levered to the given leverage.
+async fn stress_test(
+    user: AuthUser,
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Query(query): Query<StressQuery>,
+) -> Result<Json<StressReport>, ApiError> {
+    let locale = request_locale(&headers);
+    owned_trader(&state, &user, &id, locale).await?;
+    let key = state.cipher.as_ref().map(|c| c.user_key(&user.user_id));
+    let leverage = stress::Leverage {
+        btc_eth: query.btc_eth_leverage,
+        altcoin: query.altcoin_leverage,
+    };
+    match stress::for_trader(
+        &state.db.analytics(),
+        &user.user_id,
+        &id,
+        key.as_ref(),
+        leverage,
+    )
+    .await
+    {
+        Ok(report) => Ok(Json(report)),
+        Err(StressError::NoSnapshot(_)) => Err(ApiError::new(
+            StatusCode::NOT_FOUND,
+            locale,
+            Msg::NoAccountSnapshot,
+        )),
+        Err(StressError::InvalidLeverage(_)) => Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            locale,
+            Msg::InvalidRequest,
+        )),
+        Err(e) => Err(internal_error("压力测试", e, locale)),
+    }
+}
+
+/// Returns net of transfers, trade count, volume and fees over the last `days`.
+async fn trader_performance(
+    user: AuthUser,
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Query(query): Query<PerformanceQuery>,
+) -> Result<Json<TraderStats>, ApiError> {
+    let locale = request_locale(&headers);
+    owned_trader(&state, &user, &id, locale).await?;
+    let since = chrono::Utc::now() - chrono::Duration::days(query.days.clamp(1, 365));
+    let db = state.db.analytics();
+    let snapshots = db
+        .get_pnl_snapshots(&user.user_id, &id, Some(since))
+        .await
+        .map_err(|e| internal_error("获取权益快照", e, locale))?;
+    let transfers = db
+        .get_transfers(&user.user_id, &id)
+        .await
+        .map_err(|e| internal_error("获取资金划转记录", e, locale))?;
+    let trades = db
+        .get_trades(&user.user_id, &id, Some(since))
+        .await
+        .map_err(|e| internal_error("获取成交记录", e, locale))?;
+    Ok(Json(performance::trader_stats(
+        &snapshots, &transfers, &trades,
+    )))
+}
+
+#[derive(Deserialize)]
+struct EquityQuery {
+    #[serde(default = "default_performance_days")]
+    days: i64,
+    /// Bucket width; by default the narrowest that keeps the curve short.
+    bucket_minutes: Option<i64>,
+}
+
+/// The trader's equity over the last `days` in time buckets, with the
+/// running drawdown, for charting.
+async fn equity_curve(
+    user: AuthUser,
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Query(query): Query<EquityQuery>,
+) -> Result<Json<EquityCurve>, ApiError> {
+    let locale = request_locale(&headers);
+    owned_trader(&state, &user, &id, locale).await?;
+    let mut range = EquityRange::last_days(query.days.clamp(1, 365));
+    if let Some(minutes) = query.bucket_minutes {
+        range.bucket = chrono::Duration::minutes(minutes.clamp(1, 7 * 24 * 60));
+    }
+    state
+        .db
+        .analytics()
+        .get_equity_curve(&user.user_id, &id, range)
+        .await
+        .map(Json)
+        .map_err(|e| internal_error("获取权益曲线", e, locale))
+}
+
+// Shows only the end of a stored secret so users can tell keys apart.
+fn mask_secret(secret: &str) -> String {
+    // References to an external store are not secret themselves.
+    if secrets::is_reference(secret) {
+        return secret.to_string();
+    }
+    let chars: Vec<char> = secret.chars().collect();
+    match chars.len() {
+        0 => String::new(),
+        n if n <= 8 => "****".to_string(),
+        n => format!("****{}", chars[n - 4..].iter().collect::<String>()),
+    }
+}
+
+async fn list_models(
+    user: AuthUser,
+    headers: HeaderMap,
+    State(state): State<AppState>,
+) -> Result<Json<Vec<AIModelConfig>>, ApiError> {
+    let locale = request_locale(&headers);
+    let models = state
+        .db
+        .get_aimodels(&user.user_id)
+        .await
+        .map_err(|e| internal_error("获取AI模型配置", e, locale))?;
+    Ok(Json(
+        models
+            .into_iter()
+            .map(|m| AIModelConfig {
+                api_key: mask_secret(&m.api_key),
+                ..m
+            })
+            .collect(),
+    ))
+}
+
+/// AI model settings; secrets left out keep their stored value.
+#[derive(Deserialize)]
+struct ModelInput {
+    enabled: bool,
+    #[serde(default)]
+    api_key: Option<String>,
+    #[serde(default)]
+    custom_api_url: Option<String>,
+    #[serde(default)]
+    custom_model_name: Option<String>,
+}
+
+async fn update_model(
+    user: AuthUser,
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(body): Json<ModelInput>,
+) -> Result<Json<Vec<AIModelConfig>>, ApiError> {
+    let locale = request_locale(&headers);
+    let current = state
+        .db
+        .get_aimodels(&user.user_id)
+        .await
+        .map_err(|e| internal_error("获取AI模型配置", e, locale))?
+        .into_iter()
+        .find(|m| m.id == id)
+        .unwrap_or_default();
+    audit_log::before(&current);
+    state
+        .db
+        .update_aimodel(
+            &user.user_id,
+            &id,
+            body.enabled,
+            &body.api_key.unwrap_or(current.api_key),
+            &body.custom_api_url.unwrap_or(current.custom_api_url),
+            &body.custom_model_name.unwrap_or(current.custom_model_name),
+        )
+        .await
+        .map_err(|e| internal_error("更新AI模型配置", e, locale))?;
+    list_models(user, headers, State(state)).await
+}
+
+async fn list_exchanges(
+    user: AuthUser,
+    headers: HeaderMap,
+    State(state): State<AppState>,
+) -> Result<Json<Vec<ExchangeConfig>>, ApiError> {
+    let locale = request_locale(&headers);
+    let exchanges = state
+        .db
+        .get_exchanges(&user.user_id)
+        .await
+        .map_err(|e| internal_error("获取交易所配置", e, locale))?;
+    Ok(Json(
+        exchanges
+            .into_iter()
+            .map(|e| ExchangeConfig {
+                api_key: mask_secret(&e.api_key),
+                secret_key: mask_secret(&e.secret_key),
+                aster_private_key: mask_secret(&e.aster_private_key),
+                ..e
+            })
+            .collect(),
+    ))
+}
+
+/// Exchange settings; fields left out keep their stored value.
+#[derive(Deserialize)]
+struct ExchangeInput {
+    enabled: bool,
+    #[serde(default)]
+    testnet: Option<bool>,
+    #[serde(default)]
+    api_key: Option<String>,
+    #[serde(default)]
+    secret_key: Option<String>,
+    #[serde(default)]
+    hyperliquid_wallet_addr: Option<String>,
+    #[serde(default)]
+    aster_user: Option<String>,
+    #[serde(default)]
+    aster_signer: Option<String>,
+    #[serde(default)]
+    aster_private_key: Option<String>,
+}
+
+async fn update_exchange(
+    user: AuthUser,
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(body): Json<ExchangeInput>,
+) -> Result<Json<Vec<ExchangeConfig>>, ApiError> {
+    let locale = request_locale(&headers);
+    let current = state
+        .db
+        .get_exchanges(&user.user_id)
+        .await
+        .map_err(|e| internal_error("获取交易所配置", e, locale))?
+        .into_iter()
+        .find(|e| e.id == id)
+        .unwrap_or_default();
+    audit_log::before(&current);
+    state
+        .db
+        .update_exchange(
+            &user.user_id,
+            &id,
+            body.enabled,
+            &body.api_key.unwrap_or(current.api_key),
+            &body.secret_key.unwrap_or(current.secret_key),
+            body.testnet.unwrap_or(current.testnet),
+            &body
+                .hyperliquid_wallet_addr
+                .unwrap_or(current.hyperliquid_wallet_addr),
+            &body.aster_user.unwrap_or(current.aster_user),
+            &body.aster_signer.unwrap_or(current.aster_signer),
+            &body.aster_private_key.unwrap_or(current.aster_private_key),
+        )
+        .await
+        .map_err(|e| internal_error("更新交易所配置", e, locale))?;
+    list_exchanges(user, headers, State(state)).await
+}
+
+/// The caller's most recent model tournament standings, `null` before the first one.
+async fn latest_tournament(
+    user: AuthUser,
+    headers: HeaderMap,
+    State(state): State<AppState>,
+) -> Result<Json<Option<Report>>, ApiError> {
+    let locale = request_locale(&headers);
+    tournament::latest_report(&state.db.analytics(), &user.user_id)
+        .await
+        .map(Json)
+        .map_err(|e| {
+            tracing::error!("❌ 获取模型评测结果失败: {}", e);
+            ApiError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                locale,
+                Msg::InternalError,
+            )
+        })
+}
+
+/// Hit rates of the caller's AI directional calls over the last `days`, per
+/// model and per symbol.
+async fn decision_accuracy(
+    user: AuthUser,
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Query(query): Query<AccuracyQuery>,
+) -> Result<Json<AccuracyStats>, ApiError> {
+    let locale = request_locale(&headers);
+    let since = chrono::Utc::now() - chrono::Duration::days(query.days.clamp(1, 365));
+    accuracy::stats(
+        &state.db.analytics(),
+        &user.user_id,
+        query.model.as_deref(),
+        since,
+    )
+    .await
+    .map(Json)
+    .map_err(|e| internal_error("获取方向判断命中率", e, locale))
+}
+
+#[derive(Deserialize)]
+struct AccuracyQuery {
+    #[serde(default = "default_accuracy_days")]
+    days: i64,
+    model: Option<String>,
+}
+
+fn default_accuracy_days() -> i64 {
+    7
+}
+
+/// Checks the caller's saved keys for an exchange with a harmless signed call.
+async fn validate_exchange(
+    user: AuthUser,
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<CredentialCheck>, ApiError> {
+    let locale = request_locale(&headers);
+    let exchanges = state.db.get_exchanges(&user.user_id).await.map_err(|e| {
+        tracing::error!("❌ 获取交易所配置失败: {}", e);
+        ApiError::new(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            locale,
+            Msg::InternalError,
+        )
+    })?;
+    let exchange = exchanges
+        .iter()
+        .find(|e| e.id == id)
+        .ok_or_else(|| ApiError::new(StatusCode::NOT_FOUND, locale, Msg::ExchangeNotFound))?;
+    Ok(Json(exchange::validate_credentials(exchange).await))
+}
+
+/// Trader config, transfers and pause windows, read from one consistent snapshot.
+async fn trader_dashboard(
+    user: AuthUser,
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<TraderSnapshot>, ApiError> {
+    let locale = request_locale(&headers);
+    match state
+        .db
+        .analytics()
+        .get_trader_snapshot(&user.user_id, &id)
+        .await
+    {
+        Ok(Some(snapshot)) => Ok(Json(snapshot)),
+        Ok(None) => Err(ApiError::new(
+            StatusCode::NOT_FOUND,
+            locale,
+            Msg::TraderNotFound,
+        )),
+        Err(e) => {
+            tracing::error!("❌ 获取交易员看板数据失败: {}", e);
+            Err(ApiError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                locale,
+                Msg::InternalError,
+            ))
+        }
+    }
+}
+
+async fn list_transfers(
+    user: AuthUser,
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<Vec<AccountTransfer>>, ApiError> {
+    let locale = request_locale(&headers);
+    owned_trader(&state, &user, &id, locale).await?;
+    state
+        .db
+        .get_transfers(&user.user_id, &id)
+        .await
+        .map(Json)
+        .map_err(|e| {
+            tracing::error!("❌ 获取资金划转记录失败: {}", e);
+            ApiError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                locale,
+                Msg::InternalError,
+            )
+        })
+}
+
+/// A manually entered deposit (positive amount) or withdrawal (negative).
+#[derive(Deserialize)]
+struct NewTransfer {
+    amount: Decimal,
+    #[serde(default)]
+    asset: Option<String>,
+    #[serde(default)]
+    occurred_at: Option<chrono::DateTime<chrono::Utc>>,
+    #[serde(default)]
+    note: String,
+}
+
+async fn add_transfer(
+    user: AuthUser,
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(body): Json<NewTransfer>,
+) -> Result<(StatusCode, Json<Value>), ApiError> {
+    let locale = request_locale(&headers);
+    if body.amount.is_zero() {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            locale,
+            Msg::InvalidRequest,
+        ));
+    }
+    owned_trader(&state, &user, &id, locale).await?;
+
+    let transfer = AccountTransfer {
+        user_id: user.user_id.clone(),
+        trader_id: id,
+        amount: body.amount,
+        asset: body
+            .asset
+            .unwrap_or_else(|| crate::symbols::DEFAULT_QUOTE.to_string()),
+        occurred_at: body.occurred_at.unwrap_or_else(chrono::Utc::now),
+        source: "manual".to_string(),
+        note: body.note,
+        ..Default::default()
+    };
+    // 其他资产按当前汇率折算，否则收益计算无法扣除
+    let transfer = performance::in_equity_asset(transfer)
+        .await
+        .map_err(|_| ApiError::new(StatusCode::BAD_REQUEST, locale, Msg::InvalidRequest))?;
+    state.db.record_transfer(&transfer).await.map_err(|e| {
+        tracing::error!("❌ 记录资金划转失败: {}", e);
+        ApiError::new(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            locale,
+            Msg::InternalError,
+        )
+    })?;
+    Ok((StatusCode::CREATED, Json(json!({ "message": "ok" }))))
+}
+
+#[derive(Deserialize)]
+struct AuditQuery {
+    #[serde(default = "default_audit_limit")]
+    limit: i64,
+}
+
+fn default_audit_limit() -> i64 {
+    100
+}
+
+async fn execution_audit(
+    user: AuthUser,
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Query(query): Query<AuditQuery>,
+) -> Result<Json<Vec<ExecutionAudit>>, ApiError> {
+    let locale = request_locale(&headers);
+    owned_trader(&state, &user, &id, locale).await?;
+    state
+        .db
+        .analytics()
+        .get_execution_audit(&user.user_id, &id, query.limit.clamp(1, 1000))
+        .await
+        .map(Json)
+        .map_err(|e| {
+            tracing::error!("❌ 获取下单审计记录失败: {}", e);
+            ApiError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                locale,
+                Msg::InternalError,
+            )
+        })
+}
+
+async fn daily_reports(
+    user: AuthUser,
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Query(query): Query<AuditQuery>,
+) -> Result<Json<Vec<DailyReport>>, ApiError> {
+    let locale = request_locale(&headers);
+    owned_trader(&state, &user, &id, locale).await?;
+    state
+        .db
+        .analytics()
+        .get_daily_reports(&user.user_id, &id, query.limit.clamp(1, 1000))
+        .await
+        .map(Json)
+        .map_err(|e| {
+            tracing::error!("❌ 获取每日报告失败: {}", e);
+            ApiError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                locale,
+                Msg::InternalError,
+            )
+        })
+}
+
+async fn reconciliation_events(
+    user: AuthUser,
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Query(query): Query<AuditQuery>,
+) -> Result<Json<Vec<ReconciliationEvent>>, ApiError> {
+    let locale = request_locale(&headers);
+    owned_trader(&state, &user, &id, locale).await?;
+    state
+        .db
+        .analytics()
+        .get_reconciliation_events(&user.user_id, &id, query.limit.clamp(1, 1000))
+        .await
+        .map(Json)
+        .map_err(|e| {
+            tracing::error!("❌ 获取持仓对账记录失败: {}", e);
+            ApiError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                locale,
+                Msg::InternalError,
+            )
+        })
+}
+
+#[derive(Deserialize)]
+struct PerformanceQuery {
+    #[serde(default = "default_performance_days")]
+    days: i64,
+}
+
+fn default_performance_days() -> i64 {
+    30
+}
+
+/// Slippage and fill latency of the trader's fills over the last `days`, per
+/// symbol and per hour of day in the caller's timezone.
+async fn execution_quality(
+    user: AuthUser,
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Query(query): Query<PerformanceQuery>,
+) -> Result<Json<ExecutionQuality>, ApiError> {
+    let locale = request_locale(&headers);
+    owned_trader(&state, &user, &id, locale).await?;
+    let since = chrono::Utc::now() - chrono::Duration::days(query.days.clamp(1, 365));
+    let trades = state
+        .db
+        .analytics()
+        .get_trades(&user.user_id, &id, Some(since))
+        .await
+        .map_err(|e| {
+            tracing::error!("❌ 获取成交记录失败: {}", e);
+            ApiError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                locale,
+                Msg::InternalError,
+            )
+        })?;
+    let tz = match state.db.get_user_by_id(&user.user_id).await {
+        Ok(Some(u)) => u.tz(),
+        _ => chrono_tz::Tz::UTC,
+    };
+    Ok(Json(performance::execution_quality(&trades, tz)))
+}
+
+#[derive(Serialize)]
+struct MaintenanceStatus {
+    active: bool,
+    #[serde(flatten)]
+    maintenance: Option<Maintenance>,
+}
+
+/// Whether the API is read-only for maintenance; readable without logging in.
+async fn maintenance_status() -> Json<MaintenanceStatus> {
+    let maintenance = maintenance::current();
+    Json(MaintenanceStatus {
+        active: maintenance.is_some(),
+        maintenance,
+    })
+}
+
+#[derive(Deserialize)]
+struct EnableMaintenance {
+    #[serde(default)]
+    reason: String,
+    /// Resume automatically after this long, e.g. "30m".
+    #[serde(default, with = "humantime_serde")]
+    duration: Option<std::time::Duration>,
+}
+
+async fn enable_maintenance(
+    user: AuthUser,
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Json(body): Json<EnableMaintenance>,
+) -> Result<Json<MaintenanceStatus>, ApiError> {
+    let locale = request_locale(&headers);
+    require_admin(&user, locale)?;
+    let reason = if body.reason.is_empty() {
+        "maintenance"
+    } else {
+        body.reason.as_str()
+    };
+    let maintenance = maintenance::enable(&state.db, reason, body.duration)
+        .await
+        .map_err(|e| maintenance_error(e, locale))?;
+    Ok(Json(MaintenanceStatus {
+        active: true,
+        maintenance: Some(maintenance),
+    }))
+}
+
+async fn resume_maintenance(
+    user: AuthUser,
+    headers: HeaderMap,
+    State(state): State<AppState>,
+) -> Result<Json<MaintenanceStatus>, ApiError> {
+    let locale = request_locale(&headers);
+    require_admin(&user, locale)?;
+    maintenance::resume(&state.db)
+        .await
+        .map_err(|e| maintenance_error(e, locale))?;
+    Ok(Json(MaintenanceStatus {
+        active: false,
+        maintenance: None,
+    }))
+}
+
+fn maintenance_error(e: MaintenanceError, locale: Locale) -> ApiError {
+    match e {
+        MaintenanceError::InvalidDuration => {
+            ApiError::new(StatusCode::BAD_REQUEST, locale, Msg::InvalidRequest)
+        }
+        MaintenanceError::Database(e) => {
+            tracing::error!("❌ 切换维护模式失败: {}", e);
+            ApiError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                locale,
+                Msg::InternalError,
+            )
+        }
+    }
+}
+
+/// Directives from the external risk system, authenticated by signature
+/// rather than a user token.
+async fn risk_directives(
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    body: Bytes,
+) -> Result<Json<Overrides>, ApiError> {
+    let locale = request_locale(&headers);
+    let header_value = |name: &str| {
+        headers
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default()
+    };
+    risk_override::verify_signature(
+        header_value("x-risk-timestamp"),
+        &body,
+        header_value("x-risk-signature"),
+        chrono::Utc::now(),
+    )
+    .map_err(|e| risk_override_error(e, locale))?;
+    let batch: DirectiveBatch = serde_json::from_slice(&body)
+        .map_err(|_| ApiError::new(StatusCode::BAD_REQUEST, locale, Msg::InvalidRequest))?;
+    let overrides = risk_override::apply(&state.db, &batch.directives)
+        .await
+        .map_err(|e| risk_override_error(e, locale))?;
+    Ok(Json(overrides))
+}
+
+/// Risk overrides currently applied to the caller's traders.
+async fn risk_overrides(user: AuthUser) -> Json<UserOverride> {
+    Json(risk_override::for_user(&user.user_id))
+}
+
+fn risk_override_error(e: RiskOverrideError, locale: Locale) -> ApiError {
+    match e {
+        RiskOverrideError::Disabled => {
+            ApiError::new(StatusCode::NOT_FOUND, locale, Msg::InvalidRequest)
+        }
+        RiskOverrideError::InvalidSignature
+        | RiskOverrideError::StaleTimestamp
+        | RiskOverrideError::Replayed => {
+            tracing::warn!("⚠️ 拒绝外部风控请求: {}", e);
+            ApiError::new(StatusCode::UNAUTHORIZED, locale, Msg::Unauthorized)
+        }
+        // The caller is a machine; tell it exactly what was wrong.
+        RiskOverrideError::InvalidDirective(_) => ApiError {
+            status: StatusCode::BAD_REQUEST,
+            message: e.to_string(),
+        },
+        RiskOverrideError::Database(e) => internal_error("应用外部风控指令", e, locale),
+    }
+}
+
+// Fails with 403 unless the caller has the admin role.
+fn require_admin(user: &AuthUser, locale: Locale) -> Result<(), ApiError> {
+    if user.is_admin() {
+        Ok(())
+    } else {
+        Err(ApiError::new(StatusCode::FORBIDDEN, locale, Msg::Forbidden))
+    }
+}
+
+/// Every registered user.
+async fn list_users(
+    user: AuthUser,
+    headers: HeaderMap,
+    State(state): State<AppState>,
+) -> Result<Json<Vec<User>>, ApiError> {
+    let locale = request_locale(&headers);
+    require_admin(&user, locale)?;
+    state
+        .db
+        .analytics()
+        .get_all_users()
+        .await
+        .map(Json)
+        .map_err(|e| internal_error("获取用户列表", e, locale))
+}
+
+#[derive(Deserialize)]
+struct RoleChange {
+    role: Role,
+}
+
+/// Grants or revokes the admin role. Admins cannot demote themselves, so
+/// there is always one left.
+async fn update_user_role(
+    user: AuthUser,
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Path(user_id): Path<String>,
+    Json(body): Json<RoleChange>,
+) -> Result<Json<User>, ApiError> {
+    let locale = request_locale(&headers);
+    require_admin(&user, locale)?;
+    if user_id == user.user_id && body.role != Role::Admin {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            locale,
+            Msg::InvalidRequest,
+        ));
+    }
+    match state.db.get_user_by_id(&user_id).await {
+        Ok(Some(existing)) => audit_log::before(&existing),
+        Ok(None) => {
+            return Err(ApiError::new(
+                StatusCode::NOT_FOUND,
+                locale,
+                Msg::UserNotFound,
+            ));
+        }
+        Err(e) => return Err(internal_error("获取用户", e, locale)),
+    }
+    state
+        .db
+        .update_user_role(&user_id, body.role)
+        .await
+        .map_err(|e| internal_error("更新用户角色", e, locale))?;
+    tracing::info!(
+        "👮 用户 {} 的角色已由 {} 改为 {}",
+        user_id,
+        acting_user(&user),
+        body.role.as_str()
+    );
+    match state.db.get_user_by_id(&user_id).await {
+        Ok(Some(updated)) => Ok(Json(updated)),
+        Ok(None) => Err(ApiError::new(
+            StatusCode::NOT_FOUND,
+            locale,
+            Msg::UserNotFound,
+        )),
+        Err(e) => Err(internal_error("获取用户", e, locale)),
+    }
+}
+
+#[derive(Serialize)]
+struct BetaCodeStats {
+    total: i64,
+    used: i64,
+}
+
+async fn beta_code_stats(
+    user: AuthUser,
+    headers: HeaderMap,
+    State(state): State<AppState>,
+) -> Result<Json<BetaCodeStats>, ApiError> {
+    let locale = request_locale(&headers);
+    require_admin(&user, locale)?;
+    let (total, used) = state
+        .db
+        .get_beta_code_stats()
+        .await
+        .map_err(|e| internal_error("获取内测码统计", e, locale))?;
+    Ok(Json(BetaCodeStats { total, used }))
+}
+
+#[derive(Deserialize)]
+struct NewBetaCodes {
+    codes: Vec<String>,
+}
+
+/// Adds beta codes; codes that already exist are left alone.
+async fn add_beta_codes(
+    user: AuthUser,
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Json(body): Json<NewBetaCodes>,
+) -> Result<Json<Value>, ApiError> {
+    let locale = request_locale(&headers);
+    require_admin(&user, locale)?;
+    let codes: Vec<String> = body
+        .codes
+        .iter()
+        .map(|c| c.trim().to_string())
+        .filter(|c| !c.is_empty())
+        .collect();
+    let inserted = state
+        .db
+        .insert_beta_codes(&codes)
+        .await
+        .map_err(|e| internal_error("写入内测码", e, locale))?;
+    Ok(Json(json!({ "inserted": inserted })))
+}
+
+// system_config keys the API must never overwrite.
+const PROTECTED_CONFIG_KEYS: &[&str] = &["jwt_secret"];
+
+#[derive(Deserialize)]
+struct ConfigValue {
+    value: String,
+}
+
+async fn put_system_config(
+    user: AuthUser,
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Path(key): Path<String>,
+    Json(body): Json<ConfigValue>,
+) -> Result<StatusCode, ApiError> {
+    let locale = request_locale(&headers);
+    require_admin(&user, locale)?;
+    if key.trim().is_empty() || PROTECTED_CONFIG_KEYS.contains(&key.as_str()) {
+        return Err(ApiError::new(StatusCode::FORBIDDEN, locale, Msg::Forbidden));
+    }
+    if let Ok(value) = state.db.get_system_config(&key).await {
+        audit_log::before(&json!({ "value": value }));
+    }
+    state
+        .db
+        .set_system_config(&key, &body.value)
+        .await
+        .map_err(|e| internal_error("写入系统配置", e, locale))?;
+    tracing::info!("⚙️ 系统配置 {} 已由 {} 修改", key, acting_user(&user));
+    Ok(StatusCode::NO_CONTENT)
+}
+
+fn quota_error(e: QuotaError, locale: Locale) -> ApiError {
+    tracing::error!("❌ 读写配额失败: {}", e);
+    ApiError::new(
+        StatusCode::INTERNAL_SERVER_ERROR,
+        locale,
+        Msg::InternalError,
+    )
+}
+
+/// The quota in effect for a user, their override and this month's AI spend.
+#[derive(Debug, Serialize)]
+pub struct UserQuota {
+    pub quota: Quota,
+    #[serde(rename = "override")]
+    pub user_override: Option<Quota>,
+    pub ai_spend_usd: f64,
+}
+
+async fn user_quota(db: &Database, user_id: &str) -> Result<UserQuota, QuotaError> {
+    Ok(UserQuota {
+        quota: quota::for_user(db, user_id).await?,
+        user_override: quota::user_override(db, user_id).await?,
+        ai_spend_usd: quota::monthly_ai_spend(db, user_id, chrono::Utc::now()).await?,
+    })
+}
+
+#[derive(Deserialize)]
+struct AuditLogQuery {
+    #[serde(default = "default_audit_limit")]
+    limit: i64,
+    /// Only changes made by this user.
+    #[serde(default)]
+    user_id: String,
+    /// Only changes to targets starting with this, e.g. "traders/t1".
+    #[serde(default)]
+    target: String,
+}
+
+/// Recent configuration and trading-state changes, newest first.
+async fn audit_log_entries(
+    user: AuthUser,
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Query(query): Query<AuditLogQuery>,
+) -> Result<Json<Vec<AuditLogEntry>>, ApiError> {
+    let locale = request_locale(&headers);
+    require_admin(&user, locale)?;
+    state
+        .db
+        .analytics()
+        .get_audit_log(&query.user_id, &query.target, query.limit.clamp(1, 1000))
+        .await
+        .map(Json)
+        .map_err(|e| internal_error("获取变更审计记录", e, locale))
+}
+
+/// Recent account and IP lockouts after failed logins or two-factor codes.
+async fn auth_lockouts(
+    user: AuthUser,
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Query(query): Query<AuditQuery>,
+) -> Result<Json<Vec<AuthLockout>>, ApiError> {
+    let locale = request_locale(&headers);
+    require_admin(&user, locale)?;
+    state
+        .db
+        .get_auth_lockouts(query.limit.clamp(1, 1000))
+        .await
+        .map(Json)
+        .map_err(|e| internal_error("获取登录锁定记录", e, locale))
+}
+
+async fn get_default_quota(
+    user: AuthUser,
+    headers: HeaderMap,
+    State(state): State<AppState>,
+) -> Result<Json<Quota>, ApiError> {
+    let locale = request_locale(&headers);
+    require_admin(&user, locale)?;
+    quota::defaults(&state.db)
+        .await
+        .map(Json)
+        .map_err(|e| quota_error(e, locale))
+}
+
+async fn put_default_quota(
+    user: AuthUser,
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Json(new): Json<Quota>,
+) -> Result<Json<Quota>, ApiError> {
+    let locale = request_locale(&headers);
+    require_admin(&user, locale)?;
+    if let Ok(current) = quota::defaults(&state.db).await {
+        audit_log::before(&current);
+    }
+    quota::set_defaults(&state.db, &new)
+        .await
+        .map_err(|e| quota_error(e, locale))?;
+    Ok(Json(new))
+}
+
+async fn get_user_quota(
+    user: AuthUser,
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Path(user_id): Path<String>,
+) -> Result<Json<UserQuota>, ApiError> {
+    let locale = request_locale(&headers);
+    require_admin(&user, locale)?;
+    user_quota(&state.db, &user_id)
+        .await
+        .map(Json)
+        .map_err(|e| quota_error(e, locale))
+}
+
+async fn put_user_quota(
+    user: AuthUser,
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Path(user_id): Path<String>,
+    Json(new): Json<Quota>,
+) -> Result<Json<UserQuota>, ApiError> {
+    let locale = request_locale(&headers);
+    require_admin(&user, locale)?;
+    if let Ok(current) = quota::user_override(&state.db, &user_id).await {
+        audit_log::before(&current);
+    }
+    quota::set_user_override(&state.db, &user_id, Some(&new))
+        .await
+        .map_err(|e| quota_error(e, locale))?;
+    user_quota(&state.db, &user_id)
+        .await
+        .map(Json)
+        .map_err(|e| quota_error(e, locale))
+}
+
+/// Removes a user's override so the defaults apply again.
+async fn delete_user_quota(
+    user: AuthUser,
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Path(user_id): Path<String>,
+) -> Result<Json<UserQuota>, ApiError> {
+    let locale = request_locale(&headers);
+    require_admin(&user, locale)?;
+    if let Ok(current) = quota::user_override(&state.db, &user_id).await {
+        audit_log::before(&current);
+    }
+    quota::set_user_override(&state.db, &user_id, None)
+        .await
+        .map_err(|e| quota_error(e, locale))?;
+    user_quota(&state.db, &user_id)
+        .await
+        .map(Json)
+        .map_err(|e| quota_error(e, locale))
+}
+
+async fn list_pauses(
+    user: AuthUser,
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<Vec<PauseWindow>>, ApiError> {
+    let locale = request_locale(&headers);
+    owned_trader(&state, &user, &id, locale).await?;
+    state
+        .db
+        .get_pause_windows(&user.user_id, &id)
+        .await
+        .map(Json)
+        .map_err(|e| pause_error(PauseError::Database(e), locale))
+}
+
+#[derive(Deserialize)]
+struct NewPause {
+    /// Defaults to now.
+    #[serde(default)]
+    pause_at: Option<chrono::DateTime<chrono::Utc>>,
+    #[serde(default)]
+    resume_at: Option<chrono::DateTime<chrono::Utc>>,
+    #[serde(default)]
+    reason: String,
+}
+
+async fn schedule_pause(
+    user: AuthUser,
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(body): Json<NewPause>,
+) -> Result<(StatusCode, Json<PauseWindow>), ApiError> {
+    let locale = request_locale(&headers);
+    owned_trader(&state, &user, &id, locale).await?;
+    let window = pause::schedule(
+        &state.db,
+        &user.user_id,
+        &id,
+        body.pause_at.unwrap_or_else(chrono::Utc::now),
+        body.resume_at,
+        &body.reason,
+    )
+    .await
+    .map_err(|e| pause_error(e, locale))?;
+    Ok((StatusCode::CREATED, Json(window)))
+}
+
+async fn cancel_pause(
+    user: AuthUser,
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Path((id, pause_id)): Path<(String, i64)>,
+) -> Result<Json<PauseWindow>, ApiError> {
+    let locale = request_locale(&headers);
+    owned_trader(&state, &user, &id, locale).await?;
+    match state.db.get_pause_window(&user.user_id, pause_id).await {
+        Ok(Some(w)) if w.trader_id == id => {}
+        Ok(_) => return Err(pause_error(PauseError::NotFound(pause_id), locale)),
+        Err(e) => return Err(pause_error(PauseError::Database(e), locale)),
+    }
+    pause::cancel(&state.db, &user.user_id, pause_id)
+        .await
+        .map(Json)
+        .map_err(|e| pause_error(e, locale))
+}
+
+fn pause_error(e: PauseError, locale: Locale) -> ApiError {
+    match e {
+        PauseError::InvalidWindow => {
+            ApiError::new(StatusCode::BAD_REQUEST, locale, Msg::InvalidRequest)
+        }
+        PauseError::NotFound(_) => {
+            ApiError::new(StatusCode::NOT_FOUND, locale, Msg::PauseWindowNotFound)
+        }
+        PauseError::AlreadyFinished(..) => {
+            ApiError::new(StatusCode::CONFLICT, locale, Msg::InvalidRequest)
+        }
+        PauseError::Database(e) => {
+            tracing::error!("❌ 暂停窗口操作失败: {}", e);
+            ApiError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                locale,
+                Msg::InternalError,
+            )
+        }
+    }
+}
+
+// Prompt versions and proposal decisions are attributed to the caller's
+// email, or id without one.
+fn acting_user(user: &AuthUser) -> &str {
+    if user.email.is_empty() {
+        &user.user_id
+    } else {
+        &user.email
+    }
+}
+
+async fn update_prompt(
+    user: AuthUser,
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(change): Json<PromptChange>,
+) -> Result<Json<PromptVersion>, ApiError> {
+    let locale = request_locale(&headers);
+    let trader = owned_trader(&state, &user, &id, locale).await?;
+    audit_log::before(&json!({
+        "custom_prompt": trader.custom_prompt,
+        "override_base_prompt": trader.override_base_prompt,
+        "system_prompt_template": trader.system_prompt_template,
+    }));
+    prompt_history::update(&state.db, &user.user_id, &id, &change, acting_user(&user))
+        .await
+        .map(Json)
+        .map_err(|e| prompt_history_error(e, locale))
+}
+
+async fn list_prompt_versions(
+    user: AuthUser,
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<Vec<PromptVersion>>, ApiError> {
+    let locale = request_locale(&headers);
+    owned_trader(&state, &user, &id, locale).await?;
+    state
+        .db
+        .get_prompt_versions(&user.user_id, &id)
+        .await
+        .map(Json)
+        .map_err(|e| prompt_history_error(PromptHistoryError::Database(e), locale))
+}
+
+async fn get_prompt_version(
+    user: AuthUser,
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Path((id, version)): Path<(String, i64)>,
+) -> Result<Json<PromptVersion>, ApiError> {
+    let locale = request_locale(&headers);
+    owned_trader(&state, &user, &id, locale).await?;
+    match state
+        .db
+        .get_prompt_version(&user.user_id, &id, version)
+        .await
+    {
+        Ok(Some(v)) => Ok(Json(v)),
+        Ok(None) => Err(prompt_history_error(
+            PromptHistoryError::VersionNotFound(version),
+            locale,
+        )),
+        Err(e) => Err(prompt_history_error(
+            PromptHistoryError::Database(e),
+            locale,
+        )),
+    }
+}
+
+async fn rollback_prompt(
+    user: AuthUser,
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Path((id, version)): Path<(String, i64)>,
+) -> Result<Json<PromptVersion>, ApiError> {
+    let locale = request_locale(&headers);
+    owned_trader(&state, &user, &id, locale).await?;
+    prompt_history::rollback(&state.db, &user.user_id, &id, version, acting_user(&user))
+        .await
+        .map(Json)
+        .map_err(|e| prompt_history_error(e, locale))
+}
+
+#[derive(Debug, Deserialize)]
+struct VariantInput {
+    variant: String,
+    /// Defaults to the source trader's name followed by the variant.
+    name: Option<String>,
+    /// The variant's prompt; at least one field must be set.
+    #[serde(flatten)]
+    prompt: PromptChange,
+}
+
+/// Clones the trader with a different prompt as a new variant of its A/B
+/// experiment, starting one with the trader as control if needed. The clone
+/// is created stopped.
+async fn create_variant(
+    user: AuthUser,
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(input): Json<VariantInput>,
+) -> Result<(StatusCode, Json<TraderRecord>), ApiError> {
+    let locale = request_locale(&headers);
+    let mut source = owned_trader(&state, &user, &id, locale).await?;
+    let prompt = input.prompt;
+    let changes_prompt = prompt.custom_prompt.is_some()
+        || prompt.override_base_prompt.is_some()
+        || prompt.system_prompt_template.is_some();
+    if !experiment::is_valid_variant(&input.variant)
+        || input.variant == experiment::CONTROL_VARIANT
+        || !changes_prompt
+    {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            locale,
+            Msg::InvalidRequest,
+        ));
+    }
+    let members = experiment::members(&state.db, &user.user_id, &source.experiment_id)
+        .await
+        .map_err(|e| internal_error("获取实验交易员", e, locale))?;
+    if members.iter().any(|t| t.variant == input.variant) {
+        return Err(ApiError::new(
+            StatusCode::CONFLICT,
+            locale,
+            Msg::VariantExists,
+        ));
+    }
+
+    let mut variant = experiment::clone_variant(&source, &input.variant);
+    if let Some(name) = input.name {
+        variant.name = name;
+    }
+    if let Some(custom_prompt) = prompt.custom_prompt {
+        variant.custom_prompt = custom_prompt;
+    }
+    if let Some(override_base) = prompt.override_base_prompt {
+        variant.override_base_prompt = override_base;
+    }
+    if let Some(template) = prompt.system_prompt_template {
+        variant.system_prompt_template = template;
+    }
+    validate_trader(&state, &variant, locale).await?;
+    quota::check_create(&state.db, &variant)
+        .await
+        .map_err(|e| trader_quota_error(e, locale))?;
+
+    if experiment::enroll(&mut source) {
+        state
+            .db
+            .update_trader(&source)
+            .await
+            .map_err(|e| internal_error("更新交易员", e, locale))?;
+    }
+    state
+        .db
+        .create_trader(&variant)
+        .await
+        .map_err(|e| internal_error("创建交易员", e, locale))?;
+    tracing::info!(
+        "🧪 用户 {} 为实验 {} 创建变体 {} ({})",
+        user.user_id,
+        variant.experiment_id,
+        variant.variant,
+        variant.id
+    );
+    let variant = owned_trader(&state, &user, &variant.id, locale).await?;
+    Ok((StatusCode::CREATED, Json(variant)))
+}
+
+/// Win rate, PnL and Sharpe ratio of every variant in the trader's
+/// experiment; empty if it is not part of one.
+async fn variant_report(
+    user: AuthUser,
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<Vec<VariantReport>>, ApiError> {
+    let locale = request_locale(&headers);
+    let trader = owned_trader(&state, &user, &id, locale).await?;
+    let key = state.cipher.as_ref().map(|c| c.user_key(&user.user_id));
+    experiment::compare(
+        &state.db.analytics(),
+        &user.user_id,
+        &trader.experiment_id,
+        key.as_ref(),
+    )
+    .await
+    .map(Json)
+    .map_err(|e| internal_error("生成变体对比报告", e, locale))
+}
+
+#[derive(Debug, Deserialize)]
+struct ProposalQuery {
+    /// e.g. "pending"; empty lists every proposal.
+    #[serde(default)]
+    status: String,
+}
+
+async fn list_proposals(
+    user: AuthUser,
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Query(query): Query<ProposalQuery>,
+) -> Result<Json<Vec<TradeProposal>>, ApiError> {
+    let locale = request_locale(&headers);
+    owned_trader(&state, &user, &id, locale).await?;
+    state
+        .db
+        .get_trade_proposals(&user.user_id, &id, &query.status)
+        .await
+        .map(Json)
+        .map_err(|e| internal_error("获取交易提案", e, locale))
+}
+
+async fn approve_proposal(
+    user: AuthUser,
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Path((id, proposal_id)): Path<(String, String)>,
+) -> Result<Json<TradeProposal>, ApiError> {
+    let locale = request_locale(&headers);
+    owned_trader(&state, &user, &id, locale).await?;
+    approval::approve(
+        &state.db,
+        &user.user_id,
+        &id,
+        &proposal_id,
+        acting_user(&user),
+    )
+    .await
+    .map(Json)
+    .map_err(|e| approval_error(e, locale))
+}
+
+async fn reject_proposal(
+    user: AuthUser,
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Path((id, proposal_id)): Path<(String, String)>,
+) -> Result<Json<TradeProposal>, ApiError> {
+    let locale = request_locale(&headers);
+    owned_trader(&state, &user, &id, locale).await?;
+    approval::reject(
+        &state.db,
+        &user.user_id,
+        &id,
+        &proposal_id,
+        acting_user(&user),
+    )
+    .await
+    .map(Json)
+    .map_err(|e| approval_error(e, locale))
+}
+
+fn approval_error(e: ApprovalError, locale: Locale) -> ApiError {
+    match e {
+        ApprovalError::NotFound(_) => {
+            ApiError::new(StatusCode::NOT_FOUND, locale, Msg::ProposalNotFound)
+        }
+        ApprovalError::NotPending(_) => {
+            ApiError::new(StatusCode::CONFLICT, locale, Msg::ProposalNotPending)
+        }
+        ApprovalError::Database(e) => internal_error("审批交易提案", e, locale),
+    }
+}
+
+fn prompt_history_error(e: PromptHistoryError, locale: Locale) -> ApiError {
+    match e {
+        PromptHistoryError::TraderNotFound(_) => {
+            ApiError::new(StatusCode::NOT_FOUND, locale, Msg::TraderNotFound)
+        }
+        PromptHistoryError::VersionNotFound(_) => {
+            ApiError::new(StatusCode::NOT_FOUND, locale, Msg::PromptVersionNotFound)
+        }
+        PromptHistoryError::TemplateNotFound(_) => {
+            ApiError::new(StatusCode::NOT_FOUND, locale, Msg::PromptTemplateNotFound)
+        }
+        PromptHistoryError::Database(e) => {
+            tracing::error!("❌ 提示词版本操作失败: {}", e);
+            ApiError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                locale,
+                Msg::InternalError,
+            )
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct PromptTemplates {
+    /// Built-in templates; usable by name but not editable.
+    builtin: &'static [&'static str],
+    /// Variables a template may contain, as `{{name}}`.
+    variables: &'static [&'static str],
+    templates: Vec<PromptTemplate>,
+}
+
+#[derive(Deserialize)]
+struct PromptTemplateInput {
+    /// Only read when creating; the URL names the template otherwise.
+    #[serde(default)]
+    name: String,
+    content: String,
+}
+
+async fn list_prompt_templates(
+    user: AuthUser,
+    headers: HeaderMap,
+    State(state): State<AppState>,
+) -> Result<Json<PromptTemplates>, ApiError> {
+    let locale = request_locale(&headers);
+    let templates = state
+        .db
+        .get_prompt_templates(&user.user_id)
+        .await
+        .map_err(|e| internal_error("获取提示词模板", e, locale))?;
+    Ok(Json(PromptTemplates {
+        builtin: prompt::template_names(),
+        variables: prompt_template::VARIABLES,
+        templates,
+    }))
+}
+
+async fn create_prompt_template(
+    user: AuthUser,
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Json(input): Json<PromptTemplateInput>,
+) -> Result<(StatusCode, Json<PromptTemplate>), ApiError> {
+    let locale = request_locale(&headers);
+    let name = input.name.trim();
+    prompt_template::validate(name, &input.content)
+        .map_err(|_| ApiError::new(StatusCode::BAD_REQUEST, locale, Msg::InvalidRequest))?;
+    let created = state
+        .db
+        .create_prompt_template(&user.user_id, name, &input.content)
+        .await
+        .map_err(|e| internal_error("创建提示词模板", e, locale))?;
+    if !created {
+        return Err(ApiError::new(
+            StatusCode::CONFLICT,
+            locale,
+            Msg::PromptTemplateExists,
+        ));
+    }
+    let template = owned_template(&state, &user, name, locale).await?;
+    Ok((StatusCode::CREATED, Json(template)))
+}
+
+async fn get_prompt_template(
+    user: AuthUser,
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<Json<PromptTemplate>, ApiError> {
+    let locale = request_locale(&headers);
+    owned_template(&state, &user, &name, locale).await.map(Json)
+}
+
+/// Replaces a template's content; traders using it pick it up next cycle.
+async fn update_prompt_template(
+    user: AuthUser,
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    Json(input): Json<PromptTemplateInput>,
+) -> Result<Json<PromptTemplate>, ApiError> {
+    let locale = request_locale(&headers);
+    let current = owned_template(&state, &user, &name, locale).await?;
+    audit_log::before(&current);
+    prompt_template::validate(&name, &input.content)
+        .map_err(|_| ApiError::new(StatusCode::BAD_REQUEST, locale, Msg::InvalidRequest))?;
+    state
+        .db
+        .update_prompt_template(&user.user_id, &name, &input.content)
+        .await
+        .map_err(|e| internal_error("更新提示词模板", e, locale))?;
+    owned_template(&state, &user, &name, locale).await.map(Json)
+}
+
+/// Deletes a template no trader uses.
+async fn delete_prompt_template(
+    user: AuthUser,
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    let locale = request_locale(&headers);
+    owned_template(&state, &user, &name, locale).await?;
+    let traders = state
+        .db
+        .get_traders(&user.user_id)
+        .await
+        .map_err(|e| internal_error("获取交易员列表", e, locale))?;
+    if traders.iter().any(|t| t.system_prompt_template == name) {
+        return Err(ApiError::new(
+            StatusCode::CONFLICT,
+            locale,
+            Msg::PromptTemplateInUse,
+        ));
+    }
+    state
+        .db
+        .delete_prompt_template(&user.user_id, &name)
+        .await
+        .map_err(|e| internal_error("删除提示词模板", e, locale))?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// Fails with 404 unless the caller has a template by this name.
+async fn owned_template(
+    state: &AppState,
+    user: &AuthUser,
+    name: &str,
+    locale: Locale,
+) -> Result<PromptTemplate, ApiError> {
+    match state.db.get_prompt_template(&user.user_id, name).await {
+        Ok(Some(template)) => Ok(template),
+        Ok(None) => Err(ApiError::new(
+            StatusCode::NOT_FOUND,
+            locale,
+            Msg::PromptTemplateNotFound,
+        )),
+        Err(e) => Err(internal_error("获取提示词模板", e, locale)),
+    }
+}
+
+/// Serves `app` until `shutdown` resolves. A unix socket file is replaced if it
+/// is stale and removed again on shutdown.
+pub async fn serve<F>(listen: &Listen, app: Router, shutdown: F) -> Result<(), ServerError>
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    match listen {
+        Listen::Tcp(addr) => {
+            let listener = tokio::net::TcpListener::bind(addr).await?;
+            tracing::info!("🌐 API服务器启动于 http://{}", addr);
+            // The peer address feeds the per-IP login lockouts.
+            axum::serve(
+                listener,
+                app.into_make_service_with_connect_info::<SocketAddr>(),
+            )
+            .with_graceful_shutdown(shutdown)
+            .await?;
+        }
+        #[cfg(unix)]
+        Listen::Unix(path) => {
+            use std::os::unix::fs::PermissionsExt;
+
+            if path.exists() {
+                std::fs::remove_file(path)?;
+            }
+            let listener = tokio::net::UnixListener::bind(path)?;
+            // Owner and group only; the reverse proxy is expected to share the group.
+            std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o660))?;
+            tracing::info!("🌐 API服务器启动于 unix:{}", path.display());
+
+            let result = axum::serve(listener, app)
+                .with_graceful_shutdown(shutdown)
+                .await;
+            let _ = std::fs::remove_file(path);
+            result?;
+        }
+        #[cfg(not(unix))]
+        Listen::Unix(_) => {
+            return Err(ServerError::Io(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "unix sockets are not supported on this platform",
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Calls the API in-process without any socket, for embedding the engine in
+/// another Rust program.
+#[derive(Clone)]
+pub struct EmbeddedClient {
+    router: Router,
+    token: Option<String>,
+    headers: Vec<(String, String)>,
+}
+
+impl EmbeddedClient {
+    pub fn new(state: AppState) -> Self {
+        Self {
+            router: router(state),
+            token: None,
+            headers: Vec::new(),
+        }
+    }
+
+    /// Sends requests as the user the JWT belongs to.
+    pub fn with_token(mut self, token: impl Into<String>) -> Self {
+        self.token = Some(token.into());
+        self
+    }
+
+    /// Adds a header to every request, e.g. a webhook signature.
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Performs a request and returns the status and decoded JSON body.
+    pub async fn request(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<&Value>,
+    ) -> Result<(StatusCode, Value), ServerError> {
+        let (status, _, bytes) = self.request_raw(method, path, body).await?;
+        let value = if bytes.is_empty() {
+            Value::Null
+        } else {
+            serde_json::from_slice(&bytes)?
+        };
+        Ok((status, value))
+    }
+
+    /// Performs a request and returns the response undecoded, for downloads.
+    pub async fn request_raw(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<&Value>,
+    ) -> Result<(StatusCode, HeaderMap, Bytes), ServerError> {
+        let mut builder = Request::builder().method(method).uri(path);
+        if let Some(token) = &self.token {
+            builder = builder.header(header::AUTHORIZATION, format!("Bearer {}", token));
+        }
+        for (name, value) in &self.headers {
+            builder = builder.header(name.as_str(), value.as_str());
+        }
+        let request = match body {
+            Some(body) => builder
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(serde_json::to_vec(body)?))?,
+            None => builder.body(Body::empty())?,
+        };
+
+        let response = match self.router.clone().oneshot(request).await {
+            Ok(response) => response,
+            Err(never) => match never {},
+        };
+        let status = response.status();
+        let headers = response.headers().clone();
+        let bytes = response
+            .into_body()
+            .collect()
+            .await
+            .map_err(|e| ServerError::Status(status, e.to_string()))?
+            .to_bytes();
+        Ok((status, headers, bytes))
+    }
+
+    /// GETs `path` and deserializes a successful response.
+    pub async fn get<T: DeserializeOwned>(&self, path: &str) -> Result<T, ServerError> {
+        let (status, value) = self.request(Method::GET, path, None).await?;
+        if !status.is_success() {
+            return Err(ServerError::Status(status, value.to_string()));
+        }
+        Ok(serde_json::from_value(value)?)
+    }
+}
@@ -0,0 +1,206 @@
+//! Position sizing: how large an entry the AI asked for is actually opened.
+//!
+//! Each trader picks a method in its `position_sizing` column:
+//!
+//! - `""` or `ai`: the size the AI proposed.
+//! - `fixed:<pct>`: `pct`% of equity as margin, times the decision's leverage.
+//! - `atr:<risk_pct>:<multiple>`: loses `risk_pct`% of equity if the price
+//!   moves `multiple` times the 4h ATR(14) against the position.
+//! - `kelly:<fraction>:<max_pct>`: `fraction` of the Kelly margin given the
+//!   trader's recent win rate and payoff, at most `max_pct`% of equity. Until
+//!   enough trades have closed the AI's size is used, capped the same way;
+//!   with a negative edge no entries are opened.
+//!
+//! Sizes never exceed equity times leverage, the limit decisions are validated
+//! against. Closes and holds pass through untouched.
+
+use std::fmt;
+use std::str::FromStr;
+
+use thiserror::Error;
+
+use crate::data::normalize;
+use crate::decision::{Context, Decision};
+use crate::logger::PerformanceAnalysis;
+
+/// Closed trades needed before Kelly sizing trusts the win rate.
+pub const MIN_KELLY_TRADES: i32 = 10;
+
+#[derive(Error, Debug, Clone, PartialEq)]
+#[error("invalid position sizing '{spec}': {reason}")]
+pub struct SizingError {
+    pub spec: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum PositionSizing {
+    #[default]
+    Ai,
+    FixedFraction {
+        equity_pct: f64,
+    },
+    Atr {
+        risk_pct: f64,
+        atr_multiple: f64,
+    },
+    Kelly {
+        fraction: f64,
+        max_pct: f64,
+    },
+}
+
+impl FromStr for PositionSizing {
+    type Err = SizingError;
+
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        let invalid = |reason: &str| SizingError {
+            spec: spec.to_string(),
+            reason: reason.to_string(),
+        };
+        let mut parts = spec.trim().split(':').map(str::trim);
+        let method = parts.next().unwrap_or_default().to_ascii_lowercase();
+        let params = parts
+            .map(|p| p.parse::<f64>().ok().filter(|v| v.is_finite()))
+            .collect::<Option<Vec<f64>>>()
+            .ok_or_else(|| invalid("parameters must be numbers"))?;
+        let pct = |v: f64| v > 0.0 && v <= 100.0;
+        let sizing = match (method.as_str(), params.as_slice()) {
+            ("" | "ai", []) => PositionSizing::Ai,
+            ("fixed", &[equity_pct]) if pct(equity_pct) => {
+                PositionSizing::FixedFraction { equity_pct }
+            }
+            ("fixed", _) => return Err(invalid("expected fixed:<pct of equity, 0-100>")),
+            ("atr", &[risk_pct, atr_multiple]) if pct(risk_pct) && atr_multiple > 0.0 => {
+                PositionSizing::Atr {
+                    risk_pct,
+                    atr_multiple,
+                }
+            }
+            ("atr", _) => return Err(invalid("expected atr:<risk pct, 0-100>:<ATR multiple>")),
+            ("kelly", &[fraction, max_pct])
+                if fraction > 0.0 && fraction <= 1.0 && pct(max_pct) =>
+            {
+                PositionSizing::Kelly { fraction, max_pct }
+            }
+            ("kelly", _) => {
+                return Err(invalid(
+                    "expected kelly:<fraction, 0-1>:<max pct of equity, 0-100>",
+                ));
+            }
+            _ => return Err(invalid("unknown method; use ai, fixed, atr or kelly")),
+        };
+        Ok(sizing)
+    }
+}
+
+impl fmt::Display for PositionSizing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PositionSizing::Ai => write!(f, "ai"),
+            PositionSizing::FixedFraction { equity_pct } => write!(f, "fixed:{}", equity_pct),
+            PositionSizing::Atr {
+                risk_pct,
+                atr_multiple,
+            } => write!(f, "atr:{}:{}", risk_pct, atr_multiple),
+            PositionSizing::Kelly { fraction, max_pct } => {
+                write!(f, "kelly:{}:{}", fraction, max_pct)
+            }
+        }
+    }
+}
+
+impl PositionSizing {
+    /// The first leg's notional in USD for an entry, or why it should not be
+    /// opened. Falls back to the AI's size when the inputs are missing.
+    pub fn size(&self, d: &Decision, ctx: &Context) -> Result<f64, String> {
+        let equity = ctx.account.total_equity;
+        if equity <= 0.0 {
+            return Ok(d.position_size_usd);
+        }
+        let leverage = f64::from(d.leverage.max(1));
+        let target = match *self {
+            PositionSizing::Ai => d.position_size_usd,
+            PositionSizing::FixedFraction { equity_pct } => equity * equity_pct / 100.0 * leverage,
+            PositionSizing::Atr {
+                risk_pct,
+                atr_multiple,
+            } => {
+                let data = ctx.market_data.get(&normalize(&d.symbol));
+                let atr = data
+                    .and_then(|d| d.longer_term_context.as_ref())
+                    .map_or(0.0, |l| l.atr14);
+                match data {
+                    Some(data) if atr > 0.0 && data.current_price > 0.0 => {
+                        let quantity = equity * risk_pct / 100.0 / (atr_multiple * atr);
+                        quantity * data.current_price
+                    }
+                    _ => d.position_size_usd,
+                }
+            }
+            PositionSizing::Kelly { fraction, max_pct } => {
+                let cap = equity * max_pct / 100.0 * leverage;
+                match ctx.performance.as_ref().map(kelly) {
+                    Some(Some(f)) if f <= 0.0 => {
+                        return Err(format!("no edge (Kelly fraction {:.2})", f));
+                    }
+                    Some(Some(f)) => (equity * f * fraction * leverage).min(cap),
+                    _ => d.position_size_usd.min(cap),
+                }
+            }
+        };
+        let legs = 1.0 + d.pair.as_ref().map_or(0.0, |p| p.ratio);
+        Ok(target.min(equity * leverage / legs))
+    }
+}
+
+// The Kelly fraction p - (1 - p) / b from recent trades, once there are enough.
+fn kelly(performance: &PerformanceAnalysis) -> Option<f64> {
+    if performance.total_trades() < MIN_KELLY_TRADES {
+        return None;
+    }
+    let p = performance.win_rate() / 100.0;
+    Some(match performance.payoff_ratio() {
+        Some(b) if b > 0.0 => p - (1.0 - p) / b,
+        Some(_) => -1.0,
+        // Never lost: the loss term vanishes.
+        None => p,
+    })
+}
+
+/// Resizes entries with the trader's method, dropping those it refuses.
+pub fn apply(sizing: &PositionSizing, decisions: Vec<Decision>, ctx: &Context) -> Vec<Decision> {
+    decisions
+        .into_iter()
+        .filter_map(|mut d| {
+            if !d.action.is_open() {
+                return Some(d);
+            }
+            match sizing.size(&d, ctx) {
+                Ok(size) => {
+                    if (size - d.position_size_usd).abs() >= 0.01 {
+                        tracing::info!(
+                            "📐 {} 仓位按 {} 调整: {:.2} → {:.2} USD",
+                            d.symbol,
+                            sizing,
+                            d.position_size_usd,
+                            size
+                        );
+                        d.position_size_usd = size;
+                    }
+                    Some(d)
+                }
+                Err(reason) => {
+                    tracing::warn!(
+                        "📐 {} {:?} 按 {} 不开仓: {}",
+                        d.symbol,
+                        d.action,
+                        sizing,
+                        reason
+                    );
+                    None
+                }
+            }
+        })
+        .collect()
+}
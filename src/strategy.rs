@@ -0,0 +1,181 @@
+//! Compiled-in decision strategies.
+//!
+//! A [`Strategy`] either replaces the LLM step entirely (rule-based) or reviews
+//! the model's proposal before execution (hybrid). Whatever it returns goes
+//! through the same execution, risk and logging path as AI decisions.
+//!
+//! Custom strategies are registered by name at startup and selected per trader
+//! through its `strategy` setting; the runner applies it every cycle.
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, RwLock};
+
+use once_cell::sync::Lazy;
+use thiserror::Error;
+
+use crate::decision::{Action, Context, Decision};
+
+static REGISTRY: Lazy<RwLock<BTreeMap<String, Arc<dyn Strategy>>>> =
+    Lazy::new(|| RwLock::new(BTreeMap::new()));
+
+#[derive(Error, Debug)]
+pub enum StrategyError {
+    #[error("Strategy '{0}' is not registered")]
+    NotFound(String),
+    #[error("Strategy '{0}' is already registered")]
+    AlreadyRegistered(String),
+    #[error("Strategy failed: {0}")]
+    Failed(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StrategyMode {
+    /// The LLM is not called; the strategy alone produces decisions.
+    Replace,
+    /// The LLM is called first and its decisions are handed to the strategy.
+    Augment,
+}
+
+pub trait Strategy: Send + Sync {
+    fn name(&self) -> &str;
+
+    fn mode(&self) -> StrategyMode {
+        StrategyMode::Replace
+    }
+
+    /// Produces this cycle's decisions. `proposed` holds the LLM's decisions in
+    /// [`StrategyMode::Augment`] and is empty otherwise.
+    fn decide(
+        &self,
+        ctx: &Context,
+        proposed: Vec<Decision>,
+    ) -> Result<Vec<Decision>, StrategyError>;
+}
+
+/// Makes a strategy available under its name.
+pub fn register(strategy: Arc<dyn Strategy>) -> Result<(), StrategyError> {
+    let mut registry = REGISTRY.write().unwrap_or_else(|e| e.into_inner());
+    let name = strategy.name().to_string();
+    if registry.contains_key(&name) {
+        return Err(StrategyError::AlreadyRegistered(name));
+    }
+    registry.insert(name, strategy);
+    Ok(())
+}
+
+pub fn get(name: &str) -> Result<Arc<dyn Strategy>, StrategyError> {
+    REGISTRY
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(name)
+        .cloned()
+        .ok_or_else(|| StrategyError::NotFound(name.to_string()))
+}
+
+/// Names of all registered strategies, sorted.
+pub fn names() -> Vec<String> {
+    REGISTRY
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .keys()
+        .cloned()
+        .collect()
+}
+
+/// Registers the strategies that ship with the engine.
+pub fn register_builtin() {
+    for strategy in [Arc::new(EmaTrend::default()) as Arc<dyn Strategy>] {
+        if let Err(e) = register(strategy) {
+            tracing::warn!("⚠️ {}", e);
+        }
+    }
+}
+
+/// Rule-based trend follower on 3m data: long above EMA20 with positive MACD,
+/// short below it with negative MACD, exit when price crosses back over EMA20.
+/// Mostly useful as a baseline and as an example of the trait.
+pub struct EmaTrend {
+    pub position_size_usd: f64,
+    pub rsi_overbought: f64,
+    pub rsi_oversold: f64,
+}
+
+impl Default for EmaTrend {
+    fn default() -> Self {
+        Self {
+            position_size_usd: 100.0,
+            rsi_overbought: 70.0,
+            rsi_oversold: 30.0,
+        }
+    }
+}
+
+impl Strategy for EmaTrend {
+    fn name(&self) -> &str {
+        "ema_trend"
+    }
+
+    fn decide(
+        &self,
+        ctx: &Context,
+        _proposed: Vec<Decision>,
+    ) -> Result<Vec<Decision>, StrategyError> {
+        let mut decisions = Vec::new();
+
+        for position in &ctx.positions {
+            let Some(data) = ctx.market_data.get(&position.symbol) else {
+                continue;
+            };
+            let exit = match position.side.as_str() {
+                "long" if data.current_price < data.current_ema20 => Some(Action::CloseLong),
+                "short" if data.current_price > data.current_ema20 => Some(Action::CloseShort),
+                _ => None,
+            };
+            if let Some(action) = exit {
+                let mut d = Decision::new(&position.symbol, action);
+                d.reasoning = "price crossed back over EMA20".to_string();
+                decisions.push(d);
+            }
+        }
+
+        for symbol in &ctx.candidate_coins {
+            // Degraded data is only good enough to manage what is already open.
+            let Some(data) = ctx.market_data.get(symbol).filter(|d| !d.degraded) else {
+                continue;
+            };
+            if ctx.position(symbol).is_some() {
+                continue;
+            }
+
+            let action = if data.current_price > data.current_ema20
+                && data.current_macd > 0.0
+                && data.current_rsi7 < self.rsi_overbought
+            {
+                Action::OpenLong
+            } else if data.current_price < data.current_ema20
+                && data.current_macd < 0.0
+                && data.current_rsi7 > self.rsi_oversold
+            {
+                Action::OpenShort
+            } else {
+                continue;
+            };
+
+            let mut d = Decision::new(symbol, action);
+            d.leverage = if symbol == "BTCUSDT" || symbol == "ETHUSDT" {
+                ctx.btc_eth_leverage
+            } else {
+                ctx.altcoin_leverage
+            };
+            d.position_size_usd = self.position_size_usd;
+            d.confidence = 60;
+            d.reasoning = format!(
+                "price {:.4} vs EMA20 {:.4}, MACD {:.4}, RSI7 {:.1}",
+                data.current_price, data.current_ema20, data.current_macd, data.current_rsi7
+            );
+            decisions.push(d);
+        }
+
+        Ok(decisions)
+    }
+}
@@ -0,0 +1,396 @@
+//! Binance futures WebSocket market data.
+//!
+//! One combined-stream connection carries kline (3m and 4h), markPrice and
+//! ticker updates for every tracked symbol. Candles are kept in rolling buffers
+//! so `data::get` can assemble market data without re-fetching klines over REST
+//! every cycle; REST only seeds a buffer the first time a symbol is seen. A
+//! disconnect clears the buffers, since candles missed while offline would
+//! leave gaps, and the next read backfills them again.
+
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
+
+use futures_util::{SinkExt, StreamExt};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::currency;
+use crate::types::Kline;
+
+/// Candle intervals subscribed per symbol.
+pub const INTERVALS: &[&str] = &["3m", "4h"];
+// Candles kept per symbol and interval.
+const BUFFER_LEN: usize = 120;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct StreamParams {
+    /// Stream market data over WebSocket; off fetches everything over REST.
+    pub enabled: bool,
+    /// Combined-stream endpoint.
+    pub url: String,
+    /// Symbols without an update for this long are read over REST again.
+    #[serde(with = "humantime_serde")]
+    pub max_staleness: Duration,
+    #[serde(with = "humantime_serde")]
+    pub reconnect_delay: Duration,
+}
+
+impl Default for StreamParams {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            url: "wss://fstream.binance.com/stream".to_string(),
+            max_staleness: Duration::from_secs(60),
+            reconnect_delay: Duration::from_secs(5),
+        }
+    }
+}
+
+#[derive(Default)]
+struct SymbolState {
+    /// Candles per interval, oldest first.
+    klines: HashMap<String, VecDeque<Kline>>,
+    mark_price: Option<f64>,
+    funding_rate: Option<f64>,
+    last_price: Option<f64>,
+    updated_at: Option<Instant>,
+}
+
+impl SymbolState {
+    fn is_fresh(&self, max_staleness: Duration) -> bool {
+        self.updated_at
+            .is_some_and(|at| at.elapsed() <= max_staleness)
+    }
+}
+
+#[derive(Default)]
+struct State {
+    params: StreamParams,
+    tracked: BTreeSet<String>,
+    symbols: HashMap<String, SymbolState>,
+    connected: bool,
+    // New symbols to subscribe on the live connection.
+    subscribe: Option<mpsc::UnboundedSender<String>>,
+}
+
+static STATE: Lazy<RwLock<State>> = Lazy::new(|| RwLock::new(State::default()));
+
+fn read_state() -> std::sync::RwLockReadGuard<'static, State> {
+    STATE.read().unwrap_or_else(|e| e.into_inner())
+}
+
+fn write_state() -> std::sync::RwLockWriteGuard<'static, State> {
+    STATE.write().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Starts the connection task. Returns `None` when streaming is disabled.
+pub fn start(params: StreamParams) -> Option<JoinHandle<()>> {
+    if !params.enabled {
+        return None;
+    }
+    let (tx, rx) = mpsc::unbounded_channel();
+    {
+        let mut state = write_state();
+        state.params = params.clone();
+        state.subscribe = Some(tx);
+    }
+    tracing::info!("📡 启动行情WebSocket: {}", params.url);
+    Some(tokio::spawn(run(params, rx)))
+}
+
+/// True once [`start`] has been called with streaming enabled.
+pub fn is_running() -> bool {
+    read_state().subscribe.is_some()
+}
+
+/// True while the WebSocket is connected.
+pub fn is_connected() -> bool {
+    read_state().connected
+}
+
+/// Adds `symbol` to the subscriptions; already tracked symbols are ignored.
+pub fn track(symbol: &str) {
+    let symbol = symbol.to_uppercase();
+    let mut state = write_state();
+    if state.tracked.insert(symbol.clone())
+        && let Some(tx) = &state.subscribe
+    {
+        let _ = tx.send(symbol);
+    }
+}
+
+/// Symbols currently subscribed.
+pub fn tracked() -> Vec<String> {
+    read_state().tracked.iter().cloned().collect()
+}
+
+/// The latest `limit` candles of `interval`, if the stream has at least that
+/// many and the symbol is receiving updates.
+pub fn klines(symbol: &str, interval: &str, limit: usize) -> Option<Vec<Kline>> {
+    let state = read_state();
+    if !state.connected {
+        return None;
+    }
+    let s = state.symbols.get(&symbol.to_uppercase())?;
+    if !s.is_fresh(state.params.max_staleness) {
+        return None;
+    }
+    let buffer = s.klines.get(interval)?;
+    if buffer.len() < limit {
+        return None;
+    }
+    Some(buffer.iter().skip(buffer.len() - limit).cloned().collect())
+}
+
+fn fresh_value(symbol: &str, value: impl Fn(&SymbolState) -> Option<f64>) -> Option<f64> {
+    let state = read_state();
+    if !state.connected {
+        return None;
+    }
+    state
+        .symbols
+        .get(&symbol.to_uppercase())
+        .filter(|s| s.is_fresh(state.params.max_staleness))
+        .and_then(value)
+}
+
+/// Latest funding rate from the markPrice stream.
+pub fn funding_rate(symbol: &str) -> Option<f64> {
+    fresh_value(symbol, |s| s.funding_rate)
+}
+
+pub fn mark_price(symbol: &str) -> Option<f64> {
+    fresh_value(symbol, |s| s.mark_price)
+}
+
+/// Last traded price from the ticker stream.
+pub fn last_price(symbol: &str) -> Option<f64> {
+    fresh_value(symbol, |s| s.last_price)
+}
+
+/// Seeds a buffer with candles fetched over REST. Candles the stream already
+/// delivered take precedence. Ignored while disconnected, since nothing would
+/// keep the buffer current.
+pub fn backfill(symbol: &str, interval: &str, klines: &[Kline]) {
+    let mut state = write_state();
+    if !state.connected {
+        return;
+    }
+    let symbol = symbol.to_uppercase();
+    if !state.tracked.contains(&symbol) {
+        return;
+    }
+    let buffer = state
+        .symbols
+        .entry(symbol)
+        .or_default()
+        .klines
+        .entry(interval.to_string())
+        .or_default();
+    let first_streamed = buffer.front().map_or(i64::MAX, |k| k.open_time);
+    for k in klines.iter().rev().filter(|k| k.open_time < first_streamed) {
+        buffer.push_front(k.clone());
+    }
+    while buffer.len() > BUFFER_LEN {
+        buffer.pop_front();
+    }
+}
+
+// Replaces the open candle or appends a newer one; older candles are dropped.
+fn merge(buffer: &mut VecDeque<Kline>, kline: Kline) {
+    match buffer.back_mut() {
+        Some(last) if last.open_time == kline.open_time => *last = kline,
+        Some(last) if last.open_time > kline.open_time => {}
+        _ => buffer.push_back(kline),
+    }
+    while buffer.len() > BUFFER_LEN {
+        buffer.pop_front();
+    }
+}
+
+fn stream_names(symbol: &str) -> Vec<String> {
+    let s = symbol.to_lowercase();
+    let mut names: Vec<String> = INTERVALS
+        .iter()
+        .map(|i| format!("{}@kline_{}", s, i))
+        .collect();
+    names.push(format!("{}@markPrice", s));
+    names.push(format!("{}@ticker", s));
+    names
+}
+
+fn subscribe_message(symbols: &[String], id: u64) -> Message {
+    let params: Vec<String> = symbols.iter().flat_map(|s| stream_names(s)).collect();
+    Message::Text(
+        serde_json::json!({"method": "SUBSCRIBE", "params": params, "id": id}).to_string(),
+    )
+}
+
+async fn run(params: StreamParams, mut rx: mpsc::UnboundedReceiver<String>) {
+    let mut request_id = 0u64;
+    loop {
+        match tokio_tungstenite::connect_async(params.url.as_str()).await {
+            Ok((ws, _)) => {
+                let (mut write, mut read) = ws.split();
+                write_state().connected = true;
+                // Everything tracked so far goes into the first subscription.
+                while rx.try_recv().is_ok() {}
+                let symbols = tracked();
+                request_id += 1;
+                let mut result = if symbols.is_empty() {
+                    Ok(())
+                } else {
+                    write.send(subscribe_message(&symbols, request_id)).await
+                };
+                while result.is_ok() {
+                    tokio::select! {
+                        msg = read.next() => match msg {
+                            Some(Ok(Message::Text(text))) => apply(&text),
+                            Some(Ok(Message::Close(_))) | None => break,
+                            Some(Err(e)) => result = Err(e),
+                            Some(Ok(_)) => {}
+                        },
+                        Some(symbol) = rx.recv() => {
+                            request_id += 1;
+                            result = write.send(subscribe_message(&[symbol], request_id)).await;
+                        }
+                    }
+                }
+                disconnected();
+                match result {
+                    Err(e) => tracing::warn!("⚠️ 行情WebSocket断开: {}", e),
+                    Ok(()) => tracing::warn!("⚠️ 行情WebSocket被服务端关闭"),
+                }
+            }
+            Err(e) => tracing::warn!("⚠️ 行情WebSocket连接失败: {}", e),
+        }
+        tokio::time::sleep(params.reconnect_delay).await;
+    }
+}
+
+fn disconnected() {
+    let mut state = write_state();
+    state.connected = false;
+    state.symbols.clear();
+}
+
+#[derive(Deserialize)]
+struct Envelope {
+    data: Event,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "e")]
+enum Event {
+    #[serde(rename = "kline")]
+    Kline {
+        #[serde(rename = "s")]
+        symbol: String,
+        #[serde(rename = "k")]
+        kline: StreamKline,
+    },
+    #[serde(rename = "markPriceUpdate")]
+    MarkPrice {
+        #[serde(rename = "s")]
+        symbol: String,
+        #[serde(rename = "p")]
+        mark_price: String,
+        #[serde(rename = "r")]
+        funding_rate: String,
+    },
+    #[serde(rename = "24hrTicker")]
+    Ticker {
+        #[serde(rename = "s")]
+        symbol: String,
+        #[serde(rename = "c")]
+        last_price: String,
+    },
+}
+
+#[derive(Deserialize)]
+struct StreamKline {
+    #[serde(rename = "t")]
+    open_time: i64,
+    #[serde(rename = "T")]
+    close_time: i64,
+    #[serde(rename = "i")]
+    interval: String,
+    #[serde(rename = "o")]
+    open: String,
+    #[serde(rename = "h")]
+    high: String,
+    #[serde(rename = "l")]
+    low: String,
+    #[serde(rename = "c")]
+    close: String,
+    #[serde(rename = "v")]
+    volume: String,
+    #[serde(rename = "n")]
+    trades: i64,
+    #[serde(rename = "q")]
+    quote_volume: String,
+    #[serde(rename = "V")]
+    taker_buy_base_volume: String,
+    #[serde(rename = "Q")]
+    taker_buy_quote_volume: String,
+}
+
+impl From<StreamKline> for Kline {
+    fn from(k: StreamKline) -> Self {
+        let parse = |v: &str| v.parse::<f64>().unwrap_or(0.0);
+        Kline {
+            open_time: k.open_time,
+            open: parse(&k.open),
+            high: parse(&k.high),
+            low: parse(&k.low),
+            close: parse(&k.close),
+            volume: parse(&k.volume),
+            close_time: k.close_time,
+            quote_volume: parse(&k.quote_volume),
+            trades: k.trades,
+            taker_buy_base_volume: parse(&k.taker_buy_base_volume),
+            taker_buy_quote_volume: parse(&k.taker_buy_quote_volume),
+        }
+    }
+}
+
+// Applies one stream message; subscription acks and unknown events are ignored.
+fn apply(text: &str) {
+    let Ok(Envelope { data }) = serde_json::from_str::<Envelope>(text) else {
+        return;
+    };
+    let parse = |v: &str| v.parse::<f64>().ok();
+    let mut state = write_state();
+    let symbol = match &data {
+        Event::Kline { symbol, .. }
+        | Event::MarkPrice { symbol, .. }
+        | Event::Ticker { symbol, .. } => symbol.to_uppercase(),
+    };
+    let s = state.symbols.entry(symbol.clone()).or_default();
+    s.updated_at = Some(Instant::now());
+    match data {
+        Event::Kline { kline, .. } => {
+            let buffer = s.klines.entry(kline.interval.clone()).or_default();
+            merge(buffer, kline.into());
+        }
+        Event::MarkPrice {
+            mark_price,
+            funding_rate,
+            ..
+        } => {
+            s.mark_price = parse(&mark_price);
+            s.funding_rate = parse(&funding_rate);
+        }
+        Event::Ticker { last_price, .. } => {
+            s.last_price = parse(&last_price);
+            if let Some(price) = s.last_price {
+                currency::set_price(&symbol, price);
+            }
+        }
+    }
+}
@@ -0,0 +1,324 @@
+//! Adverse-scenario stress tests.
+//!
+//! Replays a trader's open positions (from its latest decision record)
+//! through a set of crash windows and synthetic price gaps and reports the
+//! worst equity, drawdown and any liquidations along the way. Positions can
+//! be re-levered first, keeping their margin, to see what a higher leverage
+//! setting would have done.
+//!
+//! The historical paths are coarse checkpoints of BTC's move through each
+//! window, lows included; other coins move by the scenario's beta. They are
+//! not tick data, so intra-candle wicks deeper than the checkpoints are
+//! missed.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::database::Database;
+use crate::decision::PositionInfo;
+use crate::logger::{self, RecordKey};
+
+/// Maintenance margin rate used to estimate liquidation prices when the
+/// exchange did not report one or positions are re-levered.
+const MAINTENANCE_MARGIN_RATE: f64 = 0.004;
+
+#[derive(Error, Debug)]
+pub enum StressError {
+    #[error("Trader {0} has no account snapshot yet")]
+    NoSnapshot(String),
+    #[error("Leverage must be between 1 and 125, got {0}")]
+    InvalidLeverage(i32),
+    #[error("Database error: {0}")]
+    Database(#[from] anyhow::Error),
+}
+
+pub type Result<T> = std::result::Result<T, StressError>;
+
+/// A price path to replay.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Scenario {
+    pub name: &'static str,
+    pub description: &'static str,
+    /// BTC's move from the start of the window, in percent, one entry per
+    /// checkpoint.
+    pub path: &'static [f64],
+    /// How much harder than BTC altcoins moved; ETH sits halfway between.
+    pub alt_beta: f64,
+}
+
+impl Scenario {
+    fn beta(&self, symbol: &str) -> f64 {
+        match symbol {
+            "BTCUSDT" => 1.0,
+            "ETHUSDT" => (1.0 + self.alt_beta) / 2.0,
+            _ => self.alt_beta,
+        }
+    }
+}
+
+/// The built-in scenarios, historical windows first.
+pub const SCENARIOS: &[Scenario] = &[
+    Scenario {
+        name: "may_2021",
+        description: "May 2021 crash (May 12-23, 2021)",
+        path: &[
+            0.0, -6.0, -12.0, -18.0, -22.0, -30.0, -46.0, -28.0, -33.0, -38.0, -35.0,
+        ],
+        alt_beta: 1.35,
+    },
+    Scenario {
+        name: "ftx_week",
+        description: "FTX collapse (Nov 6-12, 2022)",
+        path: &[0.0, -2.0, -9.0, -18.0, -24.0, -14.0, -18.0, -20.0],
+        alt_beta: 1.5,
+    },
+    Scenario {
+        name: "aug_2024",
+        description: "Yen carry unwind (Aug 2-8, 2024)",
+        path: &[0.0, -4.0, -10.0, -18.0, -26.0, -15.0, -12.0, -9.0],
+        alt_beta: 1.3,
+    },
+    Scenario {
+        name: "gap_down_10",
+        description: "Instant 10% gap down across the market",
+        path: &[0.0, -10.0],
+        alt_beta: 1.0,
+    },
+    Scenario {
+        name: "gap_down_20",
+        description: "Instant 20% gap down across the market",
+        path: &[0.0, -20.0],
+        alt_beta: 1.0,
+    },
+    Scenario {
+        name: "gap_up_15",
+        description: "Instant 15% gap up (short squeeze)",
+        path: &[0.0, 15.0],
+        alt_beta: 1.0,
+    },
+];
+
+/// Leverage to re-lever positions to before replaying; `None` keeps a
+/// position's own leverage.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct Leverage {
+    pub btc_eth: Option<i32>,
+    pub altcoin: Option<i32>,
+}
+
+impl Leverage {
+    fn for_symbol(&self, symbol: &str) -> Option<i32> {
+        match symbol {
+            "BTCUSDT" | "ETHUSDT" => self.btc_eth,
+            _ => self.altcoin,
+        }
+    }
+
+    fn validate(&self) -> Result<()> {
+        for leverage in [self.btc_eth, self.altcoin].into_iter().flatten() {
+            if !(1..=125).contains(&leverage) {
+                return Err(StressError::InvalidLeverage(leverage));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A position that would have been liquidated.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Liquidation {
+    pub symbol: String,
+    pub side: String,
+    /// Checkpoint index into the scenario's path.
+    pub step: usize,
+    pub price: f64,
+    /// Realized loss, as a positive number.
+    pub loss: f64,
+}
+
+/// How the account fares in one scenario.
+#[derive(Debug, Clone, Serialize)]
+pub struct ScenarioResult {
+    pub name: String,
+    pub description: String,
+    pub worst_equity: f64,
+    /// From the starting equity to the worst point, in percent.
+    pub max_drawdown_pct: f64,
+    pub final_equity: f64,
+    pub liquidations: Vec<Liquidation>,
+    /// Equity reached zero at some checkpoint.
+    pub wiped_out: bool,
+}
+
+/// The outcome of a stress test.
+#[derive(Debug, Clone, Serialize)]
+pub struct StressReport {
+    /// When the positions were captured.
+    pub as_of: DateTime<Utc>,
+    /// After re-levering, if any.
+    pub equity: f64,
+    pub positions: usize,
+    pub leverage: Leverage,
+    pub scenarios: Vec<ScenarioResult>,
+    /// The scenario with the deepest drawdown.
+    pub worst_scenario: Option<String>,
+}
+
+// One position as replayed.
+#[derive(Debug, Clone)]
+struct Exposure {
+    symbol: String,
+    side: String,
+    direction: f64,
+    quantity: f64,
+    entry_price: f64,
+    mark_price: f64,
+    liquidation_price: f64,
+}
+
+impl Exposure {
+    fn new(p: &PositionInfo, leverage: &Leverage) -> Self {
+        let direction = if p.side == "short" { -1.0 } else { 1.0 };
+        let own = p.leverage.max(1);
+        let (quantity, leverage, liquidation_price) = match leverage.for_symbol(&p.symbol) {
+            Some(l) if l != own => (p.quantity * l as f64 / own as f64, l, 0.0),
+            _ => (p.quantity, own, p.liquidation_price),
+        };
+        let liquidation_price = if liquidation_price > 0.0 {
+            liquidation_price
+        } else {
+            p.entry_price * (1.0 - direction * (1.0 / leverage as f64 - MAINTENANCE_MARGIN_RATE))
+        };
+        Self {
+            symbol: p.symbol.clone(),
+            side: p.side.clone(),
+            direction,
+            quantity,
+            entry_price: p.entry_price,
+            mark_price: p.mark_price,
+            liquidation_price,
+        }
+    }
+
+    fn pnl(&self, price: f64) -> f64 {
+        self.direction * self.quantity * (price - self.entry_price)
+    }
+
+    fn liquidated_at(&self, price: f64) -> bool {
+        self.liquidation_price > 0.0 && self.direction * (price - self.liquidation_price) <= 0.0
+    }
+}
+
+/// Replays positions through every built-in scenario. `equity` is the
+/// account equity the positions were captured with, unrealized PnL included.
+pub fn run(
+    equity: f64,
+    positions: &[PositionInfo],
+    leverage: Leverage,
+    as_of: DateTime<Utc>,
+) -> Result<StressReport> {
+    leverage.validate()?;
+    let positions: Vec<&PositionInfo> = positions
+        .iter()
+        .filter(|p| p.quantity > 0.0 && p.mark_price > 0.0)
+        .collect();
+    let exposures: Vec<Exposure> = positions
+        .iter()
+        .map(|p| Exposure::new(p, &leverage))
+        .collect();
+    // Re-levering changes the unrealized PnL already in the equity.
+    let equity = equity
+        + exposures
+            .iter()
+            .zip(&positions)
+            .map(|(e, p)| e.direction * (e.quantity - p.quantity) * (e.mark_price - e.entry_price))
+            .sum::<f64>();
+    let scenarios: Vec<ScenarioResult> = SCENARIOS
+        .iter()
+        .map(|s| replay(s, equity, &exposures))
+        .collect();
+    let worst_scenario = scenarios
+        .iter()
+        .filter(|r| r.max_drawdown_pct > 0.0)
+        .max_by(|a, b| a.max_drawdown_pct.total_cmp(&b.max_drawdown_pct))
+        .map(|r| r.name.clone());
+    Ok(StressReport {
+        as_of,
+        equity,
+        positions: exposures.len(),
+        leverage,
+        scenarios,
+        worst_scenario,
+    })
+}
+
+fn replay(scenario: &Scenario, equity: f64, exposures: &[Exposure]) -> ScenarioResult {
+    let cash = equity - exposures.iter().map(|e| e.pnl(e.mark_price)).sum::<f64>();
+    let mut realized = 0.0;
+    let mut live = vec![true; exposures.len()];
+    let mut liquidations = Vec::new();
+    let mut worst_equity = equity;
+    let mut final_equity = equity;
+
+    for (step, move_pct) in scenario.path.iter().enumerate() {
+        let mut unrealized = 0.0;
+        for (i, e) in exposures.iter().enumerate() {
+            if !live[i] {
+                continue;
+            }
+            let change = scenario.beta(&e.symbol) * move_pct / 100.0;
+            let price = (e.mark_price * (1.0 + change)).max(e.mark_price * 0.001);
+            if e.liquidated_at(price) {
+                let pnl = e.pnl(e.liquidation_price);
+                realized += pnl;
+                live[i] = false;
+                liquidations.push(Liquidation {
+                    symbol: e.symbol.clone(),
+                    side: e.side.clone(),
+                    step,
+                    price: e.liquidation_price,
+                    loss: -pnl,
+                });
+            } else {
+                unrealized += e.pnl(price);
+            }
+        }
+        final_equity = cash + realized + unrealized;
+        worst_equity = worst_equity.min(final_equity);
+    }
+
+    ScenarioResult {
+        name: scenario.name.to_string(),
+        description: scenario.description.to_string(),
+        worst_equity,
+        max_drawdown_pct: if equity > 0.0 {
+            (equity - worst_equity) / equity * 100.0
+        } else {
+            0.0
+        },
+        final_equity,
+        liquidations,
+        wiped_out: worst_equity <= 0.0,
+    }
+}
+
+/// Stress-tests a trader's positions as of its latest decision record.
+pub async fn for_trader(
+    db: &Database,
+    user_id: &str,
+    trader_id: &str,
+    key: Option<&RecordKey>,
+    leverage: Leverage,
+) -> Result<StressReport> {
+    let record = logger::load_records(db, user_id, trader_id, 1, key)
+        .await?
+        .pop()
+        .ok_or_else(|| StressError::NoSnapshot(trader_id.to_string()))?;
+    run(
+        record.total_equity(),
+        &record.positions(),
+        leverage,
+        record.timestamp(),
+    )
+}
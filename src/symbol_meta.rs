@@ -0,0 +1,120 @@
+//! Per-symbol order rules from exchangeInfo.
+//!
+//! Exchanges reject orders whose quantity or price carries more decimals than
+//! the symbol allows, or whose value is below the symbol's minimum notional.
+//! Each venue's exchangeInfo is cached here and reloaded once it is older than
+//! [`REFRESH_INTERVAL`]; venues round quantities and prices with it, and the
+//! executor checks the minimum notional before an order is sent.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+use once_cell::sync::Lazy;
+use serde::Serialize;
+
+use crate::cache::BoundedCache;
+use crate::money::{self, Decimal};
+use crate::types::{ExchangeInfo, SymbolInfo};
+
+/// How long a venue's exchangeInfo is used before it is loaded again.
+pub const REFRESH_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+// Quantity decimals assumed for symbols the venue does not list.
+const DEFAULT_QUANTITY_PRECISION: u32 = 3;
+// Venues whose rules are kept at once.
+const VENUE_CAPACITY: usize = 64;
+
+// exchangeInfo per venue, keyed by the venue's base URL.
+static CACHE: Lazy<BoundedCache<String, Arc<HashMap<String, SymbolMeta>>>> =
+    Lazy::new(|| BoundedCache::new(VENUE_CAPACITY, REFRESH_INTERVAL));
+
+/// What a venue accepts in one symbol's orders.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SymbolMeta {
+    pub symbol: String,
+    pub price_precision: u32,
+    pub quantity_precision: u32,
+    /// Smallest order value in the quote asset; zero when the venue sets none.
+    pub min_notional: Decimal,
+}
+
+impl SymbolMeta {
+    pub fn from_info(info: &SymbolInfo) -> Self {
+        let min_notional = info
+            .filters
+            .iter()
+            .filter(|f| f.filter_type == "MIN_NOTIONAL")
+            .find_map(|f| f.notional.as_deref())
+            .map(money::parse)
+            .unwrap_or_default();
+        Self {
+            symbol: info.symbol.clone(),
+            price_precision: info.price_precision.max(0) as u32,
+            quantity_precision: info.quantity_precision.max(0) as u32,
+            min_notional,
+        }
+    }
+
+    /// Rules for a symbol the venue does not list: three quantity decimals,
+    /// prices as they are and no minimum.
+    pub fn unlisted(symbol: &str) -> Self {
+        Self {
+            symbol: symbol.to_string(),
+            price_precision: Decimal::MAX_SCALE,
+            quantity_precision: DEFAULT_QUANTITY_PRECISION,
+            min_notional: Decimal::ZERO,
+        }
+    }
+
+    /// Truncates `quantity`, so an order never exceeds what was asked for.
+    pub fn round_quantity(&self, quantity: Decimal) -> Decimal {
+        money::round_down(quantity, self.quantity_precision)
+    }
+
+    /// Rounds `price` to the nearest accepted price.
+    pub fn round_price(&self, price: Decimal) -> Decimal {
+        price.round_dp(self.price_precision).normalize()
+    }
+
+    /// Whether `quantity` at `price` is worth at least the minimum notional.
+    pub fn meets_min_notional(&self, quantity: Decimal, price: Decimal) -> bool {
+        quantity * price >= self.min_notional
+    }
+}
+
+/// Replaces `venue`'s cached rules with an exchangeInfo snapshot.
+pub fn store(venue: &str, info: &ExchangeInfo) {
+    let symbols = info
+        .symbols
+        .iter()
+        .map(|s| (s.symbol.clone(), SymbolMeta::from_info(s)))
+        .collect();
+    CACHE.insert(venue.to_string(), Arc::new(symbols));
+}
+
+/// `venue`'s cached rules for `symbol`, or `None` when the cache is stale or
+/// the symbol is not listed.
+pub fn get(venue: &str, symbol: &str) -> Option<SymbolMeta> {
+    CACHE.get(&venue.to_string())?.get(symbol).cloned()
+}
+
+/// Whether `venue`'s rules were loaded within [`REFRESH_INTERVAL`].
+pub fn is_fresh(venue: &str) -> bool {
+    CACHE.contains(&venue.to_string())
+}
+
+/// The rules for `symbol` on `venue`, loading its exchangeInfo with `load`
+/// when the cache is stale. Symbols the venue does not list get
+/// [`SymbolMeta::unlisted`].
+pub async fn lookup<F, Fut, E>(venue: &str, symbol: &str, load: F) -> Result<SymbolMeta, E>
+where
+    F: FnOnce() -> Fut,
+    Fut: Future<Output = Result<ExchangeInfo, E>>,
+{
+    if !is_fresh(venue) {
+        store(venue, &load().await?);
+    }
+    Ok(get(venue, symbol).unwrap_or_else(|| SymbolMeta::unlisted(symbol)))
+}
@@ -0,0 +1,201 @@
+//! Watches exchangeInfo for traded symbols that stop trading or get delisted.
+//!
+//! Flagged symbols are blocked for new entries, reported once per trader so the
+//! user can be alerted, and annotated in the market data prompt so the AI stops
+//! recommending them. A symbol that returns to TRADING is unblocked again.
+
+use std::collections::{BTreeSet, HashMap};
+use std::sync::RwLock;
+
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+use serde::Serialize;
+
+use crate::api_client::ApiClient;
+use crate::data::normalize;
+use crate::database::Database;
+use crate::decision::Decision;
+use crate::i18n::{self, Locale, Msg};
+use crate::notify::{self, Event};
+use crate::symbols;
+
+const TRADING: &str = "TRADING";
+// Status reported for symbols missing from exchangeInfo altogether.
+const DELISTED: &str = "DELISTED";
+
+static UNAVAILABLE: Lazy<RwLock<HashMap<String, Unavailable>>> =
+    Lazy::new(|| RwLock::new(HashMap::new()));
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Unavailable {
+    pub status: String,
+    pub since: DateTime<Utc>,
+}
+
+/// A trader whose configured symbol became unavailable in this check.
+#[derive(Debug, Clone, Serialize)]
+pub struct SymbolAlert {
+    pub user_id: String,
+    pub trader_id: String,
+    pub trader_name: String,
+    pub symbol: String,
+    pub status: String,
+}
+
+/// True when new positions on `symbol` must not be opened.
+pub fn is_blocked(symbol: &str) -> bool {
+    UNAVAILABLE
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .contains_key(&normalize(symbol))
+}
+
+/// Status of a blocked symbol, if it is blocked.
+pub fn unavailable(symbol: &str) -> Option<Unavailable> {
+    UNAVAILABLE
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(&normalize(symbol))
+        .cloned()
+}
+
+/// All currently blocked symbols.
+pub fn blocked_symbols() -> HashMap<String, Unavailable> {
+    UNAVAILABLE
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+}
+
+/// Drops opening decisions with a leg on a blocked symbol; closes are always
+/// let through.
+pub fn drop_blocked_entries(decisions: Vec<Decision>) -> Vec<Decision> {
+    decisions
+        .into_iter()
+        .filter(|d| {
+            if !d.action.is_open() {
+                return true;
+            }
+            match d.symbols().find(|s| is_blocked(s)) {
+                Some(symbol) => {
+                    tracing::warn!("🚫 {} 已停止交易，拒绝开仓 {:?}", symbol, d.action);
+                    false
+                }
+                None => true,
+            }
+        })
+        .collect()
+}
+
+/// Fetches exchangeInfo, updates the blocked set for every symbol any trader
+/// trades, and returns one alert per affected trader for newly blocked symbols.
+pub async fn check(db: &Database, client: &ApiClient) -> anyhow::Result<Vec<SymbolAlert>> {
+    let info = client.get_exchange_info().await?;
+    symbols::set_listed(&info.symbols);
+    let statuses: HashMap<String, String> = info
+        .symbols
+        .into_iter()
+        .map(|s| (s.symbol, s.status))
+        .collect();
+
+    // symbol -> traders configured to trade it
+    let mut watched: HashMap<String, Vec<(String, String, String)>> = HashMap::new();
+    for user_id in db.get_all_users_id().await? {
+        for trader in db.get_traders(&user_id).await? {
+            let quotes = symbols::quotes_for(&trader.exchange_id, &trader.quote_assets);
+            for symbol in symbols::parse_list(&trader.trading_symbols, &quotes) {
+                watched.entry(symbol).or_default().push((
+                    trader.user_id.clone(),
+                    trader.id.clone(),
+                    trader.name.clone(),
+                ));
+            }
+        }
+    }
+
+    let newly_blocked = update(&statuses, watched.keys().cloned().collect());
+
+    let mut alerts = Vec::new();
+    for (symbol, status) in newly_blocked {
+        tracing::warn!("🚫 {} 状态变为 {}，已禁止新开仓", symbol, status);
+        for (user_id, trader_id, trader_name) in watched.get(&symbol).into_iter().flatten() {
+            alerts.push(SymbolAlert {
+                user_id: user_id.clone(),
+                trader_id: trader_id.clone(),
+                trader_name: trader_name.clone(),
+                symbol: symbol.clone(),
+                status: status.clone(),
+            });
+        }
+    }
+    Ok(alerts)
+}
+
+/// Alerts each affected user in their own language.
+pub async fn alert_users(db: &Database, alerts: &[SymbolAlert]) {
+    for alert in alerts {
+        let locale = match db.get_user_by_id(&alert.user_id).await {
+            Ok(Some(user)) => user.locale(),
+            _ => Locale::default(),
+        };
+        let message = i18n::render(
+            locale,
+            Msg::SymbolUnavailable,
+            &[
+                ("symbol", &alert.symbol),
+                ("status", &alert.status),
+                ("name", &alert.trader_name),
+            ],
+        );
+        tracing::warn!(
+            event = "symbol_unavailable",
+            user_id = %alert.user_id,
+            trader_id = %alert.trader_id,
+            symbol = %alert.symbol,
+            status = %alert.status,
+            "{}",
+            message
+        );
+        notify::notify(
+            Event::alert("symbol_unavailable", message)
+                .user(&alert.user_id)
+                .trader(&alert.trader_id)
+                .symbol(&alert.symbol)
+                .detail("status", &alert.status),
+        );
+    }
+}
+
+// Applies a fresh exchangeInfo snapshot to the watched symbols and returns the
+// ones that just became unavailable.
+fn update(statuses: &HashMap<String, String>, watched: BTreeSet<String>) -> Vec<(String, String)> {
+    let mut unavailable = UNAVAILABLE.write().unwrap_or_else(|e| e.into_inner());
+    let mut newly_blocked = Vec::new();
+
+    for symbol in watched {
+        let status = statuses
+            .get(&symbol)
+            .map(String::as_str)
+            .unwrap_or(DELISTED);
+        if status == TRADING {
+            if unavailable.remove(&symbol).is_some() {
+                tracing::info!("✅ {} 已恢复交易", symbol);
+            }
+            continue;
+        }
+
+        let changed = unavailable.get(&symbol).is_none_or(|u| u.status != status);
+        if changed {
+            unavailable.insert(
+                symbol.clone(),
+                Unavailable {
+                    status: status.to_string(),
+                    since: Utc::now(),
+                },
+            );
+            newly_blocked.push((symbol, status.to_string()));
+        }
+    }
+
+    newly_blocked
+}
@@ -0,0 +1,144 @@
+//! Symbol normalization against the exchange's listed markets.
+//!
+//! Users type bare coins ("btc") or full pairs ("BTCUSDC"). Instead of blindly
+//! appending USDT, a bare coin is resolved to the first listed pair in the
+//! trader's quote-asset preference list, so USDC-margined markets (Hyperliquid,
+//! Binance USDC perps) work. Quote preferences come from the trader, then the
+//! exchange, then the built-in default of USDT.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::RwLock;
+
+use once_cell::sync::Lazy;
+
+use crate::types::SymbolInfo;
+
+/// Quote asset used when nothing else is configured.
+pub const DEFAULT_QUOTE: &str = "USDT";
+
+// Quote assets recognised on symbols that are not (yet) in the listed set.
+const KNOWN_QUOTES: &[&str] = &["USDT", "USDC", "FDUSD", "BUSD", "USD"];
+
+#[derive(Default)]
+struct Registry {
+    /// Every listed pair.
+    listed: HashSet<String>,
+    /// Quote assets seen per base asset.
+    quotes_by_base: HashMap<String, HashSet<String>>,
+    /// Configured quote preference per exchange id.
+    exchange_quotes: HashMap<String, Vec<String>>,
+}
+
+static REGISTRY: Lazy<RwLock<Registry>> = Lazy::new(|| RwLock::new(Registry::default()));
+
+/// Replaces the listed symbol set with a fresh exchangeInfo snapshot.
+pub fn set_listed(symbols: &[SymbolInfo]) {
+    let mut registry = REGISTRY.write().unwrap_or_else(|e| e.into_inner());
+    registry.listed = symbols.iter().map(|s| s.symbol.to_uppercase()).collect();
+    registry.quotes_by_base.clear();
+    for s in symbols {
+        registry
+            .quotes_by_base
+            .entry(s.base_asset.to_uppercase())
+            .or_default()
+            .insert(s.quote_asset.to_uppercase());
+    }
+}
+
+/// Sets the quote-asset preference per exchange id, e.g. `{"hyperliquid": ["USDC"]}`.
+pub fn set_exchange_quotes(quotes: &HashMap<String, Vec<String>>) {
+    let mut registry = REGISTRY.write().unwrap_or_else(|e| e.into_inner());
+    registry.exchange_quotes = quotes
+        .iter()
+        .map(|(exchange, list)| (exchange.to_lowercase(), parse_quotes(list)))
+        .collect();
+}
+
+/// Quote preference for a trader: its own comma-separated override if set,
+/// otherwise its exchange's, otherwise USDT.
+pub fn quotes_for(exchange_id: &str, trader_override: &str) -> Vec<String> {
+    let own = parse_quotes(trader_override.split(','));
+    if !own.is_empty() {
+        return own;
+    }
+    let registry = REGISTRY.read().unwrap_or_else(|e| e.into_inner());
+    registry
+        .exchange_quotes
+        .get(&exchange_id.to_lowercase())
+        .filter(|q| !q.is_empty())
+        .cloned()
+        .unwrap_or_else(|| vec![DEFAULT_QUOTE.to_string()])
+}
+
+/// Normalizes a symbol with the default USDT preference.
+pub fn normalize(symbol: &str) -> String {
+    normalize_with(symbol, &[DEFAULT_QUOTE.to_string()])
+}
+
+/// Normalizes `symbol` to a listed pair, preferring `quotes` in order for bare
+/// coins. Symbols that already name a listed pair or carry a known quote suffix
+/// are kept as they are.
+pub fn normalize_with(symbol: &str, quotes: &[String]) -> String {
+    let upper = symbol.trim().to_uppercase();
+    let registry = REGISTRY.read().unwrap_or_else(|e| e.into_inner());
+
+    if registry.listed.contains(&upper) {
+        return upper;
+    }
+    // A listed base asset takes precedence over suffix matching, so a coin such
+    // as "SUSD" is not mistaken for "S" quoted in USD.
+    if let Some(listed_quotes) = registry.quotes_by_base.get(&upper)
+        && let Some(quote) = quotes.iter().find(|q| listed_quotes.contains(*q))
+    {
+        return format!("{}{}", upper, quote);
+    }
+    if has_quote_suffix(&upper, quotes) {
+        return upper;
+    }
+
+    let quote = quotes.first().map(String::as_str).unwrap_or(DEFAULT_QUOTE);
+    format!("{}{}", upper, quote)
+}
+
+/// Parses a comma-separated symbol list ("btc, ETHUSDT,sol"), normalizing
+/// each with `quotes` and dropping blanks and duplicates.
+pub fn parse_list(list: &str, quotes: &[String]) -> Vec<String> {
+    let mut symbols: Vec<String> = Vec::new();
+    for symbol in list.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+        let symbol = normalize_with(symbol, quotes);
+        if !symbols.contains(&symbol) {
+            symbols.push(symbol);
+        }
+    }
+    symbols
+}
+
+/// Whether the exchange lists `symbol`. Everything passes until a listing
+/// has been loaded.
+pub fn is_listed(symbol: &str) -> bool {
+    let registry = REGISTRY.read().unwrap_or_else(|e| e.into_inner());
+    registry.listed.is_empty() || registry.listed.contains(&symbol.to_uppercase())
+}
+
+fn has_quote_suffix(symbol: &str, quotes: &[String]) -> bool {
+    quotes
+        .iter()
+        .map(String::as_str)
+        .chain(KNOWN_QUOTES.iter().copied())
+        .any(|q| symbol.len() > q.len() && symbol.ends_with(q))
+}
+
+fn parse_quotes<I, S>(list: I) -> Vec<String>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    let mut quotes: Vec<String> = Vec::new();
+    for q in list {
+        let q = q.as_ref().trim().to_uppercase();
+        if !q.is_empty() && !quotes.contains(&q) {
+            quotes.push(q);
+        }
+    }
+    quotes
+}
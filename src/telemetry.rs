@@ -0,0 +1,125 @@
+use std::fmt;
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use tracing::field::{Field, Visit};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::fmt::format::{JsonFields, Writer};
+use tracing_subscriber::fmt::{FmtContext, FormatEvent, FormatFields, FormattedFields};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::util::{SubscriberInitExt, TryInitError};
+use tracing_subscriber::{EnvFilter, Layer};
+
+/// Default filter used when `RUST_LOG` is not set.
+const DEFAULT_FILTER: &str = "info,sqlx=warn";
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum LogFormat {
+    /// Human-readable, colored console output.
+    #[default]
+    Pretty,
+    /// One flat JSON object per line (`timestamp`, `level`, `module`, `span`, `message`,
+    /// plus every field of the enclosing spans such as `trader_id` and `cycle`).
+    Json,
+}
+
+/// Installs the global tracing subscriber.
+///
+/// Each trader's cycle runs in a `trader_cycle` span carrying `trader_id` and `cycle`;
+/// the `ai_call` (`provider`, `model`) and `exchange_request` (`endpoint`, `symbol`)
+/// spans opened within it inherit those, so both layers show which trader an event
+/// belongs to.
+/// `log` records emitted by dependencies such as sqlx are bridged into tracing as well.
+pub fn init(format: LogFormat) -> Result<(), TryInitError> {
+    let filter =
+        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(DEFAULT_FILTER));
+
+    let layer = match format {
+        LogFormat::Pretty => tracing_subscriber::fmt::layer().with_target(true).boxed(),
+        LogFormat::Json => tracing_subscriber::fmt::layer()
+            .fmt_fields(JsonFields::new())
+            .event_format(FlatJson)
+            .boxed(),
+    };
+
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(layer)
+        .try_init()
+}
+
+/// Event formatter that writes span and event fields into a single flat JSON object,
+/// so log shippers can index `trader_id`/`cycle` without parsing nested span lists.
+struct FlatJson;
+
+impl<S, N> FormatEvent<S, N> for FlatJson
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(
+        &self,
+        ctx: &FmtContext<'_, S, N>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> fmt::Result {
+        let meta = event.metadata();
+        let mut obj = Map::new();
+        obj.insert("timestamp".into(), Value::from(Utc::now().to_rfc3339()));
+        obj.insert("level".into(), Value::from(meta.level().as_str()));
+        obj.insert(
+            "module".into(),
+            Value::from(meta.module_path().unwrap_or(meta.target())),
+        );
+
+        // Outermost span first, so fields of inner spans win on conflicts.
+        if let Some(scope) = ctx.event_scope() {
+            for span in scope.from_root() {
+                obj.insert("span".into(), Value::from(span.name()));
+                let extensions = span.extensions();
+                let Some(fields) = extensions.get::<FormattedFields<N>>() else {
+                    continue;
+                };
+                if let Ok(Value::Object(map)) = serde_json::from_str::<Value>(fields.as_str()) {
+                    obj.extend(map);
+                }
+            }
+        }
+
+        event.record(&mut JsonVisitor(&mut obj));
+
+        writeln!(writer, "{}", Value::Object(obj))
+    }
+}
+
+struct JsonVisitor<'a>(&'a mut Map<String, Value>);
+
+impl Visit for JsonVisitor<'_> {
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.0.insert(field.name().into(), Value::from(value));
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.insert(field.name().into(), Value::from(value));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.insert(field.name().into(), Value::from(value));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.insert(field.name().into(), Value::from(value));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().into(), Value::from(value));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0
+            .insert(field.name().into(), Value::from(format!("{:?}", value)));
+    }
+}
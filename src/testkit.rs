@@ -0,0 +1,956 @@
+//! Offline test harness: a scripted exchange, a scripted AI and an in-memory
+//! database, run through the live engine's [`TraderCycle`]. Lets integration
+//! tests run whole cycles (data → decision → execution → logging → risk)
+//! without network access.
+//!
+//! Only built for tests and with the `testkit` feature.
+
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use chrono::Utc;
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::ai::{AiError, AiFuture, AiProvider};
+use crate::auth::{self, Role};
+use crate::data;
+use crate::data::MarketError;
+use crate::database::{Database, TraderRecord, User};
+use crate::decision::{AccountInfo, Action, Decision, DecisionError, PositionInfo};
+use crate::executor::{self, ExecutorError, OrderFill, TradeExecutor};
+use crate::logger::DecisionLogger;
+use crate::money::{self, Decimal};
+use crate::performance;
+use crate::runner::{Balance, RunnerConfig, TraderCycle, Venue};
+use crate::sim::Slippage;
+use crate::symbol_meta::SymbolMeta;
+use crate::types::{Data, IncomeRecord, MarketDataSource, TimeframeData};
+use crate::watch_only::{Comparison, ObservedTrade};
+
+/// An order the mock exchange filled.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MockOrder {
+    pub id: i64,
+    pub symbol: String,
+    pub action: Action,
+    pub quantity: f64,
+    pub price: f64,
+    pub leverage: i32,
+    /// Realized PnL for closes.
+    pub realized_pnl: f64,
+    pub stop_loss_hit: bool,
+}
+
+#[derive(Debug, Clone)]
+struct MockPosition {
+    side: &'static str,
+    quantity: f64,
+    entry_price: f64,
+    leverage: i32,
+    stop_loss: f64,
+    take_profit: f64,
+}
+
+#[derive(Debug, Default)]
+struct MockState {
+    balance: f64,
+    prices: HashMap<String, f64>,
+    scripts: HashMap<String, VecDeque<f64>>,
+    warming_up: HashSet<String>,
+    symbol_meta: HashMap<String, SymbolMeta>,
+    positions: HashMap<String, MockPosition>,
+    orders: Vec<MockOrder>,
+    failures: VecDeque<String>,
+    partial_fills: VecDeque<f64>,
+    slippage: Option<Slippage>,
+    next_order_id: i64,
+    transfers: Vec<IncomeRecord>,
+}
+
+impl MockState {
+    fn set_price(&mut self, symbol: &str, price: f64) {
+        self.prices.insert(data::normalize(symbol), price);
+    }
+
+    fn script_prices(&mut self, symbol: &str, prices: impl IntoIterator<Item = f64>) {
+        self.scripts
+            .entry(data::normalize(symbol))
+            .or_default()
+            .extend(prices);
+    }
+
+    fn set_warming_up(&mut self, symbol: &str, warming_up: bool) {
+        let symbol = data::normalize(symbol);
+        if warming_up {
+            self.warming_up.insert(symbol);
+        } else {
+            self.warming_up.remove(&symbol);
+        }
+    }
+
+    fn set_symbol_meta(&mut self, meta: SymbolMeta) {
+        self.symbol_meta.insert(meta.symbol.clone(), meta);
+    }
+
+    fn price(&self, symbol: &str) -> Option<f64> {
+        self.prices.get(&data::normalize(symbol)).copied()
+    }
+
+    fn fail_next_order(&mut self, error: &str) {
+        self.failures.push_back(error.to_string());
+    }
+
+    fn partial_fill_next(&mut self, fraction: f64) {
+        self.partial_fills.push_back(fraction.clamp(0.0, 1.0));
+    }
+
+    fn set_slippage(&mut self, max_bps: f64) {
+        self.slippage = Some(Slippage::new(max_bps, "mock_exchange:slippage"));
+    }
+
+    fn slip(&mut self, price: f64, buy: bool) -> f64 {
+        match &mut self.slippage {
+            Some(slippage) => slippage.fill_price(price, buy),
+            None => price,
+        }
+    }
+
+    fn advance(&mut self) -> Vec<MockOrder> {
+        for (symbol, script) in &mut self.scripts {
+            if let Some(price) = script.pop_front() {
+                self.prices.insert(symbol.clone(), price);
+            }
+        }
+
+        let mut triggered: Vec<(String, f64, bool)> = Vec::new();
+        for (symbol, p) in &self.positions {
+            let Some(&price) = self.prices.get(symbol) else {
+                continue;
+            };
+            let long = p.side == "long";
+            let sl_hit = p.stop_loss > 0.0
+                && ((long && price <= p.stop_loss) || (!long && price >= p.stop_loss));
+            let tp_hit = p.take_profit > 0.0
+                && ((long && price >= p.take_profit) || (!long && price <= p.take_profit));
+            if sl_hit {
+                triggered.push((symbol.clone(), p.stop_loss, true));
+            } else if tp_hit {
+                triggered.push((symbol.clone(), p.take_profit, false));
+            }
+        }
+        // Same order every run, whatever the map's iteration order.
+        triggered.sort_by(|a, b| a.0.cmp(&b.0));
+
+        triggered
+            .into_iter()
+            .filter_map(|(symbol, price, stop_loss_hit)| {
+                let mut order = self.reduce(&symbol, price, f64::INFINITY)?;
+                order.stop_loss_hit = stop_loss_hit;
+                if let Some(last) = self.orders.last_mut() {
+                    last.stop_loss_hit = stop_loss_hit;
+                }
+                Some(order)
+            })
+            .collect()
+    }
+
+    fn execute(&mut self, decision: &Decision) -> Result<Option<MockOrder>, String> {
+        if matches!(decision.action, Action::Hold | Action::Wait) {
+            return Ok(None);
+        }
+        let symbol = data::normalize(&decision.symbol);
+        let price = self
+            .price(&symbol)
+            .ok_or_else(|| format!("no price for {}", symbol))?;
+
+        if decision.action.is_open() {
+            if decision.position_size_usd <= 0.0 {
+                return Err("position size must be positive".to_string());
+            }
+            let side = if decision.action == Action::OpenLong {
+                "long"
+            } else {
+                "short"
+            };
+            if self.positions.contains_key(&symbol) {
+                return Err(format!("{} already has an open position", symbol));
+            }
+            let order = self.open(
+                &symbol,
+                side,
+                decision.position_size_usd / price,
+                decision.leverage,
+            )?;
+            self.protect(&symbol, decision.stop_loss, decision.take_profit);
+            Ok(Some(order))
+        } else {
+            let side = if decision.action == Action::CloseLong {
+                "long"
+            } else {
+                "short"
+            };
+            self.close_side(&symbol, side, 0.0).map(Some)
+        }
+    }
+
+    fn open(
+        &mut self,
+        symbol: &str,
+        side: &'static str,
+        quantity: f64,
+        leverage: i32,
+    ) -> Result<MockOrder, String> {
+        if let Some(error) = self.failures.pop_front() {
+            return Err(error);
+        }
+        let symbol = data::normalize(symbol);
+        let price = self
+            .price(&symbol)
+            .ok_or_else(|| format!("no price for {}", symbol))?;
+        if quantity <= 0.0 {
+            return Err("quantity must be positive".to_string());
+        }
+        if self.positions.get(&symbol).is_some_and(|p| p.side != side) {
+            return Err(format!("{} has an opposite position open", symbol));
+        }
+
+        let leverage = leverage.max(1);
+        let quantity = quantity * self.partial_fills.pop_front().unwrap_or(1.0);
+        let price = self.slip(price, side == "long");
+        if quantity * price / leverage as f64 > self.available_balance() {
+            return Err("-2019 margin is insufficient".to_string());
+        }
+
+        let p = self
+            .positions
+            .entry(symbol.clone())
+            .or_insert(MockPosition {
+                side,
+                quantity: 0.0,
+                entry_price: price,
+                leverage,
+                stop_loss: 0.0,
+                take_profit: 0.0,
+            });
+        p.entry_price = (p.entry_price * p.quantity + price * quantity) / (p.quantity + quantity);
+        p.quantity += quantity;
+        let action = if side == "long" {
+            Action::OpenLong
+        } else {
+            Action::OpenShort
+        };
+        Ok(self.fill(&symbol, action, quantity, price, leverage, 0.0))
+    }
+
+    fn close_side(&mut self, symbol: &str, side: &str, quantity: f64) -> Result<MockOrder, String> {
+        if let Some(error) = self.failures.pop_front() {
+            return Err(error);
+        }
+        let symbol = data::normalize(symbol);
+        let price = self
+            .price(&symbol)
+            .ok_or_else(|| format!("no price for {}", symbol))?;
+        let held = match self.positions.get(&symbol) {
+            Some(p) if p.side == side => p.quantity,
+            _ => return Err(format!("no {} position on {}", side, symbol)),
+        };
+        let quantity = if quantity > 0.0 {
+            quantity.min(held)
+        } else {
+            held
+        };
+        let quantity = quantity * self.partial_fills.pop_front().unwrap_or(1.0);
+        let price = self.slip(price, side == "short");
+        self.reduce(&symbol, price, quantity)
+            .ok_or_else(|| format!("no {} position on {}", side, symbol))
+    }
+
+    fn protect(&mut self, symbol: &str, stop_loss: f64, take_profit: f64) {
+        if let Some(p) = self.positions.get_mut(&data::normalize(symbol)) {
+            p.stop_loss = stop_loss;
+            p.take_profit = take_profit;
+        }
+    }
+
+    fn positions(&self) -> Vec<PositionInfo> {
+        let mut positions: Vec<PositionInfo> = self
+            .positions
+            .iter()
+            .map(|(symbol, p)| {
+                let mark = self.prices.get(symbol).copied().unwrap_or(p.entry_price);
+                let pnl = unrealized(p, mark);
+                let margin = p.quantity * p.entry_price / p.leverage as f64;
+                PositionInfo {
+                    symbol: symbol.clone(),
+                    side: p.side.to_string(),
+                    entry_price: p.entry_price,
+                    mark_price: mark,
+                    quantity: p.quantity,
+                    leverage: p.leverage,
+                    unrealized_pnl: pnl,
+                    unrealized_pnl_pct: if margin > 0.0 {
+                        pnl / margin * 100.0
+                    } else {
+                        0.0
+                    },
+                    liquidation_price: 0.0,
+                    margin_used: margin,
+                    update_time: 0,
+                }
+            })
+            .collect();
+        positions.sort_by(|a, b| a.symbol.cmp(&b.symbol));
+        positions
+    }
+
+    fn account(&self) -> AccountInfo {
+        let positions = self.positions();
+        let margin_used: f64 = positions.iter().map(|p| p.margin_used).sum();
+        let unrealized: f64 = positions.iter().map(|p| p.unrealized_pnl).sum();
+        let equity = self.balance + unrealized;
+        AccountInfo {
+            total_equity: equity,
+            available_balance: self.available_balance(),
+            total_pnl: unrealized,
+            total_pnl_pct: 0.0,
+            margin_used,
+            margin_used_pct: if equity > 0.0 {
+                margin_used / equity * 100.0
+            } else {
+                0.0
+            },
+            position_count: positions.len() as i32,
+        }
+    }
+
+    fn orders(&self) -> &[MockOrder] {
+        &self.orders
+    }
+
+    fn available_balance(&self) -> f64 {
+        let margin: f64 = self
+            .positions
+            .values()
+            .map(|p| p.quantity * p.entry_price / p.leverage as f64)
+            .sum();
+        self.balance - margin
+    }
+
+    // Closes up to `quantity` of a position at `price`, realizing its PnL.
+    fn reduce(&mut self, symbol: &str, price: f64, quantity: f64) -> Option<MockOrder> {
+        let p = self.positions.get_mut(symbol)?;
+        let quantity = quantity.min(p.quantity);
+        let closed = MockPosition {
+            quantity,
+            ..p.clone()
+        };
+        p.quantity -= quantity;
+        if p.quantity <= 1e-12 {
+            self.positions.remove(symbol);
+        }
+
+        let pnl = unrealized(&closed, price);
+        self.balance += pnl;
+        let action = if closed.side == "long" {
+            Action::CloseLong
+        } else {
+            Action::CloseShort
+        };
+        Some(self.fill(symbol, action, quantity, price, closed.leverage, pnl))
+    }
+
+    fn fill(
+        &mut self,
+        symbol: &str,
+        action: Action,
+        quantity: f64,
+        price: f64,
+        leverage: i32,
+        realized_pnl: f64,
+    ) -> MockOrder {
+        let order = MockOrder {
+            id: self.next_order_id,
+            symbol: symbol.to_string(),
+            action,
+            quantity,
+            price,
+            leverage,
+            realized_pnl,
+            stop_loss_hit: false,
+        };
+        self.next_order_id += 1;
+        self.orders.push(order.clone());
+        order
+    }
+}
+
+/// A single-account futures exchange with scripted prices. Positions carry
+/// their stop-loss / take-profit and are closed by [`advance`](Self::advance)
+/// when the next scripted price crosses them. Clones share the account, so a
+/// test can keep a handle to one it handed to a [`TraderCycle`].
+#[derive(Debug, Clone)]
+pub struct MockExchange(Arc<Mutex<MockState>>);
+
+impl MockExchange {
+    pub fn new(balance: f64) -> Self {
+        Self(Arc::new(Mutex::new(MockState {
+            balance,
+            next_order_id: 1,
+            ..Default::default()
+        })))
+    }
+
+    fn state(&self) -> MutexGuard<'_, MockState> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn set_price(&mut self, symbol: &str, price: f64) {
+        self.state().set_price(symbol, price);
+    }
+
+    /// Prices the symbol moves through, one per [`advance`](Self::advance).
+    pub fn script_prices(&mut self, symbol: &str, prices: impl IntoIterator<Item = f64>) {
+        self.state().script_prices(symbol, prices);
+    }
+
+    /// Reports the symbol's market data as warming up, as for a fresh listing
+    /// without enough candle history.
+    pub fn set_warming_up(&mut self, symbol: &str, warming_up: bool) {
+        self.state().set_warming_up(symbol, warming_up);
+    }
+
+    /// Order rules reported for the symbol; symbols without any accept any
+    /// quantity.
+    pub fn set_symbol_meta(&mut self, meta: SymbolMeta) {
+        self.state().set_symbol_meta(meta);
+    }
+
+    pub fn price(&self, symbol: &str) -> Option<f64> {
+        self.state().price(symbol)
+    }
+
+    /// Makes the next order fail with `error` (e.g. "timeout", "-2019 margin is insufficient").
+    pub fn fail_next_order(&mut self, error: &str) {
+        self.state().fail_next_order(error);
+    }
+
+    /// Fills only `fraction` (0..1) of the next order.
+    pub fn partial_fill_next(&mut self, fraction: f64) {
+        self.state().partial_fill_next(fraction);
+    }
+
+    /// Fills market orders with random adverse slippage of up to `max_bps`,
+    /// drawn from the [`sim`](crate::sim) seed when one is set.
+    pub fn set_slippage(&mut self, max_bps: f64) {
+        self.state().set_slippage(max_bps);
+    }
+
+    /// Moves every scripted symbol to its next price and closes positions
+    /// whose stop-loss or take-profit was crossed. Returns those closes.
+    pub fn advance(&mut self) -> Vec<MockOrder> {
+        self.state().advance()
+    }
+
+    /// Moves `amount` (negative for a withdrawal) in or out of the wallet,
+    /// reported in the income history with the id `tran_id`.
+    pub fn transfer(&mut self, amount: f64, tran_id: i64) {
+        let mut state = self.state();
+        state.balance += amount;
+        state.transfers.push(IncomeRecord {
+            symbol: String::new(),
+            income_type: performance::INCOME_TRANSFER.to_string(),
+            income: amount.to_string(),
+            asset: "USDT".to_string(),
+            time: Utc::now().timestamp_millis(),
+            tran_id,
+            info: String::new(),
+        });
+    }
+
+    /// Executes one decision at the current price.
+    pub fn execute(&mut self, decision: &Decision) -> Result<Option<MockOrder>, String> {
+        self.state().execute(decision)
+    }
+
+    /// Opens or adds to a position of `quantity` at the current price.
+    pub fn open(
+        &mut self,
+        symbol: &str,
+        side: &'static str,
+        quantity: f64,
+        leverage: i32,
+    ) -> Result<MockOrder, String> {
+        self.state().open(symbol, side, quantity, leverage)
+    }
+
+    /// Closes `quantity` of the position on `side` at the current price; 0 closes all.
+    pub fn close_side(
+        &mut self,
+        symbol: &str,
+        side: &str,
+        quantity: f64,
+    ) -> Result<MockOrder, String> {
+        self.state().close_side(symbol, side, quantity)
+    }
+
+    /// Sets the stop-loss / take-profit of an open position; 0 leaves none.
+    pub fn protect(&mut self, symbol: &str, stop_loss: f64, take_profit: f64) {
+        self.state().protect(symbol, stop_loss, take_profit);
+    }
+
+    pub fn positions(&self) -> Vec<PositionInfo> {
+        self.state().positions()
+    }
+
+    pub fn account(&self) -> AccountInfo {
+        self.state().account()
+    }
+
+    /// Every order filled so far, in order.
+    pub fn orders(&self) -> Vec<MockOrder> {
+        self.state().orders().to_vec()
+    }
+}
+
+fn unrealized(p: &MockPosition, price: f64) -> f64 {
+    if p.side == "long" {
+        p.quantity * (price - p.entry_price)
+    } else {
+        p.quantity * (p.entry_price - price)
+    }
+}
+
+fn order_fill(order: MockOrder, requested_quantity: Decimal) -> OrderFill {
+    OrderFill {
+        order_id: order.id,
+        symbol: order.symbol,
+        requested_quantity,
+        filled_quantity: money::from_f64(order.quantity),
+        price: money::from_f64(order.price),
+        fill_latency_ms: 0,
+    }
+}
+
+impl TradeExecutor for MockExchange {
+    async fn open_long(
+        &mut self,
+        symbol: &str,
+        quantity: Decimal,
+        leverage: i32,
+    ) -> executor::Result<OrderFill> {
+        self.open(symbol, "long", money::to_f64(quantity), leverage)
+            .map(|o| order_fill(o, quantity))
+            .map_err(ExecutorError::Exchange)
+    }
+
+    async fn open_short(
+        &mut self,
+        symbol: &str,
+        quantity: Decimal,
+        leverage: i32,
+    ) -> executor::Result<OrderFill> {
+        self.open(symbol, "short", money::to_f64(quantity), leverage)
+            .map(|o| order_fill(o, quantity))
+            .map_err(ExecutorError::Exchange)
+    }
+
+    async fn close(
+        &mut self,
+        symbol: &str,
+        side: &str,
+        quantity: Decimal,
+    ) -> executor::Result<OrderFill> {
+        let held = self
+            .state()
+            .positions
+            .get(&data::normalize(symbol))
+            .filter(|p| p.side == side)
+            .map_or(Decimal::ZERO, |p| money::from_f64(p.quantity));
+        let requested = if quantity > Decimal::ZERO {
+            quantity.min(held)
+        } else {
+            held
+        };
+        self.close_side(symbol, side, money::to_f64(quantity))
+            .map(|o| order_fill(o, requested))
+            .map_err(ExecutorError::Exchange)
+    }
+
+    async fn set_leverage(&mut self, _symbol: &str, _leverage: i32) -> executor::Result<()> {
+        Ok(())
+    }
+
+    async fn get_positions(&mut self) -> executor::Result<Vec<PositionInfo>> {
+        Ok(self.positions())
+    }
+
+    async fn get_price(&mut self, symbol: &str) -> executor::Result<f64> {
+        self.price(symbol)
+            .ok_or_else(|| ExecutorError::Exchange(format!("no price for {}", symbol)))
+    }
+
+    async fn symbol_meta(&mut self, symbol: &str) -> executor::Result<Option<SymbolMeta>> {
+        Ok(self.state().symbol_meta.get(symbol).cloned())
+    }
+
+    async fn set_protective_orders(
+        &mut self,
+        symbol: &str,
+        _side: &str,
+        _quantity: Decimal,
+        stop_loss: f64,
+        take_profit: f64,
+    ) -> executor::Result<()> {
+        self.protect(symbol, stop_loss, take_profit);
+        Ok(())
+    }
+}
+
+impl Venue for MockExchange {
+    async fn get_balance(&mut self) -> executor::Result<Balance> {
+        let state = self.state();
+        Ok(Balance {
+            wallet: state.balance,
+            available: state.available_balance(),
+        })
+    }
+
+    async fn get_transfers(
+        &mut self,
+        start_time: Option<i64>,
+    ) -> executor::Result<Vec<IncomeRecord>> {
+        let state = self.state();
+        Ok(state
+            .transfers
+            .iter()
+            .filter(|r| start_time.is_none_or(|start| r.time >= start))
+            .cloned()
+            .collect())
+    }
+
+    async fn get_market_data(&mut self, symbol: &str) -> Result<Data, MarketError> {
+        let warming_up = self.state().warming_up.contains(&data::normalize(symbol));
+        self.price(symbol)
+            .map(|p| Data {
+                warming_up,
+                ..mock_data(symbol, p)
+            })
+            .ok_or_else(|| MarketError::InsufficientData(format!("no price for {}", symbol)))
+    }
+
+    async fn get_market_data_many(
+        &mut self,
+        symbols: &[String],
+    ) -> HashMap<String, Result<Data, MarketError>> {
+        let mut data = HashMap::new();
+        for symbol in symbols {
+            data.insert(data::normalize(symbol), self.get_market_data(symbol).await);
+        }
+        data
+    }
+
+    async fn warm_up(&mut self, symbol: &str) -> Result<Data, MarketError> {
+        self.get_market_data(symbol).await
+    }
+
+    async fn get_timeframes(
+        &mut self,
+        symbol: &str,
+        intervals: &[String],
+    ) -> BTreeMap<String, TimeframeData> {
+        self.price(symbol)
+            .map(|p| mock_timeframes(intervals, p))
+            .unwrap_or_default()
+    }
+}
+
+/// A prompt the mock AI was called with.
+#[derive(Debug, Clone)]
+pub struct AiCall {
+    pub system_prompt: String,
+    pub user_prompt: String,
+}
+
+#[derive(Debug, Default)]
+struct MockAiState {
+    name: String,
+    responses: VecDeque<Result<String, String>>,
+    calls: Vec<AiCall>,
+}
+
+/// An AI provider that replays scripted responses in order. When the script
+/// runs out it answers with an empty decision list. Clones share the script
+/// and the calls, like [`MockExchange`].
+#[derive(Debug, Clone, Default)]
+pub struct MockAiProvider(Arc<Mutex<MockAiState>>);
+
+impl MockAiProvider {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A provider that shows up as `name` in tournament standings.
+    pub fn named(name: &str) -> Self {
+        let mut provider = Self::default();
+        provider.set_name(name);
+        provider
+    }
+
+    /// Renames the provider, e.g. the one a [`Harness`] already runs.
+    pub fn set_name(&mut self, name: &str) {
+        self.state().name = name.to_string();
+    }
+
+    fn state(&self) -> MutexGuard<'_, MockAiState> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Queues a raw model response (reasoning text and/or a JSON decision array).
+    pub fn push_response(&mut self, text: &str) {
+        self.state().responses.push_back(Ok(text.to_string()));
+    }
+
+    /// Queues a response consisting of these decisions as JSON.
+    pub fn push_decisions(&mut self, decisions: &[Decision]) {
+        let json = serde_json::to_string(decisions).expect("decisions serialize");
+        self.state().responses.push_back(Ok(json));
+    }
+
+    /// Queues a failed call.
+    pub fn push_error(&mut self, error: &str) {
+        self.state().responses.push_back(Err(error.to_string()));
+    }
+
+    pub fn call(&mut self, system_prompt: &str, user_prompt: &str) -> Result<String, String> {
+        let mut state = self.state();
+        state.calls.push(AiCall {
+            system_prompt: system_prompt.to_string(),
+            user_prompt: user_prompt.to_string(),
+        });
+        state
+            .responses
+            .pop_front()
+            .unwrap_or_else(|| Ok("[]".to_string()))
+    }
+
+    pub fn calls(&self) -> Vec<AiCall> {
+        self.state().calls.clone()
+    }
+}
+
+impl AiProvider for MockAiProvider {
+    fn name(&self) -> String {
+        let name = &self.state().name;
+        if name.is_empty() {
+            "mock".to_string()
+        } else {
+            name.clone()
+        }
+    }
+
+    fn chat_completion<'a>(
+        &'a mut self,
+        system_prompt: &'a str,
+        user_prompt: &'a str,
+    ) -> AiFuture<'a, String> {
+        let response = self
+            .call(system_prompt, user_prompt)
+            .map_err(|body| AiError::Api { status: 500, body });
+        Box::pin(async move { response })
+    }
+}
+
+/// A fresh, private in-memory database with the full schema.
+pub async fn memory_db() -> anyhow::Result<Database> {
+    // A named shared-cache database so every pooled connection sees the same data.
+    let url = format!(
+        "sqlite:file:testkit-{}?mode=memory&cache=shared",
+        Uuid::new_v4()
+    );
+    Database::new(&url).await
+}
+
+/// A token for the built-in admin user, for requests to admin routes. Sets a
+/// JWT secret if none is set yet.
+pub fn admin_token() -> String {
+    auth::set_jwt_secret("testkit-secret");
+    auth::generate_jwt("admin", "admin@localhost", Role::Admin).expect("admin token")
+}
+
+/// Flat market data for `symbol` at `price`, enough for prompt formatting.
+pub fn mock_data(symbol: &str, price: f64) -> Data {
+    Data {
+        symbol: data::normalize(symbol),
+        current_price: price,
+        price_change_1h: 0.0,
+        price_change_4h: 0.0,
+        current_ema20: price,
+        current_macd: 0.0,
+        current_rsi7: 50.0,
+        open_interest: None,
+        funding_rate: 0.0,
+        intraday_series: None,
+        longer_term_context: None,
+        source: MarketDataSource::Binance,
+        degraded: false,
+        warming_up: false,
+        timeframes: BTreeMap::new(),
+    }
+}
+
+/// Flat data at `price` for each of `intervals`.
+pub fn mock_timeframes(intervals: &[String], price: f64) -> BTreeMap<String, TimeframeData> {
+    intervals
+        .iter()
+        .map(|interval| {
+            let data = TimeframeData {
+                interval: interval.clone(),
+                closes: vec![price],
+                ema20: price,
+                ema50: price,
+                rsi14: 50.0,
+                ..Default::default()
+            };
+            (interval.clone(), data)
+        })
+        .collect()
+}
+
+/// What happened in one [`Harness::run_cycle`].
+#[derive(Debug, Default)]
+pub struct CycleOutcome {
+    /// Protective closes triggered when prices moved at the start of the cycle.
+    pub protective_closes: Vec<MockOrder>,
+    /// Decisions the AI proposed.
+    pub proposed: Vec<Decision>,
+    /// Decisions left after the risk filters.
+    pub approved: Vec<Decision>,
+    pub filled: Vec<MockOrder>,
+    /// Failed orders, then the cycle's error if it failed.
+    pub errors: Vec<String>,
+    /// AI decisions dropped by parsing and validation.
+    pub rejected: Vec<DecisionError>,
+    /// Id of the decision record written for the cycle.
+    pub record_id: String,
+    /// Watch-only: trades the account owner made since the previous cycle.
+    pub observed: Vec<ObservedTrade>,
+    /// Watch-only: how the previous cycle's suggestions compare with `observed`.
+    pub comparison: Option<Comparison>,
+}
+
+/// One trader run by the live engine's [`TraderCycle`] against a mock
+/// exchange, a mock AI, an in-memory database and a throwaway decision log
+/// directory. `exchange` and `ai` are handles to the ones the cycle uses.
+pub struct Harness {
+    pub db: Database,
+    pub exchange: MockExchange,
+    pub ai: MockAiProvider,
+    /// Reads the decision log the cycle writes.
+    pub logger: DecisionLogger,
+    pub user_id: String,
+    /// Changes take effect from the next cycle.
+    pub trader: TraderRecord,
+    /// Combined margin ceiling for the user, in percent of equity; 0 disables.
+    pub max_margin_usage_pct: f64,
+    cycle: TraderCycle<MockExchange>,
+    log_dir: PathBuf,
+}
+
+impl Harness {
+    /// A harness with a running trader on `symbols` and `balance` USDT.
+    pub async fn new(symbols: &[&str], balance: f64) -> anyhow::Result<Self> {
+        let db = memory_db().await?;
+        let user_id = format!("user-{}", Uuid::new_v4());
+        db.create_user(&User {
+            id: user_id.clone(),
+            email: format!("{}@testkit.local", user_id),
+            ..Default::default()
+        })
+        .await?;
+        let trader = TraderRecord {
+            id: format!("trader-{}", Uuid::new_v4()),
+            user_id: user_id.clone(),
+            name: "testkit".to_string(),
+            ai_model_id: "deepseek".to_string(),
+            exchange_id: "binance".to_string(),
+            initial_balance: balance,
+            scan_interval_minutes: 3,
+            is_running: true,
+            btc_eth_leverage: 5,
+            altcoin_leverage: 5,
+            trading_symbols: symbols.join(","),
+            stop_loss_cooldown_minutes: 30,
+            ..Default::default()
+        };
+        db.create_trader(&trader).await?;
+
+        let log_dir = std::env::temp_dir().join(format!("aitrading-testkit-{}", Uuid::new_v4()));
+        let config = RunnerConfig {
+            log_dir: log_dir.to_string_lossy().to_string(),
+            ..Default::default()
+        };
+        let exchange = MockExchange::new(balance);
+        let ai = MockAiProvider::new();
+        let cycle = TraderCycle::new(
+            db.clone(),
+            trader.clone(),
+            exchange.clone(),
+            Box::new(ai.clone()),
+            config.logger(&trader),
+            &config,
+        );
+        let logger = DecisionLogger::new(&log_dir.join(&trader.id).to_string_lossy());
+
+        Ok(Self {
+            db,
+            exchange,
+            ai,
+            logger,
+            user_id,
+            trader,
+            max_margin_usage_pct: 0.0,
+            cycle,
+            log_dir,
+        })
+    }
+
+    /// Moves prices (triggering protective orders on the exchange), then lets
+    /// the trader's task take one tick: reconcile, build the prompt, ask the
+    /// AI, apply the risk filters, execute, and log.
+    pub async fn run_cycle(&mut self) -> anyhow::Result<CycleOutcome> {
+        let protective_closes = self.exchange.advance();
+        *self.cycle.trader_mut() = self.trader.clone();
+        self.db
+            .update_user_margin_ceiling(&self.user_id, self.max_margin_usage_pct)
+            .await?;
+
+        let orders_before = self.exchange.orders().len();
+        let report = self.cycle.tick().await?;
+        let mut errors: Vec<String> = report
+            .executions
+            .iter()
+            .filter_map(|e| e.result.as_ref().err().map(ToString::to_string))
+            .collect();
+        if !report.record.is_success() {
+            errors.push(report.record.error_message().to_string());
+        }
+        Ok(CycleOutcome {
+            protective_closes,
+            proposed: report.proposed,
+            approved: report.approved,
+            filled: self.exchange.orders().split_off(orders_before),
+            errors,
+            rejected: report.rejected,
+            record_id: report.record.id().to_string(),
+            observed: report.observed,
+            comparison: report.comparison,
+        })
+    }
+}
+impl Drop for Harness {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.log_dir);
+    }
+}
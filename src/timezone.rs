@@ -0,0 +1,155 @@
+//! Per-user timezone handling.
+//!
+//! Everything is stored and computed in UTC; a user's timezone only decides
+//! where "today" starts (daily loss limits, daily summaries), how timestamps are
+//! shown in reports, and whether a trading session window is open.
+
+use chrono::{DateTime, Datelike, Days, NaiveDate, NaiveTime, TimeZone, Utc, Weekday};
+use chrono_tz::Tz;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use thiserror::Error;
+
+use crate::decision::Decision;
+
+#[derive(Error, Debug)]
+pub enum TimezoneError {
+    #[error("Unknown timezone '{0}', expected an IANA name such as 'Asia/Shanghai'")]
+    Unknown(String),
+}
+
+/// Parses an IANA timezone name.
+pub fn parse_tz(name: &str) -> Result<Tz, TimezoneError> {
+    name.trim()
+        .parse::<Tz>()
+        .map_err(|_| TimezoneError::Unknown(name.to_string()))
+}
+
+/// Like [`parse_tz`], but falls back to UTC for empty or unknown names.
+pub fn tz_or_utc(name: &str) -> Tz {
+    parse_tz(name).unwrap_or(Tz::UTC)
+}
+
+/// The user's calendar date at `at`.
+pub fn local_date(tz: Tz, at: DateTime<Utc>) -> NaiveDate {
+    at.with_timezone(&tz).date_naive()
+}
+
+/// Start (inclusive) and end (exclusive) of the local day containing `at`, in UTC.
+pub fn day_bounds(tz: Tz, at: DateTime<Utc>) -> (DateTime<Utc>, DateTime<Utc>) {
+    let date = local_date(tz, at);
+    let next = date.checked_add_days(Days::new(1)).unwrap_or(date);
+    (local_midnight(tz, date), local_midnight(tz, next))
+}
+
+// Midnight can be skipped by a DST change in a few zones; use the first
+// instant that exists on that date in that case.
+fn local_midnight(tz: Tz, date: NaiveDate) -> DateTime<Utc> {
+    let mut naive = date.and_time(NaiveTime::MIN);
+    for _ in 0..4 {
+        if let Some(dt) = tz.from_local_datetime(&naive).earliest() {
+            return dt.with_timezone(&Utc);
+        }
+        naive += chrono::Duration::minutes(30);
+    }
+    Utc.from_utc_datetime(&date.and_time(NaiveTime::MIN))
+}
+
+/// Formats `at` in the user's timezone for reports.
+pub fn format_local(tz: Tz, at: DateTime<Utc>) -> String {
+    at.with_timezone(&tz)
+        .format("%Y-%m-%d %H:%M:%S %Z")
+        .to_string()
+}
+
+/// A recurring window in local time during which a trader may open positions,
+/// e.g. `{"days": ["Mon", "Tue"], "start": "09:30", "end": "16:00"}`.
+/// `end` before `start` means the window runs past midnight; no days means every day.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionWindow {
+    #[serde(default)]
+    pub days: Vec<Weekday>,
+    #[serde(with = "hhmm")]
+    pub start: NaiveTime,
+    #[serde(with = "hhmm")]
+    pub end: NaiveTime,
+}
+
+impl SessionWindow {
+    pub fn contains(&self, tz: Tz, at: DateTime<Utc>) -> bool {
+        let local = at.with_timezone(&tz);
+        let time = local.time();
+        let today = local.weekday();
+
+        if self.start <= self.end {
+            self.on_day(today) && time >= self.start && time < self.end
+        } else {
+            // Overnight: the evening part belongs to today, the morning part to yesterday.
+            (self.on_day(today) && time >= self.start)
+                || (self.on_day(today.pred()) && time < self.end)
+        }
+    }
+
+    fn on_day(&self, day: Weekday) -> bool {
+        self.days.is_empty() || self.days.contains(&day)
+    }
+}
+
+/// True when no windows are configured or any of them is open.
+pub fn in_session(windows: &[SessionWindow], tz: Tz, at: DateTime<Utc>) -> bool {
+    windows.is_empty() || windows.iter().any(|w| w.contains(tz, at))
+}
+
+/// Parses a trader's stored windows, a JSON array of [`SessionWindow`]s;
+/// an empty string means none.
+pub fn parse_windows(json: &str) -> Result<Vec<SessionWindow>, serde_json::Error> {
+    if json.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+    serde_json::from_str(json)
+}
+
+/// Drops opening decisions while no window is open. Closes always go through.
+pub fn drop_entries_outside_session(
+    windows: &[SessionWindow],
+    tz: Tz,
+    at: DateTime<Utc>,
+    decisions: Vec<Decision>,
+) -> Vec<Decision> {
+    if in_session(windows, tz, at) {
+        return decisions;
+    }
+    decisions
+        .into_iter()
+        .filter(|d| {
+            if d.action.is_open() {
+                tracing::warn!("🕘 不在交易时段内，拒绝开仓 {} {:?}", d.symbol, d.action);
+            }
+            !d.action.is_open()
+        })
+        .collect()
+}
+
+/// A line for the prompt while outside every trading window.
+pub fn prompt_annotation(windows: &[SessionWindow], tz: Tz, at: DateTime<Utc>) -> Option<String> {
+    (!in_session(windows, tz, at)).then(|| {
+        format!(
+            "Outside the trading session windows ({} local time): do not open new positions, only manage existing ones.",
+            at.with_timezone(&tz).format("%a %H:%M")
+        )
+    })
+}
+
+mod hhmm {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(time: &NaiveTime, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_str(&time.format("%H:%M").to_string())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<NaiveTime, D::Error> {
+        let s = String::deserialize(d)?;
+        NaiveTime::parse_from_str(&s, "%H:%M")
+            .or_else(|_| NaiveTime::parse_from_str(&s, "%H:%M:%S"))
+            .map_err(serde::de::Error::custom)
+    }
+}
@@ -0,0 +1,418 @@
+//! Scheduled model evaluation tournaments.
+//!
+//! Every decision cycle can freeze its prompts and the prices it saw as an
+//! evaluation scenario. Once the scoring horizon has passed, the scenario is
+//! settled with the prices at that point. A tournament then replays the same
+//! settled scenarios through every contestant model in dry-run, never touching
+//! an exchange, and scores what each one would have done: an open scores the
+//! unleveraged price move in its direction, a close scores the move it avoided.
+//! Hold and wait decisions score nothing. Stop-loss and take-profit levels are
+//! ignored, only the price at the horizon counts. Each standing also shows the
+//! model's live hit rate from [`accuracy`] scoring of its real calls.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::accuracy;
+use crate::ai::{self, AiProvider};
+use crate::data;
+use crate::database::{Database, EvalScenario};
+use crate::decision::{self, Action, Decision};
+use crate::types::Data;
+
+#[derive(Error, Debug)]
+pub enum TournamentError {
+    #[error("Database error: {0}")]
+    Database(#[from] anyhow::Error),
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+pub type Result<T> = std::result::Result<T, TournamentError>;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[serde(default)]
+pub struct TournamentParams {
+    /// How long after capture a scenario is settled and scored.
+    #[serde(with = "humantime_serde")]
+    pub horizon: Duration,
+    /// Most recent settled scenarios replayed per tournament.
+    pub scenarios: usize,
+    /// Scenarios older than this are deleted.
+    #[serde(with = "humantime_serde")]
+    pub retention: Duration,
+    /// Window of live calls behind each standing's live hit rate.
+    #[serde(with = "humantime_serde")]
+    pub live_window: Duration,
+}
+
+impl Default for TournamentParams {
+    fn default() -> Self {
+        Self {
+            horizon: Duration::from_secs(4 * 3600),
+            scenarios: 50,
+            retention: Duration::from_secs(14 * 86400),
+            live_window: Duration::from_secs(7 * 86400),
+        }
+    }
+}
+
+/// A frozen decision input and the prices around it.
+#[derive(Debug, Clone)]
+pub struct Scenario {
+    pub id: String,
+    pub captured_at: DateTime<Utc>,
+    pub system_prompt: String,
+    pub user_prompt: String,
+    pub entry_prices: HashMap<String, f64>,
+    /// Prices at the horizon; `None` until settled.
+    pub exit_prices: Option<HashMap<String, f64>>,
+}
+
+impl Scenario {
+    fn from_row(row: &EvalScenario) -> Result<Self> {
+        Ok(Self {
+            id: row.id.clone(),
+            captured_at: row.captured_at,
+            system_prompt: row.system_prompt.clone(),
+            user_prompt: row.user_prompt.clone(),
+            entry_prices: serde_json::from_str(&row.entry_prices)?,
+            exit_prices: row
+                .exit_prices
+                .as_deref()
+                .map(serde_json::from_str)
+                .transpose()?,
+        })
+    }
+}
+
+/// Freezes one cycle's prompts and prices as an evaluation scenario.
+pub async fn capture(
+    db: &Database,
+    user_id: &str,
+    trader_id: &str,
+    system_prompt: &str,
+    user_prompt: &str,
+    market_data: &HashMap<String, Data>,
+) -> Result<()> {
+    let prices: HashMap<&str, f64> = market_data
+        .iter()
+        .filter(|(_, d)| d.current_price > 0.0)
+        .map(|(symbol, d)| (symbol.as_str(), d.current_price))
+        .collect();
+    if prices.is_empty() {
+        return Ok(());
+    }
+    db.save_eval_scenario(&EvalScenario {
+        id: Uuid::new_v4().to_string(),
+        user_id: user_id.to_string(),
+        trader_id: trader_id.to_string(),
+        captured_at: Utc::now(),
+        system_prompt: system_prompt.to_string(),
+        user_prompt: user_prompt.to_string(),
+        entry_prices: serde_json::to_string(&prices)?,
+        exit_prices: None,
+        settled_at: None,
+    })
+    .await?;
+    Ok(())
+}
+
+/// Settles every scenario older than the horizon with current prices and
+/// prunes expired ones. Returns how many were settled.
+pub async fn settle_due(db: &Database, params: &TournamentParams) -> Result<usize> {
+    let horizon = chrono::Duration::from_std(params.horizon).unwrap_or_default();
+    let due = db
+        .get_unsettled_eval_scenarios(Utc::now() - horizon)
+        .await?;
+
+    let mut prices: HashMap<String, Option<f64>> = HashMap::new();
+    let mut settled = 0;
+    for row in &due {
+        let scenario = Scenario::from_row(row)?;
+        let mut exit = HashMap::new();
+        for symbol in scenario.entry_prices.keys() {
+            if !prices.contains_key(symbol) {
+                let price = match data::get(symbol).await {
+                    Ok(d) => Some(d.current_price),
+                    Err(e) => {
+                        tracing::warn!("⚠️ 评测场景结算时获取 {} 价格失败: {}", symbol, e);
+                        None
+                    }
+                };
+                prices.insert(symbol.clone(), price);
+            }
+            if let Some(price) = prices[symbol] {
+                exit.insert(symbol.clone(), price);
+            }
+        }
+        if exit.is_empty() {
+            continue;
+        }
+        db.settle_eval_scenario(&scenario.id, &serde_json::to_string(&exit)?)
+            .await?;
+        settled += 1;
+    }
+
+    let retention = chrono::Duration::from_std(params.retention).unwrap_or_default();
+    let pruned = db.prune_eval_scenarios(Utc::now() - retention).await?;
+    if settled > 0 || pruned > 0 {
+        tracing::info!("🏁 评测场景已结算 {} 个，清理过期 {} 个", settled, pruned);
+    }
+    Ok(settled)
+}
+
+/// Score of one model's decisions on one scenario.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ScenarioScore {
+    /// Opens and closes that could be scored.
+    pub trades: u32,
+    /// Trades that scored above zero.
+    pub wins: u32,
+    /// Sum of the trades' scores, in percent.
+    pub return_pct: f64,
+}
+
+/// Scores decisions against the price moves between entry and exit.
+pub fn score(
+    decisions: &[Decision],
+    entry_prices: &HashMap<String, f64>,
+    exit_prices: &HashMap<String, f64>,
+) -> ScenarioScore {
+    let mut score = ScenarioScore::default();
+    for d in decisions {
+        let symbol = data::normalize(&d.symbol);
+        let (Some(&entry), Some(&exit)) = (entry_prices.get(&symbol), exit_prices.get(&symbol))
+        else {
+            continue;
+        };
+        if entry <= 0.0 {
+            continue;
+        }
+        let move_pct = (exit - entry) / entry * 100.0;
+        let pct = match d.action {
+            Action::OpenLong | Action::CloseShort => move_pct,
+            Action::OpenShort | Action::CloseLong => -move_pct,
+            Action::Hold | Action::Wait => continue,
+        };
+        score.trades += 1;
+        if pct > 0.0 {
+            score.wins += 1;
+        }
+        score.return_pct += pct;
+    }
+    score
+}
+
+/// One model's results over all scenarios of a tournament.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Standing {
+    pub model: String,
+    pub scenarios: u32,
+    /// Scenarios where the call failed or the response held no decisions.
+    pub failures: u32,
+    pub trades: u32,
+    pub wins: u32,
+    pub total_return_pct: f64,
+    /// The model's scored calls in live trading, and how many were right.
+    #[serde(default)]
+    pub live_calls: u32,
+    #[serde(default)]
+    pub live_hits: u32,
+}
+
+impl Standing {
+    pub fn hit_rate(&self) -> f64 {
+        if self.trades == 0 {
+            0.0
+        } else {
+            self.wins as f64 / self.trades as f64 * 100.0
+        }
+    }
+
+    pub fn avg_return_pct(&self) -> f64 {
+        if self.trades == 0 {
+            0.0
+        } else {
+            self.total_return_pct / self.trades as f64
+        }
+    }
+
+    /// Live hit rate in percent, `None` without scored live calls.
+    pub fn live_hit_rate(&self) -> Option<f64> {
+        (self.live_calls > 0).then(|| self.live_hits as f64 / self.live_calls as f64 * 100.0)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Report {
+    pub run_at: DateTime<Utc>,
+    pub scenarios: usize,
+    /// Best total return first.
+    pub standings: Vec<Standing>,
+}
+
+impl Report {
+    /// The standings as a plain-text table.
+    pub fn table(&self) -> String {
+        let mut out = format!(
+            "{:<24} {:>9} {:>8} {:>7} {:>8} {:>10} {:>10} {:>8}\n",
+            "model", "scenarios", "failures", "trades", "hit %", "avg %", "total %", "live %"
+        );
+        for s in &self.standings {
+            let live = s
+                .live_hit_rate()
+                .map_or("-".to_string(), |r| format!("{:.1}", r));
+            out.push_str(&format!(
+                "{:<24} {:>9} {:>8} {:>7} {:>8.1} {:>10.3} {:>10.2} {:>8}\n",
+                s.model,
+                s.scenarios,
+                s.failures,
+                s.trades,
+                s.hit_rate(),
+                s.avg_return_pct(),
+                s.total_return_pct,
+                live
+            ));
+        }
+        out
+    }
+}
+
+/// Replays settled scenarios through every contestant. Unsettled scenarios
+/// are skipped.
+pub async fn run(contestants: &mut [Box<dyn AiProvider>], scenarios: &[Scenario]) -> Report {
+    let settled: Vec<(&Scenario, &HashMap<String, f64>)> = scenarios
+        .iter()
+        .filter_map(|s| s.exit_prices.as_ref().map(|exit| (s, exit)))
+        .collect();
+
+    let mut standings = Vec::with_capacity(contestants.len());
+    for contestant in contestants.iter_mut() {
+        let mut standing = Standing {
+            model: contestant.name(),
+            ..Default::default()
+        };
+        for (scenario, exit) in &settled {
+            standing.scenarios += 1;
+            let decisions = match contestant
+                .chat_completion(&scenario.system_prompt, &scenario.user_prompt)
+                .await
+                .map_err(|e| e.to_string())
+                .and_then(|response| {
+                    decision::parse_decisions(&response).map_err(|e| e.to_string())
+                }) {
+                Ok(decisions) => decisions,
+                Err(e) => {
+                    tracing::warn!(
+                        "⚠️ 模型 {} 在评测场景 {} 中失败: {}",
+                        standing.model,
+                        scenario.id,
+                        e
+                    );
+                    standing.failures += 1;
+                    continue;
+                }
+            };
+            let s = score(&decisions, &scenario.entry_prices, exit);
+            standing.trades += s.trades;
+            standing.wins += s.wins;
+            standing.total_return_pct += s.return_pct;
+        }
+        standings.push(standing);
+    }
+    standings.sort_by(|a, b| {
+        b.total_return_pct
+            .total_cmp(&a.total_return_pct)
+            .then_with(|| a.model.cmp(&b.model))
+    });
+
+    Report {
+        run_at: Utc::now(),
+        scenarios: settled.len(),
+        standings,
+    }
+}
+
+/// The user's `limit` most recent settled scenarios.
+pub async fn settled_scenarios(
+    db: &Database,
+    user_id: &str,
+    limit: usize,
+) -> Result<Vec<Scenario>> {
+    db.get_settled_eval_scenarios(user_id, limit as i64)
+        .await?
+        .iter()
+        .map(Scenario::from_row)
+        .collect()
+}
+
+/// Runs a tournament over the user's most recent settled scenarios and stores
+/// the report.
+pub async fn run_for_user(
+    db: &Database,
+    user_id: &str,
+    contestants: &mut [Box<dyn AiProvider>],
+    params: &TournamentParams,
+) -> Result<Report> {
+    let scenarios = settled_scenarios(db, user_id, params.scenarios).await?;
+    let mut report = run(contestants, &scenarios).await;
+    let since = Utc::now() - chrono::Duration::from_std(params.live_window).unwrap_or_default();
+    match accuracy::stats(db, user_id, None, since).await {
+        Ok(live) => {
+            for standing in &mut report.standings {
+                if let Some(rate) = live.model(&standing.model) {
+                    standing.live_calls = rate.calls;
+                    standing.live_hits = rate.hits;
+                }
+            }
+        }
+        Err(e) => tracing::warn!("⚠️ 读取模型实盘命中率失败: {}", e),
+    }
+    db.save_tournament_report(user_id, report.run_at, &serde_json::to_string(&report)?)
+        .await?;
+    tracing::info!(
+        "🏆 用户 {} 的模型评测完成（{} 个场景）\n{}",
+        user_id,
+        report.scenarios,
+        report.table()
+    );
+    Ok(report)
+}
+
+/// The user's most recent tournament report.
+pub async fn latest_report(db: &Database, user_id: &str) -> Result<Option<Report>> {
+    match db.get_latest_tournament_report(user_id).await? {
+        Some(json) => Ok(Some(serde_json::from_str(&json)?)),
+        None => Ok(None),
+    }
+}
+
+/// Runs a tournament for every user between all their enabled AI models.
+/// Users with no settled scenarios or no usable model are skipped.
+pub async fn run_all(db: &Database, params: &TournamentParams) -> Result<usize> {
+    let mut runs = 0;
+    for user_id in db.get_all_users_id().await? {
+        let mut contestants: Vec<Box<dyn AiProvider>> = Vec::new();
+        for model in db.get_aimodels(&user_id).await? {
+            if !model.enabled {
+                continue;
+            }
+            match ai::from_model_config(&model) {
+                Ok(provider) => contestants.push(provider),
+                Err(e) => tracing::warn!("⚠️ 模型 {} 无法参加评测: {}", model.id, e),
+            }
+        }
+        if contestants.is_empty() || db.get_settled_eval_scenarios(&user_id, 1).await?.is_empty() {
+            continue;
+        }
+        run_for_user(db, &user_id, &mut contestants, params).await?;
+        runs += 1;
+    }
+    Ok(runs)
+}
@@ -1,8 +1,14 @@
 use chrono::{DateTime, Utc};
 use once_cell::sync::Lazy;
-use serde::{Deserialize, Serialize};
+use serde::de::{self, MapAccess, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::BTreeMap;
+use std::fmt;
 use std::time::Duration;
 
+use crate::indicators::Bands;
+use crate::money::Decimal;
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Data {
     pub symbol: String,
@@ -19,6 +25,32 @@ pub struct Data {
     pub intraday_series: Option<IntradayData>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub longer_term_context: Option<LongerTermData>,
+    /// Venue the data was fetched from.
+    #[serde(default)]
+    pub source: MarketDataSource,
+    /// Set when the data did not come from the primary venue; consumers should
+    /// restrict themselves to managing existing positions.
+    #[serde(default)]
+    pub degraded: bool,
+    /// Set while there are too few candles to seed every indicator, e.g. right
+    /// after a listing; new positions must wait until it clears.
+    #[serde(default)]
+    pub warming_up: bool,
+    /// Extra timeframes the trader asked for, keyed by interval ("15m", "1d").
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub timeframes: BTreeMap<String, TimeframeData>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MarketDataSource {
+    #[default]
+    Binance,
+    Bybit,
+    Okx,
+    Snapshot,
+    Hyperliquid,
+    Aster,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -35,6 +67,17 @@ pub struct IntradayData {
     pub macd_values: Vec<f64>,
     pub rsi7_values: Vec<f64>,
     pub rsi14_values: Vec<f64>,
+    /// Bollinger Bands (20, 2).
+    #[serde(default)]
+    pub bollinger_values: Vec<Bands>,
+    /// Stochastic %K (14) and %D (3).
+    #[serde(default)]
+    pub stoch_k_values: Vec<f64>,
+    #[serde(default)]
+    pub stoch_d_values: Vec<f64>,
+    /// Session VWAP, reset at 00:00 UTC.
+    #[serde(default)]
+    pub vwap_values: Vec<f64>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, Default)]
@@ -48,6 +91,56 @@ pub struct LongerTermData {
     pub average_volume: f64,
     pub macd_values: Vec<f64>,
     pub rsi14_values: Vec<f64>,
+    /// Latest Bollinger Bands (20, 2); zero until seeded.
+    #[serde(default)]
+    pub bollinger: Bands,
+    #[serde(default)]
+    pub stoch_k: f64,
+    #[serde(default)]
+    pub stoch_d: f64,
+    /// Session VWAP, reset at 00:00 UTC.
+    #[serde(default)]
+    pub vwap: f64,
+}
+
+/// Indicators over one extra timeframe, for strategies that want a
+/// granularity other than the 3m series and 4h context.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct TimeframeData {
+    pub interval: String,
+    /// Change over the last closed candle, in percent.
+    pub price_change: f64,
+    /// Closes of the last 10 candles, oldest first.
+    pub closes: Vec<f64>,
+    pub ema20: f64,
+    pub ema50: f64,
+    pub macd: f64,
+    pub rsi14: f64,
+    pub atr14: f64,
+    pub bollinger: Bands,
+    pub stoch_k: f64,
+    pub stoch_d: f64,
+    /// Set while there are too few candles to seed every indicator.
+    pub warming_up: bool,
+}
+
+/// One entry of the futures income history (`/fapi/v1/income`).
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct IncomeRecord {
+    #[serde(default)]
+    pub symbol: String,
+    /// TRANSFER, REALIZED_PNL, FUNDING_FEE, COMMISSION, ...
+    pub income_type: String,
+    /// Signed amount as a decimal string.
+    pub income: String,
+    pub asset: String,
+    /// Milliseconds since the epoch.
+    pub time: i64,
+    pub tran_id: i64,
+    #[serde(default)]
+    pub info: String,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -65,16 +158,38 @@ pub struct SymbolInfo {
     pub contract_type: String,
     pub price_precision: i32,
     pub quantity_precision: i32,
+    /// Listing time in milliseconds since the epoch.
+    #[serde(default)]
+    pub onboard_date: i64,
+    /// Initial margin percent of the first leverage bracket, e.g. "5.0000" for 20x.
+    #[serde(default)]
+    pub required_margin_percent: String,
+    #[serde(default)]
+    pub filters: Vec<SymbolFilter>,
 }
 
-/// Represents a single Kline (candlestick). Note: Binance often sends this
-/// as a JSON array, not an object. If so, a custom deserializer would be needed.
-/// This struct assumes a JSON object response as defined by the Go struct tags.
-#[derive(Debug, Serialize, Deserialize, Clone)]
+/// One of a symbol's order filters; only the fields we use.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 #[serde(rename_all = "camelCase")]
+pub struct SymbolFilter {
+    pub filter_type: String,
+    /// Minimum order value of a `MIN_NOTIONAL` filter.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notional: Option<String>,
+}
+
+/// A single candlestick.
+///
+/// Serializes as a camelCase object. Deserializes from that object or from
+/// the array Binance's REST API returns
+/// (`[openTime, "open", "high", "low", "close", "volume", closeTime,
+/// "quoteVolume", trades, "takerBuyBase", "takerBuyQuote", "ignore"]`), with
+/// prices and volumes given as decimal strings or numbers.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(remote = "Self", rename_all = "camelCase")]
 pub struct Kline {
     pub open_time: i64,
-    pub open: f64, // Prices/volumes are often strings to avoid precision loss
+    pub open: f64,
     pub high: f64,
     pub low: f64,
     pub close: f64,
@@ -86,40 +201,354 @@ pub struct Kline {
     pub taker_buy_quote_volume: f64,
 }
 
-impl From<Vec<serde_json::Value>> for Kline {
-    fn from(value: Vec<serde_json::Value>) -> Self {
-        fn parse_val<T: std::str::FromStr>(val: &serde_json::Value) -> T {
-            val.as_str()
-                .unwrap_or("0")
-                .parse()
-                .unwrap_or_else(|_| T::from_str("0").ok().unwrap())
+impl Serialize for Kline {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        Kline::serialize(self, serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Kline {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(KlineVisitor)
+    }
+}
+
+struct KlineVisitor;
+
+impl<'de> Visitor<'de> for KlineVisitor {
+    type Value = Kline;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a kline array or object")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Kline, A::Error> {
+        let mut index = 0;
+        let mut next = |seq: &mut A| {
+            index += 1;
+            seq.next_element::<KlineField>()?
+                .ok_or_else(|| de::Error::invalid_length(index - 1, &"11 or more kline fields"))
+        };
+        let kline = Kline {
+            open_time: next(&mut seq)?.integer()?,
+            open: next(&mut seq)?.0,
+            high: next(&mut seq)?.0,
+            low: next(&mut seq)?.0,
+            close: next(&mut seq)?.0,
+            volume: next(&mut seq)?.0,
+            close_time: next(&mut seq)?.integer()?,
+            quote_volume: next(&mut seq)?.0,
+            trades: next(&mut seq)?.integer()?,
+            taker_buy_base_volume: next(&mut seq)?.0,
+            taker_buy_quote_volume: next(&mut seq)?.0,
+        };
+        // Binance appends an unused field; newer fields are ignored too.
+        while seq.next_element::<de::IgnoredAny>()?.is_some() {}
+        Ok(kline)
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<Kline, A::Error> {
+        Kline::deserialize(de::value::MapAccessDeserializer::new(map))
+    }
+}
+
+/// One element of a kline array: a number or a decimal string.
+struct KlineField(f64);
+
+impl KlineField {
+    fn integer<E: de::Error>(self) -> Result<i64, E> {
+        if self.0.fract() == 0.0 {
+            Ok(self.0 as i64)
+        } else {
+            Err(E::invalid_value(
+                de::Unexpected::Float(self.0),
+                &"an integer",
+            ))
         }
-        fn parse_int(val: &serde_json::Value) -> i64 {
-            val.as_i64().unwrap_or(0)
+    }
+}
+
+impl<'de> Deserialize<'de> for KlineField {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct FieldVisitor;
+
+        impl Visitor<'_> for FieldVisitor {
+            type Value = KlineField;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a number or a decimal string")
+            }
+
+            fn visit_i64<E: de::Error>(self, v: i64) -> Result<KlineField, E> {
+                Ok(KlineField(v as f64))
+            }
+
+            fn visit_u64<E: de::Error>(self, v: u64) -> Result<KlineField, E> {
+                Ok(KlineField(v as f64))
+            }
+
+            fn visit_f64<E: de::Error>(self, v: f64) -> Result<KlineField, E> {
+                Ok(KlineField(v))
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<KlineField, E> {
+                v.trim()
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|v| v.is_finite())
+                    .map(KlineField)
+                    .ok_or_else(|| E::invalid_value(de::Unexpected::Str(v), &self))
+            }
         }
 
-        Kline {
-            open_time: parse_int(&value[0]),
-            open: parse_val(&value[1]),
-            high: parse_val(&value[2]),
-            low: parse_val(&value[3]),
-            close: parse_val(&value[4]),
-            volume: parse_val(&value[5]),
-            close_time: parse_int(&value[6]),
-            quote_volume: parse_val(&value[7]),
-            trades: parse_int(&value[8]),
-            taker_buy_base_volume: parse_val(&value[10]),
-            taker_buy_quote_volume: parse_val(&value[11]),
+        deserializer.deserialize_any(FieldVisitor)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PriceTicker {
+    pub symbol: String,
+    pub price: String,
+}
+
+/// Rolling 24h statistics for one symbol; only the fields we use.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Ticker24h {
+    pub symbol: String,
+    pub quote_volume: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum OrderSide {
+    Buy,
+    Sell,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum OrderType {
+    Market,
+    Limit,
+    StopMarket,
+    TakeProfitMarket,
+}
+
+impl OrderSide {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            OrderSide::Buy => "BUY",
+            OrderSide::Sell => "SELL",
         }
     }
 }
 
-pub type KlineResponse = Vec<serde_json::Value>;
+impl OrderType {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            OrderType::Market => "MARKET",
+            OrderType::Limit => "LIMIT",
+            OrderType::StopMarket => "STOP_MARKET",
+            OrderType::TakeProfitMarket => "TAKE_PROFIT_MARKET",
+        }
+    }
+}
 
+/// `Both` in one-way mode, `Long` / `Short` in hedge mode.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum PositionSide {
+    Both,
+    Long,
+    Short,
+}
+
+impl PositionSide {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            PositionSide::Both => "BOTH",
+            PositionSide::Long => "LONG",
+            PositionSide::Short => "SHORT",
+        }
+    }
+}
+
+/// A new futures order (`POST /fapi/v1/order`).
 #[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct PriceTicker {
+#[serde(rename_all = "camelCase")]
+pub struct OrderRequest {
     pub symbol: String,
+    pub side: OrderSide,
+    #[serde(rename = "type")]
+    pub order_type: OrderType,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub position_side: Option<PositionSide>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quantity: Option<Decimal>,
+    /// Limit price.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub price: Option<Decimal>,
+    /// Trigger price for stop / take-profit orders.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stop_price: Option<Decimal>,
+    pub reduce_only: bool,
+    /// Close the whole position when triggered (stop / take-profit only).
+    pub close_position: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub new_client_order_id: Option<String>,
+}
+
+impl OrderRequest {
+    pub fn market(symbol: &str, side: OrderSide, quantity: Decimal) -> Self {
+        Self {
+            symbol: symbol.to_string(),
+            side,
+            order_type: OrderType::Market,
+            position_side: None,
+            quantity: Some(quantity),
+            price: None,
+            stop_price: None,
+            reduce_only: false,
+            close_position: false,
+            new_client_order_id: None,
+        }
+    }
+
+    /// A stop-loss (`StopMarket`) or take-profit (`TakeProfitMarket`) that
+    /// closes the whole position at `stop_price`.
+    pub fn close_at(
+        symbol: &str,
+        side: OrderSide,
+        order_type: OrderType,
+        stop_price: Decimal,
+    ) -> Self {
+        Self {
+            order_type,
+            quantity: None,
+            stop_price: Some(stop_price),
+            close_position: true,
+            ..Self::market(symbol, side, Decimal::ZERO)
+        }
+    }
+
+    /// Request parameters in Binance's query-string form, unsigned.
+    pub fn params(&self) -> Vec<(&'static str, String)> {
+        let mut params = vec![
+            ("symbol", self.symbol.clone()),
+            ("side", self.side.as_str().to_string()),
+            ("type", self.order_type.as_str().to_string()),
+        ];
+        if let Some(side) = self.position_side {
+            params.push(("positionSide", side.as_str().to_string()));
+        }
+        if let Some(quantity) = self.quantity {
+            params.push(("quantity", quantity.to_string()));
+        }
+        if let Some(price) = self.price {
+            params.push(("price", price.to_string()));
+            params.push(("timeInForce", "GTC".to_string()));
+        }
+        if let Some(stop_price) = self.stop_price {
+            params.push(("stopPrice", stop_price.to_string()));
+        }
+        if self.close_position {
+            params.push(("closePosition", "true".to_string()));
+        } else if self.reduce_only {
+            params.push(("reduceOnly", "true".to_string()));
+        }
+        if let Some(id) = &self.new_client_order_id {
+            params.push(("newClientOrderId", id.clone()));
+        }
+        // The default ACK response reports a market order's fill as zero.
+        if self.order_type == OrderType::Market {
+            params.push(("newOrderRespType", "RESULT".to_string()));
+        }
+        params
+    }
+}
+
+/// An order as Binance reports it. Prices and quantities are decimal strings.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct OrderResponse {
+    pub order_id: i64,
+    pub symbol: String,
+    /// NEW, PARTIALLY_FILLED, FILLED, CANCELED, EXPIRED, ...
+    pub status: String,
+    #[serde(default)]
+    pub client_order_id: String,
+    #[serde(default)]
     pub price: String,
+    #[serde(default)]
+    pub avg_price: String,
+    #[serde(default)]
+    pub orig_qty: String,
+    #[serde(default)]
+    pub executed_qty: String,
+    #[serde(default)]
+    pub stop_price: String,
+    pub side: String,
+    #[serde(rename = "type")]
+    pub order_type: String,
+    #[serde(default)]
+    pub reduce_only: bool,
+    #[serde(default)]
+    pub update_time: i64,
+}
+
+impl OrderResponse {
+    /// Whether this is a stop-loss or take-profit order.
+    pub fn is_protective(&self) -> bool {
+        self.order_type == OrderType::StopMarket.as_str()
+            || self.order_type == OrderType::TakeProfitMarket.as_str()
+    }
+}
+
+/// One asset of the futures wallet (`/fapi/v2/balance`).
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountBalance {
+    pub asset: String,
+    pub balance: String,
+    #[serde(default)]
+    pub cross_wallet_balance: String,
+    #[serde(default)]
+    pub cross_un_pnl: String,
+    pub available_balance: String,
+}
+
+/// Permissions of a Binance API key (`/sapi/v1/account/apiRestrictions`).
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ApiRestrictions {
+    pub ip_restrict: bool,
+    pub enable_reading: bool,
+    pub enable_futures: bool,
+    pub enable_spot_and_margin_trading: bool,
+    pub enable_withdrawals: bool,
+    pub enable_internal_transfer: bool,
+}
+
+/// A position as reported by `/fapi/v2/positionRisk`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PositionRisk {
+    pub symbol: String,
+    /// Signed; negative for shorts in one-way mode.
+    pub position_amt: String,
+    pub entry_price: String,
+    pub mark_price: String,
+    #[serde(rename = "unRealizedProfit")]
+    pub unrealized_profit: String,
+    pub liquidation_price: String,
+    pub leverage: String,
+    #[serde(default)]
+    pub margin_type: String,
+    #[serde(default)]
+    pub position_side: String,
+    #[serde(default)]
+    pub update_time: i64,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
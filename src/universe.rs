@@ -0,0 +1,251 @@
+//! Scheduled refresh of the default trading universe.
+//!
+//! `default_coins` in system_config used to be a hand-maintained list. When
+//! enabled, a job replaces it with the top perpetuals by 24h quote volume,
+//! skipping recent listings and symbols that cannot take the required leverage.
+//! Traders with no symbols of their own trade the default list and are alerted
+//! when it changes.
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::sync::RwLock;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::api_client::ApiClient;
+use crate::database::{Database, TraderRecord};
+use crate::i18n::{self, Locale, Msg};
+use crate::notify::{self, Event};
+use crate::types::{SymbolInfo, Ticker24h};
+
+const CONFIG_KEY: &str = "default_coins";
+const TRADING: &str = "TRADING";
+const PERPETUAL: &str = "PERPETUAL";
+
+static DEFAULT_COINS: Lazy<RwLock<Vec<String>>> = Lazy::new(|| RwLock::new(Vec::new()));
+
+#[derive(Error, Debug)]
+pub enum UniverseError {
+    #[error("No symbol passed the universe filters")]
+    Empty,
+    #[error(transparent)]
+    Exchange(#[from] anyhow::Error),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct UniverseParams {
+    /// Refresh `default_coins` on a schedule; off keeps the stored list.
+    pub enabled: bool,
+    pub schedule: String,
+    /// Number of symbols kept.
+    pub top_n: usize,
+    pub quote_asset: String,
+    /// Symbols listed more recently than this are skipped.
+    #[serde(with = "humantime_serde")]
+    pub min_listing_age: Duration,
+    /// Symbols whose maximum leverage is below this are skipped.
+    pub min_leverage: u32,
+}
+
+impl Default for UniverseParams {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            schedule: "0 0 * * *".to_string(),
+            top_n: 8,
+            quote_asset: "USDT".to_string(),
+            min_listing_age: Duration::from_secs(30 * 24 * 3600),
+            min_leverage: 5,
+        }
+    }
+}
+
+/// A change to the default list.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct UniverseChange {
+    pub coins: Vec<String>,
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+/// Symbols traded by traders that have none configured.
+pub fn default_coins() -> Vec<String> {
+    DEFAULT_COINS
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+}
+
+pub fn set_default_coins(coins: Vec<String>) {
+    *DEFAULT_COINS.write().unwrap_or_else(|e| e.into_inner()) = coins;
+}
+
+async fn stored_coins(db: &Database) -> Vec<String> {
+    db.get_system_config(CONFIG_KEY)
+        .await
+        .ok()
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default()
+}
+
+/// Loads the stored default list.
+pub async fn load(db: &Database) {
+    set_default_coins(stored_coins(db).await);
+}
+
+/// True when the trader trades the default list.
+pub fn uses_defaults(trader: &TraderRecord) -> bool {
+    trader.trading_symbols.trim().is_empty()
+}
+
+// Maximum leverage implied by the first bracket's initial margin.
+fn max_leverage(info: &SymbolInfo) -> Option<f64> {
+    let margin: f64 = info.required_margin_percent.parse().ok()?;
+    (margin > 0.0).then(|| 100.0 / margin)
+}
+
+/// Picks the universe from exchangeInfo and 24h tickers. Symbols without
+/// leverage data are kept; the exchange does not report it everywhere.
+pub fn select(
+    symbols: &[SymbolInfo],
+    tickers: &[Ticker24h],
+    params: &UniverseParams,
+    now: DateTime<Utc>,
+) -> Vec<String> {
+    let volumes: HashMap<&str, f64> = tickers
+        .iter()
+        .filter_map(|t| Some((t.symbol.as_str(), t.quote_volume.parse().ok()?)))
+        .collect();
+    let listed_before = now.timestamp_millis()
+        - chrono::Duration::from_std(params.min_listing_age)
+            .map(|d| d.num_milliseconds())
+            .unwrap_or(0);
+
+    let mut ranked: Vec<(&str, f64)> = symbols
+        .iter()
+        .filter(|s| {
+            s.status == TRADING
+                && s.contract_type == PERPETUAL
+                && s.quote_asset.eq_ignore_ascii_case(&params.quote_asset)
+                && s.onboard_date <= listed_before
+                && max_leverage(s).is_none_or(|l| l >= params.min_leverage as f64)
+        })
+        .filter_map(|s| Some((s.symbol.as_str(), *volumes.get(s.symbol.as_str())?)))
+        .collect();
+    ranked.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(Ordering::Equal));
+    ranked
+        .into_iter()
+        .take(params.top_n)
+        .map(|(s, _)| s.to_string())
+        .collect()
+}
+
+/// Recomputes the universe and stores it as `default_coins`. Returns the
+/// change, or `None` when the list is unchanged.
+pub async fn refresh(
+    db: &Database,
+    client: &ApiClient,
+    params: &UniverseParams,
+) -> Result<Option<UniverseChange>, UniverseError> {
+    let info = client.get_exchange_info().await?;
+    let tickers = client.get_24h_tickers().await?;
+    let coins = select(&info.symbols, &tickers, params, Utc::now());
+    // Never replace a working list with nothing.
+    if coins.is_empty() {
+        return Err(UniverseError::Empty);
+    }
+
+    let previous = stored_coins(db).await;
+    set_default_coins(coins.clone());
+    if coins == previous {
+        return Ok(None);
+    }
+    db.set_system_config(
+        CONFIG_KEY,
+        &serde_json::to_string(&coins).unwrap_or_default(),
+    )
+    .await?;
+
+    let added: Vec<String> = coins
+        .iter()
+        .filter(|c| !previous.contains(c))
+        .cloned()
+        .collect();
+    let removed: Vec<String> = previous
+        .into_iter()
+        .filter(|c| !coins.contains(c))
+        .collect();
+    tracing::info!(
+        "🔄 默认币种已更新: {} (新增 {:?}, 移除 {:?})",
+        coins.join(","),
+        added,
+        removed
+    );
+    Ok(Some(UniverseChange {
+        coins,
+        added,
+        removed,
+    }))
+}
+
+/// Alerts every trader on the default list in its owner's language and
+/// returns the ids of the traders alerted.
+pub async fn notify_traders(db: &Database, change: &UniverseChange) -> anyhow::Result<Vec<String>> {
+    let list = |coins: &[String]| {
+        if coins.is_empty() {
+            "-".to_string()
+        } else {
+            coins.join(",")
+        }
+    };
+    let (added, removed) = (list(&change.added), list(&change.removed));
+
+    let mut notified = Vec::new();
+    for user_id in db.get_all_users_id().await? {
+        let traders: Vec<TraderRecord> = db
+            .get_traders(&user_id)
+            .await?
+            .into_iter()
+            .filter(uses_defaults)
+            .collect();
+        if traders.is_empty() {
+            continue;
+        }
+        let locale = match db.get_user_by_id(&user_id).await {
+            Ok(Some(user)) => user.locale(),
+            _ => Locale::default(),
+        };
+        for trader in traders {
+            let message = i18n::render(
+                locale,
+                Msg::DefaultCoinsChanged,
+                &[
+                    ("name", &trader.name),
+                    ("added", &added),
+                    ("removed", &removed),
+                ],
+            );
+            tracing::info!(
+                event = "default_coins_changed",
+                user_id = %user_id,
+                trader_id = %trader.id,
+                "{}",
+                message
+            );
+            notify::notify(
+                Event::alert("default_coins_changed", message)
+                    .user(&user_id)
+                    .trader(&trader.id)
+                    .detail("added", &change.added)
+                    .detail("removed", &change.removed),
+            );
+            notified.push(trader.id);
+        }
+    }
+    Ok(notified)
+}
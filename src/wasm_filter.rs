@@ -0,0 +1,216 @@
+//! User-supplied decision filters running in a wasmtime sandbox.
+//!
+//! A filter sees the proposed decisions plus account state and returns the
+//! decisions it allows, optionally resized. It can only restrict: it may drop
+//! a decision or lower its size or leverage, never add one or enlarge it.
+//!
+//! Module ABI (no imports are provided, so filters are pure and deterministic):
+//!
+//! - `memory`: exported linear memory
+//! - `alloc(len: i32) -> i32`: returns a buffer the host writes the input into
+//! - `filter(ptr: i32, len: i32) -> i64`: reads the input JSON and returns the
+//!   output JSON location packed as `(ptr << 32) | len`
+//!
+//! Input: `{"decisions": [...], "account": {...}, "positions": [...]}`
+//! Output: `{"decisions": [...]}`
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use wasmtime::{Config, Engine, Linker, Module, Store, StoreLimits, StoreLimitsBuilder};
+
+use crate::decision::{AccountInfo, Context, Decision, PositionInfo};
+
+// Upper bound on instructions per call; keeps a runaway filter from stalling a cycle.
+const FUEL_PER_CALL: u64 = 50_000_000;
+const MAX_MEMORY_BYTES: usize = 16 * 1024 * 1024;
+const MAX_OUTPUT_BYTES: usize = 1024 * 1024;
+/// Largest module accepted on upload.
+pub const MAX_MODULE_BYTES: usize = 4 * 1024 * 1024;
+
+#[derive(Error, Debug)]
+pub enum FilterError {
+    #[error("Filter module is too large ({0} bytes)")]
+    TooLarge(usize),
+    #[error("Invalid filter module: {0}")]
+    InvalidModule(String),
+    #[error("Filter execution failed: {0}")]
+    Execution(String),
+    #[error("Filter returned invalid output: {0}")]
+    InvalidOutput(String),
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+#[derive(Serialize)]
+struct FilterInput<'a> {
+    decisions: &'a [Decision],
+    account: &'a AccountInfo,
+    positions: &'a [PositionInfo],
+}
+
+#[derive(Deserialize)]
+struct FilterOutput {
+    decisions: Vec<Decision>,
+}
+
+struct SandboxState {
+    limits: StoreLimits,
+}
+
+/// A compiled filter module. Each call runs in a fresh instance, so no state
+/// carries over between cycles.
+pub struct WasmFilter {
+    engine: Engine,
+    module: Module,
+}
+
+impl WasmFilter {
+    /// Compiles a module and checks that it exports the expected ABI.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, FilterError> {
+        if bytes.len() > MAX_MODULE_BYTES {
+            return Err(FilterError::TooLarge(bytes.len()));
+        }
+
+        let mut config = Config::new();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config).map_err(|e| FilterError::InvalidModule(e.to_string()))?;
+        let module =
+            Module::new(&engine, bytes).map_err(|e| FilterError::InvalidModule(e.to_string()))?;
+
+        if module.imports().len() > 0 {
+            return Err(FilterError::InvalidModule(
+                "filters must not import anything".to_string(),
+            ));
+        }
+        for name in ["memory", "alloc", "filter"] {
+            if module.get_export(name).is_none() {
+                return Err(FilterError::InvalidModule(format!(
+                    "missing export '{}'",
+                    name
+                )));
+            }
+        }
+
+        Ok(Self { engine, module })
+    }
+
+    /// Runs the filter over `decisions` and returns what it allows.
+    pub fn apply(
+        &self,
+        decisions: &[Decision],
+        ctx: &Context,
+    ) -> Result<Vec<Decision>, FilterError> {
+        let input = serde_json::to_vec(&FilterInput {
+            decisions,
+            account: &ctx.account,
+            positions: &ctx.positions,
+        })?;
+        let output = self.call(&input)?;
+        let output: FilterOutput = serde_json::from_slice(&output)?;
+
+        enforce_restrict_only(decisions, output.decisions)
+    }
+
+    /// Like [`apply`](Self::apply), but on failure keeps only decisions that reduce
+    /// exposure (closes, holds), so a broken filter can never let new risk through.
+    pub fn apply_or_fail_closed(&self, decisions: Vec<Decision>, ctx: &Context) -> Vec<Decision> {
+        match self.apply(&decisions, ctx) {
+            Ok(filtered) => filtered,
+            Err(e) => {
+                tracing::warn!("⚠️ 决策过滤器执行失败，拒绝所有开仓: {}", e);
+                decisions
+                    .into_iter()
+                    .filter(|d| !d.action.is_open())
+                    .collect()
+            }
+        }
+    }
+
+    fn call(&self, input: &[u8]) -> Result<Vec<u8>, FilterError> {
+        let exec = |e: wasmtime::Error| FilterError::Execution(e.to_string());
+
+        let mut store = Store::new(
+            &self.engine,
+            SandboxState {
+                limits: StoreLimitsBuilder::new()
+                    .memory_size(MAX_MEMORY_BYTES)
+                    .instances(1)
+                    .build(),
+            },
+        );
+        store.limiter(|state| &mut state.limits);
+        store.set_fuel(FUEL_PER_CALL).map_err(exec)?;
+
+        let instance = Linker::new(&self.engine)
+            .instantiate(&mut store, &self.module)
+            .map_err(exec)?;
+        let memory = instance
+            .get_memory(&mut store, "memory")
+            .ok_or_else(|| FilterError::InvalidModule("'memory' is not a memory".to_string()))?;
+        let alloc = instance
+            .get_typed_func::<i32, i32>(&mut store, "alloc")
+            .map_err(exec)?;
+        let filter = instance
+            .get_typed_func::<(i32, i32), i64>(&mut store, "filter")
+            .map_err(exec)?;
+
+        let len = i32::try_from(input.len())
+            .map_err(|_| FilterError::Execution("input too large".to_string()))?;
+        let ptr = alloc.call(&mut store, len).map_err(exec)?;
+        memory
+            .write(&mut store, ptr as u32 as usize, input)
+            .map_err(|e| FilterError::Execution(e.to_string()))?;
+
+        let packed = filter.call(&mut store, (ptr, len)).map_err(exec)? as u64;
+        let out_ptr = (packed >> 32) as usize;
+        let out_len = (packed & 0xffff_ffff) as usize;
+        if out_len > MAX_OUTPUT_BYTES {
+            return Err(FilterError::InvalidOutput(format!(
+                "output of {} bytes exceeds the limit",
+                out_len
+            )));
+        }
+
+        let mut output = vec![0u8; out_len];
+        memory
+            .read(&store, out_ptr, &mut output)
+            .map_err(|e| FilterError::InvalidOutput(e.to_string()))?;
+        Ok(output)
+    }
+}
+
+// Every returned decision must correspond to its own proposed one and may
+// only be smaller; the proposed decision's fields win for anything else. A
+// proposed decision is matched at most once, so returning it twice cannot
+// double its exposure.
+fn enforce_restrict_only(
+    proposed: &[Decision],
+    returned: Vec<Decision>,
+) -> Result<Vec<Decision>, FilterError> {
+    let mut matched = vec![false; proposed.len()];
+    let mut allowed = Vec::with_capacity(returned.len());
+    for d in returned {
+        let index = proposed
+            .iter()
+            .enumerate()
+            .position(|(i, p)| !matched[i] && p.symbol == d.symbol && p.action == d.action)
+            .ok_or_else(|| {
+                FilterError::InvalidOutput(format!(
+                    "decision {:?} {} was not proposed, or was returned more than once",
+                    d.action, d.symbol
+                ))
+            })?;
+        matched[index] = true;
+        let original = &proposed[index];
+
+        let mut kept = original.clone();
+        if d.position_size_usd > 0.0 && d.position_size_usd < original.position_size_usd {
+            kept.position_size_usd = d.position_size_usd;
+        }
+        if d.leverage > 0 && d.leverage < original.leverage {
+            kept.leverage = d.leverage;
+        }
+        allowed.push(kept);
+    }
+    Ok(allowed)
+}
@@ -0,0 +1,227 @@
+//! Watch-only traders.
+//!
+//! A watch-only trader runs the normal decision cycle against an account that
+//! a human manages, usually with read-only API keys, but never places an
+//! order. The AI's decisions are logged as suggestions, and the trades the
+//! human actually made are inferred from position changes between cycles and
+//! logged as the record's executions, so decision logs, alerts and performance
+//! analytics describe the real account. Comparing each cycle's suggestions
+//! with the trades that followed shows how the AI fares against the human.
+
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use crate::decision::{Action, Decision, PositionInfo};
+use crate::executor::{self, ExecutorError, OrderFill, TradeExecutor};
+use crate::logger::DecisionRecord;
+use crate::money::{self, Decimal};
+use crate::symbol_meta::SymbolMeta;
+
+const REFUSED: &str = "watch-only trader never places orders";
+
+// Position changes smaller than this fraction of the position are noise.
+const QUANTITY_EPSILON: f64 = 1e-9;
+
+/// Wraps an exchange so that account and price reads go through but every
+/// order is refused.
+pub struct ReadOnly<E>(pub E);
+
+impl<E: TradeExecutor> TradeExecutor for ReadOnly<E> {
+    async fn open_long(
+        &mut self,
+        _symbol: &str,
+        _quantity: Decimal,
+        _leverage: i32,
+    ) -> executor::Result<OrderFill> {
+        Err(ExecutorError::InvalidOrder(REFUSED.to_string()))
+    }
+
+    async fn open_short(
+        &mut self,
+        _symbol: &str,
+        _quantity: Decimal,
+        _leverage: i32,
+    ) -> executor::Result<OrderFill> {
+        Err(ExecutorError::InvalidOrder(REFUSED.to_string()))
+    }
+
+    async fn close(
+        &mut self,
+        _symbol: &str,
+        _side: &str,
+        _quantity: Decimal,
+    ) -> executor::Result<OrderFill> {
+        Err(ExecutorError::InvalidOrder(REFUSED.to_string()))
+    }
+
+    async fn set_leverage(&mut self, _symbol: &str, _leverage: i32) -> executor::Result<()> {
+        Err(ExecutorError::InvalidOrder(REFUSED.to_string()))
+    }
+
+    async fn get_positions(&mut self) -> executor::Result<Vec<PositionInfo>> {
+        self.0.get_positions().await
+    }
+
+    async fn get_price(&mut self, symbol: &str) -> executor::Result<f64> {
+        self.0.get_price(symbol).await
+    }
+
+    async fn symbol_meta(&mut self, symbol: &str) -> executor::Result<Option<SymbolMeta>> {
+        self.0.symbol_meta(symbol).await
+    }
+}
+
+/// A trade the account's owner made, inferred from a position change.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ObservedTrade {
+    pub symbol: String,
+    pub action: Action,
+    pub quantity: f64,
+    /// Mark price when the change was observed.
+    pub price: f64,
+    pub leverage: i32,
+}
+
+/// The trades that turn `before` into `after`: a new or grown position is an
+/// open, a shrunk or vanished one a close.
+pub fn observe_trades(before: &[PositionInfo], after: &[PositionInfo]) -> Vec<ObservedTrade> {
+    let key = |p: &PositionInfo| (p.symbol.clone(), p.side.clone());
+    let previous: HashMap<_, &PositionInfo> = before.iter().map(|p| (key(p), p)).collect();
+    let current: HashMap<_, &PositionInfo> = after.iter().map(|p| (key(p), p)).collect();
+
+    let mut trades = Vec::new();
+    for p in after {
+        let held = previous.get(&key(p)).map_or(0.0, |q| q.quantity.abs());
+        let delta = p.quantity.abs() - held;
+        if delta.abs() <= QUANTITY_EPSILON * p.quantity.abs().max(held) {
+            continue;
+        }
+        let long = p.side == "long";
+        let action = match (delta > 0.0, long) {
+            (true, true) => Action::OpenLong,
+            (true, false) => Action::OpenShort,
+            (false, true) => Action::CloseLong,
+            (false, false) => Action::CloseShort,
+        };
+        trades.push(ObservedTrade {
+            symbol: p.symbol.clone(),
+            action,
+            quantity: delta.abs(),
+            price: p.mark_price,
+            leverage: p.leverage,
+        });
+    }
+    for p in before {
+        if current.contains_key(&key(p)) || p.quantity == 0.0 {
+            continue;
+        }
+        trades.push(ObservedTrade {
+            symbol: p.symbol.clone(),
+            action: if p.side == "long" {
+                Action::CloseLong
+            } else {
+                Action::CloseShort
+            },
+            quantity: p.quantity.abs(),
+            price: p.mark_price,
+            leverage: p.leverage,
+        });
+    }
+    trades.sort_by(|a, b| a.symbol.cmp(&b.symbol));
+    trades
+}
+
+/// How the AI's suggestions from one cycle line up with what the human did
+/// before the next.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Comparison {
+    /// Suggestions the human acted on (same symbol and action).
+    pub agreed: Vec<Decision>,
+    /// Suggestions the human did not act on.
+    pub ai_only: Vec<Decision>,
+    /// Trades the AI did not suggest.
+    pub human_only: Vec<ObservedTrade>,
+}
+
+impl Comparison {
+    /// Share of all opens and closes, suggested or made, that both agreed on, in percent.
+    pub fn agreement_pct(&self) -> f64 {
+        let total = self.agreed.len() + self.ai_only.len() + self.human_only.len();
+        if total == 0 {
+            100.0
+        } else {
+            self.agreed.len() as f64 / total as f64 * 100.0
+        }
+    }
+}
+
+/// Compares suggested opens and closes with the observed trades. Hold and
+/// wait suggestions are ignored.
+pub fn compare(suggestions: &[Decision], trades: &[ObservedTrade]) -> Comparison {
+    let mut unmatched: Vec<&ObservedTrade> = trades.iter().collect();
+    let mut comparison = Comparison::default();
+    for d in suggestions
+        .iter()
+        .filter(|d| d.action.is_open() || d.action.is_close())
+    {
+        match unmatched
+            .iter()
+            .position(|t| t.symbol == d.symbol && t.action == d.action)
+        {
+            Some(i) => {
+                unmatched.remove(i);
+                comparison.agreed.push(d.clone());
+            }
+            None => comparison.ai_only.push(d.clone()),
+        }
+    }
+    comparison.human_only = unmatched.into_iter().cloned().collect();
+    comparison
+}
+
+/// Logs the AI's decisions as suggestions that were not executed.
+pub fn record_suggestions(record: &mut DecisionRecord, decisions: &[Decision]) {
+    for d in decisions
+        .iter()
+        .filter(|d| d.action.is_open() || d.action.is_close())
+    {
+        record.log(format!(
+            "💡 {:?} {} (watch-only, not executed)",
+            d.action, d.symbol
+        ));
+    }
+}
+
+/// Logs observed trades as the record's executions and the comparison with
+/// the previous cycle's suggestions.
+pub fn record_observed(
+    record: &mut DecisionRecord,
+    trades: &[ObservedTrade],
+    comparison: &Comparison,
+) {
+    for t in trades {
+        record.record_execution(
+            t.action,
+            &t.symbol,
+            money::from_f64(t.quantity),
+            t.leverage,
+            money::from_f64(t.price),
+            0,
+            None,
+        );
+        record.log(format!(
+            "👀 {:?} {} {} @ {} (account owner)",
+            t.action, t.symbol, t.quantity, t.price
+        ));
+    }
+    if !trades.is_empty() || !comparison.ai_only.is_empty() {
+        record.log(format!(
+            "🤝 AI agreement {:.0}%: {} agreed, {} suggested only, {} traded only",
+            comparison.agreement_pct(),
+            comparison.agreed.len(),
+            comparison.ai_only.len(),
+            comparison.human_only.len()
+        ));
+    }
+}
@@ -0,0 +1,103 @@
+//! Scoring of the AI's directional calls and the hit rates built from them.
+
+use std::collections::HashMap;
+
+use aitrading::accuracy;
+use aitrading::ai::AiProvider;
+use aitrading::decision::{Action, Decision};
+use aitrading::testkit::{Harness, MockAiProvider};
+use aitrading::tournament::{self, TournamentParams};
+use chrono::{Duration, Utc};
+
+fn decision(symbol: &str, action: Action) -> Decision {
+    Decision {
+        leverage: 3,
+        position_size_usd: 100.0,
+        ..Decision::new(symbol, action)
+    }
+}
+
+#[tokio::test]
+async fn calls_are_scored_and_fed_back() {
+    let mut h = Harness::new(&["BTCUSDT", "ETHUSDT"], 1000.0).await.unwrap();
+    h.exchange.set_price("BTCUSDT", 100.0);
+    h.exchange.set_price("ETHUSDT", 10.0);
+    h.ai.set_name("bull");
+    h.ai.push_decisions(&[
+        decision("BTCUSDT", Action::OpenLong),
+        decision("ETHUSDT", Action::OpenShort),
+        Decision::new("ETHUSDT", Action::Wait),
+    ]);
+    h.run_cycle().await.unwrap();
+
+    // Wait is not a call; each open is recorded at the price the model saw.
+    let mut due = h.db.get_unsettled_decision_calls(Utc::now()).await.unwrap();
+    due.sort_by(|a, b| a.symbol.cmp(&b.symbol));
+    assert_eq!(due.len(), 2);
+    assert_eq!(
+        (
+            due[0].symbol.as_str(),
+            due[0].model.as_str(),
+            due[0].entry_price
+        ),
+        ("BTCUSDT", "bull", 100.0)
+    );
+    assert!(
+        h.db.get_unsettled_decision_calls(Utc::now() - Duration::hours(1))
+            .await
+            .unwrap()
+            .is_empty()
+    );
+
+    // BTC rose 5%: the long was right. ETH rose 10%: the short was wrong.
+    accuracy::settle(&h.db, &due[0], 105.0).await.unwrap();
+    accuracy::settle(&h.db, &due[1], 11.0).await.unwrap();
+    let since = Utc::now() - Duration::days(1);
+    let stats = accuracy::stats(&h.db, &h.user_id, None, since)
+        .await
+        .unwrap();
+    assert_eq!((stats.overall.calls, stats.overall.hits), (2, 1));
+    assert!((stats.overall.avg_return_pct() + 2.5).abs() < 1e-9);
+    assert_eq!(stats.by_symbol[0].key, "BTCUSDT");
+    assert_eq!(stats.by_symbol[0].hit_rate(), 100.0);
+    assert_eq!(stats.by_symbol[1].hit_rate(), 0.0);
+    assert_eq!(stats.model("bull").unwrap().calls, 2);
+    let other = accuracy::stats(&h.db, &h.user_id, Some("bear"), since)
+        .await
+        .unwrap();
+    assert_eq!(other.overall.calls, 0);
+
+    // The model sees its own record in the next prompt.
+    h.run_cycle().await.unwrap();
+    let prompt = h.ai.calls()[1].user_prompt.clone();
+    assert!(prompt.contains(
+        "Your directional calls over the last 7days, scored 4h later: 50% correct (1/2)"
+    ));
+    assert!(prompt.contains("BTCUSDT: 100% (1/1)"));
+
+    // Tournament standings show the live hit rate next to the replay score.
+    let exit = serde_json::to_string(&HashMap::from([("BTCUSDT", 105.0)])).unwrap();
+    for scenario in h.db.get_unsettled_eval_scenarios(Utc::now()).await.unwrap() {
+        h.db.settle_eval_scenario(&scenario.id, &exit)
+            .await
+            .unwrap();
+    }
+    let mut contestants: Vec<Box<dyn AiProvider>> = vec![
+        Box::new(MockAiProvider::named("bull")),
+        Box::new(MockAiProvider::named("bear")),
+    ];
+    let report = tournament::run_for_user(
+        &h.db,
+        &h.user_id,
+        &mut contestants,
+        &TournamentParams::default(),
+    )
+    .await
+    .unwrap();
+    let bull = report.standings.iter().find(|s| s.model == "bull").unwrap();
+    assert_eq!((bull.live_calls, bull.live_hits), (2, 1));
+    assert_eq!(bull.live_hit_rate(), Some(50.0));
+    let bear = report.standings.iter().find(|s| s.model == "bear").unwrap();
+    assert_eq!(bear.live_hit_rate(), None);
+    assert!(report.table().contains("live %"));
+}
@@ -0,0 +1,220 @@
+//! OpenAI-compatible, Anthropic and Gemini clients against local stub endpoints.
+
+use aitrading::ai::{self, AiError, AiProvider, AnthropicClient, GeminiClient, OpenAiCompatClient};
+use aitrading::database::AIModelConfig;
+use axum::Router;
+use axum::body::Body;
+use axum::extract::Path;
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::post;
+use serde_json::{Value, json};
+
+async fn completions(headers: HeaderMap, body: String) -> Response {
+    if headers.get("authorization").and_then(|v| v.to_str().ok()) != Some("Bearer sk-test") {
+        return (StatusCode::UNAUTHORIZED, "bad key").into_response();
+    }
+    let request: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(request["messages"][0]["role"], "system");
+    if request["stream"] == true {
+        let events = [
+            json!({"choices": [{"delta": {"content": "[{\"symbol\":\"BTCUSDT\","}}]}),
+            json!({"choices": [{"delta": {"content": "\"action\":\"wait\",\"reasoning\":\"止损\"}]"}}]}),
+        ];
+        let mut sse = String::new();
+        for e in events {
+            sse.push_str(&format!("data: {}\n\n", e));
+        }
+        sse.push_str("data: [DONE]\n\n");
+        return Response::new(Body::from(sse));
+    }
+    axum::Json(json!({
+        "choices": [{"message": {"role": "assistant", "content": format!("model={}", request["model"].as_str().unwrap())}}]
+    }))
+    .into_response()
+}
+
+async fn messages(headers: HeaderMap, body: String) -> Response {
+    if headers.get("x-api-key").and_then(|v| v.to_str().ok()) != Some("sk-ant")
+        || headers.get("anthropic-version").is_none()
+    {
+        return (StatusCode::UNAUTHORIZED, "bad key").into_response();
+    }
+    let request: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(request["system"], "system");
+    assert_eq!(request["messages"][0]["role"], "user");
+    assert_eq!(request["max_tokens"], 8192);
+    if request["stream"] == true {
+        let events = [
+            json!({"type": "message_start", "message": {"content": []}}),
+            json!({"type": "content_block_delta", "index": 0, "delta": {"type": "text_delta", "text": "hold "}}),
+            json!({"type": "content_block_delta", "index": 0, "delta": {"type": "text_delta", "text": "BTC"}}),
+            json!({"type": "message_stop"}),
+        ];
+        let mut sse = String::new();
+        for e in events {
+            sse.push_str(&format!(
+                "event: {}\ndata: {}\n\n",
+                e["type"].as_str().unwrap(),
+                e
+            ));
+        }
+        return Response::new(Body::from(sse));
+    }
+    axum::Json(json!({
+        "content": [
+            {"type": "thinking", "thinking": "hmm"},
+            {"type": "text", "text": format!("model={}", request["model"].as_str().unwrap())},
+        ]
+    }))
+    .into_response()
+}
+
+async fn generate(Path(call): Path<String>, headers: HeaderMap, body: String) -> Response {
+    if headers.get("x-goog-api-key").and_then(|v| v.to_str().ok()) != Some("gm-key") {
+        return (StatusCode::FORBIDDEN, "bad key").into_response();
+    }
+    let request: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(request["systemInstruction"]["parts"][0]["text"], "system");
+    assert_eq!(request["contents"][0]["parts"][0]["text"], "user");
+    let (model, method) = call.split_once(':').unwrap();
+    let answer = |text: &str| {
+        json!({"candidates": [{"content": {"role": "model", "parts": [
+            {"text": "thinking...", "thought": true},
+            {"text": text},
+        ]}}]})
+    };
+    match method {
+        "generateContent" => axum::Json(answer(&format!("model={}", model))).into_response(),
+        "streamGenerateContent" => {
+            let sse = format!(
+                "data: {}\r\n\r\ndata: {}\r\n\r\n",
+                answer("wait "),
+                answer("ETH")
+            );
+            Response::new(Body::from(sse))
+        }
+        _ => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+async fn stub() -> String {
+    let app = Router::new()
+        .route("/v1/chat/completions", post(completions))
+        .route("/v1/messages", post(messages))
+        .route("/v1beta/models/{call}", post(generate));
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    format!("http://{}/v1", addr)
+}
+
+#[tokio::test]
+async fn completes_and_streams() {
+    let url = stub().await;
+    let mut client = OpenAiCompatClient::custom(&url, "sk-test", "my-model").unwrap();
+    assert_eq!(client.name(), "custom/my-model");
+    let text = client.chat_completion("system", "user").await.unwrap();
+    assert_eq!(text, "model=my-model");
+
+    let mut deltas = Vec::new();
+    let mut on_delta = |d: &str| deltas.push(d.to_string());
+    let text = client
+        .chat_completion_stream("system", "user", &mut on_delta)
+        .await
+        .unwrap();
+    assert_eq!(deltas.len(), 2);
+    let decisions = aitrading::decision::parse_decisions(&text).unwrap();
+    assert_eq!(decisions[0].reasoning, "止损");
+
+    let mut wrong_key = OpenAiCompatClient::custom(&url, "sk-other", "my-model").unwrap();
+    match wrong_key.chat_completion("system", "user").await {
+        Err(AiError::Api { status: 401, .. }) => {}
+        other => panic!("expected a 401, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn anthropic_takes_the_system_prompt_separately() {
+    let url = stub().await;
+    let mut client = AnthropicClient::new(&url, "sk-ant", "claude-test").unwrap();
+    assert_eq!(client.name(), "anthropic/claude-test");
+    let text = client.chat_completion("system", "user").await.unwrap();
+    assert_eq!(text, "model=claude-test");
+
+    let mut deltas = Vec::new();
+    let mut on_delta = |d: &str| deltas.push(d.to_string());
+    let text = client
+        .chat_completion_stream("system", "user", &mut on_delta)
+        .await
+        .unwrap();
+    assert_eq!(text, "hold BTC");
+    assert_eq!(deltas, ["hold ", "BTC"]);
+
+    let mut wrong_key = AnthropicClient::new(&url, "sk-other", "claude-test").unwrap();
+    assert!(matches!(
+        wrong_key.chat_completion("system", "user").await,
+        Err(AiError::Api { status: 401, .. })
+    ));
+}
+
+#[tokio::test]
+async fn gemini_skips_thoughts() {
+    let url = stub().await;
+    let base = format!("{}beta", url);
+    let mut client = GeminiClient::new(&base, "gm-key", "gemini-test").unwrap();
+    assert_eq!(client.name(), "gemini/gemini-test");
+    let text = client.chat_completion("system", "user").await.unwrap();
+    assert_eq!(text, "model=gemini-test");
+
+    let mut deltas = Vec::new();
+    let mut on_delta = |d: &str| deltas.push(d.to_string());
+    let text = client
+        .chat_completion_stream("system", "user", &mut on_delta)
+        .await
+        .unwrap();
+    assert_eq!(text, "wait ETH");
+    assert_eq!(deltas.len(), 2);
+
+    let mut wrong_key = GeminiClient::new(&base, "other", "gemini-test").unwrap();
+    assert!(matches!(
+        wrong_key.chat_completion("system", "user").await,
+        Err(AiError::Api { status: 403, .. })
+    ));
+}
+
+#[test]
+fn builds_clients_from_model_config() {
+    let mut model = AIModelConfig {
+        id: "qwen".to_string(),
+        provider: "qwen".to_string(),
+        enabled: true,
+        api_key: "sk-test".to_string(),
+        ..Default::default()
+    };
+    assert_eq!(
+        ai::from_model_config(&model).unwrap().name(),
+        format!("qwen/{}", ai::QWEN_MODEL)
+    );
+
+    model.custom_model_name = "qwen-plus".to_string();
+    assert_eq!(
+        ai::from_model_config(&model).unwrap().name(),
+        "qwen/qwen-plus"
+    );
+
+    model.provider = "anthropic".to_string();
+    model.custom_model_name = String::new();
+    assert_eq!(
+        ai::from_model_config(&model).unwrap().name(),
+        format!("anthropic/{}", ai::ANTHROPIC_MODEL)
+    );
+    model.provider = "gemini".to_string();
+    assert_eq!(
+        ai::from_model_config(&model).unwrap().name(),
+        format!("gemini/{}", ai::GEMINI_MODEL)
+    );
+
+    model.provider = "custom".to_string();
+    assert!(ai::from_model_config(&model).is_err());
+}
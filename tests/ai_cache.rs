@@ -0,0 +1,58 @@
+//! Reuse of AI responses to identical prompts.
+
+use std::time::Duration;
+
+use aitrading::ai_cache::{self, AiCacheParams};
+use aitrading::testkit::MockAiProvider;
+
+#[tokio::test]
+async fn identical_prompts_are_answered_once() {
+    let mut mock = MockAiProvider::named("cache-test");
+    mock.push_error("connection reset");
+    mock.push_response("first");
+    mock.push_response("second");
+    mock.push_response("third");
+    mock.push_response("fourth");
+    let mut ai = ai_cache::wrap(Box::new(mock));
+    assert_eq!(ai.name(), "cache-test");
+
+    // Disabled by default: every call goes through.
+    assert!(ai.chat_completion("system", "user").await.is_err());
+    assert_eq!(ai.chat_completion("system", "user").await.unwrap(), "first");
+
+    ai_cache::set_params(AiCacheParams {
+        enabled: true,
+        ttl: Duration::from_secs(60),
+    });
+    let before = ai_cache::stats();
+    assert_eq!(
+        ai.chat_completion("system", "user").await.unwrap(),
+        "second"
+    );
+    assert_eq!(
+        ai.chat_completion("system", "user").await.unwrap(),
+        "second"
+    );
+    let mut deltas = Vec::new();
+    let mut on_delta = |d: &str| deltas.push(d.to_string());
+    let streamed = ai
+        .chat_completion_stream("system", "user", &mut on_delta)
+        .await
+        .unwrap();
+    assert_eq!(streamed, "second");
+    assert_eq!(deltas, ["second"]);
+    // Moving text between the prompts is a different request.
+    assert_eq!(ai.chat_completion("systemuser", "").await.unwrap(), "third");
+    let stats = ai_cache::stats();
+    assert_eq!(stats.hits - before.hits, 2);
+    assert_eq!(stats.misses - before.misses, 2);
+
+    ai_cache::set_params(AiCacheParams {
+        enabled: true,
+        ttl: Duration::ZERO,
+    });
+    assert_eq!(ai.chat_completion("other", "user").await.unwrap(), "fourth");
+    // Expired at once, so the script runs out and the mock answers "[]".
+    assert_eq!(ai.chat_completion("other", "user").await.unwrap(), "[]");
+    ai_cache::clear();
+}
@@ -0,0 +1,415 @@
+//! REST API: accounts, trader CRUD, model/exchange config, decisions, stats
+//! and the overview.
+
+use aitrading::auth;
+use aitrading::database::{PnlSnapshot, Trade};
+use aitrading::decision::{AccountInfo, PositionInfo};
+use aitrading::logger::{self, DecisionRecord};
+use aitrading::money::Decimal;
+use aitrading::server::{AppState, EmbeddedClient};
+use aitrading::testkit;
+use aitrading::timezone;
+use axum::http::{Method, StatusCode};
+use chrono::{Duration, Utc};
+use serde_json::{Value, json};
+
+async fn sign_up(client: &EmbeddedClient, email: &str) -> (String, String) {
+    let (status, body) = client
+        .request(
+            Method::POST,
+            "/api/register",
+            Some(&json!({ "email": email, "password": "correct horse" })),
+        )
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::CREATED);
+    (
+        body["token"].as_str().unwrap().to_string(),
+        body["user"]["id"].as_str().unwrap().to_string(),
+    )
+}
+
+#[tokio::test]
+async fn accounts_traders_and_configs() {
+    auth::set_jwt_secret("api-test-secret");
+    let db = testkit::memory_db().await.unwrap();
+    let client = EmbeddedClient::new(AppState {
+        db: db.clone(),
+        scheduler: None,
+        cipher: None,
+    });
+
+    let (_, user_id) = sign_up(&client, "Alice@Example.com").await;
+    let (status, _) = client
+        .request(
+            Method::POST,
+            "/api/register",
+            Some(&json!({ "email": "alice@example.com", "password": "another one" })),
+        )
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::CONFLICT);
+    let (status, _) = client
+        .request(
+            Method::POST,
+            "/api/register",
+            Some(&json!({ "email": "bob@example.com", "password": "short" })),
+        )
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _) = client
+        .request(
+            Method::POST,
+            "/api/login",
+            Some(&json!({ "email": "alice@example.com", "password": "wrong password" })),
+        )
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, body) = client
+        .request(
+            Method::POST,
+            "/api/login",
+            Some(&json!({ "email": "alice@example.com", "password": "correct horse" })),
+        )
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::OK);
+    assert!(body["user"].get("password_hash").is_none());
+
+    let (status, _) = client
+        .request(Method::GET, "/api/traders", None)
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let alice = client.clone().with_token(body["token"].as_str().unwrap());
+
+    // Secrets are stored but only shown masked.
+    let (status, body) = alice
+        .request(
+            Method::PUT,
+            "/api/models/deepseek",
+            Some(&json!({ "enabled": true, "api_key": "sk-1234567890abcd" })),
+        )
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body[0]["apiKey"], "****abcd");
+    let model_id = body[0]["id"].as_str().unwrap().to_string();
+    let (status, body) = alice
+        .request(
+            Method::PUT,
+            "/api/exchanges/binance",
+            Some(&json!({ "enabled": true, "api_key": "key-123456789", "secret_key": "s" })),
+        )
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body[0]["SecretKey"], "****");
+    // Leaving a secret out keeps it.
+    alice
+        .request(
+            Method::PUT,
+            "/api/exchanges/binance",
+            Some(&json!({ "enabled": false })),
+        )
+        .await
+        .unwrap();
+    let stored = db.get_exchanges(&user_id).await.unwrap();
+    assert_eq!(stored[0].api_key, "key-123456789");
+    assert!(!stored[0].enabled);
+
+    let (status, _) = alice
+        .request(
+            Method::POST,
+            "/api/traders",
+            Some(&json!({
+                "name": "t", "ai_model_id": "missing", "exchange_id": "binance",
+                "initial_balance": 1000.0,
+            })),
+        )
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, trader) = alice
+        .request(
+            Method::POST,
+            "/api/traders",
+            Some(&json!({
+                "name": "t", "ai_model_id": model_id, "exchange_id": "binance",
+                "initial_balance": 1000.0, "custom_prompt": "Trade BTC.",
+            })),
+        )
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(trader["scan_interval_minutes"], 3);
+    assert_eq!(trader["custom_prompt"], "Trade BTC.");
+    let id = trader["id"].as_str().unwrap().to_string();
+
+    // Prompt edits made through a trader update are versioned.
+    let (status, trader) = alice
+        .request(
+            Method::PUT,
+            &format!("/api/traders/{id}"),
+            Some(&json!({ "scan_interval_minutes": 5, "custom_prompt": "Trade ETH." })),
+        )
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(trader["scan_interval_minutes"], 5);
+    assert_eq!(trader["custom_prompt"], "Trade ETH.");
+    let (_, versions) = alice
+        .request(
+            Method::GET,
+            &format!("/api/traders/{id}/prompt/versions"),
+            None,
+        )
+        .await
+        .unwrap();
+    assert_eq!(versions[0]["author"], "alice@example.com");
+    assert_eq!(versions.as_array().unwrap().len(), 2);
+
+    // Other users cannot see it.
+    let (bob_token, _) = sign_up(&client, "bob@example.com").await;
+    let bob = client.clone().with_token(bob_token);
+    let (status, _) = bob
+        .request(Method::GET, &format!("/api/traders/{id}"), None)
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    db.update_trader_status(&user_id, &id, true).await.unwrap();
+    let (status, _) = alice
+        .request(Method::DELETE, &format!("/api/traders/{id}"), None)
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::CONFLICT);
+    db.update_trader_status(&user_id, &id, false).await.unwrap();
+    let (status, _) = alice
+        .request(Method::DELETE, &format!("/api/traders/{id}"), None)
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (_, traders) = alice
+        .request(Method::GET, "/api/traders", None)
+        .await
+        .unwrap();
+    assert_eq!(traders, json!([]));
+}
+
+#[tokio::test]
+async fn decisions_and_performance() {
+    auth::set_jwt_secret("api-test-secret");
+    let db = testkit::memory_db().await.unwrap();
+    let client = EmbeddedClient::new(AppState {
+        db: db.clone(),
+        scheduler: None,
+        cipher: None,
+    });
+    let (token, user_id) = sign_up(&client, "carol@example.com").await;
+    let carol = client.with_token(token);
+    carol
+        .request(
+            Method::PUT,
+            "/api/models/qwen",
+            Some(&json!({ "enabled": true })),
+        )
+        .await
+        .unwrap();
+    carol
+        .request(
+            Method::PUT,
+            "/api/exchanges/binance",
+            Some(&json!({ "enabled": true })),
+        )
+        .await
+        .unwrap();
+    let (_, models) = carol
+        .request(Method::GET, "/api/models", None)
+        .await
+        .unwrap();
+    let (_, trader) = carol
+        .request(
+            Method::POST,
+            "/api/traders",
+            Some(&json!({
+                "name": "t", "ai_model_id": models[0]["id"], "exchange_id": "binance",
+                "initial_balance": 1000.0,
+            })),
+        )
+        .await
+        .unwrap();
+    let id = trader["id"].as_str().unwrap().to_string();
+
+    let record = DecisionRecord::new("system", "input", "thinking", "[]");
+    logger::store_record(&db, &user_id, &id, &record, None)
+        .await
+        .unwrap();
+    let (status, decisions) = carol
+        .request(
+            Method::GET,
+            &format!("/api/traders/{id}/decisions?limit=10"),
+            None,
+        )
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(decisions.as_array().unwrap().len(), 1);
+    assert_eq!(decisions[0]["cot_trace"], "thinking");
+
+    let now = Utc::now();
+    for (hours_ago, equity) in [(2, 1000), (1, 1100)] {
+        db.save_pnl_snapshot(&PnlSnapshot {
+            user_id: user_id.clone(),
+            trader_id: id.clone(),
+            taken_at: now - Duration::hours(hours_ago),
+            total_equity: Decimal::from(equity),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    }
+    db.record_trade(&Trade {
+        user_id: user_id.clone(),
+        trader_id: id.clone(),
+        symbol: "BTCUSDT".to_string(),
+        side: "buy".to_string(),
+        action: "open_long".to_string(),
+        quantity: Decimal::new(5, 1),
+        price: Decimal::ONE_HUNDRED,
+        fee: Decimal::new(2, 2),
+        executed_at: now,
+        ..Default::default()
+    })
+    .await
+    .unwrap();
+    let (status, stats): (_, Value) = carol
+        .request(
+            Method::GET,
+            &format!("/api/traders/{id}/performance?days=7"),
+            None,
+        )
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(stats["trades"], 1);
+    assert_eq!(stats["volume"], 50.0);
+    assert_eq!(stats["returns"]["pnl"], 100.0);
+}
+
+#[tokio::test]
+async fn overview_joins_each_trader_of_the_caller() {
+    auth::set_jwt_secret("api-test-secret");
+    let db = testkit::memory_db().await.unwrap();
+    let client = EmbeddedClient::new(AppState {
+        db: db.clone(),
+        scheduler: None,
+        cipher: None,
+    });
+    let (token, user_id) = sign_up(&client, "dave@example.com").await;
+    let dave = client.clone().with_token(token);
+    for path in ["/api/models/qwen", "/api/exchanges/binance"] {
+        dave.request(Method::PUT, path, Some(&json!({ "enabled": true })))
+            .await
+            .unwrap();
+    }
+    let (_, models) = dave
+        .request(Method::GET, "/api/models", None)
+        .await
+        .unwrap();
+    let mut ids = Vec::new();
+    for name in ["quiet", "busy"] {
+        let (_, trader) = dave
+            .request(
+                Method::POST,
+                "/api/traders",
+                Some(&json!({
+                    "name": name, "ai_model_id": models[0]["id"], "exchange_id": "binance",
+                    "initial_balance": 1000.0,
+                })),
+            )
+            .await
+            .unwrap();
+        ids.push(trader["id"].as_str().unwrap().to_string());
+    }
+    let busy = &ids[1];
+
+    let mut record = DecisionRecord::new("system", "input", "", "[]");
+    record.set_account(
+        &AccountInfo {
+            total_equity: 1_050.0,
+            position_count: 1,
+            ..Default::default()
+        },
+        &[PositionInfo {
+            symbol: "BTCUSDT".to_string(),
+            side: "long".to_string(),
+            entry_price: 100.0,
+            mark_price: 105.0,
+            quantity: 2.0,
+            leverage: 5,
+            ..Default::default()
+        }],
+    );
+    record.set_error("AI call timed out");
+    logger::store_record(&db, &user_id, busy, &record, None)
+        .await
+        .unwrap();
+    let now = Utc::now();
+    let (day_start, _) = timezone::day_bounds(chrono_tz::UTC, now);
+    for (at, equity) in [(day_start, 1000), (now, 1050)] {
+        db.save_pnl_snapshot(&PnlSnapshot {
+            user_id: user_id.clone(),
+            trader_id: busy.clone(),
+            taken_at: at,
+            total_equity: Decimal::from(equity),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    }
+    db.add_ai_usage(&user_id, busy, now.date_naive(), 1200, 0.25)
+        .await
+        .unwrap();
+
+    let (status, overview) = dave
+        .request(Method::GET, "/api/overview", None)
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::OK, "{overview}");
+    assert_eq!(overview["pnl_today"], 50.0);
+    assert_eq!(overview["ai_cost_today_usd"], 0.25);
+    assert_eq!(overview["risk_override"]["halted"], false);
+    let traders = overview["traders"].as_array().unwrap();
+    assert_eq!(traders.len(), 2);
+    let busy = traders
+        .iter()
+        .find(|t| t["trader"]["id"] == busy.as_str())
+        .unwrap();
+    assert_eq!(busy["equity"], 1050.0);
+    assert_eq!(busy["positions"][0]["symbol"], "BTCUSDT");
+    assert_eq!(busy["pnl_today"]["pnl"], 50.0);
+    assert_eq!(busy["loss_limits"]["halted"], false);
+    assert_eq!(busy["last_decision"]["success"], false);
+    assert_eq!(busy["last_decision"]["error_message"], "AI call timed out");
+    assert_eq!(busy["ai_usage_today"]["calls"], 1);
+    let quiet = traders
+        .iter()
+        .find(|t| t["trader"]["name"] == "quiet")
+        .unwrap();
+    assert!(quiet["last_decision"].is_null());
+    assert!(quiet["pnl_today"].is_null());
+    assert_eq!(quiet["ai_usage_today"]["cost_usd"], 0.0);
+
+    // Other users only see their own traders.
+    let (token, _) = sign_up(&client, "erin@example.com").await;
+    let (_, overview) = client
+        .with_token(token)
+        .request(Method::GET, "/api/overview", None)
+        .await
+        .unwrap();
+    assert!(overview["traders"].as_array().unwrap().is_empty());
+}
@@ -0,0 +1,168 @@
+//! Human approval of trades above a trader's notional threshold.
+
+use aitrading::approval::{self, ApprovalError};
+use aitrading::database::{TradeProposal, TraderRecord};
+use aitrading::decision::{Action, Decision};
+use aitrading::runner::{RunnerConfig, TraderCycle};
+use aitrading::server::{AppState, EmbeddedClient};
+use aitrading::testkit::{self, MockAiProvider, MockExchange};
+use axum::http::{Method, StatusCode};
+use chrono::{Duration, Utc};
+use uuid::Uuid;
+
+fn open_long(symbol: &str, size: f64) -> Decision {
+    Decision {
+        leverage: 5,
+        position_size_usd: size,
+        ..Decision::new(symbol, Action::OpenLong)
+    }
+}
+
+#[tokio::test]
+async fn large_entries_wait_for_approval() {
+    let db = testkit::memory_db().await.unwrap();
+    let trader = TraderRecord {
+        id: "t1".to_string(),
+        user_id: "admin".to_string(),
+        name: "approver".to_string(),
+        ai_model_id: "deepseek".to_string(),
+        exchange_id: "binance".to_string(),
+        initial_balance: 1000.0,
+        btc_eth_leverage: 5,
+        altcoin_leverage: 5,
+        trading_symbols: "BTCUSDT,ETHUSDT".to_string(),
+        approval_threshold_usd: 200.0,
+        approval_ttl_minutes: 15,
+        ..Default::default()
+    };
+    db.create_trader(&trader).await.unwrap();
+    let log_dir = std::env::temp_dir().join(format!("aitrading-approval-{}", Uuid::new_v4()));
+    let config = RunnerConfig {
+        log_dir: log_dir.to_string_lossy().to_string(),
+        ..Default::default()
+    };
+    let mut ai = MockAiProvider::new();
+    ai.push_decisions(&[open_long("BTCUSDT", 500.0), open_long("ETHUSDT", 100.0)]);
+    let mut exchange = MockExchange::new(1000.0);
+    exchange.set_price("BTCUSDT", 100.0);
+    exchange.set_price("ETHUSDT", 10.0);
+    let mut cycle = TraderCycle::new(
+        db.clone(),
+        trader.clone(),
+        exchange,
+        Box::new(ai),
+        config.logger(&trader),
+        &config,
+    );
+
+    // Only the small entry executes; the large one becomes a proposal.
+    let record = cycle.run_cycle().await.unwrap();
+    let json = serde_json::to_value(&record).unwrap();
+    assert_eq!(json["decisions"].as_array().unwrap().len(), 1);
+    assert_eq!(json["decisions"][0]["symbol"], "ETHUSDT");
+    assert!(json["execution_log"].to_string().contains("等待审批"));
+    let pending = db
+        .get_trade_proposals("admin", "t1", "pending")
+        .await
+        .unwrap();
+    assert_eq!(pending.len(), 1);
+    assert_eq!(pending[0].symbol, "BTCUSDT");
+    assert_eq!(pending[0].notional_usd, 500.0);
+    assert!(pending[0].expires_at <= Utc::now() + Duration::minutes(15));
+
+    let client = EmbeddedClient::new(AppState {
+        db: db.clone(),
+        scheduler: None,
+        cipher: None,
+    })
+    .with_token(testkit::admin_token());
+    let (status, body) = client
+        .request(
+            Method::GET,
+            "/api/traders/t1/proposals?status=pending",
+            None,
+        )
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::OK);
+    let id = body[0]["id"].as_str().unwrap().to_string();
+    let (status, body) = client
+        .request(
+            Method::POST,
+            &format!("/api/traders/t1/proposals/{id}/approve"),
+            None,
+        )
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["status"], "approved");
+    assert_eq!(body["decided_by"], "admin@localhost");
+    let (status, _) = client
+        .request(
+            Method::POST,
+            &format!("/api/traders/t1/proposals/{id}/reject"),
+            None,
+        )
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::CONFLICT);
+    let (status, _) = client
+        .request(
+            Method::POST,
+            "/api/traders/t1/proposals/missing/approve",
+            None,
+        )
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    // The next cycle executes it and records who approved it.
+    let record = cycle.run_cycle().await.unwrap();
+    let json = serde_json::to_value(&record).unwrap();
+    assert_eq!(json["decisions"][0]["symbol"], "BTCUSDT");
+    assert_eq!(json["decisions"][0]["success"], true);
+    assert_eq!(json["decisions"][0]["approved_by"], "admin@localhost");
+    let proposal = db
+        .get_trade_proposal("admin", "t1", &id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(proposal.status, "executed");
+    assert_eq!(db.get_trades("admin", "t1", None).await.unwrap().len(), 2);
+
+    // Proposals past their expiry can no longer be approved and are swept.
+    let stale = TradeProposal {
+        id: "stale".to_string(),
+        user_id: "admin".to_string(),
+        trader_id: "t1".to_string(),
+        symbol: "BTCUSDT".to_string(),
+        action: "open_long".to_string(),
+        decision: serde_json::to_string(&open_long("BTCUSDT", 500.0)).unwrap(),
+        notional_usd: 500.0,
+        status: "pending".to_string(),
+        created_at: Utc::now() - Duration::hours(1),
+        expires_at: Utc::now() - Duration::minutes(1),
+        decided_by: String::new(),
+        decided_at: None,
+        result: String::new(),
+    };
+    db.create_trade_proposal(&stale).await.unwrap();
+    assert!(matches!(
+        approval::approve(&db, "admin", "t1", "stale", "admin").await,
+        Err(ApprovalError::NotPending(status)) if status == "expired"
+    ));
+    let record = cycle.run_cycle().await.unwrap();
+    assert!(
+        serde_json::to_value(&record).unwrap()["execution_log"]
+            .to_string()
+            .contains("已过期")
+    );
+    let stale = db
+        .get_trade_proposal("admin", "t1", "stale")
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(stale.status, "expired");
+
+    let _ = std::fs::remove_dir_all(&log_dir);
+}
@@ -0,0 +1,102 @@
+//! Market data from a local stub of Aster's public futures endpoints.
+
+use aitrading::aster::{self, AsterClient};
+use aitrading::runner::Venue;
+use aitrading::types::MarketDataSource;
+use axum::Router;
+use axum::extract::Query;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use serde_json::{Value, json};
+use std::collections::HashMap;
+
+const KEY: &str = "0x4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318";
+
+async fn klines(Query(query): Query<HashMap<String, String>>) -> Response {
+    if query["symbol"] != "ETHUSDT" {
+        return (
+            StatusCode::BAD_REQUEST,
+            axum::Json(json!({"code": -1121, "msg": "Invalid symbol."})),
+        )
+            .into_response();
+    }
+    let limit: i64 = query["limit"].parse().unwrap();
+    let rows: Vec<Value> = (0..limit.min(3))
+        .map(|i| {
+            let open_time = 1_700_000_000_000i64 + i * 180_000;
+            json!([
+                open_time,
+                "2000.0",
+                "2010.0",
+                "1990.0",
+                format!("{}", 2000 + i),
+                "10.0",
+                open_time + 179_999,
+                "20000.0",
+                25,
+                "5.0",
+                "10000.0",
+                "0"
+            ])
+        })
+        .collect();
+    axum::Json(Value::Array(rows)).into_response()
+}
+
+async fn premium_index() -> axum::Json<Value> {
+    axum::Json(json!({
+        "symbol": "ETHUSDT", "markPrice": "2003.40", "indexPrice": "2003.10",
+        "lastFundingRate": "0.00012", "nextFundingTime": 1_700_000_000_000i64,
+    }))
+}
+
+async fn stub(open_interest: bool) -> String {
+    let mut app = Router::new()
+        .route("/fapi/v1/klines", get(klines))
+        .route("/fapi/v1/premiumIndex", get(premium_index));
+    if open_interest {
+        app = app.route(
+            "/fapi/v1/openInterest",
+            get(|| async { axum::Json(json!({"symbol": "ETHUSDT", "openInterest": "4200.5"})) }),
+        );
+    }
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    format!("http://{}", addr)
+}
+
+#[tokio::test]
+async fn fetches_candles_mark_price_funding_and_open_interest() {
+    let url = stub(true).await;
+    let raw = aster::fetch_market_data_from(&url, "ETHUSDT")
+        .await
+        .unwrap();
+
+    let closes: Vec<f64> = raw.klines_3m.iter().map(|k| k.close).collect();
+    assert_eq!(closes, [2000.0, 2001.0, 2002.0]);
+    assert_eq!(raw.klines_4h.len(), 3);
+    assert_eq!(raw.mark_price, Some(2003.4));
+    assert_eq!(raw.funding_rate, Some(0.00012));
+    assert_eq!(raw.open_interest.unwrap().latest, 4200.5);
+
+    assert!(aster::klines(&url, "NOPEUSDT", "3m", 50).await.is_err());
+}
+
+#[tokio::test]
+async fn open_interest_is_optional() {
+    let url = stub(false).await;
+    let raw = aster::fetch_market_data_from(&url, "ETHUSDT")
+        .await
+        .unwrap();
+    assert!(raw.open_interest.is_none());
+    assert_eq!(raw.mark_price, Some(2003.4));
+}
+
+#[test]
+fn aster_traders_read_aster_market_data() {
+    let signer = aster::address_for_key(KEY).unwrap();
+    let client = AsterClient::new(&signer, &signer, KEY).unwrap();
+    assert_eq!(client.market_source(), MarketDataSource::Aster);
+}
@@ -0,0 +1,80 @@
+//! Raw order payloads in the execution audit trail.
+
+use std::time::Duration;
+
+use aitrading::api_client::ApiClient;
+use aitrading::audit::{self, AuditParams};
+use aitrading::money::Decimal;
+use aitrading::testkit;
+use aitrading::types::{OrderRequest, OrderSide};
+use axum::Router;
+use axum::http::StatusCode;
+use axum::routing::{get, post};
+use serde_json::{Value, json};
+
+async fn stub() -> String {
+    let app = Router::new()
+        .route(
+            "/fapi/v1/order",
+            post(|| async {
+                (
+                    StatusCode::BAD_REQUEST,
+                    axum::Json(json!({"code": -2019, "msg": "Margin is insufficient."})),
+                )
+            }),
+        )
+        .route("/fapi/v2/balance", get(|| async { axum::Json(json!([])) }));
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    format!("http://{}", addr)
+}
+
+// One test owns the process-wide audit database.
+#[tokio::test]
+async fn order_calls_are_stored_without_secrets_and_pruned() {
+    let db = testkit::memory_db().await.unwrap();
+    audit::set_database(db.clone());
+    let client = ApiClient::new()
+        .with_base_url(&stub().await)
+        .with_credentials("key", "secret")
+        .with_audit_owner("user-1", "trader-1");
+
+    let order = OrderRequest::market("BTCUSDT", OrderSide::Buy, Decimal::new(1, 2));
+    let err = client.place_order(&order).await.unwrap_err();
+    assert!(err.to_string().contains("-2019"));
+    // Account reads are not order calls.
+    client.get_balances().await.unwrap();
+
+    let entries = db
+        .get_execution_audit("user-1", "trader-1", 10)
+        .await
+        .unwrap();
+    assert_eq!(entries.len(), 1);
+    let entry = &entries[0];
+    assert_eq!(entry.exchange, "binance");
+    assert_eq!(entry.method, "POST");
+    assert_eq!(entry.endpoint, "/fapi/v1/order");
+    assert_eq!(entry.status, 400);
+    assert!(entry.response.contains("Margin is insufficient"));
+    let request: Value = serde_json::from_str(&entry.request).unwrap();
+    assert_eq!(request["symbol"], "BTCUSDT");
+    assert_eq!(request["side"], "BUY");
+    assert!(request.get("timestamp").is_some());
+    assert!(request.get("signature").is_none());
+    assert!(!entry.request.contains("secret"));
+
+    let keep_all = AuditParams::default();
+    assert_eq!(audit::prune(&db, &keep_all).await.unwrap(), 0);
+    let keep_none = AuditParams {
+        retention: Duration::from_secs(3600),
+        max_rows: 0,
+    };
+    assert_eq!(audit::prune(&db, &keep_none).await.unwrap(), 1);
+    assert!(
+        db.get_execution_audit("user-1", "trader-1", 10)
+            .await
+            .unwrap()
+            .is_empty()
+    );
+}
@@ -0,0 +1,181 @@
+//! Audit log of configuration and trading-state changes.
+
+use aitrading::audit_log;
+use aitrading::database::TraderRecord;
+use aitrading::pause;
+use aitrading::server::{AppState, EmbeddedClient};
+use aitrading::testkit;
+use axum::http::{Method, StatusCode};
+use chrono::{Duration, Utc};
+use serde_json::{Value, json};
+
+#[test]
+fn credentials_are_redacted_at_any_depth() {
+    let redacted = audit_log::redact(json!({
+        "apiKey": "live-key",
+        "SecretKey": "",
+        "aster_private_key": "vault://kv/aster#key",
+        "asterSigner": "0xabc",
+        "code": "123456",
+        "models": [{ "api_key": "sk-live", "enabled": true }],
+    }));
+    assert_eq!(
+        redacted,
+        json!({
+            "apiKey": "[redacted]",
+            "SecretKey": "",
+            "aster_private_key": "vault://kv/aster#key",
+            "asterSigner": "0xabc",
+            "code": "[redacted]",
+            "models": [{ "api_key": "[redacted]", "enabled": true }],
+        })
+    );
+}
+
+fn parse(value: &Value) -> Value {
+    serde_json::from_str(value.as_str().unwrap()).unwrap()
+}
+
+#[tokio::test]
+async fn api_writes_are_recorded_with_old_and_new_values() {
+    let db = testkit::memory_db().await.unwrap();
+    db.ensure_admin_user().await.unwrap();
+    let client = EmbeddedClient::new(AppState {
+        db: db.clone(),
+        scheduler: None,
+        cipher: None,
+    })
+    .with_token(testkit::admin_token());
+
+    for (path, body) in [
+        (
+            "/api/exchanges/binance",
+            json!({ "enabled": true, "api_key": "first-key", "secret_key": "first-secret" }),
+        ),
+        (
+            "/api/exchanges/binance",
+            json!({ "enabled": true, "api_key": "second-key" }),
+        ),
+    ] {
+        let (status, body) = client
+            .request(Method::PUT, path, Some(&body))
+            .await
+            .unwrap();
+        assert_eq!(status, StatusCode::OK, "{}", body);
+    }
+    let (_, models) = client
+        .request(
+            Method::PUT,
+            "/api/models/deepseek",
+            Some(&json!({ "enabled": true, "api_key": "sk-live" })),
+        )
+        .await
+        .unwrap();
+    let (status, trader) = client
+        .request(
+            Method::POST,
+            "/api/traders",
+            Some(&json!({
+                "name": "audited", "ai_model_id": models[0]["id"], "exchange_id": "binance",
+                "initial_balance": 1000.0, "btc_eth_leverage": 5,
+            })),
+        )
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::CREATED, "{}", trader);
+    let id = trader["id"].as_str().unwrap();
+    client
+        .request(
+            Method::PUT,
+            &format!("/api/traders/{id}"),
+            Some(&json!({ "btc_eth_leverage": 10 })),
+        )
+        .await
+        .unwrap();
+    // Failed writes and reads are not recorded.
+    let (status, _) = client
+        .request(
+            Method::PUT,
+            "/api/traders/missing",
+            Some(&json!({ "btc_eth_leverage": 10 })),
+        )
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    client
+        .request(Method::GET, "/api/traders", None)
+        .await
+        .unwrap();
+
+    let (status, entries) = client
+        .request(Method::GET, "/api/admin/audit-log", None)
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::OK, "{}", entries);
+    let entries = entries.as_array().unwrap();
+    assert_eq!(entries.len(), 5);
+    assert!(entries.iter().all(|e| e["user_id"] == "admin"));
+    assert!(entries.iter().all(|e| e["source"] == "api"));
+
+    let key_change = &entries[3];
+    assert_eq!(key_change["action"], "PUT /api/exchanges/{id}");
+    assert_eq!(key_change["target"], "exchanges/binance");
+    assert_eq!(parse(&key_change["new_value"])["api_key"], "[redacted]");
+    assert_eq!(parse(&key_change["old_value"])["apiKey"], "[redacted]");
+    let stored = serde_json::to_string(entries).unwrap();
+    for secret in ["first-key", "second-key", "first-secret", "sk-live"] {
+        assert!(!stored.contains(secret), "{} leaked", secret);
+    }
+
+    let leverage = &entries[0];
+    assert_eq!(leverage["action"], "PUT /api/traders/{id}");
+    assert_eq!(parse(&leverage["old_value"])["btc_eth_leverage"], 5);
+    assert_eq!(parse(&leverage["new_value"])["btc_eth_leverage"], 10);
+
+    let (_, filtered) = client
+        .request(
+            Method::GET,
+            &format!("/api/admin/audit-log?target=traders/{id}"),
+            None,
+        )
+        .await
+        .unwrap();
+    assert_eq!(filtered.as_array().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn pause_windows_record_trader_stops_and_starts() {
+    let db = testkit::memory_db().await.unwrap();
+    db.ensure_admin_user().await.unwrap();
+    let trader = TraderRecord {
+        id: "paused".to_string(),
+        user_id: "admin".to_string(),
+        name: "paused".to_string(),
+        ai_model_id: "deepseek".to_string(),
+        exchange_id: "binance".to_string(),
+        ..Default::default()
+    };
+    db.create_trader(&trader).await.unwrap();
+    db.update_trader_status("admin", &trader.id, true)
+        .await
+        .unwrap();
+
+    let now = Utc::now();
+    pause::schedule(
+        &db,
+        "admin",
+        &trader.id,
+        now - Duration::minutes(1),
+        Some(now + Duration::hours(1)),
+        "maintenance",
+    )
+    .await
+    .unwrap();
+    pause::apply_due(&db).await.unwrap();
+
+    let entries = db.get_audit_log("", "traders/", 10).await.unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].action, "trader.stop");
+    assert_eq!(entries[0].source, "system");
+    assert_eq!(entries[0].target, format!("traders/{}", trader.id));
+}
@@ -0,0 +1,202 @@
+//! Sharing traders as signed strategy files over the API.
+
+use aitrading::auth;
+use aitrading::bundle::SignedStrategy;
+use aitrading::server::{AppState, EmbeddedClient};
+use aitrading::testkit;
+use axum::http::{Method, StatusCode};
+use ed25519_dalek::SigningKey;
+use serde_json::{Value, json};
+
+// Registers a user with a model and exchange configured; returns their client
+// and model id.
+async fn user(client: &EmbeddedClient, email: &str) -> (EmbeddedClient, Value) {
+    let (_, body) = client
+        .request(
+            Method::POST,
+            "/api/register",
+            Some(&json!({ "email": email, "password": "correct horse" })),
+        )
+        .await
+        .unwrap();
+    let user = client.clone().with_token(body["token"].as_str().unwrap());
+    user.request(
+        Method::PUT,
+        "/api/models/qwen",
+        Some(&json!({ "enabled": true, "api_key": "sk" })),
+    )
+    .await
+    .unwrap();
+    user.request(
+        Method::PUT,
+        "/api/exchanges/binance",
+        Some(&json!({ "enabled": true, "api_key": "key", "secret_key": "secret" })),
+    )
+    .await
+    .unwrap();
+    let (_, models) = user
+        .request(Method::GET, "/api/models", None)
+        .await
+        .unwrap();
+    (user, models[0]["id"].clone())
+}
+
+#[tokio::test]
+async fn traders_are_exported_and_imported_by_another_user() {
+    auth::set_jwt_secret("bundle-test-secret");
+    let db = testkit::memory_db().await.unwrap();
+    let client = EmbeddedClient::new(AppState {
+        db: db.clone(),
+        scheduler: None,
+        cipher: None,
+    });
+    let (alice, alice_model) = user(&client, "alice@example.com").await;
+    let (bob, bob_model) = user(&client, "bob@example.com").await;
+
+    alice
+        .request(
+            Method::POST,
+            "/api/prompt-templates",
+            Some(&json!({ "name": "scalper", "content": "Scalp {{symbol_list}}." })),
+        )
+        .await
+        .unwrap();
+    let (status, trader) = alice
+        .request(
+            Method::POST,
+            "/api/traders",
+            Some(&json!({
+                "name": "shared", "ai_model_id": alice_model, "exchange_id": "binance",
+                "initial_balance": 500.0, "trading_symbols": "BTCUSDT,ETHUSDT",
+                "custom_coins": "SOLUSDT", "approval_threshold_usd": 2000.0,
+                "liquidation_alert_pct": 10.0, "system_prompt_template": "scalper",
+            })),
+        )
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::CREATED, "{}", trader);
+    let id = trader["id"].as_str().unwrap();
+
+    let (status, exported) = alice
+        .request(Method::GET, &format!("/api/traders/{id}/export"), None)
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::OK, "{}", exported);
+    let strategy = &exported["strategy"];
+    assert_eq!(strategy["prompt_template"], "Scalp {{symbol_list}}.");
+    assert_eq!(strategy["custom_coins"], json!(["SOLUSDT"]));
+    assert_eq!(strategy["risk"]["approval_threshold_usd"], 2000.0);
+    assert!(!exported.to_string().contains("secret"));
+
+    // Bob has a different template of the same name, so the import gets its own.
+    bob.request(
+        Method::POST,
+        "/api/prompt-templates",
+        Some(&json!({ "name": "scalper", "content": "Mine." })),
+    )
+    .await
+    .unwrap();
+    let import = |strategy: &Value| json!({ "strategy": strategy, "ai_model_id": bob_model, "exchange_id": "binance" });
+    let (status, imported) = bob
+        .request(
+            Method::POST,
+            "/api/traders/import",
+            Some(&import(&exported)),
+        )
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::CREATED, "{}", imported);
+    assert_ne!(imported["id"], trader["id"]);
+    assert_eq!(imported["is_running"], false);
+    assert_eq!(imported["system_prompt_template"], "scalper-2");
+    assert_eq!(imported["custom_coins"], "SOLUSDT");
+    assert_eq!(imported["liquidation_alert_pct"], 10.0);
+    let (_, templates) = bob
+        .request(Method::GET, "/api/prompt-templates", None)
+        .await
+        .unwrap();
+    assert_eq!(templates["templates"].as_array().unwrap().len(), 2);
+
+    // Importing again reuses the template created the first time.
+    let (_, again) = bob
+        .request(
+            Method::POST,
+            "/api/traders/import",
+            Some(&import(&exported)),
+        )
+        .await
+        .unwrap();
+    assert_eq!(again["system_prompt_template"], "scalper-2");
+
+    let mut tampered = exported.clone();
+    tampered["strategy"]["altcoin_leverage"] = json!(20);
+    let (status, _) = bob
+        .request(
+            Method::POST,
+            "/api/traders/import",
+            Some(&import(&tampered)),
+        )
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    // Edited and re-signed with another key: valid signature, unknown signer.
+    let mut edited: SignedStrategy = serde_json::from_value(exported.clone()).unwrap();
+    edited.strategy.altcoin_leverage = 20;
+    let resigned = SignedStrategy::sign(
+        edited.strategy,
+        &SigningKey::generate(&mut rand::rngs::OsRng),
+    )
+    .unwrap();
+    let fingerprint = resigned.signer_fingerprint();
+    let resigned = serde_json::to_value(&resigned).unwrap();
+    let (status, error) = bob
+        .request(
+            Method::POST,
+            "/api/traders/import",
+            Some(&import(&resigned)),
+        )
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert!(error["error"].as_str().unwrap().contains(&fingerprint));
+    let mut confirmed = import(&resigned);
+    confirmed["trust_signer"] = json!("0000000000000000");
+    let (status, _) = bob
+        .request(Method::POST, "/api/traders/import", Some(&confirmed))
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::CONFLICT);
+    confirmed["trust_signer"] = json!(fingerprint);
+    let (status, imported) = bob
+        .request(Method::POST, "/api/traders/import", Some(&confirmed))
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::CREATED, "{}", imported);
+    assert_eq!(imported["altcoin_leverage"], 20);
+    // Trusted from now on, but only by the user who confirmed it.
+    let (status, _) = bob
+        .request(
+            Method::POST,
+            "/api/traders/import",
+            Some(&import(&resigned)),
+        )
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::CREATED);
+    let (status, _) = alice
+        .request(
+            Method::POST,
+            "/api/traders/import",
+            Some(&json!({ "strategy": resigned, "ai_model_id": alice_model, "exchange_id": "binance" })),
+        )
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    let (status, _) = bob
+        .request(Method::GET, &format!("/api/traders/{id}/export"), None)
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
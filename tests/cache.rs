@@ -0,0 +1,51 @@
+//! The shared bounded cache: LRU eviction, TTL expiry and stats.
+
+use aitrading::cache::BoundedCache;
+use std::time::Duration;
+
+#[test]
+fn full_cache_evicts_the_least_recently_used_entry() {
+    let cache = BoundedCache::new(2, Duration::from_secs(60));
+    cache.insert("a", 1);
+    cache.insert("b", 2);
+    // 读取 a 使 b 成为最久未使用的条目
+    assert_eq!(cache.get(&"a"), Some(1));
+    cache.insert("c", 3);
+
+    assert_eq!(cache.len(), 2);
+    assert_eq!(cache.get(&"b"), None);
+    assert_eq!(cache.get(&"a"), Some(1));
+    assert_eq!(cache.get(&"c"), Some(3));
+
+    // 覆盖已有的键不触发淘汰
+    cache.insert("c", 4);
+    assert_eq!(cache.get(&"c"), Some(4));
+
+    let stats = cache.stats();
+    assert_eq!(stats.evictions, 1);
+    assert_eq!(stats.hits, 4);
+    assert_eq!(stats.misses, 1);
+}
+
+#[test]
+fn expired_entries_are_dropped_before_evicting_live_ones() {
+    let cache = BoundedCache::new(2, Duration::from_secs(60));
+    cache.insert_with_ttl("short", 1, Duration::from_millis(20));
+    cache.insert("long", 2);
+    assert!(cache.contains(&"short"));
+
+    std::thread::sleep(Duration::from_millis(40));
+    assert!(!cache.contains(&"short"));
+    assert_eq!(cache.get(&"short"), None);
+
+    cache.insert_with_ttl("other", 3, Duration::from_millis(20));
+    std::thread::sleep(Duration::from_millis(40));
+    cache.insert("new", 4);
+
+    assert_eq!(cache.get(&"long"), Some(2));
+    assert_eq!(cache.get(&"new"), Some(4));
+    let stats = cache.stats();
+    assert_eq!(stats.evictions, 0);
+    assert_eq!(stats.expirations, 2);
+    assert_eq!(stats.misses, 1);
+}
@@ -0,0 +1,144 @@
+//! Economic calendar feeds, prompt notes and trading blackouts.
+
+use std::time::Duration;
+
+use aitrading::calendar::{self, CalendarParams, Event, FeedFormat, Impact};
+use aitrading::testkit::Harness;
+use chrono::{TimeZone, Utc};
+
+const ICS: &str = "BEGIN:VCALENDAR\r
+VERSION:2.0\r
+BEGIN:VEVENT\r
+SUMMARY:US CPI\r
+  (YoY)\r
+DTSTART:20250115T133000Z\r
+X-CURRENCY:USD\r
+X-IMPACT:High\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+SUMMARY:FOMC Rate Decision\r
+DTSTART;TZID=America/New_York:20250129T140000\r
+CATEGORIES:USD,Central bank\r
+PRIORITY:1\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+SUMMARY:German Factory Orders\r
+DTSTART;VALUE=DATE:20250106\r
+CATEGORIES:EUR\r
+PRIORITY:7\r
+END:VEVENT\r
+END:VCALENDAR\r
+";
+
+fn event(title: &str, time: chrono::DateTime<Utc>) -> Event {
+    Event {
+        title: title.to_string(),
+        currency: "USD".to_string(),
+        impact: Impact::High,
+        time,
+    }
+}
+
+#[test]
+fn parses_ics_and_json_feeds() {
+    let events = calendar::parse(ICS, FeedFormat::Auto).unwrap();
+    assert_eq!(events.len(), 3);
+    assert_eq!(events[0].title, "US CPI (YoY)");
+    assert_eq!(
+        events[0].time,
+        Utc.with_ymd_and_hms(2025, 1, 15, 13, 30, 0).unwrap()
+    );
+    assert_eq!(events[1].currency, "USD");
+    assert_eq!(
+        events[1].time,
+        Utc.with_ymd_and_hms(2025, 1, 29, 19, 0, 0).unwrap()
+    );
+    assert_eq!(events[2].impact, Impact::Low);
+
+    let params = CalendarParams::default();
+    let relevant: Vec<_> = events
+        .iter()
+        .filter(|e| calendar::is_relevant(e, &params))
+        .collect();
+    assert_eq!(relevant.len(), 2);
+
+    let json = r#"[
+        {"title": "Non-Farm Employment Change", "country": "USD", "date": "2025-01-10T08:30:00-05:00", "impact": "High"},
+        {"title": "Bank Holiday", "country": "JPY", "date": "not a date", "impact": "Holiday"}
+    ]"#;
+    let events = calendar::parse(json, FeedFormat::Auto).unwrap();
+    assert_eq!(events.len(), 1);
+    assert_eq!(
+        events[0].time,
+        Utc.with_ymd_and_hms(2025, 1, 10, 13, 30, 0).unwrap()
+    );
+}
+
+#[test]
+fn overlapping_blackouts_are_merged() {
+    let cpi = Utc.with_ymd_and_hms(2025, 1, 15, 13, 30, 0).unwrap();
+    let events = [
+        event("US CPI", cpi),
+        event("Core CPI", cpi),
+        event("Retail Sales", cpi + chrono::Duration::hours(3)),
+    ];
+    let spans = calendar::blackouts(
+        &events,
+        Duration::from_secs(15 * 60),
+        Duration::from_secs(30 * 60),
+    );
+    assert_eq!(spans.len(), 2);
+    assert_eq!(spans[0].start, cpi - chrono::Duration::minutes(15));
+    assert_eq!(spans[0].end, cpi + chrono::Duration::minutes(30));
+    assert_eq!(spans[0].reason, "calendar: US CPI (USD), Core CPI (USD)");
+}
+
+// One test owns the process-wide calendar state.
+#[tokio::test]
+async fn upcoming_events_reach_the_prompt_and_pause_traders() {
+    let now = Utc::now();
+    calendar::set_params(CalendarParams {
+        blackout: true,
+        ..Default::default()
+    });
+    calendar::set_events(vec![
+        event("US CPI", now + chrono::Duration::minutes(42)),
+        event("FOMC Minutes", now + chrono::Duration::days(3)),
+    ]);
+
+    let note = calendar::prompt_annotation(now).unwrap();
+    assert!(note.contains("- US CPI (USD) in 42 minutes"), "{}", note);
+    assert!(!note.contains("FOMC"));
+
+    let mut h = Harness::new(&["BTCUSDT"], 1000.0).await.unwrap();
+    h.exchange.set_price("BTCUSDT", 100.0);
+    h.ai.push_response("[]");
+    h.run_cycle().await.unwrap();
+    assert!(h.ai.calls()[0].user_prompt.contains("- US CPI (USD) in 4"));
+
+    let params = calendar::params();
+    assert_eq!(
+        calendar::schedule_blackouts(&h.db, &params, now)
+            .await
+            .unwrap(),
+        1
+    );
+    // Already scheduled windows are not duplicated.
+    assert_eq!(
+        calendar::schedule_blackouts(&h.db, &params, now)
+            .await
+            .unwrap(),
+        0
+    );
+
+    let windows =
+        h.db.get_pause_windows(&h.user_id, &h.trader.id)
+            .await
+            .unwrap();
+    assert_eq!(windows.len(), 1);
+    assert_eq!(windows[0].pause_at, now + chrono::Duration::minutes(27));
+    assert_eq!(
+        windows[0].resume_at,
+        Some(now + chrono::Duration::minutes(72))
+    );
+}
@@ -0,0 +1,145 @@
+//! Config files in JSON, TOML and YAML, with environment overrides, in the
+//! clear or encrypted.
+
+use aitrading::config::{self, ConfigError, ConfigFormat, ConfigKey};
+
+const TOML: &str = r#"
+api_server_port = 8081
+default_coins = ["BTCUSDT"]
+
+[leverage]
+btc_eth_leverage = 3
+
+[[traders]]
+id = "t1"
+name = "Main"
+enabled = true
+ai_model = "deepseek"
+binance_api_key = "from-file"
+initial_balance = 1000.0
+"#;
+
+const YAML: &str = r#"
+api_server_port: 8081
+default_coins: [BTCUSDT]
+leverage:
+  btc_eth_leverage: 3
+traders:
+  - id: t1
+    name: Main
+    enabled: true
+    ai_model: deepseek
+    binance_api_key: from-file
+    initial_balance: 1000.0
+"#;
+
+fn env(vars: &[(&str, &str)]) -> Vec<(String, String)> {
+    vars.iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect()
+}
+
+#[test]
+fn format_follows_the_extension() {
+    assert_eq!(ConfigFormat::from_path("config.toml"), ConfigFormat::Toml);
+    assert_eq!(
+        ConfigFormat::from_path("/etc/ait/config.YML"),
+        ConfigFormat::Yaml
+    );
+    assert_eq!(ConfigFormat::from_path("config.yaml"), ConfigFormat::Yaml);
+    assert_eq!(ConfigFormat::from_path("config.json"), ConfigFormat::Json);
+    assert_eq!(ConfigFormat::from_path("config"), ConfigFormat::Json);
+}
+
+#[test]
+fn toml_and_yaml_parse_like_json() {
+    let secrets = env(&[
+        ("AIT_TRADERS__0__BINANCE_SECRET_KEY", "s3cret"),
+        ("AIT_TRADERS__0__DEEPSEEK_KEY", "12345"),
+    ]);
+    for (data, format) in [(TOML, ConfigFormat::Toml), (YAML, ConfigFormat::Yaml)] {
+        let config = config::parse_config(data, format, secrets.clone()).unwrap();
+        assert_eq!(config.api_server_port, 8081);
+        assert_eq!(config.default_coins, ["BTCUSDT"]);
+        assert_eq!(config.leverage.btc_eth_leverage, 3);
+        assert_eq!(config.leverage.altcoin_leverage, 5);
+        assert_eq!(config.traders[0].id, "t1");
+        assert_eq!(
+            config.traders[0].binance_secret_key.as_deref(),
+            Some("s3cret")
+        );
+        // Numeric-looking secrets stay strings.
+        assert_eq!(config.traders[0].deepseek_key.as_deref(), Some("12345"));
+    }
+    assert!(matches!(
+        config::parse_config("traders = [", ConfigFormat::Toml, env(&[])),
+        Err(ConfigError::Toml(_))
+    ));
+}
+
+#[test]
+fn environment_overrides_the_file() {
+    let vars = env(&[
+        ("AIT_API_SERVER_PORT", "9090"),
+        ("AIT_TRADERS__0__BINANCE_API_KEY", "from-env"),
+        ("AIT_TRADERS__0__BINANCE_SECRET_KEY", "s3cret"),
+        ("AIT_TRADERS__0__DEEPSEEK_KEY", "sk"),
+        ("AIT_TRADERS__0__INITIAL_BALANCE", "2500"),
+        ("AIT_LEVERAGE__ALTCOIN_LEVERAGE", "2"),
+        ("AIT_DEFAULT_COINS", r#"["ETHUSDT","SOLUSDT"]"#),
+        ("AIT_TIMEZONE", "Asia/Shanghai"),
+        ("OTHER_API_SERVER_PORT", "1"),
+    ]);
+    let config = config::parse_config(TOML, ConfigFormat::Toml, vars).unwrap();
+    assert_eq!(config.api_server_port, 9090);
+    assert_eq!(
+        config.traders[0].binance_api_key.as_deref(),
+        Some("from-env")
+    );
+    assert_eq!(config.traders[0].initial_balance, 2500.0);
+    assert_eq!(config.leverage.btc_eth_leverage, 3);
+    assert_eq!(config.leverage.altcoin_leverage, 2);
+    assert_eq!(config.default_coins, ["ETHUSDT", "SOLUSDT"]);
+    assert_eq!(config.timezone, "Asia/Shanghai");
+
+    for (name, value) in [
+        ("AIT_API_SERVER_PORT", "not-a-port"),
+        ("AIT_TRADERS__5__NAME", "gap"),
+        ("AIT_API_SERVER_PORT__X", "1"),
+    ] {
+        let err =
+            config::parse_config(TOML, ConfigFormat::Toml, env(&[(name, value)])).unwrap_err();
+        assert!(
+            matches!(&err, ConfigError::EnvOverride(n, _) if n == name),
+            "{name}: {err}"
+        );
+    }
+}
+
+#[test]
+fn encrypted_files_load_with_their_key() {
+    let dir = std::env::temp_dir().join(format!("aitrading-config-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let plain = dir.join("config.toml").to_string_lossy().into_owned();
+    let sealed = dir.join("config.enc").to_string_lossy().into_owned();
+    let complete = format!("{TOML}binance_secret_key = \"s3cret\"\ndeepseek_key = \"sk\"\n");
+    std::fs::write(&plain, complete).unwrap();
+    let key = ConfigKey::Passphrase("correct horse".into());
+    config::encrypt_config_file(&plain, &sealed, &key).unwrap();
+
+    assert!(config::is_encrypted_config(&sealed).unwrap());
+    let config = config::load_config_with_key(&sealed, &key).unwrap();
+    assert_eq!(config.api_server_port, 8081);
+    assert_eq!(config.traders[0].id, "t1");
+
+    let wrong = ConfigKey::Passphrase("battery staple".into());
+    assert!(matches!(
+        config::load_config_with_key(&sealed, &wrong),
+        Err(ConfigError::Crypto(_))
+    ));
+    // Plaintext files ignore the key.
+    let config = config::load_config_with_key(&plain, &wrong).unwrap();
+    assert_eq!(config.api_server_port, 8081);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
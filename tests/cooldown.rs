@@ -0,0 +1,58 @@
+//! Re-entry cooldown after a stop-loss exit.
+
+use aitrading::cooldown;
+use aitrading::decision::{Action, Decision, PairLeg};
+use chrono::{Duration, Utc};
+
+fn open(symbol: &str) -> Decision {
+    Decision {
+        leverage: 2,
+        position_size_usd: 100.0,
+        ..Decision::new(symbol, Action::OpenLong)
+    }
+}
+
+#[test]
+fn entries_on_either_leg_wait_out_the_cooldown() {
+    let trader = "cooldown-trader";
+    cooldown::record_stop_loss(trader, "ETHUSDT", Utc::now());
+    cooldown::record_stop_loss(trader, "SOLUSDT", Utc::now() - Duration::minutes(45));
+
+    let kept = cooldown::drop_cooling_entries(
+        trader,
+        30,
+        vec![
+            open("ETHUSDT"),
+            Decision {
+                pair: Some(PairLeg {
+                    symbol: "ETHUSDT".to_string(),
+                    ratio: 1.0,
+                }),
+                ..open("BTCUSDT")
+            },
+            // Cooled down already.
+            open("SOLUSDT"),
+            Decision::new("ETHUSDT", Action::CloseLong),
+        ],
+    );
+    let kept: Vec<_> = kept.iter().map(|d| (d.symbol.as_str(), d.action)).collect();
+    assert_eq!(
+        kept,
+        vec![
+            ("SOLUSDT", Action::OpenLong),
+            ("ETHUSDT", Action::CloseLong)
+        ]
+    );
+
+    // Another trader is not affected.
+    assert_eq!(
+        cooldown::drop_cooling_entries("other", 30, vec![open("ETHUSDT")]).len(),
+        1
+    );
+    assert!(
+        cooldown::prompt_annotation(trader, 30)
+            .unwrap()
+            .contains("ETHUSDT")
+    );
+    assert!(cooldown::cooldown_until(trader, "ETHUSDT", 0).is_none());
+}
@@ -0,0 +1,57 @@
+//! Authenticated encryption of secrets at rest.
+
+use aitrading::crypto::{self, CryptoError};
+
+const MAGIC: &[u8] = b"TEST1";
+
+#[test]
+fn sealed_data_opens_only_with_its_key() {
+    let key = crypto::derive_subkey(&[7u8; 32], b"user-1");
+    let other = crypto::derive_subkey(&[7u8; 32], b"user-2");
+    assert_ne!(key, other);
+
+    let sealed = crypto::seal(&key, b"api-secret").unwrap();
+    assert_eq!(crypto::open(&key, &sealed).unwrap(), b"api-secret");
+    assert!(matches!(
+        crypto::open(&other, &sealed),
+        Err(CryptoError::Decrypt)
+    ));
+
+    // 每次加密使用新的 nonce
+    assert_ne!(crypto::seal(&key, b"api-secret").unwrap(), sealed);
+
+    let mut tampered = sealed.clone();
+    *tampered.last_mut().unwrap() ^= 1;
+    assert!(matches!(
+        crypto::open(&key, &tampered),
+        Err(CryptoError::Decrypt)
+    ));
+    assert!(matches!(
+        crypto::open(&key, &sealed[..4]),
+        Err(CryptoError::Malformed)
+    ));
+}
+
+#[test]
+fn secret_encryption_round_trips_and_rejects_bad_input() {
+    let data = crypto::encrypt_with_secret(MAGIC, b"passphrase", b"backup").unwrap();
+    assert!(data.starts_with(MAGIC));
+    assert_eq!(
+        crypto::decrypt_with_secret(MAGIC, b"passphrase", &data).unwrap(),
+        b"backup"
+    );
+    assert!(matches!(
+        crypto::decrypt_with_secret(MAGIC, b"wrong", &data),
+        Err(CryptoError::Decrypt)
+    ));
+
+    // 头部或长度不对时不做密钥派生，直接拒绝
+    assert!(matches!(
+        crypto::decrypt_with_secret(b"OTHER", b"passphrase", &data),
+        Err(CryptoError::Malformed)
+    ));
+    assert!(matches!(
+        crypto::decrypt_with_secret(MAGIC, b"passphrase", &data[..MAGIC.len() + 8]),
+        Err(CryptoError::Malformed)
+    ));
+}
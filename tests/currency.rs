@@ -0,0 +1,53 @@
+//! Balance conversion across collateral assets.
+
+use aitrading::currency::{self, Valuation};
+use aitrading::types::AccountBalance;
+
+fn balance(asset: &str, wallet: &str, available: &str) -> AccountBalance {
+    AccountBalance {
+        asset: asset.to_string(),
+        balance: wallet.to_string(),
+        cross_wallet_balance: String::new(),
+        cross_un_pnl: String::new(),
+        available_balance: available.to_string(),
+    }
+}
+
+// One test owns the process-wide reporting currency.
+#[tokio::test]
+async fn balances_are_valued_in_one_currency() {
+    currency::set_price("BTCUSDT", 60_000.0);
+    currency::set_price("USDCUSDT", 0.999);
+
+    assert_eq!(currency::rate("usdt", "USDT").await.unwrap(), 1.0);
+    assert_eq!(currency::rate("BTC", "USDT").await.unwrap(), 60_000.0);
+    // The inverse pair works too.
+    let usdt_btc = currency::rate("USDT", "BTC").await.unwrap();
+    assert!((usdt_btc - 1.0 / 60_000.0).abs() < 1e-12);
+
+    let valuation = currency::value_balances(
+        &[
+            balance("USDT", "1000", "800"),
+            balance("USDC", "500", "500"),
+            balance("BTC", "0.01", "0"),
+            balance("ETH", "0", "0"),
+        ],
+        "USDT",
+    )
+    .await;
+    assert_eq!(
+        valuation,
+        Valuation {
+            currency: "USDT".to_string(),
+            wallet: 1000.0 + 499.5 + 600.0,
+            available: 800.0 + 499.5,
+            unpriced: Vec::new(),
+        }
+    );
+
+    assert_eq!(currency::reporting_currency(), "USDT");
+    currency::set_reporting_currency("btc");
+    let in_btc = currency::to_reporting(120_000.0, "USDT").await.unwrap();
+    assert!((in_btc - 2.0).abs() < 1e-9);
+    currency::set_reporting_currency(currency::DEFAULT_REPORTING_CURRENCY);
+}
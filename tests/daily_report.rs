@@ -0,0 +1,117 @@
+//! Daily performance reports compiled from the trade ledger.
+
+use aitrading::daily_report;
+use aitrading::database::{Trade, TraderRecord, User};
+use aitrading::money::Decimal;
+use aitrading::testkit;
+use chrono::{Duration, NaiveDate, TimeZone, Utc};
+
+fn dec(value: &str) -> Decimal {
+    value.parse().unwrap()
+}
+
+fn trade(symbol: &str, action: &str, quantity: &str, price: &str, fee: &str) -> Trade {
+    Trade {
+        user_id: "user-report".to_string(),
+        trader_id: "trader-report".to_string(),
+        symbol: symbol.to_string(),
+        action: action.to_string(),
+        quantity: dec(quantity),
+        price: dec(price),
+        fee: dec(fee),
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn previous_day_is_summed_up_and_stored() {
+    let db = testkit::memory_db().await.unwrap();
+    db.create_user(&User {
+        id: "user-report".to_string(),
+        email: "report@example.com".to_string(),
+        ..Default::default()
+    })
+    .await
+    .unwrap();
+    db.create_trader(&TraderRecord {
+        id: "trader-report".to_string(),
+        user_id: "user-report".to_string(),
+        name: "reporter".to_string(),
+        ..Default::default()
+    })
+    .await
+    .unwrap();
+
+    let day = NaiveDate::from_ymd_opt(2026, 3, 2).unwrap();
+    let start = Utc.with_ymd_and_hms(2026, 3, 2, 0, 0, 0).unwrap();
+    let fills = [
+        // Entered the day before; only its price and fee carry over.
+        (
+            -Duration::hours(3),
+            trade("BTCUSDT", "open_long", "2", "100", "0.2"),
+        ),
+        (
+            Duration::hours(1),
+            trade("BTCUSDT", "close_long", "1", "110", "0.1"),
+        ),
+        (
+            Duration::hours(2),
+            trade("ETHUSDT", "open_short", "1", "50", "0.05"),
+        ),
+        (
+            Duration::hours(3),
+            trade("ETHUSDT", "close_short", "1", "55", "0.05"),
+        ),
+        // The next day is left out.
+        (
+            Duration::hours(25),
+            trade("BTCUSDT", "close_long", "1", "90", "0.1"),
+        ),
+    ];
+    for (offset, fill) in fills {
+        db.record_trade(&Trade {
+            executed_at: start + offset,
+            ..fill
+        })
+        .await
+        .unwrap();
+    }
+    db.add_ai_usage("user-report", "trader-report", day, 1200, 0.25)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        daily_report::previous_day(start + Duration::minutes(5) + Duration::days(1)),
+        day
+    );
+    let reports = daily_report::generate(&db, day).await.unwrap();
+    assert_eq!(reports.len(), 1);
+    let report = &reports[0];
+    assert_eq!(
+        (report.trades, report.closed_trades, report.wins),
+        (3, 2, 1)
+    );
+    // BTC: +10 less half the entry fee and the exit fee; ETH: -5 less both fees.
+    assert_eq!(report.realized_pnl, dec("4.7"));
+    assert_eq!(report.fees, dec("0.2"));
+    assert_eq!(
+        (report.best_symbol.as_str(), report.best_pnl),
+        ("BTCUSDT", dec("9.8"))
+    );
+    assert_eq!(
+        (report.worst_symbol.as_str(), report.worst_pnl),
+        ("ETHUSDT", dec("-5.1"))
+    );
+    assert_eq!(report.win_rate(), 0.5);
+    assert_eq!((report.ai_calls, report.ai_cost_usd), (1, 0.25));
+
+    // Running again replaces the stored report.
+    daily_report::generate(&db, day).await.unwrap();
+    let stored = db
+        .get_daily_reports("user-report", "trader-report", 10)
+        .await
+        .unwrap();
+    assert_eq!(stored.len(), 1);
+    assert_eq!(stored[0].day, "2026-03-02");
+    assert_eq!(stored[0].realized_pnl, dec("4.7"));
+}
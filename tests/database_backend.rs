@@ -0,0 +1,271 @@
+//! Backend detection and the Postgres query dialect.
+//!
+//! The Postgres round trip runs only when `AITRADING_TEST_POSTGRES_URL` points
+//! at a scratch database.
+
+use aitrading::auth::Role;
+use aitrading::database::{
+    AccountTransfer, Backend, Database, DatabaseParams, DecisionCall, Trade, TradeProposal,
+    TraderRecord, User, latest_schema_version, number_placeholders,
+};
+use aitrading::money::Decimal;
+use chrono::{Duration, Utc};
+
+#[test]
+fn backend_is_detected_from_the_url() {
+    assert_eq!(
+        Backend::detect("postgres://u:p@db/aitrading"),
+        Backend::Postgres
+    );
+    assert_eq!(
+        Backend::detect("postgresql://db/aitrading"),
+        Backend::Postgres
+    );
+    assert_eq!(Backend::detect("sqlite::memory:"), Backend::Sqlite);
+    assert_eq!(Backend::detect("config.db"), Backend::Sqlite);
+}
+
+#[test]
+fn placeholders_are_numbered_outside_literals() {
+    assert_eq!(
+        number_placeholders("SELECT * FROM t WHERE a = ? AND b = '?' AND c = ?"),
+        "SELECT * FROM t WHERE a = $1 AND b = '?' AND c = $2"
+    );
+    let query = "UPDATE t SET a = ? WHERE id = ?";
+    assert_eq!(Backend::Sqlite.sql(query), query);
+    assert_eq!(
+        Backend::Postgres.sql(query),
+        "UPDATE t SET a = $1 WHERE id = $2"
+    );
+    // Cached per query.
+    assert!(std::ptr::eq(
+        Backend::Postgres.sql(query),
+        Backend::Postgres.sql(query)
+    ));
+}
+
+#[tokio::test]
+async fn analytics_reads_go_to_a_read_only_pool() {
+    let url = format!(
+        "sqlite:file:analytics-{}?mode=memory&cache=shared",
+        uuid::Uuid::new_v4()
+    );
+    let params = DatabaseParams {
+        read_max_connections: 1,
+        ..Default::default()
+    };
+    let db = Database::connect(&url, &params).await.unwrap();
+    db.set_system_config("motd", "hello").await.unwrap();
+
+    let analytics = db.analytics();
+    assert_eq!(analytics.get_system_config("motd").await.unwrap(), "hello");
+    assert!(analytics.set_system_config("motd", "bye").await.is_err());
+    assert_eq!(db.get_system_config("motd").await.unwrap(), "hello");
+
+    let replica = DatabaseParams {
+        read_replica_url: Some("postgres://replica/aitrading".to_string()),
+        ..Default::default()
+    };
+    assert!(Database::connect(&url, &replica).await.is_err());
+}
+
+#[tokio::test]
+async fn postgres_round_trip() {
+    let Ok(url) = std::env::var("AITRADING_TEST_POSTGRES_URL") else {
+        return;
+    };
+    let params = DatabaseParams {
+        read_replica_url: Some(url.clone()),
+        ..Default::default()
+    };
+    let db = Database::connect(&url, &params).await.unwrap();
+    assert_eq!(db.backend(), Backend::Postgres);
+    // Migrating again is a no-op.
+    assert_eq!(db.migrate().await.unwrap(), latest_schema_version());
+    db.init_default_data().await.unwrap();
+
+    let user_id = format!("pg-{}", uuid::Uuid::new_v4());
+    let trader = TraderRecord {
+        id: format!("{}-trader", user_id),
+        user_id: user_id.clone(),
+        name: "pg".to_string(),
+        ai_model_id: "deepseek".to_string(),
+        exchange_id: "binance".to_string(),
+        initial_balance: 1000.0,
+        scan_interval_minutes: 5,
+        is_cross_margin: true,
+        timeframes: "15m,1d".to_string(),
+        ..Default::default()
+    };
+    db.create_trader(&trader).await.unwrap();
+    db.update_trader_status(&user_id, &trader.id, true)
+        .await
+        .unwrap();
+    let stored = db.get_trader(&user_id, &trader.id).await.unwrap().unwrap();
+    assert!(stored.is_running && stored.is_cross_margin);
+    assert_eq!(stored.timeframes, "15m,1d");
+    let replica = db.analytics();
+    assert!(
+        replica
+            .get_trader(&user_id, &trader.id)
+            .await
+            .unwrap()
+            .is_some()
+    );
+
+    db.set_system_config(&user_id, "a").await.unwrap();
+    db.set_system_config(&user_id, "b").await.unwrap();
+    assert_eq!(db.get_system_config(&user_id).await.unwrap(), "b");
+
+    let trade = Trade {
+        user_id: user_id.clone(),
+        trader_id: trader.id.clone(),
+        symbol: "BTCUSDT".to_string(),
+        side: "buy".to_string(),
+        action: "open_long".to_string(),
+        quantity: Decimal::new(1, 1),
+        price: Decimal::ONE_HUNDRED,
+        executed_at: Utc::now(),
+        ..Default::default()
+    };
+    let first = db.record_trade(&trade).await.unwrap();
+    let second = db.record_trade(&trade).await.unwrap();
+    assert!(second > first);
+
+    let transfer = AccountTransfer {
+        user_id: user_id.clone(),
+        trader_id: trader.id.clone(),
+        amount: Decimal::from(50),
+        asset: "USDT".to_string(),
+        occurred_at: Utc::now(),
+        source: "income".to_string(),
+        external_id: Some("tx-1".to_string()),
+        ..Default::default()
+    };
+    assert!(db.record_transfer(&transfer).await.unwrap());
+    assert!(!db.record_transfer(&transfer).await.unwrap());
+
+    let window = db
+        .create_pause_window(&user_id, &trader.id, Utc::now(), None, "test")
+        .await
+        .unwrap();
+    assert_eq!(window.state, "pending");
+
+    for expected in 1..=2 {
+        let version = db
+            .record_prompt_version(&user_id, &trader.id, "p", true, "default", "", "pg", "")
+            .await
+            .unwrap();
+        assert_eq!(version.version, expected);
+        assert!(version.override_base_prompt);
+    }
+
+    let proposal = TradeProposal {
+        id: uuid::Uuid::new_v4().to_string(),
+        user_id: user_id.clone(),
+        trader_id: trader.id.clone(),
+        symbol: "BTCUSDT".to_string(),
+        action: "open_long".to_string(),
+        status: "pending".to_string(),
+        created_at: Utc::now(),
+        expires_at: Utc::now() + Duration::minutes(30),
+        ..Default::default()
+    };
+    db.create_trade_proposal(&proposal).await.unwrap();
+    assert!(
+        db.decide_trade_proposal(
+            &user_id,
+            &trader.id,
+            &proposal.id,
+            "approved",
+            "pg",
+            Utc::now()
+        )
+        .await
+        .unwrap()
+    );
+    assert!(
+        db.transition_trade_proposal(&proposal.id, "approved", "executing", "")
+            .await
+            .unwrap()
+    );
+    assert_eq!(
+        db.expire_trade_proposals(&user_id, &trader.id, Utc::now() + Duration::hours(1))
+            .await
+            .unwrap(),
+        0
+    );
+
+    let call = DecisionCall {
+        id: uuid::Uuid::new_v4().to_string(),
+        user_id: user_id.clone(),
+        trader_id: trader.id.clone(),
+        model: "pg/model".to_string(),
+        symbol: "BTCUSDT".to_string(),
+        action: "open_long".to_string(),
+        entry_price: 100.0,
+        decided_at: Utc::now(),
+        ..Default::default()
+    };
+    db.save_decision_call(&call).await.unwrap();
+    db.settle_decision_call(&call.id, 105.0, 5.0, true, Utc::now())
+        .await
+        .unwrap();
+    let calls = db
+        .get_settled_decision_calls(&user_id, Utc::now() - Duration::hours(1))
+        .await
+        .unwrap();
+    assert_eq!(calls[0].correct, Some(true));
+
+    db.create_user(&User {
+        id: user_id.clone(),
+        email: format!("{}@example.com", user_id),
+        ..Default::default()
+    })
+    .await
+    .unwrap();
+    db.reset_user_otp(&user_id, "SECRET").await.unwrap();
+    db.replace_otp_recovery_codes(&user_id, &["a".to_string(), "b".to_string()])
+        .await
+        .unwrap();
+    assert!(
+        db.consume_otp_recovery_code(&user_id, "a", Utc::now())
+            .await
+            .unwrap()
+    );
+    assert!(
+        !db.consume_otp_recovery_code(&user_id, "a", Utc::now())
+            .await
+            .unwrap()
+    );
+    assert_eq!(db.count_otp_recovery_codes(&user_id).await.unwrap(), 1);
+    db.update_user_role(&user_id, Role::Admin).await.unwrap();
+    assert!(
+        db.get_all_users()
+            .await
+            .unwrap()
+            .iter()
+            .any(|u| u.id == user_id && u.role() == Role::Admin)
+    );
+
+    let today = Utc::now().date_naive();
+    db.add_ai_usage(&user_id, &trader.id, today, 100, 0.25)
+        .await
+        .unwrap();
+    db.add_ai_usage(&user_id, &trader.id, today, 100, 0.25)
+        .await
+        .unwrap();
+    assert_eq!(db.get_ai_spend(&user_id, today).await.unwrap(), 0.5);
+
+    let snapshot = db
+        .get_trader_snapshot(&user_id, &trader.id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(snapshot.transfers.len(), 1);
+    assert_eq!(snapshot.pause_windows.len(), 1);
+
+    db.delete_trader(&user_id, &trader.id).await.unwrap();
+    db.delete_system_config(&user_id).await.unwrap();
+    assert!(db.backup_to("/tmp/unused.db").await.is_err());
+}
@@ -0,0 +1,162 @@
+//! Parsing and validating AI decision responses.
+
+use aitrading::decision::{self, Action, DecisionError, Limits};
+
+fn limits() -> Limits {
+    Limits {
+        symbols: vec!["BTCUSDT".to_string(), "SOLUSDT".to_string()],
+        btc_eth_leverage: 10,
+        altcoin_leverage: 5,
+        equity: 1000.0,
+        positions: vec![("SOLUSDT".to_string(), "short".to_string())],
+        ..Default::default()
+    }
+}
+
+#[test]
+fn finds_the_decision_array_after_the_reasoning() {
+    let response = r#"Funding is [mildly] positive; last signals were [{"x": 1}].
+[{"symbol": "sol", "action": "close_short", "reasoning": "trend turned", "extra": [{"a": 1}]}]"#;
+    let parsed = decision::parse_response(response, &limits()).unwrap();
+    assert!(parsed.rejected.is_empty(), "{:?}", parsed.rejected);
+    assert_eq!(parsed.decisions.len(), 1);
+    assert_eq!(parsed.decisions[0].symbol, "SOLUSDT");
+    assert_eq!(parsed.decisions[0].action, Action::CloseShort);
+    assert!(parsed.cot_trace.starts_with("Funding is"));
+}
+
+#[test]
+fn rejects_entries_outside_the_limits() {
+    let response = r#"[
+        {"symbol": "BTCUSDT", "action": "open_long", "leverage": 0, "position_size_usd": 100},
+        {"symbol": "BTCUSDT", "action": "open_long", "leverage": 2, "position_size_usd": 5000},
+        {"symbol": "BTCUSDT", "action": "open_short", "leverage": 2, "position_size_usd": 100, "stop_loss": 90, "take_profit": 110},
+        {"symbol": "SOLUSDT", "action": "close_long"},
+        {"symbol": "SOLUSDT", "action": "buy"},
+        {"symbol": "BTCUSDT", "action": "open_long", "leverage": 10, "position_size_usd": 500, "confidence": 80}
+    ]"#;
+    let parsed = decision::parse_response(response, &limits()).unwrap();
+    assert_eq!(parsed.decisions.len(), 1);
+    assert_eq!(parsed.decisions[0].leverage, 10);
+
+    let reasons: Vec<String> = parsed.rejected.iter().map(|e| e.to_string()).collect();
+    assert_eq!(reasons.len(), 5);
+    assert!(reasons[0].starts_with("decision #0 (BTCUSDT): leverage 0"));
+    assert!(reasons[1].contains("exceeds equity"));
+    assert!(reasons[2].contains("short stop-loss is not above"));
+    assert!(reasons[3].contains("no open long position"));
+    assert!(reasons[4].starts_with("decision #4 (SOLUSDT): unknown variant `buy`"));
+}
+
+#[test]
+fn reports_missing_or_broken_json() {
+    assert_eq!(
+        decision::parse_response("I would wait.", &limits()).unwrap_err(),
+        DecisionError::NoJson
+    );
+    assert!(matches!(
+        decision::parse_decisions(r#"[{"symbol": "BTCUSDT", "action": }]"#),
+        Err(DecisionError::Json(_))
+    ));
+    assert!(
+        decision::parse_decisions("Nothing to do: []")
+            .unwrap()
+            .is_empty()
+    );
+}
+
+#[test]
+fn pair_trades_are_checked_as_a_whole() {
+    let response = r#"[
+        {"symbol": "SOLUSDT", "action": "open_long", "leverage": 5, "position_size_usd": 2100, "pair": {"symbol": "BTCUSDT", "ratio": 1.5}},
+        {"symbol": "SOLUSDT", "action": "open_long", "leverage": 5, "position_size_usd": 500, "pair": {"symbol": "ETHUSDT"}},
+        {"symbol": "SOLUSDT", "action": "open_long", "leverage": 5, "position_size_usd": 500, "pair": {"symbol": "btc"}}
+    ]"#;
+    let parsed = decision::parse_response(response, &limits()).unwrap();
+    let reasons: Vec<String> = parsed.rejected.iter().map(|e| e.to_string()).collect();
+    // 2100 USD plus a 3150 USD hedge is more than 1000 USD equity at 5x.
+    assert!(
+        reasons[0].contains("5250.00 USD exceeds equity"),
+        "{:?}",
+        reasons
+    );
+    assert!(reasons[1].contains("pair symbol ETHUSDT is not in the trader's coin list"));
+
+    assert_eq!(parsed.decisions.len(), 1);
+    let hedge = parsed.decisions[0].hedge().unwrap();
+    assert_eq!(hedge.symbol, "BTCUSDT");
+    assert_eq!(hedge.action, Action::OpenShort);
+    assert_eq!(hedge.position_size_usd, 500.0);
+}
+
+#[test]
+fn nothing_opens_on_symbols_still_warming_up() {
+    let limits = Limits {
+        warming_up: vec!["SOLUSDT".to_string()],
+        ..limits()
+    };
+    let response = r#"[
+        {"symbol": "SOLUSDT", "action": "open_long", "leverage": 5, "position_size_usd": 100},
+        {"symbol": "BTCUSDT", "action": "open_long", "leverage": 5, "position_size_usd": 100, "pair": {"symbol": "SOLUSDT"}},
+        {"symbol": "SOLUSDT", "action": "close_short"},
+        {"symbol": "BTCUSDT", "action": "open_long", "leverage": 5, "position_size_usd": 100}
+    ]"#;
+    let parsed = decision::parse_response(response, &limits).unwrap();
+    let reasons: Vec<String> = parsed.rejected.iter().map(|e| e.to_string()).collect();
+    assert_eq!(
+        reasons,
+        vec![
+            "decision #0 (SOLUSDT): indicators for SOLUSDT are still warming up",
+            "decision #1 (BTCUSDT): indicators for SOLUSDT are still warming up",
+        ]
+    );
+    assert_eq!(parsed.decisions.len(), 2);
+}
+
+#[test]
+fn degraded_data_only_allows_managing_positions() {
+    let limits = Limits {
+        degraded: vec!["SOLUSDT".to_string()],
+        ..limits()
+    };
+    let response = r#"[
+        {"symbol": "SOLUSDT", "action": "open_long", "leverage": 5, "position_size_usd": 100},
+        {"symbol": "BTCUSDT", "action": "open_short", "leverage": 5, "position_size_usd": 100, "pair": {"symbol": "SOLUSDT"}},
+        {"symbol": "SOLUSDT", "action": "close_short"},
+        {"symbol": "BTCUSDT", "action": "open_long", "leverage": 5, "position_size_usd": 100}
+    ]"#;
+    let parsed = decision::parse_response(response, &limits).unwrap();
+    let reasons: Vec<String> = parsed.rejected.iter().map(|e| e.to_string()).collect();
+    assert_eq!(
+        reasons,
+        vec![
+            "decision #0 (SOLUSDT): market data for SOLUSDT is degraded; only existing positions may be managed",
+            "decision #1 (BTCUSDT): market data for SOLUSDT is degraded; only existing positions may be managed",
+        ]
+    );
+    assert_eq!(parsed.decisions.len(), 2);
+}
+
+#[test]
+fn closes_need_the_position_they_close() {
+    let flat = Limits {
+        positions: Vec::new(),
+        ..limits()
+    };
+    let response = r#"[
+        {"symbol": "SOLUSDT", "action": "close_short"},
+        {"symbol": "BTCUSDT", "action": "close_long"}
+    ]"#;
+    let parsed = decision::parse_response(response, &flat).unwrap();
+    assert!(parsed.decisions.is_empty());
+    assert_eq!(parsed.rejected.len(), 2);
+
+    let parsed = decision::parse_response(response, &limits()).unwrap();
+    assert_eq!(parsed.decisions.len(), 1);
+    assert_eq!(parsed.decisions[0].symbol, "SOLUSDT");
+    assert!(
+        parsed.rejected[0]
+            .to_string()
+            .contains("no open long position on BTCUSDT")
+    );
+}
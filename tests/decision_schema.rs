@@ -0,0 +1,68 @@
+//! Decision records written by older versions of the format.
+
+use std::fs;
+
+use aitrading::logger::{DECISION_SCHEMA_VERSION, DecisionLogger};
+use serde_json::json;
+
+fn v1_record() -> serde_json::Value {
+    let action = |action: &str, symbol: &str| {
+        json!({
+            "action": action, "symbol": symbol, "quantity": 1.0, "leverage": 5,
+            "price": 100.0, "order_id": 7, "timestamp": "2025-01-01T00:00:00Z",
+            "success": true, "error": ""
+        })
+    };
+    json!({
+        "timestamp": "2025-01-01T00:00:00Z",
+        "cycle_number": 1,
+        "input_prompt": "prompt",
+        "cot_trace": "",
+        "decision_json": "[]",
+        "account_state": {
+            "total_balance": 1000.0, "available_balance": 900.0,
+            "total_unrealized_profit": 0.0, "position_count": 1, "margin_used_pct": 10.0
+        },
+        "positions": [],
+        "candidate_coins": ["BTCUSDT"],
+        "decisions": [action("open_long", "BTCUSDT"), action("partial_close", "ETHUSDT")],
+        "execution_log": [],
+        "success": true,
+        "error_message": ""
+    })
+}
+
+#[test]
+fn upgrades_old_records_on_read() {
+    let dir = std::env::temp_dir().join(format!("aitrading-schema-{}", uuid::Uuid::new_v4()));
+    let logger = DecisionLogger::new(&dir.to_string_lossy());
+    let id = "decision_20250101_000000_cycle1";
+    fs::write(dir.join(format!("{}.json", id)), v1_record().to_string()).unwrap();
+
+    let mut newer = v1_record();
+    newer["schema_version"] = json!(DECISION_SCHEMA_VERSION + 1);
+    fs::write(
+        dir.join("decision_20250101_000300_cycle2.json"),
+        newer.to_string(),
+    )
+    .unwrap();
+
+    let record = logger.get_record(id).unwrap();
+    assert_eq!(record.id(), id);
+    let value = serde_json::to_value(&record).unwrap();
+    assert_eq!(value["schema_version"], DECISION_SCHEMA_VERSION);
+    assert_eq!(value["decisions"].as_array().unwrap().len(), 1);
+    assert!(
+        value["execution_log"][0]
+            .as_str()
+            .unwrap()
+            .contains("partial_close")
+    );
+
+    // Analytics see the upgraded record and skip the one from a newer build.
+    let stats = logger.get_statistics().unwrap();
+    assert_eq!(stats.total_cycles, 1);
+    assert_eq!(stats.total_open_positions, 1);
+
+    fs::remove_dir_all(dir).unwrap();
+}
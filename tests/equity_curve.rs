@@ -0,0 +1,112 @@
+//! Bucketed equity curves with running drawdown.
+
+use aitrading::database::{AccountTransfer, PnlSnapshot, TraderRecord, User};
+use aitrading::money;
+use aitrading::performance::EquityRange;
+use aitrading::server::{AppState, EmbeddedClient};
+use aitrading::testkit;
+use axum::http::{Method, StatusCode};
+use chrono::{DateTime, Duration, DurationRound, Utc};
+
+fn snapshot(at: DateTime<Utc>, equity: f64) -> PnlSnapshot {
+    PnlSnapshot {
+        user_id: "admin".to_string(),
+        trader_id: "trader-1".to_string(),
+        taken_at: at,
+        total_equity: money::from_f64(equity),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn default_buckets_keep_curves_short() {
+    assert_eq!(EquityRange::last_days(1).bucket, Duration::minutes(5));
+    assert_eq!(EquityRange::last_days(7).bucket, Duration::minutes(60));
+    assert_eq!(EquityRange::last_days(30).bucket, Duration::minutes(240));
+    assert_eq!(EquityRange::last_days(365).bucket, Duration::minutes(1440));
+}
+
+#[tokio::test]
+async fn curve_is_bucketed_with_drawdown_net_of_withdrawals() {
+    let db = testkit::memory_db().await.unwrap();
+    db.create_user(&User {
+        id: "admin".to_string(),
+        email: "admin@localhost".to_string(),
+        ..Default::default()
+    })
+    .await
+    .unwrap();
+    db.create_trader(&TraderRecord {
+        id: "trader-1".to_string(),
+        user_id: "admin".to_string(),
+        name: "curve".to_string(),
+        ai_model_id: "deepseek".to_string(),
+        exchange_id: "binance".to_string(),
+        ..Default::default()
+    })
+    .await
+    .unwrap();
+
+    let base = Utc::now().duration_trunc(Duration::hours(1)).unwrap() - Duration::hours(6);
+    let at = |h: i64, m: i64| base + Duration::hours(h) + Duration::minutes(m);
+    for (h, m, equity) in [
+        (0, 5, 1000.0),
+        (1, 10, 1100.0),
+        (1, 40, 1050.0),
+        (2, 10, 990.0),
+        (3, 10, 600.0),
+        (4, 10, 570.0),
+    ] {
+        db.save_pnl_snapshot(&snapshot(at(h, m), equity))
+            .await
+            .unwrap();
+    }
+    db.record_transfer(&AccountTransfer {
+        user_id: "admin".to_string(),
+        trader_id: "trader-1".to_string(),
+        amount: money::from_f64(-500.0),
+        asset: "USDT".to_string(),
+        occurred_at: at(2, 30),
+        source: "manual".to_string(),
+        ..Default::default()
+    })
+    .await
+    .unwrap();
+
+    let client = EmbeddedClient::new(AppState {
+        db: db.clone(),
+        scheduler: None,
+        cipher: None,
+    })
+    .with_token(testkit::admin_token());
+    let (status, body) = client
+        .request(
+            Method::GET,
+            "/api/traders/trader-1/equity?days=1&bucket_minutes=60",
+            None,
+        )
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["bucket_minutes"], 60);
+    let points = body["points"].as_array().unwrap();
+    assert_eq!(points.len(), 5);
+    // The bucket keeps its last snapshot.
+    assert_eq!(points[1]["equity"], 1050.0);
+    assert_eq!(points[1]["peak"], 1100.0);
+    let drawdown = |i: usize| points[i]["drawdown_pct"].as_f64().unwrap();
+    assert!((drawdown(1) - 50.0 / 1100.0 * 100.0).abs() < 1e-6);
+    assert!((drawdown(2) - 10.0).abs() < 1e-6);
+    // The withdrawal moves the peak down with it instead of counting as a loss.
+    assert_eq!(points[3]["peak"], 600.0);
+    assert_eq!(drawdown(3), 0.0);
+    assert!((drawdown(4) - 5.0).abs() < 1e-6);
+    assert!((body["max_drawdown_pct"].as_f64().unwrap() - 10.0).abs() < 1e-6);
+    assert!((body["current_drawdown_pct"].as_f64().unwrap() - 5.0).abs() < 1e-6);
+
+    let (status, _) = client
+        .request(Method::GET, "/api/traders/missing/equity", None)
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
@@ -0,0 +1,63 @@
+//! Failed AI calls and rejected orders reach the registered error sinks.
+
+use std::sync::{Arc, Mutex};
+
+use aitrading::decision::{Action, Decision};
+use aitrading::error_sink::{self, ErrorEvent, ErrorKind, ErrorSink};
+use aitrading::testkit::Harness;
+
+#[derive(Default)]
+struct Collector(Mutex<Vec<ErrorEvent>>);
+
+impl ErrorSink for Collector {
+    fn capture(&self, event: &ErrorEvent) {
+        self.0.lock().unwrap().push(event.clone());
+    }
+}
+
+impl Collector {
+    // Sinks are global, so only look at this test's trader.
+    fn of(&self, trader_id: &str) -> Vec<ErrorEvent> {
+        let events = self.0.lock().unwrap();
+        events
+            .iter()
+            .filter(|e| e.trader_id.as_deref() == Some(trader_id))
+            .cloned()
+            .collect()
+    }
+}
+
+#[tokio::test]
+async fn ai_failures_and_rejected_orders_are_captured() {
+    let collector = Arc::new(Collector::default());
+    error_sink::register_sink(collector.clone());
+    let mut h = Harness::new(&["BTCUSDT"], 1000.0).await.unwrap();
+    h.exchange.set_price("BTCUSDT", 100.0);
+    let open_long = Decision {
+        leverage: 2,
+        position_size_usd: 100.0,
+        ..Decision::new("BTCUSDT", Action::OpenLong)
+    };
+
+    h.ai.push_error("model overloaded");
+    h.run_cycle().await.unwrap();
+    let events = collector.of(&h.trader.id);
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].kind, ErrorKind::AiCall);
+    assert!(events[0].message.contains("model overloaded"));
+
+    // A timeout is queued for a retry rather than reported.
+    h.exchange.fail_next_order("timeout");
+    h.ai.push_decisions(std::slice::from_ref(&open_long));
+    h.run_cycle().await.unwrap();
+    assert_eq!(collector.of(&h.trader.id).len(), 1);
+
+    h.exchange.fail_next_order("-2019 margin is insufficient");
+    h.ai.push_decisions(&[open_long]);
+    h.run_cycle().await.unwrap();
+    let events = collector.of(&h.trader.id);
+    assert_eq!(events.len(), 2);
+    assert_eq!(events[1].kind, ErrorKind::OrderRejected);
+    assert_eq!(events[1].symbol.as_deref(), Some("BTCUSDT"));
+    assert!(events[1].message.contains("-2019"));
+}
@@ -0,0 +1,106 @@
+//! Credential checks against local stub exchanges.
+
+use aitrading::api_client::ApiClient;
+use aitrading::aster;
+use aitrading::exchange;
+use axum::Router;
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use serde_json::{Value, json};
+
+const AGENT_KEY: &str = "0x4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318";
+const WALLET: &str = "0x00000000000000000000000000000000000000aa";
+
+async fn balance(headers: HeaderMap) -> Response {
+    if headers.get("X-MBX-APIKEY").and_then(|v| v.to_str().ok()) != Some("good-key") {
+        return (
+            StatusCode::UNAUTHORIZED,
+            axum::Json(
+                json!({"code": -2015, "msg": "Invalid API-key, IP, or permissions for action."}),
+            ),
+        )
+            .into_response();
+    }
+    axum::Json(json!([
+        {"asset": "USDT", "balance": "1234.5", "availableBalance": "1000"}
+    ]))
+    .into_response()
+}
+
+async fn restrictions() -> axum::Json<Value> {
+    axum::Json(json!({
+        "ipRestrict": false,
+        "enableReading": true,
+        "enableFutures": false,
+        "enableSpotAndMarginTrading": false,
+        "enableWithdrawals": false
+    }))
+}
+
+async fn info(axum::Json(body): axum::Json<Value>) -> axum::Json<Value> {
+    let agent = aster::address_for_key(AGENT_KEY).unwrap();
+    match body["type"].as_str().unwrap() {
+        "userRole" if body["user"] == agent.as_str() => {
+            axum::Json(json!({"role": "agent", "data": {"user": WALLET}}))
+        }
+        "userRole" => axum::Json(json!({"role": "missing"})),
+        _ => axum::Json(json!({"marginSummary": {"accountValue": "250.0"}})),
+    }
+}
+
+async fn stub() -> String {
+    let app = Router::new()
+        .route("/fapi/v2/balance", get(balance))
+        .route("/sapi/v1/account/apiRestrictions", get(restrictions))
+        .route("/info", post(info));
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    format!("http://{}", addr)
+}
+
+fn binance(url: &str, api_key: &str) -> ApiClient {
+    ApiClient::new()
+        .with_base_url(url)
+        .with_spot_url(url)
+        .with_credentials(api_key, "secret")
+}
+
+#[tokio::test]
+async fn binance_reports_rejected_and_read_only_keys() {
+    let url = stub().await;
+
+    let check = exchange::validate_binance(binance(&url, "bad-key"), false).await;
+    assert!(!check.auth_ok);
+    assert!(check.error.unwrap().contains("API key rejected"));
+
+    let check = exchange::validate_binance(binance(&url, "good-key"), false).await;
+    assert!(check.auth_ok);
+    assert_eq!(check.balance, Some(1234.5));
+    assert_eq!(check.futures_enabled, Some(false));
+    assert_eq!(check.read_only, Some(true));
+    assert_eq!(check.ip_restricted, Some(false));
+    assert!(!check.can_trade());
+    assert_eq!(check.warnings.len(), 2);
+}
+
+#[tokio::test]
+async fn hyperliquid_requires_an_approved_agent_key() {
+    let url = stub().await;
+
+    let check = exchange::validate_hyperliquid(&url, WALLET, AGENT_KEY).await;
+    assert!(check.can_trade(), "{:?}", check);
+    assert_eq!(check.withdrawals_enabled, Some(false));
+    assert_eq!(check.balance, Some(250.0));
+
+    let other_key = "0x0123456789012345678901234567890123456789012345678901234567890123";
+    let check = exchange::validate_hyperliquid(&url, WALLET, other_key).await;
+    assert!(!check.auth_ok);
+    assert!(check.error.unwrap().contains("neither the wallet"));
+
+    let check = exchange::validate_hyperliquid(&url, WALLET, "").await;
+    assert!(!check.auth_ok);
+    assert_eq!(check.read_only, Some(true));
+    assert_eq!(check.balance, Some(250.0));
+}
@@ -0,0 +1,237 @@
+//! Order execution against the mock exchange.
+
+use std::time::Duration;
+
+use aitrading::decision::{Action, Decision, PairLeg};
+use aitrading::executor::{Executor, ExecutorError};
+use aitrading::logger::DecisionRecord;
+use aitrading::money::Decimal;
+use aitrading::retry_queue::RetryPolicy;
+use aitrading::symbol_meta::SymbolMeta;
+use aitrading::testkit::MockExchange;
+
+fn open_long(symbol: &str, size: f64) -> Decision {
+    Decision {
+        leverage: 5,
+        position_size_usd: size,
+        stop_loss: 90.0,
+        ..Decision::new(symbol, Action::OpenLong)
+    }
+}
+
+fn executor(balance: f64) -> Executor<MockExchange> {
+    let mut exchange = MockExchange::new(balance);
+    exchange.set_price("BTCUSDT", 100.0);
+    let policy = RetryPolicy {
+        base_delay: Duration::ZERO,
+        ..Default::default()
+    };
+    Executor::new(exchange, policy)
+}
+
+#[tokio::test]
+async fn partial_open_is_topped_up() {
+    let mut ex = executor(1000.0);
+    ex.exchange_mut().partial_fill_next(0.4);
+    let mut record = DecisionRecord::new("", "", "", "");
+
+    let executions = ex
+        .execute(&[open_long("BTCUSDT", 500.0)], &mut record)
+        .await;
+    let fill = executions[0].result.clone().unwrap().unwrap();
+    assert_eq!(fill.filled_quantity, Decimal::from(5));
+    assert!(!fill.is_partial());
+    assert_eq!(ex.exchange().orders().len(), 2);
+    assert_eq!(ex.exchange().positions()[0].quantity, 5.0);
+}
+
+#[tokio::test]
+async fn closes_run_before_opens_and_close_out_remainders() {
+    let mut ex = executor(1000.0);
+    let mut record = DecisionRecord::new("", "", "", "");
+    ex.execute(&[open_long("BTCUSDT", 500.0)], &mut record)
+        .await;
+
+    ex.exchange_mut().set_price("ETHUSDT", 50.0);
+    ex.exchange_mut().partial_fill_next(0.5);
+    let decisions = [
+        open_long("ETHUSDT", 200.0),
+        Decision::new("BTCUSDT", Action::CloseLong),
+    ];
+    let executions = ex.execute(&decisions, &mut record).await;
+    assert_eq!(executions[0].decision.action, Action::CloseLong);
+    let close = executions[0].result.clone().unwrap().unwrap();
+    assert_eq!(close.filled_quantity, Decimal::from(5));
+
+    let positions = ex.exchange().positions();
+    assert_eq!(positions.len(), 1);
+    assert_eq!(positions[0].symbol, "ETHUSDT");
+}
+
+#[tokio::test]
+async fn transient_failure_is_retried() {
+    let mut ex = executor(1000.0);
+    ex.exchange_mut().fail_next_order("request timed out");
+    let mut record = DecisionRecord::new("", "", "", "");
+
+    let executions = ex
+        .execute(&[open_long("BTCUSDT", 500.0)], &mut record)
+        .await;
+    assert!(executions[0].queued_for_retry);
+    assert_eq!(ex.pending_retries(), 1);
+
+    let retried = ex.retry_due(Some(&mut record)).await;
+    assert_eq!(retried.len(), 1);
+    assert!(retried[0].result.is_ok());
+    assert_eq!(ex.pending_retries(), 0);
+    assert_eq!(ex.exchange().positions().len(), 1);
+}
+
+#[tokio::test]
+async fn retry_is_dropped_after_price_drift() {
+    let mut ex = executor(1000.0);
+    ex.exchange_mut().fail_next_order("429 too many requests");
+    let mut record = DecisionRecord::new("", "", "", "");
+    ex.execute(&[open_long("BTCUSDT", 500.0)], &mut record)
+        .await;
+
+    ex.exchange_mut().set_price("BTCUSDT", 105.0);
+    assert!(ex.retry_due(None).await.is_empty());
+    assert_eq!(ex.pending_retries(), 0);
+    assert!(ex.exchange().positions().is_empty());
+}
+
+#[tokio::test]
+async fn rejections_are_not_retried() {
+    let mut ex = executor(1000.0);
+    let mut record = DecisionRecord::new("", "", "", "");
+
+    let executions = ex
+        .execute(&[Decision::new("BTCUSDT", Action::CloseShort)], &mut record)
+        .await;
+    assert_eq!(
+        executions[0].result,
+        Err(ExecutorError::NoPosition(
+            "BTCUSDT".to_string(),
+            "short".to_string()
+        ))
+    );
+    assert!(!executions[0].queued_for_retry);
+    assert_eq!(ex.pending_retries(), 0);
+}
+
+#[tokio::test]
+async fn orders_follow_the_symbol_rules() {
+    let mut ex = executor(1000.0);
+    ex.exchange_mut().set_symbol_meta(SymbolMeta {
+        symbol: "BTCUSDT".to_string(),
+        price_precision: 2,
+        quantity_precision: 1,
+        min_notional: Decimal::ONE_HUNDRED,
+    });
+    let mut record = DecisionRecord::new("", "", "", "");
+
+    let executions = ex.execute(&[open_long("BTCUSDT", 80.0)], &mut record).await;
+    assert!(matches!(
+        &executions[0].result,
+        Err(ExecutorError::InvalidOrder(e)) if e.contains("minimum notional of 100")
+    ));
+    assert!(ex.exchange().orders().is_empty());
+
+    let executions = ex
+        .execute(&[open_long("BTCUSDT", 155.0)], &mut record)
+        .await;
+    let fill = executions[0].result.clone().unwrap().unwrap();
+    assert_eq!(fill.requested_quantity, "1.5".parse::<Decimal>().unwrap());
+    assert_eq!(ex.exchange().orders()[0].quantity, 1.5);
+}
+
+fn long_eth_short_btc(ratio: f64) -> Decision {
+    Decision {
+        pair: Some(PairLeg {
+            symbol: "BTCUSDT".to_string(),
+            ratio,
+        }),
+        ..open_long("ETHUSDT", 200.0)
+    }
+}
+
+#[tokio::test]
+async fn pair_legs_execute_together_with_one_group() {
+    let mut ex = executor(1000.0);
+    ex.exchange_mut().set_price("ETHUSDT", 50.0);
+    let mut record = DecisionRecord::new("", "", "", "");
+
+    let executions = ex.execute(&[long_eth_short_btc(1.5)], &mut record).await;
+    assert_eq!(executions.len(), 2);
+    let group = executions[0].group_id.clone().unwrap();
+    assert_eq!(executions[1].group_id.as_deref(), Some(group.as_str()));
+    assert_eq!(executions[1].decision.action, Action::OpenShort);
+
+    let positions = ex.exchange().positions();
+    let btc = positions.iter().find(|p| p.symbol == "BTCUSDT").unwrap();
+    let eth = positions.iter().find(|p| p.symbol == "ETHUSDT").unwrap();
+    assert_eq!((eth.side.as_str(), eth.quantity), ("long", 4.0));
+    assert_eq!((btc.side.as_str(), btc.quantity), ("short", 3.0));
+
+    let close = Decision {
+        pair: Some(PairLeg {
+            symbol: "BTCUSDT".to_string(),
+            ratio: 1.5,
+        }),
+        ..Decision::new("ETHUSDT", Action::CloseLong)
+    };
+    let executions = ex.execute(&[close], &mut record).await;
+    assert!(executions.iter().all(|e| e.result.is_ok()));
+    assert!(ex.exchange().positions().is_empty());
+}
+
+#[tokio::test]
+async fn failed_pair_leg_unwinds_the_other() {
+    let mut ex = executor(1000.0);
+    ex.exchange_mut().set_price("ETHUSDT", 50.0);
+    let mut record = DecisionRecord::new("", "", "", "");
+
+    let decision = Decision {
+        pair: Some(PairLeg {
+            symbol: "SOLUSDT".to_string(),
+            ratio: 1.0,
+        }),
+        ..open_long("ETHUSDT", 200.0)
+    };
+    let executions = ex.execute(&[decision], &mut record).await;
+    assert_eq!(executions.len(), 3);
+    assert!(executions[0].result.is_ok());
+    assert!(executions[1].result.is_err());
+    assert_eq!(executions[2].decision.action, Action::CloseLong);
+    assert!(executions[2].result.is_ok());
+    assert!(ex.exchange().positions().is_empty());
+    assert_eq!(ex.pending_retries(), 0);
+}
+
+#[tokio::test]
+async fn unwinding_a_pair_keeps_the_position_already_held() {
+    let mut ex = executor(1000.0);
+    ex.exchange_mut().set_price("ETHUSDT", 50.0);
+    ex.exchange_mut().open("ETHUSDT", "long", 2.0, 5).unwrap();
+    let mut record = DecisionRecord::new("", "", "", "");
+
+    let decision = Decision {
+        pair: Some(PairLeg {
+            symbol: "SOLUSDT".to_string(),
+            ratio: 1.0,
+        }),
+        ..open_long("ETHUSDT", 200.0)
+    };
+    let executions = ex.execute(&[decision], &mut record).await;
+    assert!(executions[1].result.is_err());
+    let undo = executions[2].result.clone().unwrap().unwrap();
+    assert_eq!(undo.filled_quantity, Decimal::from(4));
+
+    let positions = ex.exchange().positions();
+    assert_eq!(positions.len(), 1);
+    assert_eq!(
+        (positions[0].side.as_str(), positions[0].quantity),
+        ("long", 2.0)
+    );
+}
@@ -0,0 +1,227 @@
+//! A/B tests of prompts across trader clones.
+
+use aitrading::auth;
+use aitrading::database::Trade;
+use aitrading::decision::{AccountInfo, Action};
+use aitrading::experiment;
+use aitrading::logger::{self, DecisionLogger, DecisionRecord};
+use aitrading::money::Decimal;
+use aitrading::server::{AppState, EmbeddedClient};
+use aitrading::testkit::{self, Harness};
+use axum::http::{Method, StatusCode};
+use serde_json::json;
+use uuid::Uuid;
+
+// A cycle that opens or closes one BTC long, with the equity after it.
+fn cycle(variant: &str, action: Action, price: i64, equity: f64) -> DecisionRecord {
+    let mut record = DecisionRecord::new("system", "input", "", "[]");
+    record.set_variant(variant);
+    record.set_account(
+        &AccountInfo {
+            total_equity: equity,
+            ..Default::default()
+        },
+        &[],
+    );
+    record.record_execution(
+        action,
+        "BTCUSDT",
+        Decimal::ONE,
+        5,
+        Decimal::from(price),
+        1,
+        None,
+    );
+    record
+}
+
+#[tokio::test]
+async fn variants_are_cloned_and_compared() {
+    auth::set_jwt_secret("experiments-test-secret");
+    let db = testkit::memory_db().await.unwrap();
+    let client = EmbeddedClient::new(AppState {
+        db: db.clone(),
+        scheduler: None,
+        cipher: None,
+    });
+    let (_, body) = client
+        .request(
+            Method::POST,
+            "/api/register",
+            Some(&json!({ "email": "ab@example.com", "password": "correct horse" })),
+        )
+        .await
+        .unwrap();
+    let user_id = body["user"]["id"].as_str().unwrap_or_default().to_string();
+    let user = client.clone().with_token(body["token"].as_str().unwrap());
+    user.request(
+        Method::PUT,
+        "/api/models/qwen",
+        Some(&json!({ "enabled": true, "api_key": "sk" })),
+    )
+    .await
+    .unwrap();
+    user.request(
+        Method::PUT,
+        "/api/exchanges/binance",
+        Some(&json!({ "enabled": true, "api_key": "key", "secret_key": "secret" })),
+    )
+    .await
+    .unwrap();
+    let (_, models) = user
+        .request(Method::GET, "/api/models", None)
+        .await
+        .unwrap();
+    let (status, control) = user
+        .request(
+            Method::POST,
+            "/api/traders",
+            Some(&json!({
+                "name": "base", "ai_model_id": models[0]["id"], "exchange_id": "binance",
+                "initial_balance": 1000.0, "btc_eth_leverage": 3,
+            })),
+        )
+        .await
+        .unwrap();
+    assert!(status.is_success(), "{}", control);
+    let control_id = control["id"].as_str().unwrap().to_string();
+    let variants = format!("/api/traders/{control_id}/variants");
+
+    let (_, report) = user.request(Method::GET, &variants, None).await.unwrap();
+    assert_eq!(report, json!([]));
+    for bad in [
+        json!({ "variant": "b" }),
+        json!({ "variant": "control", "custom_prompt": "x" }),
+        json!({ "variant": "no spaces", "custom_prompt": "x" }),
+    ] {
+        let (status, _) = user
+            .request(Method::POST, &variants, Some(&bad))
+            .await
+            .unwrap();
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}", bad);
+    }
+    let (status, _) = user
+        .request(
+            Method::POST,
+            &variants,
+            Some(&json!({ "variant": "b", "system_prompt_template": "missing" })),
+        )
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, clone) = user
+        .request(
+            Method::POST,
+            &variants,
+            Some(&json!({ "variant": "b", "custom_prompt": "Only trade breakouts." })),
+        )
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::CREATED, "{}", clone);
+    assert_ne!(clone["id"], control["id"]);
+    assert_eq!(clone["name"], "base (b)");
+    assert_eq!(clone["experiment_id"], control_id);
+    assert_eq!(clone["variant"], "b");
+    assert_eq!(clone["custom_prompt"], "Only trade breakouts.");
+    assert_eq!(clone["btc_eth_leverage"], 3);
+    assert_eq!(clone["is_running"], false);
+    let clone_id = clone["id"].as_str().unwrap().to_string();
+    let (_, control) = user
+        .request(Method::GET, &format!("/api/traders/{control_id}"), None)
+        .await
+        .unwrap();
+    assert_eq!(control["experiment_id"], control_id);
+    assert_eq!(control["variant"], experiment::CONTROL_VARIANT);
+    // A variant's clones join the same experiment, where "b" is taken.
+    let (status, _) = user
+        .request(
+            Method::POST,
+            &format!("/api/traders/{clone_id}/variants"),
+            Some(&json!({ "variant": "b", "custom_prompt": "again" })),
+        )
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    let log_dir = std::env::temp_dir().join(format!("aitrading-experiments-{}", Uuid::new_v4()));
+    let mut logger = DecisionLogger::new(&log_dir.to_string_lossy());
+    let mut store = async |trader_id: &str, mut record: DecisionRecord| {
+        logger.log_decision(&mut record).unwrap();
+        logger::store_record(&db, &user_id, trader_id, &record, None)
+            .await
+            .unwrap();
+    };
+    // Before the experiment started: not counted.
+    store(&control_id, cycle("", Action::OpenLong, 50, 1000.0)).await;
+    store(&control_id, cycle("", Action::CloseLong, 100, 1050.0)).await;
+    store(&control_id, cycle("control", Action::OpenLong, 100, 1000.0)).await;
+    store(&control_id, cycle("control", Action::CloseLong, 90, 990.0)).await;
+    store(&clone_id, cycle("b", Action::OpenLong, 100, 1000.0)).await;
+    store(&clone_id, cycle("b", Action::CloseLong, 120, 1020.0)).await;
+    store(&clone_id, cycle("b", Action::OpenLong, 120, 1010.0)).await;
+    store(&clone_id, cycle("b", Action::CloseLong, 115, 1015.0)).await;
+
+    let (status, report) = user.request(Method::GET, &variants, None).await.unwrap();
+    assert_eq!(status, StatusCode::OK);
+    let report = report.as_array().unwrap();
+    assert_eq!(report.len(), 2);
+    assert_eq!(report[0]["variant"], "control");
+    assert_eq!(report[0]["cycles"], 2);
+    assert_eq!(report[0]["total_trades"], 1);
+    assert_eq!(report[0]["win_rate"], 0.0);
+    assert_eq!(report[1]["variant"], "b");
+    assert_eq!(report[1]["trader_id"], clone_id);
+    assert_eq!(report[1]["total_trades"], 2);
+    assert_eq!(report[1]["win_rate"], 50.0);
+    let reports = experiment::compare(&db, &user_id, &control_id, None)
+        .await
+        .unwrap();
+    assert_eq!(reports[0].total_pnl, Decimal::from(-10));
+    assert_eq!(reports[1].total_pnl, Decimal::from(15));
+    assert!(reports[1].sharpe_ratio != 0.0);
+    let _ = std::fs::remove_dir_all(&log_dir);
+}
+
+#[tokio::test]
+async fn cycles_tag_records_and_trades_with_the_variant() {
+    let mut h = Harness::new(&["BTCUSDT"], 1000.0).await.unwrap();
+    h.trader.experiment_id = h.trader.id.clone();
+    h.trader.variant = "b".to_string();
+    h.db.update_trader(&h.trader).await.unwrap();
+    let stored = h.db.get_trader(&h.user_id, &h.trader.id).await.unwrap();
+    assert_eq!(stored.unwrap().variant, "b");
+
+    let outcome = h.run_cycle().await.unwrap();
+    let record = h.logger.get_record(&outcome.record_id).unwrap();
+    assert_eq!(record.variant(), "b");
+    let json = serde_json::to_value(&record).unwrap();
+    assert_eq!(json["variant"], "b");
+    // Records outside an experiment serialize as before.
+    let plain = DecisionRecord::new("system", "input", "", "[]");
+    assert!(
+        serde_json::to_value(&plain)
+            .unwrap()
+            .get("variant")
+            .is_none()
+    );
+
+    let id =
+        h.db.record_trade(&Trade {
+            user_id: h.user_id.clone(),
+            trader_id: h.trader.id.clone(),
+            symbol: "BTCUSDT".to_string(),
+            side: "buy".to_string(),
+            action: "open_long".to_string(),
+            variant: "b".to_string(),
+            executed_at: chrono::Utc::now(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    let trades =
+        h.db.get_trades(&h.user_id, &h.trader.id, None)
+            .await
+            .unwrap();
+    assert_eq!(trades.iter().find(|t| t.id == id).unwrap().variant, "b");
+}
@@ -0,0 +1,150 @@
+//! Account takeout through the API and as a directory of JSON/CSV files.
+
+use aitrading::auth;
+use aitrading::database::{AccountTransfer, Trade};
+use aitrading::export;
+use aitrading::logger::{self, DecisionRecord, RecordCipher};
+use aitrading::money::Decimal;
+use aitrading::server::{AppState, EmbeddedClient};
+use aitrading::testkit;
+use axum::http::{Method, StatusCode};
+use chrono::{TimeZone, Utc};
+use serde_json::{Value, json};
+use uuid::Uuid;
+
+#[tokio::test]
+async fn takeout_covers_config_and_history_without_secrets() {
+    auth::set_jwt_secret("export-test-secret");
+    let db = testkit::memory_db().await.unwrap();
+    let cipher = RecordCipher::from_secret(b"export master key");
+    let client = EmbeddedClient::new(AppState {
+        db: db.clone(),
+        scheduler: None,
+        cipher: Some(cipher.clone()),
+    });
+    let (status, body) = client
+        .request(
+            Method::POST,
+            "/api/register",
+            Some(&json!({ "email": "erin@example.com", "password": "correct horse" })),
+        )
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::CREATED);
+    let user_id = body["user"]["id"].as_str().unwrap().to_string();
+    let erin = client.clone().with_token(body["token"].as_str().unwrap());
+    erin.request(
+        Method::PUT,
+        "/api/models/qwen",
+        Some(&json!({ "enabled": true, "api_key": "sk-model-secret" })),
+    )
+    .await
+    .unwrap();
+    erin.request(
+        Method::PUT,
+        "/api/exchanges/binance",
+        Some(
+            &json!({ "enabled": true, "api_key": "exchange-key", "secret_key": "exchange-secret" }),
+        ),
+    )
+    .await
+    .unwrap();
+    let (_, models) = erin
+        .request(Method::GET, "/api/models", None)
+        .await
+        .unwrap();
+    let (_, trader) = erin
+        .request(
+            Method::POST,
+            "/api/traders",
+            Some(&json!({
+                "name": "t", "ai_model_id": models[0]["id"], "exchange_id": "binance",
+                "initial_balance": 1000.0, "custom_prompt": "Trade BTC.",
+            })),
+        )
+        .await
+        .unwrap();
+    let id = trader["id"].as_str().unwrap().to_string();
+
+    let key = cipher.user_key(&user_id);
+    let record = DecisionRecord::new("system", "input", "thinking", "[]");
+    logger::store_record(&db, &user_id, &id, &record, Some(&key))
+        .await
+        .unwrap();
+    let at = Utc.with_ymd_and_hms(2026, 3, 1, 12, 0, 0).unwrap();
+    db.record_trade(&Trade {
+        user_id: user_id.clone(),
+        trader_id: id.clone(),
+        symbol: "BTCUSDT".to_string(),
+        side: "buy".to_string(),
+        action: "open_long".to_string(),
+        quantity: Decimal::new(5, 1),
+        price: Decimal::ONE_HUNDRED,
+        fee: Decimal::new(2, 2),
+        executed_at: at,
+        ..Default::default()
+    })
+    .await
+    .unwrap();
+    db.record_transfer(&AccountTransfer {
+        user_id: user_id.clone(),
+        trader_id: id.clone(),
+        amount: Decimal::from(250),
+        asset: "USDT".to_string(),
+        occurred_at: at,
+        source: "manual".to_string(),
+        note: "top up, \"weekly\"".to_string(),
+        ..Default::default()
+    })
+    .await
+    .unwrap();
+
+    let (status, takeout): (_, Value) = erin
+        .request(Method::GET, "/api/export", None)
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(takeout["format"], export::EXPORT_FORMAT);
+    assert_eq!(takeout["user"]["email"], "erin@example.com");
+    assert!(takeout["user"].get("password_hash").is_none());
+    let text = takeout.to_string();
+    for secret in ["sk-model-secret", "exchange-key", "exchange-secret"] {
+        assert!(!text.contains(secret), "{} leaked", secret);
+    }
+    let exported = &takeout["traders"][0];
+    assert_eq!(exported["trader"]["custom_prompt"], "Trade BTC.");
+    assert!(exported["prompt_versions"].is_array());
+    assert_eq!(exported["decisions"][0]["cot_trace"], "thinking");
+    assert_eq!(exported["trades"][0]["price"], 100.0);
+    assert_eq!(exported["performance"]["trades"], 1);
+
+    let takeout = export::collect(&db, &user_id, Some(&key)).await.unwrap();
+    let dir = std::env::temp_dir().join(format!("aitrading-export-{}", Uuid::new_v4()));
+    let files = export::write_dir(&takeout, &dir).unwrap();
+    assert_eq!(files.len(), 7);
+    assert!(matches!(
+        export::write_dir(&takeout, &dir),
+        Err(export::ExportError::DestinationExists(_))
+    ));
+    let manifest: Value =
+        serde_json::from_str(&std::fs::read_to_string(dir.join("manifest.json")).unwrap()).unwrap();
+    assert_eq!(manifest["version"], export::EXPORT_VERSION);
+    assert_eq!(manifest["files"].as_array().unwrap().len(), 6);
+
+    let trader_dir = dir.join("traders").join(&id);
+    assert_eq!(
+        std::fs::read_to_string(trader_dir.join("trades.csv")).unwrap(),
+        "executed_at,symbol,side,action,quantity,price,fee,order_id,group_id,expected_price,fill_latency_ms\n\
+         2026-03-01T12:00:00+00:00,BTCUSDT,buy,open_long,0.5,100,0.02,0,,0,0\n"
+    );
+    assert_eq!(
+        std::fs::read_to_string(trader_dir.join("transfers.csv")).unwrap(),
+        "occurred_at,amount,asset,source,external_id,note\n\
+         2026-03-01T12:00:00+00:00,250,USDT,manual,,\"top up, \"\"weekly\"\"\"\n"
+    );
+    let decisions: Value =
+        serde_json::from_str(&std::fs::read_to_string(trader_dir.join("decisions.json")).unwrap())
+            .unwrap();
+    assert_eq!(decisions.as_array().unwrap().len(), 1);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,140 @@
+//! Per-trader history downloads filtered by date range.
+
+use aitrading::database::{PnlSnapshot, Trade, TraderRecord, User};
+use aitrading::export::{self, Dataset, DateRange, FileFormat};
+use aitrading::logger::{self, DecisionRecord};
+use aitrading::money::Decimal;
+use aitrading::server::{AppState, EmbeddedClient};
+use aitrading::testkit;
+use axum::http::{Method, StatusCode, header};
+use chrono::{Duration, TimeZone, Utc};
+
+#[tokio::test]
+async fn history_is_filtered_and_streamed_as_csv() {
+    let db = testkit::memory_db().await.unwrap();
+    db.create_user(&User {
+        id: "admin".to_string(),
+        email: "admin@localhost".to_string(),
+        ..Default::default()
+    })
+    .await
+    .unwrap();
+    db.create_trader(&TraderRecord {
+        id: "trader-1".to_string(),
+        user_id: "admin".to_string(),
+        name: "history".to_string(),
+        ai_model_id: "deepseek".to_string(),
+        exchange_id: "binance".to_string(),
+        ..Default::default()
+    })
+    .await
+    .unwrap();
+
+    let start = Utc.with_ymd_and_hms(2026, 3, 1, 0, 0, 0).unwrap();
+    for day in 0..3 {
+        let at = start + Duration::days(day);
+        db.record_trade(&Trade {
+            user_id: "admin".to_string(),
+            trader_id: "trader-1".to_string(),
+            symbol: "BTCUSDT".to_string(),
+            side: "buy".to_string(),
+            action: "open_long".to_string(),
+            quantity: Decimal::ONE,
+            price: Decimal::from(100 + day),
+            executed_at: at,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        db.save_pnl_snapshot(&PnlSnapshot {
+            user_id: "admin".to_string(),
+            trader_id: "trader-1".to_string(),
+            taken_at: at,
+            total_equity: Decimal::from(1000 + day),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    }
+    let record = DecisionRecord::new("system", "input", "thinking, then \"hold\"", "[]");
+    logger::store_record(&db, "admin", "trader-1", &record, None)
+        .await
+        .unwrap();
+
+    let range = DateRange {
+        since: Some(start + Duration::days(1)),
+        until: Some(start + Duration::days(1)),
+    };
+    let trades = export::history(&db, "admin", "trader-1", Dataset::Trades, range, None)
+        .await
+        .unwrap();
+    assert_eq!(trades.len(), 1);
+    let equity = export::history(&db, "admin", "trader-1", Dataset::Equity, range, None)
+        .await
+        .unwrap();
+    assert_eq!(
+        equity.to_csv(),
+        "taken_at,total_equity,available_balance,unrealized_pnl,margin_used,position_count\n\
+         2026-03-02T00:00:00+00:00,1001,0,0,0,0\n"
+    );
+    assert!(matches!(
+        export::history(&db, "admin", "missing", Dataset::Trades, range, None).await,
+        Err(export::ExportError::TraderNotFound(_))
+    ));
+
+    let client = EmbeddedClient::new(AppState {
+        db: db.clone(),
+        scheduler: None,
+        cipher: None,
+    })
+    .with_token(testkit::admin_token());
+    let (status, headers, body) = client
+        .request_raw(
+            Method::GET,
+            "/api/traders/trader-1/history/trades?since=2026-03-02T00:00:00Z",
+            None,
+        )
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(headers[header::CONTENT_TYPE], "text/csv; charset=utf-8");
+    assert_eq!(
+        headers[header::CONTENT_DISPOSITION],
+        "attachment; filename=\"trader-1-trades.csv\""
+    );
+    let csv = String::from_utf8(body.to_vec()).unwrap();
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert!(lines[1].starts_with("2026-03-02T00:00:00+00:00,BTCUSDT,buy,open_long,1,101,"));
+
+    let (status, _, body) = client
+        .request_raw(Method::GET, "/api/traders/trader-1/history/decisions", None)
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::OK);
+    let csv = String::from_utf8(body.to_vec()).unwrap();
+    assert!(csv.starts_with("id,timestamp,cycle_number,success,"));
+    assert!(csv.contains("\"thinking, then \"\"hold\"\"\""));
+
+    let (status, _) = client
+        .request(Method::GET, "/api/traders/missing/history/trades", None)
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, _, body) = client
+        .request_raw(
+            Method::GET,
+            "/api/traders/trader-1/history/equity?format=parquet",
+            None,
+        )
+        .await
+        .unwrap();
+    if cfg!(feature = "parquet") {
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.starts_with(b"PAR1") && body.ends_with(b"PAR1"));
+    } else {
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+    assert_eq!(FileFormat::Parquet.extension(), "parquet");
+}
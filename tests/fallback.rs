@@ -0,0 +1,94 @@
+//! Which market data errors count towards failing over to the fallback source,
+//! and when data switches to it and back.
+
+use aitrading::data::{FAILOVER_THRESHOLD, Failover, MarketError, PRIMARY_RETRY_AFTER};
+use axum::Router;
+use axum::extract::Path;
+use axum::http::StatusCode;
+use axum::routing::get;
+use std::time::{Duration, Instant};
+
+/// A server answering `/ok` with a non-JSON body and `/{status}` with that status.
+async fn stub() -> String {
+    let app = Router::new()
+        .route("/ok", get(|| async { "not json" }))
+        .route(
+            "/{status}",
+            get(|Path(status): Path<u16>| async move { StatusCode::from_u16(status).unwrap() }),
+        );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    addr.to_string()
+}
+
+async fn status_error(url: &str) -> MarketError {
+    reqwest::get(url)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap_err()
+        .into()
+}
+
+#[tokio::test]
+async fn only_host_outages_count() {
+    let host = stub().await;
+
+    let unavailable = status_error(&format!("http://{}/503", host)).await;
+    assert_eq!(unavailable.outage_host(), Some("127.0.0.1"));
+    let bad_request = status_error(&format!("http://{}/400", host)).await;
+    assert_eq!(bad_request.outage_host(), None);
+
+    let undecodable: MarketError = reqwest::get(format!("http://{}/ok", host))
+        .await
+        .unwrap()
+        .json::<Vec<u8>>()
+        .await
+        .unwrap_err()
+        .into();
+    assert_eq!(undecodable.outage_host(), None);
+
+    let closed = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let closed_addr = closed.local_addr().unwrap();
+    drop(closed);
+    let refused: MarketError = reqwest::get(format!("http://{}/", closed_addr))
+        .await
+        .unwrap_err()
+        .into();
+    assert_eq!(refused.outage_host(), Some("127.0.0.1"));
+
+    assert_eq!(MarketError::NotListed("FOOUSDT".into()).outage_host(), None);
+}
+
+#[test]
+fn repeated_outages_of_one_host_switch_to_the_fallback_until_recovery() {
+    let mut failover = Failover::default();
+    let now = Instant::now();
+
+    // 不同主机的故障分开计数
+    for _ in 1..FAILOVER_THRESHOLD {
+        assert_eq!(failover.record_outage("api.binance.com", now), None);
+        assert_eq!(failover.record_outage("fapi.binance.com", now), None);
+    }
+    assert!(!failover.is_failed_over());
+
+    assert_eq!(
+        failover.record_outage("fapi.binance.com", now),
+        Some(FAILOVER_THRESHOLD)
+    );
+    assert!(failover.is_failed_over());
+    assert_eq!(failover.cooling_host(now), Some("fapi.binance.com"));
+    // 冷却结束后重新尝试 Binance，但仍处于故障转移状态
+    assert_eq!(
+        failover.cooling_host(now + PRIMARY_RETRY_AFTER + Duration::from_secs(1)),
+        None
+    );
+    assert!(failover.is_failed_over());
+
+    assert!(failover.record_success());
+    assert!(!failover.is_failed_over());
+    assert!(!failover.record_success());
+    // 恢复后计数清零
+    assert_eq!(failover.record_outage("api.binance.com", now), None);
+}
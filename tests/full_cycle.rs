@@ -0,0 +1,220 @@
+//! Full decision cycles against the mock exchange and mock AI.
+
+use aitrading::decision::{Action, Decision};
+use aitrading::testkit::Harness;
+
+fn open_long(symbol: &str, size: f64, leverage: i32, stop_loss: f64) -> Decision {
+    Decision {
+        leverage,
+        position_size_usd: size,
+        stop_loss,
+        reasoning: format!("{} breakout", symbol),
+        ..Decision::new(symbol, Action::OpenLong)
+    }
+}
+
+#[tokio::test]
+async fn opens_then_stop_loss_blocks_reentry() {
+    let mut h = Harness::new(&["BTCUSDT"], 1000.0).await.unwrap();
+    h.exchange.set_price("BTCUSDT", 100.0);
+    h.exchange.script_prices("BTCUSDT", [100.0, 94.0]);
+
+    h.ai.push_decisions(&[open_long("BTCUSDT", 500.0, 5, 95.0)]);
+    let first = h.run_cycle().await.unwrap();
+    assert_eq!(first.filled.len(), 1);
+    assert_eq!(h.exchange.positions().len(), 1);
+
+    // The price falls through the stop; the AI immediately tries again.
+    h.ai.push_decisions(&[open_long("BTCUSDT", 500.0, 5, 90.0)]);
+    let second = h.run_cycle().await.unwrap();
+    assert_eq!(second.protective_closes.len(), 1);
+    assert!(second.protective_closes[0].stop_loss_hit);
+    assert_eq!(second.proposed.len(), 1);
+    assert!(second.approved.is_empty());
+    assert!(second.filled.is_empty());
+    assert!(h.exchange.positions().is_empty());
+    assert!(h.exchange.account().total_equity < 1000.0);
+
+    // The next prompt tells the AI about the cooldown.
+    h.run_cycle().await.unwrap();
+    let prompt = h.ai.calls().last().unwrap().user_prompt.clone();
+    assert!(prompt.contains("Stop-loss cooldown"));
+}
+
+#[tokio::test]
+async fn ai_failure_places_no_orders() {
+    let mut h = Harness::new(&["ETHUSDT"], 1000.0).await.unwrap();
+    h.exchange.set_price("ETHUSDT", 2000.0);
+    h.ai.push_error("deepseek: 503 service unavailable");
+
+    let outcome = h.run_cycle().await.unwrap();
+    assert!(outcome.filled.is_empty());
+    assert_eq!(outcome.errors.len(), 1);
+    assert!(h.exchange.orders().is_empty());
+
+    let record = h.logger.get_record(&outcome.record_id).unwrap();
+    assert!(!record.is_success());
+}
+
+#[tokio::test]
+async fn margin_ceiling_downsizes_entries() {
+    let mut h = Harness::new(&["BTCUSDT", "SOLUSDT"], 1000.0).await.unwrap();
+    h.max_margin_usage_pct = 50.0;
+    h.exchange.set_price("BTCUSDT", 100.0);
+    h.exchange.set_price("SOLUSDT", 10.0);
+
+    // 400 + 400 margin against a 500 ceiling: the second entry is cut to 100 margin.
+    h.ai.push_decisions(&[
+        open_long("BTCUSDT", 2000.0, 5, 0.0),
+        open_long("SOLUSDT", 2000.0, 5, 0.0),
+    ]);
+    let outcome = h.run_cycle().await.unwrap();
+    assert_eq!(outcome.filled.len(), 2);
+    let sol = &outcome.approved[1];
+    assert!((sol.position_size_usd - 500.0).abs() < 1e-6);
+    assert!(h.exchange.account().margin_used <= 500.0 + 1e-6);
+}
+
+#[tokio::test]
+async fn failed_order_is_logged() {
+    let mut h = Harness::new(&["BTCUSDT"], 1000.0).await.unwrap();
+    h.exchange.set_price("BTCUSDT", 100.0);
+    h.exchange.fail_next_order("timeout");
+    h.ai.push_decisions(&[open_long("BTCUSDT", 500.0, 5, 0.0)]);
+
+    let outcome = h.run_cycle().await.unwrap();
+    assert!(outcome.filled.is_empty());
+    assert_eq!(outcome.errors, vec!["Exchange error: timeout".to_string()]);
+    assert!(h.exchange.positions().is_empty());
+}
+
+#[tokio::test]
+async fn close_is_attributed_to_its_open() {
+    let mut h = Harness::new(&["BTCUSDT"], 1000.0).await.unwrap();
+    h.exchange.set_price("BTCUSDT", 100.0);
+    h.exchange.script_prices("BTCUSDT", [100.0, 110.0]);
+
+    h.ai.push_decisions(&[open_long("BTCUSDT", 500.0, 5, 0.0)]);
+    let open = h.run_cycle().await.unwrap();
+
+    h.ai.push_response(
+        r#"Target reached, taking profit.
+[{"symbol":"BTCUSDT","action":"close_long","reasoning":"target reached"}]"#,
+    );
+    let close = h.run_cycle().await.unwrap();
+    assert_eq!(close.filled.len(), 1);
+    assert!(close.filled[0].realized_pnl > 0.0);
+
+    let attributions = h.logger.trade_attributions(10);
+    assert_eq!(attributions.len(), 1);
+    assert_eq!(attributions[0].open_record_id, open.record_id);
+    assert_eq!(attributions[0].close_record_id, close.record_id);
+    assert_eq!(attributions[0].open_reasoning, "BTCUSDT breakout");
+    assert_eq!(attributions[0].close_reasoning, "target reached");
+
+    // The next prompt quotes why the trade was opened and closed.
+    let mut reopen = open_long("BTCUSDT", 500.0, 5, 0.0);
+    reopen.reasoning = "retest held".to_string();
+    h.ai.push_decisions(&[reopen]);
+    let reopened = h.run_cycle().await.unwrap();
+    let prompt = &h.ai.calls()[2].user_prompt;
+    assert!(
+        prompt.contains("Opened: \"BTCUSDT breakout\""),
+        "{}",
+        prompt
+    );
+    assert!(prompt.contains("Closed: \"target reached\""), "{}", prompt);
+
+    // A later close links to the open it closes, not the one already closed.
+    h.ai.push_decisions(&[Decision::new("BTCUSDT", Action::CloseLong)]);
+    let closed_again = h.run_cycle().await.unwrap();
+    let attributions = h.logger.trade_attributions(10);
+    assert_eq!(attributions.len(), 2);
+    assert_eq!(attributions[1].open_record_id, reopened.record_id);
+    assert_eq!(attributions[1].close_record_id, closed_again.record_id);
+    assert_eq!(attributions[1].open_reasoning, "retest held");
+}
+
+#[tokio::test]
+async fn target_below_costs_is_rejected() {
+    let mut h = Harness::new(&["BTCUSDT"], 1000.0).await.unwrap();
+    h.exchange.set_price("BTCUSDT", 100.0);
+
+    // A 0.1% target on 500 USD earns 0.50; fees alone are 0.50.
+    let mut thin = open_long("BTCUSDT", 500.0, 5, 0.0);
+    thin.take_profit = 100.1;
+    h.ai.push_decisions(&[thin]);
+    let outcome = h.run_cycle().await.unwrap();
+    assert!(outcome.approved.is_empty());
+    assert!(h.ai.calls()[0].user_prompt.contains("Projected costs"));
+
+    let mut wide = open_long("BTCUSDT", 500.0, 5, 0.0);
+    wide.take_profit = 103.0;
+    h.ai.push_decisions(&[wide]);
+    assert_eq!(h.run_cycle().await.unwrap().filled.len(), 1);
+}
+
+#[tokio::test]
+async fn watch_only_logs_suggestions_against_owner_trades() {
+    let mut h = Harness::new(&["BTCUSDT", "ETHUSDT"], 1000.0).await.unwrap();
+    h.trader.watch_only = true;
+    h.exchange.set_price("BTCUSDT", 100.0);
+    h.exchange.set_price("ETHUSDT", 50.0);
+
+    h.ai.push_decisions(&[
+        open_long("BTCUSDT", 200.0, 2, 0.0),
+        open_long("ETHUSDT", 200.0, 2, 0.0),
+    ]);
+    let first = h.run_cycle().await.unwrap();
+    assert_eq!(first.approved.len(), 2);
+    assert!(first.filled.is_empty());
+    assert!(h.exchange.orders().is_empty());
+
+    // The owner follows the BTC idea but shorts ETH instead.
+    h.exchange.open("BTCUSDT", "long", 2.0, 2).unwrap();
+    h.exchange.open("ETHUSDT", "short", 4.0, 2).unwrap();
+    let second = h.run_cycle().await.unwrap();
+    assert_eq!(second.observed.len(), 2);
+    let comparison = second.comparison.unwrap();
+    assert_eq!(comparison.agreed.len(), 1);
+    assert_eq!(comparison.agreed[0].symbol, "BTCUSDT");
+    assert_eq!(comparison.ai_only.len(), 1);
+    assert_eq!(comparison.human_only.len(), 1);
+    assert_eq!(comparison.human_only[0].action, Action::OpenShort);
+
+    let record = h.logger.get_record(&second.record_id).unwrap();
+    let json = serde_json::to_value(&record).unwrap();
+    assert_eq!(json["decisions"].as_array().unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn invalid_decisions_are_dropped_and_recorded() {
+    let mut h = Harness::new(&["BTCUSDT"], 1000.0).await.unwrap();
+    h.exchange.set_price("BTCUSDT", 100.0);
+
+    h.ai.push_response(
+        r#"BTC looks strong, DOGE too.
+```json
+[{"symbol":"BTCUSDT","action":"open_long","leverage":50,"position_size_usd":500},
+ {"symbol":"DOGEUSDT","action":"open_long","leverage":2,"position_size_usd":100},
+ {"symbol":"btc","action":"open_long","leverage":3,"position_size_usd":300,"stop_loss":95,"take_profit":120}]
+```"#,
+    );
+    let outcome = h.run_cycle().await.unwrap();
+    assert_eq!(outcome.filled.len(), 1);
+    assert_eq!(outcome.filled[0].leverage, 3);
+    assert_eq!(outcome.rejected.len(), 2);
+
+    let record = h.logger.get_record(&outcome.record_id).unwrap();
+    assert!(record.is_success());
+    assert!(
+        record
+            .error_message()
+            .contains("leverage 50 is outside 1-5")
+    );
+    assert!(
+        record
+            .error_message()
+            .contains("not in the trader's coin list")
+    );
+}
@@ -0,0 +1,140 @@
+//! Settings reloaded from the config file and system_config while running.
+
+use std::fs::{self, File};
+use std::time::{Duration, SystemTime};
+
+use aitrading::hot_reload::{self, Reloader};
+use aitrading::server::{AppState, EmbeddedClient};
+use aitrading::testkit;
+use aitrading::universe;
+use axum::http::Method;
+use serde_json::json;
+use uuid::Uuid;
+
+const TRADER: &str = r#"
+[[traders]]
+id = "t1"
+name = "Main"
+enabled = true
+ai_model = "deepseek"
+binance_api_key = "key"
+binance_secret_key = "secret"
+deepseek_key = "sk"
+initial_balance = 1000.0
+"#;
+
+// Writes `data` plus a trader, with the modification time `age` seconds
+// past a fixed point so rewrites within one second still count as changes.
+fn write_config(path: &std::path::Path, data: &str, age: u64) {
+    fs::write(path, format!("{data}{TRADER}")).unwrap();
+    let modified = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000 + age);
+    File::options()
+        .write(true)
+        .open(path)
+        .unwrap()
+        .set_modified(modified)
+        .unwrap();
+}
+
+#[tokio::test]
+async fn edits_apply_without_restart() {
+    let db = testkit::memory_db().await.unwrap();
+    let path = std::env::temp_dir().join(format!("aitrading-reload-{}.toml", Uuid::new_v4()));
+    write_config(
+        &path,
+        "default_coins = [\"BTCUSDT\"]\n[leverage]\nbtc_eth_leverage = 4\n",
+        0,
+    );
+
+    // system_config wins over the file at startup.
+    let mut reloader = Reloader::start(&path, &db).await;
+    let settings = hot_reload::current();
+    assert_eq!(settings.btc_eth_leverage, 5);
+    assert_eq!(settings.max_daily_loss, 10.0);
+    assert_eq!(settings.stop_trading_minutes, 60);
+    assert!(reloader.poll(&db).await.is_empty());
+
+    db.set_system_config("max_daily_loss", "3.5").await.unwrap();
+    db.set_system_config("default_coins", r#"["ETHUSDT","SOLUSDT"]"#)
+        .await
+        .unwrap();
+    let changes = reloader.poll(&db).await;
+    assert_eq!(changes.len(), 2);
+    let loss = changes.iter().find(|c| c.key == "max_daily_loss").unwrap();
+    assert_eq!(loss.old, json!(10.0));
+    assert_eq!(loss.new, json!(3.5));
+    assert_eq!(loss.source, "system_config");
+    assert_eq!(hot_reload::current().max_daily_loss, 3.5);
+    assert_eq!(universe::default_coins(), ["ETHUSDT", "SOLUSDT"]);
+
+    // Only keys edited in the file are applied; the rest stay as they are.
+    write_config(
+        &path,
+        "default_coins = [\"BTCUSDT\"]\nmax_drawdown = 15.0\n[leverage]\nbtc_eth_leverage = 4\naltcoin_leverage = 2\n",
+        1,
+    );
+    let changes = reloader.poll(&db).await;
+    let keys: Vec<_> = changes.iter().map(|c| c.key.as_str()).collect();
+    assert_eq!(keys, ["altcoin_leverage", "max_drawdown"]);
+    assert!(changes.iter().all(|c| c.source == "file"));
+    let settings = hot_reload::current();
+    assert_eq!(settings.altcoin_leverage, 2);
+    assert_eq!(settings.max_drawdown, 15.0);
+    assert_eq!(settings.btc_eth_leverage, 5);
+    assert_eq!(settings.default_coins, ["ETHUSDT", "SOLUSDT"]);
+
+    // Invalid values are ignored and the previous setting kept.
+    db.set_system_config("btc_eth_leverage", "0").await.unwrap();
+    db.set_system_config("stop_trading_minutes", "soon")
+        .await
+        .unwrap();
+    write_config(&path, "max_drawdown = \"high\"\n", 2);
+    assert!(reloader.poll(&db).await.is_empty());
+    assert_eq!(hot_reload::current().btc_eth_leverage, 5);
+    assert_eq!(hot_reload::current().stop_trading_minutes, 60);
+
+    // New traders get the reloaded leverage defaults.
+    db.set_system_config("btc_eth_leverage", "7").await.unwrap();
+    reloader.poll(&db).await;
+    let client = EmbeddedClient::new(AppState {
+        db: db.clone(),
+        scheduler: None,
+        cipher: None,
+    })
+    .with_token(testkit::admin_token());
+    client
+        .request(
+            Method::PUT,
+            "/api/models/qwen",
+            Some(&json!({ "enabled": true, "api_key": "sk" })),
+        )
+        .await
+        .unwrap();
+    client
+        .request(
+            Method::PUT,
+            "/api/exchanges/binance",
+            Some(&json!({ "enabled": true, "api_key": "key", "secret_key": "secret" })),
+        )
+        .await
+        .unwrap();
+    let (_, models) = client
+        .request(Method::GET, "/api/models", None)
+        .await
+        .unwrap();
+    let (status, trader) = client
+        .request(
+            Method::POST,
+            "/api/traders",
+            Some(&json!({
+                "name": "reloaded", "ai_model_id": models[0]["id"], "exchange_id": "binance",
+                "initial_balance": 1000.0,
+            })),
+        )
+        .await
+        .unwrap();
+    assert!(status.is_success(), "{}", trader);
+    assert_eq!(trader["btc_eth_leverage"], 7);
+    assert_eq!(trader["altcoin_leverage"], 2);
+    let _ = fs::remove_file(&path);
+}
@@ -0,0 +1,175 @@
+//! Market data and accounts from a local stub of Hyperliquid's info API.
+
+use aitrading::data::MarketError;
+use aitrading::database::{TraderRecord, User};
+use aitrading::executor::TradeExecutor;
+use aitrading::hyperliquid::{self, HyperliquidAccount};
+use aitrading::money::Decimal;
+use aitrading::runner::{Runner, RunnerConfig, RunnerError, Venue};
+use aitrading::testkit;
+use aitrading::types::MarketDataSource;
+use axum::Router;
+use axum::routing::post;
+use serde_json::{Value, json};
+
+const WALLET: &str = "0x00000000000000000000000000000000000000aa";
+
+async fn info(axum::Json(body): axum::Json<Value>) -> axum::Json<Value> {
+    match body["type"].as_str().unwrap() {
+        "candleSnapshot" => {
+            assert_eq!(body["req"]["coin"], "ETH");
+            let interval = body["req"]["interval"].as_str().unwrap().to_string();
+            // Newest first, to check that candles are put in order.
+            let candles: Vec<Value> = (0..3)
+                .rev()
+                .map(|i| {
+                    json!({
+                        "t": 1_700_000_000_000i64 + i * 180_000, "T": 1_700_000_179_999i64 + i * 180_000,
+                        "s": "ETH", "i": interval, "o": "2000.0", "h": "2010.5", "l": "1995.0",
+                        "c": format!("{}", 2000 + i), "v": "12.5", "n": 40,
+                    })
+                })
+                .collect();
+            axum::Json(Value::Array(candles))
+        }
+        "metaAndAssetCtxs" => axum::Json(json!([
+            { "universe": [{ "name": "BTC", "szDecimals": 5 }, { "name": "ETH", "szDecimals": 4 }] },
+            [
+                { "funding": "0.0000125", "openInterest": "9000.0", "markPx": "65000.0" },
+                { "funding": "0.00001", "openInterest": "150000.5", "markPx": "2001.7" },
+            ],
+        ])),
+        "clearinghouseState" => {
+            assert_eq!(body["user"], WALLET);
+            axum::Json(json!({
+                "marginSummary": { "accountValue": "1250.5", "totalMarginUsed": "200.17" },
+                "withdrawable": "1000.25",
+                "assetPositions": [{
+                    "type": "oneWay",
+                    "position": {
+                        "coin": "ETH", "szi": "-0.5", "entryPx": "2010.0",
+                        "positionValue": "1000.85", "unrealizedPnl": "4.15",
+                        "leverage": { "type": "cross", "value": 5 },
+                        "liquidationPx": "2300.0", "marginUsed": "200.17",
+                    },
+                }],
+            }))
+        }
+        other => panic!("unexpected info request {}", other),
+    }
+}
+
+async fn stub() -> String {
+    let app = Router::new().route("/info", post(info));
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    format!("http://{}", addr)
+}
+
+#[test]
+fn symbols_map_to_coins() {
+    assert_eq!(hyperliquid::coin("BTCUSDT"), "BTC");
+    assert_eq!(hyperliquid::coin("ethusdc"), "ETH");
+    assert_eq!(hyperliquid::coin("kPEPE"), "KPEPE");
+}
+
+#[tokio::test]
+async fn fetches_candles_mark_price_funding_and_open_interest() {
+    let url = stub().await;
+    let raw = hyperliquid::fetch_from(&url, "ETHUSDT").await.unwrap();
+
+    let closes: Vec<f64> = raw.klines_3m.iter().map(|k| k.close).collect();
+    assert_eq!(closes, [2000.0, 2001.0, 2002.0]);
+    assert_eq!(raw.klines_4h.len(), 3);
+    assert_eq!(raw.klines_3m[0].trades, 40);
+    assert_eq!(raw.mark_price, Some(2001.7));
+    // Hourly funding is reported as an 8-hour rate, like Binance's.
+    assert!((raw.funding_rate.unwrap() - 0.00008).abs() < 1e-12);
+    assert_eq!(raw.open_interest.unwrap().latest, 150000.5);
+}
+
+#[tokio::test]
+async fn unknown_coins_are_not_listed() {
+    let url = stub().await;
+    let err = hyperliquid::asset_context(&url, "NOPE").await.unwrap_err();
+    assert!(matches!(err, MarketError::NotListed(coin) if coin == "NOPE"));
+}
+
+#[tokio::test]
+async fn accounts_are_priced_and_watched_from_hyperliquid() {
+    let url = stub().await;
+    let mut account = HyperliquidAccount::new(WALLET).with_base_url(&url);
+    assert_eq!(account.market_source(), MarketDataSource::Hyperliquid);
+
+    assert_eq!(account.get_price("ETHUSDT").await.unwrap(), 2001.7);
+    let balance = account.get_balance().await.unwrap();
+    assert_eq!((balance.wallet, balance.available), (1250.5, 1000.25));
+
+    let positions = account.get_positions().await.unwrap();
+    assert_eq!(positions.len(), 1);
+    let eth = &positions[0];
+    assert_eq!(
+        (eth.symbol.as_str(), eth.side.as_str()),
+        ("ETHUSDT", "short")
+    );
+    assert_eq!((eth.quantity, eth.leverage), (0.5, 5));
+    assert_eq!(eth.mark_price, 2001.7);
+    assert_eq!(eth.liquidation_price, 2300.0);
+
+    let refused = account.open_long("ETHUSDT", Decimal::ONE, 5).await;
+    assert!(refused.unwrap_err().to_string().contains("watch-only"));
+}
+
+#[tokio::test]
+async fn hyperliquid_traders_must_be_watch_only() {
+    let db = testkit::memory_db().await.unwrap();
+    let user_id = "hyperliquid-user";
+    db.create_user(&User {
+        id: user_id.to_string(),
+        email: "hyperliquid@testkit.local".to_string(),
+        ..Default::default()
+    })
+    .await
+    .unwrap();
+    db.update_aimodel(user_id, "hl_deepseek", true, "sk-test", "", "")
+        .await
+        .unwrap();
+    db.create_exchange(
+        user_id,
+        "hyperliquid",
+        "Hyperliquid",
+        "dex",
+        true,
+        "",
+        "",
+        false,
+        WALLET,
+        "",
+        "",
+        "",
+    )
+    .await
+    .unwrap();
+    let trader = TraderRecord {
+        id: "hyperliquid-trader".to_string(),
+        user_id: user_id.to_string(),
+        name: "hyperliquid".to_string(),
+        ai_model_id: "hl_deepseek".to_string(),
+        exchange_id: "hyperliquid".to_string(),
+        initial_balance: 1000.0,
+        scan_interval_minutes: 3,
+        trading_symbols: "ETHUSDT".to_string(),
+        ..Default::default()
+    };
+    db.create_trader(&trader).await.unwrap();
+
+    let handle = Runner::new(db, RunnerConfig::default()).spawn();
+    let err = handle.start(user_id, &trader.id).await.unwrap_err();
+    assert!(
+        matches!(&err, RunnerError::WatchOnlyExchange(id) if id == "hyperliquid"),
+        "{err:?}"
+    );
+    assert!(handle.running().await.unwrap().is_empty());
+    handle.shutdown().await.unwrap();
+}
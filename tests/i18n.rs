@@ -0,0 +1,74 @@
+//! Notifications reach users in their own language.
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use aitrading::decision::{Action, Decision};
+use aitrading::i18n::Locale;
+use aitrading::notify::{self, Event, Notifier, NotifyFuture};
+use aitrading::testkit::Harness;
+
+/// Collects one trader's events.
+struct Collector {
+    trader_id: String,
+    events: Arc<Mutex<Vec<Event>>>,
+}
+
+impl Notifier for Collector {
+    fn name(&self) -> &str {
+        "collector"
+    }
+
+    fn send<'a>(&'a self, event: &'a Event) -> NotifyFuture<'a> {
+        if event.trader_id.as_deref() == Some(&self.trader_id) {
+            self.events.lock().unwrap().push(event.clone());
+        }
+        Box::pin(async { Ok(()) })
+    }
+}
+
+// Waits for the background dispatch of an event named `name`.
+async fn wait_for(events: &Mutex<Vec<Event>>, name: &str) -> Event {
+    for _ in 0..100 {
+        if let Some(event) = events.lock().unwrap().iter().find(|e| e.name == name) {
+            return event.clone();
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    panic!("no {} event", name);
+}
+
+#[tokio::test]
+async fn runner_notifications_use_the_users_locale() {
+    let mut h = Harness::new(&["BTCUSDT"], 1000.0).await.unwrap();
+    h.db.update_user_locale(&h.user_id, Locale::Zh)
+        .await
+        .unwrap();
+    let events = Arc::new(Mutex::new(Vec::new()));
+    notify::register(Arc::new(Collector {
+        trader_id: h.trader.id.clone(),
+        events: events.clone(),
+    }));
+    h.exchange.set_price("BTCUSDT", 100.0);
+    let open_long = Decision {
+        leverage: 2,
+        position_size_usd: 100.0,
+        ..Decision::new("BTCUSDT", Action::OpenLong)
+    };
+
+    h.exchange.fail_next_order("-2019 margin is insufficient");
+    h.ai.push_decisions(std::slice::from_ref(&open_long));
+    h.run_cycle().await.unwrap();
+    let rejected = wait_for(&events, "order_rejected").await;
+    assert!(
+        rejected.message.contains("下单被拒绝"),
+        "{}",
+        rejected.message
+    );
+    assert!(rejected.message.contains("-2019"));
+
+    h.ai.push_decisions(&[open_long]);
+    h.run_cycle().await.unwrap();
+    let trade = wait_for(&events, "trade").await;
+    assert!(trade.message.starts_with("testkit："), "{}", trade.message);
+}
@@ -0,0 +1,108 @@
+//! Bollinger Bands, Stochastic and VWAP, incrementally and in the prompt data.
+
+use aitrading::data::{calculate_intraday_series, calculate_longer_term_data};
+use aitrading::indicators::{Bollinger, Stochastic, Vwap};
+use aitrading::types::Kline;
+
+const DAY_MS: i64 = 86_400_000;
+
+fn candle(open_time: i64, high: f64, low: f64, close: f64, volume: f64) -> Kline {
+    Kline {
+        open_time,
+        open: close,
+        high,
+        low,
+        close,
+        volume,
+        close_time: open_time + 179_999,
+        quote_volume: 0.0,
+        trades: 0,
+        taker_buy_base_volume: 0.0,
+        taker_buy_quote_volume: 0.0,
+    }
+}
+
+#[test]
+fn bollinger_uses_a_rolling_window() {
+    let mut bb = Bollinger::new(20, 2.0);
+    for close in 1..20 {
+        bb.update(close as f64);
+    }
+    assert!(bb.value().is_none());
+    bb.update(20.0);
+    // Population variance of 1..=20 is (20² - 1) / 12.
+    let std_dev = (399.0f64 / 12.0).sqrt();
+    let bands = bb.value().unwrap();
+    assert!((bands.middle - 10.5).abs() < 1e-9);
+    assert!((bands.upper - (10.5 + 2.0 * std_dev)).abs() < 1e-9);
+    assert!((bands.lower - (10.5 - 2.0 * std_dev)).abs() < 1e-9);
+
+    bb.update(21.0);
+    let bands = bb.value().unwrap();
+    assert!((bands.middle - 11.5).abs() < 1e-9);
+    assert!((bands.upper - bands.middle - 2.0 * std_dev).abs() < 1e-9);
+}
+
+#[test]
+fn stochastic_k_and_d() {
+    let mut stoch = Stochastic::new(14, 3);
+    for i in 1..=15 {
+        let close = i as f64;
+        stoch.update(&candle(0, close + 1.0, close - 1.0, close, 1.0));
+    }
+    assert!(stoch.value().is_none());
+    stoch.update(&candle(0, 17.0, 15.0, 16.0, 1.0));
+    // Highest high i + 1, lowest low i - 14: %K is 14/15 on every candle.
+    let (k, d) = stoch.value().unwrap();
+    assert!((k - 1400.0 / 15.0).abs() < 1e-9);
+    assert!((d - k).abs() < 1e-9);
+
+    // A close at the bottom of the range pulls %K to 0 and %D down a third.
+    stoch.update(&candle(0, 17.0, 3.0, 3.0, 1.0));
+    let (k, d) = stoch.value().unwrap();
+    assert_eq!(k, 0.0);
+    assert!((d - 2800.0 / 45.0).abs() < 1e-9);
+}
+
+#[test]
+fn vwap_resets_each_utc_day() {
+    let mut vwap = Vwap::new();
+    vwap.update(&candle(DAY_MS - 360_000, 10.0, 10.0, 10.0, 0.0));
+    assert!(vwap.value().is_none());
+    vwap.update(&candle(DAY_MS - 180_000, 12.0, 6.0, 9.0, 2.0));
+    assert_eq!(vwap.value(), Some(9.0));
+    vwap.update(&candle(DAY_MS - 180_000, 20.0, 20.0, 20.0, 1.0));
+    assert!((vwap.value().unwrap() - 38.0 / 3.0).abs() < 1e-9);
+    vwap.update(&candle(DAY_MS, 30.0, 30.0, 30.0, 5.0));
+    assert_eq!(vwap.value(), Some(30.0));
+}
+
+#[test]
+fn prompt_series_include_the_new_indicators() {
+    let klines: Vec<Kline> = (0..60)
+        .map(|i| {
+            let close = 100.0 + (i as f64 * 0.4).sin() * 3.0;
+            candle(i * 180_000, close + 0.5, close - 0.5, close, 10.0)
+        })
+        .collect();
+    let intraday = calculate_intraday_series(&klines);
+    assert_eq!(intraday.bollinger_values.len(), 10);
+    assert_eq!(intraday.stoch_k_values.len(), 10);
+    assert_eq!(intraday.stoch_d_values.len(), 10);
+    assert_eq!(intraday.vwap_values.len(), 10);
+    for (bands, close) in intraday.bollinger_values.iter().zip(&intraday.mid_prices) {
+        assert!(bands.lower < bands.middle && bands.middle < bands.upper);
+        assert!((bands.lower - 3.0..=bands.upper + 3.0).contains(close));
+    }
+    assert!(
+        intraday
+            .stoch_k_values
+            .iter()
+            .all(|k| (0.0..=100.0).contains(k))
+    );
+
+    let longer = calculate_longer_term_data(&klines);
+    assert_eq!(longer.bollinger, *intraday.bollinger_values.last().unwrap());
+    assert_eq!(longer.stoch_k, *intraday.stoch_k_values.last().unwrap());
+    assert_eq!(longer.vwap, *intraday.vwap_values.last().unwrap());
+}
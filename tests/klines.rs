@@ -0,0 +1,86 @@
+//! Parsing klines in Binance's array form.
+
+use aitrading::api_client::ApiClient;
+use aitrading::types::Kline;
+use axum::Router;
+use axum::routing::get;
+
+/// Recorded from `GET /fapi/v1/klines?symbol=BTCUSDT&interval=3m&limit=2`.
+const FUTURES_KLINES: &str = r#"[
+  [1760572800000,"111250.10","111322.00","111180.50","111301.90","182.413",1760572979999,"20296431.83060",4127,"97.208","10816133.49640","0"],
+  [1760572980000,"111301.90","111301.90","111020.00","111064.30","305.927",1760573159999,"33990011.77720",6210,"121.554","13504188.37810","0"]
+]"#;
+
+/// Recorded from `GET /api/v3/klines?symbol=ETHUSDT&interval=4h&limit=1`.
+const SPOT_KLINES: &str = r#"[
+  [1760572800000,"4012.37000000","4050.00000000","3998.12000000","4031.58000000","21873.41260000",1760587199999,"88139922.41825300",412903,"11204.88150000","45151377.08233900","0"]
+]"#;
+
+#[test]
+fn binance_arrays_parse() {
+    let klines: Vec<Kline> = serde_json::from_str(FUTURES_KLINES).unwrap();
+    assert_eq!(klines.len(), 2);
+    let k = &klines[0];
+    assert_eq!(k.open_time, 1_760_572_800_000);
+    assert_eq!(k.open, 111_250.10);
+    assert_eq!(k.high, 111_322.0);
+    assert_eq!(k.low, 111_180.50);
+    assert_eq!(k.close, 111_301.90);
+    assert_eq!(k.volume, 182.413);
+    assert_eq!(k.close_time, 1_760_572_979_999);
+    assert_eq!(k.quote_volume, 20296431.8306);
+    assert_eq!(k.trades, 4127);
+    assert_eq!(k.taker_buy_base_volume, 97.208);
+    assert_eq!(k.taker_buy_quote_volume, 10816133.4964);
+    assert_eq!(klines[1].close, 111_064.30);
+
+    let spot: Vec<Kline> = serde_json::from_str(SPOT_KLINES).unwrap();
+    assert_eq!(spot[0].close, 4031.58);
+    assert_eq!(spot[0].trades, 412_903);
+
+    // Without the trailing unused field, and with numbers instead of strings.
+    let k: Kline =
+        serde_json::from_str(r#"[0, 1.5, "2", "1", "1.25", 10, 59999, "0", 3, "4", "5"]"#).unwrap();
+    assert_eq!((k.open, k.high, k.close, k.volume), (1.5, 2.0, 1.25, 10.0));
+    assert_eq!((k.close_time, k.trades), (59_999, 3));
+}
+
+#[test]
+fn objects_still_round_trip() {
+    let k: Kline = serde_json::from_str(FUTURES_KLINES)
+        .map(|mut v: Vec<Kline>| v.remove(0))
+        .unwrap();
+    let json = serde_json::to_value(&k).unwrap();
+    assert_eq!(json["openTime"], 1_760_572_800_000_i64);
+    assert_eq!(json["takerBuyQuoteVolume"], 10816133.4964);
+    let back: Kline = serde_json::from_value(json).unwrap();
+    assert_eq!(back.close, k.close);
+    assert_eq!(back.close_time, k.close_time);
+}
+
+#[test]
+fn malformed_rows_are_rejected() {
+    for row in [
+        r#"[0, "1", "2", "1", "1.5"]"#,
+        r#"[0, "abc", "2", "1", "1.5", "10", 59999, "0", 3, "4", "5"]"#,
+        r#"[0, "NaN", "2", "1", "1.5", "10", 59999, "0", 3, "4", "5"]"#,
+        r#"[0.5, "1", "2", "1", "1.5", "10", 59999, "0", 3, "4", "5"]"#,
+        r#"[0, null, "2", "1", "1.5", "10", 59999, "0", 3, "4", "5"]"#,
+        r#""kline""#,
+    ] {
+        assert!(serde_json::from_str::<Kline>(row).is_err(), "{}", row);
+    }
+}
+
+#[tokio::test]
+async fn api_client_parses_live_responses() {
+    let app = Router::new().route("/fapi/v1/klines", get(|| async { FUTURES_KLINES }));
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+    let client = ApiClient::new().with_base_url(&format!("http://{}", addr));
+    let klines = client.get_klines("BTCUSDT", "3m", 2).await.unwrap();
+    assert_eq!(klines.len(), 2);
+    assert_eq!(klines[1].open_time, 1_760_572_980_000);
+}
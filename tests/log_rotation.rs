@@ -0,0 +1,106 @@
+//! Compression, deletion and indexing of decision log files.
+
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+use aitrading::logger::{DecisionLogger, DecisionRecord, RotationParams};
+
+fn log_dir() -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("aitrading-rotation-{}", uuid::Uuid::new_v4()))
+}
+
+fn log(logger: &mut DecisionLogger, n: usize) -> Vec<String> {
+    (0..n)
+        .map(|_| {
+            let mut record = DecisionRecord::new("system", "input", "", "[]");
+            logger.log_decision(&mut record).unwrap();
+            record.id().to_string()
+        })
+        .collect()
+}
+
+fn files_ending(dir: &Path, suffix: &str) -> usize {
+    fs::read_dir(dir)
+        .unwrap()
+        .filter(|e| {
+            e.as_ref()
+                .unwrap()
+                .file_name()
+                .to_string_lossy()
+                .ends_with(suffix)
+        })
+        .count()
+}
+
+#[test]
+fn old_records_are_compressed_and_stay_readable() {
+    let dir = log_dir();
+    let mut logger = DecisionLogger::new(&dir.to_string_lossy()).with_rotation(RotationParams {
+        compress_after: Duration::from_nanos(1),
+        keep_recent: 2,
+        ..Default::default()
+    });
+    let ids = log(&mut logger, 5);
+
+    assert_eq!(files_ending(&dir, ".json.gz"), 3);
+    assert_eq!(files_ending(&dir, ".json"), 2);
+    let index = fs::read_to_string(dir.join("index.jsonl")).unwrap();
+    assert_eq!(index.lines().count(), 5);
+
+    let records = logger.get_latest_records(10).unwrap();
+    let read: Vec<&str> = records.iter().map(|r| r.id()).collect();
+    assert_eq!(read, ids);
+    assert_eq!(logger.get_record(&ids[0]).unwrap().id(), ids[0]);
+    assert_eq!(logger.get_statistics().unwrap().total_cycles, 5);
+    assert_eq!(
+        DecisionLogger::resume(&dir.to_string_lossy()).cycle_number(),
+        5
+    );
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn size_limit_compresses_oldest_first_and_retention_deletes() {
+    let dir = log_dir();
+    let mut logger = DecisionLogger::new(&dir.to_string_lossy()).with_rotation(RotationParams {
+        compress_after: Duration::ZERO,
+        max_active_bytes: 1,
+        keep_recent: 1,
+        retention: Duration::ZERO,
+    });
+    let ids = log(&mut logger, 3);
+    assert_eq!(files_ending(&dir, ".json.gz"), 2);
+    assert!(dir.join(format!("{}.json", ids[2])).exists());
+
+    let logger = logger.with_rotation(RotationParams {
+        compress_after: Duration::ZERO,
+        max_active_bytes: 0,
+        keep_recent: 1,
+        retention: Duration::from_nanos(1),
+    });
+    logger.rotate().unwrap();
+    assert_eq!(files_ending(&dir, ".json.gz"), 0);
+    let records = logger.get_latest_records(10).unwrap();
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0].id(), ids[2]);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn directories_without_an_index_are_indexed_on_the_next_write() {
+    let dir = log_dir();
+    let mut logger = DecisionLogger::new(&dir.to_string_lossy());
+    log(&mut logger, 1);
+    fs::remove_file(dir.join("index.jsonl")).unwrap();
+    assert_eq!(logger.get_latest_records(10).unwrap().len(), 1);
+
+    log(&mut logger, 1);
+    let index = fs::read_to_string(dir.join("index.jsonl")).unwrap();
+    assert_eq!(index.lines().count(), 2);
+    assert_eq!(logger.get_latest_records(10).unwrap().len(), 2);
+
+    fs::remove_dir_all(dir).unwrap();
+}
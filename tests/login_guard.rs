@@ -0,0 +1,177 @@
+//! Lockouts after repeated failed logins, per account and per client IP.
+
+use std::time::Duration;
+
+use aitrading::auth;
+use aitrading::database::User;
+use aitrading::login_guard::{self, Attempt, GuardParams};
+use aitrading::server::{AppState, EmbeddedClient};
+use aitrading::testkit;
+use axum::http::{Method, StatusCode};
+use serde_json::json;
+
+// Every test in this file shares the global guard, so they all use these
+// limits and their own accounts and addresses.
+fn configure() {
+    login_guard::set_params(GuardParams {
+        max_account_failures: 3,
+        max_ip_failures: 5,
+        base_lockout: Duration::from_millis(300),
+        trust_forwarded_for: true,
+        ..Default::default()
+    });
+}
+
+#[tokio::test]
+async fn repeated_lockouts_double_and_are_recorded() {
+    configure();
+    let db = testkit::memory_db().await.unwrap();
+    let account = "repeat@example.com";
+
+    for _ in 0..2 {
+        assert!(
+            login_guard::failed(&db, Attempt::Login, account, None)
+                .await
+                .is_none()
+        );
+    }
+    let first = login_guard::failed(&db, Attempt::Login, account, None).await;
+    assert_eq!(first, Some(Duration::from_millis(300)));
+    assert!(
+        login_guard::locked_for(Attempt::Login, account, None)
+            .await
+            .is_some()
+    );
+    // Other attempt types are counted separately.
+    assert!(
+        login_guard::locked_for(Attempt::Otp, account, None)
+            .await
+            .is_none()
+    );
+
+    tokio::time::sleep(Duration::from_millis(350)).await;
+    assert!(
+        login_guard::locked_for(Attempt::Login, account, None)
+            .await
+            .is_none()
+    );
+    for _ in 0..2 {
+        login_guard::failed(&db, Attempt::Login, account, None).await;
+    }
+    let second = login_guard::failed(&db, Attempt::Login, account, None).await;
+    assert_eq!(second, Some(Duration::from_millis(600)));
+
+    let lockouts = db.get_auth_lockouts(10).await.unwrap();
+    assert_eq!(lockouts.len(), 2);
+    assert_eq!(lockouts[0].strike, 2);
+    assert_eq!(lockouts[0].kind, "account");
+    assert_eq!(lockouts[0].subject, account);
+    assert_eq!(lockouts[0].attempt, "login");
+
+    // Getting in starts the account over.
+    login_guard::succeeded(Attempt::Login, account).await;
+    assert!(
+        login_guard::locked_for(Attempt::Login, account, None)
+            .await
+            .is_none()
+    );
+}
+
+#[tokio::test]
+async fn login_endpoint_locks_out_accounts_and_addresses() {
+    configure();
+    auth::set_jwt_secret("login-guard-test-secret");
+    let db = testkit::memory_db().await.unwrap();
+    db.create_user(&User {
+        id: "locked-user".to_string(),
+        email: "locked@example.com".to_string(),
+        password_hash: auth::hash_password("correct horse").unwrap(),
+        ..Default::default()
+    })
+    .await
+    .unwrap();
+    let state = AppState {
+        db: db.clone(),
+        scheduler: None,
+        cipher: None,
+    };
+    let client = EmbeddedClient::new(state.clone()).with_header("x-forwarded-for", "203.0.113.7");
+    let login = |email: &str, password: &str| json!({ "email": email, "password": password });
+
+    let mut statuses = Vec::new();
+    for _ in 0..3 {
+        let (status, _) = client
+            .request(
+                Method::POST,
+                "/api/login",
+                Some(&login("locked@example.com", "wrong password")),
+            )
+            .await
+            .unwrap();
+        statuses.push(status);
+    }
+    assert_eq!(
+        statuses,
+        [
+            StatusCode::UNAUTHORIZED,
+            StatusCode::UNAUTHORIZED,
+            StatusCode::TOO_MANY_REQUESTS
+        ]
+    );
+    // The right password does not help while locked.
+    let (status, body) = client
+        .request(
+            Method::POST,
+            "/api/login",
+            Some(&login("locked@example.com", "correct horse")),
+        )
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+    assert!(body["error"].as_str().unwrap().contains("try again"));
+
+    // Two more misses on other accounts reach the address's limit of five.
+    for email in ["nobody1@example.com", "nobody2@example.com"] {
+        client
+            .request(Method::POST, "/api/login", Some(&login(email, "guess")))
+            .await
+            .unwrap();
+    }
+    let (status, _) = client
+        .request(
+            Method::POST,
+            "/api/login",
+            Some(&login("nobody3@example.com", "guess")),
+        )
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+    let lockouts = db.get_auth_lockouts(10).await.unwrap();
+    assert!(
+        lockouts
+            .iter()
+            .any(|l| l.kind == "ip" && l.subject == "203.0.113.7")
+    );
+
+    // Elsewhere the account is still locked, and unlocks on its own.
+    let elsewhere = EmbeddedClient::new(state).with_header("x-forwarded-for", "198.51.100.1");
+    let (status, _) = elsewhere
+        .request(
+            Method::POST,
+            "/api/login",
+            Some(&login("locked@example.com", "correct horse")),
+        )
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+    tokio::time::sleep(Duration::from_millis(350)).await;
+    let (status, body) = elsewhere
+        .request(
+            Method::POST,
+            "/api/login",
+            Some(&login("locked@example.com", "correct horse")),
+        )
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::OK, "{}", body);
+}
@@ -0,0 +1,84 @@
+//! Daily loss and drawdown limits stopping new entries.
+
+use aitrading::decision::{Action, Decision};
+use aitrading::hot_reload::{self, Settings};
+use aitrading::loss_limits::{self, Breach};
+use aitrading::testkit::Harness;
+use chrono::{NaiveDate, Utc};
+
+fn open_long(symbol: &str, size: f64) -> Decision {
+    Decision {
+        leverage: 5,
+        position_size_usd: size,
+        reasoning: format!("{} breakout", symbol),
+        ..Decision::new(symbol, Action::OpenLong)
+    }
+}
+
+#[test]
+fn limits_halt_for_the_configured_time() {
+    let settings = Settings {
+        max_daily_loss: 5.0,
+        max_drawdown: 10.0,
+        stop_trading_minutes: 30,
+        ..Settings::default()
+    };
+    let day = NaiveDate::from_ymd_opt(2026, 3, 2).unwrap();
+    let now = Utc::now();
+    assert_eq!(loss_limits::observe("t", 1000.0, day, now, &settings), None);
+    assert_eq!(loss_limits::observe("t", 980.0, day, now, &settings), None);
+    assert!(matches!(
+        loss_limits::observe("t", 940.0, day, now, &settings),
+        Some(Breach::DailyLoss(pct)) if (pct - 6.0).abs() < 1e-9
+    ));
+    assert!(loss_limits::is_halted("t"));
+    assert!(loss_limits::prompt_annotation("t").is_some());
+
+    // A new day resets the daily loss but not the drawdown from the peak.
+    let next = day.succ_opt().unwrap();
+    assert_eq!(loss_limits::observe("t", 930.0, next, now, &settings), None);
+    // The halt lasts stop_trading_minutes after the last breach.
+    assert!(loss_limits::is_halted("t"));
+    assert!(matches!(
+        loss_limits::observe("t", 899.0, next, now, &settings),
+        Some(Breach::Drawdown(_))
+    ));
+
+    let off = Settings {
+        max_daily_loss: 0.0,
+        max_drawdown: 0.0,
+        ..settings
+    };
+    assert_eq!(loss_limits::observe("u", 1000.0, day, now, &off), None);
+    assert_eq!(loss_limits::observe("u", 100.0, day, now, &off), None);
+    assert!(!loss_limits::is_halted("u"));
+}
+
+#[tokio::test]
+async fn breached_trader_only_closes() {
+    hot_reload::set(Settings {
+        max_daily_loss: 5.0,
+        stop_trading_minutes: 60,
+        ..Settings::default()
+    });
+    let mut h = Harness::new(&["BTCUSDT", "ETHUSDT"], 1000.0).await.unwrap();
+    h.exchange.set_price("BTCUSDT", 100.0);
+    h.exchange.set_price("ETHUSDT", 2000.0);
+    h.ai.push_decisions(&[open_long("BTCUSDT", 1000.0)]);
+    let first = h.run_cycle().await.unwrap();
+    assert_eq!(first.filled.len(), 1);
+
+    // A 10% drop at 5x leverage costs half the margin.
+    h.exchange.set_price("BTCUSDT", 90.0);
+    h.ai.push_decisions(&[
+        open_long("ETHUSDT", 200.0),
+        Decision::new("BTCUSDT", Action::CloseLong),
+    ]);
+    let second = h.run_cycle().await.unwrap();
+    assert_eq!(second.proposed.len(), 2);
+    assert_eq!(second.approved.len(), 1);
+    assert_eq!(second.approved[0].action, Action::CloseLong);
+    assert!(h.exchange.positions().is_empty());
+    let prompt = h.ai.calls().last().unwrap().user_prompt.clone();
+    assert!(prompt.contains("do not open new positions"), "{prompt}");
+}
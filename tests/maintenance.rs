@@ -0,0 +1,89 @@
+//! Maintenance mode: read-only API and automatic resume.
+
+use std::time::Duration;
+
+use aitrading::maintenance;
+use aitrading::server::{AppState, EmbeddedClient};
+use aitrading::testkit;
+use axum::http::{Method, StatusCode};
+use serde_json::json;
+
+// One test owns the process-wide admin and maintenance state.
+#[tokio::test]
+async fn writes_are_rejected_until_maintenance_ends() {
+    let db = testkit::memory_db().await.unwrap();
+    let client = EmbeddedClient::new(AppState {
+        db: db.clone(),
+        scheduler: None,
+        cipher: None,
+    })
+    .with_token(testkit::admin_token());
+
+    let (status, body) = client
+        .request(
+            Method::PUT,
+            "/api/admin/maintenance",
+            Some(&json!({ "reason": "backup", "duration": "1h" })),
+        )
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["active"], true);
+    assert_eq!(body["reason"], "backup");
+    assert!(maintenance::is_active());
+
+    // Reads keep working, writes do not.
+    let (status, body) = client
+        .request(Method::GET, "/api/maintenance", None)
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["active"], true);
+    let (status, _) = client
+        .request(Method::GET, "/api/admin/quotas", None)
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::OK);
+    let (status, body) = client
+        .request(
+            Method::PUT,
+            "/api/admin/quotas",
+            Some(&json!({ "max_traders": 3 })),
+        )
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert!(body["error"].as_str().unwrap().contains("maintenance"));
+
+    // Another process (the CLI) turning it off is picked up by sync.
+    db.delete_system_config("maintenance").await.unwrap();
+    maintenance::sync(&db).await.unwrap();
+    assert!(!maintenance::is_active());
+    let (status, _) = client
+        .request(
+            Method::PUT,
+            "/api/admin/quotas",
+            Some(&json!({ "max_traders": 3 })),
+        )
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::OK);
+
+    // A window whose deadline passed resumes on its own.
+    maintenance::enable(&db, "upgrade", Some(Duration::ZERO))
+        .await
+        .unwrap();
+    assert!(!maintenance::is_active());
+    maintenance::sync(&db).await.unwrap();
+    assert!(db.get_system_config("maintenance").await.is_err());
+
+    // The admin endpoint stays writable so maintenance can be ended.
+    maintenance::enable(&db, "migration", None).await.unwrap();
+    let (status, body) = client
+        .request(Method::DELETE, "/api/admin/maintenance", None)
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["active"], false);
+    assert!(!maintenance::is_active());
+}
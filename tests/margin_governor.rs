@@ -0,0 +1,84 @@
+//! The margin ceiling shared by one user's traders.
+
+use std::collections::BTreeMap;
+
+use aitrading::decision::{Action, Decision, PositionInfo};
+use aitrading::margin_governor;
+use aitrading::money::Decimal;
+use uuid::Uuid;
+
+fn open_long(symbol: &str, size: f64, leverage: i32) -> Decision {
+    Decision {
+        leverage,
+        position_size_usd: size,
+        ..Decision::new(symbol, Action::OpenLong)
+    }
+}
+
+fn position(symbol: &str, side: &str, quantity: f64, margin_used: f64) -> PositionInfo {
+    PositionInfo {
+        symbol: symbol.to_string(),
+        side: side.to_string(),
+        quantity,
+        margin_used,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn traders_count_only_their_own_positions() {
+    // The shared account holds 2 BTC long and an ETH short.
+    let positions = [
+        position("BTCUSDT", "long", 2.0, 400.0),
+        position("ETHUSDT", "short", 10.0, 300.0),
+    ];
+    let mut ledger = BTreeMap::new();
+    ledger.insert(("BTCUSDT".to_string(), "long".to_string()), Decimal::ONE);
+    assert_eq!(margin_governor::own_margin(&positions, &ledger), 200.0);
+    assert_eq!(
+        margin_governor::own_margin(&positions, &BTreeMap::new()),
+        0.0
+    );
+
+    // A ledger ahead of the exchange never counts more than the position.
+    ledger.insert(
+        ("ETHUSDT".to_string(), "short".to_string()),
+        Decimal::from(20),
+    );
+    assert_eq!(margin_governor::own_margin(&positions, &ledger), 500.0);
+}
+
+#[test]
+fn entries_share_the_users_headroom() {
+    let user = format!("user-{}", Uuid::new_v4());
+    margin_governor::report(&user, "a", 300.0, 1000.0);
+    margin_governor::report(&user, "b", 100.0, 1000.0);
+    assert_eq!(margin_governor::usage(&user).margin_used, 400.0);
+
+    // At a 50% ceiling 100 USD of margin is left; the entry is downsized.
+    let allowed = margin_governor::govern(&user, "a", 50.0, vec![open_long("BTCUSDT", 1000.0, 5)]);
+    assert_eq!(allowed.len(), 1);
+    assert!((allowed[0].position_size_usd - 500.0).abs() < 1e-9);
+
+    // The grant is reserved, so a sibling deciding right after gets nothing;
+    // closes always pass.
+    let allowed = margin_governor::govern(
+        &user,
+        "b",
+        50.0,
+        vec![
+            open_long("ETHUSDT", 500.0, 5),
+            Decision::new("SOLUSDT", Action::CloseLong),
+        ],
+    );
+    assert_eq!(allowed.len(), 1);
+    assert_eq!(allowed[0].action, Action::CloseLong);
+
+    // A stopped trader's margin is released.
+    margin_governor::report(&user, "a", 300.0, 1000.0);
+    margin_governor::remove_trader(&user, "a");
+    let usage = margin_governor::usage(&user);
+    assert_eq!((usage.traders, usage.margin_used), (1, 100.0));
+    let allowed = margin_governor::govern(&user, "b", 50.0, vec![open_long("ETHUSDT", 500.0, 5)]);
+    assert_eq!(allowed.len(), 1);
+}
@@ -0,0 +1,146 @@
+//! Liquidation distance and margin ratio alerts, with auto-deleveraging.
+
+use aitrading::database::TraderRecord;
+use aitrading::decision::PositionInfo;
+use aitrading::margin_monitor::{self, DELEVERAGE_GROUP, Thresholds};
+use aitrading::money::Decimal;
+use aitrading::runner::{RunnerConfig, TraderCycle};
+use aitrading::testkit::{self, MockAiProvider, MockExchange};
+use uuid::Uuid;
+
+fn position(side: &str, liquidation_price: f64) -> PositionInfo {
+    PositionInfo {
+        symbol: "BTCUSDT".to_string(),
+        side: side.to_string(),
+        entry_price: 100.0,
+        mark_price: 100.0,
+        quantity: 2.0,
+        leverage: 5,
+        liquidation_price,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn levels_measure_the_way_to_liquidation() {
+    // Without a reported price, a 5x long liquidates 20% below entry.
+    let long = margin_monitor::measure(&position("long", 0.0), Some(90.0)).unwrap();
+    assert_eq!(long.liquidation_price, 80.0);
+    assert!((long.distance_pct - 100.0 / 9.0).abs() < 1e-9);
+    assert!((long.margin_ratio_pct - 50.0).abs() < 1e-9);
+
+    let mut short = position("short", 120.0);
+    short.mark_price = 110.0;
+    let level = margin_monitor::measure(&short, None).unwrap();
+    assert!((level.distance_pct - 100.0 / 11.0).abs() < 1e-9);
+    assert!((level.margin_ratio_pct - 50.0).abs() < 1e-9);
+
+    // In profit the ratio stays at 0; past liquidation the distance at 0.
+    let level = margin_monitor::measure(&short, Some(90.0)).unwrap();
+    assert_eq!(level.margin_ratio_pct, 0.0);
+    let level = margin_monitor::measure(&short, Some(130.0)).unwrap();
+    assert_eq!((level.distance_pct, level.margin_ratio_pct), (0.0, 100.0));
+
+    let unlevered = PositionInfo {
+        leverage: 0,
+        ..position("long", 0.0)
+    };
+    assert_eq!(margin_monitor::measure(&unlevered, None), None);
+}
+
+#[test]
+fn alerts_fire_once_per_crossing() {
+    let thresholds = Thresholds {
+        liquidation_alert_pct: 5.0,
+        margin_ratio_alert_pct: 70.0,
+        auto_deleverage_pct: 0.0,
+    };
+    assert!(thresholds.is_valid());
+    assert!(
+        !Thresholds {
+            margin_ratio_alert_pct: 150.0,
+            ..thresholds
+        }
+        .is_valid()
+    );
+    let at = |mark: f64| margin_monitor::measure(&position("long", 0.0), Some(mark)).unwrap();
+    let trader = format!("trader-{}", Uuid::new_v4());
+
+    assert!(margin_monitor::crossed(&trader, &[at(90.0)], &thresholds).is_empty());
+    // A 70% margin ratio is 86 for a 5x long from 100.
+    let crossed = margin_monitor::crossed(&trader, &[at(85.0)], &thresholds);
+    assert_eq!(crossed, [at(85.0)]);
+    assert!(margin_monitor::crossed(&trader, &[at(83.0)], &thresholds).is_empty());
+    // Recovering re-arms the alert.
+    assert!(margin_monitor::crossed(&trader, &[at(95.0)], &thresholds).is_empty());
+    assert_eq!(
+        margin_monitor::crossed(&trader, &[at(84.0)], &thresholds).len(),
+        1
+    );
+    // Within 5% of liquidation crosses on distance alone.
+    let distance_only = Thresholds {
+        margin_ratio_alert_pct: 0.0,
+        ..thresholds
+    };
+    let other = format!("trader-{}", Uuid::new_v4());
+    assert!(margin_monitor::crossed(&other, &[at(85.0)], &distance_only).is_empty());
+    assert_eq!(
+        margin_monitor::crossed(&other, &[at(84.0)], &distance_only).len(),
+        1
+    );
+}
+
+#[tokio::test]
+async fn crossing_positions_are_partly_closed() {
+    let db = testkit::memory_db().await.unwrap();
+    let trader = TraderRecord {
+        id: format!("trader-{}", Uuid::new_v4()),
+        user_id: format!("user-{}", Uuid::new_v4()),
+        name: "margin".to_string(),
+        initial_balance: 1000.0,
+        scan_interval_minutes: 3,
+        trading_symbols: "BTCUSDT".to_string(),
+        margin_ratio_alert_pct: 50.0,
+        auto_deleverage_pct: 40.0,
+        ..Default::default()
+    };
+    db.create_trader(&trader).await.unwrap();
+    let stored = db.get_trader(&trader.user_id, &trader.id).await.unwrap();
+    assert_eq!(stored.unwrap().auto_deleverage_pct, 40.0);
+
+    let mut exchange = MockExchange::new(10_000.0);
+    exchange.set_price("BTCUSDT", 100.0);
+    exchange.open("BTCUSDT", "long", 10.0, 5).unwrap();
+    exchange.set_price("BTCUSDT", 88.0);
+    let config = RunnerConfig {
+        log_dir: std::env::temp_dir()
+            .join(format!("aitrading-margin-{}", Uuid::new_v4()))
+            .to_string_lossy()
+            .to_string(),
+        ..Default::default()
+    };
+    let mut cycle = TraderCycle::new(
+        db.clone(),
+        trader.clone(),
+        exchange,
+        Box::new(MockAiProvider::new()),
+        config.logger(&trader),
+        &config,
+    );
+
+    let crossed = cycle.monitor_margin().await.unwrap();
+    assert_eq!(crossed.len(), 1);
+    assert!((crossed[0].margin_ratio_pct - 60.0).abs() < 1e-9);
+    let trades = db
+        .get_trades(&trader.user_id, &trader.id, None)
+        .await
+        .unwrap();
+    assert_eq!(trades.len(), 1);
+    assert_eq!(trades[0].action, "close_long");
+    assert_eq!(trades[0].quantity, Decimal::from(4));
+    assert_eq!(trades[0].group_id, DELEVERAGE_GROUP);
+
+    // Still past the threshold, but the crossing was already handled.
+    assert!(cycle.monitor_margin().await.unwrap().is_empty());
+    let _ = std::fs::remove_dir_all(config.log_dir);
+}
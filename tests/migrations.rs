@@ -0,0 +1,121 @@
+//! Versioned schema migrations, including upgrades of pre-versioning databases.
+
+use aitrading::database::{Database, latest_schema_version};
+use aitrading::testkit;
+use sqlx::SqlitePool;
+use sqlx::sqlite::SqliteConnectOptions;
+
+#[tokio::test]
+async fn fresh_database_is_fully_migrated() {
+    let db = testkit::memory_db().await.unwrap();
+    assert_eq!(db.schema_version().await.unwrap(), latest_schema_version());
+    assert_eq!(db.migrate().await.unwrap(), latest_schema_version());
+}
+
+// A database from before schema versioning: single-column exchanges key with
+// the DEX columns appended by ALTER, and traders/users missing later columns.
+async fn legacy_database(path: &str) {
+    let options = SqliteConnectOptions::new()
+        .filename(path)
+        .create_if_missing(true);
+    let pool = SqlitePool::connect_with(options).await.unwrap();
+    for query in [
+        r#"CREATE TABLE exchanges (
+            id TEXT PRIMARY KEY,
+            user_id TEXT NOT NULL DEFAULT 'default',
+            name TEXT NOT NULL,
+            type TEXT NOT NULL,
+            enabled BOOLEAN DEFAULT 0,
+            api_key TEXT DEFAULT '',
+            secret_key TEXT DEFAULT '',
+            testnet BOOLEAN DEFAULT 0,
+            created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+            updated_at DATETIME DEFAULT CURRENT_TIMESTAMP
+        )"#,
+        "ALTER TABLE exchanges ADD COLUMN hyperliquid_wallet_addr TEXT DEFAULT ''",
+        "ALTER TABLE exchanges ADD COLUMN aster_user TEXT DEFAULT ''",
+        "ALTER TABLE exchanges ADD COLUMN aster_signer TEXT DEFAULT ''",
+        "ALTER TABLE exchanges ADD COLUMN aster_private_key TEXT DEFAULT ''",
+        r#"INSERT INTO exchanges (id, user_id, name, type, enabled, api_key, secret_key, testnet, hyperliquid_wallet_addr)
+            VALUES ('hyperliquid', 'user-1', 'Hyperliquid', 'dex', 1, 'key', 'secret', 1, '0xabc')"#,
+        r#"CREATE TABLE traders (
+            id TEXT PRIMARY KEY,
+            user_id TEXT NOT NULL DEFAULT 'default',
+            name TEXT NOT NULL,
+            ai_model_id TEXT NOT NULL,
+            exchange_id TEXT NOT NULL,
+            initial_balance REAL NOT NULL,
+            scan_interval_minutes INTEGER DEFAULT 3,
+            is_running BOOLEAN DEFAULT 0,
+            created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+            updated_at DATETIME DEFAULT CURRENT_TIMESTAMP
+        )"#,
+        r#"INSERT INTO traders (id, user_id, name, ai_model_id, exchange_id, initial_balance)
+            VALUES ('trader-1', 'user-1', 'Old', 'deepseek', 'hyperliquid', 500)"#,
+        r#"CREATE TABLE users (
+            id TEXT PRIMARY KEY,
+            email TEXT UNIQUE NOT NULL,
+            password_hash TEXT NOT NULL,
+            otp_secret TEXT,
+            otp_verified BOOLEAN DEFAULT 0,
+            created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+            updated_at DATETIME DEFAULT CURRENT_TIMESTAMP
+        )"#,
+        r#"INSERT INTO users (id, email, password_hash, otp_secret)
+            VALUES ('user-1', 'old@example.com', 'hash', '')"#,
+    ] {
+        sqlx::query(query).execute(&pool).await.unwrap();
+    }
+    pool.close().await;
+}
+
+#[tokio::test]
+async fn legacy_database_is_upgraded_in_place() {
+    let path = std::env::temp_dir().join(format!("aitrading-legacy-{}.db", uuid::Uuid::new_v4()));
+    let path = path.to_str().unwrap().to_string();
+    legacy_database(&path).await;
+
+    let db = Database::new(&path).await.unwrap();
+    assert_eq!(db.schema_version().await.unwrap(), latest_schema_version());
+
+    // Rows survive the exchanges rebuild with every column in place.
+    let exchanges = db.get_exchanges("user-1").await.unwrap();
+    let hyperliquid = exchanges.iter().find(|e| e.id == "hyperliquid").unwrap();
+    assert_eq!(hyperliquid.api_key, "key");
+    assert!(hyperliquid.testnet);
+    assert_eq!(hyperliquid.hyperliquid_wallet_addr, "0xabc");
+    // The composite key lets another user configure the same exchange.
+    db.create_exchange(
+        "user-2",
+        "hyperliquid",
+        "Hyperliquid",
+        "dex",
+        true,
+        "",
+        "",
+        false,
+        "0xdef",
+        "",
+        "",
+        "",
+    )
+    .await
+    .unwrap();
+    assert_eq!(db.get_exchanges("user-2").await.unwrap().len(), 1);
+
+    // Added columns take their defaults.
+    let trader = db.get_trader("user-1", "trader-1").await.unwrap().unwrap();
+    assert_eq!(trader.initial_balance, 500.0);
+    assert_eq!(trader.stop_loss_cooldown_minutes, 30);
+    assert!(trader.is_cross_margin);
+    let user = db.get_user_by_id("user-1").await.unwrap().unwrap();
+    assert_eq!(user.timezone, "UTC");
+    db.close().await.unwrap();
+
+    // Reopening applies nothing new.
+    let db = Database::new(&path).await.unwrap();
+    assert_eq!(db.migrate().await.unwrap(), latest_schema_version());
+    assert_eq!(db.get_exchanges("user-2").await.unwrap().len(), 1);
+    db.close().await.unwrap();
+    let _ = std::fs::remove_file(&path);
+}
@@ -0,0 +1,88 @@
+//! Decimal money: exact sizing and fees, JSON numbers, database round trips.
+
+use aitrading::database::{PnlSnapshot, Trade};
+use aitrading::money::{self, Decimal};
+use aitrading::testkit;
+use aitrading::types::{OrderRequest, OrderSide};
+use chrono::Utc;
+use serde_json::json;
+
+#[test]
+fn sizing_and_fees_are_exact() {
+    // 0.29 is 0.28999... as a float; truncating to 2 places used to give 0.28.
+    assert_eq!(
+        money::round_down(money::from_f64(0.29), 2),
+        Decimal::new(29, 2)
+    );
+    assert_eq!(
+        money::round_down(Decimal::new(123_456, 4), 3),
+        Decimal::new(12_345, 3)
+    );
+    assert_eq!(
+        money::round_down(Decimal::new(-15, 1), 0),
+        Decimal::from(-1)
+    );
+    assert_eq!(money::parse(" 0.001 "), Decimal::new(1, 3));
+    assert_eq!(money::parse("n/a"), Decimal::ZERO);
+
+    let fee = Decimal::new(1, 1) * Decimal::new(3, 1);
+    let total: Decimal = std::iter::repeat_n(fee, 10).sum();
+    assert_eq!(total, Decimal::new(3, 1));
+
+    let order = OrderRequest::market("BTCUSDT", OrderSide::Buy, Decimal::new(1, 2));
+    assert!(order.params().contains(&("quantity", "0.01".to_string())));
+}
+
+#[test]
+fn amounts_serialize_as_json_numbers() {
+    let trade = Trade {
+        quantity: Decimal::new(5, 1),
+        price: Decimal::ONE_HUNDRED,
+        ..Default::default()
+    };
+    let value = serde_json::to_value(&trade).unwrap();
+    assert_eq!(value["quantity"], json!(0.5));
+    assert_eq!(value["price"], json!(100.0));
+
+    // Both numbers and exchange-style strings parse.
+    let mut value = value;
+    value["price"] = json!("101.25");
+    let trade: Trade = serde_json::from_value(value).unwrap();
+    assert_eq!(trade.price, Decimal::new(10_125, 2));
+}
+
+#[tokio::test]
+async fn stored_amounts_round_trip() {
+    let db = testkit::memory_db().await.unwrap();
+    let now = Utc::now();
+    db.record_trade(&Trade {
+        user_id: "u1".to_string(),
+        trader_id: "t1".to_string(),
+        symbol: "BTCUSDT".to_string(),
+        side: "buy".to_string(),
+        action: "open_long".to_string(),
+        quantity: Decimal::new(3, 3),
+        price: Decimal::new(6_543_210, 2),
+        fee: Decimal::new(9, 2),
+        executed_at: now,
+        ..Default::default()
+    })
+    .await
+    .unwrap();
+    db.save_pnl_snapshot(&PnlSnapshot {
+        user_id: "u1".to_string(),
+        trader_id: "t1".to_string(),
+        taken_at: now,
+        total_equity: Decimal::new(100_010, 2),
+        ..Default::default()
+    })
+    .await
+    .unwrap();
+
+    let trade = &db.get_trades("u1", "t1", None).await.unwrap()[0];
+    assert_eq!(trade.quantity, Decimal::new(3, 3));
+    assert_eq!(trade.price, Decimal::new(6_543_210, 2));
+    assert_eq!(trade.fee, Decimal::new(9, 2));
+    let snapshot = &db.get_pnl_snapshots("u1", "t1", None).await.unwrap()[0];
+    assert_eq!(snapshot.total_equity, Decimal::new(100_010, 2));
+}
@@ -0,0 +1,134 @@
+//! Discord and generic webhook notifications.
+
+use std::sync::{Arc, Mutex};
+
+use aitrading::database::Trade;
+use aitrading::money::Decimal;
+use aitrading::notify::{self, Event, EventKind, NotifierKind, NotifierSettings, NotifyError};
+use serde_json::{Value, json};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+/// An HTTP endpoint that records request heads and JSON bodies and answers
+/// with `status`.
+async fn webhook_stub(status: u16) -> (String, Arc<Mutex<Vec<(String, Value)>>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let log = seen.clone();
+    tokio::spawn(async move {
+        while let Ok((mut socket, _)) = listener.accept().await {
+            let mut request = Vec::new();
+            let mut buf = [0u8; 4096];
+            loop {
+                let text = String::from_utf8_lossy(&request).to_string();
+                if let Some(end) = text.find("\r\n\r\n") {
+                    let length = text[..end]
+                        .lines()
+                        .find_map(|l| {
+                            l.to_lowercase()
+                                .strip_prefix("content-length:")
+                                .map(|v| v.trim().parse::<usize>().unwrap())
+                        })
+                        .unwrap_or(0);
+                    if request.len() >= end + 4 + length {
+                        let body = serde_json::from_slice(&request[end + 4..]).unwrap();
+                        log.lock().unwrap().push((text[..end].to_string(), body));
+                        break;
+                    }
+                }
+                match socket.read(&mut buf).await {
+                    Ok(0) | Err(_) => break,
+                    Ok(n) => request.extend_from_slice(&buf[..n]),
+                }
+            }
+            let response = format!(
+                "HTTP/1.1 {} X\r\nContent-Length: 4\r\nConnection: close\r\n\r\nnope",
+                status
+            );
+            let _ = socket.write_all(response.as_bytes()).await;
+        }
+    });
+    (format!("http://{}/hook", addr), seen)
+}
+
+fn settings(kind: NotifierKind, url: &str) -> NotifierSettings {
+    NotifierSettings {
+        kind,
+        url: url.to_string(),
+        name: String::new(),
+        headers: Default::default(),
+        template: None,
+        events: Vec::new(),
+    }
+}
+
+#[test]
+fn templates_fill_placeholders_from_the_event() {
+    let event = json!({ "message": "hi", "details": { "price": 1.5, "side": "buy" } });
+    let template = json!({
+        "text": "{{details.side}} at {{ details.price }}{{missing}}",
+        "price": "{{details.price}}",
+        "nested": [{ "raw": 3, "missing": "{{nope}}" }],
+    });
+    assert_eq!(
+        notify::render_template(&template, &event),
+        json!({
+            "text": "buy at 1.5",
+            "price": 1.5,
+            "nested": [{ "raw": 3, "missing": null }],
+        })
+    );
+}
+
+#[tokio::test]
+async fn events_are_routed_to_matching_webhooks() {
+    let (discord_url, discord) = webhook_stub(204).await;
+    let (hook_url, hook) = webhook_stub(200).await;
+    let (broken_url, _) = webhook_stub(500).await;
+
+    let mut generic = settings(NotifierKind::Webhook, &hook_url);
+    generic.name = "ops".to_string();
+    generic
+        .headers
+        .insert("Authorization".to_string(), "Bearer t0ken".to_string());
+    generic.template = Some(json!({ "event": "{{name}}", "qty": "{{details.quantity}}" }));
+    let mut alerts_only = settings(NotifierKind::Webhook, &broken_url);
+    alerts_only.events = vec![EventKind::Alert];
+    let failed = notify::configure(&[
+        settings(NotifierKind::Discord, &discord_url),
+        generic,
+        alerts_only,
+        settings(NotifierKind::Webhook, "ftp://example.com"),
+    ]);
+    assert_eq!(failed.len(), 1);
+    assert!(matches!(failed[0].1, NotifyError::InvalidUrl(_)));
+
+    let trade = Trade {
+        user_id: "u1".to_string(),
+        trader_id: "t1".to_string(),
+        symbol: "BTCUSDT".to_string(),
+        side: "buy".to_string(),
+        action: "open_long".to_string(),
+        quantity: Decimal::new(5, 1),
+        price: Decimal::ONE_HUNDRED,
+        ..Default::default()
+    };
+    let results = notify::dispatch(&Event::trade("alpha", &trade)).await;
+    assert_eq!(results.len(), 2);
+    assert!(results.iter().all(|(_, r)| r.is_ok()), "{:?}", results);
+
+    let (_, body) = discord.lock().unwrap()[0].clone();
+    assert_eq!(
+        body,
+        json!({ "content": "**trade** alpha: buy 0.5 BTCUSDT @ 100 (open_long)" })
+    );
+    let (head, body) = hook.lock().unwrap()[0].clone();
+    assert!(head.to_lowercase().contains("authorization: bearer t0ken"));
+    assert_eq!(body, json!({ "event": "trade", "qty": 0.5 }));
+
+    // Alerts also go to the alerts-only sink, whose failure is reported.
+    let results = notify::dispatch(&Event::alert("symbol_unavailable", "gone")).await;
+    assert_eq!(results.len(), 3);
+    assert!(matches!(results[2].1, Err(NotifyError::Status(500, _))));
+}
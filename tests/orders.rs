@@ -0,0 +1,253 @@
+//! Order placement against a stub Binance futures API.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use aitrading::api_client::ApiClient;
+use aitrading::aster::{self, AsterClient};
+use aitrading::decision::{Action, Decision};
+use aitrading::executor::{Executor, TradeExecutor};
+use aitrading::logger::DecisionRecord;
+use aitrading::money::Decimal;
+use aitrading::retry_queue::RetryPolicy;
+use axum::Form;
+use axum::Router;
+use axum::extract::{Query, State};
+use axum::routing::{get, post};
+use serde_json::{Value, json};
+
+type Params = Query<HashMap<String, String>>;
+
+#[derive(Default)]
+struct Venue {
+    position: f64,
+    open_orders: Vec<Value>,
+    next_id: i64,
+    /// Parameters of every order placed.
+    placed: Vec<HashMap<String, String>>,
+}
+
+type Shared = Arc<Mutex<Venue>>;
+
+fn order(id: i64, params: &HashMap<String, String>, executed: &str) -> Value {
+    json!({
+        "orderId": id,
+        "symbol": params["symbol"],
+        "status": "FILLED",
+        "side": params["side"],
+        "type": params["type"],
+        "executedQty": executed,
+        "avgPrice": if executed == "0" { "0" } else { "100" },
+    })
+}
+
+async fn place(State(venue): State<Shared>, Query(params): Params) -> axum::Json<Value> {
+    axum::Json(fill(&venue, params))
+}
+
+// Aster 的下单参数在表单里
+async fn place_form(
+    State(venue): State<Shared>,
+    Form(params): Form<HashMap<String, String>>,
+) -> axum::Json<Value> {
+    axum::Json(fill(&venue, params))
+}
+
+fn fill(venue: &Shared, params: HashMap<String, String>) -> Value {
+    let mut venue = venue.lock().unwrap();
+    venue.next_id += 1;
+    let id = venue.next_id;
+    venue.placed.push(params.clone());
+    if params["type"] != "MARKET" {
+        let resting = order(id, &params, "0");
+        venue.open_orders.push(resting.clone());
+        return resting;
+    }
+    let quantity: f64 = params["quantity"].parse().unwrap();
+    venue.position += if params["side"] == "BUY" {
+        quantity
+    } else {
+        -quantity
+    };
+    // 与 Binance 一致：默认的 ACK 响应不带成交信息
+    let executed = match params.get("newOrderRespType").map(String::as_str) {
+        Some("RESULT") => params["quantity"].clone(),
+        _ => "0".to_string(),
+    };
+    order(id, &params, &executed)
+}
+
+async fn cancel(State(venue): State<Shared>, Query(params): Params) -> axum::Json<Value> {
+    let mut venue = venue.lock().unwrap();
+    let id: i64 = params["orderId"].parse().unwrap();
+    let index = venue
+        .open_orders
+        .iter()
+        .position(|o| o["orderId"] == id)
+        .unwrap();
+    let mut cancelled = venue.open_orders.remove(index);
+    cancelled["status"] = json!("CANCELED");
+    axum::Json(cancelled)
+}
+
+async fn open_orders(State(venue): State<Shared>) -> axum::Json<Value> {
+    axum::Json(Value::from(venue.lock().unwrap().open_orders.clone()))
+}
+
+async fn positions(State(venue): State<Shared>) -> axum::Json<Value> {
+    let amount = venue.lock().unwrap().position;
+    axum::Json(json!([{
+        "symbol": "BTCUSDT",
+        "positionAmt": amount.to_string(),
+        "entryPrice": "100",
+        "markPrice": "100",
+        "unRealizedProfit": "0",
+        "liquidationPrice": "0",
+        "leverage": "5"
+    }]))
+}
+
+/// Serves Binance's signed endpoints and Aster's `/fapi/v3` ones off one venue.
+async fn stub(venue: Shared) -> String {
+    let leverage = || post(|| async { axum::Json(json!({})) });
+    let app = Router::new()
+        .route(
+            "/fapi/v1/exchangeInfo",
+            get(|| async {
+                axum::Json(json!({"symbols": [{
+                    "symbol": "BTCUSDT",
+                    "status": "TRADING",
+                    "baseAsset": "BTC",
+                    "quoteAsset": "USDT",
+                    "contractType": "PERPETUAL",
+                    "pricePrecision": 2,
+                    "quantityPrecision": 3
+                }]}))
+            }),
+        )
+        .route(
+            "/fapi/v1/ticker/price",
+            get(|| async { axum::Json(json!({"symbol": "BTCUSDT", "price": "100"})) }),
+        )
+        .route("/fapi/v1/leverage", leverage())
+        .route("/fapi/v1/order", post(place).delete(cancel))
+        .route("/fapi/v1/openOrders", get(open_orders))
+        .route("/fapi/v2/positionRisk", get(positions))
+        .route("/fapi/v3/leverage", leverage())
+        .route("/fapi/v3/order", post(place_form).delete(cancel))
+        .route("/fapi/v3/openOrders", get(open_orders))
+        .route("/fapi/v3/positionRisk", get(positions))
+        .with_state(venue);
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    format!("http://{}", addr)
+}
+
+fn executor<E: TradeExecutor>(client: E) -> Executor<E> {
+    let policy = RetryPolicy {
+        base_delay: Duration::ZERO,
+        ..Default::default()
+    };
+    Executor::new(client, policy)
+}
+
+fn binance(url: &str) -> ApiClient {
+    ApiClient::new()
+        .with_base_url(url)
+        .with_credentials("key", "secret")
+}
+
+fn open_long() -> Decision {
+    Decision {
+        leverage: 5,
+        position_size_usd: 500.0,
+        stop_loss: 90.0,
+        take_profit: 120.0,
+        ..Decision::new("BTCUSDT", Action::OpenLong)
+    }
+}
+
+fn order_types(venue: &Shared) -> Vec<String> {
+    let venue = venue.lock().unwrap();
+    venue
+        .open_orders
+        .iter()
+        .map(|o| o["type"].as_str().unwrap().to_string())
+        .collect()
+}
+
+#[tokio::test]
+async fn market_fills_are_reported_and_stale_stops_cancelled() {
+    let venue = Shared::default();
+    // 上一个仓位留下的止损单
+    venue.lock().unwrap().open_orders.push(json!({
+        "orderId": 99,
+        "symbol": "BTCUSDT",
+        "status": "NEW",
+        "side": "SELL",
+        "type": "STOP_MARKET",
+    }));
+    let url = stub(venue.clone()).await;
+    let mut ex = executor(binance(&url));
+    let mut record = DecisionRecord::new("", "", "", "");
+
+    let executions = ex.execute(&[open_long()], &mut record).await;
+    let fill = executions[0].result.clone().unwrap().unwrap();
+    assert_eq!(fill.filled_quantity, Decimal::from(5));
+    assert_eq!(fill.price, Decimal::ONE_HUNDRED);
+
+    let placed = venue.lock().unwrap().placed.clone();
+    assert_eq!(placed[0]["type"], "MARKET");
+    assert_eq!(placed[0]["newOrderRespType"], "RESULT");
+    assert!(
+        placed[1..]
+            .iter()
+            .all(|p| !p.contains_key("newOrderRespType"))
+    );
+    assert_eq!(order_types(&venue), ["STOP_MARKET", "TAKE_PROFIT_MARKET"]);
+    assert!(
+        venue
+            .lock()
+            .unwrap()
+            .open_orders
+            .iter()
+            .all(|o| o["orderId"] != 99),
+        "the stale stop was left on the book"
+    );
+
+    let close = Decision::new("BTCUSDT", Action::CloseLong);
+    let executions = ex.execute(&[close], &mut record).await;
+    let fill = executions[0].result.clone().unwrap().unwrap();
+    assert_eq!(fill.filled_quantity, Decimal::from(5));
+    assert_eq!(venue.lock().unwrap().position, 0.0);
+    assert!(order_types(&venue).is_empty());
+}
+
+#[tokio::test]
+async fn aster_market_fills_are_reported() {
+    const KEY: &str = "0x4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318";
+    let venue = Shared::default();
+    let url = stub(venue.clone()).await;
+    let signer = aster::address_for_key(KEY).unwrap();
+    let client = AsterClient::new("0x00000000000000000000000000000000000000aa", &signer, KEY)
+        .unwrap()
+        .with_base_url(&url);
+    let mut ex = executor(client);
+    let mut record = DecisionRecord::new("", "", "", "");
+
+    let executions = ex.execute(&[open_long()], &mut record).await;
+    let fill = executions[0].result.clone().unwrap().unwrap();
+    assert_eq!(fill.filled_quantity, Decimal::from(5));
+    assert_eq!(fill.price, Decimal::ONE_HUNDRED);
+
+    let placed = venue.lock().unwrap().placed.clone();
+    assert_eq!(placed[0]["newOrderRespType"], "RESULT");
+    assert!(
+        placed[1..]
+            .iter()
+            .all(|p| !p.contains_key("newOrderRespType"))
+    );
+    assert_eq!(order_types(&venue), ["STOP_MARKET", "TAKE_PROFIT_MARKET"]);
+}
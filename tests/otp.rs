@@ -0,0 +1,209 @@
+//! Two-factor enrollment, recovery codes and re-enrollment, and the
+//! registration, login and setup endpoints around them.
+
+use aitrading::auth;
+use aitrading::database::User;
+use aitrading::otp::{self, OtpError, SecondFactor};
+use aitrading::server::{AppState, EmbeddedClient};
+use aitrading::testkit;
+use axum::http::{Method, StatusCode};
+use serde_json::json;
+use totp_rs::{Algorithm, Secret, TOTP};
+
+fn current_code(secret: &str) -> String {
+    TOTP::new(
+        Algorithm::SHA1,
+        6,
+        1,
+        30,
+        Secret::Encoded(secret.to_string()).to_bytes().unwrap(),
+        None,
+        "test".to_string(),
+    )
+    .unwrap()
+    .generate_current()
+    .unwrap()
+}
+
+#[tokio::test]
+async fn recovery_codes_stand_in_for_totp_once() {
+    let db = testkit::memory_db().await.unwrap();
+    let user = User {
+        id: "u1".to_string(),
+        email: "alice@example.com".to_string(),
+        password_hash: auth::hash_password("correct horse").unwrap(),
+        ..Default::default()
+    };
+    db.create_user(&user).await.unwrap();
+
+    let enrollment = otp::enroll(&db, &user).await.unwrap();
+    assert_eq!(enrollment.recovery_codes.len(), auth::RECOVERY_CODE_COUNT);
+    assert!(enrollment.qr_code_url.contains(&enrollment.secret));
+    assert_eq!(db.count_otp_recovery_codes("u1").await.unwrap(), 10);
+    assert!(matches!(
+        otp::confirm(&db, "u1", "000000").await,
+        Err(OtpError::InvalidCode)
+    ));
+    let user = otp::confirm(&db, "u1", &current_code(&enrollment.secret))
+        .await
+        .unwrap();
+    assert!(user.otp_verified);
+    assert!(db.get_user_by_id("u1").await.unwrap().unwrap().otp_verified);
+    assert!(matches!(
+        otp::enroll(&db, &user).await,
+        Err(OtpError::AlreadyEnrolled)
+    ));
+
+    // A recovery code works once, however it is typed.
+    let code = enrollment.recovery_codes[0].clone();
+    let typed = format!(" {} ", code.replace('-', "").to_uppercase());
+    assert_eq!(
+        otp::verify(&db, &user, &typed).await.unwrap(),
+        SecondFactor::RecoveryCode
+    );
+    assert!(matches!(
+        otp::verify(&db, &user, &code).await,
+        Err(OtpError::InvalidCode)
+    ));
+    assert!(matches!(
+        otp::verify(&db, &user, "").await,
+        Err(OtpError::InvalidCode)
+    ));
+    assert_eq!(db.count_otp_recovery_codes("u1").await.unwrap(), 9);
+    assert_eq!(
+        otp::verify(&db, &user, &current_code(&user.otp_secret))
+            .await
+            .unwrap(),
+        SecondFactor::Totp
+    );
+
+    // Lost authenticator: password plus a recovery code resets everything.
+    assert!(matches!(
+        otp::re_enroll(&db, &user, "wrong", &enrollment.recovery_codes[1]).await,
+        Err(OtpError::InvalidPassword)
+    ));
+    let renewed = otp::re_enroll(&db, &user, "correct horse", &enrollment.recovery_codes[1])
+        .await
+        .unwrap();
+    assert_ne!(renewed.secret, enrollment.secret);
+    let user = db.get_user_by_id("u1").await.unwrap().unwrap();
+    assert!(!user.otp_verified);
+    assert_eq!(user.otp_secret, renewed.secret);
+    assert_eq!(db.count_otp_recovery_codes("u1").await.unwrap(), 10);
+    assert!(
+        !db.consume_otp_recovery_code(
+            "u1",
+            &auth::hash_recovery_code(&enrollment.recovery_codes[2]),
+            chrono::Utc::now()
+        )
+        .await
+        .unwrap()
+    );
+    otp::confirm(&db, "u1", &current_code(&renewed.secret))
+        .await
+        .unwrap();
+
+    let user = db.get_user_by_id("u1").await.unwrap().unwrap();
+    let fresh = otp::regenerate_recovery_codes(&db, &user, &renewed.recovery_codes[0])
+        .await
+        .unwrap();
+    assert!(
+        otp::verify(&db, &user, &renewed.recovery_codes[1])
+            .await
+            .is_err()
+    );
+    assert!(otp::verify(&db, &user, &fresh[0]).await.is_ok());
+}
+
+#[tokio::test]
+async fn beta_registration_and_two_factor_setup_over_the_api() {
+    auth::set_jwt_secret("otp-test-secret");
+    let db = testkit::memory_db().await.unwrap();
+    db.set_system_config("beta_mode", "true").await.unwrap();
+    db.insert_beta_codes(&["BETA2345".to_string()])
+        .await
+        .unwrap();
+    let client = EmbeddedClient::new(AppState {
+        db: db.clone(),
+        scheduler: None,
+        cipher: None,
+    });
+    let register = |email: &str, beta_code: &str| json!({ "email": email, "password": "correct horse", "beta_code": beta_code });
+
+    let (status, _) = client
+        .request(
+            Method::POST,
+            "/api/register",
+            Some(&register("alice@example.com", "WRONG")),
+        )
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, body) = client
+        .request(
+            Method::POST,
+            "/api/register",
+            Some(&register("alice@example.com", "BETA2345")),
+        )
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+    let (status, _) = client
+        .request(
+            Method::POST,
+            "/api/register",
+            Some(&register("bob@example.com", "BETA2345")),
+        )
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let alice = client.clone().with_token(body["token"].as_str().unwrap());
+
+    let (status, _) = alice
+        .request(
+            Method::POST,
+            "/api/otp/verify",
+            Some(&json!({ "code": "1" })),
+        )
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, setup) = alice
+        .request(Method::GET, "/api/otp/setup", None)
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::OK);
+    let secret = setup["secret"].as_str().unwrap();
+    assert!(setup["qr_code_url"].as_str().unwrap().contains(secret));
+    let (status, user) = alice
+        .request(
+            Method::POST,
+            "/api/otp/verify",
+            Some(&json!({ "code": current_code(secret) })),
+        )
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(user["otp_verified"], true);
+    let (status, _) = alice
+        .request(Method::GET, "/api/otp/setup", None)
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    let login = |otp_code: &str| json!({ "email": "alice@example.com", "password": "correct horse", "otp_code": otp_code });
+    let (status, _) = client
+        .request(Method::POST, "/api/login", Some(&login("")))
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, _) = client
+        .request(
+            Method::POST,
+            "/api/login",
+            Some(&login(&current_code(secret))),
+        )
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::OK);
+}
@@ -0,0 +1,149 @@
+//! Execution quality analytics over recorded fills.
+
+use aitrading::database::{Trade, TraderRecord, User};
+use aitrading::money::{self, Decimal};
+use aitrading::performance;
+use aitrading::server::{AppState, EmbeddedClient};
+use aitrading::testkit;
+use axum::http::{Method, StatusCode};
+use chrono::{TimeZone, Utc};
+
+fn fill(symbol: &str, side: &str, expected: f64, price: f64, latency_ms: i64, hour: u32) -> Trade {
+    Trade {
+        user_id: "admin".to_string(),
+        trader_id: "trader-1".to_string(),
+        symbol: symbol.to_string(),
+        side: side.to_string(),
+        action: if side == "buy" {
+            "open_long"
+        } else {
+            "close_long"
+        }
+        .to_string(),
+        quantity: Decimal::TWO,
+        price: money::from_f64(price),
+        expected_price: money::from_f64(expected),
+        fill_latency_ms: latency_ms,
+        executed_at: Utc::now()
+            .date_naive()
+            .and_hms_opt(hour, 0, 0)
+            .unwrap()
+            .and_utc()
+            - chrono::Duration::days(1),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn slippage_is_signed_by_side() {
+    let buy = fill("BTCUSDT", "buy", 100.0, 100.1, 0, 0);
+    let sell = fill("BTCUSDT", "sell", 100.0, 100.1, 0, 0);
+    assert!((performance::slippage_bps(&buy).unwrap() - 10.0).abs() < 1e-9);
+    assert!((performance::slippage_bps(&sell).unwrap() + 10.0).abs() < 1e-9);
+    // Fills from before expected prices were recorded are not scored.
+    assert_eq!(
+        performance::slippage_bps(&fill("BTCUSDT", "buy", 0.0, 100.0, 0, 0)),
+        None
+    );
+
+    let at = Utc.with_ymd_and_hms(2026, 1, 1, 23, 30, 0).unwrap();
+    let late = Trade {
+        executed_at: at,
+        ..buy.clone()
+    };
+    let quality = performance::execution_quality(&[late], chrono_tz::Asia::Shanghai);
+    // 23:30 UTC is 07:30 in Shanghai.
+    assert_eq!(quality.by_hour.keys().copied().collect::<Vec<_>>(), vec![7]);
+}
+
+// One test owns the process-wide admin mode.
+#[tokio::test]
+async fn execution_quality_is_served_per_symbol_and_hour() {
+    let db = testkit::memory_db().await.unwrap();
+    db.create_user(&User {
+        id: "admin".to_string(),
+        email: "admin@localhost".to_string(),
+        ..Default::default()
+    })
+    .await
+    .unwrap();
+    db.create_trader(&TraderRecord {
+        id: "trader-1".to_string(),
+        user_id: "admin".to_string(),
+        name: "quality".to_string(),
+        ai_model_id: "deepseek".to_string(),
+        exchange_id: "binance".to_string(),
+        ..Default::default()
+    })
+    .await
+    .unwrap();
+    for trade in [
+        fill("BTCUSDT", "buy", 100.0, 100.2, 300, 9),
+        fill("BTCUSDT", "sell", 100.0, 100.0, 100, 9),
+        fill("ETHUSDT", "buy", 10.0, 9.99, 800, 14),
+        // No expected price: ignored.
+        fill("ETHUSDT", "buy", 0.0, 12.0, 0, 14),
+    ] {
+        db.record_trade(&trade).await.unwrap();
+    }
+
+    let client = EmbeddedClient::new(AppState {
+        db: db.clone(),
+        scheduler: None,
+        cipher: None,
+    })
+    .with_token(testkit::admin_token());
+    let (status, body) = client
+        .request(
+            Method::GET,
+            "/api/traders/trader-1/performance/execution?days=7",
+            None,
+        )
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["overall"]["fills"], 3);
+    assert_eq!(body["overall"]["max_latency_ms"], 800);
+    let btc = &body["by_symbol"]["BTCUSDT"];
+    assert_eq!(btc["fills"], 2);
+    assert!((btc["avg_slippage_bps"].as_f64().unwrap() - 10.0).abs() < 1e-6);
+    assert!((btc["worst_slippage_bps"].as_f64().unwrap() - 20.0).abs() < 1e-6);
+    assert!((btc["slippage_cost"].as_f64().unwrap() - 0.4).abs() < 1e-6);
+    assert_eq!(btc["avg_latency_ms"], 200.0);
+    // Buying below the expected price is negative slippage.
+    assert!(
+        body["by_symbol"]["ETHUSDT"]["avg_slippage_bps"]
+            .as_f64()
+            .unwrap()
+            < 0.0
+    );
+    assert_eq!(body["by_hour"]["9"]["fills"], 2);
+    assert_eq!(body["by_hour"]["14"]["fills"], 1);
+
+    let (status, _) = client
+        .request(
+            Method::GET,
+            "/api/traders/missing/performance/execution",
+            None,
+        )
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn transfers_are_synced_from_the_income_history() {
+    let mut h = testkit::Harness::new(&["BTCUSDT"], 1000.0).await.unwrap();
+    h.exchange.set_price("BTCUSDT", 100.0);
+    h.exchange.transfer(500.0, 1);
+    h.run_cycle().await.unwrap();
+    h.exchange.transfer(-200.0, 2);
+    h.run_cycle().await.unwrap();
+    h.run_cycle().await.unwrap();
+
+    let transfers = h.db.get_transfers(&h.user_id, &h.trader.id).await.unwrap();
+    let mut amounts: Vec<f64> = transfers.iter().map(|t| money::to_f64(t.amount)).collect();
+    amounts.sort_by(f64::total_cmp);
+    assert_eq!(amounts, [-200.0, 500.0]);
+    assert!(transfers.iter().all(|t| t.source == "income"));
+}
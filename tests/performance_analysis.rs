@@ -0,0 +1,148 @@
+//! Closed-trade analysis of a trader's decision records.
+
+use std::fs;
+
+use aitrading::logger::DecisionLogger;
+use serde_json::{Value, json};
+
+fn record(id: &str, at: &str, equity: f64, decisions: Value) -> Value {
+    json!({
+        "schema_version": 2,
+        "id": id,
+        "timestamp": at,
+        "cycle_number": 1,
+        "system_prompt": "",
+        "input_prompt": "",
+        "cot_trace": "",
+        "decision_json": "[]",
+        "account_state": {
+            "total_balance": equity, "available_balance": equity,
+            "total_unrealized_profit": 0.0, "position_count": 0, "margin_used_pct": 0.0
+        },
+        "positions": [],
+        "candidate_coins": [],
+        "decisions": decisions,
+        "execution_log": [],
+        "success": true,
+        "error_message": ""
+    })
+}
+
+fn action(action: &str, symbol: &str, quantity: f64, leverage: i32, price: f64, at: &str) -> Value {
+    json!({
+        "action": action, "symbol": symbol, "quantity": quantity, "leverage": leverage,
+        "price": price, "order_id": 1, "timestamp": at, "success": true, "error": ""
+    })
+}
+
+#[test]
+fn pairs_opens_with_closes() {
+    let dir = std::env::temp_dir().join(format!("aitrading-analysis-{}", uuid::Uuid::new_v4()));
+    let logger = DecisionLogger::new(&dir.to_string_lossy());
+    let mut failed = action("open_long", "SOLUSDT", 1.0, 5, 20.0, "2025-01-01T02:00:00Z");
+    failed["success"] = json!(false);
+    let records = [
+        (
+            "decision_20250101_000000_cycle1",
+            "2025-01-01T00:00:00Z",
+            1000.0,
+            json!([action(
+                "open_long",
+                "BTCUSDT",
+                1.0,
+                5,
+                100.0,
+                "2025-01-01T00:00:00Z"
+            )]),
+        ),
+        (
+            "decision_20250101_010000_cycle2",
+            "2025-01-01T01:00:00Z",
+            1010.0,
+            json!([action(
+                "close_long",
+                "BTCUSDT",
+                1.0,
+                5,
+                110.0,
+                "2025-01-01T01:00:00Z"
+            )]),
+        ),
+        (
+            "decision_20250101_020000_cycle3",
+            "2025-01-01T02:00:00Z",
+            990.0,
+            json!([
+                action(
+                    "open_short",
+                    "ETHUSDT",
+                    2.0,
+                    2,
+                    50.0,
+                    "2025-01-01T02:00:00Z"
+                ),
+                failed
+            ]),
+        ),
+        (
+            "decision_20250101_040000_cycle4",
+            "2025-01-01T04:00:00Z",
+            1000.0,
+            json!([action(
+                "close_short",
+                "ETHUSDT",
+                2.0,
+                2,
+                55.0,
+                "2025-01-01T04:00:00Z"
+            )]),
+        ),
+    ];
+    for (id, at, equity, decisions) in records {
+        fs::write(
+            dir.join(format!("{}.json", id)),
+            record(id, at, equity, decisions).to_string(),
+        )
+        .unwrap();
+    }
+
+    // The BTC open is before the three-cycle window and is found anyway.
+    let analysis = logger.analyze_performance(3).unwrap();
+    let v = serde_json::to_value(&analysis).unwrap();
+    assert_eq!(v["total_trades"], 2);
+    assert_eq!(
+        (v["winning_trades"].clone(), v["losing_trades"].clone()),
+        (json!(1), json!(1))
+    );
+    assert_eq!(v["win_rate"], 50.0);
+    assert_eq!(
+        (v["avg_win"].clone(), v["avg_loss"].clone()),
+        (json!(10.0), json!(-10.0))
+    );
+    assert_eq!(v["profit_factor"], 1.0);
+    assert_eq!(v["avg_holding_minutes"], 90.0);
+    assert_eq!(
+        (v["best_symbol"].clone(), v["worst_symbol"].clone()),
+        (json!("BTCUSDT"), json!("ETHUSDT"))
+    );
+
+    let btc = &v["recent_trades"][0];
+    assert_eq!(btc["pn_l_pct"], 50.0);
+    assert_eq!(btc["duration"], "1h00m");
+    assert_eq!(btc["open_record_id"], "decision_20250101_000000_cycle1");
+    assert_eq!(v["recent_trades"][1]["side"], "SHORT");
+    assert_eq!(v["symbol_stats"]["ETHUSDT"]["avg_holding_minutes"], 120.0);
+
+    // Equity in the window went 1010 -> 990 -> 1000.
+    let drawdown = v["max_drawdown_pct"].as_f64().unwrap();
+    assert!((drawdown - 20.0 / 1010.0 * 100.0).abs() < 1e-9);
+    assert!(v["sharpe_ratio"].as_f64().unwrap() < 0.0);
+    assert!(v["sortino_ratio"].as_f64().unwrap() < v["sharpe_ratio"].as_f64().unwrap());
+
+    let summary = analysis.prompt_summary();
+    assert!(summary.contains("Win rate 50.0%"));
+    assert!(summary.contains("Avg hold 1h30m"));
+    assert!(summary.contains("Best symbol: BTCUSDT | Worst symbol: ETHUSDT"));
+
+    fs::remove_dir_all(dir).unwrap();
+}
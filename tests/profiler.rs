@@ -0,0 +1,30 @@
+//! Cycle timings collected while traders run.
+
+use aitrading::decision::{Action, Decision};
+use aitrading::profiler;
+use aitrading::testkit::Harness;
+
+#[tokio::test]
+async fn cycles_are_timed_by_stage() {
+    let mut h = Harness::new(&["BTCUSDT"], 1000.0).await.unwrap();
+    h.exchange.set_price("BTCUSDT", 100.0);
+    h.ai.push_decisions(&[Decision {
+        leverage: 2,
+        position_size_usd: 100.0,
+        ..Decision::new("BTCUSDT", Action::OpenLong)
+    }]);
+    h.run_cycle().await.unwrap();
+
+    let report = profiler::report();
+    let cycle = report
+        .slowest_cycles
+        .iter()
+        .find(|c| c.trader_id == h.trader.id)
+        .expect("the cycle is among the slowest kept");
+    assert_eq!(cycle.cycle_number, 1);
+    let stages: Vec<&str> = cycle.stages.iter().map(|(s, _)| s.as_str()).collect();
+    assert_eq!(stages, ["context", "ai_call", "execution"]);
+    for stage in ["cycle", "ai_call", "execution"] {
+        assert!(report.stages[stage].count >= 1, "{}", stage);
+    }
+}
@@ -0,0 +1,119 @@
+//! System and user prompt assembly.
+
+use aitrading::database::TraderRecord;
+use aitrading::decision::{AccountInfo, Context, PositionInfo};
+use aitrading::prompt;
+use aitrading::prompt_template::{self, TemplateError};
+use aitrading::testkit::mock_data;
+use chrono::{TimeZone, Utc};
+
+fn context() -> Context {
+    let now = Utc.with_ymd_and_hms(2025, 3, 1, 12, 0, 0).unwrap();
+    let symbols = ["SOLUSDT", "BTCUSDT", "ETHUSDT"];
+    Context {
+        current_time: now,
+        runtime_minutes: 90,
+        call_count: 30,
+        account: AccountInfo {
+            total_equity: 1000.0,
+            available_balance: 800.0,
+            margin_used_pct: 20.0,
+            position_count: 1,
+            ..Default::default()
+        },
+        positions: vec![PositionInfo {
+            symbol: "ETHUSDT".to_string(),
+            side: "long".to_string(),
+            entry_price: 2000.0,
+            mark_price: 2050.0,
+            quantity: 0.5,
+            leverage: 5,
+            update_time: (now - chrono::Duration::minutes(135)).timestamp_millis(),
+            ..Default::default()
+        }],
+        candidate_coins: symbols.iter().map(|s| s.to_string()).collect(),
+        market_data: symbols
+            .iter()
+            .map(|s| (s.to_string(), mock_data(s, 100.0)))
+            .collect(),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn user_prompt_is_reproducible() {
+    let notes = vec!["🧊 cooling down".to_string()];
+    let first = prompt::user_prompt(&context(), &notes);
+    assert_eq!(first, prompt::user_prompt(&context(), &notes));
+
+    assert!(first.starts_with("Time: 2025-03-01 12:00:00 UTC | Runtime: 90 min | Cycle: #30"));
+    assert!(first.contains("1. ETHUSDT LONG"));
+    assert!(first.contains("held 2h15m"));
+    assert!(first.contains("## Candidate coins (2)"));
+    let btc = first.find("=== BTCUSDT ===").unwrap();
+    let sol = first.find("=== SOLUSDT ===").unwrap();
+    assert!(btc < sol);
+    assert!(!first.contains("=== ETHUSDT ==="));
+}
+
+#[test]
+fn system_prompt_follows_trader_settings() {
+    let mut trader = TraderRecord {
+        btc_eth_leverage: 10,
+        altcoin_leverage: 3,
+        custom_prompt: "Only trade BTC.".to_string(),
+        ..Default::default()
+    };
+    let default = prompt::builtin_template(prompt::DEFAULT_TEMPLATE).unwrap();
+    let base = prompt::system_prompt(&trader, default, &context());
+    assert!(base.contains("at most 10x on BTC/ETH and at most 3x"));
+    assert!(base.contains("Only trade BTC."));
+    assert!(base.contains("# Output format"));
+
+    trader.override_base_prompt = true;
+    let own = prompt::system_prompt(&trader, default, &context());
+    assert!(own.starts_with("Only trade BTC."));
+    assert!(!own.contains("# Hard rules"));
+    assert!(own.contains("# Output format"));
+}
+
+#[test]
+fn templates_fill_in_variables() {
+    let trader = TraderRecord {
+        btc_eth_leverage: 10,
+        altcoin_leverage: 3,
+        ..Default::default()
+    };
+    let prompt = prompt::system_prompt(
+        &trader,
+        "Trade only {{ symbol_list }}.\n{{risk_limits}}\nSo far: {{performance_summary}}",
+        &context(),
+    );
+    assert!(
+        prompt.starts_with(
+            "Trade only BTCUSDT, ETHUSDT, SOLUSDT.\n- Leverage at most 10x on BTC/ETH"
+        )
+    );
+    assert!(prompt.contains("So far: No closed trades yet."));
+    assert!(prompt.contains("# Output format"));
+
+    let vars = prompt_template::variables(&trader, &context());
+    assert_eq!(
+        prompt_template::render("{{btc_eth_leverage}}/{{altcoin_leverage}}", &vars).unwrap(),
+        "10/3"
+    );
+    assert_eq!(
+        prompt_template::render("{{leverage", &vars),
+        Err(TemplateError::Unclosed(0))
+    );
+    assert_eq!(
+        prompt_template::validate("mine", "Use {{max_positions}}."),
+        Err(TemplateError::UnknownVariable("max_positions".to_string()))
+    );
+    assert_eq!(
+        prompt_template::validate("conservative", "x"),
+        Err(TemplateError::Reserved("conservative".to_string()))
+    );
+    assert!(prompt_template::validate("my template", "x").is_err());
+    assert!(prompt_template::validate("scalper_v2", "Scalp {{symbol_list}}.").is_ok());
+}
@@ -0,0 +1,169 @@
+//! Trader prompt versioning: diffs, history and rollback.
+
+use aitrading::database::{Database, TraderRecord};
+use aitrading::prompt_history::{self, PromptChange, PromptHistoryError, PromptState};
+use aitrading::server::{AppState, EmbeddedClient};
+use aitrading::testkit;
+use axum::http::{Method, StatusCode};
+use serde_json::json;
+
+async fn trader_db(user_id: &str) -> Database {
+    let db = testkit::memory_db().await.unwrap();
+    db.ensure_admin_user().await.unwrap();
+    db.create_trader(&TraderRecord {
+        id: "t1".to_string(),
+        user_id: user_id.to_string(),
+        name: "t1".to_string(),
+        ai_model_id: "deepseek".to_string(),
+        exchange_id: "binance".to_string(),
+        custom_prompt: "Trade BTC only.\nKeep leverage low.\n".to_string(),
+        system_prompt_template: "default".to_string(),
+        ..Default::default()
+    })
+    .await
+    .unwrap();
+    db
+}
+
+fn state(custom_prompt: &str, override_base: bool, template: &str) -> PromptState {
+    PromptState {
+        custom_prompt: custom_prompt.to_string(),
+        override_base_prompt: override_base,
+        system_prompt_template: template.to_string(),
+    }
+}
+
+#[test]
+fn diff_lists_setting_changes_and_prompt_lines() {
+    let old = state("a\nb\n", false, "default");
+    let new = state("a\nc\n", true, "aggressive");
+    let diff = prompt_history::diff(&old, &new);
+    assert!(diff.starts_with("system_prompt_template: default -> aggressive\n"));
+    assert!(diff.contains("override_base_prompt: false -> true\n"));
+    assert!(diff.contains("--- a/custom_prompt\n+++ b/custom_prompt\n"));
+    assert!(diff.contains("-b\n+c\n"));
+    assert_eq!(prompt_history::diff(&old, &old), "");
+}
+
+#[tokio::test]
+async fn edits_are_versioned_and_can_be_rolled_back() {
+    let db = trader_db("admin").await;
+    let change = PromptChange {
+        custom_prompt: Some("Trade BTC only.\nUse high leverage.\n".to_string()),
+        note: "more risk".to_string(),
+        ..Default::default()
+    };
+    let v2 = prompt_history::update(&db, "admin", "t1", &change, "alice")
+        .await
+        .unwrap();
+    assert_eq!(v2.version, 2);
+    assert_eq!(v2.author, "alice");
+    assert!(
+        v2.diff
+            .contains("-Keep leverage low.\n+Use high leverage.\n")
+    );
+
+    // The prompt the trader was created with became version 1.
+    let versions = db.get_prompt_versions("admin", "t1").await.unwrap();
+    assert_eq!(
+        versions.iter().map(|v| v.version).collect::<Vec<_>>(),
+        vec![2, 1]
+    );
+    assert_eq!(
+        versions[1].custom_prompt,
+        "Trade BTC only.\nKeep leverage low.\n"
+    );
+    assert_eq!(versions[1].diff, "");
+
+    // Repeating the same edit records nothing.
+    let again = prompt_history::update(&db, "admin", "t1", &change, "alice")
+        .await
+        .unwrap();
+    assert_eq!(again.version, 2);
+
+    let v3 = prompt_history::rollback(&db, "admin", "t1", 1, "bob")
+        .await
+        .unwrap();
+    assert_eq!(v3.version, 3);
+    assert_eq!(v3.note, "rollback to v1");
+    assert!(v3.diff.contains("+Keep leverage low.\n"));
+    let trader = db.get_trader("admin", "t1").await.unwrap().unwrap();
+    assert_eq!(
+        trader.custom_prompt,
+        "Trade BTC only.\nKeep leverage low.\n"
+    );
+
+    assert!(matches!(
+        prompt_history::rollback(&db, "admin", "t1", 9, "bob").await,
+        Err(PromptHistoryError::VersionNotFound(9))
+    ));
+    assert!(matches!(
+        prompt_history::update(&db, "admin", "missing", &change, "bob").await,
+        Err(PromptHistoryError::TraderNotFound(_))
+    ));
+}
+
+#[tokio::test]
+async fn prompt_endpoints_record_the_caller_as_author() {
+    let db = trader_db("admin").await;
+    let client = EmbeddedClient::new(AppState {
+        db: db.clone(),
+        scheduler: None,
+        cipher: None,
+    })
+    .with_token(testkit::admin_token());
+
+    let (status, body) = client
+        .request(
+            Method::PUT,
+            "/api/traders/t1/prompt",
+            Some(&json!({ "system_prompt_template": "conservative" })),
+        )
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["version"], 2);
+    assert_eq!(body["author"], "admin@localhost");
+    assert_eq!(
+        body["diff"],
+        "system_prompt_template: default -> conservative\n"
+    );
+
+    let (status, body) = client
+        .request(Method::GET, "/api/traders/t1/prompt/versions", None)
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body.as_array().unwrap().len(), 2);
+
+    let (status, body) = client
+        .request(Method::GET, "/api/traders/t1/prompt/versions/1", None)
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["system_prompt_template"], "default");
+
+    let (status, body) = client
+        .request(
+            Method::POST,
+            "/api/traders/t1/prompt/versions/1/rollback",
+            None,
+        )
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["version"], 3);
+    let trader = db.get_trader("admin", "t1").await.unwrap().unwrap();
+    assert_eq!(trader.system_prompt_template, "default");
+
+    let (status, _) = client
+        .request(Method::GET, "/api/traders/t1/prompt/versions/7", None)
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, _) = client
+        .request(Method::GET, "/api/traders/other/prompt/versions", None)
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
@@ -0,0 +1,146 @@
+//! User-managed system prompt templates.
+
+use aitrading::database::TraderRecord;
+use aitrading::server::{AppState, EmbeddedClient};
+use aitrading::testkit::{self, Harness};
+use axum::http::{Method, StatusCode};
+use serde_json::json;
+
+#[tokio::test]
+async fn templates_are_managed_and_used_by_traders() {
+    let db = testkit::memory_db().await.unwrap();
+    db.ensure_admin_user().await.unwrap();
+    db.create_trader(&TraderRecord {
+        id: "t1".to_string(),
+        user_id: "admin".to_string(),
+        name: "t1".to_string(),
+        ai_model_id: "deepseek".to_string(),
+        exchange_id: "binance".to_string(),
+        system_prompt_template: "default".to_string(),
+        ..Default::default()
+    })
+    .await
+    .unwrap();
+    let client = EmbeddedClient::new(AppState {
+        db: db.clone(),
+        scheduler: None,
+        cipher: None,
+    })
+    .with_token(testkit::admin_token());
+
+    let template =
+        json!({ "name": "scalper", "content": "Scalp {{symbol_list}} at {{leverage}}." });
+    let (status, created) = client
+        .request(Method::POST, "/api/prompt-templates", Some(&template))
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::CREATED, "{}", created);
+    assert_eq!(created["name"], "scalper");
+    let (status, _) = client
+        .request(Method::POST, "/api/prompt-templates", Some(&template))
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::CONFLICT);
+    for bad in [
+        json!({ "name": "bad", "content": "{{unknown}}" }),
+        json!({ "name": "default", "content": "mine" }),
+        json!({ "name": "", "content": "mine" }),
+    ] {
+        let (status, _) = client
+            .request(Method::POST, "/api/prompt-templates", Some(&bad))
+            .await
+            .unwrap();
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}", bad);
+    }
+
+    let (status, list) = client
+        .request(Method::GET, "/api/prompt-templates", None)
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(list["builtin"], json!(["default", "conservative"]));
+    assert!(
+        list["variables"]
+            .as_array()
+            .unwrap()
+            .contains(&json!("risk_limits"))
+    );
+    assert_eq!(list["templates"].as_array().unwrap().len(), 1);
+
+    let (status, _) = client
+        .request(
+            Method::PUT,
+            "/api/traders/t1/prompt",
+            Some(&json!({ "system_prompt_template": "missing" })),
+        )
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, _) = client
+        .request(
+            Method::PUT,
+            "/api/traders/t1/prompt",
+            Some(&json!({ "system_prompt_template": "scalper" })),
+        )
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, updated) = client
+        .request(
+            Method::PUT,
+            "/api/prompt-templates/scalper",
+            Some(&json!({ "content": "Scalp {{ symbol_list }} only." })),
+        )
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(updated["content"], "Scalp {{ symbol_list }} only.");
+
+    let (status, _) = client
+        .request(Method::DELETE, "/api/prompt-templates/scalper", None)
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::CONFLICT);
+    client
+        .request(
+            Method::PUT,
+            "/api/traders/t1/prompt",
+            Some(&json!({ "system_prompt_template": "default" })),
+        )
+        .await
+        .unwrap();
+    let (status, _) = client
+        .request(Method::DELETE, "/api/prompt-templates/scalper", None)
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (status, _) = client
+        .request(Method::GET, "/api/prompt-templates/scalper", None)
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn cycle_renders_the_trader_template() {
+    let mut h = Harness::new(&["BTCUSDT"], 1000.0).await.unwrap();
+    assert!(
+        h.db.create_prompt_template(&h.user_id, "mine", "Watch {{symbol_list}}; {{leverage}}.")
+            .await
+            .unwrap()
+    );
+    h.trader.system_prompt_template = "mine".to_string();
+    h.db.update_trader(&h.trader).await.unwrap();
+    h.exchange.set_price("BTCUSDT", 100.0);
+
+    h.run_cycle().await.unwrap();
+    let call = h.ai.calls().pop().unwrap();
+    assert!(
+        call.system_prompt
+            .starts_with("Watch BTCUSDT; at most 5x on BTC/ETH"),
+        "{}",
+        call.system_prompt
+    );
+    assert!(call.system_prompt.contains("# Output format"));
+}
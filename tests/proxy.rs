@@ -0,0 +1,83 @@
+//! Outbound proxies per kind of traffic.
+
+use std::sync::{Arc, Mutex};
+
+use aitrading::api_client::{self, ApiClient, EndpointClass, Proxies, ProxySettings};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+/// A forward proxy stub that records request heads and answers every request
+/// with `body`.
+async fn proxy_stub(body: &'static str) -> (String, Arc<Mutex<Vec<String>>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let log = seen.clone();
+    tokio::spawn(async move {
+        while let Ok((mut socket, _)) = listener.accept().await {
+            let mut head = Vec::new();
+            let mut buf = [0u8; 1024];
+            while !head.windows(4).any(|w| w == b"\r\n\r\n") {
+                match socket.read(&mut buf).await {
+                    Ok(0) | Err(_) => break,
+                    Ok(n) => head.extend_from_slice(&buf[..n]),
+                }
+            }
+            log.lock()
+                .unwrap()
+                .push(String::from_utf8_lossy(&head).to_string());
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            let _ = socket.write_all(response.as_bytes()).await;
+        }
+    });
+    (format!("http://{}", addr), seen)
+}
+
+#[tokio::test]
+async fn market_data_goes_through_its_own_proxy() {
+    #[cfg(not(feature = "socks"))]
+    {
+        let socks = Proxies {
+            ai: Some(ProxySettings {
+                url: "socks5h://127.0.0.1:1080".to_string(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let err = api_client::set_proxies(&socks).unwrap_err();
+        assert!(err.to_string().contains("socks"));
+    }
+
+    let (proxy_url, seen) = proxy_stub(r#"{"symbol":"BTCUSDT","price":"123.5"}"#).await;
+    api_client::set_proxies(&Proxies {
+        market_data: Some(ProxySettings {
+            url: proxy_url,
+            username: "user".to_string(),
+            password: "secret".to_string(),
+            ..Default::default()
+        }),
+        ..Default::default()
+    })
+    .unwrap();
+
+    let client = ApiClient::new().with_base_url("http://binance.test");
+    assert_eq!(client.get_current_price("BTCUSDT").await.unwrap(), 123.5);
+    let head = seen.lock().unwrap()[0].to_lowercase();
+    assert!(head.starts_with("get http://binance.test/fapi/v1/ticker/price?symbol=btcusdt "));
+    // "user:secret", base64-encoded.
+    assert!(head.contains("proxy-authorization: basic dxnlcjpzzwnyzxq="));
+
+    // Exchange traffic has no proxy configured and connects directly.
+    let (direct_url, direct_seen) = proxy_stub("{}").await;
+    api_client::client_for(EndpointClass::Trading)
+        .get(format!("{}/direct", direct_url))
+        .send()
+        .await
+        .unwrap();
+    assert!(direct_seen.lock().unwrap()[0].starts_with("GET /direct "));
+    assert_eq!(seen.lock().unwrap().len(), 1);
+}
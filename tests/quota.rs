@@ -0,0 +1,127 @@
+//! Per-user quotas checked at trader creation and start.
+
+use aitrading::database::TraderRecord;
+use aitrading::quota::{self, Quota, QuotaError};
+use aitrading::server::{AppState, EmbeddedClient};
+use aitrading::testkit;
+use axum::http::{Method, StatusCode};
+use serde_json::json;
+
+fn trader(id: &str, user_id: &str) -> TraderRecord {
+    TraderRecord {
+        id: id.to_string(),
+        user_id: user_id.to_string(),
+        name: id.to_string(),
+        ai_model_id: "deepseek".to_string(),
+        exchange_id: "binance".to_string(),
+        initial_balance: 1000.0,
+        scan_interval_minutes: 5,
+        trading_symbols: "BTCUSDT,ETHUSDT".to_string(),
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn limits_apply_at_creation_and_start() {
+    let db = testkit::memory_db().await.unwrap();
+    // Unlimited by default.
+    let first = trader("trader-1", "user-1");
+    quota::check_create(&db, &first).await.unwrap();
+    db.create_trader(&first).await.unwrap();
+
+    quota::set_defaults(
+        &db,
+        &Quota {
+            max_traders: 1,
+            min_scan_interval_minutes: 5,
+            max_ai_spend_usd: 0.01,
+            max_symbols: 2,
+        },
+    )
+    .await
+    .unwrap();
+    let second = trader("trader-2", "user-1");
+    assert!(matches!(
+        quota::check_create(&db, &second).await,
+        Err(QuotaError::TooManyTraders(1))
+    ));
+    // Re-checking an existing trader does not count it twice.
+    quota::check_create(&db, &first).await.unwrap();
+    let fast = TraderRecord {
+        scan_interval_minutes: 1,
+        ..first.clone()
+    };
+    assert!(matches!(
+        quota::check_start(&db, &fast).await,
+        Err(QuotaError::ScanTooFrequent(5))
+    ));
+    let wide = TraderRecord {
+        trading_symbols: "BTCUSDT,ETHUSDT,SOLUSDT".to_string(),
+        ..first.clone()
+    };
+    assert!(matches!(
+        quota::check_start(&db, &wide).await,
+        Err(QuotaError::TooManySymbols(2))
+    ));
+
+    // 5000 tokens at the flat price is exactly the budget.
+    quota::check_start(&db, &first).await.unwrap();
+    quota::record_ai_call(&db, &first, 16_000, 4_000).await;
+    assert!(matches!(
+        quota::check_start(&db, &first).await,
+        Err(QuotaError::AiBudgetExhausted(_))
+    ));
+
+    // Another user's spend is their own.
+    let other = trader("trader-3", "user-2");
+    quota::check_start(&db, &other).await.unwrap();
+}
+
+// One test owns the process-wide admin mode.
+#[tokio::test]
+async fn admins_manage_quotas_through_the_api() {
+    let db = testkit::memory_db().await.unwrap();
+    let client = EmbeddedClient::new(AppState {
+        db: db.clone(),
+        scheduler: None,
+        cipher: None,
+    })
+    .with_token(testkit::admin_token());
+
+    let (status, body) = client
+        .request(
+            Method::PUT,
+            "/api/admin/quotas",
+            Some(&json!({ "max_traders": 3 })),
+        )
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["max_traders"], 3);
+    assert_eq!(body["max_symbols"], 0);
+
+    let (status, body) = client
+        .request(
+            Method::PUT,
+            "/api/admin/quotas/user-1",
+            Some(&json!({ "max_traders": 10, "max_ai_spend_usd": 25.0 })),
+        )
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["quota"]["max_traders"], 10);
+    assert_eq!(body["ai_spend_usd"], 0.0);
+    assert_eq!(
+        quota::for_user(&db, "user-1").await.unwrap().max_traders,
+        10
+    );
+    assert_eq!(quota::for_user(&db, "user-2").await.unwrap().max_traders, 3);
+
+    let (status, body) = client
+        .request(Method::DELETE, "/api/admin/quotas/user-1", None)
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::OK);
+    assert!(body["override"].is_null());
+    assert_eq!(body["quota"]["max_traders"], 3);
+}
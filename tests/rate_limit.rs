@@ -0,0 +1,156 @@
+//! Per-host request weights, 429/5xx backoff and bans.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use aitrading::api_client::{ApiClient, EndpointClass, client_for};
+use aitrading::rate_limit::{self, RateLimitError, RateLimits, SendLimited};
+use axum::Router;
+use axum::http::{HeaderMap, StatusCode};
+use axum::routing::any;
+use reqwest::Url;
+
+/// A server that answers the first `failures` requests with `status` (and
+/// `Retry-After: 0`, except for bans) and the rest with `[]`, reporting
+/// `used_weight`. Returns its address and request counter.
+async fn stub(status: StatusCode, failures: usize, used_weight: u32) -> (String, Arc<AtomicUsize>) {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let counter = calls.clone();
+    let app = Router::new().fallback(any(move || {
+        let counter = counter.clone();
+        async move {
+            let mut headers = HeaderMap::new();
+            headers.insert("x-mbx-used-weight-1m", used_weight.into());
+            if counter.fetch_add(1, Ordering::SeqCst) < failures {
+                if status != StatusCode::IM_A_TEAPOT {
+                    headers.insert("retry-after", 0.into());
+                }
+                (status, headers, "{}")
+            } else {
+                (StatusCode::OK, headers, "[]")
+            }
+        }
+    }));
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    (addr.to_string(), calls)
+}
+
+fn get(host: &str, path: &str) -> reqwest::RequestBuilder {
+    client_for(EndpointClass::MarketData).get(format!("http://{}{}", host, path))
+}
+
+#[test]
+fn endpoint_weights_follow_binance() {
+    let weight = |url: &str| rate_limit::weight(&Url::parse(url).unwrap());
+    assert_eq!(
+        weight("https://fapi.binance.com/fapi/v1/klines?symbol=BTCUSDT&limit=99"),
+        1
+    );
+    assert_eq!(
+        weight("https://fapi.binance.com/fapi/v1/klines?symbol=BTCUSDT&limit=120"),
+        2
+    );
+    assert_eq!(
+        weight("https://fapi.binance.com/fapi/v1/klines?symbol=BTCUSDT"),
+        5
+    );
+    assert_eq!(
+        weight("https://fapi.binance.com/fapi/v1/klines?limit=1500"),
+        10
+    );
+    assert_eq!(weight("https://fapi.binance.com/fapi/v1/ticker/24hr"), 40);
+    assert_eq!(
+        weight("https://fapi.binance.com/fapi/v1/ticker/24hr?symbol=BTCUSDT"),
+        1
+    );
+    assert_eq!(
+        weight("https://fapi.binance.com/fapi/v1/openOrders?timestamp=1"),
+        40
+    );
+    assert_eq!(weight("https://fapi.binance.com/fapi/v1/premiumIndex"), 10);
+    assert_eq!(weight("https://fapi.binance.com/fapi/v2/positionRisk"), 5);
+    assert_eq!(weight("https://fapi.binance.com/fapi/v1/income"), 30);
+    assert_eq!(weight("https://fapi.binance.com/fapi/v1/order"), 1);
+}
+
+// One test owns the process-wide limits.
+#[tokio::test]
+async fn budgets_backoff_and_bans() {
+    let (limited, limited_calls) = stub(StatusCode::TOO_MANY_REQUESTS, 2, 0).await;
+    let (flaky, flaky_calls) = stub(StatusCode::SERVICE_UNAVAILABLE, 1, 0).await;
+    let (banned, banned_calls) = stub(StatusCode::IM_A_TEAPOT, 1, 0).await;
+    let (busy, busy_calls) = stub(StatusCode::OK, 0, 0).await;
+    let (shared, _) = stub(StatusCode::OK, 0, 900).await;
+    let mut limits = RateLimits {
+        base_delay: Duration::from_millis(10),
+        max_wait: Duration::from_millis(500),
+        ..Default::default()
+    };
+    limits.weight_per_minute.insert(busy.clone(), 3);
+    rate_limit::set_limits(limits);
+
+    // 429s are retried, here through the Binance client.
+    let client = ApiClient::new().with_base_url(&format!("http://{}", limited));
+    assert!(
+        client
+            .get_klines("BTCUSDT", "3m", 2)
+            .await
+            .unwrap()
+            .is_empty()
+    );
+    assert_eq!(limited_calls.load(Ordering::SeqCst), 3);
+
+    // 5xx is retried for reads but not for writes, which may have gone through.
+    let resp = get(&flaky, "/fapi/v1/time").send_limited().await.unwrap();
+    assert_eq!(resp.status().as_u16(), StatusCode::OK.as_u16());
+    assert_eq!(flaky_calls.load(Ordering::SeqCst), 2);
+    let (flaky, flaky_calls) = stub(StatusCode::SERVICE_UNAVAILABLE, 1, 0).await;
+    let resp = client_for(EndpointClass::Trading)
+        .post(format!("http://{}/fapi/v1/order", flaky))
+        .send_limited()
+        .await
+        .unwrap();
+    assert_eq!(
+        resp.status().as_u16(),
+        StatusCode::SERVICE_UNAVAILABLE.as_u16()
+    );
+    assert_eq!(flaky_calls.load(Ordering::SeqCst), 1);
+
+    // A ban stops further requests to the host without sending them.
+    let resp = get(&banned, "/fapi/v1/time").send_limited().await.unwrap();
+    assert_eq!(resp.status().as_u16(), StatusCode::IM_A_TEAPOT.as_u16());
+    let err = get(&banned, "/fapi/v1/time")
+        .send_limited()
+        .await
+        .unwrap_err();
+    assert!(matches!(err, RateLimitError::Blocked { ref host, .. } if *host == banned));
+    assert_eq!(banned_calls.load(Ordering::SeqCst), 1);
+
+    // Once the minute's weight is spent, requests wait for the next minute,
+    // failing when that is longer than max_wait.
+    get(&busy, "/fapi/v1/klines?limit=120")
+        .send_limited()
+        .await
+        .unwrap();
+    get(&busy, "/fapi/v1/time").send_limited().await.unwrap();
+    assert_eq!(rate_limit::used_weight(&busy), 3);
+    let until_next_minute = 60_000 - chrono::Utc::now().timestamp_millis() % 60_000;
+    if until_next_minute > 1_000 {
+        let err = get(&busy, "/fapi/v1/time")
+            .send_limited()
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("Rate limited"), "{}", err);
+        assert_eq!(busy_calls.load(Ordering::SeqCst), 2);
+    }
+
+    // The exchange's own count wins when other clients share the IP.
+    get(&shared, "/fapi/v1/time").send_limited().await.unwrap();
+    assert!(rate_limit::used_weight(&shared) >= 900);
+
+    rate_limit::reset();
+    rate_limit::set_limits(RateLimits::default());
+}
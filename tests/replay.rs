@@ -0,0 +1,179 @@
+//! Replaying stored decisions with the current prompt template.
+
+use aitrading::database::TraderRecord;
+use aitrading::decision::{AccountInfo, Action, Context, Decision};
+use aitrading::logger::{self, DecisionLogger, DecisionRecord};
+use aitrading::prompt;
+use aitrading::replay;
+use aitrading::server::{AppState, EmbeddedClient};
+use aitrading::testkit::{self, MockAiProvider};
+use axum::http::{Method, StatusCode};
+use serde_json::json;
+use uuid::Uuid;
+
+fn trader() -> TraderRecord {
+    TraderRecord {
+        id: "t1".to_string(),
+        user_id: "admin".to_string(),
+        name: "replayed".to_string(),
+        ai_model_id: "deepseek".to_string(),
+        exchange_id: "binance".to_string(),
+        initial_balance: 1_000.0,
+        btc_eth_leverage: 5,
+        altcoin_leverage: 3,
+        ..Default::default()
+    }
+}
+
+fn open_long(symbol: &str, size: f64) -> Decision {
+    Decision {
+        leverage: 5,
+        position_size_usd: size,
+        ..Decision::new(symbol, Action::OpenLong)
+    }
+}
+
+// A logged cycle on BTC and ETH whose system prompt came from the default
+// template.
+fn record(trader: &TraderRecord, decisions: &[Decision]) -> DecisionRecord {
+    let account = AccountInfo {
+        total_equity: 1_000.0,
+        available_balance: 1_000.0,
+        ..Default::default()
+    };
+    let ctx = Context {
+        account: account.clone(),
+        candidate_coins: vec!["BTCUSDT".to_string(), "ETHUSDT".to_string()],
+        btc_eth_leverage: trader.btc_eth_leverage,
+        altcoin_leverage: trader.altcoin_leverage,
+        ..Default::default()
+    };
+    let template = prompt::builtin_template(prompt::DEFAULT_TEMPLATE).unwrap();
+    let mut record = DecisionRecord::new(
+        &prompt::system_prompt(trader, template, &ctx),
+        "Time: then",
+        "",
+        &serde_json::to_string(decisions).unwrap(),
+    );
+    record.set_account(&account, &[]);
+    record.set_candidate_coins(ctx.candidate_coins.clone());
+    record
+}
+
+#[test]
+fn diff_reports_added_removed_and_changed_decisions() {
+    let original = [open_long("BTCUSDT", 500.0), open_long("ETHUSDT", 300.0)];
+    let replayed = [
+        open_long("BTCUSDT", 500.0),
+        Decision {
+            reasoning: "different words".to_string(),
+            ..open_long("ETHUSDT", 200.0)
+        },
+        Decision::new("SOLUSDT", Action::OpenShort),
+    ];
+    let diff = replay::diff(&original, &replayed);
+    assert_eq!(diff.len(), 2);
+    assert_eq!(diff[0].symbol, "ETHUSDT");
+    assert_eq!(diff[0].changed, vec!["position_size_usd"]);
+    assert_eq!(diff[1].symbol, "SOLUSDT");
+    assert!(diff[1].original.is_none());
+    assert!(diff[1].changed.is_empty());
+
+    let removed = replay::diff(&original, &original[..1]);
+    assert_eq!(removed.len(), 1);
+    assert_eq!(removed[0].symbol, "ETHUSDT");
+    assert!(removed[0].replayed.is_none());
+    assert!(replay::diff(&original, &original).is_empty());
+}
+
+#[tokio::test]
+async fn replays_render_the_current_template_and_keep_the_user_prompt() {
+    let mut trader = trader();
+    let record = record(&trader, &[open_long("BTCUSDT", 500.0)]);
+    let default = prompt::builtin_template(prompt::DEFAULT_TEMPLATE).unwrap();
+
+    let mut ai = MockAiProvider::new();
+    ai.push_decisions(&[open_long("BTCUSDT", 500.0)]);
+    let same = replay::run(&mut ai, &trader, default, &record)
+        .await
+        .unwrap();
+    assert!(!same.system_prompt_changed);
+    assert!(same.diff.is_empty());
+    assert_eq!(ai.calls()[0].user_prompt, "Time: then");
+
+    trader.system_prompt_template = "conservative".to_string();
+    let conservative = prompt::builtin_template("conservative").unwrap();
+    ai.push_response("Too risky. [{\"symbol\": \"DOGEUSDT\", \"action\": \"open_long\"}]");
+    let changed = replay::run(&mut ai, &trader, conservative, &record)
+        .await
+        .unwrap();
+    assert!(changed.system_prompt_changed);
+    assert!(changed.error.is_none());
+    assert!(changed.replayed.is_empty());
+    assert_eq!(changed.rejected.len(), 1);
+    assert_eq!(changed.diff.len(), 1);
+    assert!(changed.diff[0].replayed.is_none());
+
+    ai.push_response("no decisions here");
+    let unusable = replay::run(&mut ai, &trader, default, &record)
+        .await
+        .unwrap();
+    assert!(unusable.error.is_some());
+
+    ai.push_error("overloaded");
+    assert!(
+        replay::run(&mut ai, &trader, default, &record)
+            .await
+            .is_err()
+    );
+}
+
+#[tokio::test]
+async fn decisions_are_replayed_over_the_api() {
+    let db = testkit::memory_db().await.unwrap();
+    db.ensure_admin_user().await.unwrap();
+    let trader = trader();
+    db.create_trader(&trader).await.unwrap();
+    let client = EmbeddedClient::new(AppState {
+        db: db.clone(),
+        scheduler: None,
+        cipher: None,
+    })
+    .with_token(testkit::admin_token());
+
+    let log_dir = std::env::temp_dir().join(format!("aitrading-replay-{}", Uuid::new_v4()));
+    let mut logger = DecisionLogger::new(&log_dir.to_string_lossy());
+    let mut record = record(&trader, &[open_long("BTCUSDT", 500.0)]);
+    logger.log_decision(&mut record).unwrap();
+    logger::store_record(&db, "admin", "t1", &record, None)
+        .await
+        .unwrap();
+
+    let canned = serde_json::to_string(&[open_long("ETHUSDT", 250.0)]).unwrap();
+    let (status, replayed) = client
+        .request(
+            Method::POST,
+            &format!("/api/traders/t1/decisions/{}/replay", record.id()),
+            Some(&json!({ "response": canned })),
+        )
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::OK, "{}", replayed);
+    assert_eq!(replayed["record_id"], record.id());
+    assert_eq!(replayed["model"], "canned");
+    assert_eq!(replayed["system_prompt_changed"], false);
+    assert_eq!(replayed["diff"].as_array().unwrap().len(), 2);
+    assert_eq!(replayed["diff"][0]["symbol"], "BTCUSDT");
+    assert_eq!(replayed["diff"][0]["replayed"], json!(null));
+
+    let (status, _) = client
+        .request(
+            Method::POST,
+            "/api/traders/t1/decisions/nope/replay",
+            Some(&json!({ "response": canned })),
+        )
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let _ = std::fs::remove_dir_all(&log_dir);
+}
@@ -0,0 +1,144 @@
+//! Directives pushed by an external risk system.
+
+use aitrading::decision::{Action, Decision, PairLeg};
+use aitrading::risk_override;
+use aitrading::server::{AppState, EmbeddedClient};
+use aitrading::testkit;
+use axum::http::{Method, StatusCode};
+use chrono::Utc;
+use serde_json::{Value, json};
+
+const SECRET: &str = "risk-secret";
+
+fn signed(state: &AppState, body: &Value, timestamp: i64, secret: &str) -> EmbeddedClient {
+    let timestamp = timestamp.to_string();
+    let signature = risk_override::sign(secret, &timestamp, &serde_json::to_vec(body).unwrap());
+    EmbeddedClient::new(state.clone())
+        .with_header("X-Risk-Timestamp", timestamp)
+        .with_header("X-Risk-Signature", format!("sha256={}", signature))
+}
+
+fn open(symbol: &str, leverage: i32) -> Decision {
+    Decision {
+        leverage,
+        position_size_usd: 100.0,
+        ..Decision::new(symbol, Action::OpenLong)
+    }
+}
+
+#[tokio::test]
+async fn signed_directives_restrict_the_next_cycle() {
+    let db = testkit::memory_db().await.unwrap();
+    db.ensure_admin_user().await.unwrap();
+    let state = AppState {
+        db: db.clone(),
+        scheduler: None,
+        cipher: None,
+    };
+    let body = json!({ "directives": [
+        { "action": "cap_leverage", "user_id": "admin", "max_leverage": 3 },
+        { "action": "blacklist_symbol", "user_id": "admin", "symbol": "eth" },
+        { "action": "blacklist_symbol", "symbol": "DOGEUSDT" },
+    ]});
+    let now = Utc::now().timestamp();
+
+    risk_override::set_webhook_secret(None);
+    let (status, _) = signed(&state, &body, now, SECRET)
+        .request(Method::POST, "/api/risk/directives", Some(&body))
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    risk_override::set_webhook_secret(Some(SECRET));
+    for client in [
+        EmbeddedClient::new(state.clone()),
+        signed(&state, &body, now, "wrong secret"),
+        signed(&state, &body, now - 600, SECRET),
+    ] {
+        let (status, _) = client
+            .request(Method::POST, "/api/risk/directives", Some(&body))
+            .await
+            .unwrap();
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+    let unknown = json!({ "directives": [{ "action": "halt_user", "user_id": "nobody" }] });
+    let (status, error) = signed(&state, &unknown, now, SECRET)
+        .request(Method::POST, "/api/risk/directives", Some(&unknown))
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(error["error"].as_str().unwrap().contains("nobody"));
+
+    let (status, overrides) = signed(&state, &body, now, SECRET)
+        .request(Method::POST, "/api/risk/directives", Some(&body))
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(overrides["users"]["admin"]["max_leverage"], 3);
+    assert_eq!(overrides["symbols"], json!(["DOGEUSDT"]));
+    // The same signed request is not accepted twice.
+    let (status, _) = signed(&state, &body, now, SECRET)
+        .request(Method::POST, "/api/risk/directives", Some(&body))
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let (_, mine) = EmbeddedClient::new(state.clone())
+        .with_token(testkit::admin_token())
+        .request(Method::GET, "/api/risk/overrides", None)
+        .await
+        .unwrap();
+    assert_eq!(mine["symbols"], json!(["DOGEUSDT", "ETHUSDT"]));
+
+    let filtered = risk_override::filter(
+        "admin",
+        vec![
+            open("BTCUSDT", 10),
+            open("ETHUSDT", 2),
+            open("DOGEUSDT", 2),
+            // Short leg on a blacklisted symbol.
+            Decision {
+                pair: Some(PairLeg {
+                    symbol: "ETHUSDT".to_string(),
+                    ratio: 1.0,
+                }),
+                ..open("BTCUSDT", 2)
+            },
+            Decision::new("ETHUSDT", Action::CloseLong),
+        ],
+    );
+    assert_eq!(filtered.len(), 2);
+    assert_eq!(filtered[0].leverage, 3);
+    assert_eq!(filtered[1].action, Action::CloseLong);
+    // Other users only see the global blacklist.
+    assert_eq!(
+        risk_override::filter("u2", vec![open("ETHUSDT", 10)]).len(),
+        1
+    );
+    let note = risk_override::prompt_annotation("admin").unwrap();
+    assert!(note.contains("3x") && note.contains("ETHUSDT"));
+
+    // A halt survives a reload from the database and blocks every entry.
+    let halt = json!({ "directives": [{ "action": "halt_user", "user_id": "admin", "reason": "limit breach" }] });
+    let (status, _) = signed(&state, &halt, now, SECRET)
+        .request(Method::POST, "/api/risk/directives", Some(&halt))
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::OK);
+    risk_override::sync(&db).await.unwrap();
+    assert!(risk_override::for_user("admin").halted);
+    assert!(risk_override::filter("admin", vec![open("BTCUSDT", 1)]).is_empty());
+
+    let resume = json!({ "directives": [
+        { "action": "resume_user", "user_id": "admin" },
+        { "action": "cap_leverage", "user_id": "admin", "max_leverage": 0 },
+        { "action": "unblacklist_symbol", "user_id": "admin", "symbol": "ETHUSDT" },
+        { "action": "unblacklist_symbol", "symbol": "DOGEUSDT" },
+    ]});
+    let (_, overrides) = signed(&state, &resume, now, SECRET)
+        .request(Method::POST, "/api/risk/directives", Some(&resume))
+        .await
+        .unwrap();
+    assert_eq!(overrides, json!({ "users": {}, "symbols": [] }));
+    assert!(risk_override::prompt_annotation("admin").is_none());
+}
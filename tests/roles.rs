@@ -0,0 +1,170 @@
+//! Per-request admin checks driven by the role in the user's token.
+
+use aitrading::auth::{self, Role};
+use aitrading::server::{AppState, EmbeddedClient};
+use aitrading::testkit;
+use axum::http::{Method, StatusCode};
+use chrono::Utc;
+use jsonwebtoken::{EncodingKey, Header};
+use serde_json::json;
+
+const SECRET: &str = "roles-test-secret";
+
+#[tokio::test]
+async fn admin_routes_follow_the_token_role() {
+    auth::set_jwt_secret(SECRET);
+    let db = testkit::memory_db().await.unwrap();
+    let client = EmbeddedClient::new(AppState {
+        db: db.clone(),
+        scheduler: None,
+        cipher: None,
+    });
+    let credentials = json!({ "email": "dana@example.com", "password": "correct horse" });
+    let (status, body) = client
+        .request(Method::POST, "/api/register", Some(&credentials))
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::CREATED);
+    let user_id = body["user"]["id"].as_str().unwrap().to_string();
+    assert_eq!(
+        auth::validate_jwt(body["token"].as_str().unwrap())
+            .unwrap()
+            .claims
+            .role,
+        Role::User
+    );
+    let dana = client.clone().with_token(body["token"].as_str().unwrap());
+
+    let config = json!({ "value": "42" });
+    for (method, path, body) in [
+        (Method::GET, "/api/admin/users", None),
+        (Method::GET, "/api/admin/beta-codes", None),
+        (Method::GET, "/api/profile", None),
+        (
+            Method::PUT,
+            "/api/admin/system-config/max_traders",
+            Some(&config),
+        ),
+    ] {
+        let (status, _) = dana.request(method, path, body).await.unwrap();
+        assert_eq!(status, StatusCode::FORBIDDEN, "{}", path);
+    }
+
+    // Promotion takes effect with the next token.
+    db.update_user_role(&user_id, Role::Admin).await.unwrap();
+    let (status, _) = dana
+        .request(Method::GET, "/api/admin/users", None)
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, body) = client
+        .request(Method::POST, "/api/login", Some(&credentials))
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::OK);
+    let dana = client.clone().with_token(body["token"].as_str().unwrap());
+
+    let (status, users) = dana
+        .request(Method::GET, "/api/admin/users", None)
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(users[0]["role"], "admin");
+
+    let codes = json!({ "codes": ["BETA-1", " BETA-2 ", ""] });
+    let (_, inserted) = dana
+        .request(Method::POST, "/api/admin/beta-codes", Some(&codes))
+        .await
+        .unwrap();
+    assert_eq!(inserted["inserted"], 2);
+    let (_, stats) = dana
+        .request(Method::GET, "/api/admin/beta-codes", None)
+        .await
+        .unwrap();
+    assert_eq!(stats, json!({ "total": 2, "used": 0 }));
+
+    let (status, _) = dana
+        .request(
+            Method::PUT,
+            "/api/admin/system-config/max_traders",
+            Some(&config),
+        )
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    assert_eq!(db.get_system_config("max_traders").await.unwrap(), "42");
+    let (status, _) = dana
+        .request(
+            Method::PUT,
+            "/api/admin/system-config/jwt_secret",
+            Some(&config),
+        )
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let demote = json!({ "role": "user" });
+    let (status, _) = dana
+        .request(
+            Method::PUT,
+            &format!("/api/admin/users/{}/role", user_id),
+            Some(&demote),
+        )
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = dana
+        .request(Method::PUT, "/api/admin/users/nobody/role", Some(&demote))
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn tokens_without_a_role_are_plain_users() {
+    auth::set_jwt_secret(SECRET);
+    let now = Utc::now().timestamp();
+    let legacy = jsonwebtoken::encode(
+        &Header::default(),
+        &json!({
+            "user_id": "u1",
+            "email": "old@example.com",
+            "exp": now + 3600,
+            "iat": now,
+            "nbf": now,
+            "iss": "AITrading",
+        }),
+        &EncodingKey::from_secret(SECRET.as_bytes()),
+    )
+    .unwrap();
+    let claims = auth::validate_jwt(&legacy).unwrap().claims;
+    assert_eq!(claims.user_id, "u1");
+    assert_eq!(claims.role, Role::User);
+}
+
+#[tokio::test]
+async fn a_fresh_install_requires_a_token() {
+    auth::set_jwt_secret(SECRET);
+    let db = testkit::memory_db().await.unwrap();
+    db.ensure_admin_user().await.unwrap();
+    assert_eq!(db.get_system_config("admin_mode").await.unwrap(), "false");
+    let anonymous = EmbeddedClient::new(AppState {
+        db: db.clone(),
+        scheduler: None,
+        cipher: None,
+    });
+
+    let config = json!({ "value": "42" });
+    for (method, path, body) in [
+        (Method::GET, "/api/admin/users", None),
+        (
+            Method::PUT,
+            "/api/admin/system-config/max_daily_loss",
+            Some(&config),
+        ),
+        (Method::GET, "/api/traders", None),
+    ] {
+        let (status, _) = anonymous.request(method, path, body).await.unwrap();
+        assert_eq!(status, StatusCode::UNAUTHORIZED, "{}", path);
+    }
+}
@@ -0,0 +1,608 @@
+//! Live trader tasks against the mock exchange and mock AI.
+
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use aitrading::ai::{AiFuture, AiProvider};
+use aitrading::database::{Database, Trade, TraderRecord};
+use aitrading::decision::{Action, Decision};
+use aitrading::logger::DecisionLogger;
+use aitrading::money::Decimal;
+use aitrading::notify::{self, Event, Notifier, NotifyFuture};
+use aitrading::recovery;
+use aitrading::runner::{CycleAlignment, Runner, RunnerConfig, TraderCycle};
+use aitrading::scheduler::Schedule;
+use aitrading::testkit::{self, MockAiProvider, MockExchange};
+use aitrading::types::Kline;
+use aitrading::{cooldown, data, margin_governor};
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+struct Setup {
+    db: Database,
+    trader: TraderRecord,
+    config: RunnerConfig,
+    log_dir: PathBuf,
+}
+
+impl Drop for Setup {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.log_dir);
+    }
+}
+
+async fn setup() -> Setup {
+    let db = testkit::memory_db().await.unwrap();
+    let trader = TraderRecord {
+        id: format!("trader-{}", Uuid::new_v4()),
+        user_id: format!("user-{}", Uuid::new_v4()),
+        name: "runner".to_string(),
+        ai_model_id: "deepseek".to_string(),
+        exchange_id: "binance".to_string(),
+        initial_balance: 1000.0,
+        scan_interval_minutes: 3,
+        is_running: true,
+        btc_eth_leverage: 5,
+        altcoin_leverage: 5,
+        trading_symbols: "BTCUSDT".to_string(),
+        ..Default::default()
+    };
+    db.create_trader(&trader).await.unwrap();
+    let log_dir = std::env::temp_dir().join(format!("aitrading-runner-{}", Uuid::new_v4()));
+    let config = RunnerConfig {
+        log_dir: log_dir.to_string_lossy().to_string(),
+        ..Default::default()
+    };
+    Setup {
+        db,
+        trader,
+        config,
+        log_dir,
+    }
+}
+
+fn cycle(s: &Setup, ai: MockAiProvider) -> TraderCycle<MockExchange> {
+    let mut exchange = MockExchange::new(1000.0);
+    exchange.set_price("BTCUSDT", 100.0);
+    TraderCycle::new(
+        s.db.clone(),
+        s.trader.clone(),
+        exchange,
+        Box::new(ai),
+        s.config.logger(&s.trader),
+        &s.config,
+    )
+}
+
+fn logger(s: &Setup) -> DecisionLogger {
+    DecisionLogger::new(&s.log_dir.join(&s.trader.id).to_string_lossy())
+}
+
+#[tokio::test]
+async fn cycle_executes_and_logs_decisions() {
+    let s = setup().await;
+    let mut ai = MockAiProvider::new();
+    ai.push_decisions(&[Decision {
+        leverage: 5,
+        position_size_usd: 500.0,
+        stop_loss: 95.0,
+        ..Decision::new("BTCUSDT", Action::OpenLong)
+    }]);
+    let mut cycle = cycle(&s, ai);
+
+    let record = cycle.run_cycle().await.unwrap();
+    assert!(record.is_success(), "{}", record.error_message());
+    assert_eq!(record.cycle_number(), 1);
+    let json = serde_json::to_value(&record).unwrap();
+    assert_eq!(json["decisions"].as_array().unwrap().len(), 1);
+    assert_eq!(json["decisions"][0]["success"], true);
+    assert_eq!(json["positions"].as_array().unwrap().len(), 0);
+
+    // The next cycle sees the position it opened.
+    let record = cycle.run_cycle().await.unwrap();
+    let json = serde_json::to_value(&record).unwrap();
+    assert_eq!(json["positions"][0]["symbol"], "BTCUSDT");
+    assert_eq!(logger(&s).get_latest_records(10).unwrap().len(), 2);
+
+    // Fills and equity snapshots land in the database.
+    let trades =
+        s.db.get_trades(&s.trader.user_id, &s.trader.id, None)
+            .await
+            .unwrap();
+    assert_eq!(trades.len(), 1);
+    assert_eq!(trades[0].side, "buy");
+    assert_eq!(trades[0].action, "open_long");
+    assert_eq!(trades[0].quantity, Decimal::from(5));
+    assert_eq!(trades[0].fee, Decimal::new(25, 2));
+    // The price the cycle decided on is kept for slippage analysis.
+    assert_eq!(trades[0].expected_price, Decimal::ONE_HUNDRED);
+    let snapshots =
+        s.db.get_pnl_snapshots(&s.trader.user_id, &s.trader.id, None)
+            .await
+            .unwrap();
+    assert_eq!(snapshots.len(), 2);
+    assert_eq!(snapshots[1].position_count, 1);
+    let later =
+        s.db.get_trades(
+            &s.trader.user_id,
+            &s.trader.id,
+            Some(trades[0].executed_at + chrono::Duration::seconds(1)),
+        )
+        .await
+        .unwrap();
+    assert!(later.is_empty());
+}
+
+#[tokio::test]
+async fn entries_wait_for_indicators_to_warm_up() {
+    let candles: Vec<Kline> = (0..60)
+        .map(|i| Kline {
+            open_time: i * 60_000,
+            open: 100.0,
+            high: 101.0 + i as f64,
+            low: 99.0,
+            close: 100.0 + i as f64,
+            volume: 1.0,
+            close_time: i * 60_000 + 59_999,
+            quote_volume: 100.0,
+            trades: 1,
+            taker_buy_base_volume: 0.5,
+            taker_buy_quote_volume: 50.0,
+        })
+        .collect();
+    // EMA50 is the last indicator to get a value.
+    assert!(!data::is_seeded(&candles[..49]));
+    assert!(data::is_seeded(&candles[..50]));
+
+    let s = setup().await;
+    let mut ai = MockAiProvider::new();
+    ai.push_decisions(&[Decision {
+        leverage: 5,
+        position_size_usd: 500.0,
+        ..Decision::new("BTCUSDT", Action::OpenLong)
+    }]);
+    let mut exchange = MockExchange::new(1000.0);
+    exchange.set_price("BTCUSDT", 100.0);
+    exchange.set_warming_up("BTCUSDT", true);
+    let mut cycle = TraderCycle::new(
+        s.db.clone(),
+        s.trader.clone(),
+        exchange,
+        Box::new(ai),
+        s.config.logger(&s.trader),
+        &s.config,
+    );
+
+    let record = cycle.run_cycle().await.unwrap();
+    let json = serde_json::to_value(&record).unwrap();
+    let log = json["execution_log"].to_string();
+    assert!(log.contains("BTCUSDT 指标预热中"), "{}", log);
+    assert!(
+        record
+            .error_message()
+            .contains("indicators for BTCUSDT are still warming up")
+    );
+    let trades =
+        s.db.get_trades(&s.trader.user_id, &s.trader.id, None)
+            .await
+            .unwrap();
+    assert!(trades.is_empty());
+}
+
+#[tokio::test]
+async fn symbols_without_market_data_are_left_out() {
+    let mut s = setup().await;
+    s.trader.trading_symbols = "BTCUSDT,ethusdt,SOLUSDT".to_string();
+    let mut exchange = MockExchange::new(1000.0);
+    exchange.set_price("BTCUSDT", 100.0);
+    exchange.set_price("SOLUSDT", 20.0);
+    let mut cycle = TraderCycle::new(
+        s.db.clone(),
+        s.trader.clone(),
+        exchange,
+        Box::new(MockAiProvider::new()),
+        s.config.logger(&s.trader),
+        &s.config,
+    );
+
+    // The first cycle warms symbols up one by one, later ones fetch them together.
+    for _ in 0..2 {
+        let record = cycle.run_cycle().await.unwrap();
+        let log = serde_json::to_value(&record).unwrap()["execution_log"].to_string();
+        assert!(log.contains("ETHUSDT 市场数据获取失败"), "{}", log);
+        assert!(!log.contains("BTCUSDT 市场数据获取失败"), "{}", log);
+        assert!(!log.contains("SOLUSDT 市场数据获取失败"), "{}", log);
+    }
+    assert!(data::get_many(&[]).await.is_empty());
+}
+
+#[tokio::test]
+async fn runner_stops_traders_after_their_cycle() {
+    let s = setup().await;
+    let mut runner = Runner::new(s.db.clone(), s.config.clone());
+    runner.launch(cycle(&s, MockAiProvider::new())).await;
+    let handle = runner.spawn();
+    assert_eq!(handle.running().await.unwrap(), vec![s.trader.id.clone()]);
+
+    // The first tick runs a cycle straight away.
+    let mut waited = Duration::ZERO;
+    while logger(&s)
+        .get_latest_records(1)
+        .map_or(true, |r| r.is_empty())
+    {
+        assert!(waited < Duration::from_secs(5), "no cycle ran");
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        waited += Duration::from_millis(20);
+    }
+
+    assert_eq!(margin_governor::usage(&s.trader.user_id).traders, 1);
+
+    handle.stop(&s.trader.user_id, &s.trader.id).await.unwrap();
+    assert!(handle.running().await.unwrap().is_empty());
+    // A stopped trader's margin no longer counts for its siblings.
+    assert_eq!(margin_governor::usage(&s.trader.user_id).traders, 0);
+    let trader =
+        s.db.get_trader(&s.trader.user_id, &s.trader.id)
+            .await
+            .unwrap()
+            .unwrap();
+    assert!(!trader.is_running);
+
+    handle.shutdown().await.unwrap();
+    assert!(handle.running().await.is_err());
+}
+
+/// A model that never answers.
+struct Silent;
+
+impl AiProvider for Silent {
+    fn name(&self) -> String {
+        "silent".to_string()
+    }
+
+    fn chat_completion<'a>(&'a mut self, _: &'a str, _: &'a str) -> AiFuture<'a, String> {
+        Box::pin(std::future::pending())
+    }
+}
+
+#[tokio::test]
+async fn shutdown_cuts_short_cycles_waiting_for_the_ai() {
+    let s = setup().await;
+    let mut exchange = MockExchange::new(1000.0);
+    exchange.set_price("BTCUSDT", 100.0);
+    let cycle = TraderCycle::new(
+        s.db.clone(),
+        s.trader.clone(),
+        exchange,
+        Box::new(Silent),
+        s.config.logger(&s.trader),
+        &s.config,
+    );
+    let mut runner = Runner::new(s.db.clone(), s.config.clone());
+    runner.launch(cycle).await;
+    let handle = runner.spawn();
+    // The equity snapshot is saved just before the AI is asked.
+    let mut waited = Duration::ZERO;
+    while s
+        .db
+        .get_pnl_snapshots(&s.trader.user_id, &s.trader.id, None)
+        .await
+        .unwrap()
+        .is_empty()
+    {
+        assert!(waited < Duration::from_secs(5), "no cycle started");
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        waited += Duration::from_millis(20);
+    }
+    s.db.update_trader_status(&s.trader.user_id, &s.trader.id, false)
+        .await
+        .unwrap();
+
+    tokio::time::timeout(Duration::from_secs(5), handle.shutdown())
+        .await
+        .expect("shutdown waited for the AI")
+        .unwrap();
+    let records = logger(&s).get_latest_records(10).unwrap();
+    assert_eq!(records.len(), 1);
+    assert!(!records[0].is_success());
+    assert_eq!(records[0].error_message(), "cycle interrupted by shutdown");
+    // The trader resumes with the process.
+    let trader =
+        s.db.get_trader(&s.trader.user_id, &s.trader.id)
+            .await
+            .unwrap()
+            .unwrap();
+    assert!(trader.is_running);
+}
+
+#[tokio::test]
+async fn start_reconciles_recorded_fills_with_the_exchange() {
+    let s = setup().await;
+    let fill = |symbol: &str, action: &str, quantity: i64| Trade {
+        user_id: s.trader.user_id.clone(),
+        trader_id: s.trader.id.clone(),
+        symbol: symbol.to_string(),
+        side: "buy".to_string(),
+        action: action.to_string(),
+        quantity: Decimal::from(quantity),
+        price: Decimal::ONE_HUNDRED,
+        executed_at: Utc::now() - chrono::Duration::hours(1),
+        ..Default::default()
+    };
+    // Closed on the exchange while the bot was down.
+    s.db.record_trade(&fill("BTCUSDT", "open_long", 5))
+        .await
+        .unwrap();
+    // Partly closed while down: 7 recorded, 3 still open.
+    s.db.record_trade(&fill("SOLUSDT", "open_short", 7))
+        .await
+        .unwrap();
+    let mut exchange = MockExchange::new(10_000.0);
+    exchange.set_price("BTCUSDT", 110.0);
+    exchange.set_price("ETHUSDT", 2000.0);
+    exchange.set_price("SOLUSDT", 100.0);
+    // Filled just before a crash, never recorded.
+    exchange.open("ETHUSDT", "long", 0.5, 5).unwrap();
+    exchange.open("SOLUSDT", "short", 3.0, 5).unwrap();
+    let mut cycle = TraderCycle::new(
+        s.db.clone(),
+        s.trader.clone(),
+        exchange,
+        Box::new(MockAiProvider::new()),
+        s.config.logger(&s.trader),
+        &s.config,
+    );
+
+    let events = cycle.reconcile().await.unwrap();
+    let found: Vec<_> = events
+        .iter()
+        .map(|e| {
+            (
+                e.symbol.as_str(),
+                e.side.as_str(),
+                e.recorded_quantity,
+                e.exchange_quantity,
+            )
+        })
+        .collect();
+    assert_eq!(
+        found,
+        [
+            ("BTCUSDT", "long", Decimal::from(5), Decimal::ZERO),
+            ("ETHUSDT", "long", Decimal::ZERO, Decimal::new(5, 1)),
+            ("SOLUSDT", "short", Decimal::from(7), Decimal::from(3)),
+        ]
+    );
+    let trades =
+        s.db.get_trades(&s.trader.user_id, &s.trader.id, None)
+            .await
+            .unwrap();
+    assert_eq!(trades.len(), 5);
+    let corrections: Vec<_> = events
+        .iter()
+        .map(|e| trades.iter().find(|t| t.id == e.trade_id).unwrap())
+        .collect();
+    let summary: Vec<_> = corrections
+        .iter()
+        .map(|t| (t.symbol.as_str(), t.action.as_str(), t.quantity, t.price))
+        .collect();
+    assert_eq!(
+        summary,
+        [
+            (
+                "BTCUSDT",
+                "close_long",
+                Decimal::from(5),
+                Decimal::from(110)
+            ),
+            (
+                "ETHUSDT",
+                "open_long",
+                Decimal::new(5, 1),
+                Decimal::from(2000)
+            ),
+            (
+                "SOLUSDT",
+                "close_short",
+                Decimal::from(4),
+                Decimal::ONE_HUNDRED
+            ),
+        ]
+    );
+    assert!(
+        corrections
+            .iter()
+            .all(|t| t.group_id == recovery::RECONCILIATION_GROUP)
+    );
+    let ledger = recovery::ledger_positions(&trades);
+    assert_eq!(ledger.len(), 2);
+    let stored =
+        s.db.get_reconciliation_events(&s.trader.user_id, &s.trader.id, 10)
+            .await
+            .unwrap();
+    assert_eq!(stored.len(), 3);
+    // Once in line, nothing more is written.
+    assert!(cycle.reconcile().await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn stop_loss_exits_start_a_cooldown() {
+    let mut s = setup().await;
+    s.trader.stop_loss_cooldown_minutes = 30;
+    let open = Decision {
+        leverage: 5,
+        position_size_usd: 500.0,
+        stop_loss: 95.0,
+        ..Decision::new("BTCUSDT", Action::OpenLong)
+    };
+    let mut ai = MockAiProvider::new();
+    ai.push_decisions(&[open.clone(), open]);
+    let mut exchange = MockExchange::new(1000.0);
+    exchange.set_price("BTCUSDT", 100.0);
+    let mut cycle = TraderCycle::new(
+        s.db.clone(),
+        s.trader.clone(),
+        exchange.clone(),
+        Box::new(ai),
+        s.config.logger(&s.trader),
+        &s.config,
+    );
+    cycle.run_cycle().await.unwrap();
+    assert_eq!(exchange.positions().len(), 1);
+
+    // The exchange's stop fills while the trader waits for its next cycle.
+    exchange.script_prices("BTCUSDT", [94.0]);
+    assert!(exchange.advance()[0].stop_loss_hit);
+    assert!(cooldown::cooldown_until(&s.trader.id, "BTCUSDT", 30).is_none());
+    cycle.reconcile().await.unwrap();
+    assert!(cooldown::cooldown_until(&s.trader.id, "BTCUSDT", 30).is_some());
+
+    // The AI tries to re-enter right away; the cycle refuses and says why.
+    let record = cycle.run_cycle().await.unwrap();
+    let json = serde_json::to_value(&record).unwrap();
+    assert!(json["decisions"].as_array().unwrap().is_empty());
+    assert!(
+        json["input_prompt"]
+            .as_str()
+            .unwrap()
+            .contains("Stop-loss cooldown")
+    );
+    assert!(exchange.positions().is_empty());
+}
+
+#[tokio::test]
+async fn closes_above_the_stop_start_no_cooldown() {
+    let mut s = setup().await;
+    s.trader.stop_loss_cooldown_minutes = 30;
+    let mut ai = MockAiProvider::new();
+    ai.push_decisions(&[Decision {
+        leverage: 5,
+        position_size_usd: 500.0,
+        stop_loss: 95.0,
+        ..Decision::new("BTCUSDT", Action::OpenLong)
+    }]);
+    let mut exchange = MockExchange::new(1000.0);
+    exchange.set_price("BTCUSDT", 100.0);
+    let mut cycle = TraderCycle::new(
+        s.db.clone(),
+        s.trader.clone(),
+        exchange.clone(),
+        Box::new(ai),
+        s.config.logger(&s.trader),
+        &s.config,
+    );
+    cycle.run_cycle().await.unwrap();
+
+    // Closed by hand at a profit.
+    exchange.set_price("BTCUSDT", 104.0);
+    exchange.close_side("BTCUSDT", "long", 0.0).unwrap();
+    assert_eq!(cycle.reconcile().await.unwrap().len(), 1);
+    assert!(cooldown::cooldown_until(&s.trader.id, "BTCUSDT", 30).is_none());
+}
+
+#[test]
+fn aligned_cycles_run_just_after_candle_closes() {
+    let at = |s: &str| s.parse::<DateTime<Utc>>().unwrap();
+    let alignment = CycleAlignment {
+        enabled: true,
+        delay: Duration::from_secs(5),
+    };
+    let schedule = alignment.schedule(Duration::from_secs(180));
+    let next = |after: &str| schedule.next_after(at(after), None).unwrap();
+    assert_eq!(next("2024-05-01T10:01:30Z"), at("2024-05-01T10:03:05Z"));
+    // Still inside the buffer after a close: that close is next.
+    assert_eq!(next("2024-05-01T10:03:02Z"), at("2024-05-01T10:03:05Z"));
+    assert_eq!(next("2024-05-01T10:03:05Z"), at("2024-05-01T10:06:05Z"));
+    assert_eq!(next("2024-05-01T23:59:10Z"), at("2024-05-02T00:00:05Z"));
+
+    let parsed: Schedule = "@aligned 1h +30s".parse().unwrap();
+    assert_eq!(
+        parsed.next_after(at("2024-05-01T10:20:00Z"), None),
+        Some(at("2024-05-01T11:00:30Z"))
+    );
+    assert!("@aligned 0s".parse::<Schedule>().is_err());
+
+    // Disabled keeps the plain interval.
+    let plain = CycleAlignment::default().schedule(Duration::from_secs(180));
+    assert_eq!(
+        plain.next_after(at("2024-05-01T10:01:30Z"), None),
+        Some(at("2024-05-01T10:04:30Z"))
+    );
+}
+
+/// Collects one trader's `trader_recovered` events.
+struct Collector {
+    trader_id: String,
+    events: Arc<Mutex<Vec<Event>>>,
+}
+
+impl Notifier for Collector {
+    fn name(&self) -> &str {
+        "collector"
+    }
+
+    fn send<'a>(&'a self, event: &'a Event) -> NotifyFuture<'a> {
+        if event.name == "trader_recovered" && event.trader_id.as_deref() == Some(&self.trader_id) {
+            self.events.lock().unwrap().push(event.clone());
+        }
+        Box::pin(async { Ok(()) })
+    }
+}
+
+#[tokio::test]
+async fn recovery_rearms_stops_and_reports() {
+    let s = setup().await;
+    let events = Arc::new(Mutex::new(Vec::new()));
+    notify::register(Arc::new(Collector {
+        trader_id: s.trader.id.clone(),
+        events: events.clone(),
+    }));
+    let mut ai = MockAiProvider::new();
+    ai.push_decisions(&[Decision {
+        leverage: 5,
+        position_size_usd: 500.0,
+        stop_loss: 95.0,
+        ..Decision::new("BTCUSDT", Action::OpenLong)
+    }]);
+    let mut exchange = MockExchange::new(1000.0);
+    exchange.set_price("BTCUSDT", 100.0);
+    let mut cycle = TraderCycle::new(
+        s.db.clone(),
+        s.trader.clone(),
+        exchange.clone(),
+        Box::new(ai),
+        s.config.logger(&s.trader),
+        &s.config,
+    );
+    cycle.run_cycle().await.unwrap();
+
+    // The process dies and the exchange loses the position's stop.
+    drop(cycle);
+    exchange.protect("BTCUSDT", 0.0, 0.0);
+    let mut cycle = TraderCycle::new(
+        s.db.clone(),
+        s.trader.clone(),
+        exchange.clone(),
+        Box::new(MockAiProvider::new()),
+        s.config.logger(&s.trader),
+        &s.config,
+    );
+    let recovered = cycle.recover().await.unwrap();
+    assert_eq!(recovered.cycle_number, 1);
+    assert_eq!(recovered.protective.len(), 1);
+    assert_eq!(recovered.protective[0].stop_loss, Some(95.0));
+
+    exchange.script_prices("BTCUSDT", [94.0]);
+    assert!(exchange.advance()[0].stop_loss_hit);
+
+    for _ in 0..50 {
+        if !events.lock().unwrap().is_empty() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    let events = events.lock().unwrap();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].details["rearmed"], 1);
+}
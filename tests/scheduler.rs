@@ -0,0 +1,44 @@
+//! Background job scheduling.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use aitrading::scheduler::Scheduler;
+use aitrading::testkit;
+
+#[tokio::test]
+async fn a_panicking_job_can_run_again() {
+    let db = testkit::memory_db().await.unwrap();
+    let scheduler = Scheduler::new(db);
+    let runs = Arc::new(AtomicUsize::new(0));
+    let counter = runs.clone();
+    scheduler
+        .register("panics", "@every 1h", Duration::ZERO, move || {
+            let counter = counter.clone();
+            async move {
+                counter.fetch_add(1, Ordering::SeqCst);
+                panic!("handler bug");
+            }
+        })
+        .await
+        .unwrap();
+
+    let idle = || async {
+        for _ in 0..100 {
+            if runs.load(Ordering::SeqCst) > 0 && !scheduler.status()[0].running {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        panic!("job still marked as running");
+    };
+    // The first run may already be underway from the schedule.
+    let _ = scheduler.run_now("panics");
+    idle().await;
+    let before = runs.load(Ordering::SeqCst);
+
+    scheduler.run_now("panics").unwrap();
+    idle().await;
+    assert!(runs.load(Ordering::SeqCst) > before);
+}
@@ -0,0 +1,215 @@
+//! Secret references resolved from Vault, AWS Secrets Manager and custom stores.
+
+use std::sync::{Arc, Mutex};
+
+use aitrading::config::{self, ConfigFormat};
+use aitrading::database::ExchangeConfig;
+use aitrading::exchange;
+use aitrading::secrets::{
+    self, AwsCredentials, AwsSecretsManager, SecretError, SecretFuture, SecretProvider, SecretRef,
+    VaultProvider,
+};
+use serde_json::json;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+/// A store that records request heads and bodies and answers with `body`.
+async fn store_stub(body: String) -> (String, Arc<Mutex<Vec<String>>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let log = seen.clone();
+    tokio::spawn(async move {
+        while let Ok((mut socket, _)) = listener.accept().await {
+            let mut request = Vec::new();
+            let mut buf = [0u8; 4096];
+            loop {
+                let text = String::from_utf8_lossy(&request).to_string();
+                if let Some(end) = text.find("\r\n\r\n") {
+                    let length = text[..end]
+                        .lines()
+                        .find_map(|l| {
+                            l.to_lowercase()
+                                .strip_prefix("content-length:")
+                                .map(|v| v.trim().parse::<usize>().unwrap())
+                        })
+                        .unwrap_or(0);
+                    if request.len() >= end + 4 + length {
+                        log.lock().unwrap().push(text);
+                        break;
+                    }
+                }
+                match socket.read(&mut buf).await {
+                    Ok(0) | Err(_) => break,
+                    Ok(n) => request.extend_from_slice(&buf[..n]),
+                }
+            }
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            let _ = socket.write_all(response.as_bytes()).await;
+        }
+    });
+    (format!("http://{}", addr), seen)
+}
+
+/// Answers every reference with its location reversed.
+struct Mirror;
+
+impl SecretProvider for Mirror {
+    fn scheme(&self) -> &str {
+        "mirror"
+    }
+
+    fn fetch<'a>(&'a self, location: &'a str, field: Option<&'a str>) -> SecretFuture<'a> {
+        Box::pin(async move {
+            let value: String = location.chars().rev().collect();
+            Ok(format!("{}{}", value, field.unwrap_or_default()))
+        })
+    }
+}
+
+#[tokio::test]
+async fn references_are_parsed_and_resolved_through_providers() {
+    assert_eq!(
+        SecretRef::parse("vault://secret/data/ait#key")
+            .unwrap()
+            .unwrap(),
+        SecretRef {
+            scheme: "vault".to_string(),
+            location: "secret/data/ait".to_string(),
+            field: Some("key".to_string()),
+        }
+    );
+    assert!(secrets::is_reference("aws-sm://prod/binance"));
+    for raw in ["", "plain-secret", "abc+/def==", "HTTP://upper"] {
+        assert!(!secrets::is_reference(raw), "{raw}");
+        assert_eq!(secrets::resolve(raw).await.unwrap(), raw);
+    }
+    assert!(matches!(
+        secrets::resolve("vault://#key").await,
+        Err(SecretError::InvalidReference(_))
+    ));
+    assert!(matches!(
+        secrets::resolve("nowhere://x").await,
+        Err(SecretError::NoProvider(s)) if s == "nowhere"
+    ));
+
+    secrets::register(Arc::new(Mirror));
+    assert_eq!(secrets::resolve("mirror://abc#!").await.unwrap(), "cba!");
+
+    let toml = r#"
+        [[traders]]
+        id = "t1"
+        name = "Main"
+        enabled = true
+        ai_model = "deepseek"
+        binance_api_key = "mirror://yek"
+        binance_secret_key = "mirror://terces"
+        deepseek_key = "sk-raw"
+        initial_balance = 1000.0
+    "#;
+    let mut config = config::parse_config(toml, ConfigFormat::Toml, Vec::new()).unwrap();
+    secrets::resolve_config(&mut config).await.unwrap();
+    let trader = &config.traders[0];
+    assert_eq!(trader.binance_api_key.as_deref(), Some("key"));
+    assert_eq!(trader.binance_secret_key.as_deref(), Some("secret"));
+    assert_eq!(trader.deepseek_key.as_deref(), Some("sk-raw"));
+
+    config.traders[0].deepseek_key = Some("nowhere://x".to_string());
+    let err = secrets::resolve_config(&mut config).await.unwrap_err();
+    assert!(
+        err.to_string().starts_with("traders[t1].deepseek_key"),
+        "{err}"
+    );
+
+    // Credential checks report a reference that cannot be read.
+    let check = exchange::validate_credentials(&ExchangeConfig {
+        id: "binance".to_string(),
+        api_key: "nowhere://binance#key".to_string(),
+        ..Default::default()
+    })
+    .await;
+    assert!(!check.auth_ok);
+    assert!(check.error.unwrap().contains("nowhere://"));
+}
+
+#[tokio::test]
+async fn vault_reads_kv_fields() {
+    let body = json!({
+        "data": { "data": { "api_key": "k", "secret_key": "s" }, "metadata": { "version": 3 } }
+    });
+    let (addr, seen) = store_stub(body.to_string()).await;
+    secrets::register(Arc::new(
+        VaultProvider::new(&addr, "hvs.token").with_namespace("team"),
+    ));
+
+    let exchange = secrets::resolve_exchange(&ExchangeConfig {
+        id: "binance".to_string(),
+        api_key: "vault://secret/data/ait/binance#api_key".to_string(),
+        secret_key: "vault://secret/data/ait/binance#secret_key".to_string(),
+        ..Default::default()
+    })
+    .await
+    .unwrap();
+    assert_eq!(exchange.api_key, "k");
+    assert_eq!(exchange.secret_key, "s");
+    let head = seen.lock().unwrap()[0].to_lowercase();
+    assert!(
+        head.starts_with("get /v1/secret/data/ait/binance "),
+        "{head}"
+    );
+    assert!(head.contains("x-vault-token: hvs.token"));
+    assert!(head.contains("x-vault-namespace: team"));
+
+    assert!(matches!(
+        secrets::resolve("vault://secret/data/ait/binance#passphrase").await,
+        Err(SecretError::MissingField(_, f)) if f == "passphrase"
+    ));
+    assert!(matches!(
+        secrets::resolve("vault://secret/data/ait/binance").await,
+        Err(SecretError::Ambiguous(_))
+    ));
+}
+
+#[tokio::test]
+async fn aws_secrets_manager_requests_are_signed() {
+    let body = json!({ "Name": "prod/binance", "SecretString": r#"{"secret_key":"s3"}"# });
+    let (addr, seen) = store_stub(body.to_string()).await;
+    let credentials = AwsCredentials {
+        access_key_id: "AKIDEXAMPLE".to_string(),
+        secret_access_key: "wJalrXUtnFEMI".to_string(),
+        session_token: Some("session".to_string()),
+    };
+    secrets::register(Arc::new(
+        AwsSecretsManager::new("eu-west-1", credentials).with_endpoint(&addr),
+    ));
+
+    assert_eq!(
+        secrets::resolve("aws-sm://prod/binance#secret_key")
+            .await
+            .unwrap(),
+        "s3"
+    );
+    assert_eq!(
+        secrets::resolve("aws-sm://prod/binance").await.unwrap(),
+        r#"{"secret_key":"s3"}"#
+    );
+    let request = seen.lock().unwrap()[0].clone();
+    let head = request.to_lowercase();
+    assert!(head.starts_with("post / "), "{head}");
+    assert!(head.contains("x-amz-target: secretsmanager.getsecretvalue"));
+    assert!(head.contains("x-amz-security-token: session"));
+    let auth = request
+        .lines()
+        .find_map(|l| l.strip_prefix("authorization: "))
+        .unwrap();
+    let date = chrono::Utc::now().format("%Y%m%d");
+    assert!(auth.starts_with(&format!(
+        "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/{date}/eu-west-1/secretsmanager/aws4_request, \
+         SignedHeaders=content-type;host;x-amz-date;x-amz-security-token;x-amz-target, Signature="
+    )));
+    assert!(request.ends_with(r#"{"SecretId":"prod/binance"}"#));
+}
@@ -0,0 +1,40 @@
+//! Reproducible dry runs from a fixed simulation seed.
+
+use aitrading::decision::{Action, Decision};
+use aitrading::sim;
+use aitrading::testkit::{Harness, MockOrder};
+
+// A paper run: open, let the price move, close.
+async fn dry_run() -> Vec<MockOrder> {
+    let mut h = Harness::new(&["BTCUSDT"], 1000.0).await.unwrap();
+    h.exchange.set_slippage(10.0);
+    h.exchange.set_price("BTCUSDT", 100.0);
+    h.exchange.script_prices("BTCUSDT", [100.0, 104.0]);
+
+    h.ai.push_decisions(&[Decision {
+        leverage: 5,
+        position_size_usd: 500.0,
+        ..Decision::new("BTCUSDT", Action::OpenLong)
+    }]);
+    h.run_cycle().await.unwrap();
+    h.ai.push_decisions(&[Decision::new("BTCUSDT", Action::CloseLong)]);
+    h.run_cycle().await.unwrap();
+    h.exchange.orders().to_vec()
+}
+
+// One test owns the process-wide seed.
+#[tokio::test]
+async fn same_seed_gives_identical_fills() {
+    sim::set_seed(Some(42));
+    let first = dry_run().await;
+    let second = dry_run().await;
+    assert_eq!(first.len(), 2);
+    assert_eq!(first, second);
+    // Slippage works against the trader on both sides.
+    assert!(first[0].price > 100.0 && first[0].price < 100.1);
+    assert!(first[1].price < 104.0 && first[1].price > 103.8);
+
+    sim::set_seed(Some(7));
+    assert_ne!(dry_run().await, first);
+    sim::set_seed(None);
+}
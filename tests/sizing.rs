@@ -0,0 +1,142 @@
+//! Position sizing methods and their use in the decision cycle.
+
+use aitrading::decision::{AccountInfo, Action, Context, Decision, PairLeg};
+use aitrading::logger::PerformanceAnalysis;
+use aitrading::sizing::{self, PositionSizing};
+use aitrading::testkit::{self, Harness};
+use aitrading::types::LongerTermData;
+use serde_json::json;
+
+fn open_long(symbol: &str, size: f64, leverage: i32) -> Decision {
+    Decision {
+        leverage,
+        position_size_usd: size,
+        ..Decision::new(symbol, Action::OpenLong)
+    }
+}
+
+fn context(equity: f64, atr14: f64, performance: Option<PerformanceAnalysis>) -> Context {
+    let mut data = testkit::mock_data("BTCUSDT", 100.0);
+    data.longer_term_context = Some(LongerTermData {
+        atr14,
+        ..Default::default()
+    });
+    Context {
+        account: AccountInfo {
+            total_equity: equity,
+            ..Default::default()
+        },
+        market_data: [("BTCUSDT".to_string(), data)].into_iter().collect(),
+        performance,
+        ..Default::default()
+    }
+}
+
+fn performance(trades: i32, win_rate: f64, avg_win: f64, avg_loss: f64) -> PerformanceAnalysis {
+    serde_json::from_value(json!({
+        "total_trades": trades, "winning_trades": 0, "losing_trades": 0,
+        "win_rate": win_rate, "avg_win": avg_win, "avg_loss": avg_loss,
+        "profit_factor": 0.0, "sharpe_ratio": 0.0, "recent_trades": [],
+        "symbol_stats": {}, "best_symbol": "", "worst_symbol": ""
+    }))
+    .unwrap()
+}
+
+#[test]
+fn parses_and_prints_methods() {
+    assert_eq!("".parse::<PositionSizing>().unwrap(), PositionSizing::Ai);
+    for spec in ["ai", "fixed:2.5", "atr:1:2", "kelly:0.5:10"] {
+        let sizing: PositionSizing = spec.parse().unwrap();
+        assert_eq!(sizing.to_string(), spec);
+    }
+    assert_eq!(
+        " Kelly : 0.25 : 5 ".parse::<PositionSizing>().unwrap(),
+        PositionSizing::Kelly {
+            fraction: 0.25,
+            max_pct: 5.0
+        }
+    );
+    for bad in [
+        "fixed",
+        "fixed:0",
+        "fixed:150",
+        "atr:1",
+        "kelly:2:10",
+        "ai:1",
+        "martingale",
+    ] {
+        let err = bad.parse::<PositionSizing>().unwrap_err();
+        assert_eq!(err.spec, bad);
+    }
+}
+
+#[test]
+fn fixed_fraction_and_atr_scale_with_equity() {
+    let ctx = context(1000.0, 5.0, None);
+    let d = open_long("BTCUSDT", 300.0, 5);
+
+    let fixed = PositionSizing::FixedFraction { equity_pct: 2.0 };
+    assert!((fixed.size(&d, &ctx).unwrap() - 100.0).abs() < 1e-9);
+
+    // Risking 1% (10 USD) on a 2 x ATR (10) stop buys one unit at 100.
+    let atr = PositionSizing::Atr {
+        risk_pct: 1.0,
+        atr_multiple: 2.0,
+    };
+    assert!((atr.size(&d, &ctx).unwrap() - 100.0).abs() < 1e-9);
+    // Without an ATR the AI's size stands.
+    assert_eq!(atr.size(&d, &context(1000.0, 0.0, None)).unwrap(), 300.0);
+
+    // Never more than equity times leverage across both legs.
+    let all_in = PositionSizing::FixedFraction { equity_pct: 100.0 };
+    let pair = Decision {
+        pair: Some(PairLeg {
+            symbol: "ETHUSDT".to_string(),
+            ratio: 1.0,
+        }),
+        ..d.clone()
+    };
+    assert!((all_in.size(&pair, &ctx).unwrap() - 2500.0).abs() < 1e-9);
+}
+
+#[test]
+fn kelly_needs_history_and_an_edge() {
+    let kelly = PositionSizing::Kelly {
+        fraction: 0.5,
+        max_pct: 10.0,
+    };
+    let d = open_long("BTCUSDT", 800.0, 2);
+
+    // Too few trades: the AI's size, capped at 10% margin.
+    let ctx = context(1000.0, 5.0, Some(performance(3, 100.0, 10.0, 0.0)));
+    assert!((kelly.size(&d, &ctx).unwrap() - 200.0).abs() < 1e-9);
+
+    // 60% wins at 1:1 gives a Kelly fraction of 0.2; half of it is 10%.
+    let ctx = context(1000.0, 5.0, Some(performance(20, 60.0, 10.0, -10.0)));
+    let small = open_long("BTCUSDT", 50.0, 1);
+    assert!((kelly.size(&small, &ctx).unwrap() - 100.0).abs() < 1e-9);
+
+    // A losing record opens nothing; closes are untouched.
+    let ctx = context(1000.0, 5.0, Some(performance(20, 30.0, 10.0, -10.0)));
+    assert!(kelly.size(&d, &ctx).is_err());
+    let close = Decision::new("BTCUSDT", Action::CloseLong);
+    let kept = sizing::apply(&kelly, vec![d, close.clone()], &ctx);
+    assert_eq!(kept, vec![close]);
+}
+
+#[tokio::test]
+async fn cycle_sizes_entries_by_the_trader_method() {
+    let mut h = Harness::new(&["BTCUSDT"], 1000.0).await.unwrap();
+    h.exchange.set_price("BTCUSDT", 100.0);
+    h.trader.position_sizing = "fixed:5".to_string();
+    h.db.update_trader(&h.trader).await.unwrap();
+    let stored = h.db.get_trader(&h.user_id, &h.trader.id).await.unwrap();
+    assert_eq!(stored.unwrap().position_sizing, "fixed:5");
+
+    h.ai.push_decisions(&[open_long("BTCUSDT", 900.0, 4)]);
+    let outcome = h.run_cycle().await.unwrap();
+    assert_eq!(outcome.proposed[0].position_size_usd, 900.0);
+    assert_eq!(outcome.approved[0].position_size_usd, 200.0);
+    let order = &outcome.filled[0];
+    assert!((order.quantity * order.price - 200.0).abs() < 1.0);
+}
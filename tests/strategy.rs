@@ -0,0 +1,108 @@
+//! Compiled-in strategies selected per trader, in place of or after the AI.
+
+use std::sync::Arc;
+
+use aitrading::decision::{Action, Context, Decision};
+use aitrading::strategy::{self, Strategy, StrategyError, StrategyMode};
+use aitrading::testkit::Harness;
+
+/// Opens a long on every candidate coin, or keeps only the first AI
+/// decision when reviewing.
+struct Scripted {
+    name: &'static str,
+    mode: StrategyMode,
+}
+
+impl Strategy for Scripted {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn mode(&self) -> StrategyMode {
+        self.mode
+    }
+
+    fn decide(
+        &self,
+        ctx: &Context,
+        proposed: Vec<Decision>,
+    ) -> Result<Vec<Decision>, StrategyError> {
+        if self.mode == StrategyMode::Augment {
+            return Ok(proposed.into_iter().take(1).collect());
+        }
+        Ok(ctx
+            .candidate_coins
+            .iter()
+            .map(|symbol| Decision {
+                leverage: if symbol == "ETHUSDT" { 0 } else { 2 },
+                position_size_usd: 100.0,
+                ..Decision::new(symbol, Action::OpenLong)
+            })
+            .collect())
+    }
+}
+
+fn register(name: &'static str, mode: StrategyMode) {
+    let _ = strategy::register(Arc::new(Scripted { name, mode }));
+}
+
+fn open_long(symbol: &str) -> Decision {
+    Decision {
+        leverage: 2,
+        position_size_usd: 100.0,
+        ..Decision::new(symbol, Action::OpenLong)
+    }
+}
+
+async fn harness() -> Harness {
+    let mut h = Harness::new(&["BTCUSDT", "ETHUSDT"], 1000.0).await.unwrap();
+    h.exchange.set_price("BTCUSDT", 100.0);
+    h.exchange.set_price("ETHUSDT", 10.0);
+    h
+}
+
+#[tokio::test]
+async fn a_replacing_strategy_trades_without_the_ai() {
+    register("test_replace", StrategyMode::Replace);
+    let mut h = harness().await;
+    h.trader.strategy = "test_replace".to_string();
+
+    let outcome = h.run_cycle().await.unwrap();
+    assert!(h.ai.calls().is_empty());
+    // Its decisions are validated like the AI's: ETH asked for no leverage.
+    assert_eq!(outcome.rejected.len(), 1);
+    assert!(outcome.rejected[0].to_string().contains("ETHUSDT"));
+    assert_eq!(outcome.filled.len(), 1);
+    assert_eq!(outcome.filled[0].symbol, "BTCUSDT");
+}
+
+#[tokio::test]
+async fn an_augmenting_strategy_reviews_the_ais_decisions() {
+    register("test_review", StrategyMode::Augment);
+    let mut h = harness().await;
+    h.trader.strategy = "test_review".to_string();
+
+    h.ai.push_decisions(&[open_long("ETHUSDT"), open_long("BTCUSDT")]);
+    let outcome = h.run_cycle().await.unwrap();
+    assert_eq!(h.ai.calls().len(), 1);
+    assert_eq!(outcome.proposed.len(), 1);
+    assert_eq!(outcome.filled.len(), 1);
+    assert_eq!(outcome.filled[0].symbol, "ETHUSDT");
+}
+
+#[tokio::test]
+async fn an_unknown_strategy_trades_nothing() {
+    let mut h = harness().await;
+    h.trader.strategy = "missing".to_string();
+
+    h.ai.push_decisions(&[open_long("BTCUSDT")]);
+    let outcome = h.run_cycle().await.unwrap();
+    assert!(h.ai.calls().is_empty());
+    assert!(outcome.filled.is_empty());
+    assert!(
+        outcome
+            .errors
+            .iter()
+            .any(|e| e.contains("Strategy 'missing' is not registered"))
+    );
+}
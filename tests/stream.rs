@@ -0,0 +1,129 @@
+//! WebSocket market data against a local stub stream.
+
+use std::time::Duration;
+
+use aitrading::stream::{self, StreamParams};
+use aitrading::types::Kline;
+use futures_util::{SinkExt, StreamExt};
+use serde_json::{Value, json};
+use tokio_tungstenite::tungstenite::Message;
+
+const MINUTE: i64 = 60_000;
+
+fn kline_event(open_time: i64, close: &str) -> String {
+    json!({
+        "stream": "btcusdt@kline_3m",
+        "data": {
+            "e": "kline",
+            "s": "BTCUSDT",
+            "k": {
+                "t": open_time, "T": open_time + 3 * MINUTE - 1, "i": "3m",
+                "o": "100", "h": "110", "l": "90", "c": close, "v": "5",
+                "n": 10, "q": "500", "V": "2", "Q": "200", "x": false
+            }
+        }
+    })
+    .to_string()
+}
+
+// Accepts one client and answers its subscription with a few updates.
+async fn stub() -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        let (tcp, _) = listener.accept().await.unwrap();
+        let mut ws = tokio_tungstenite::accept_async(tcp).await.unwrap();
+        while let Some(Ok(msg)) = ws.next().await {
+            let Message::Text(text) = msg else { continue };
+            let request: Value = serde_json::from_str(&text).unwrap();
+            assert_eq!(request["method"], "SUBSCRIBE");
+            assert!(
+                request["params"]
+                    .as_array()
+                    .unwrap()
+                    .contains(&json!("btcusdt@kline_3m"))
+            );
+            ws.send(Message::Text(
+                json!({"result": null, "id": request["id"]}).to_string(),
+            ))
+            .await
+            .unwrap();
+            for event in [
+                kline_event(0, "101"),
+                kline_event(3 * MINUTE, "102"),
+                kline_event(6 * MINUTE, "103"),
+                // The open candle updates in place.
+                kline_event(6 * MINUTE, "104"),
+                json!({"stream": "btcusdt@markPrice", "data": {
+                    "e": "markPriceUpdate", "s": "BTCUSDT", "p": "104.5", "r": "0.0001"
+                }})
+                .to_string(),
+                json!({"stream": "btcusdt@ticker", "data": {
+                    "e": "24hrTicker", "s": "BTCUSDT", "c": "104.2", "q": "123456"
+                }})
+                .to_string(),
+            ] {
+                ws.send(Message::Text(event)).await.unwrap();
+            }
+        }
+    });
+    format!("ws://{}", addr)
+}
+
+fn rest_kline(open_time: i64, close: f64) -> Kline {
+    Kline {
+        open_time,
+        open: close,
+        high: close,
+        low: close,
+        close,
+        volume: 1.0,
+        close_time: open_time + 3 * MINUTE - 1,
+        quote_volume: close,
+        trades: 1,
+        taker_buy_base_volume: 0.0,
+        taker_buy_quote_volume: 0.0,
+    }
+}
+
+// One test owns the process-wide stream.
+#[tokio::test]
+async fn streamed_candles_are_buffered_and_backfilled() {
+    let task = stream::start(StreamParams {
+        enabled: true,
+        url: stub().await,
+        ..Default::default()
+    })
+    .unwrap();
+    stream::track("btcusdt");
+
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(5);
+    while stream::last_price("BTCUSDT").is_none() {
+        assert!(tokio::time::Instant::now() < deadline, "no stream updates");
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    assert!(stream::is_connected());
+    assert_eq!(stream::tracked(), vec!["BTCUSDT"]);
+
+    let klines = stream::klines("BTCUSDT", "3m", 3).unwrap();
+    let closes: Vec<f64> = klines.iter().map(|k| k.close).collect();
+    assert_eq!(closes, vec![101.0, 102.0, 104.0]);
+    assert!(stream::klines("BTCUSDT", "3m", 4).is_none());
+    assert_eq!(stream::funding_rate("BTCUSDT"), Some(0.0001));
+    assert_eq!(stream::mark_price("BTCUSDT"), Some(104.5));
+
+    // REST history goes in front; streamed candles win where they overlap.
+    let history: Vec<Kline> = (-2..=1).map(|i| rest_kline(i * 3 * MINUTE, 50.0)).collect();
+    stream::backfill("BTCUSDT", "3m", &history);
+    let closes: Vec<f64> = stream::klines("BTCUSDT", "3m", 5)
+        .unwrap()
+        .iter()
+        .map(|k| k.close)
+        .collect();
+    assert_eq!(closes, vec![50.0, 50.0, 101.0, 102.0, 104.0]);
+
+    // Untracked symbols are not buffered.
+    stream::backfill("ETHUSDT", "3m", &history);
+    assert!(stream::klines("ETHUSDT", "3m", 1).is_none());
+    task.abort();
+}
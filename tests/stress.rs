@@ -0,0 +1,138 @@
+//! Stress tests over crash scenarios and price gaps.
+
+use aitrading::database::TraderRecord;
+use aitrading::decision::{AccountInfo, PositionInfo};
+use aitrading::logger::{self, DecisionRecord};
+use aitrading::server::{AppState, EmbeddedClient};
+use aitrading::stress::{self, Leverage, StressError};
+use aitrading::testkit;
+use axum::http::{Method, StatusCode};
+use chrono::Utc;
+
+fn btc_long(leverage: i32) -> PositionInfo {
+    PositionInfo {
+        symbol: "BTCUSDT".to_string(),
+        side: "long".to_string(),
+        entry_price: 50_000.0,
+        mark_price: 50_000.0,
+        quantity: 0.1,
+        leverage,
+        margin_used: 5_000.0 / leverage as f64,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn gaps_and_crashes_report_drawdown_and_liquidations() {
+    let report = stress::run(1_000.0, &[btc_long(5)], Leverage::default(), Utc::now()).unwrap();
+    let scenario = |name: &str| report.scenarios.iter().find(|s| s.name == name).unwrap();
+
+    let gap = scenario("gap_down_10");
+    assert!((gap.worst_equity - 500.0).abs() < 1e-6);
+    assert!((gap.max_drawdown_pct - 50.0).abs() < 1e-6);
+    assert!(gap.liquidations.is_empty());
+
+    // 5x liquidates a little under 20% down: 50000 * (1 - 0.2 + 0.004).
+    let gap = scenario("gap_down_20");
+    assert_eq!(gap.liquidations.len(), 1);
+    assert!((gap.liquidations[0].price - 40_200.0).abs() < 1e-6);
+    assert!((gap.final_equity - 20.0).abs() < 1e-6);
+    assert_eq!(scenario("may_2021").liquidations[0].step, 4);
+    assert_eq!(scenario("gap_up_15").max_drawdown_pct, 0.0);
+    assert!(report.worst_scenario.is_some());
+
+    // At 2x with the same margin the position survives the gap.
+    let report = stress::run(
+        1_000.0,
+        &[btc_long(5)],
+        Leverage {
+            btc_eth: Some(2),
+            altcoin: None,
+        },
+        Utc::now(),
+    )
+    .unwrap();
+    let gap = report
+        .scenarios
+        .iter()
+        .find(|s| s.name == "gap_down_20")
+        .unwrap();
+    assert!(gap.liquidations.is_empty());
+    assert!((gap.max_drawdown_pct - 40.0).abs() < 1e-6);
+
+    assert!(matches!(
+        stress::run(
+            1_000.0,
+            &[],
+            Leverage {
+                btc_eth: Some(200),
+                altcoin: None,
+            },
+            Utc::now()
+        ),
+        Err(StressError::InvalidLeverage(200))
+    ));
+}
+
+#[tokio::test]
+async fn api_uses_the_latest_snapshot() {
+    let db = testkit::memory_db().await.unwrap();
+    db.ensure_admin_user().await.unwrap();
+    db.create_trader(&TraderRecord {
+        id: "t1".to_string(),
+        user_id: "admin".to_string(),
+        name: "stressed".to_string(),
+        ai_model_id: "deepseek".to_string(),
+        exchange_id: "binance".to_string(),
+        initial_balance: 1_000.0,
+        ..Default::default()
+    })
+    .await
+    .unwrap();
+    let client = EmbeddedClient::new(AppState {
+        db: db.clone(),
+        scheduler: None,
+        cipher: None,
+    })
+    .with_token(testkit::admin_token());
+
+    let (status, _) = client
+        .request(Method::GET, "/api/traders/t1/stress", None)
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let mut record = DecisionRecord::new("system", "input", "", "[]");
+    record.set_account(
+        &AccountInfo {
+            total_equity: 1_000.0,
+            position_count: 1,
+            ..Default::default()
+        },
+        &[btc_long(5)],
+    );
+    logger::store_record(&db, "admin", "t1", &record, None)
+        .await
+        .unwrap();
+    let (status, report) = client
+        .request(
+            Method::GET,
+            "/api/traders/t1/stress?btc_eth_leverage=10",
+            None,
+        )
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(report["positions"], 1);
+    assert_eq!(report["leverage"]["btc_eth"], 10);
+    assert!(report["scenarios"].as_array().unwrap().len() >= 6);
+    let (status, _) = client
+        .request(
+            Method::GET,
+            "/api/traders/t1/stress?altcoin_leverage=0",
+            None,
+        )
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
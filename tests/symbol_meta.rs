@@ -0,0 +1,79 @@
+//! Cached exchangeInfo order rules: rounding and minimum notional.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use aitrading::money::Decimal;
+use aitrading::symbol_meta::{self, SymbolMeta};
+use aitrading::types::ExchangeInfo;
+use serde_json::json;
+
+fn exchange_info() -> ExchangeInfo {
+    serde_json::from_value(json!({
+        "symbols": [{
+            "symbol": "BTCUSDT",
+            "status": "TRADING",
+            "baseAsset": "BTC",
+            "quoteAsset": "USDT",
+            "contractType": "PERPETUAL",
+            "pricePrecision": 2,
+            "quantityPrecision": 3,
+            "filters": [
+                { "filterType": "PRICE_FILTER", "tickSize": "0.10" },
+                { "filterType": "MIN_NOTIONAL", "notional": "100" }
+            ]
+        }]
+    }))
+    .unwrap()
+}
+
+#[test]
+fn rules_round_orders_and_check_their_value() {
+    let info = exchange_info();
+    let meta = SymbolMeta::from_info(&info.symbols[0]);
+    assert_eq!(meta.min_notional, Decimal::ONE_HUNDRED);
+    assert_eq!(
+        meta.round_quantity("0.12345".parse().unwrap()),
+        "0.123".parse::<Decimal>().unwrap()
+    );
+    assert_eq!(
+        meta.round_price("64123.456".parse().unwrap()),
+        "64123.46".parse::<Decimal>().unwrap()
+    );
+    let price = Decimal::from(50_000);
+    assert!(meta.meets_min_notional("0.002".parse().unwrap(), price));
+    assert!(!meta.meets_min_notional("0.001".parse().unwrap(), price));
+
+    // Unlisted symbols keep the old default of three decimals.
+    let unlisted = SymbolMeta::unlisted("NEWUSDT");
+    assert_eq!(unlisted.quantity_precision, 3);
+    assert_eq!(
+        unlisted.round_price("1.23456".parse().unwrap()),
+        "1.23456".parse::<Decimal>().unwrap()
+    );
+}
+
+#[tokio::test]
+async fn exchange_info_is_loaded_once_per_venue() {
+    let loads = AtomicUsize::new(0);
+    let load = || async {
+        loads.fetch_add(1, Ordering::SeqCst);
+        Ok::<_, String>(exchange_info())
+    };
+    let venue = "https://venue.test";
+
+    let meta = symbol_meta::lookup(venue, "BTCUSDT", load).await.unwrap();
+    assert_eq!(meta.price_precision, 2);
+    let meta = symbol_meta::lookup(venue, "NEWUSDT", load).await.unwrap();
+    assert_eq!(meta, SymbolMeta::unlisted("NEWUSDT"));
+    assert_eq!(loads.load(Ordering::SeqCst), 1);
+    assert!(symbol_meta::is_fresh(venue));
+
+    // Another venue has its own rules, and a failed load is passed on.
+    let err = symbol_meta::lookup("https://other.test", "BTCUSDT", || async {
+        Err::<ExchangeInfo, _>("down".to_string())
+    })
+    .await
+    .unwrap_err();
+    assert_eq!(err, "down");
+    assert!(symbol_meta::get("https://other.test", "BTCUSDT").is_none());
+}
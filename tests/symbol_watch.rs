@@ -0,0 +1,97 @@
+//! Entries are refused on symbols the exchange stopped trading.
+
+use aitrading::api_client::ApiClient;
+use aitrading::database::{TraderRecord, User};
+use aitrading::decision::{Action, Decision, PairLeg};
+use aitrading::symbol_watch;
+use aitrading::testkit;
+use axum::Router;
+use axum::routing::get;
+use serde_json::{Value, json};
+
+fn symbol(name: &str, status: &str) -> Value {
+    json!({
+        "symbol": name,
+        "status": status,
+        "baseAsset": name.trim_end_matches("USDT"),
+        "quoteAsset": "USDT",
+        "contractType": "PERPETUAL",
+        "pricePrecision": 2,
+        "quantityPrecision": 3
+    })
+}
+
+async fn stub() -> String {
+    let app = Router::new().route(
+        "/fapi/v1/exchangeInfo",
+        get(|| async {
+            axum::Json(json!({"symbols": [
+                symbol("BTCUSDT", "TRADING"),
+                symbol("ETHUSDT", "SETTLING"),
+            ]}))
+        }),
+    );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    format!("http://{}", addr)
+}
+
+fn open(symbol: &str) -> Decision {
+    Decision {
+        leverage: 2,
+        position_size_usd: 100.0,
+        ..Decision::new(symbol, Action::OpenLong)
+    }
+}
+
+#[tokio::test]
+async fn entries_with_a_leg_on_an_unavailable_symbol_are_dropped() {
+    let db = testkit::memory_db().await.unwrap();
+    db.create_user(&User {
+        id: "user-1".to_string(),
+        email: "watch@example.com".to_string(),
+        ..Default::default()
+    })
+    .await
+    .unwrap();
+    db.create_trader(&TraderRecord {
+        id: "watcher".to_string(),
+        user_id: "user-1".to_string(),
+        name: "watcher".to_string(),
+        ai_model_id: "deepseek".to_string(),
+        exchange_id: "binance".to_string(),
+        trading_symbols: "BTCUSDT,ETHUSDT".to_string(),
+        ..Default::default()
+    })
+    .await
+    .unwrap();
+
+    let client = ApiClient::new().with_base_url(&stub().await);
+    let alerts = symbol_watch::check(&db, &client).await.unwrap();
+    assert_eq!(alerts.len(), 1);
+    assert_eq!(alerts[0].symbol, "ETHUSDT");
+    assert!(symbol_watch::is_blocked("ETHUSDT"));
+    assert!(!symbol_watch::is_blocked("BTCUSDT"));
+
+    let kept = symbol_watch::drop_blocked_entries(vec![
+        open("BTCUSDT"),
+        open("ETHUSDT"),
+        Decision {
+            pair: Some(PairLeg {
+                symbol: "ETHUSDT".to_string(),
+                ratio: 1.0,
+            }),
+            ..open("BTCUSDT")
+        },
+        Decision::new("ETHUSDT", Action::CloseLong),
+    ]);
+    let kept: Vec<_> = kept.iter().map(|d| (d.symbol.as_str(), d.action)).collect();
+    assert_eq!(
+        kept,
+        vec![
+            ("BTCUSDT", Action::OpenLong),
+            ("ETHUSDT", Action::CloseLong)
+        ]
+    );
+}
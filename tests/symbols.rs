@@ -0,0 +1,74 @@
+//! Symbol lists: parsing, checks against the listed markets and the merged
+//! custom coin list.
+
+use aitrading::database::TraderRecord;
+use aitrading::symbols;
+use aitrading::testkit;
+use aitrading::types::SymbolInfo;
+use serde_json::json;
+
+fn listed(symbol: &str, base: &str) -> SymbolInfo {
+    serde_json::from_value(json!({
+        "symbol": symbol,
+        "status": "TRADING",
+        "baseAsset": base,
+        "quoteAsset": "USDT",
+        "contractType": "PERPETUAL",
+        "pricePrecision": 2,
+        "quantityPrecision": 3,
+    }))
+    .unwrap()
+}
+
+fn trader(id: &str, trading_symbols: &str, custom_coins: &str) -> TraderRecord {
+    TraderRecord {
+        id: id.to_string(),
+        user_id: "user-coins".to_string(),
+        name: id.to_string(),
+        initial_balance: 1000.0,
+        trading_symbols: trading_symbols.to_string(),
+        custom_coins: custom_coins.to_string(),
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn custom_coins_are_merged_across_traders() {
+    let usdt = ["USDT".to_string()];
+    assert_eq!(
+        symbols::parse_list(" btc, ETHUSDT,,btcusdt ,sol", &usdt),
+        ["BTCUSDT", "ETHUSDT", "SOLUSDT"]
+    );
+    assert!(symbols::parse_list(" , ", &usdt).is_empty());
+
+    // Without any trader symbols the default list is used.
+    let db = testkit::memory_db().await.unwrap();
+    let defaults: Vec<String> =
+        serde_json::from_str(&db.get_system_config("default_coins").await.unwrap()).unwrap();
+    assert_eq!(db.get_custom_coins().await.unwrap(), defaults);
+
+    // Nothing is rejected before the listing is known.
+    assert!(symbols::is_listed("FAKEUSDT"));
+
+    symbols::set_listed(&[
+        listed("BTCUSDT", "BTC"),
+        listed("ETHUSDT", "ETH"),
+        listed("SOLUSDT", "SOL"),
+        listed("DOGEUSDT", "DOGE"),
+    ]);
+    assert!(symbols::is_listed("ethusdt"));
+    assert!(!symbols::is_listed("FAKEUSDT"));
+
+    db.create_trader(&trader("trader-a", "btc,eth", "doge"))
+        .await
+        .unwrap();
+    db.create_trader(&trader("trader-b", "ETHUSDT, sol", "fake,DOGE"))
+        .await
+        .unwrap();
+    let stored = db.get_trader("user-coins", "trader-b").await.unwrap();
+    assert_eq!(stored.unwrap().custom_coins, "fake,DOGE");
+    assert_eq!(
+        db.get_custom_coins().await.unwrap(),
+        ["BTCUSDT", "ETHUSDT", "DOGEUSDT", "SOLUSDT"]
+    );
+}
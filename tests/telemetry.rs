@@ -0,0 +1,113 @@
+//! Spans the engine opens, so logs show which trader, cycle and AI call they
+//! belong to.
+
+use std::sync::{Arc, Mutex};
+
+use aitrading::ai::{AiProvider, OpenAiCompatClient};
+use aitrading::decision::{Action, Decision};
+use aitrading::testkit::Harness;
+use axum::Router;
+use axum::routing::post;
+use serde_json::json;
+use tracing::Subscriber;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id};
+use tracing_subscriber::Layer;
+use tracing_subscriber::layer::{Context, SubscriberExt};
+use tracing_subscriber::registry::LookupSpan;
+
+type SpanFields = Vec<(String, String)>;
+
+/// Span names with their fields, in the order they were opened.
+#[derive(Clone, Default)]
+struct Spans(Arc<Mutex<Vec<(String, SpanFields)>>>);
+
+impl Spans {
+    fn named(&self, name: &str) -> Vec<SpanFields> {
+        let spans = self.0.lock().unwrap();
+        spans
+            .iter()
+            .filter(|(n, _)| n == name)
+            .map(|(_, fields)| fields.clone())
+            .collect()
+    }
+}
+
+struct Fields<'a>(&'a mut SpanFields);
+
+impl Visit for Fields<'_> {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        self.0
+            .push((field.name().to_string(), format!("{:?}", value)));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.push((field.name().to_string(), value.to_string()));
+    }
+}
+
+impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for Spans {
+    fn on_new_span(&self, attrs: &Attributes<'_>, _: &Id, _: Context<'_, S>) {
+        let mut fields = Vec::new();
+        attrs.record(&mut Fields(&mut fields));
+        let name = attrs.metadata().name().to_string();
+        self.0.lock().unwrap().push((name, fields));
+    }
+}
+
+fn field<'a>(fields: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    fields
+        .iter()
+        .find(|(n, _)| n == name)
+        .map(|(_, v)| v.as_str())
+}
+
+#[tokio::test]
+async fn cycles_run_in_a_span_naming_the_trader_and_cycle() {
+    let spans = Spans::default();
+    let _guard =
+        tracing::subscriber::set_default(tracing_subscriber::registry().with(spans.clone()));
+
+    let mut h = Harness::new(&["BTCUSDT"], 1000.0).await.unwrap();
+    h.exchange.set_price("BTCUSDT", 100.0);
+    h.ai.push_decisions(&[Decision::new("BTCUSDT", Action::Hold)]);
+    h.run_cycle().await.unwrap();
+    h.ai.push_decisions(&[Decision::new("BTCUSDT", Action::Hold)]);
+    h.run_cycle().await.unwrap();
+
+    let cycles = spans.named("trader_cycle");
+    assert_eq!(cycles.len(), 2);
+    for (i, fields) in cycles.iter().enumerate() {
+        assert_eq!(field(fields, "trader_id"), Some(h.trader.id.as_str()));
+        assert_eq!(field(fields, "cycle"), Some((i + 1).to_string().as_str()));
+    }
+}
+
+#[tokio::test]
+async fn ai_calls_run_in_a_span_naming_the_model() {
+    let spans = Spans::default();
+    let _guard =
+        tracing::subscriber::set_default(tracing_subscriber::registry().with(spans.clone()));
+
+    let app = Router::new().route(
+        "/v1/chat/completions",
+        post(|| async {
+            axum::Json(json!({"choices": [{"message": {"role": "assistant", "content": "ok"}}]}))
+        }),
+    );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+    let url = format!("http://{}/v1", addr);
+    let mut client = OpenAiCompatClient::custom(&url, "sk-test", "my-model").unwrap();
+    assert_eq!(
+        client.chat_completion("system", "user").await.unwrap(),
+        "ok"
+    );
+
+    let calls = spans.named("ai_call");
+    assert_eq!(calls.len(), 1);
+    assert_eq!(field(&calls[0], "provider"), Some("custom"));
+    assert_eq!(field(&calls[0], "model"), Some("my-model"));
+}
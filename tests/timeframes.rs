@@ -0,0 +1,178 @@
+//! Extra per-trader timeframes in the market data and the prompt.
+
+use aitrading::auth;
+use aitrading::data::{self, MarketError};
+use aitrading::database::TraderRecord;
+use aitrading::logger::DecisionLogger;
+use aitrading::runner::{RunnerConfig, TraderCycle};
+use aitrading::server::{AppState, EmbeddedClient};
+use aitrading::testkit::{self, MockAiProvider, MockExchange};
+use aitrading::types::{Data, Kline};
+use axum::http::{Method, StatusCode};
+use serde_json::json;
+use uuid::Uuid;
+
+fn candle(i: i64, close: f64) -> Kline {
+    Kline {
+        open_time: i * 3_600_000,
+        open: close,
+        high: close + 1.0,
+        low: close - 1.0,
+        close,
+        volume: 10.0,
+        close_time: i * 3_600_000 + 3_599_999,
+        quote_volume: 0.0,
+        trades: 0,
+        taker_buy_base_volume: 0.0,
+        taker_buy_quote_volume: 0.0,
+    }
+}
+
+#[test]
+fn timeframe_lists_are_validated() {
+    assert_eq!(
+        data::parse_timeframes(" 15m,1h, ,1d,1h").unwrap(),
+        ["15m", "1h", "1d"]
+    );
+    assert!(data::parse_timeframes("").unwrap().is_empty());
+    assert!(matches!(
+        data::parse_timeframes("1h,7m"),
+        Err(MarketError::UnsupportedTimeframe(t)) if t == "7m"
+    ));
+}
+
+#[test]
+fn timeframe_data_and_prompt_section() {
+    let klines: Vec<Kline> = (0..100).map(|i| candle(i, 100.0 + i as f64)).collect();
+    let tf = data::calculate_timeframe_data("1h", &klines);
+    assert_eq!(tf.interval, "1h");
+    assert!(!tf.warming_up);
+    assert_eq!(tf.closes.len(), 10);
+    assert_eq!(tf.closes.last(), Some(&199.0));
+    assert!((tf.price_change - 1.0 / 198.0 * 100.0).abs() < 1e-9);
+    assert!(tf.ema20 > tf.ema50);
+    assert_eq!(tf.rsi14, 100.0);
+
+    let short = data::calculate_timeframe_data("1d", &klines[..5]);
+    assert!(short.warming_up);
+    assert_eq!(short.ema50, 0.0);
+
+    let mut market = testkit::mock_data("BTCUSDT", 199.0);
+    let plain = data::format(&market);
+    assert!(!plain.contains("Additional timeframe"));
+    market.timeframes.insert("1h".to_string(), tf);
+    market.timeframes.insert("1d".to_string(), short);
+    let text = data::format(&market);
+    let daily = text.find("Additional timeframe (1d candles)").unwrap();
+    let hourly = text.find("Additional timeframe (1h candles)").unwrap();
+    assert!(daily < hourly);
+    assert!(text.contains("Closes: [190.000"));
+    assert!(text[daily..hourly].contains("not enough history"));
+
+    // Data serialized before timeframes existed still parses.
+    let mut json = serde_json::to_value(&market).unwrap();
+    json.as_object_mut().unwrap().remove("timeframes");
+    let old: Data = serde_json::from_value(json).unwrap();
+    assert!(old.timeframes.is_empty());
+}
+
+#[tokio::test]
+async fn cycle_prompt_includes_configured_timeframes() {
+    let db = testkit::memory_db().await.unwrap();
+    let trader = TraderRecord {
+        id: format!("trader-{}", Uuid::new_v4()),
+        user_id: "u1".to_string(),
+        name: "mtf".to_string(),
+        initial_balance: 1000.0,
+        btc_eth_leverage: 5,
+        altcoin_leverage: 5,
+        trading_symbols: "BTCUSDT".to_string(),
+        timeframes: "15m,1d".to_string(),
+        ..Default::default()
+    };
+    db.create_trader(&trader).await.unwrap();
+    let stored = db.get_trader("u1", &trader.id).await.unwrap().unwrap();
+    assert_eq!(stored.timeframes, "15m,1d");
+
+    let log_dir = std::env::temp_dir().join(format!("aitrading-mtf-{}", Uuid::new_v4()));
+    let config = RunnerConfig {
+        log_dir: log_dir.to_string_lossy().to_string(),
+        ..Default::default()
+    };
+    let mut exchange = MockExchange::new(1000.0);
+    exchange.set_price("BTCUSDT", 100.0);
+    let mut cycle = TraderCycle::new(
+        db,
+        stored,
+        exchange,
+        Box::new(MockAiProvider::new()),
+        config.logger(&trader),
+        &config,
+    );
+    cycle.run_cycle().await.unwrap();
+
+    let records = DecisionLogger::new(&log_dir.join(&trader.id).to_string_lossy())
+        .get_latest_records(1)
+        .unwrap();
+    let record = serde_json::to_value(&records[0]).unwrap();
+    let prompt = record["input_prompt"].as_str().unwrap();
+    assert!(prompt.contains("Additional timeframe (15m candles)"));
+    assert!(prompt.contains("Additional timeframe (1d candles)"));
+    let _ = std::fs::remove_dir_all(&log_dir);
+}
+
+#[tokio::test]
+async fn api_rejects_unknown_timeframes() {
+    auth::set_jwt_secret("timeframes-test-secret");
+    let db = testkit::memory_db().await.unwrap();
+    let client = EmbeddedClient::new(AppState {
+        db: db.clone(),
+        scheduler: None,
+        cipher: None,
+    });
+    let (_, body) = client
+        .request(
+            Method::POST,
+            "/api/register",
+            Some(&json!({ "email": "tf@example.com", "password": "correct horse" })),
+        )
+        .await
+        .unwrap();
+    let user = client.clone().with_token(body["token"].as_str().unwrap());
+    user.request(
+        Method::PUT,
+        "/api/models/qwen",
+        Some(&json!({ "enabled": true, "api_key": "sk" })),
+    )
+    .await
+    .unwrap();
+    user.request(
+        Method::PUT,
+        "/api/exchanges/binance",
+        Some(&json!({ "enabled": true, "api_key": "key", "secret_key": "secret" })),
+    )
+    .await
+    .unwrap();
+    let (_, models) = user
+        .request(Method::GET, "/api/models", None)
+        .await
+        .unwrap();
+    let trader = |timeframes: &str| {
+        json!({
+            "name": "mtf", "ai_model_id": models[0]["id"], "exchange_id": "binance",
+            "initial_balance": 1000.0, "timeframes": timeframes,
+        })
+    };
+
+    let (status, _) = user
+        .request(Method::POST, "/api/traders", Some(&trader("1h,90m")))
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, body) = user
+        .request(Method::POST, "/api/traders", Some(&trader("1h,1d")))
+        .await
+        .unwrap();
+    assert!(status.is_success(), "{}", body);
+    assert_eq!(body["timeframes"], "1h,1d");
+}
@@ -0,0 +1,53 @@
+//! Session windows in the user's timezone gate new entries.
+
+use aitrading::decision::{Action, Decision};
+use aitrading::testkit::Harness;
+use chrono::{Duration, Utc};
+use serde_json::json;
+
+fn window(from_hours: i64, to_hours: i64) -> String {
+    let now = Utc::now();
+    json!([{
+        "start": (now + Duration::hours(from_hours)).format("%H:%M").to_string(),
+        "end": (now + Duration::hours(to_hours)).format("%H:%M").to_string(),
+    }])
+    .to_string()
+}
+
+#[tokio::test]
+async fn entries_wait_for_a_trading_window() {
+    let mut h = Harness::new(&["BTCUSDT", "ETHUSDT"], 1000.0).await.unwrap();
+    h.exchange.set_price("BTCUSDT", 100.0);
+    h.exchange.set_price("ETHUSDT", 10.0);
+    h.exchange.open("BTCUSDT", "long", 1.0, 2).unwrap();
+    let decisions = [
+        Decision {
+            leverage: 2,
+            position_size_usd: 100.0,
+            ..Decision::new("ETHUSDT", Action::OpenLong)
+        },
+        Decision::new("BTCUSDT", Action::CloseLong),
+    ];
+
+    h.trader.trading_windows = window(2, 3);
+    h.ai.push_decisions(&decisions);
+    let outcome = h.run_cycle().await.unwrap();
+    assert_eq!(outcome.approved.len(), 1);
+    assert_eq!(outcome.approved[0].action, Action::CloseLong);
+    assert!(
+        h.ai.calls()[0]
+            .user_prompt
+            .contains("Outside the trading session")
+    );
+
+    h.trader.trading_windows = window(-1, 1);
+    h.ai.push_decisions(&decisions[..1]);
+    let outcome = h.run_cycle().await.unwrap();
+    assert_eq!(outcome.approved.len(), 1);
+    assert_eq!(outcome.approved[0].action, Action::OpenLong);
+    assert!(
+        !h.ai.calls()[1]
+            .user_prompt
+            .contains("Outside the trading session")
+    );
+}
@@ -0,0 +1,87 @@
+//! Model tournaments over scenarios captured by full decision cycles.
+
+use std::collections::HashMap;
+
+use aitrading::ai::AiProvider;
+use aitrading::decision::{Action, Decision};
+use aitrading::testkit::{Harness, MockAiProvider};
+use aitrading::tournament::{self, TournamentParams};
+use chrono::Utc;
+
+fn provider(name: &str, action: Action, scenarios: usize) -> Box<dyn AiProvider> {
+    let mut ai = MockAiProvider::named(name);
+    for _ in 0..scenarios {
+        let decision = Decision {
+            leverage: 3,
+            position_size_usd: 100.0,
+            ..Decision::new("BTCUSDT", action)
+        };
+        ai.push_decisions(&[decision]);
+    }
+    Box::new(ai)
+}
+
+#[tokio::test]
+async fn ranks_models_on_settled_scenarios() {
+    let mut h = Harness::new(&["BTCUSDT"], 1000.0).await.unwrap();
+    h.exchange.set_price("BTCUSDT", 100.0);
+    h.run_cycle().await.unwrap();
+    h.run_cycle().await.unwrap();
+
+    // Both scenarios are due; settle them with BTC 5% higher.
+    let due = h.db.get_unsettled_eval_scenarios(Utc::now()).await.unwrap();
+    assert_eq!(due.len(), 2);
+    let exit = serde_json::to_string(&HashMap::from([("BTCUSDT", 105.0)])).unwrap();
+    for scenario in &due {
+        h.db.settle_eval_scenario(&scenario.id, &exit)
+            .await
+            .unwrap();
+    }
+
+    let mut broken = MockAiProvider::named("broken");
+    broken.push_response("I am not sure.");
+    broken.push_error("timeout");
+    let mut contestants = vec![
+        provider("bear", Action::OpenShort, 2),
+        provider("bull", Action::OpenLong, 2),
+        Box::new(broken) as Box<dyn AiProvider>,
+    ];
+    let report = tournament::run_for_user(
+        &h.db,
+        &h.user_id,
+        &mut contestants,
+        &TournamentParams::default(),
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(report.scenarios, 2);
+    let names: Vec<&str> = report.standings.iter().map(|s| s.model.as_str()).collect();
+    assert_eq!(names, ["bull", "broken", "bear"]);
+    let bull = &report.standings[0];
+    assert_eq!((bull.trades, bull.wins), (2, 2));
+    assert!((bull.total_return_pct - 10.0).abs() < 1e-9);
+    assert_eq!(report.standings[1].failures, 2);
+    assert!((report.standings[2].total_return_pct + 10.0).abs() < 1e-9);
+    assert!(report.table().contains("bull"));
+
+    let stored = tournament::latest_report(&h.db, &h.user_id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(stored.standings.len(), 3);
+}
+
+#[test]
+fn closes_score_the_move_they_avoided() {
+    let entry = HashMap::from([("ETHUSDT".to_string(), 200.0)]);
+    let exit = HashMap::from([("ETHUSDT".to_string(), 190.0)]);
+    let decisions = [
+        Decision::new("ETHUSDT", Action::CloseLong),
+        Decision::new("ETHUSDT", Action::Hold),
+        Decision::new("SOLUSDT", Action::OpenLong),
+    ];
+    let score = tournament::score(&decisions, &entry, &exit);
+    assert_eq!((score.trades, score.wins), (1, 1));
+    assert!((score.return_pct - 5.0).abs() < 1e-9);
+}
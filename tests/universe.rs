@@ -0,0 +1,126 @@
+//! Default coin universe refresh against a stub exchange.
+
+use aitrading::api_client::ApiClient;
+use aitrading::database::{TraderRecord, User};
+use aitrading::testkit;
+use aitrading::universe::{self, UniverseParams};
+use axum::Router;
+use axum::routing::get;
+use chrono::Utc;
+use serde_json::{Value, json};
+
+fn symbol(name: &str, quote: &str, listed_days_ago: i64, margin_pct: &str) -> Value {
+    json!({
+        "symbol": name,
+        "status": "TRADING",
+        "baseAsset": name.trim_end_matches(quote),
+        "quoteAsset": quote,
+        "contractType": "PERPETUAL",
+        "pricePrecision": 2,
+        "quantityPrecision": 3,
+        "onboardDate": (Utc::now() - chrono::Duration::days(listed_days_ago)).timestamp_millis(),
+        "requiredMarginPercent": margin_pct
+    })
+}
+
+async fn stub() -> String {
+    let app = Router::new()
+        .route(
+            "/fapi/v1/exchangeInfo",
+            get(|| async {
+                axum::Json(json!({"symbols": [
+                    symbol("BTCUSDT", "USDT", 2000, "2.5000"),
+                    symbol("ETHUSDT", "USDT", 2000, "2.5000"),
+                    symbol("SOLUSDT", "USDT", 900, "5.0000"),
+                    // Listed last week.
+                    symbol("NEWUSDT", "USDT", 7, "5.0000"),
+                    // At most 2x.
+                    symbol("LOWUSDT", "USDT", 400, "50.0000"),
+                    // Other quote asset.
+                    symbol("BTCUSDC", "USDC", 400, "2.5000"),
+                ]}))
+            }),
+        )
+        .route(
+            "/fapi/v1/ticker/24hr",
+            get(|| async {
+                axum::Json(json!([
+                    {"symbol": "BTCUSDT", "quoteVolume": "9000000000"},
+                    {"symbol": "ETHUSDT", "quoteVolume": "5000000000"},
+                    {"symbol": "SOLUSDT", "quoteVolume": "7000000000"},
+                    {"symbol": "NEWUSDT", "quoteVolume": "9900000000"},
+                    {"symbol": "LOWUSDT", "quoteVolume": "9800000000"},
+                    {"symbol": "BTCUSDC", "quoteVolume": "9700000000"}
+                ]))
+            }),
+        );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    format!("http://{}", addr)
+}
+
+#[tokio::test]
+async fn refresh_ranks_filtered_symbols_and_alerts_default_traders() {
+    let db = testkit::memory_db().await.unwrap();
+    db.create_user(&User {
+        id: "user-1".to_string(),
+        email: "universe@example.com".to_string(),
+        ..Default::default()
+    })
+    .await
+    .unwrap();
+    for (id, symbols) in [("defaults", ""), ("own", "DOGEUSDT")] {
+        db.create_trader(&TraderRecord {
+            id: id.to_string(),
+            user_id: "user-1".to_string(),
+            name: id.to_string(),
+            ai_model_id: "deepseek".to_string(),
+            exchange_id: "binance".to_string(),
+            trading_symbols: symbols.to_string(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    }
+
+    let client = ApiClient::new().with_base_url(&stub().await);
+    let params = UniverseParams {
+        enabled: true,
+        top_n: 2,
+        ..Default::default()
+    };
+    let change = universe::refresh(&db, &client, &params)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(change.coins, vec!["BTCUSDT", "SOLUSDT"]);
+    assert!(change.removed.contains(&"ETHUSDT".to_string()));
+    assert_eq!(universe::default_coins(), change.coins);
+    assert_eq!(
+        db.get_system_config("default_coins").await.unwrap(),
+        r#"["BTCUSDT","SOLUSDT"]"#
+    );
+
+    let notified = universe::notify_traders(&db, &change).await.unwrap();
+    assert_eq!(notified, vec!["defaults"]);
+
+    // An unchanged universe is not reported again.
+    assert!(
+        universe::refresh(&db, &client, &params)
+            .await
+            .unwrap()
+            .is_none()
+    );
+
+    // Filters that leave nothing keep the stored list.
+    let strict = UniverseParams {
+        min_leverage: 100,
+        ..params
+    };
+    assert!(universe::refresh(&db, &client, &strict).await.is_err());
+    assert_eq!(
+        db.get_system_config("default_coins").await.unwrap(),
+        r#"["BTCUSDT","SOLUSDT"]"#
+    );
+}
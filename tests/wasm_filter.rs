@@ -0,0 +1,184 @@
+//! User-supplied decision filters: the restrict-only contract and the runner
+//! running them before execution.
+
+use aitrading::decision::{Action, Decision};
+use aitrading::testkit::Harness;
+
+fn open_long(symbol: &str, size: f64, leverage: i32) -> Decision {
+    Decision {
+        leverage,
+        position_size_usd: size,
+        ..Decision::new(symbol, Action::OpenLong)
+    }
+}
+
+// Builds a filter module by hand: `alloc` hands out a buffer at 4096 and
+// `filter` ignores its input and returns `output`, or traps when `output` is
+// `None`.
+#[cfg(feature = "wasm-filters")]
+fn module(output: Option<&str>) -> Vec<u8> {
+    fn uleb(mut v: u64, out: &mut Vec<u8>) {
+        loop {
+            let byte = (v & 0x7f) as u8;
+            v >>= 7;
+            if v == 0 {
+                out.push(byte);
+                return;
+            }
+            out.push(byte | 0x80);
+        }
+    }
+    fn sleb(mut v: i64, out: &mut Vec<u8>) {
+        loop {
+            let byte = (v & 0x7f) as u8;
+            v >>= 7;
+            if (v == 0 && byte & 0x40 == 0) || (v == -1 && byte & 0x40 != 0) {
+                out.push(byte);
+                return;
+            }
+            out.push(byte | 0x80);
+        }
+    }
+    fn section(id: u8, content: Vec<u8>, out: &mut Vec<u8>) {
+        out.push(id);
+        uleb(content.len() as u64, out);
+        out.extend(content);
+    }
+    fn name(s: &str, out: &mut Vec<u8>) {
+        uleb(s.len() as u64, out);
+        out.extend(s.as_bytes());
+    }
+    const OUTPUT_AT: i64 = 16;
+
+    let mut wasm = b"\0asm\x01\0\0\0".to_vec();
+    // (i32) -> i32 and (i32, i32) -> i64
+    section(
+        1,
+        vec![2, 0x60, 1, 0x7f, 1, 0x7f, 0x60, 2, 0x7f, 0x7f, 1, 0x7e],
+        &mut wasm,
+    );
+    section(3, vec![2, 0, 1], &mut wasm);
+    section(5, vec![1, 0, 1], &mut wasm);
+    let mut exports = vec![3];
+    for (export, kind, index) in [("memory", 2, 0), ("alloc", 0, 0), ("filter", 0, 1)] {
+        name(export, &mut exports);
+        exports.extend([kind, index]);
+    }
+    section(7, exports, &mut wasm);
+
+    let mut alloc = vec![0, 0x41];
+    sleb(4096, &mut alloc);
+    alloc.push(0x0b);
+    let mut filter = vec![0];
+    match output {
+        Some(output) => {
+            filter.push(0x42);
+            sleb((OUTPUT_AT << 32) | output.len() as i64, &mut filter);
+        }
+        None => filter.push(0x00),
+    }
+    filter.push(0x0b);
+    let mut code = vec![2];
+    for body in [alloc, filter] {
+        uleb(body.len() as u64, &mut code);
+        code.extend(body);
+    }
+    section(10, code, &mut wasm);
+
+    if let Some(output) = output {
+        let mut data = vec![1, 0, 0x41];
+        sleb(OUTPUT_AT, &mut data);
+        data.push(0x0b);
+        uleb(output.len() as u64, &mut data);
+        data.extend(output.as_bytes());
+        section(11, data, &mut wasm);
+    }
+    wasm
+}
+
+#[cfg(feature = "wasm-filters")]
+#[test]
+fn filters_can_only_restrict() {
+    use aitrading::decision::Context;
+    use aitrading::wasm_filter::{FilterError, WasmFilter};
+
+    let proposed = vec![
+        open_long("BTCUSDT", 500.0, 5),
+        Decision::new("ETHUSDT", Action::CloseLong),
+    ];
+    let ctx = Context::default();
+    let apply =
+        |output: &str| WasmFilter::from_bytes(&module(Some(output)))?.apply(&proposed, &ctx);
+
+    // Smaller sizes are kept; larger leverage and dropped fields are not.
+    let allowed = apply(
+        r#"{"decisions":[{"symbol":"BTCUSDT","action":"open_long","leverage":20,"position_size_usd":200}]}"#,
+    )
+    .unwrap();
+    assert_eq!(allowed.len(), 1);
+    assert_eq!(allowed[0].position_size_usd, 200.0);
+    assert_eq!(allowed[0].leverage, 5);
+
+    // Nothing may be added, and a proposed entry may not be returned twice.
+    for output in [
+        r#"{"decisions":[{"symbol":"SOLUSDT","action":"open_long","leverage":1,"position_size_usd":10}]}"#,
+        r#"{"decisions":[{"symbol":"BTCUSDT","action":"open_long"},{"symbol":"BTCUSDT","action":"open_long"}]}"#,
+    ] {
+        assert!(matches!(apply(output), Err(FilterError::InvalidOutput(_))));
+    }
+
+    // A filter that fails lets only exposure-reducing decisions through.
+    let broken = WasmFilter::from_bytes(&module(None)).unwrap();
+    assert!(matches!(
+        broken.apply(&proposed, &ctx),
+        Err(FilterError::Execution(_))
+    ));
+    let kept = broken.apply_or_fail_closed(proposed.clone(), &ctx);
+    assert_eq!(kept.len(), 1);
+    assert_eq!(kept[0].action, Action::CloseLong);
+
+    assert!(WasmFilter::from_bytes(b"not wasm").is_err());
+}
+
+#[cfg(feature = "wasm-filters")]
+#[tokio::test]
+async fn the_runner_applies_the_traders_filter() {
+    let mut h = Harness::new(&["BTCUSDT", "ETHUSDT"], 1000.0).await.unwrap();
+    h.exchange.set_price("BTCUSDT", 100.0);
+    h.exchange.set_price("ETHUSDT", 10.0);
+    let filter = module(Some(
+        r#"{"decisions":[{"symbol":"ETHUSDT","action":"open_long","position_size_usd":50}]}"#,
+    ));
+    h.db.set_decision_filter(&h.user_id, &h.trader.id, &filter)
+        .await
+        .unwrap();
+
+    h.ai.push_decisions(&[
+        open_long("BTCUSDT", 300.0, 3),
+        open_long("ETHUSDT", 300.0, 3),
+    ]);
+    let outcome = h.run_cycle().await.unwrap();
+    assert_eq!(outcome.approved.len(), 1);
+    assert_eq!(outcome.filled.len(), 1);
+    assert_eq!(outcome.filled[0].symbol, "ETHUSDT");
+    assert!((outcome.filled[0].quantity - 5.0).abs() < 1e-9);
+}
+
+#[cfg(not(feature = "wasm-filters"))]
+#[tokio::test]
+async fn filters_that_cannot_run_refuse_entries() {
+    let mut h = Harness::new(&["BTCUSDT"], 1000.0).await.unwrap();
+    h.exchange.set_price("BTCUSDT", 100.0);
+    h.exchange.open("BTCUSDT", "long", 1.0, 1).unwrap();
+    h.db.set_decision_filter(&h.user_id, &h.trader.id, b"\0asm")
+        .await
+        .unwrap();
+
+    h.ai.push_decisions(&[open_long("BTCUSDT", 300.0, 3)]);
+    assert!(h.run_cycle().await.unwrap().approved.is_empty());
+
+    h.ai.push_decisions(&[Decision::new("BTCUSDT", Action::CloseLong)]);
+    let outcome = h.run_cycle().await.unwrap();
+    assert_eq!(outcome.filled.len(), 1);
+    assert_eq!(outcome.filled[0].action, Action::CloseLong);
+}